@@ -12,6 +12,12 @@ pub use error::*;
 /// global empty byte array as place holder
 pub const EMPTY_BYTE_ARRAY: [u8; 0] = [];
 
+/// subtract length decoded from input, which may be malformed
+pub fn checked_sub_len(len: usize, sub: usize) -> Result<usize> {
+    len.checked_sub(sub)
+        .ok_or_else(|| Error::ConstraintError(format!("invalid length {} less than {}", len, sub)))
+}
+
 pub trait ReadFromBytes
 where
     Self: Sized,
@@ -257,7 +263,7 @@ impl ReadBytesExt for Bytes {
         if self.remaining() < 8 {
             return Err(Error::InputIncomplete(
                 Bytes::new(),
-                Needed::Size(4 - self.remaining()),
+                Needed::Size(8 - self.remaining()),
             ));
         }
         Ok(self.get_f64_le())
@@ -457,6 +463,24 @@ mod tests {
     use super::*;
     use crate::error::Result;
 
+    #[test]
+    fn test_checked_sub_len() {
+        assert_eq!(0, checked_sub_len(4, 4).unwrap());
+        assert!(matches!(
+            checked_sub_len(3, 4),
+            Err(Error::ConstraintError(_))
+        ));
+    }
+
+    #[test]
+    fn test_incomplete_f64() {
+        let mut input = Bytes::from_static(&[0u8; 5]);
+        assert!(matches!(
+            input.read_le_f64(),
+            Err(Error::InputIncomplete(_, Needed::Size(3)))
+        ));
+    }
+
     #[test]
     fn test_u8() -> Result<()> {
         // read
@@ -85,6 +85,37 @@ fn scramble411(password: &[u8], seed: &[u8]) -> Result<Vec<u8>> {
     Ok(rst)
 }
 
+/// implementation of mysql_clear_password
+///
+/// client side plugin of PAM and LDAP simple authentication
+#[derive(Debug, Default)]
+pub struct MysqlClearPassword {
+    password: Vec<u8>,
+}
+
+impl MysqlClearPassword {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AuthPlugin for MysqlClearPassword {
+    fn name(&self) -> &'static str {
+        "mysql_clear_password"
+    }
+
+    fn set_credential(&mut self, _username: &str, password: &str) {
+        self.password = Vec::from(password.as_bytes());
+    }
+
+    fn next(&mut self, _input: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        // null-terminated even if password is empty
+        output.extend_from_slice(&self.password);
+        output.push(0);
+        Ok(())
+    }
+}
+
 /// implementation of caching_sha2_password
 #[derive(Debug)]
 pub struct CachingSha2Password {
@@ -122,6 +153,7 @@ impl AuthPlugin for CachingSha2Password {
         }
         match self.stage {
             CachingSha2Stage::FastAuthSendScramble => {
+                self.seed = input.to_vec();
                 let resp = scramble_caching_sha2(&self.password, &self.seed)?;
                 output.extend(resp);
                 self.stage = CachingSha2Stage::FastAuthReadResult;
@@ -1,13 +1,22 @@
 use crate::conn::Conn;
-use crate::error::{Error, Needed, Result};
+use crate::error::{BinlogDumpError, BinlogDumpErrorKind, Error, Needed, Result, ResumeHint};
+use crate::offload::ParseOffload;
+use crate::replication::MIN_GENERATED_SERVER_ID;
+use crate::task::{TaskKind, TaskName};
 use bytes::{Buf, Bytes};
-use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use bytes_parser::{checked_sub_len, ReadBytesExt, ReadFromBytes};
+use futures::channel::mpsc;
+use futures::future::{self, Either};
 use futures::{AsyncRead, AsyncWrite};
+use mybin_core::binlog::table_filter::TableFilter;
 use mybin_core::binlog::*;
 use mybin_core::cmd::*;
 use mybin_core::col::TextColumnValue;
 use mybin_core::packet::{EofPacket, ErrPacket};
+use mybin_core::quit::ComQuit;
 use mybin_core::resultset::{ColumnExtractor, RowMapper};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::adapter::Hyphenated;
 use uuid::Uuid;
@@ -22,10 +31,18 @@ pub struct Binlog<'s, S> {
     binlog_filename: String,
     binlog_pos: u64,
     server_id: u32,
+    check_server_id: bool,
     sids: Vec<SidRange>,
     non_block: bool,
     validate_checksum: bool,
+    validate_position: bool,
     heartbeat_interval: Duration,
+    skip_artificial_rotate: bool,
+    parse_workers: usize,
+    parser_limits: ParserLimits,
+    listeners: RotateListeners,
+    include_tables: Vec<String>,
+    exclude_tables: Vec<String>,
 }
 
 impl<'s, S> Binlog<'s, S> {
@@ -35,10 +52,18 @@ impl<'s, S> Binlog<'s, S> {
             binlog_filename: String::new(),
             binlog_pos: 4,
             server_id: 0,
+            check_server_id: false,
             sids: vec![],
             non_block: false,
             validate_checksum: false,
+            validate_position: false,
             heartbeat_interval: Duration::from_secs(30),
+            skip_artificial_rotate: true,
+            parse_workers: 0,
+            parser_limits: ParserLimits::default(),
+            listeners: RotateListeners::default(),
+            include_tables: vec![],
+            exclude_tables: vec![],
         }
     }
 
@@ -57,6 +82,13 @@ impl<'s, S> Binlog<'s, S> {
         self
     }
 
+    /// check server_id is not used by source or other replicas before
+    /// registering, generated server_id is re-picked on collision
+    pub fn check_server_id(mut self, check_server_id: bool) -> Self {
+        self.check_server_id = check_server_id;
+        self
+    }
+
     pub fn sid(mut self, sid: SidRange) -> Self {
         self.sids.push(sid);
         self
@@ -77,19 +109,96 @@ impl<'s, S> Binlog<'s, S> {
         self
     }
 
+    /// check binlog file and position against SHOW BINARY LOGS before
+    /// requesting stream, false by default
+    ///
+    /// it costs one more round trip and needs REPLICATION CLIENT
+    /// privilege, validation is skipped if access is denied
+    pub fn validate_position(mut self, validate_position: bool) -> Self {
+        self.validate_position = validate_position;
+        self
+    }
+
     pub fn heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
         self.heartbeat_interval = heartbeat_interval;
         self
     }
+
+    /// whether to hide artificial RotateEvents from consumers, true by default
+    ///
+    /// master sends an artificial RotateEvent at start of each binlog file
+    /// to tell the slave current filename, it is not a real rotation
+    pub fn skip_artificial_rotate(mut self, skip_artificial_rotate: bool) -> Self {
+        self.skip_artificial_rotate = skip_artificial_rotate;
+        self
+    }
+
+    /// number of threads to validate checksum, parse events and
+    /// decompress transaction payloads, 0 by default which means
+    /// parsing on the reading task
+    ///
+    /// workers help when the stream is catching up and parsing
+    /// saturates one core. the stream reads ahead at most twice as
+    /// many events as workers, and returns parsed events without
+    /// waiting for next message.
+    pub fn parse_workers(mut self, parse_workers: usize) -> Self {
+        self.parse_workers = parse_workers;
+        self
+    }
+
+    /// notified when stream moves to another binlog file, including
+    /// the first one
+    pub fn rotate_listener(mut self, listener: Arc<dyn RotateListener>) -> Self {
+        self.listeners.add(listener);
+        self
+    }
+
+    /// limits on event size checked before parsing
+    pub fn parser_limits(mut self, parser_limits: ParserLimits) -> Self {
+        self.parser_limits = parser_limits;
+        self
+    }
+
+    /// only stream rows events of tables matching any pattern, e.g.
+    /// `shop.order_*`, see TableFilter
+    ///
+    /// table maps and rows events of other tables are skipped by
+    /// table id without decoding, query events are not filtered
+    pub fn include_tables<I, T>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.include_tables
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// skip rows events of tables matching any pattern, takes
+    /// precedence over include_tables
+    pub fn exclude_tables<I, T>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.exclude_tables
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
 }
 
 impl<'s, S> Binlog<'s, S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    pub async fn request_stream(self) -> Result<BinlogStream<'s, S>> {
+    pub async fn request_stream(mut self) -> Result<BinlogStream<'s, S>> {
         use rand::Rng;
         log::debug!("setup preconditions before request binlog stream");
+        let table_filter = TableFilter::new(&self.include_tables, &self.exclude_tables)?;
+        // 0. validate start position if binlog file specified
+        if self.validate_position && !self.binlog_filename.is_empty() {
+            self.validate_start_position().await?;
+        }
         // 1. fetch server_id as master_id
         let master_id: u32 = self
             .conn
@@ -113,12 +222,13 @@ where
             "set @master_heartbeat_period to {:?}",
             self.heartbeat_interval
         );
-        // 3. fetch binlog_checksum
+        // 3. fetch binlog related variables, including binlog_checksum
         //    the binlog parser needs to know if checksum is enabled
-        let binlog_checksum: String = self
-            .conn
-            .get_var("BINLOG_CHECKSUM", true)
-            .await?
+        let variables = self.conn.binlog_variables().await?;
+        log::debug!("binlog variables={:?}", variables);
+        let binlog_checksum = variables
+            .binlog_checksum
+            .clone()
             .ok_or_else(|| Error::CustomError("missing variable binlog_checksum".to_owned()))?;
         log::debug!("binlog_checksum={}", binlog_checksum);
         // the only available checksum algorithm is CRC32
@@ -128,11 +238,10 @@ where
         self.conn
             .set_user_var("MASTER_BINLOG_CHECKSUM", binlog_checksum)
             .await?;
-        // 5. fetch gtid_mode
-        let gtid_mode: String = self
-            .conn
-            .get_var("GTID_MODE", true)
-            .await?
+        // 5. check gtid_mode
+        let gtid_mode = variables
+            .gtid_mode
+            .clone()
             .ok_or_else(|| Error::CustomError("missing variable gtid_mode".to_owned()))?;
         log::debug!("gtid_mode={}", gtid_mode);
         // 6. fetch server_uuid
@@ -142,6 +251,20 @@ where
             .await?
             .ok_or_else(|| Error::CustomError("missing variable server_uuid".to_owned()))?;
         log::debug!("server_uuid={}", server_uuid);
+        // 6.1 capture gtid sets and verify requested gtids are not purged
+        let source_info = if gtid_mode == "ON" {
+            let source_info = self.source_info(server_uuid).await?;
+            if !self.sids.is_empty() {
+                let requested = GtidSet::from_sid_ranges(&self.sids);
+                let missing = source_info.gtid_purged.subtract(&requested);
+                if !missing.is_empty() {
+                    return Err(Error::StartPositionPurged(missing));
+                }
+            }
+            Some(source_info)
+        } else {
+            None
+        };
         // 7. set @slave_uuid to random uuid
         let slave_uuid = {
             let mut buf = vec![0u8; Hyphenated::LENGTH];
@@ -152,10 +275,17 @@ where
         self.conn.set_user_var("SLAVE_UUID", slave_uuid).await?;
 
         // 8. register slave
-        let slave_id: u32 = if self.server_id != 0 {
+        let slave_id: u32 = if self.check_server_id {
+            if self.server_id != 0 {
+                self.conn.check_server_id(self.server_id).await?;
+                self.server_id
+            } else {
+                self.conn.unique_server_id().await?
+            }
+        } else if self.server_id != 0 {
             self.server_id
         } else {
-            rand::thread_rng().gen()
+            rand::thread_rng().gen_range(MIN_GENERATED_SERVER_ID..=u32::MAX)
         };
         let register = ComRegisterSlave::new(slave_id, master_id);
         self.conn.send_msg(register, true).await?;
@@ -189,20 +319,39 @@ where
         if !msg.has_remaining() {
             return Err(Error::InputIncomplete(Bytes::new(), Needed::Unknown));
         }
-        match msg[0] {
+        let (binlog_filename, binlog_pos) = match msg[0] {
             0xff => {
                 let err = ErrPacket::read_from(&mut msg, &self.conn.cap_flags, true)?;
-                return Err(err.into());
+                return Err(dump_error(self.conn, err).await);
             }
             0xfe => {
                 EofPacket::read_from(&mut msg, &self.conn.cap_flags)?;
                 return Ok(BinlogStream {
                     conn: self.conn,
                     // a pseudo parser which won't be called
-                    pv4: ParserV4::new(vec![], false),
+                    pv4: Arc::new(ParserV4::new(vec![], false)),
                     validate_checksum: self.validate_checksum,
+                    offload: None,
+                    end_received: false,
                     completed: true,
                     non_block: self.non_block,
+                    incident: None,
+                    skip_artificial_rotate: self.skip_artificial_rotate,
+                    binlog_filename: self.binlog_filename,
+                    binlog_pos: self.binlog_pos,
+                    in_trx: false,
+                    begun: false,
+                    gtid: None,
+                    last_position: None,
+                    listeners: self.listeners,
+                    source_info,
+                    variables,
+                    partial_json_seen: false,
+                    table_filter,
+                    skipped_tables: HashSet::new(),
+                    side_events: None,
+                    payload_events: VecDeque::new(),
+                    payload_header: None,
                 });
             }
             0x00 => {
@@ -216,12 +365,17 @@ where
                     ));
                 }
                 if checksum {
-                    let mut crc32 = msg.split_off(msg.remaining() - 4);
+                    let mut crc32 = msg.split_off(checked_sub_len(msg.remaining(), 4)?);
                     let crc32 = crc32.read_le_u32()?;
                     log::debug!("checksum={}", crc32);
                 }
                 let rd = RotateData::read_from(&mut msg)?;
                 log::debug!("rotate={:?}", rd);
+                // the real filename, even if requested filename is empty
+                (
+                    rd.next_binlog_filename.to_string_lossy().into_owned(),
+                    rd.position,
+                )
             }
             _ => {
                 return Err(Error::PacketError(format!(
@@ -229,8 +383,9 @@ where
                     msg[0]
                 )))
             }
-        }
+        };
 
+        self.listeners.on_rotate("", &binlog_filename, binlog_pos);
         // second event is always FDE, and we can construct parser from this event
         let mut msg = self.conn.recv_msg().await?;
         msg.read_u8()?;
@@ -238,15 +393,101 @@ where
         if let Some(crc32) = crc32 {
             log::debug!("checksum={:?}", crc32);
         }
+        self.listeners
+            .on_format_description(&binlog_filename, crc32.is_some());
         log::debug!("pv4={:?}", pv4);
+        let pv4 = Arc::new(pv4.with_limits(self.parser_limits));
+        let offload = if self.parse_workers > 0 {
+            let mut name = TaskName::new(TaskKind::BinlogParser);
+            if let Some(id) = self.conn.connection_id {
+                name = name.connection_id(id);
+            }
+            Some(ParseOffload::new(
+                Arc::clone(&pv4),
+                self.validate_checksum,
+                self.parse_workers,
+                name,
+            )?)
+        } else {
+            None
+        };
         Ok(BinlogStream {
             conn: self.conn,
             pv4,
             validate_checksum: self.validate_checksum,
+            offload,
+            end_received: false,
             completed: false,
             non_block: self.non_block,
+            incident: None,
+            skip_artificial_rotate: self.skip_artificial_rotate,
+            binlog_filename,
+            binlog_pos,
+            in_trx: false,
+            begun: false,
+            gtid: None,
+            last_position: None,
+            listeners: self.listeners,
+            source_info,
+            variables,
+            partial_json_seen: false,
+            table_filter,
+            skipped_tables: HashSet::new(),
+            side_events: None,
+            payload_events: VecDeque::new(),
+            payload_header: None,
+        })
+    }
+
+    async fn source_info(&mut self, server_uuid: String) -> Result<SourceInfo> {
+        let gtid_executed: Option<String> = self.conn.get_var("GTID_EXECUTED", true).await?;
+        let gtid_purged = self.conn.gtid_purged().await?;
+        Ok(SourceInfo {
+            server_uuid,
+            gtid_executed: gtid_executed.unwrap_or_default().parse()?,
+            gtid_purged: gtid_purged.parse()?,
         })
     }
+
+    /// check if requested binlog file exists on master
+    /// and position does not exceed its size
+    async fn validate_start_position(&mut self) -> Result<()> {
+        let files = self.conn.show_binary_logs().await;
+        check_start_position(&self.binlog_filename, self.binlog_pos, files)
+    }
+}
+
+/// check start position against binlog files on master, skipped
+/// if listing files is denied
+fn check_start_position(
+    binlog_filename: &str,
+    binlog_pos: u64,
+    files: Result<Vec<BinlogFile>>,
+) -> Result<()> {
+    let files = match files {
+        Ok(files) => files,
+        Err(e) if e.is_access_denied() => {
+            log::warn!("skip validating start position: {}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    match files.iter().find(|f| f.filename == binlog_filename) {
+        Some(f) if binlog_pos <= f.size => Ok(()),
+        Some(_) => Err(Error::InvalidStartPosition(
+            binlog_filename.to_owned(),
+            binlog_pos,
+            None,
+        )),
+        None => Err(Error::InvalidStartPosition(
+            binlog_filename.to_owned(),
+            binlog_pos,
+            files
+                .into_iter()
+                .next()
+                .map(|f| ResumeHint::EarliestBinlogFile(f.filename)),
+        )),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -268,40 +509,382 @@ impl RowMapper<TextColumnValue> for BinlogFileMapper {
     }
 }
 
+/// result of SHOW MASTER STATUS
+#[derive(Debug, Clone)]
+pub struct MasterStatus {
+    pub file: String,
+    pub position: u64,
+    pub binlog_do_db: String,
+    pub binlog_ignore_db: String,
+    // only available since 5.6
+    pub executed_gtid_set: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct MasterStatusMapper;
+
+impl RowMapper<TextColumnValue> for MasterStatusMapper {
+    type Output = Result<MasterStatus>;
+
+    fn map_row(&self, extr: &ColumnExtractor, row: Vec<TextColumnValue>) -> Self::Output {
+        let file = extr.get_col(&row, 0)?;
+        let position = extr.get_col(&row, 1)?;
+        let binlog_do_db = extr.get_col(&row, 2)?;
+        let binlog_ignore_db = extr.get_col(&row, 3)?;
+        let executed_gtid_set = if row.len() > 4 {
+            extr.get_col(&row, 4)?
+        } else {
+            None
+        };
+        Ok(MasterStatus {
+            file,
+            position,
+            binlog_do_db,
+            binlog_ignore_db,
+            executed_gtid_set,
+        })
+    }
+}
+
+/// how long binlogs are kept before purged automatically
+///
+/// expire_logs_days is deprecated in 8.0 and replaced by
+/// binlog_expire_logs_seconds, either may be missing
+/// depending on server version
+#[derive(Debug, Clone)]
+pub struct BinlogRetention {
+    pub expire_logs_days: Option<u64>,
+    pub binlog_expire_logs_seconds: Option<u64>,
+}
+
+impl BinlogRetention {
+    /// effective retention, None means binlogs never expire
+    pub fn retention(&self) -> Option<Duration> {
+        match (self.binlog_expire_logs_seconds, self.expire_logs_days) {
+            (Some(secs), _) if secs > 0 => Some(Duration::from_secs(secs)),
+            (_, Some(days)) if days > 0 => Some(Duration::from_secs(days * 86400)),
+            _ => None,
+        }
+    }
+}
+
+/// binlog related variables of source, fetched when stream starts
+///
+/// variables missing in server version are None, e.g.
+/// binlog_row_value_options before 8.0.3
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BinlogVariables {
+    pub binlog_format: Option<String>,
+    pub binlog_row_image: Option<String>,
+    pub binlog_row_metadata: Option<String>,
+    pub binlog_row_value_options: Option<String>,
+    pub binlog_checksum: Option<String>,
+    pub gtid_mode: Option<String>,
+}
+
+impl BinlogVariables {
+    /// whether updates of JSON columns are logged as partial updates
+    /// by default, which only applies to row format
+    ///
+    /// sessions may change binlog_row_value_options, so partial
+    /// updates can appear even if it is false
+    pub fn partial_json(&self) -> bool {
+        let row_format = self
+            .binlog_format
+            .as_deref()
+            .is_some_and(|f| f.eq_ignore_ascii_case("ROW"));
+        let partial = self
+            .binlog_row_value_options
+            .as_deref()
+            .is_some_and(|o| o.to_ascii_uppercase().contains("PARTIAL_JSON"));
+        row_format && partial
+    }
+
+    /// whether table maps carry full optional metadata, e.g. column names
+    pub fn full_row_metadata(&self) -> bool {
+        self.binlog_row_metadata
+            .as_deref()
+            .is_some_and(|m| m.eq_ignore_ascii_case("FULL"))
+    }
+}
+
+/// snapshot of source state captured before GTID-based dump
+#[derive(Debug, Clone)]
+pub struct SourceInfo {
+    pub server_uuid: String,
+    pub gtid_executed: GtidSet,
+    pub gtid_purged: GtidSet,
+}
+
+/// notification out of data path of binlog stream
+#[derive(Debug, Clone)]
+pub enum SideEvent {
+    /// incident received, the stream is poisoned afterwards
+    Incident {
+        incident: IncidentType,
+        message: String,
+        position: SourcePosition,
+    },
+    /// ignorable event skipped by stream, type code is the original
+    /// type, e.g. RowsQueryLogEvent
+    Ignorable {
+        type_code: LogEventType,
+        position: SourcePosition,
+    },
+}
+
 #[derive(Debug)]
 pub struct BinlogStream<'s, S> {
     conn: &'s mut Conn<S>,
-    pv4: ParserV4,
+    pv4: Arc<ParserV4>,
     validate_checksum: bool,
+    offload: Option<ParseOffload>,
+    // end of non-blocking stream received, but read-ahead events
+    // are not yet consumed
+    end_received: bool,
     completed: bool,
     non_block: bool,
+    // stream is poisoned once an incident is received
+    incident: Option<(IncidentType, String)>,
+    skip_artificial_rotate: bool,
+    binlog_filename: String,
+    binlog_pos: u64,
+    // last returned event is inside a transaction
+    in_trx: bool,
+    // current transaction is started by BEGIN
+    begun: bool,
+    // gtid of current transaction
+    gtid: Option<(u128, u64)>,
+    last_position: Option<SourcePosition>,
+    listeners: RotateListeners,
+    // None if gtid_mode is not ON
+    source_info: Option<SourceInfo>,
+    variables: BinlogVariables,
+    // PartialUpdateRowsEvent received
+    partial_json_seen: bool,
+    table_filter: TableFilter,
+    // ids of tables filtered out by last table maps
+    skipped_tables: HashSet<u64>,
+    side_events: Option<mpsc::Sender<SideEvent>>,
+    // events of last transaction payload not yet returned,
+    // with header of the payload event
+    payload_events: VecDeque<Event>,
+    payload_header: Option<EventHeader>,
+}
+
+impl<'s, S> BinlogStream<'s, S> {
+    /// name of binlog file being read
+    pub fn binlog_filename(&self) -> &str {
+        &self.binlog_filename
+    }
+
+    /// end position of last received event
+    pub fn binlog_pos(&self) -> u64 {
+        self.binlog_pos
+    }
+
+    /// name of task reading the stream, with current position
+    pub fn task_name(&self) -> TaskName {
+        let mut name = TaskName::new(TaskKind::BinlogReader)
+            .position(self.binlog_filename.as_str(), self.binlog_pos);
+        if let Some(id) = self.conn.connection_id {
+            name = name.connection_id(id);
+        }
+        name
+    }
+
+    /// whether last returned event is inside a transaction
+    pub fn in_trx(&self) -> bool {
+        self.in_trx
+    }
+
+    /// position of last returned event
+    pub fn last_position(&self) -> Option<&SourcePosition> {
+        self.last_position.as_ref()
+    }
+
+    /// gtid sets and uuid of source when dump started
+    pub fn source_info(&self) -> Option<&SourceInfo> {
+        self.source_info.as_ref()
+    }
+
+    /// binlog related variables of source when dump started
+    pub fn binlog_variables(&self) -> &BinlogVariables {
+        &self.variables
+    }
+
+    /// whether source may log partial updates of JSON columns, by its
+    /// variables or by any PartialUpdateRowsEvent received, so consumer
+    /// can decide to handle JSON patches or require full documents
+    pub fn partial_json(&self) -> bool {
+        self.partial_json_seen || self.variables.partial_json()
+    }
+
+    /// receive incidents and skipped ignorable events, replacing
+    /// previous receiver. events are dropped with a warning if the
+    /// channel is full, so the stream is never blocked by it
+    pub fn side_events(&mut self, capacity: usize) -> mpsc::Receiver<SideEvent> {
+        let (tx, rx) = mpsc::channel(capacity);
+        self.side_events = Some(tx);
+        rx
+    }
+
+    fn send_side_event(&mut self, event: SideEvent) {
+        if let Some(tx) = self.side_events.as_mut() {
+            if let Err(e) = tx.try_send(event) {
+                if e.is_disconnected() {
+                    self.side_events = None;
+                } else {
+                    log::warn!("side event dropped: {:?}", e.into_inner());
+                }
+            }
+        }
+    }
 }
 
 impl<'s, S> BinlogStream<'s, S>
 where
-    S: AsyncRead + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
 {
+    /// returns next event of the stream
+    ///
+    /// if IncidentEvent is received, the events after it can not be
+    /// trusted, so the stream is poisoned and always returns error
+    /// until client resyncs from a consistent position
     pub async fn next_event(&mut self) -> Result<Option<Event>> {
+        if let Some((incident, msg)) = self.incident.as_ref() {
+            return Err(Error::BinlogIncident(*incident, msg.clone()));
+        }
         if self.completed {
             return Ok(None);
         }
         loop {
-            match self.recv_and_parse_event().await? {
+            let bse = if let Some(evt) = self.payload_events.pop_front() {
+                self.handle_event(Some(evt), None, true)?
+            } else if self.offload.is_some() {
+                self.recv_and_parse_offloaded().await?
+            } else {
+                self.recv_and_parse_event().await?
+            };
+            match bse {
                 BinlogStreamEvent::Single(evt) => return Ok(Some(evt)),
-                BinlogStreamEvent::UnsupportedEvent => (),
-                BinlogStreamEvent::End => return Ok(None),
+                BinlogStreamEvent::UnsupportedEvent | BinlogStreamEvent::Skipped => (),
+                BinlogStreamEvent::End => {
+                    self.completed = true;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// returns next event with its position
+    pub async fn next_event_with_position(&mut self) -> Result<Option<(Event, SourcePosition)>> {
+        match self.next_event().await? {
+            Some(event) => {
+                let pos = self
+                    .last_position
+                    .clone()
+                    .expect("position of returned event");
+                Ok(Some((event, pos)))
             }
+            None => Ok(None),
         }
     }
 
+    /// stop the stream at transaction boundary
+    ///
+    /// remaining events of the in-flight transaction are passed to
+    /// handler, then COM_QUIT is sent and the connection can not be
+    /// used any more. returns position after the last handled event,
+    /// which should be flushed as checkpoint to resume from.
+    pub async fn shutdown<F>(mut self, mut handler: F) -> Result<(String, u64)>
+    where
+        F: FnMut(Event) -> Result<()>,
+    {
+        while self.in_trx {
+            match self.next_event().await? {
+                Some(event) => handler(event)?,
+                None => break,
+            }
+        }
+        log::debug!(
+            "shutdown binlog stream at {}:{}",
+            self.binlog_filename,
+            self.binlog_pos
+        );
+        self.conn.send_msg(ComQuit::new(), true).await?;
+        self.conn.hooks.on_disconnect(None);
+        Ok((self.binlog_filename, self.binlog_pos))
+    }
+
     async fn recv_and_parse_event(&mut self) -> Result<BinlogStreamEvent> {
+        match self.recv_event_msg().await? {
+            Some(mut msg) => {
+                let parsed = self.pv4.parse_event(&mut msg, self.validate_checksum)?;
+                self.handle_event(parsed, None, false)
+            }
+            None => Ok(BinlogStreamEvent::End),
+        }
+    }
+
+    /// messages are read ahead and parsed by workers,
+    /// events are handled in order of arrival
+    async fn recv_and_parse_offloaded(&mut self) -> Result<BinlogStreamEvent> {
+        loop {
+            let offload = self.offload.as_mut().unwrap();
+            let parsed = if let Some(parsed) = offload.try_next()? {
+                parsed
+            } else if self.end_received || offload.is_full() {
+                match offload.next().await? {
+                    Some(parsed) => parsed,
+                    None => return Ok(BinlogStreamEvent::End),
+                }
+            } else if !offload.is_empty() && self.conn.peeked.is_none() {
+                // parsed event should not wait for next message,
+                // which may not arrive until next heartbeat
+                let head = offload.wait_head();
+                let msg = self.conn.wait_msg();
+                futures::pin_mut!(head, msg);
+                match future::select(head, msg).await {
+                    Either::Left((res, _)) | Either::Right((res, _)) => res?,
+                }
+                continue;
+            } else {
+                match self.recv_event_msg().await? {
+                    Some(msg) => {
+                        // messages after FDE must be parsed by new parser,
+                        // so parser is switched before submitting them
+                        if is_fde(&msg) {
+                            let mut pv4 = ParserV4::from_fde_bytes(&mut msg.clone())?.0;
+                            pv4 = pv4.with_limits(*self.pv4.limits());
+                            self.offload.as_mut().unwrap().set_parser(Arc::new(pv4));
+                        }
+                        self.offload.as_mut().unwrap().submit(msg)?
+                    }
+                    None => self.end_received = true,
+                }
+                continue;
+            };
+            return self.handle_event(parsed.event, parsed.payload, false);
+        }
+    }
+
+    /// receive message of single event with stream header removed,
+    /// returns None if non-blocking stream reaches end
+    async fn recv_event_msg(&mut self) -> Result<Option<Bytes>> {
         let mut msg = self.conn.recv_msg().await?;
         if !msg.has_remaining() {
             return Err(Error::InputIncomplete(Bytes::new(), Needed::Unknown));
         }
+        if msg[0] == 0xff {
+            // dump is terminated by master
+            let err = ErrPacket::read_from(&mut msg, &self.conn.cap_flags, true)?;
+            self.completed = true;
+            return Err(dump_error(self.conn, err).await);
+        }
         let header = msg.read_u8().unwrap();
         if self.non_block && header == 0xfe {
-            return Ok(BinlogStreamEvent::End);
+            return Ok(None);
         }
         if header != 0x00 {
             return Err(Error::PacketError(format!(
@@ -309,24 +892,253 @@ where
                 header
             )));
         }
-        match self.pv4.parse_event(&mut msg, self.validate_checksum)? {
-            Some(evt) => Ok(BinlogStreamEvent::Single(evt)),
+        if !self.partial_json_seen && is_partial_update(&msg) {
+            log::info!("partial JSON update received from source");
+            self.partial_json_seen = true;
+        }
+        Ok(Some(msg))
+    }
+
+    /// payload is the events of transaction payload, if already parsed.
+    /// inner is true for events in transaction payload, which take
+    /// position of the payload event
+    fn handle_event(
+        &mut self,
+        parsed: Option<Event>,
+        payload: Option<Vec<Event>>,
+        inner: bool,
+    ) -> Result<BinlogStreamEvent> {
+        match parsed {
+            Some(Event::IncidentEvent(raw)) => {
+                let position =
+                    SourcePosition::of_event(&self.binlog_filename, &raw.header, self.gtid);
+                let data = raw.into_data()?;
+                let incident = data.incident()?;
+                let msg = data.message().into_owned();
+                log::error!("binlog incident {:?}: {}", incident, msg);
+                self.send_side_event(SideEvent::Incident {
+                    incident,
+                    message: msg.clone(),
+                    position,
+                });
+                self.incident = Some((incident, msg.clone()));
+                Err(Error::BinlogIncident(incident, msg))
+            }
+            Some(Event::RotateEvent(raw)) => {
+                let artificial = raw.header.flags().contains(EventHeaderFlags::ARTIFICIAL);
+                let data = raw.clone().into_data()?;
+                // filename changes when artificial rotate of next file arrives
+                if artificial {
+                    let next = data.next_binlog_filename.to_string_lossy().into_owned();
+                    if next != self.binlog_filename {
+                        self.listeners
+                            .on_rotate(&self.binlog_filename, &next, data.position);
+                    }
+                    self.binlog_filename = next;
+                    self.binlog_pos = data.position;
+                    log::debug!(
+                        "artificial rotate to {}:{}",
+                        self.binlog_filename,
+                        self.binlog_pos
+                    );
+                    if self.skip_artificial_rotate {
+                        return Ok(BinlogStreamEvent::Skipped);
+                    }
+                }
+                self.last_position = Some(SourcePosition::of_event(
+                    &self.binlog_filename,
+                    &raw.header,
+                    None,
+                ));
+                Ok(BinlogStreamEvent::Single(Event::RotateEvent(raw)))
+            }
+            Some(evt) => {
+                // artificial events have no position
+                let end_pos = evt.header().end_position();
+                if end_pos != 0 && !inner {
+                    self.binlog_pos = end_pos as u64;
+                }
+                if let Event::TransactionPayloadEvent(raw) = &evt {
+                    let events = match payload {
+                        Some(events) => events,
+                        None => self.pv4.parse_payload(&raw.clone().into_data()?)?,
+                    };
+                    self.payload_events.extend(events);
+                    self.payload_header = Some(raw.header.clone());
+                    return Ok(BinlogStreamEvent::Skipped);
+                }
+                if self.filter_table(&evt)? {
+                    return Ok(BinlogStreamEvent::Skipped);
+                }
+                if let Event::IgnorableLogEvent(raw) = &evt {
+                    log::debug!("skip ignorable event {:?}", raw.header.type_code);
+                    self.send_side_event(SideEvent::Ignorable {
+                        type_code: raw.header.type_code,
+                        position: SourcePosition::of_event(
+                            &self.binlog_filename,
+                            &raw.header,
+                            self.gtid,
+                        ),
+                    });
+                    return Ok(BinlogStreamEvent::Skipped);
+                }
+                match &evt {
+                    Event::FormatDescriptionEvent(raw) => {
+                        // binlog_checksum may be changed before rotation
+                        let data = raw.clone().into_data()?;
+                        let checksum = data.checksum_flag == 1;
+                        let mut pv4 = ParserV4::clone(&self.pv4);
+                        let changed = pv4.update_fde(data);
+                        self.pv4 = Arc::new(pv4);
+                        self.listeners
+                            .on_format_description(&self.binlog_filename, checksum);
+                        if changed {
+                            self.listeners
+                                .on_checksum_change(&self.binlog_filename, checksum);
+                        }
+                    }
+                    Event::GtidLogEvent(raw) => {
+                        let data = raw.clone().into_data()?;
+                        self.gtid = Some((data.encoded_sid, data.encoded_gno));
+                    }
+                    Event::AnonymousGtidLogEvent(_) => self.gtid = None,
+                    // standalone event
+                    _ if !self.in_trx => self.gtid = None,
+                    _ => (),
+                }
+                let header = match &self.payload_header {
+                    Some(header) if inner => header,
+                    _ => evt.header(),
+                };
+                self.last_position = Some(SourcePosition::of_event(
+                    &self.binlog_filename,
+                    header,
+                    self.gtid,
+                ));
+                match &evt {
+                    Event::GtidLogEvent(_) | Event::AnonymousGtidLogEvent(_) => {
+                        self.in_trx = true;
+                        self.begun = false;
+                    }
+                    Event::XidEvent(_) => {
+                        self.in_trx = false;
+                        self.begun = false;
+                    }
+                    // DML in statement-based replication does not end
+                    // transaction started by BEGIN
+                    Event::QueryEvent(raw) => {
+                        match (self.begun, raw.clone().into_data()?.trx_boundary()?) {
+                            (false, TrxBoundary::Begin) => {
+                                self.in_trx = true;
+                                self.begun = true;
+                            }
+                            (true, TrxBoundary::Commit) | (false, TrxBoundary::ImplicitCommit) => {
+                                self.in_trx = false;
+                                self.begun = false;
+                            }
+                            _ => (),
+                        }
+                    }
+                    _ => (),
+                }
+                Ok(BinlogStreamEvent::Single(evt))
+            }
             None => Ok(BinlogStreamEvent::UnsupportedEvent),
         }
     }
 }
 
+impl<'s, S> BinlogStream<'s, S> {
+    /// whether the table map or rows event is filtered out
+    fn filter_table(&mut self, evt: &Event) -> Result<bool> {
+        if self.table_filter.is_empty() {
+            return Ok(false);
+        }
+        if let Event::TableMapEvent(raw) = evt {
+            let data = raw.clone().into_data()?;
+            let (db, table) = data.names()?;
+            // table id may be reused by another table
+            if self.table_filter.accepts(&db, &table) {
+                self.skipped_tables.remove(&data.table_id);
+                return Ok(false);
+            }
+            self.skipped_tables.insert(data.table_id);
+            return Ok(true);
+        }
+        match evt.rows_table_id()? {
+            Some(table_id) => Ok(self.skipped_tables.contains(&table_id)),
+            None => Ok(false),
+        }
+    }
+}
+
+/// message of FormatDescriptionEvent, stream header removed
+fn is_fde(msg: &Bytes) -> bool {
+    msg.get(4).copied() == Some(u8::from(LogEventType::FormatDescriptionEvent))
+}
+
+fn is_partial_update(msg: &Bytes) -> bool {
+    msg.get(4).copied() == Some(u8::from(LogEventType::PartialUpdateRowsEvent))
+}
+
+/// translate ERR packet received during binlog dump into typed error,
+/// and try to retrieve resume hint from master
+async fn dump_error<S>(conn: &mut Conn<S>, err: ErrPacket) -> Error
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let sql_error = match Error::from(err) {
+        Error::SqlError(e) => e,
+        other => return other,
+    };
+    let kind = match BinlogDumpErrorKind::classify(&sql_error) {
+        Some(kind) => kind,
+        None => return Error::SqlError(sql_error),
+    };
+    // failure of retrieving hint should not hide the original error
+    let resume_hint = match kind {
+        BinlogDumpErrorKind::UnknownBinlogFile => conn
+            .binlog_files()
+            .await
+            .ok()
+            .and_then(|files| files.into_iter().next())
+            .map(|f| ResumeHint::EarliestBinlogFile(f.filename)),
+        BinlogDumpErrorKind::GtidPurged => conn
+            .get_var::<String, _>("GTID_PURGED", true)
+            .await
+            .ok()
+            .flatten()
+            .map(ResumeHint::GtidPurged),
+        BinlogDumpErrorKind::Fatal => None,
+    };
+    log::debug!(
+        "binlog dump error={:?}, resume_hint={:?}",
+        sql_error,
+        resume_hint
+    );
+    Error::BinlogDumpError(BinlogDumpError {
+        kind,
+        sql_error,
+        resume_hint,
+    })
+}
+
 #[derive(Debug, Clone)]
 enum BinlogStreamEvent {
     Single(Event),
     UnsupportedEvent,
+    // artificial event consumed by stream itself
+    Skipped,
     End,
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::{check_start_position, BinlogFile, BinlogVariables};
     use crate::conn::tests::new_conn;
+    use crate::error::{Error, SqlError, ER_SPECIFIC_ACCESS_DENIED_ERROR};
+    use mybin_core::binlog::RotateListener;
+    use std::sync::Arc;
     // use bigdecimal::BigDecimal;
     use uuid::adapter::Hyphenated;
     use uuid::Uuid;
@@ -344,6 +1156,22 @@ mod tests {
         dbg!(server_uuid);
     }
 
+    #[test]
+    fn test_binlog_variables_partial_json() {
+        let mut vars = BinlogVariables {
+            binlog_format: Some("ROW".to_owned()),
+            binlog_row_value_options: Some("PARTIAL_JSON".to_owned()),
+            binlog_row_metadata: Some("MINIMAL".to_owned()),
+            ..Default::default()
+        };
+        assert!(vars.partial_json());
+        assert!(!vars.full_row_metadata());
+        vars.binlog_format = Some("MIXED".to_owned());
+        assert!(!vars.partial_json());
+        // 5.7 does not have the variable
+        assert!(!BinlogVariables::default().partial_json());
+    }
+
     #[smol_potat::test]
     async fn test_setup_binlog_related_variables() {
         let mut conn = new_conn().await;
@@ -382,8 +1210,14 @@ mod tests {
             .request_stream()
             .await
             .unwrap();
+        assert_eq!("mysql-bin.000002", binlog_stream.binlog_filename());
         let mut cnt = 0;
         while let Some(re) = binlog_stream.next_event().await.unwrap() {
+            // artificial rotates are consumed by stream
+            assert!(!re
+                .header()
+                .flags()
+                .contains(mybin_core::binlog::EventHeaderFlags::ARTIFICIAL));
             dbg!(re);
             cnt += 1;
             if cnt == 50 {
@@ -391,4 +1225,93 @@ mod tests {
             }
         }
     }
+
+    #[smol_potat::test]
+    async fn test_binlog_stream_shutdown() {
+        let mut conn = new_conn().await;
+        let mut binlog_stream = conn
+            .binlog()
+            .binlog_filename("mysql-bin.000002")
+            .binlog_pos(4)
+            .non_block(true)
+            .request_stream()
+            .await
+            .unwrap();
+        // stop in the middle of first transaction
+        while let Some(re) = binlog_stream.next_event().await.unwrap() {
+            if binlog_stream.in_trx() {
+                dbg!(re);
+                break;
+            }
+        }
+        let mut handled = vec![];
+        let (filename, pos) = binlog_stream
+            .shutdown(|e| {
+                handled.push(e);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!("mysql-bin.000002", filename);
+        if let Some(last) = handled.last() {
+            assert_eq!(pos, last.header().end_position() as u64);
+        }
+    }
+
+    #[derive(Default)]
+    struct RotateRecorder(std::sync::Mutex<Vec<String>>);
+
+    impl RotateListener for RotateRecorder {
+        fn on_rotate(&self, _from: &str, to: &str, _pos: u64) {
+            self.0.lock().unwrap().push(to.to_owned());
+        }
+    }
+
+    #[smol_potat::test]
+    async fn test_binlog_stream_rotate_listener() {
+        let mut conn = new_conn().await;
+        let rotations = Arc::new(RotateRecorder::default());
+        let mut binlog_stream = conn
+            .binlog()
+            .binlog_filename("mysql-bin.000001")
+            .binlog_pos(4)
+            .non_block(true)
+            .rotate_listener(rotations.clone())
+            .request_stream()
+            .await
+            .unwrap();
+        while binlog_stream.next_event().await.unwrap().is_some() {}
+        let rotations = rotations.0.lock().unwrap();
+        assert_eq!("mysql-bin.000001", rotations[0]);
+        assert!(rotations.len() > 1);
+    }
+
+    #[test]
+    fn test_check_start_position() {
+        let files = || {
+            Ok(vec![BinlogFile {
+                filename: "mysql-bin.000002".to_owned(),
+                size: 1024,
+            }])
+        };
+        assert!(check_start_position("mysql-bin.000002", 4, files()).is_ok());
+        assert!(matches!(
+            check_start_position("mysql-bin.000002", 2048, files()),
+            Err(Error::InvalidStartPosition(_, 2048, None))
+        ));
+        assert!(matches!(
+            check_start_position("mysql-bin.000001", 4, files()),
+            Err(Error::InvalidStartPosition(_, 4, Some(_)))
+        ));
+        // skipped without REPLICATION CLIENT privilege
+        let denied = Err(Error::SqlError(SqlError {
+            error_code: ER_SPECIFIC_ACCESS_DENIED_ERROR,
+            sql_state_marker: b'#',
+            sql_state: "42000".to_owned(),
+            error_message: "Access denied; you need (at least one of) the SUPER, REPLICATION CLIENT privilege(s) for this operation".to_owned(),
+        }));
+        assert!(check_start_position("mysql-bin.000001", 4, denied).is_ok());
+        let other = Err(Error::CustomError("closed".to_owned()));
+        assert!(check_start_position("mysql-bin.000001", 4, other).is_err());
+    }
 }
@@ -0,0 +1,146 @@
+//! reusable buffers of received packets
+//!
+//! each received message is handed out as Bytes, which is a view of a
+//! larger pooled chunk. once all messages of a chunk are dropped, the
+//! chunk can be reclaimed without new allocation, so a stream whose
+//! events are consumed promptly does not allocate in steady state.
+use bytes::BytesMut;
+
+/// options of buffer pool
+///
+/// chunks are grouped by size classes of power of two, from min_size
+/// to max_size. messages larger than max_size are not pooled.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferPoolOpts {
+    pub min_size: usize,
+    pub max_size: usize,
+    /// total capacity of chunks kept by pool
+    pub max_retained_bytes: usize,
+}
+
+impl Default for BufferPoolOpts {
+    fn default() -> Self {
+        BufferPoolOpts {
+            min_size: 4 * 1024,
+            max_size: 1024 * 1024,
+            max_retained_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// buffers served from retained chunks
+    pub hits: u64,
+    /// new chunks allocated and retained
+    pub misses: u64,
+    /// buffers allocated outside the pool, because of too large
+    /// message or retained limit
+    pub unpooled: u64,
+    pub retained_bytes: usize,
+}
+
+#[derive(Debug)]
+pub struct BufferPool {
+    opts: BufferPoolOpts,
+    // chunks of each size class
+    classes: Vec<Vec<BytesMut>>,
+    stats: BufferPoolStats,
+}
+
+impl BufferPool {
+    pub fn new(opts: BufferPoolOpts) -> Self {
+        let min_size = opts.min_size.max(1).next_power_of_two();
+        let max_size = opts.max_size.max(min_size).next_power_of_two();
+        let n_classes = (max_size.trailing_zeros() - min_size.trailing_zeros()) as usize + 1;
+        BufferPool {
+            opts: BufferPoolOpts {
+                min_size,
+                max_size,
+                ..opts
+            },
+            classes: vec![vec![]; n_classes],
+            stats: BufferPoolStats::default(),
+        }
+    }
+
+    /// returns zeroed buffer of given length
+    pub fn acquire(&mut self, len: usize) -> BytesMut {
+        if len == 0 || len > self.opts.max_size {
+            self.stats.unpooled += 1;
+            return zeroed(&mut BytesMut::with_capacity(len), len);
+        }
+        let size = len.max(self.opts.min_size).next_power_of_two();
+        let class = (size.trailing_zeros() - self.opts.min_size.trailing_zeros()) as usize;
+        let chunks = &mut self.classes[class];
+        // spare capacity of chunk, or whole chunk if all buffers
+        // split from it are dropped
+        for chunk in chunks.iter_mut() {
+            if chunk.capacity() >= len || chunk.try_reclaim(len) {
+                self.stats.hits += 1;
+                return zeroed(chunk, len);
+            }
+        }
+        if self.stats.retained_bytes + size > self.opts.max_retained_bytes {
+            self.stats.unpooled += 1;
+            return zeroed(&mut BytesMut::with_capacity(len), len);
+        }
+        self.stats.misses += 1;
+        self.stats.retained_bytes += size;
+        let mut chunk = BytesMut::with_capacity(size);
+        let buf = zeroed(&mut chunk, len);
+        chunks.push(chunk);
+        buf
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        self.stats
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        BufferPool::new(BufferPoolOpts::default())
+    }
+}
+
+impl Clone for BufferPool {
+    /// chunks are not shared, cloned pool starts empty
+    fn clone(&self) -> Self {
+        BufferPool::new(self.opts)
+    }
+}
+
+/// split zeroed buffer of len from front of chunk
+fn zeroed(chunk: &mut BytesMut, len: usize) -> BytesMut {
+    chunk.resize(len, 0);
+    chunk.split_to(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_pool_reuse() {
+        let mut pool = BufferPool::default();
+        for _ in 0..100 {
+            let a = pool.acquire(1000).freeze();
+            let b = pool.acquire(3000).freeze();
+            assert_eq!(1000, a.len());
+            assert_eq!(3000, b.len());
+        }
+        let stats = pool.stats();
+        // both messages fit in one 4k chunk, reclaimed once dropped
+        assert_eq!(1, stats.misses);
+        assert_eq!(199, stats.hits);
+        assert_eq!(4 * 1024, stats.retained_bytes);
+        // held buffers prevent reclamation
+        let held: Vec<_> = (0..4).map(|_| pool.acquire(4000)).collect();
+        assert_eq!(4, held.len());
+        assert_eq!(4, pool.stats().misses);
+        // too large
+        pool.acquire(2 * 1024 * 1024);
+        assert_eq!(1, pool.stats().unpooled);
+    }
+}
@@ -0,0 +1,211 @@
+//! typed configuration of binlog consumers
+//!
+//! the structs only define the layout, parsing of TOML or YAML is
+//! left to applications, e.g. mybinmsg. all sections except
+//! connection are optional.
+use crate::conn::ConnOpts;
+use crate::error::{Error, Result};
+use mybin_core::binlog::checkpoint::PositionStore;
+use mybin_core::binlog::osc::ShadowPolicy;
+use mybin_core::binlog::Projections;
+use serde_derive::*;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub connection: ConnectionConfig,
+    #[serde(default)]
+    pub filters: FilterConfig,
+    #[serde(default)]
+    pub transforms: TransformConfig,
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+    #[serde(default)]
+    pub sink: SinkConfig,
+    #[serde(default)]
+    pub snapshot: Option<SnapshotConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    /// host of TCP connection, ignored if socket is set
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// path of unix socket
+    #[serde(default)]
+    pub socket: Option<PathBuf>,
+    /// server id of replica, a random one is generated if not set
+    #[serde(default)]
+    pub server_id: Option<u32>,
+    #[serde(flatten)]
+    pub opts: ConnOpts,
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_owned()
+}
+
+fn default_port() -> u16 {
+    3306
+}
+
+impl ConnectionConfig {
+    /// address of TCP connection
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// databases and tables of delivered events, as regular expressions
+/// matched against whole names. everything is delivered if not set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub database_filter: Option<String>,
+    #[serde(default)]
+    pub table_filter: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransformConfig {
+    /// columns decoded per table
+    #[serde(default)]
+    pub projections: Projections,
+    /// rows events of online schema change shadow tables
+    #[serde(default)]
+    pub shadow_tables: ShadowPolicy,
+}
+
+/// where the position to resume from is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    /// no checkpoint if not set, the stream starts from position
+    /// of connection or snapshot
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default = "default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+fn default_flush_interval_secs() -> u64 {
+    5
+}
+
+impl CheckpointConfig {
+    pub fn store(&self) -> Option<PositionStore> {
+        self.path.as_ref().map(PositionStore::new)
+    }
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            path: None,
+            flush_interval_secs: default_flush_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkFormat {
+    #[default]
+    Json,
+    Csv,
+    Sql,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Stdout {
+        #[serde(default)]
+        format: SinkFormat,
+    },
+    File {
+        path: PathBuf,
+        #[serde(default)]
+        format: SinkFormat,
+    },
+    Http {
+        url: String,
+    },
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        SinkConfig::Stdout {
+            format: SinkFormat::default(),
+        }
+    }
+}
+
+/// initial snapshot before following binlog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// tables as `db.table`
+    pub tables: Vec<String>,
+    #[serde(default = "default_lock_tables")]
+    pub lock_tables: bool,
+}
+
+fn default_lock_tables() -> bool {
+    true
+}
+
+impl SnapshotConfig {
+    /// (db, table) of configured tables
+    pub fn table_names(&self) -> Result<Vec<(String, String)>> {
+        self.tables
+            .iter()
+            .map(|t| match t.split_once('.') {
+                Some((db, tbl)) if !db.is_empty() && !tbl.is_empty() => {
+                    Ok((db.to_owned(), tbl.to_owned()))
+                }
+                _ => Err(Error::CustomError(format!(
+                    "invalid snapshot table {}, expected db.table",
+                    t
+                ))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_defaults() {
+        let json = r#"{
+            "connection": {"username": "root", "password": "", "database": ""},
+            "snapshot": {"tables": ["db1.t1"]},
+            "sink": {"type": "file", "path": "/tmp/out.csv", "format": "csv"}
+        }"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!("127.0.0.1:3306", config.connection.addr());
+        assert_eq!("root", config.connection.opts.username);
+        assert_eq!(5, config.checkpoint.flush_interval_secs);
+        assert_eq!(ShadowPolicy::Suppress, config.transforms.shadow_tables);
+        assert_eq!(
+            SinkConfig::File {
+                path: PathBuf::from("/tmp/out.csv"),
+                format: SinkFormat::Csv,
+            },
+            config.sink
+        );
+        let snapshot = config.snapshot.unwrap();
+        assert!(snapshot.lock_tables);
+        assert_eq!(
+            vec![("db1".to_owned(), "t1".to_owned())],
+            snapshot.table_names().unwrap()
+        );
+        let invalid = SnapshotConfig {
+            tables: vec!["t1".to_owned()],
+            lock_tables: false,
+        };
+        assert!(invalid.table_names().is_err());
+    }
+}
@@ -1,24 +1,50 @@
-use crate::auth_plugin::{AuthPlugin, CachingSha2Password, MysqlNativePassword};
-use crate::binlog::{Binlog, BinlogFile, BinlogFileMapper};
+use crate::auth_plugin::{
+    AuthPlugin, CachingSha2Password, MysqlClearPassword, MysqlNativePassword,
+};
+use crate::binlog::{
+    Binlog, BinlogFile, BinlogFileMapper, BinlogRetention, BinlogVariables, MasterStatus,
+    MasterStatusMapper,
+};
+use crate::buf_pool::{BufferPool, BufferPoolOpts, BufferPoolStats};
+use crate::dns::DnsOpts;
 use crate::error::{Error, Result};
-use crate::query::Query;
+use crate::hook::{ConnHook, ConnHooks, ConnectInfo, PacketDirection};
+use crate::multi_host::{Endpoint, ReadPolicy};
+use crate::proxy::ProxyProtocol;
+use crate::query::{Query, QueryResult};
+use crate::replication::{
+    pick_server_id, ReplicaStatus, ReplicaStatusMapper, ReplicationChannel,
+    ReplicationChannelMapper, TopologyNode, TopologyNodeMapper, WaitOutcome,
+    REPLICATION_CHANNELS_SQL,
+};
 use crate::resultset::{new_result_set, ResultSet};
+use crate::snapshot::SnapshotAndFollow;
 use crate::stmt::Stmt;
+use crate::task::{TaskKind, TaskName};
+use crate::timing::{Timing, TimingRecorder};
+use crate::trace::ProtocolTracer;
+use crate::trx::{AccessMode, IsolationLevel, PendingRollback, Transaction, TransactionBuilder};
 use bytes::{Buf, Bytes, BytesMut};
 use bytes_parser::{ReadFromBytes, WriteToBytes, WriteToBytesWithContext};
+use chrono::FixedOffset;
 use futures::io::{AsyncReadExt, AsyncWriteExt};
 use futures::{AsyncRead, AsyncWrite};
+use mybin_core::binlog::GtidSet;
 use mybin_core::cmd::*;
 use mybin_core::col::{ColumnDefinition, TextColumnValue};
 use mybin_core::flag::{CapabilityFlags, StatusFlags};
 use mybin_core::handshake::{ConnectAttr, HandshakeClientResponse41, InitialHandshake};
-use mybin_core::packet::{HandshakeMessage, OkPacket};
+use mybin_core::packet::{ErrPacket, HandshakeMessage, OkPacket};
 use mybin_core::quit::ComQuit;
 use mybin_core::resp::ComResponse;
-use mybin_core::resultset::{ColumnExtractor, FromColumnValue, RowMapper};
+use mybin_core::resultset::{ColumnExtractor, FromColumnValue, FromRow, RowMapper};
+use mybin_core::session::SqlMode;
 use mybin_core::stmt::ToColumnValue;
+use mybin_core::version::{Feature, ServerVersion};
 use serde_derive::*;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 /// MySQL connection
 ///
 /// A generic MySQL connection based on AsyncRead and AsyncWrite.
@@ -28,6 +54,28 @@ pub struct Conn<S> {
     pub(crate) cap_flags: CapabilityFlags,
     pub(crate) server_status: StatusFlags,
     pub(crate) pkt_nr: u8,
+    // rollback deferred by dropped transaction
+    pub(crate) pending_rollback: Option<PendingRollback>,
+    // max_allowed_packet of server, queried after handshake
+    pub(crate) max_allowed_packet: Option<u64>,
+    // max size of received message, unlimited if None
+    pub(crate) max_recv_size: Option<u64>,
+    pub(crate) buf_pool: BufferPool,
+    pub(crate) hooks: ConnHooks,
+    pub(crate) tracer: Option<Arc<ProtocolTracer>>,
+    // packets sent in handshake carry auth data
+    pub(crate) authenticating: bool,
+    // mysql_clear_password is allowed without TLS
+    pub(crate) allow_cleartext_password: bool,
+    pub(crate) timing: TimingRecorder,
+    // fail on unexpected sequence id instead of following it
+    pub(crate) strict_seq: bool,
+    // assigned by server in handshake
+    pub(crate) connection_id: Option<u32>,
+    // parsed from initial handshake
+    pub(crate) server_version: Option<ServerVersion>,
+    // first byte of next message, read by wait_msg
+    pub(crate) peeked: Option<u8>,
 }
 
 impl<S> Conn<S> {
@@ -37,6 +85,115 @@ impl<S> Conn<S> {
     pub fn reset_pkt_nr(&mut self) {
         self.pkt_nr = 0;
     }
+
+    /// sequence id of next packet, sent or received
+    pub fn pkt_nr(&self) -> u8 {
+        self.pkt_nr
+    }
+
+    /// fail with PacketOutOfOrder if sequence id of received packet
+    /// is not expected, by default the connection follows sequence id
+    /// of server. use resync() to recover from the error
+    pub fn set_strict_seq(&mut self, strict_seq: bool) {
+        self.strict_seq = strict_seq;
+    }
+
+    /// connection id assigned by server, None before handshake
+    pub fn connection_id(&self) -> Option<u32> {
+        self.connection_id
+    }
+
+    /// server version of initial handshake, None before handshake or
+    /// if not recognized
+    pub fn server_version(&self) -> Option<ServerVersion> {
+        self.server_version
+    }
+
+    /// name of task running queries on the connection
+    pub fn task_name(&self) -> TaskName {
+        let name = TaskName::new(TaskKind::Query);
+        match self.connection_id {
+            Some(id) => name.connection_id(id),
+            None => name,
+        }
+    }
+
+    /// max_allowed_packet of server, None if unknown
+    pub fn max_allowed_packet(&self) -> Option<u64> {
+        self.max_allowed_packet
+    }
+
+    /// override max_allowed_packet, e.g. after the global variable
+    /// is changed
+    pub fn set_max_allowed_packet(&mut self, max_allowed_packet: Option<u64>) {
+        self.max_allowed_packet = max_allowed_packet;
+    }
+
+    /// limit size of received message, e.g. when server is not trusted,
+    /// message exceeding limit fails with PacketTooLarge before its
+    /// payload is read
+    pub fn set_max_recv_size(&mut self, max_recv_size: Option<u64>) {
+        self.max_recv_size = max_recv_size;
+    }
+
+    /// replace buffer pool of received packets, buffers in use
+    /// are not affected
+    pub fn set_buffer_pool(&mut self, opts: BufferPoolOpts) {
+        self.buf_pool = BufferPool::new(opts);
+    }
+
+    /// status flags of last OK or EOF packet
+    pub fn server_status(&self) -> StatusFlags {
+        self.server_status
+    }
+
+    /// whether a transaction is open on server, connection must not
+    /// be returned to pool or routed to another server if true
+    pub fn in_transaction(&self) -> bool {
+        self.server_status.in_transaction()
+    }
+
+    pub fn autocommit(&self) -> bool {
+        self.server_status.autocommit()
+    }
+
+    /// timing of last query or statement execution
+    pub fn last_timing(&self) -> Timing {
+        self.timing.timing()
+    }
+
+    /// statistics of buffer pool, for tuning its options
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.buf_pool.stats()
+    }
+
+    /// register observer of connection events,
+    /// hooks are shared with cloned connections
+    pub fn add_hook(&mut self, hook: Arc<dyn ConnHook>) {
+        self.hooks.add(hook);
+    }
+
+    /// record all packets sent and received, None to disable
+    pub fn set_tracer(&mut self, tracer: Option<Arc<ProtocolTracer>>) {
+        self.tracer = tracer;
+    }
+
+    fn trace_packet(&self, direction: PacketDirection, seq: u8, payload: &[u8]) {
+        if let Some(tracer) = &self.tracer {
+            tracer.trace(direction, seq, payload, self.authenticating);
+        }
+        if !self.hooks.is_empty() {
+            self.hooks.on_packet(direction, seq, payload);
+        }
+    }
+
+    /// notify hooks if connection is broken
+    fn check_broken<T>(&self, res: Result<T>) -> Result<T> {
+        if let Err(e @ Error::IO(_)) = &res {
+            self.hooks.on_disconnect(Some(e));
+        }
+        res
+    }
 }
 
 impl<S> Conn<S>
@@ -46,12 +203,44 @@ where
     /// receive message from MySQL server
     ///
     /// this method will concat mutliple packets if payload too large.
+    /// single-packet message is read into buffer from connection's pool.
     pub async fn recv_msg(&mut self) -> Result<Bytes> {
-        let mut bs = Vec::new();
+        let res = self.recv_msg_inner().await;
+        self.check_broken(res)
+    }
+
+    /// wait until next message arrives, without receiving it
+    ///
+    /// it is cancel safe, so can be raced with other futures. the
+    /// first byte is kept for next recv_msg
+    pub(crate) async fn wait_msg(&mut self) -> Result<()> {
+        if self.peeked.is_some() {
+            return Ok(());
+        }
+        let mut b = 0u8;
+        let res = match self.stream.read(std::slice::from_mut(&mut b)).await {
+            Ok(0) => Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into())),
+            Ok(_) => {
+                self.peeked = Some(b);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+        self.check_broken(res)
+    }
+
+    async fn recv_msg_inner(&mut self) -> Result<Bytes> {
+        let mut bs = BytesMut::new();
         loop {
             // 1. first 3 bytes as message length
             let mut len = [0u8; 3];
-            let _ = self.stream.read_exact(&mut len).await?;
+            match self.peeked.take() {
+                Some(b) => {
+                    len[0] = b;
+                    self.stream.read_exact(&mut len[1..]).await?;
+                }
+                None => self.stream.read_exact(&mut len).await?,
+            }
             let len = (len[0] as u64) + ((len[1] as u64) << 8) + ((len[2] as u64) << 16);
             // 2. then 1 byte packet sequence
             let mut seq = 0u8;
@@ -59,24 +248,39 @@ where
                 .stream
                 .read_exact(std::slice::from_mut(&mut seq))
                 .await?;
-            if seq == 0xff {
-                self.pkt_nr = 0;
-            } else {
-                self.pkt_nr = seq + 1;
+            if seq != self.pkt_nr {
+                if self.strict_seq {
+                    return Err(Error::PacketOutOfOrder {
+                        expected: self.pkt_nr,
+                        got: seq,
+                    });
+                }
+                log::debug!("expect sequence id {} but got {}", self.pkt_nr, seq);
+            }
+            self.pkt_nr = seq.wrapping_add(1);
+            if let Some(allowed) = self.max_recv_size {
+                let needed = bs.len() as u64 + len;
+                if needed > allowed {
+                    return Err(Error::PacketTooLarge { needed, allowed });
+                }
             }
             // 3. payload with <msg_len> bytes
             // if msg_len equals 0xffffff, additional packet follows
-            let start = bs.len();
-            bs.reserve(len as usize);
-            for _ in 0..len {
-                bs.push(0);
+            if bs.is_empty() && len < 0xff_ffff {
+                let mut buf = self.buf_pool.acquire(len as usize);
+                self.stream.read_exact(&mut buf[..]).await?;
+                self.trace_packet(PacketDirection::Received, seq, &buf);
+                return Ok(buf.freeze());
             }
+            let start = bs.len();
+            bs.resize(start + len as usize, 0);
             let _ = self.stream.read_exact(&mut bs[start..]).await?;
+            self.trace_packet(PacketDirection::Received, seq, &bs[start..]);
             if len < 0xff_ffff {
                 break;
             }
         }
-        Ok(Bytes::from(bs))
+        Ok(bs.freeze())
     }
 }
 
@@ -97,7 +301,98 @@ where
         }
         let mut bs = BytesMut::new();
         msg.write_to(&mut bs)?;
-        let mut bs = bs.freeze();
+        // server disconnects on packet exceeding the limit, fail early
+        if let Some(allowed) = self.max_allowed_packet {
+            let needed = bs.len() as u64;
+            if needed > allowed {
+                return Err(Error::PacketTooLarge { needed, allowed });
+            }
+        }
+        let res = self.send_payload(bs.freeze()).await;
+        self.check_broken(res)
+    }
+
+    /// send command followed by chunks as payload
+    ///
+    /// chunks are written into packets as is, without being copied
+    /// into one buffer, unless packets are traced.
+    pub(crate) async fn send_chunks<B: Buf>(&mut self, cmd: u8, chunks: Vec<B>) -> Result<()> {
+        self.reset_pkt_nr();
+        let total = 1 + chunks.iter().map(|c| c.remaining()).sum::<usize>();
+        if let Some(allowed) = self.max_allowed_packet {
+            let needed = total as u64;
+            if needed > allowed {
+                return Err(Error::PacketTooLarge { needed, allowed });
+            }
+        }
+        let res = self.send_chunks_payload(cmd, total, chunks).await;
+        self.check_broken(res)
+    }
+
+    async fn send_chunks_payload<B: Buf>(
+        &mut self,
+        cmd: u8,
+        total: usize,
+        chunks: Vec<B>,
+    ) -> Result<()> {
+        let tracing = self.tracer.is_some() || !self.hooks.is_empty();
+        let mut chunks = chunks.into_iter().filter(|c| c.has_remaining());
+        let mut cur = chunks.next();
+        let mut left = total;
+        loop {
+            let len = left.min(0xff_ffff);
+            let seq = self.pkt_nr;
+            let header = [
+                (len & 0xff) as u8,
+                ((len >> 8) & 0xff) as u8,
+                ((len >> 16) & 0xff) as u8,
+                seq,
+            ];
+            self.stream.write_all(&header).await?;
+            let mut traced = BytesMut::new();
+            let mut n = len;
+            // command byte is the first byte of first packet
+            if left == total {
+                self.stream.write_all(&[cmd]).await?;
+                if tracing {
+                    traced.extend_from_slice(&[cmd]);
+                }
+                n -= 1;
+            }
+            while n > 0 {
+                let c = match cur.as_mut() {
+                    Some(c) => c,
+                    None => {
+                        return Err(Error::CustomError(
+                            "chunks end before payload is sent".to_owned(),
+                        ))
+                    }
+                };
+                let bs = c.chunk();
+                let k = bs.len().min(n);
+                self.stream.write_all(&bs[..k]).await?;
+                if tracing {
+                    traced.extend_from_slice(&bs[..k]);
+                }
+                c.advance(k);
+                n -= k;
+                if !c.has_remaining() {
+                    cur = chunks.next();
+                }
+            }
+            if tracing {
+                self.trace_packet(PacketDirection::Sent, seq, &traced);
+            }
+            self.pkt_nr = self.pkt_nr.wrapping_add(1);
+            left -= len;
+            // payload of exact multiple of 0xffffff ends with empty packet
+            if len < 0xff_ffff {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn send_payload(&mut self, mut bs: Bytes) -> Result<()> {
         while bs.remaining() >= 0xff_ffff {
             let payload = bs.split_to(0xff_ffff);
             self.send_packet(payload).await?;
@@ -108,6 +403,7 @@ where
     }
 
     async fn send_packet(&mut self, payload: Bytes) -> Result<()> {
+        self.trace_packet(PacketDirection::Sent, self.pkt_nr, payload.chunk());
         // 1. 3-byte packet length
         let len = payload.remaining();
         let len = [
@@ -122,7 +418,7 @@ where
         // 3. <len> bytes payload
         let _ = self.stream.write_all(payload.chunk()).await?;
         // increment pkt_nr at end
-        self.pkt_nr += 1;
+        self.pkt_nr = self.pkt_nr.wrapping_add(1);
         Ok(())
     }
 }
@@ -139,6 +435,19 @@ where
             cap_flags: CapabilityFlags::empty(),
             server_status: StatusFlags::empty(),
             pkt_nr: 0,
+            pending_rollback: None,
+            max_allowed_packet: None,
+            max_recv_size: None,
+            buf_pool: BufferPool::default(),
+            hooks: ConnHooks::default(),
+            tracer: None,
+            authenticating: false,
+            allow_cleartext_password: false,
+            timing: TimingRecorder::default(),
+            strict_seq: false,
+            connection_id: None,
+            server_version: None,
+            peeked: None,
         }
     }
 
@@ -149,6 +458,19 @@ where
             cap_flags,
             server_status,
             pkt_nr: 0,
+            pending_rollback: None,
+            max_allowed_packet: None,
+            max_recv_size: None,
+            buf_pool: BufferPool::default(),
+            hooks: ConnHooks::default(),
+            tracer: None,
+            authenticating: false,
+            allow_cleartext_password: false,
+            timing: TimingRecorder::default(),
+            strict_seq: false,
+            connection_id: None,
+            server_version: None,
+            peeked: None,
         }
     }
 
@@ -156,6 +478,13 @@ where
     /// should be called before any other commands
     /// this method will change the connect capability flags
     pub async fn handshake(&mut self, opts: ConnOpts) -> Result<()> {
+        let init_stmts = opts.session_init_stmts()?;
+        let username = opts.username.clone();
+        self.allow_cleartext_password = opts.allow_cleartext_password;
+        self.authenticating = true;
+        if let Some(proxy) = &opts.proxy_protocol {
+            self.send_proxy_header(proxy).await?;
+        }
         let mut msg = self.recv_msg().await?;
         let handshake = InitialHandshake::read_from(&mut msg)?;
         log::debug!(
@@ -164,15 +493,20 @@ where
             String::from_utf8_lossy(handshake.server_version.chunk()),
             handshake.connection_id,
         );
+        self.connection_id = Some(handshake.connection_id);
+        self.server_version = handshake.version();
         log::debug!(
             "auth_plugin={}, auth_data_1={:?}, auth_data_2={:?}",
             handshake.auth_plugin_name,
             handshake.auth_plugin_data_1,
             handshake.auth_plugin_data_2
         );
-        let mut seed = vec![];
-        seed.extend(handshake.auth_plugin_data_1);
-        seed.extend(handshake.auth_plugin_data_2);
+        let server_cap_flags = CapabilityFlags::from_bits_truncate(handshake.capability_flags);
+        if !server_cap_flags.contains(CapabilityFlags::PROTOCOL_41) {
+            return Err(Error::PacketError(
+                "server does not support protocol 41".to_owned(),
+            ));
+        }
 
         self.cap_flags.insert(CapabilityFlags::PLUGIN_AUTH);
         self.cap_flags.insert(CapabilityFlags::LONG_PASSWORD);
@@ -184,15 +518,37 @@ where
         self.cap_flags.insert(CapabilityFlags::DEPRECATE_EOF);
         self.cap_flags
             .insert(CapabilityFlags::PLUGIN_AUTH_LENENC_CLIENT_DATA);
-        CapabilityFlags::default();
         // disable ssl currently
         self.cap_flags.remove(CapabilityFlags::SSL);
+        // optional capabilities are used only if server supports them
+        for flag in &[
+            CapabilityFlags::PLUGIN_AUTH,
+            CapabilityFlags::PLUGIN_AUTH_LENENC_CLIENT_DATA,
+            CapabilityFlags::DEPRECATE_EOF,
+            CapabilityFlags::CONNECT_ATTRS,
+            CapabilityFlags::SECURE_CONNECTION,
+        ] {
+            if !server_cap_flags.contains(*flag) {
+                self.cap_flags.remove(*flag);
+            }
+        }
+        // some proxies advertise the flag on behalf of older servers
+        if let Some(version) = self.server_version {
+            if !version.supports(Feature::DeprecateEof) {
+                self.cap_flags.remove(CapabilityFlags::DEPRECATE_EOF);
+            }
+        }
         // use server suggested plugin to generate auth response
         //       e.g. MySQL 8.0.x suggests caching_sha2_password by default.
-        // currently only two auth plugins are supported
-        let mut auth_plugin = new_auth_plugin(&handshake.auth_plugin_name)?;
-        let auth_response =
-            gen_init_auth_resp(&mut *auth_plugin, &opts.username, &opts.password, &seed)?;
+        // server may switch to another plugin later
+        let auth_plugin_name = handshake.auth_plugin_name_or_default().to_owned();
+        let mut auth_plugin = new_auth_plugin(&auth_plugin_name, self.allow_cleartext_password)?;
+        let auth_response = gen_init_auth_resp(
+            &mut *auth_plugin,
+            &opts.username,
+            &opts.password,
+            handshake.scramble(),
+        )?;
 
         if !opts.database.is_empty() {
             self.cap_flags.insert(CapabilityFlags::CONNECT_WITH_DB);
@@ -200,23 +556,67 @@ where
 
         let client_resp = HandshakeClientResponse41 {
             capability_flags: self.cap_flags.clone(),
+            max_packet_size: opts.max_packet_size,
             username: opts.username,
             auth_response,
             database: opts.database,
-            auth_plugin_name: handshake.auth_plugin_name,
+            auth_plugin_name,
             ..Default::default()
         };
         self.send_msg(client_resp, false).await?;
         let cap_flags = self.cap_flags.clone();
+        let res = self
+            .recv_auth_result(&cap_flags, auth_plugin, &username, &opts.password)
+            .await;
+        self.authenticating = false;
+        res?;
+        match self.get_var::<u64, _>("max_allowed_packet", false).await {
+            Ok(max_allowed_packet) => self.max_allowed_packet = max_allowed_packet,
+            Err(e) => log::warn!("failed to query max_allowed_packet: {}", e),
+        }
+        for stmt in init_stmts {
+            log::debug!("session init: {}", stmt);
+            if let Err(e) = self.exec(stmt.as_str()).await {
+                return Err(Error::SessionInitError(stmt, Box::new(e)));
+            }
+        }
+        if !self.hooks.is_empty() {
+            self.hooks.on_connect(&ConnectInfo {
+                connection_id: handshake.connection_id,
+                server_version: String::from_utf8_lossy(handshake.server_version.chunk())
+                    .into_owned(),
+                username,
+            });
+        }
+        Ok(())
+    }
+
+    /// send PROXY protocol header, must be the first bytes of connection
+    pub async fn send_proxy_header(&mut self, proxy: &ProxyProtocol) -> Result<()> {
+        let header = proxy.header()?;
+        let res = self.stream.write_all(&header).await.map_err(Error::from);
+        self.check_broken(res)
+    }
+
+    /// receive auth result, answering more data requested by plugin
+    ///
+    /// server can switch auth plugin at any step
+    async fn recv_auth_result(
+        &mut self,
+        cap_flags: &CapabilityFlags,
+        mut auth_plugin: Box<dyn AuthPlugin>,
+        username: &str,
+        password: &str,
+    ) -> Result<()> {
         loop {
             let mut msg = self.recv_msg().await?;
-            match HandshakeMessage::read_from(&mut msg, &cap_flags)? {
+            match HandshakeMessage::read_from(&mut msg, cap_flags)? {
                 HandshakeMessage::Ok(ok) => {
                     log::debug!("handshake succeeds");
                     self.server_status = ok.status_flags;
                     // reset packet number for command phase
                     self.reset_pkt_nr();
-                    break;
+                    return Ok(());
                 }
                 HandshakeMessage::Err(err) => {
                     return Err(Error::PacketError(format!(
@@ -231,7 +631,15 @@ where
                         switch.plugin_name,
                         switch.auth_plugin_data
                     );
-                    unimplemented!();
+                    auth_plugin =
+                        new_auth_plugin(&switch.plugin_name, self.allow_cleartext_password)?;
+                    let resp = gen_init_auth_resp(
+                        &mut *auth_plugin,
+                        username,
+                        password,
+                        switch.auth_plugin_data,
+                    )?;
+                    self.send_msg(&resp[..], false).await?;
                 }
                 HandshakeMessage::MoreData(more) => {
                     log::debug!("auth more data={:?}", more);
@@ -243,7 +651,6 @@ where
                 }
             }
         }
-        Ok(())
     }
 
     /// tells the server that client wants to close the connection
@@ -251,6 +658,7 @@ where
         // use mybin_core::packet::OkPacket;
         let cmd = ComQuit::new();
         self.send_msg(cmd, true).await?;
+        self.hooks.on_disconnect(None);
         // let mut msg = self.recv_msg().await?;
         // OkPacket::read_with_ctx(&mut msg, &self.cap_flags)?;
         Ok(())
@@ -349,10 +757,44 @@ where
         let cmd = ComPing::new();
         self.send_msg(cmd, true).await?;
         let mut msg = self.recv_msg().await?;
-        OkPacket::read_from(&mut msg, &self.cap_flags)?;
+        let ok = OkPacket::read_from(&mut msg, &self.cap_flags)?;
+        self.server_status = ok.status_flags;
         Ok(())
     }
 
+    /// drain packets left by an interrupted command, e.g. after
+    /// protocol error or result set not fully read, so next command
+    /// starts at packet boundary
+    ///
+    /// COM_PING is sent and packets are discarded until its response,
+    /// the OK packet with sequence id 1. returns number of discarded
+    /// messages
+    pub async fn resync(&mut self) -> Result<usize> {
+        let cmd = ComPing::new();
+        self.send_msg(cmd, true).await?;
+        let strict_seq = std::mem::replace(&mut self.strict_seq, false);
+        let res = self.drain_until_ping_ok().await;
+        self.strict_seq = strict_seq;
+        res
+    }
+
+    async fn drain_until_ping_ok(&mut self) -> Result<usize> {
+        let mut discarded = 0;
+        loop {
+            let msg = self.recv_msg().await?;
+            // OK packet of ping has no info, at most 7 bytes
+            if self.pkt_nr == 2 && msg.len() <= 7 && msg.first() == Some(&0x00) {
+                let ok = OkPacket::read_from(&mut msg.clone(), &self.cap_flags)?;
+                self.server_status = ok.status_flags;
+                if discarded > 0 {
+                    log::warn!("discarded {} messages to resync connection", discarded);
+                }
+                return Ok(discarded);
+            }
+            discarded += 1;
+        }
+    }
+
     /// change the user of the current connection
     pub async fn change_user(
         &mut self,
@@ -360,6 +802,21 @@ where
         password: &str,
         db_name: &str,
         attrs: Vec<ConnectAttr>,
+    ) -> Result<()> {
+        self.authenticating = true;
+        let res = self
+            .change_user_auth(username, password, db_name, attrs)
+            .await;
+        self.authenticating = false;
+        res
+    }
+
+    async fn change_user_auth(
+        &mut self,
+        username: &str,
+        password: &str,
+        db_name: &str,
+        attrs: Vec<ConnectAttr>,
     ) -> Result<()> {
         // send empty auth data in first request
         // and send read auth data in AuthSwitchResponse
@@ -375,7 +832,10 @@ where
                 ComChangeUserResponse::Ok(_) => break,
                 ComChangeUserResponse::Err(err) => return Err(err.into()),
                 ComChangeUserResponse::Switch(switch) => {
-                    auth_plugin.replace(new_auth_plugin(&switch.plugin_name)?);
+                    auth_plugin.replace(new_auth_plugin(
+                        &switch.plugin_name,
+                        self.allow_cleartext_password,
+                    )?);
                     let resp = gen_init_auth_resp(
                         auth_plugin.as_mut().unwrap().as_mut(),
                         username,
@@ -414,7 +874,10 @@ where
         self.send_msg(cmd, true).await?;
         let mut msg = self.recv_msg().await?;
         match ComResetConnectionResponse::read_from(&mut msg, &self.cap_flags)? {
-            ComResetConnectionResponse::Ok(_) => Ok(()),
+            ComResetConnectionResponse::Ok(ok) => {
+                self.server_status = ok.status_flags;
+                Ok(())
+            }
             ComResetConnectionResponse::Err(err) => Err(err.into()),
         }
     }
@@ -459,9 +922,17 @@ where
 
     /// get a list of binlog files
     pub async fn binlog_files(&mut self) -> Result<Vec<BinlogFile>> {
+        self.show_binary_logs().await
+    }
+
+    /// get a list of binlog files, earliest first
+    ///
+    /// SQL:
+    /// SHOW BINARY LOGS
+    pub async fn show_binary_logs(&mut self) -> Result<Vec<BinlogFile>> {
         let mut rs = self
             .query()
-            .qry("SHOW MASTER LOGS")
+            .qry("SHOW BINARY LOGS")
             .await?
             .map_rows(BinlogFileMapper);
 
@@ -472,6 +943,248 @@ where
         Ok(files)
     }
 
+    /// get current binlog position of master
+    ///
+    /// returns None if binlog is disabled
+    ///
+    /// SQL:
+    /// SHOW MASTER STATUS
+    pub async fn show_master_status(&mut self) -> Result<Option<MasterStatus>> {
+        let status = self
+            .query()
+            .qry("SHOW MASTER STATUS")
+            .await?
+            .map_rows(MasterStatusMapper)
+            .first_or_none()
+            .await?;
+        status.transpose()
+    }
+
+    /// get status of replication, one for each channel
+    ///
+    /// returns empty if server is not a replica
+    ///
+    /// SQL:
+    /// SHOW REPLICA STATUS, or SHOW SLAVE STATUS before 8.0.22
+    pub async fn show_replica_status(&mut self) -> Result<Vec<ReplicaStatus>> {
+        let sql = match self.query().qry("SHOW REPLICA STATUS").await {
+            Err(Error::SqlError(_)) => "SHOW SLAVE STATUS",
+            Err(e) => return Err(e),
+            Ok(rs) => {
+                return rs
+                    .map_rows(ReplicaStatusMapper)
+                    .all()
+                    .await?
+                    .into_iter()
+                    .collect()
+            }
+        };
+        let rs = self.query().qry(sql).await?;
+        rs.map_rows(ReplicaStatusMapper)
+            .all()
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    /// get replicas registered on this server
+    ///
+    /// SQL:
+    /// SHOW REPLICAS, or SHOW SLAVE HOSTS before 8.0.22
+    pub async fn show_replicas(&mut self) -> Result<Vec<TopologyNode>> {
+        let sql = match self.query().qry("SHOW REPLICAS").await {
+            Err(Error::SqlError(_)) => "SHOW SLAVE HOSTS",
+            Err(e) => return Err(e),
+            Ok(rs) => {
+                return rs
+                    .map_rows(TopologyNodeMapper)
+                    .all()
+                    .await?
+                    .into_iter()
+                    .collect()
+            }
+        };
+        let rs = self.query().qry(sql).await?;
+        rs.map_rows(TopologyNodeMapper)
+            .all()
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    /// server_ids of source and its registered replicas
+    pub async fn taken_server_ids(&mut self) -> Result<Vec<u32>> {
+        let mut ids: Vec<u32> = self
+            .show_replicas()
+            .await?
+            .into_iter()
+            .map(|node| node.server_id)
+            .collect();
+        if let Some(source_id) = self.get_var("SERVER_ID", true).await? {
+            ids.push(source_id);
+        }
+        Ok(ids)
+    }
+
+    /// fails if server_id is used by source or another replica,
+    /// source disconnects replicas with the same server_id silently
+    pub async fn check_server_id(&mut self, server_id: u32) -> Result<()> {
+        if self.taken_server_ids().await?.contains(&server_id) {
+            return Err(Error::ServerIdCollision(server_id));
+        }
+        Ok(())
+    }
+
+    /// random high server_id not used by source or any replica
+    pub async fn unique_server_id(&mut self) -> Result<u32> {
+        let taken = self.taken_server_ids().await?;
+        pick_server_id(&mut rand::thread_rng(), &taken, 16)
+            .ok_or_else(|| Error::CustomError("failed to generate unique server_id".to_owned()))
+    }
+
+    /// get state of replication channels from performance_schema
+    pub async fn replication_channels(&mut self) -> Result<Vec<ReplicationChannel>> {
+        self.query()
+            .qry(REPLICATION_CHANNELS_SQL)
+            .await?
+            .map_rows(ReplicationChannelMapper)
+            .all()
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    /// get GTID set that has been purged from binlogs
+    ///
+    /// SQL:
+    /// SHOW GLOBAL VARIABLES LIKE 'GTID_PURGED'
+    pub async fn gtid_purged(&mut self) -> Result<String> {
+        let gtid_purged: Option<String> = self.get_var("GTID_PURGED", true).await?;
+        Ok(gtid_purged.unwrap_or_default())
+    }
+
+    /// get retention settings of binlogs
+    pub async fn binlog_retention(&mut self) -> Result<BinlogRetention> {
+        let expire_logs_days = self.get_var("EXPIRE_LOGS_DAYS", true).await?;
+        let binlog_expire_logs_seconds = self.get_var("BINLOG_EXPIRE_LOGS_SECONDS", true).await?;
+        Ok(BinlogRetention {
+            expire_logs_days,
+            binlog_expire_logs_seconds,
+        })
+    }
+
+    /// get binlog related variables, e.g. binlog_row_value_options
+    pub async fn binlog_variables(&mut self) -> Result<BinlogVariables> {
+        Ok(BinlogVariables {
+            binlog_format: self.get_var("BINLOG_FORMAT", true).await?,
+            binlog_row_image: self.get_var("BINLOG_ROW_IMAGE", true).await?,
+            binlog_row_metadata: self.get_var("BINLOG_ROW_METADATA", true).await?,
+            binlog_row_value_options: self.get_var("BINLOG_ROW_VALUE_OPTIONS", true).await?,
+            binlog_checksum: self.get_var("BINLOG_CHECKSUM", true).await?,
+            gtid_mode: self.get_var("GTID_MODE", true).await?,
+        })
+    }
+
+    /// wait until given GTID set is applied on this server, None
+    /// timeout waits indefinitely
+    ///
+    /// SQL:
+    /// SELECT WAIT_FOR_EXECUTED_GTID_SET('<set>', <timeout>)
+    pub async fn wait_for_gtid(
+        &mut self,
+        gtid_set: &GtidSet,
+        timeout: Option<Duration>,
+    ) -> Result<WaitOutcome> {
+        let qry = match timeout {
+            Some(timeout) => format!(
+                "SELECT WAIT_FOR_EXECUTED_GTID_SET({}, {})",
+                quote_str(&gtid_set.to_string()),
+                wait_timeout_secs(timeout)
+            ),
+            None => format!(
+                "SELECT WAIT_FOR_EXECUTED_GTID_SET({})",
+                quote_str(&gtid_set.to_string())
+            ),
+        };
+        let value: Option<String> = self.query_scalar(qry).await?;
+        WaitOutcome::from_gtid_wait(value.as_deref())
+    }
+
+    /// wait until replica SQL thread applies up to given position of
+    /// source binlog, None timeout waits indefinitely
+    ///
+    /// SQL:
+    /// SELECT MASTER_POS_WAIT('<file>', <pos>, <timeout>)
+    pub async fn wait_for_position(
+        &mut self,
+        file: &str,
+        pos: u64,
+        timeout: Option<Duration>,
+    ) -> Result<WaitOutcome> {
+        let qry = match timeout {
+            Some(timeout) => format!(
+                "SELECT MASTER_POS_WAIT({}, {}, {})",
+                quote_str(file),
+                pos,
+                wait_timeout_secs(timeout)
+            ),
+            None => format!("SELECT MASTER_POS_WAIT({}, {})", quote_str(file), pos),
+        };
+        let value: Option<String> = self.query_scalar(qry).await?;
+        WaitOutcome::from_pos_wait(value.as_deref())
+    }
+
+    /// sql_mode of current session
+    pub async fn sql_mode(&mut self) -> Result<SqlMode> {
+        let sql_mode: Option<String> = self.get_var("SQL_MODE", false).await?;
+        Ok(sql_mode.unwrap_or_default().parse()?)
+    }
+
+    pub async fn set_sql_mode(&mut self, sql_mode: SqlMode) -> Result<()> {
+        self.exec(format!("SET sql_mode = '{}'", sql_mode)).await?;
+        Ok(())
+    }
+
+    /// default isolation level of session, transaction_isolation
+    /// replaces tx_isolation since 5.7.20
+    pub async fn isolation_level(&mut self) -> Result<IsolationLevel> {
+        let mut level: Option<String> = self.get_var("TRANSACTION_ISOLATION", false).await?;
+        if level.is_none() {
+            level = self.get_var("TX_ISOLATION", false).await?;
+        }
+        let level = level.ok_or_else(|| {
+            Error::CustomError("missing variable transaction_isolation".to_owned())
+        })?;
+        Ok(level.parse()?)
+    }
+
+    pub async fn set_isolation_level(&mut self, isolation_level: IsolationLevel) -> Result<()> {
+        self.exec(format!(
+            "SET SESSION TRANSACTION ISOLATION LEVEL {}",
+            isolation_level
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// default access mode of session
+    pub async fn access_mode(&mut self) -> Result<AccessMode> {
+        let mut mode: Option<String> = self.get_var("TRANSACTION_READ_ONLY", false).await?;
+        if mode.is_none() {
+            mode = self.get_var("TX_READ_ONLY", false).await?;
+        }
+        let mode = mode.ok_or_else(|| {
+            Error::CustomError("missing variable transaction_read_only".to_owned())
+        })?;
+        Ok(mode.parse()?)
+    }
+
+    pub async fn set_access_mode(&mut self, access_mode: AccessMode) -> Result<()> {
+        self.exec(format!("SET SESSION TRANSACTION {}", access_mode))
+            .await?;
+        Ok(())
+    }
+
     /// get variable by name
     ///
     /// SQL:
@@ -581,18 +1294,127 @@ where
     pub fn stmt(&mut self) -> Stmt<S> {
         Stmt::new(self)
     }
+
+    /// provide a builder to take consistent snapshot of tables and
+    /// then follow binlog changes
+    pub fn snapshot_and_follow(&mut self) -> SnapshotAndFollow<'_, S> {
+        SnapshotAndFollow::new(self)
+    }
+
+    /// start a transaction with default modifiers
+    pub async fn begin(&mut self) -> Result<Transaction<'_, S>> {
+        self.transaction().begin().await
+    }
+
+    /// provide a builder to start a transaction with isolation level
+    /// and access mode
+    pub fn transaction(&mut self) -> TransactionBuilder<'_, S> {
+        TransactionBuilder::new(self)
+    }
+
+    /// send the rollback deferred by dropped transaction, if any
+    pub(crate) async fn rollback_pending(&mut self) -> Result<()> {
+        if let Some(pending) = self.pending_rollback.take() {
+            let sql = pending.to_sql();
+            log::debug!("send deferred rollback: {}", sql);
+            self.send_msg(ComQuery::new(sql), true).await?;
+            let mut msg = self.recv_msg().await?;
+            if msg.has_remaining() && msg[0] == 0xff {
+                let err = ErrPacket::read_from(&mut msg, &self.cap_flags, true)?;
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// execute a statement that does not return any rows
+    pub async fn exec<Q: Into<String>>(&mut self, qry: Q) -> Result<QueryResult> {
+        self.query().exec(qry).await
+    }
+
+    /// execute query given as chunks without assembling them,
+    /// see Query::exec_streamed
+    pub async fn query_streamed<I, B>(&mut self, chunks: I) -> Result<QueryResult>
+    where
+        I: IntoIterator<Item = B>,
+        B: Buf,
+    {
+        self.query().exec_streamed(chunks).await
+    }
+
+    /// query a single value, e.g. SELECT COUNT(*) FROM t
+    ///
+    /// the query must return exactly one row, and value of
+    /// first column is returned
+    pub async fn query_scalar<T, Q>(&mut self, qry: Q) -> Result<T>
+    where
+        T: FromColumnValue<TextColumnValue>,
+        Q: Into<String>,
+    {
+        let rs = self.query().qry(qry).await?;
+        let extractor = rs.extractor();
+        let row = rs.one().await?;
+        let value = extractor.get_col(&row, 0)?;
+        Ok(value)
+    }
+
+    /// current offset of session time zone to UTC
+    ///
+    /// the offset is computed by server, so named time zones are
+    /// supported, but the offset may change with daylight saving time
+    pub async fn session_time_zone(&mut self) -> Result<FixedOffset> {
+        let secs: i32 = self
+            .query_scalar("SELECT TIMESTAMPDIFF(SECOND, UTC_TIMESTAMP(), NOW())")
+            .await?;
+        // round to minutes to tolerate clock tick between the two calls
+        let secs = (secs as f64 / 60.0).round() as i32 * 60;
+        FixedOffset::east_opt(secs)
+            .ok_or_else(|| Error::CustomError(format!("invalid time zone offset {}", secs)))
+    }
+
+    /// query a single row and convert it to given type
+    ///
+    /// the query must return exactly one row
+    pub async fn query_one<T, Q>(&mut self, qry: Q) -> Result<T>
+    where
+        T: FromRow<TextColumnValue>,
+        Q: Into<String>,
+    {
+        let rs = self.query().qry(qry).await?;
+        let extractor = rs.extractor();
+        let row = rs.one().await?;
+        let value = T::from_row(&extractor, row)?;
+        Ok(value)
+    }
+
+    /// optimizer cost of last compiled query in session, from status
+    /// variable Last_query_cost
+    ///
+    /// the query overwrites timing of last command
+    pub async fn last_query_cost(&mut self) -> Result<f64> {
+        let rs = self
+            .query()
+            .qry("SHOW SESSION STATUS LIKE 'Last_query_cost'")
+            .await?;
+        let extractor = rs.extractor();
+        let row = rs.one().await?;
+        let value: Option<String> = extractor.get_col(&row, 1)?;
+        value
+            .as_deref()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| Error::CustomError(format!("invalid Last_query_cost {:?}", value)))
+    }
 }
 
-fn new_auth_plugin(plugin_name: &str) -> Result<Box<dyn AuthPlugin>> {
+/// TLS is not supported, so password in clear text must be allowed
+/// explicitly
+fn new_auth_plugin(plugin_name: &str, allow_cleartext: bool) -> Result<Box<dyn AuthPlugin>> {
     let auth_plugin: Box<dyn AuthPlugin> = match plugin_name {
         "mysql_native_password" => Box::new(MysqlNativePassword::new()),
         "caching_sha2_password" => Box::new(CachingSha2Password::with_ssl(false)),
-        _ => {
-            return Err(Error::CustomError(format!(
-                "auth plugin {} not supported",
-                plugin_name
-            )))
-        }
+        "mysql_clear_password" if allow_cleartext => Box::new(MysqlClearPassword::new()),
+        "mysql_clear_password" => return Err(Error::CleartextPasswordDisallowed),
+        _ => return Err(Error::UnsupportedAuthPlugin(plugin_name.to_owned())),
     };
     Ok(auth_plugin)
 }
@@ -624,6 +1446,146 @@ pub struct ConnOpts {
     pub username: String,
     pub password: String,
     pub database: String,
+    /// max size of packet client can receive
+    #[serde(default = "default_max_packet_size")]
+    pub max_packet_size: u32,
+    /// endpoints used by MultiHostConnector
+    #[serde(default)]
+    pub endpoints: Vec<Endpoint>,
+    #[serde(default)]
+    pub read_policy: ReadPolicy,
+    /// resolution and connection of host names, used by HostConnector
+    #[serde(default)]
+    pub dns: DnsOpts,
+    /// session time zone set after handshake, e.g. "+00:00",
+    /// server default is used if not specified.
+    /// named time zone, e.g. "Asia/Shanghai", requires time zone
+    /// tables loaded on server
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    #[serde(default)]
+    pub sql_mode: Option<SqlMode>,
+    /// seconds before server closes idle connection
+    #[serde(default)]
+    pub wait_timeout: Option<u32>,
+    /// default isolation level of session
+    #[serde(default)]
+    pub isolation_level: Option<IsolationLevel>,
+    /// default access mode of session
+    #[serde(default)]
+    pub access_mode: Option<AccessMode>,
+    /// other session variables, e.g. ("net_read_timeout", "60"),
+    /// numeric values are kept as is and others are quoted as string
+    #[serde(default)]
+    pub session_vars: Vec<(String, String)>,
+    /// statements executed after session variables are set
+    #[serde(default)]
+    pub init_stmts: Vec<String>,
+    /// PROXY protocol header sent before handshake, required by
+    /// some load balancers in front of MySQL
+    #[serde(default)]
+    pub proxy_protocol: Option<ProxyProtocol>,
+    /// allow mysql_clear_password, used by PAM and LDAP accounts.
+    /// password is sent in clear text as TLS is not supported
+    #[serde(default)]
+    pub allow_cleartext_password: bool,
+}
+
+impl ConnOpts {
+    /// statements executed in order after handshake, fails if name
+    /// of session variable is not an identifier
+    pub fn session_init_stmts(&self) -> Result<Vec<String>> {
+        let mut stmts = vec![];
+        if let Some(time_zone) = &self.time_zone {
+            stmts.push(format!("SET time_zone = {}", quote_str(time_zone)));
+        }
+        if let Some(sql_mode) = self.sql_mode {
+            stmts.push(format!("SET sql_mode = '{}'", sql_mode));
+        }
+        if let Some(wait_timeout) = self.wait_timeout {
+            stmts.push(format!("SET wait_timeout = {}", wait_timeout));
+        }
+        if let Some(isolation_level) = self.isolation_level {
+            stmts.push(format!(
+                "SET SESSION TRANSACTION ISOLATION LEVEL {}",
+                isolation_level
+            ));
+        }
+        if let Some(access_mode) = self.access_mode {
+            stmts.push(format!("SET SESSION TRANSACTION {}", access_mode));
+        }
+        for (name, value) in &self.session_vars {
+            stmts.push(set_session_var_stmt(name, value)?);
+        }
+        stmts.extend(self.init_stmts.iter().cloned());
+        Ok(stmts)
+    }
+}
+
+/// zero timeout means no timeout for both wait functions, so the
+/// smallest timeout is 1 millisecond
+fn wait_timeout_secs(timeout: Duration) -> f64 {
+    timeout.max(Duration::from_millis(1)).as_millis() as f64 / 1000.0
+}
+
+/// statement to set session variable
+pub(crate) fn set_session_var_stmt(name: &str, value: &str) -> Result<String> {
+    let is_ident = !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$');
+    if !is_ident {
+        return Err(Error::InvalidVariableName(name.to_owned()));
+    }
+    let is_numeric = value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || b == b'.' || b == b'-')
+        && value.parse::<f64>().is_ok();
+    let value = if is_numeric {
+        // numeric variables reject strings
+        value.to_owned()
+    } else {
+        quote_str(value)
+    };
+    Ok(format!("SET @@SESSION.{} = {}", name, value))
+}
+
+/// string literal independent of NO_BACKSLASH_ESCAPES, quote is
+/// doubled and string with backslash is written as hex literal
+fn quote_str(s: &str) -> String {
+    if s.contains('\\') {
+        let hex: String = s.bytes().map(|b| format!("{:02x}", b)).collect();
+        return format!("x'{}'", hex);
+    }
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+impl Default for ConnOpts {
+    fn default() -> Self {
+        ConnOpts {
+            username: String::new(),
+            password: String::new(),
+            database: String::new(),
+            max_packet_size: default_max_packet_size(),
+            endpoints: vec![],
+            read_policy: ReadPolicy::default(),
+            dns: DnsOpts::default(),
+            time_zone: None,
+            sql_mode: None,
+            wait_timeout: None,
+            isolation_level: None,
+            access_mode: None,
+            session_vars: vec![],
+            init_stmts: vec![],
+            proxy_protocol: None,
+            allow_cleartext_password: false,
+        }
+    }
+}
+
+fn default_max_packet_size() -> u32 {
+    // max length of three-byte word
+    0xff_ffff
 }
 
 #[derive(Debug)]
@@ -648,6 +1610,291 @@ pub(crate) mod tests {
     use super::*;
     use async_net::TcpStream;
 
+    #[test]
+    fn test_session_init_stmts() {
+        let opts = ConnOpts {
+            time_zone: Some("+08:00".to_owned()),
+            sql_mode: Some(SqlMode::STRICT_ALL_TABLES | SqlMode::NO_ZERO_DATE),
+            wait_timeout: Some(600),
+            isolation_level: Some(IsolationLevel::ReadCommitted),
+            access_mode: Some(AccessMode::ReadOnly),
+            session_vars: vec![("net_read_timeout".to_owned(), "60".to_owned())],
+            init_stmts: vec!["SET NAMES utf8mb4".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                "SET time_zone = '+08:00'",
+                "SET sql_mode = 'STRICT_ALL_TABLES,NO_ZERO_DATE'",
+                "SET wait_timeout = 600",
+                "SET SESSION TRANSACTION ISOLATION LEVEL READ COMMITTED",
+                "SET SESSION TRANSACTION READ ONLY",
+                "SET @@SESSION.net_read_timeout = 60",
+                "SET NAMES utf8mb4",
+            ],
+            opts.session_init_stmts().unwrap()
+        );
+        assert!(ConnOpts::default().session_init_stmts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_session_var_stmt() {
+        assert_eq!(
+            "SET @@SESSION.net_read_timeout = 60",
+            set_session_var_stmt("net_read_timeout", "60").unwrap()
+        );
+        assert_eq!(
+            "SET @@SESSION.time_zone = 'it''s'",
+            set_session_var_stmt("time_zone", "it's").unwrap()
+        );
+        assert_eq!(
+            "SET @@SESSION.time_zone = x'615c27'",
+            set_session_var_stmt("time_zone", "a\\'").unwrap()
+        );
+        assert_eq!(
+            "SET @@SESSION.max_execution_time = 'inf'",
+            set_session_var_stmt("max_execution_time", "inf").unwrap()
+        );
+        assert_eq!(
+            "SET @@SESSION.sql_log_bin = 'OFF'",
+            set_session_var_stmt("sql_log_bin", "OFF").unwrap()
+        );
+        for name in &["", "a b", "x = 1; DROP TABLE t; SET @y", "a.b"] {
+            assert!(matches!(
+                set_session_var_stmt(name, "1"),
+                Err(Error::InvalidVariableName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_max_recv_size() {
+        // header declares payload of 0xfffffe bytes
+        let input = futures::io::Cursor::new(vec![0xfe, 0xff, 0xff, 0]);
+        let mut conn = Conn::new(input);
+        conn.set_max_recv_size(Some(1024));
+        match futures::executor::block_on(conn.recv_msg()) {
+            Err(Error::PacketTooLarge { needed, allowed }) => {
+                assert_eq!((0xff_fffe, 1024), (needed, allowed))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    /// replays server messages and records client messages
+    struct MockStream {
+        input: futures::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl AsyncRead for MockStream {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::pin::Pin::new(&mut self.input).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for MockStream {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.output.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    fn packets(msgs: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut bs = vec![];
+        for (seq, payload) in msgs {
+            bs.extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+            bs.push(*seq);
+            bs.extend_from_slice(payload);
+        }
+        bs
+    }
+
+    /// initial handshake suggesting caching_sha2_password
+    fn initial_handshake() -> Vec<u8> {
+        let cap_flags = CapabilityFlags::LONG_PASSWORD
+            | CapabilityFlags::PROTOCOL_41
+            | CapabilityFlags::SECURE_CONNECTION
+            | CapabilityFlags::TRANSACTIONS
+            | CapabilityFlags::PLUGIN_AUTH;
+        let mut init = b"\x0a8.0.30\x00\x01\x00\x00\x00abcdefgh\x00".to_vec();
+        init.extend_from_slice(&(cap_flags.bits() as u16).to_le_bytes());
+        init.extend_from_slice(&[33, 2, 0]);
+        init.extend_from_slice(&((cap_flags.bits() >> 16) as u16).to_le_bytes());
+        init.push(21);
+        init.extend_from_slice(&[0; 10]);
+        init.extend_from_slice(b"ijklmnopqrst\x00caching_sha2_password\x00");
+        init
+    }
+
+    fn mock_conn(input: Vec<u8>) -> Conn<MockStream> {
+        Conn::new(MockStream {
+            input: futures::io::Cursor::new(input),
+            output: vec![],
+        })
+    }
+
+    #[test]
+    fn test_wait_msg() {
+        let mut conn = mock_conn(packets(&[(0, b"abc"), (1, b"de")]));
+        futures::executor::block_on(conn.wait_msg()).unwrap();
+        // waiting again does not consume more bytes
+        futures::executor::block_on(conn.wait_msg()).unwrap();
+        let msg = futures::executor::block_on(conn.recv_msg()).unwrap();
+        assert_eq!(&b"abc"[..], msg.chunk());
+        let msg = futures::executor::block_on(conn.recv_msg()).unwrap();
+        assert_eq!(&b"de"[..], msg.chunk());
+        assert!(futures::executor::block_on(conn.wait_msg()).is_err());
+    }
+
+    #[test]
+    fn test_packet_seq_resync() {
+        let ok = b"\x00\x00\x00\x02\x00\x00\x00";
+        let mut conn = mock_conn(packets(&[(3, &ok[..])]));
+        conn.set_strict_seq(true);
+        match futures::executor::block_on(conn.recv_msg()) {
+            Err(Error::PacketOutOfOrder { expected, got }) => assert_eq!((0, 3), (expected, got)),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // rows of previous result set left before response of ping
+        let mut conn = mock_conn(packets(&[
+            (5, b"\x01a"),
+            (6, b"\x01b"),
+            (7, b"\xfe\x00\x00\x02\x00"),
+            (1, &ok[..]),
+        ]));
+        conn.set_strict_seq(true);
+        assert_eq!(3, futures::executor::block_on(conn.resync()).unwrap());
+        assert_eq!(2, conn.pkt_nr());
+        assert!(conn.strict_seq);
+        // COM_PING sent with sequence id 0
+        assert_eq!(vec![1, 0, 0, 0, 0x0e], conn.stream.output);
+    }
+
+    #[test]
+    fn test_query_streamed() {
+        let ok = b"\x00\x01\x00\x02\x00\x00\x00";
+        let mut conn = mock_conn(packets(&[(1, &ok[..])]));
+        let res = futures::executor::block_on(conn.query_streamed(vec![
+            &b"insert into t values "[..],
+            b"",
+            b"(1)",
+        ]))
+        .unwrap();
+        assert_eq!(1, res.affected_rows);
+        assert_eq!(
+            packets(&[(0, b"\x03insert into t values (1)")]),
+            conn.stream.output
+        );
+
+        // split at max packet length, exact multiple ends with empty packet
+        let big = Bytes::from(vec![b'a'; 0xff_fffe]);
+        for (tail, seq) in [(&b"(1)"[..], 2), (b"", 2)] {
+            let mut conn = mock_conn(packets(&[(seq, &ok[..])]));
+            futures::executor::block_on(
+                conn.query_streamed(vec![big.clone(), Bytes::from_static(tail)]),
+            )
+            .unwrap();
+            let out = &conn.stream.output;
+            assert_eq!(&[0xff, 0xff, 0xff, 0, 0x03], &out[..5]);
+            assert_eq!(4 + 0xff_ffff + 4 + tail.len(), out.len());
+            let second = &out[4 + 0xff_ffff..];
+            assert_eq!(&[tail.len() as u8, 0, 0, 1], &second[..4]);
+            assert_eq!(tail, &second[4..]);
+        }
+    }
+
+    #[test]
+    fn test_handshake_auth_switch() {
+        let init = initial_handshake();
+        let switch = b"\xfemysql_native_password\x0001234567890123456789\x00";
+        let ok = b"\x00\x00\x00\x02\x00\x00\x00";
+        let mut conn = mock_conn(packets(&[(0, &init), (2, &switch[..]), (4, &ok[..])]));
+        let opts = ConnOpts {
+            username: "u".to_owned(),
+            password: "pw".to_owned(),
+            ..Default::default()
+        };
+        futures::executor::block_on(conn.handshake(opts)).unwrap();
+        // connect attributes are not supported by server
+        assert!(!conn.cap_flags.contains(CapabilityFlags::CONNECT_ATTRS));
+        assert!(!conn
+            .cap_flags
+            .contains(CapabilityFlags::PLUGIN_AUTH_LENENC_CLIENT_DATA));
+        // second client packet answers the switch request
+        let mut output = Bytes::from(std::mem::take(&mut conn.stream.output));
+        let len = output.get_uint_le(3) as usize;
+        output.advance(1 + len);
+        let len = output.get_uint_le(3) as usize;
+        assert_eq!(3, output.get_u8());
+        let mut expected = vec![];
+        let mut plugin = MysqlNativePassword::new();
+        plugin.set_credential("u", "pw");
+        plugin.next(b"01234567890123456789", &mut expected).unwrap();
+        assert_eq!(&expected[..], &output[..len]);
+
+        // truly unsupported plugin
+        let switch = b"\xfeauth_gssapi_client\x00data";
+        let mut conn = mock_conn(packets(&[(0, &init), (2, &switch[..])]));
+        match futures::executor::block_on(conn.handshake(ConnOpts::default())) {
+            Err(Error::UnsupportedAuthPlugin(name)) => assert_eq!("auth_gssapi_client", name),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_handshake_clear_password() {
+        let init = initial_handshake();
+        let switch = b"\xfemysql_clear_password\x00";
+        let ok = b"\x00\x00\x00\x02\x00\x00\x00";
+        let input = packets(&[(0, &init), (2, &switch[..]), (4, &ok[..])]);
+        let opts = ConnOpts {
+            username: "u".to_owned(),
+            password: "pw".to_owned(),
+            ..Default::default()
+        };
+        let mut conn = mock_conn(input.clone());
+        match futures::executor::block_on(conn.handshake(opts.clone())) {
+            Err(Error::CleartextPasswordDisallowed) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let mut conn = mock_conn(input);
+        let opts = ConnOpts {
+            allow_cleartext_password: true,
+            ..opts
+        };
+        futures::executor::block_on(conn.handshake(opts)).unwrap();
+        let output = &conn.stream.output;
+        let first_len = 4 + output[0] as usize;
+        assert_eq!(
+            &b"\x03\x00\x00\x03pw\x00"[..],
+            &output[first_len..first_len + 7]
+        );
+    }
+
     pub(crate) async fn new_conn() -> Conn<async_net::TcpStream> {
         let stream = TcpStream::connect("127.0.0.1:13306").await.unwrap();
         let mut conn = Conn::new(stream);
@@ -655,6 +1902,7 @@ pub(crate) mod tests {
             username: "root".to_owned(),
             password: "password".to_owned(),
             database: "".to_owned(),
+            ..Default::default()
         };
         conn.handshake(opts).await.unwrap();
         conn
@@ -825,6 +2073,50 @@ pub(crate) mod tests {
         assert!(!files.is_empty());
     }
 
+    #[smol_potat::test]
+    async fn test_conn_binlog_position_helpers() {
+        let mut conn = new_conn().await;
+        let status = conn.show_master_status().await.unwrap();
+        dbg!(status);
+        let gtid_purged = conn.gtid_purged().await.unwrap();
+        dbg!(gtid_purged);
+        let retention = conn.binlog_retention().await.unwrap();
+        dbg!(retention.retention());
+    }
+
+    #[smol_potat::test]
+    async fn test_conn_server_status() {
+        let mut conn = new_conn().await;
+        conn.exec("BEGIN").await.unwrap();
+        assert!(conn.in_transaction());
+        conn.exec("ROLLBACK").await.unwrap();
+        assert!(!conn.in_transaction());
+        assert!(conn.autocommit());
+    }
+
+    #[smol_potat::test]
+    async fn test_conn_timing() {
+        let mut conn = new_conn().await;
+        let res = conn.exec("DO SLEEP(0.01)").await.unwrap();
+        assert!(res.timing.server_latency() >= Duration::from_millis(10));
+        assert_eq!(res.timing, conn.last_timing());
+        let rows = conn.query().qry("SELECT 1").await.unwrap().all().await;
+        assert_eq!(1, rows.unwrap().len());
+        assert!(conn.last_timing().complete >= conn.last_timing().first_byte);
+        assert!(conn.last_query_cost().await.unwrap() >= 0.0);
+    }
+
+    #[smol_potat::test]
+    async fn test_conn_wait_for_position() {
+        let mut conn = new_conn().await;
+        // test server is not a replica
+        let outcome = conn
+            .wait_for_position("mysql-bin.000001", 4, Some(Duration::from_millis(100)))
+            .await
+            .unwrap();
+        assert_eq!(WaitOutcome::NotRunning, outcome);
+    }
+
     #[smol_potat::test]
     async fn test_conn_ops_var() {
         let mut conn = new_conn().await;
@@ -839,4 +2131,37 @@ pub(crate) mod tests {
             .await
             .unwrap();
     }
+
+    #[smol_potat::test]
+    async fn test_conn_packet_too_large() {
+        let mut conn = new_conn().await;
+        let allowed = conn.max_allowed_packet().unwrap();
+        let qry = format!("SELECT '{}'", "a".repeat(allowed as usize));
+        match conn.exec(qry).await {
+            Err(Error::PacketTooLarge { needed, .. }) => assert!(needed > allowed),
+            other => panic!("unexpected result {:?}", other),
+        }
+        // connection is still usable
+        conn.ping().await.unwrap();
+    }
+
+    #[smol_potat::test]
+    async fn test_conn_query_scalar_and_one() {
+        let mut conn = new_conn().await;
+        let n: u32 = conn.query_scalar("SELECT 1").await.unwrap();
+        assert_eq!(1, n);
+        let (id, name): (u32, String) = conn.query_one("SELECT 1, 'hello'").await.unwrap();
+        assert_eq!(1, id);
+        assert_eq!("hello", name);
+        let empty = conn
+            .query_scalar::<u32, _>("SELECT 1 FROM DUAL WHERE 1 = 0")
+            .await;
+        assert!(matches!(empty, Err(Error::EmptyResultSet)));
+        let many = conn
+            .query_scalar::<u32, _>("SELECT 1 UNION ALL SELECT 2")
+            .await;
+        assert!(matches!(many, Err(Error::TooManyRows(2))));
+        let res = conn.exec("SET @mybin_test = 1").await.unwrap();
+        assert_eq!(0, res.affected_rows);
+    }
 }
@@ -0,0 +1,374 @@
+//! split binlog stream into per-database sub-streams
+//!
+//! tenants sharded by schema are consumed and checkpointed
+//! independently. transactions are dispatched to bounded channels of
+//! databases they change, a transaction changing several databases
+//! is delivered to each of them. events out of transactions and
+//! transactions of databases not subscribed are dropped.
+//!
+//! when the channel of a database is full, its transactions are held
+//! by the demultiplexer so other databases keep receiving. reading of
+//! the stream is paused only if held transactions exceed the limit.
+//! held transactions are sent in round robin over databases.
+use crate::binlog::BinlogStream;
+use crate::error::Result;
+use crate::merge::{next_source_trx, SourceTrx};
+use futures::channel::mpsc;
+use futures::future::poll_fn;
+use futures::{AsyncRead, AsyncWrite, StreamExt};
+use mybin_core::binlog::checkpoint::Checkpoint;
+use mybin_core::binlog::ddl::classify;
+use mybin_core::binlog::{Event, OrderingKey};
+use smol_str::SmolStr;
+use std::collections::VecDeque;
+use std::task::Poll;
+
+/// demultiplexer of binlog stream by database
+#[derive(Debug)]
+pub struct DbDemux<'s, S> {
+    stream: BinlogStream<'s, S>,
+    dispatcher: Dispatcher,
+}
+
+impl<'s, S> DbDemux<'s, S> {
+    pub fn new(stream: BinlogStream<'s, S>) -> Self {
+        DbDemux {
+            stream,
+            dispatcher: Dispatcher::new(16, 1024),
+        }
+    }
+
+    /// capacity of channel of each database, applied to databases
+    /// subscribed afterwards. 16 by default
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.dispatcher.capacity = capacity;
+        self
+    }
+
+    /// max transactions held when channels are full, 1024 by default
+    pub fn max_held(mut self, max_held: usize) -> Self {
+        self.dispatcher.max_held = max_held.max(1);
+        self
+    }
+
+    /// subscribe transactions of database
+    pub fn subscribe<D: Into<SmolStr>>(&mut self, db: D) -> DbStream {
+        self.dispatcher.subscribe(db.into(), None)
+    }
+
+    /// subscribe transactions of database after its checkpoint, the
+    /// stream should start at the earliest checkpoint of all databases
+    pub fn subscribe_from<D: Into<SmolStr>>(&mut self, db: D, checkpoint: Checkpoint) -> DbStream {
+        self.dispatcher.subscribe(db.into(), Some(checkpoint))
+    }
+}
+
+impl<'s, S> DbDemux<'s, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// dispatch transactions until stream ends or all sub-streams are
+    /// dropped, must be polled for sub-streams to receive anything
+    pub async fn run(&mut self) -> Result<()> {
+        loop {
+            self.dispatcher.flush();
+            if self.dispatcher.is_empty() {
+                log::debug!("all database streams are dropped");
+                return Ok(());
+            }
+            if self.dispatcher.held() >= self.dispatcher.max_held {
+                self.dispatcher.wait_ready().await;
+                continue;
+            }
+            match next_source_trx("", &mut self.stream).await? {
+                Some(trx) => {
+                    let dbs = trx_databases(&trx)?;
+                    self.dispatcher.dispatch(&dbs, trx);
+                }
+                None => break,
+            }
+        }
+        // deliver held transactions before closing channels
+        while self.dispatcher.held() > 0 {
+            self.dispatcher.wait_ready().await;
+            self.dispatcher.flush();
+        }
+        Ok(())
+    }
+}
+
+/// transactions of one database
+#[derive(Debug)]
+pub struct DbStream {
+    db: SmolStr,
+    rx: mpsc::Receiver<SourceTrx>,
+    last: Option<Checkpoint>,
+}
+
+impl DbStream {
+    pub fn db(&self) -> &str {
+        &self.db
+    }
+
+    /// None if the stream of demultiplexer ends
+    pub async fn next_trx(&mut self) -> Option<SourceTrx> {
+        let trx = self.rx.next().await?;
+        self.last = Some(Checkpoint {
+            binlog_filename: trx.position.binlog_filename.clone(),
+            binlog_pos: trx.position.end_pos,
+            executed_gtid_set: None,
+        });
+        Some(trx)
+    }
+
+    /// position after last received transaction, to be stored once
+    /// the transaction is processed
+    pub fn checkpoint(&self) -> Option<&Checkpoint> {
+        self.last.as_ref()
+    }
+}
+
+#[derive(Debug)]
+struct DbSender {
+    db: SmolStr,
+    tx: mpsc::Sender<SourceTrx>,
+    held: VecDeque<SourceTrx>,
+    // transactions ending at or before are already processed
+    skip_until: Option<Checkpoint>,
+}
+
+#[derive(Debug)]
+struct Dispatcher {
+    senders: Vec<DbSender>,
+    // start of next round robin
+    next: usize,
+    capacity: usize,
+    max_held: usize,
+}
+
+impl Dispatcher {
+    fn new(capacity: usize, max_held: usize) -> Self {
+        Dispatcher {
+            senders: vec![],
+            next: 0,
+            capacity,
+            max_held,
+        }
+    }
+
+    fn subscribe(&mut self, db: SmolStr, skip_until: Option<Checkpoint>) -> DbStream {
+        let (tx, rx) = mpsc::channel(self.capacity);
+        self.senders.retain(|s| s.db != db);
+        self.senders.push(DbSender {
+            db: db.clone(),
+            tx,
+            held: VecDeque::new(),
+            skip_until,
+        });
+        DbStream { db, rx, last: None }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    fn held(&self) -> usize {
+        self.senders.iter().map(|s| s.held.len()).sum()
+    }
+
+    fn dispatch(&mut self, dbs: &[SmolStr], trx: SourceTrx) {
+        for s in self.senders.iter_mut().filter(|s| dbs.contains(&s.db)) {
+            if let Some(ckpt) = &s.skip_until {
+                if !is_after(&trx, ckpt) {
+                    continue;
+                }
+                s.skip_until = None;
+            }
+            s.held.push_back(trx.clone());
+        }
+    }
+
+    /// send held transactions in round robin, one per database each
+    /// round, until all channels are full or nothing is held
+    fn flush(&mut self) {
+        let n = self.senders.len();
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..n {
+                let s = &mut self.senders[(self.next + i) % n];
+                if let Some(trx) = s.held.pop_front() {
+                    match s.tx.try_send(trx) {
+                        Ok(_) => progress = true,
+                        Err(e) if e.is_full() => s.held.push_front(e.into_inner()),
+                        // receiver dropped
+                        Err(_) => s.held.clear(),
+                    }
+                }
+            }
+        }
+        if n > 0 {
+            self.next = (self.next + 1) % n;
+        }
+        self.senders.retain(|s| {
+            let closed = s.tx.is_closed();
+            if closed {
+                log::debug!("stream of database {} is dropped", s.db);
+            }
+            !closed
+        });
+    }
+
+    /// wait until any database holding transactions can receive
+    async fn wait_ready(&mut self) {
+        let senders = &mut self.senders;
+        poll_fn(|cx| {
+            let mut blocked = false;
+            for s in senders.iter_mut().filter(|s| !s.held.is_empty()) {
+                match s.tx.poll_ready(cx) {
+                    Poll::Ready(_) => return Poll::Ready(()),
+                    Poll::Pending => blocked = true,
+                }
+            }
+            if blocked {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await
+    }
+}
+
+// binlog files of one server sort by numeric extension, which
+// may outgrow its zero padding, e.g. mysql-bin.999999 is followed
+// by mysql-bin.1000000
+fn is_after(trx: &SourceTrx, ckpt: &Checkpoint) -> bool {
+    let ckpt_key = OrderingKey::from_file_pos(&ckpt.binlog_filename, ckpt.binlog_pos);
+    match (trx.position.ordering_key(), ckpt_key) {
+        (Some(trx_key), Some(ckpt_key)) => trx_key > ckpt_key,
+        // no numeric extension, fall back to names
+        _ => {
+            (trx.position.binlog_filename.as_str(), trx.position.end_pos)
+                > (ckpt.binlog_filename.as_str(), ckpt.binlog_pos)
+        }
+    }
+}
+
+/// databases changed by transaction, by table maps and DDL
+fn trx_databases(trx: &SourceTrx) -> Result<Vec<SmolStr>> {
+    let mut dbs: Vec<SmolStr> = vec![];
+    let mut add = |db: SmolStr| {
+        if !db.is_empty() && !dbs.contains(&db) {
+            dbs.push(db);
+        }
+    };
+    for event in &trx.events {
+        match event {
+            Event::TableMapEvent(raw) => {
+                let tm = raw.clone().into_data()?.table_map()?;
+                add(tm.schema_name);
+            }
+            Event::QueryEvent(raw) => {
+                let data = raw.clone().into_data()?;
+                let query = data.query_text()?;
+                if query.eq_ignore_ascii_case("BEGIN") || query.eq_ignore_ascii_case("COMMIT") {
+                    continue;
+                }
+                let default_db = String::from_utf8_lossy(&data.schema);
+                let c = classify(&query);
+                if c.kind.is_database() {
+                    c.objects.into_iter().for_each(|obj| add(obj.name));
+                    continue;
+                }
+                let c = c.with_default_db(&default_db);
+                if c.objects.is_empty() {
+                    add(SmolStr::new(&default_db));
+                }
+                c.objects
+                    .into_iter()
+                    .filter_map(|obj| obj.db)
+                    .for_each(&mut add);
+            }
+            _ => (),
+        }
+    }
+    Ok(dbs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mybin_core::binlog::SourcePosition;
+
+    fn trx(pos: u64) -> SourceTrx {
+        trx_in("mysql-bin.000001", pos)
+    }
+
+    fn trx_in(binlog_filename: &str, pos: u64) -> SourceTrx {
+        SourceTrx {
+            source: String::new(),
+            gtid: None,
+            commit_ts: 0,
+            position: SourcePosition {
+                binlog_filename: binlog_filename.to_owned(),
+                start_pos: pos - 10,
+                end_pos: pos,
+                gtid: None,
+                server_id: 1,
+                timestamp: 0,
+            },
+            events: vec![],
+        }
+    }
+
+    #[test]
+    fn test_dispatcher() {
+        let mut d = Dispatcher::new(0, 8);
+        let mut noisy = d.subscribe("noisy".into(), None);
+        let mut quiet = d.subscribe(
+            "quiet".into(),
+            Some(Checkpoint {
+                binlog_filename: "mysql-bin.000001".to_owned(),
+                binlog_pos: 100,
+                executed_gtid_set: None,
+            }),
+        );
+        let noisy_db = [SmolStr::new("noisy")];
+        for pos in 1..=5 {
+            d.dispatch(&noisy_db, trx(pos * 100));
+        }
+        // transaction of both databases, quiet skips the first one
+        d.dispatch(&[SmolStr::new("noisy"), SmolStr::new("quiet")], trx(100));
+        d.dispatch(&[SmolStr::new("quiet"), SmolStr::new("other")], trx(600));
+        d.flush();
+        // capacity 0 leaves one slot per sender
+        assert_eq!(5, d.held());
+        smol::block_on(async {
+            // quiet is not blocked by transactions held for noisy
+            assert_eq!(600, quiet.next_trx().await.unwrap().position.end_pos);
+            assert_eq!(600, quiet.checkpoint().unwrap().binlog_pos);
+            assert_eq!(100, noisy.next_trx().await.unwrap().position.end_pos);
+        });
+        d.flush();
+        assert_eq!(4, d.held());
+        drop(noisy);
+        d.flush();
+        assert_eq!(0, d.held());
+        assert_eq!(1, d.senders.len());
+    }
+
+    #[test]
+    fn test_is_after() {
+        let ckpt = Checkpoint {
+            binlog_filename: "mysql-bin.999999".to_owned(),
+            binlog_pos: 500,
+            executed_gtid_set: None,
+        };
+        assert!(!is_after(&trx_in("mysql-bin.999999", 500), &ckpt));
+        assert!(is_after(&trx_in("mysql-bin.999999", 600), &ckpt));
+        assert!(!is_after(&trx_in("mysql-bin.999998", 600), &ckpt));
+        // index outgrows zero padding
+        assert!(is_after(&trx_in("mysql-bin.1000000", 100), &ckpt));
+    }
+}
@@ -0,0 +1,422 @@
+//! host name resolution and connection over resolved addresses
+//!
+//! names are resolved by a pluggable Resolver, e.g. one backed by
+//! trust-dns. the default resolver calls system resolver on its own
+//! thread, so a slow resolver never blocks the executor, and the
+//! lookup is abandoned once resolve timeout elapses.
+//!
+//! resolved addresses are tried in happy eyeballs style (RFC 8305):
+//! address families are interleaved, and next address is tried if
+//! current attempt does not complete within attempt delay, while
+//! earlier attempts are kept running. the first connected stream
+//! wins. multiple addresses of the same family are rotated across
+//! connects to spread load.
+//!
+//! the connector does not depend on any runtime, streams are created
+//! by user provided function. delays are timed by async-io timer if
+//! feature timer is enabled, which is the default, otherwise on
+//! helper threads.
+use futures::channel::oneshot;
+use futures::future::{self, BoxFuture, Either};
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde_derive::*;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpPreference {
+    /// interleave families, starting with IPv6
+    #[default]
+    Ipv6First,
+    /// interleave families, starting with IPv4
+    Ipv4First,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsOpts {
+    /// None waits for resolver indefinitely
+    #[serde(default = "default_resolve_timeout")]
+    pub resolve_timeout: Option<Duration>,
+    /// delay before trying next address while current attempt
+    /// is in progress
+    #[serde(default = "default_attempt_delay")]
+    pub attempt_delay: Duration,
+    #[serde(default)]
+    pub ip_preference: IpPreference,
+    /// rotate addresses of the same family on each connect
+    #[serde(default)]
+    pub rotate: bool,
+    /// limit of whole connect including resolution and all attempts,
+    /// None waits until the last attempt fails
+    #[serde(default)]
+    pub connect_timeout: Option<Duration>,
+}
+
+fn default_resolve_timeout() -> Option<Duration> {
+    Some(Duration::from_secs(5))
+}
+
+fn default_attempt_delay() -> Duration {
+    // recommended by RFC 8305
+    Duration::from_millis(250)
+}
+
+impl Default for DnsOpts {
+    fn default() -> Self {
+        DnsOpts {
+            resolve_timeout: default_resolve_timeout(),
+            attempt_delay: default_attempt_delay(),
+            ip_preference: IpPreference::default(),
+            rotate: false,
+            connect_timeout: None,
+        }
+    }
+}
+
+pub trait Resolver: Send + Sync {
+    /// socket addresses of host, with given port
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>>;
+}
+
+/// system resolver called on a new thread for each lookup
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadResolver;
+
+impl Resolver for ThreadResolver {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        let host = host.to_owned();
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || {
+            let res = (host.as_str(), port)
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect());
+            let _ = tx.send(res);
+        });
+        Box::pin(async move {
+            rx.await
+                .unwrap_or_else(|_| Err(io::Error::other("resolver thread exited")))
+        })
+    }
+}
+
+/// fixed addresses of hosts, e.g. to pin names in tests
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn host<T: Into<String>>(mut self, host: T, addrs: Vec<IpAddr>) -> Self {
+        self.hosts.insert(host.into(), addrs);
+        self
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        let res = match self.hosts.get(host) {
+            Some(addrs) => Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("host {} not found", host),
+            )),
+        };
+        Box::pin(future::ready(res))
+    }
+}
+
+/// connects host by name, trying its addresses
+pub struct HostConnector<F> {
+    opts: DnsOpts,
+    resolver: Arc<dyn Resolver>,
+    connect: F,
+    next: AtomicUsize,
+}
+
+impl<F> HostConnector<F> {
+    /// use ThreadResolver by default
+    pub fn new(opts: DnsOpts, connect: F) -> Self {
+        HostConnector {
+            opts,
+            resolver: Arc::new(ThreadResolver),
+            connect,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// addresses of host in order of attempts
+    pub async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let addrs = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => {
+                let lookup = self.resolver.resolve(host, port);
+                match self.opts.resolve_timeout {
+                    Some(timeout) => match future::select(lookup, delay(timeout)).await {
+                        Either::Left((res, _)) => res?,
+                        Either::Right(_) => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                format!("resolve {} timed out after {:?}", host, timeout),
+                            ))
+                        }
+                    },
+                    None => lookup.await?,
+                }
+            }
+        };
+        let offset = if self.opts.rotate {
+            self.next.fetch_add(1, Ordering::Relaxed)
+        } else {
+            0
+        };
+        let addrs = order_addrs(addrs, self.opts.ip_preference, offset);
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no address of {} matches {:?}",
+                    host, self.opts.ip_preference
+                ),
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+impl<F, Fut, S> HostConnector<F>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+{
+    /// connect to host, returns error of last attempt if all fail
+    pub async fn connect(&self, host: &str, port: u16) -> io::Result<S> {
+        let timeout = match self.opts.connect_timeout {
+            Some(timeout) => timeout,
+            None => return self.connect_addrs(host, port).await,
+        };
+        let connect = Box::pin(self.connect_addrs(host, port));
+        match future::select(connect, delay(timeout)).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("connect {} timed out after {:?}", host, timeout),
+            )),
+        }
+    }
+
+    async fn connect_addrs(&self, host: &str, port: u16) -> io::Result<S> {
+        let mut addrs: VecDeque<_> = self.resolve(host, port).await?.into();
+        let mut attempts = FuturesUnordered::new();
+        let mut last_err = None;
+        loop {
+            if attempts.is_empty() {
+                match addrs.pop_front() {
+                    Some(addr) => attempts.push(self.attempt(addr)),
+                    None => break,
+                }
+            }
+            let done = if addrs.is_empty() {
+                attempts.next().await
+            } else {
+                match future::select(attempts.next(), delay(self.opts.attempt_delay)).await {
+                    Either::Left((done, _)) => done,
+                    Either::Right(_) => None,
+                }
+            };
+            match done {
+                Some((_, Ok(stream))) => return Ok(stream),
+                Some((addr, Err(e))) => {
+                    log::warn!("failed to connect {}: {}", addr, e);
+                    last_err = Some(e);
+                    // next address is tried immediately
+                    if let Some(addr) = addrs.pop_front() {
+                        attempts.push(self.attempt(addr));
+                    }
+                }
+                None => {
+                    let addr = addrs.pop_front().unwrap();
+                    log::debug!("attempt delay elapsed, try {}", addr);
+                    attempts.push(self.attempt(addr));
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| io::Error::other("no address to connect")))
+    }
+
+    fn attempt(&self, addr: SocketAddr) -> impl Future<Output = (SocketAddr, io::Result<S>)> {
+        let fut = (self.connect)(addr);
+        async move { (addr, fut.await) }
+    }
+}
+
+/// interleave families by preference, each family rotated by offset
+fn order_addrs(addrs: Vec<SocketAddr>, pref: IpPreference, offset: usize) -> Vec<SocketAddr> {
+    let (mut v6, mut v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|a| a.is_ipv6());
+    for family in [&mut v6, &mut v4].iter_mut() {
+        if !family.is_empty() {
+            let n = offset % family.len();
+            family.rotate_left(n);
+        }
+    }
+    let (first, second) = match pref {
+        IpPreference::Ipv6First => (v6, v4),
+        IpPreference::Ipv4First => (v4, v6),
+        IpPreference::Ipv4Only => (v4, vec![]),
+        IpPreference::Ipv6Only => (v6, vec![]),
+    };
+    let mut res = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => res.extend(a.into_iter().chain(b)),
+        }
+    }
+    res
+}
+
+/// completes after given duration
+#[cfg(feature = "timer")]
+fn delay(duration: Duration) -> async_io::Timer {
+    async_io::Timer::after(duration)
+}
+
+/// completes after given duration, timed on a helper thread
+#[cfg(not(feature = "timer"))]
+fn delay(duration: Duration) -> oneshot::Receiver<()> {
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        let _ = tx.send(());
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_order_addrs() {
+        let addrs = vec![
+            addr("10.0.0.1:3306"),
+            addr("10.0.0.2:3306"),
+            addr("[::1]:3306"),
+            addr("10.0.0.3:3306"),
+        ];
+        assert_eq!(
+            vec![
+                addr("[::1]:3306"),
+                addr("10.0.0.1:3306"),
+                addr("10.0.0.2:3306"),
+                addr("10.0.0.3:3306"),
+            ],
+            order_addrs(addrs.clone(), IpPreference::Ipv6First, 0)
+        );
+        assert_eq!(
+            vec![
+                addr("10.0.0.2:3306"),
+                addr("[::1]:3306"),
+                addr("10.0.0.3:3306"),
+                addr("10.0.0.1:3306"),
+            ],
+            order_addrs(addrs.clone(), IpPreference::Ipv4First, 1)
+        );
+        assert_eq!(
+            vec![addr("[::1]:3306")],
+            order_addrs(addrs, IpPreference::Ipv6Only, 5)
+        );
+    }
+
+    #[smol_potat::test]
+    async fn test_host_connector() {
+        let resolver = StaticResolver::new().host(
+            "db",
+            vec!["::1".parse().unwrap(), "10.0.0.1".parse().unwrap()],
+        );
+        let opts = DnsOpts {
+            attempt_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+        // IPv6 never completes, IPv4 wins after attempt delay
+        let connector = HostConnector::new(opts.clone(), |addr: SocketAddr| {
+            Box::pin(async move {
+                if addr.is_ipv6() {
+                    future::pending::<()>().await;
+                }
+                Ok(addr)
+            }) as BoxFuture<'static, io::Result<SocketAddr>>
+        })
+        .resolver(Arc::new(resolver.clone()));
+        assert_eq!(
+            addr("10.0.0.1:3306"),
+            connector.connect("db", 3306).await.unwrap()
+        );
+        // ip literal bypasses resolver
+        assert_eq!(
+            addr("127.0.0.1:3306"),
+            connector.connect("127.0.0.1", 3306).await.unwrap()
+        );
+        assert!(connector.connect("unknown", 3306).await.is_err());
+
+        // all addresses fail
+        let connector = HostConnector::new(opts.clone(), |addr: SocketAddr| {
+            future::ready(Err::<(), _>(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                addr.to_string(),
+            )))
+        })
+        .resolver(Arc::new(resolver));
+        let err = connector.connect("db", 3306).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+        assert_eq!("10.0.0.1:3306", err.to_string());
+
+        // resolver never responds
+        struct Stuck;
+        impl Resolver for Stuck {
+            fn resolve(&self, _: &str, _: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+                Box::pin(future::pending())
+            }
+        }
+        let opts = DnsOpts {
+            resolve_timeout: Some(Duration::from_millis(10)),
+            ..opts
+        };
+        let connector = HostConnector::new(opts.clone(), |_: SocketAddr| future::ready(Ok(())))
+            .resolver(Arc::new(Stuck));
+        let err = connector.connect("db", 3306).await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        // no attempt completes within connect timeout
+        let opts = DnsOpts {
+            connect_timeout: Some(Duration::from_millis(30)),
+            ..opts
+        };
+        let connector =
+            HostConnector::new(opts, |_: SocketAddr| future::pending::<io::Result<()>>());
+        let err = connector.connect("127.0.0.1", 3306).await.unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+}
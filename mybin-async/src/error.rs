@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use mybin_core::binlog::{GtidSet, IncidentType};
 use mybin_core::packet::ErrPacket;
 use thiserror::*;
 
@@ -22,8 +23,32 @@ pub enum Error {
     Utf8Error(#[from] std::string::FromUtf8Error),
     #[error("binlog stream not ended")]
     BinlogStreamNotEnded,
+    #[error("binlog incident {0:?}: {1}, resync required")]
+    BinlogIncident(IncidentType, String),
+    #[error("binlog dump error: {0:?}")]
+    BinlogDumpError(BinlogDumpError),
+    #[error("invalid start position {0}:{1}, resume hint: {2:?}")]
+    InvalidStartPosition(String, u64, Option<ResumeHint>),
     #[error("empty result set")]
     EmptyResultSet,
+    #[error("expect exactly one row but got {0}")]
+    TooManyRows(usize),
+    #[error("packet too large: needed={needed}, allowed={allowed}")]
+    PacketTooLarge { needed: u64, allowed: u64 },
+    #[error("auth plugin {0} not supported")]
+    UnsupportedAuthPlugin(String),
+    #[error("mysql_clear_password requested by server but not allowed")]
+    CleartextPasswordDisallowed,
+    #[error("server_id {0} is used by source or another replica")]
+    ServerIdCollision(u32),
+    #[error("start position purged on source, missing gtids: {0}")]
+    StartPositionPurged(GtidSet),
+    #[error("packet out of order: expected sequence id {expected}, got {got}")]
+    PacketOutOfOrder { expected: u8, got: u8 },
+    #[error("invalid variable name {0:?}")]
+    InvalidVariableName(String),
+    #[error("session init statement {0} failed: {1}")]
+    SessionInitError(String, Box<Error>),
     #[error("core error {0}")]
     CoreError(#[from] mybin_core::error::Error),
     #[error("{0}")]
@@ -58,6 +83,19 @@ pub enum Needed {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// whether server denied the statement for lack of privilege
+    pub fn is_access_denied(&self) -> bool {
+        matches!(
+            self,
+            Error::SqlError(SqlError {
+                error_code: ER_ACCESS_DENIED_ERROR | ER_SPECIFIC_ACCESS_DENIED_ERROR,
+                ..
+            })
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SqlError {
     pub error_code: u16,
@@ -65,3 +103,90 @@ pub struct SqlError {
     pub sql_state: String,
     pub error_message: String,
 }
+
+/// error code of ER_ACCESS_DENIED_ERROR
+pub const ER_ACCESS_DENIED_ERROR: u16 = 1045;
+/// error code of ER_SPECIFIC_ACCESS_DENIED_ERROR, e.g. missing
+/// REPLICATION CLIENT privilege
+pub const ER_SPECIFIC_ACCESS_DENIED_ERROR: u16 = 1227;
+/// error code of ER_MASTER_FATAL_ERROR_READING_BINLOG
+pub const ER_MASTER_FATAL_ERROR_READING_BINLOG: u16 = 1236;
+/// error code of ER_MASTER_HAS_PURGED_REQUIRED_GTIDS, used in 5.6
+pub const ER_MASTER_HAS_PURGED_REQUIRED_GTIDS: u16 = 1789;
+
+/// error sent by master during binlog dump
+#[derive(Debug, Clone)]
+pub struct BinlogDumpError {
+    pub kind: BinlogDumpErrorKind,
+    pub sql_error: SqlError,
+    /// hint to resume the stream, retrieved from master after error
+    pub resume_hint: Option<ResumeHint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinlogDumpErrorKind {
+    /// requested binlog file does not exist or already purged
+    UnknownBinlogFile,
+    /// master has purged binlogs containing GTIDs the client requires
+    GtidPurged,
+    /// other fatal error reading binlog
+    Fatal,
+}
+
+impl BinlogDumpErrorKind {
+    /// classify replication errors by code and message,
+    /// returns None if it's not a binlog dump error
+    pub fn classify(err: &SqlError) -> Option<Self> {
+        match err.error_code {
+            ER_MASTER_HAS_PURGED_REQUIRED_GTIDS => Some(BinlogDumpErrorKind::GtidPurged),
+            ER_MASTER_FATAL_ERROR_READING_BINLOG => {
+                let msg = &err.error_message;
+                if msg.contains("purged binary logs containing GTIDs") {
+                    Some(BinlogDumpErrorKind::GtidPurged)
+                } else if msg.contains("Could not find first log file name")
+                    || msg.contains("could not find next log")
+                {
+                    Some(BinlogDumpErrorKind::UnknownBinlogFile)
+                } else {
+                    Some(BinlogDumpErrorKind::Fatal)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ResumeHint {
+    /// earliest binlog file still available on master
+    EarliestBinlogFile(String),
+    /// GTID set purged on master, client must have executed it
+    /// before resuming with auto position
+    GtidPurged(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_binlog_dump_error() {
+        let mut err = SqlError {
+            error_code: ER_MASTER_FATAL_ERROR_READING_BINLOG,
+            sql_state_marker: b'#',
+            sql_state: "HY000".to_owned(),
+            error_message: "Could not find first log file name in binary log index file".to_owned(),
+        };
+        assert_eq!(
+            Some(BinlogDumpErrorKind::UnknownBinlogFile),
+            BinlogDumpErrorKind::classify(&err)
+        );
+        err.error_message = "The slave is connecting using CHANGE MASTER TO MASTER_AUTO_POSITION = 1, but the master has purged binary logs containing GTIDs that the slave requires.".to_owned();
+        assert_eq!(
+            Some(BinlogDumpErrorKind::GtidPurged),
+            BinlogDumpErrorKind::classify(&err)
+        );
+        err.error_code = 1045;
+        assert_eq!(None, BinlogDumpErrorKind::classify(&err));
+    }
+}
@@ -0,0 +1,232 @@
+//! flash back transactions of a gtid range
+//!
+//! binlog can only be read forward, so the inverse operations of all
+//! transactions in range are collected first, then applied on target
+//! in reverse order, one transaction at a time.
+//! only row-based changes can be reversed.
+use crate::binlog::BinlogStream;
+use crate::conn::Conn;
+use crate::error::{Error, Result};
+use futures::{AsyncRead, AsyncWrite};
+use mybin_core::binlog::transform::sql::SqlCollection;
+use mybin_core::binlog::transform::undo::UndoSql;
+use mybin_core::binlog::transform::FromRowsV2;
+use mybin_core::binlog::{Event, GtidRange};
+use mybin_core::col::{ColumnDefinition, ColumnMetas};
+use smol_str::SmolStr;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlashbackSummary {
+    /// number of transactions reversed
+    pub trx_count: usize,
+    /// number of statements applied
+    pub stmt_count: usize,
+}
+
+/// reverse all transactions in gtid range, and apply the inverse
+/// operations on target connection
+///
+/// the stream should start at a position before the first transaction
+/// in range. reading stops once a gtid of the same sid beyond the range
+/// is met, or the stream ends.
+/// each reversed transaction is applied in its own transaction on target,
+/// and table definitions are queried from target as well, by qualified
+/// table name, so the default database of target is left unchanged.
+pub async fn flashback<S, T>(
+    mut stream: BinlogStream<'_, S>,
+    range: &GtidRange,
+    target: &mut Conn<T>,
+) -> Result<FlashbackSummary>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let max_gno = range
+        .max_gno()
+        .ok_or_else(|| Error::CustomError("empty gtid range to flash back".to_owned()))?;
+    let mut tbls: HashMap<u64, FlashbackTable> = HashMap::new();
+    // undo statements of each transaction in range
+    let mut trxs: Vec<Vec<UndoSql>> = vec![];
+    let mut current: Option<Vec<UndoSql>> = None;
+    while let Some(event) = stream.next_event().await? {
+        match event {
+            Event::GtidLogEvent(raw) => {
+                let data = raw.into_data()?;
+                if data.encoded_sid == range.sid && data.encoded_gno > max_gno {
+                    break;
+                }
+                if range.contains(data.encoded_sid, data.encoded_gno) {
+                    current = Some(vec![]);
+                }
+            }
+            Event::XidEvent(_) => {
+                if let Some(undo) = current.take() {
+                    trxs.push(undo);
+                }
+            }
+            Event::QueryEvent(raw) => {
+                if current.is_none() {
+                    continue;
+                }
+                let data = raw.into_data()?;
+                let query = data.query_text()?;
+                if !query.eq_ignore_ascii_case("BEGIN") {
+                    return Err(Error::CustomError(format!(
+                        "can not flash back statement: {}",
+                        query
+                    )));
+                }
+            }
+            Event::TableMapEvent(raw) => {
+                if current.is_none() {
+                    continue;
+                }
+                let data = raw.into_data()?;
+                let tbl_id = data.table_id;
+                if let Entry::Vacant(entry) = tbls.entry(tbl_id) {
+                    let tm = data.into_table_map()?;
+                    let col_defs = col_defs(target, &tm.schema_name, &tm.table_name).await?;
+                    entry.insert(FlashbackTable {
+                        db: tm.schema_name,
+                        tbl: tm.table_name,
+                        col_metas: tm.col_metas,
+                        col_defs,
+                    });
+                }
+            }
+            Event::WriteRowsEventV2(raw) => {
+                if let Some(undo) = current.as_mut() {
+                    let data = raw.into_data()?;
+                    let tm = table(&tbls, data.table_id)?;
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    undo.push(UndoSql::from_insert(
+                        tm.db.clone(),
+                        tm.tbl.clone(),
+                        rows,
+                        &tm.col_defs,
+                    ));
+                }
+            }
+            Event::DeleteRowsEventV2(raw) => {
+                if let Some(undo) = current.as_mut() {
+                    let data = raw.into_data()?;
+                    let tm = table(&tbls, data.table_id)?;
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    undo.push(UndoSql::from_delete(
+                        tm.db.clone(),
+                        tm.tbl.clone(),
+                        rows,
+                        &tm.col_defs,
+                    ));
+                }
+            }
+            Event::UpdateRowsEventV2(raw) => {
+                if let Some(undo) = current.as_mut() {
+                    let data = raw.into_data()?;
+                    let tm = table(&tbls, data.table_id)?;
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    undo.push(UndoSql::from_update(
+                        tm.db.clone(),
+                        tm.tbl.clone(),
+                        rows,
+                        &tm.col_defs,
+                    ));
+                }
+            }
+            Event::RotateEvent(_) => {
+                // table id is only valid in single binlog file
+                tbls.clear();
+            }
+            _ => (),
+        }
+    }
+    if current.is_some() {
+        log::warn!("stream ends with incomplete transaction, which is not flashed back");
+    }
+    let mut summary = FlashbackSummary::default();
+    for undo in trxs.iter().rev() {
+        let mut tx = target.begin().await?;
+        for sql in undo.iter().rev().flat_map(|u| u.sql_list()) {
+            tx.exec(sql.into_owned()).await?;
+            summary.stmt_count += 1;
+        }
+        tx.commit().await?;
+        summary.trx_count += 1;
+    }
+    Ok(summary)
+}
+
+#[derive(Debug)]
+struct FlashbackTable {
+    db: SmolStr,
+    tbl: SmolStr,
+    col_metas: ColumnMetas,
+    col_defs: Vec<ColumnDefinition>,
+}
+
+async fn col_defs<T>(conn: &mut Conn<T>, db: &str, tbl: &str) -> Result<Vec<ColumnDefinition>>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let rs = conn
+        .query()
+        .qry(format!(
+            "SELECT * FROM `{}`.`{}` LIMIT 0",
+            db.replace('`', "``"),
+            tbl.replace('`', "``")
+        ))
+        .await?;
+    let col_defs = rs.col_defs.clone();
+    rs.count().await?;
+    Ok(col_defs)
+}
+
+fn table(tbls: &HashMap<u64, FlashbackTable>, tbl_id: u64) -> Result<&FlashbackTable> {
+    tbls.get(&tbl_id)
+        .ok_or_else(|| Error::CustomError(format!("table map not found for table id {}", tbl_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::tests::new_conn;
+
+    #[smol_potat::test]
+    async fn test_flashback() {
+        let mut conn = new_conn().await;
+        let mut target = new_conn().await;
+        let server_uuid: String = conn.get_var("SERVER_UUID", true).await.unwrap().unwrap();
+        conn.exec("CREATE DATABASE IF NOT EXISTS bintest1")
+            .await
+            .unwrap();
+        conn.exec("DROP TABLE IF EXISTS bintest1.flashback1")
+            .await
+            .unwrap();
+        conn.exec("CREATE TABLE bintest1.flashback1 (id INT PRIMARY KEY, v INT)")
+            .await
+            .unwrap();
+        // start after DDL which can not be flashed back
+        let status = conn.show_master_status().await.unwrap().unwrap();
+        conn.exec("INSERT INTO bintest1.flashback1 VALUES (1, 1), (2, 2)")
+            .await
+            .unwrap();
+        let range: GtidRange = format!("{}:1-1000000", server_uuid).parse().unwrap();
+        let stream = conn
+            .binlog()
+            .binlog_filename(status.file)
+            .binlog_pos(status.position)
+            .non_block(true)
+            .request_stream()
+            .await
+            .unwrap();
+        let summary = flashback(stream, &range, &mut target).await.unwrap();
+        assert_eq!(1, summary.trx_count);
+        let cnt: u32 = target
+            .query_scalar("SELECT COUNT(*) FROM bintest1.flashback1")
+            .await
+            .unwrap();
+        assert_eq!(0, cnt);
+    }
+}
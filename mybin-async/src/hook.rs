@@ -0,0 +1,121 @@
+//! observers of connection lifecycle and packets
+//!
+//! hooks are called synchronously in the task driving the connection,
+//! so they should return quickly, e.g. by sending to a channel.
+use crate::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// information of established connection
+#[derive(Debug, Clone)]
+pub struct ConnectInfo {
+    pub connection_id: u32,
+    pub server_version: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+pub trait ConnHook: Send + Sync {
+    /// handshake and session initialization succeed
+    fn on_connect(&self, _info: &ConnectInfo) {}
+
+    /// connection is closed by quit, or broken by IO error
+    fn on_disconnect(&self, _err: Option<&Error>) {}
+
+    /// raw packet without header, only called in debug builds
+    fn on_packet(&self, _direction: PacketDirection, _seq: u8, _payload: &[u8]) {}
+}
+
+/// hooks registered on connection, shared by its clones
+#[derive(Clone, Default)]
+pub struct ConnHooks(Vec<Arc<dyn ConnHook>>);
+
+impl ConnHooks {
+    pub fn add(&mut self, hook: Arc<dyn ConnHook>) {
+        self.0.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn on_connect(&self, info: &ConnectInfo) {
+        for hook in &self.0 {
+            hook.on_connect(info);
+        }
+    }
+
+    pub(crate) fn on_disconnect(&self, err: Option<&Error>) {
+        for hook in &self.0 {
+            hook.on_disconnect(err);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    pub(crate) fn on_packet(&self, direction: PacketDirection, seq: u8, payload: &[u8]) {
+        for hook in &self.0 {
+            hook.on_packet(direction, seq, payload);
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline]
+    pub(crate) fn on_packet(&self, _direction: PacketDirection, _seq: u8, _payload: &[u8]) {}
+}
+
+impl fmt::Debug for ConnHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ConnHooks({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::Conn;
+    use futures::executor::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ConnHook for Recorder {
+        fn on_disconnect(&self, err: Option<&Error>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("disconnect {}", err.is_some()));
+        }
+
+        fn on_packet(&self, direction: PacketDirection, seq: u8, payload: &[u8]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{:?} {} {:?}", direction, seq, payload));
+        }
+    }
+
+    #[test]
+    fn test_conn_hooks() {
+        let recorder = Arc::new(Recorder::default());
+        // one packet of payload [1, 2] with seq 0
+        let input = futures::io::Cursor::new(vec![2u8, 0, 0, 0, 1, 2]);
+        let mut conn = Conn::new(input);
+        conn.add_hook(recorder.clone());
+        let msg = block_on(conn.recv_msg()).unwrap();
+        assert_eq!(&[1, 2][..], &msg[..]);
+        assert!(block_on(conn.recv_msg()).is_err());
+        let events = recorder.0.lock().unwrap().clone();
+        let mut expected = vec![];
+        if cfg!(debug_assertions) {
+            expected.push("Received 0 [1, 2]".to_owned());
+        }
+        expected.push("disconnect true".to_owned());
+        assert_eq!(expected, events);
+    }
+}
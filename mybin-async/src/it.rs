@@ -0,0 +1,402 @@
+//! helpers of integration tests against real MySQL servers
+//!
+//! enabled by feature `it-tests`. servers are started as docker
+//! containers, so docker must be available on the host.
+//! see tests/it_mysql.rs for usage.
+use crate::conn::{Conn, ConnOpts};
+use crate::error::{Error, Result};
+use async_net::TcpStream;
+use bytes::Buf;
+use mybin_core::binlog::*;
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+pub const IT_PASSWORD: &str = "password";
+pub const IT_DATABASE: &str = "mybin_it";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MysqlVersion {
+    V5_6,
+    V5_7,
+    V8_0,
+}
+
+impl MysqlVersion {
+    pub fn all() -> Vec<MysqlVersion> {
+        vec![MysqlVersion::V5_6, MysqlVersion::V5_7, MysqlVersion::V8_0]
+    }
+
+    pub fn image(self) -> &'static str {
+        match self {
+            MysqlVersion::V5_6 => "mysql:5.6",
+            MysqlVersion::V5_7 => "mysql:5.7",
+            MysqlVersion::V8_0 => "mysql:8.0",
+        }
+    }
+
+    /// server options to enable binlog in ROW format with GTID
+    pub fn server_args(self) -> Vec<&'static str> {
+        let mut args = vec![
+            "--server-id=1",
+            "--log-bin=mysql-bin",
+            "--binlog-format=ROW",
+            "--gtid-mode=ON",
+            "--enforce-gtid-consistency=ON",
+        ];
+        match self {
+            // GTID requires log_slave_updates before 5.7
+            MysqlVersion::V5_6 => args.push("--log-slave-updates"),
+            MysqlVersion::V5_7 => (),
+            // full auth of caching_sha2_password is not implemented
+            MysqlVersion::V8_0 => {
+                args.push("--default-authentication-plugin=mysql_native_password")
+            }
+        }
+        args
+    }
+}
+
+impl std::str::FromStr for MysqlVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "5.6" => Ok(MysqlVersion::V5_6),
+            "5.7" => Ok(MysqlVersion::V5_7),
+            "8.0" => Ok(MysqlVersion::V8_0),
+            _ => Err(Error::CustomError(format!(
+                "unsupported mysql version {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// MySQL server in docker container, removed on drop
+#[derive(Debug)]
+pub struct MysqlContainer {
+    pub version: MysqlVersion,
+    pub name: String,
+    pub port: u16,
+}
+
+impl MysqlContainer {
+    /// start container and wait until server accepts TCP connections
+    pub fn start(version: MysqlVersion, port: u16, timeout: Duration) -> Result<Self> {
+        let name = format!("mybin-it-{}", port);
+        let output = Command::new("docker")
+            .args(["run", "-d", "--rm", "--name", &name])
+            .args(["-e", &format!("MYSQL_ROOT_PASSWORD={}", IT_PASSWORD)])
+            .args(["-p", &format!("{}:3306", port)])
+            .arg(version.image())
+            .args(version.server_args())
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::CustomError(format!(
+                "failed to start {}: {}",
+                version.image(),
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        let container = MysqlContainer {
+            version,
+            name,
+            port,
+        };
+        container.wait_ready(timeout)?;
+        Ok(container)
+    }
+
+    fn wait_ready(&self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        // ping through TCP, the temporary server during
+        // initialization only listens on socket
+        while start.elapsed() < timeout {
+            let status = Command::new("docker")
+                .args(["exec", &self.name, "mysqladmin", "ping", "-h127.0.0.1"])
+                .arg(format!("-p{}", IT_PASSWORD))
+                .args(["-uroot", "--silent"])
+                .output()?
+                .status;
+            if status.success() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        Err(Error::CustomError(format!(
+            "{} not ready in {:?}",
+            self.name, timeout
+        )))
+    }
+
+    pub async fn connect(&self) -> Result<Conn<TcpStream>> {
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).await?;
+        let mut conn = Conn::new(stream);
+        conn.handshake(ConnOpts {
+            username: "root".to_owned(),
+            password: IT_PASSWORD.to_owned(),
+            ..Default::default()
+        })
+        .await?;
+        Ok(conn)
+    }
+}
+
+impl Drop for MysqlContainer {
+    fn drop(&mut self) {
+        if let Err(e) = Command::new("docker")
+            .args(["rm", "-f", &self.name])
+            .output()
+        {
+            log::warn!("failed to remove container {}: {}", self.name, e);
+        }
+    }
+}
+
+/// statements to execute and their expected effect on binlog
+#[derive(Debug, Clone, Default)]
+pub struct Workload {
+    pub stmts: Vec<String>,
+    /// tables expected in TableMapEvents
+    pub tables: Vec<String>,
+    /// number of rows inserted, updated and deleted
+    pub row_changes: usize,
+}
+
+impl Workload {
+    pub fn merge(mut self, other: Workload) -> Self {
+        self.stmts.extend(other.stmts);
+        self.tables.extend(other.tables);
+        self.row_changes += other.row_changes;
+        self
+    }
+
+    pub async fn run(&self, conn: &mut Conn<TcpStream>) -> Result<()> {
+        for stmt in &self.stmts {
+            log::debug!("run workload: {}", stmt);
+            conn.exec(stmt.as_str()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// create, alter, rename and drop tables
+pub fn ddl_workload() -> Workload {
+    let stmts = vec![
+        format!("CREATE DATABASE IF NOT EXISTS {}", IT_DATABASE),
+        format!(
+            "CREATE TABLE {}.ddl1 (id INT PRIMARY KEY, c1 VARCHAR(20))",
+            IT_DATABASE
+        ),
+        format!("ALTER TABLE {}.ddl1 ADD COLUMN c2 INT", IT_DATABASE),
+        format!("CREATE INDEX idx_c2 ON {}.ddl1 (c2)", IT_DATABASE),
+        format!("RENAME TABLE {}.ddl1 TO {}.ddl2", IT_DATABASE, IT_DATABASE),
+        format!("DROP TABLE {}.ddl2", IT_DATABASE),
+    ];
+    Workload {
+        stmts,
+        ..Default::default()
+    }
+}
+
+/// table with all column types supported in binlog,
+/// with rows of boundary values and nulls
+pub fn all_types_workload() -> Workload {
+    let stmts = vec![
+        format!("CREATE DATABASE IF NOT EXISTS {}", IT_DATABASE),
+        format!(
+            "CREATE TABLE {}.all_types (
+                id INT PRIMARY KEY,
+                c_tinyint TINYINT, c_utinyint TINYINT UNSIGNED,
+                c_smallint SMALLINT, c_mediumint MEDIUMINT,
+                c_int INT, c_bigint BIGINT, c_ubigint BIGINT UNSIGNED,
+                c_float FLOAT, c_double DOUBLE, c_decimal DECIMAL(20,6),
+                c_bit BIT(10), c_year YEAR, c_date DATE, c_time TIME(3),
+                c_datetime DATETIME(6), c_timestamp TIMESTAMP(3) NULL,
+                c_char CHAR(10), c_varchar VARCHAR(100), c_binary BINARY(4),
+                c_varbinary VARBINARY(100), c_text TEXT, c_blob BLOB,
+                c_enum ENUM('a','b','c'), c_set SET('x','y','z')
+            )",
+            IT_DATABASE
+        ),
+        format!(
+            "INSERT INTO {}.all_types VALUES
+                (1, -128, 255, -32768, -8388608, -2147483648, -9223372036854775808,
+                 18446744073709551615, 1.5, -2.25, -12345678901234.123456,
+                 b'1010101010', 2021, '2021-01-01', '-838:59:59.000',
+                 '2021-01-01 12:34:56.123456', '2021-01-01 00:00:01.001',
+                 'char', 'varchar', x'01020304', x'ff00', 'text', x'deadbeef',
+                 'b', 'x,z'),
+                (2, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL,
+                 NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL,
+                 NULL, NULL, NULL, NULL)",
+            IT_DATABASE
+        ),
+        format!(
+            "UPDATE {}.all_types SET c_int = 1, c_varchar = 'updated'",
+            IT_DATABASE
+        ),
+        format!("DELETE FROM {}.all_types WHERE id = 2", IT_DATABASE),
+    ];
+    Workload {
+        stmts,
+        tables: vec!["all_types".to_owned()],
+        row_changes: 5,
+    }
+}
+
+/// single transaction larger than default max size of binlog event,
+/// so its rows are split into multiple events
+pub fn big_trx_workload(n_rows: usize) -> Workload {
+    let mut stmts = vec![
+        format!("CREATE DATABASE IF NOT EXISTS {}", IT_DATABASE),
+        format!(
+            "CREATE TABLE {}.big_trx (id INT PRIMARY KEY, payload VARCHAR(1000))",
+            IT_DATABASE
+        ),
+        "BEGIN".to_owned(),
+    ];
+    let batch = 100;
+    for start in (0..n_rows).step_by(batch) {
+        let values: Vec<String> = (start..n_rows.min(start + batch))
+            .map(|i| format!("({}, REPEAT('x', 1000))", i))
+            .collect();
+        stmts.push(format!(
+            "INSERT INTO {}.big_trx VALUES {}",
+            IT_DATABASE,
+            values.join(",")
+        ));
+    }
+    stmts.push("COMMIT".to_owned());
+    Workload {
+        stmts,
+        tables: vec!["big_trx".to_owned()],
+        row_changes: n_rows,
+    }
+}
+
+/// what is parsed from binlog
+#[derive(Debug, Clone, Default)]
+pub struct BinlogSummary {
+    pub event_counts: HashMap<LogEventType, usize>,
+    pub tables: HashSet<String>,
+    pub row_changes: usize,
+    pub queries: Vec<String>,
+}
+
+impl BinlogSummary {
+    pub fn count(&self, type_code: LogEventType) -> usize {
+        self.event_counts.get(&type_code).cloned().unwrap_or(0)
+    }
+
+    /// panics if binlog does not match workload
+    pub fn assert_workload(&self, workload: &Workload) {
+        for tbl in &workload.tables {
+            assert!(
+                self.tables.contains(tbl),
+                "table {} not found in binlog, tables={:?}",
+                tbl,
+                self.tables
+            );
+        }
+        assert_eq!(
+            workload.row_changes, self.row_changes,
+            "row changes mismatch"
+        );
+    }
+}
+
+/// read binlog from given position to the end, and decode every
+/// supported event completely
+pub async fn collect_binlog(
+    conn: &mut Conn<TcpStream>,
+    binlog_filename: &str,
+    binlog_pos: u64,
+) -> Result<BinlogSummary> {
+    let mut stream = conn
+        .binlog()
+        .binlog_filename(binlog_filename)
+        .binlog_pos(binlog_pos)
+        .validate_checksum(true)
+        .non_block(true)
+        .request_stream()
+        .await?;
+    let mut summary = BinlogSummary::default();
+    let mut table_maps = HashMap::new();
+    while let Some(event) = stream.next_event().await? {
+        *summary
+            .event_counts
+            .entry(event.header().type_code)
+            .or_default() += 1;
+        match event {
+            Event::TableMapEvent(e) => {
+                let data = e.into_data()?;
+                let table_id = data.table_id;
+                let tm = data.into_table_map()?;
+                summary.tables.insert(tm.table_name.to_string());
+                table_maps.insert(table_id, tm);
+            }
+            Event::WriteRowsEventV2(e) => {
+                let data = e.into_data()?;
+                let tm = table_map_of(&table_maps, data.table_id)?;
+                summary.row_changes += data.into_rows(&tm.col_metas)?.rows.len();
+            }
+            Event::DeleteRowsEventV2(e) => {
+                let data = e.into_data()?;
+                let tm = table_map_of(&table_maps, data.table_id)?;
+                summary.row_changes += data.into_rows(&tm.col_metas)?.rows.len();
+            }
+            Event::UpdateRowsEventV2(e) => {
+                let data = e.into_data()?;
+                let tm = table_map_of(&table_maps, data.table_id)?;
+                summary.row_changes += data.into_rows(&tm.col_metas)?.rows.len();
+            }
+            Event::QueryEvent(e) => {
+                let data = e.into_data()?;
+                summary.queries.push(data.query_text()?.into_owned());
+            }
+            Event::GtidLogEvent(e) => {
+                e.into_data()?;
+            }
+            Event::XidEvent(e) => {
+                e.into_data()?;
+            }
+            Event::PreviousGtidsLogEvent(e) => {
+                let data = e.into_data()?;
+                data.gtid_set()?;
+            }
+            _ => (),
+        }
+    }
+    Ok(summary)
+}
+
+fn table_map_of(table_maps: &HashMap<u64, TableMap>, table_id: u64) -> Result<&TableMap> {
+    table_maps
+        .get(&table_id)
+        .ok_or_else(|| Error::CustomError(format!("table map of table_id {} not found", table_id)))
+}
+
+/// run query and compare text values, NULL as None
+pub async fn assert_query_rows(
+    conn: &mut Conn<TcpStream>,
+    sql: &str,
+    expected: &[&[Option<&str>]],
+) -> Result<()> {
+    let rows = conn.query().qry(sql).await?.all().await?;
+    let actual: Vec<Vec<Option<String>>> = rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|v| v.map(|bs| String::from_utf8_lossy(bs.chunk()).into_owned()))
+                .collect()
+        })
+        .collect();
+    let expected: Vec<Vec<Option<String>>> = expected
+        .iter()
+        .map(|row| row.iter().map(|v| v.map(|s| s.to_owned())).collect())
+        .collect();
+    assert_eq!(expected, actual, "result of {}", sql);
+    Ok(())
+}
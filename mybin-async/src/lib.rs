@@ -1,8 +1,29 @@
 #![forbid(unsafe_code)]
 mod auth_plugin;
 pub mod binlog;
+pub mod buf_pool;
+pub mod config;
 pub mod conn;
+pub mod demux;
+pub mod dns;
 pub mod error;
+pub mod flashback;
+pub mod hook;
+#[cfg(feature = "it-tests")]
+pub mod it;
+pub mod lob;
+pub mod merge;
+pub mod multi_host;
+mod offload;
+pub mod proxy;
 pub mod query;
+pub mod reconnect;
+pub mod replication;
 pub mod resultset;
+pub mod retention;
+pub mod snapshot;
 pub mod stmt;
+pub mod task;
+pub mod timing;
+pub mod trace;
+pub mod trx;
@@ -0,0 +1,97 @@
+//! async access of large column values
+//!
+//! values are already in memory once the row is received, so reads
+//! never pend. the reader can be passed to `Stmt::send_long_data`
+//! to copy a value to another statement chunk by chunk.
+use futures::io::{AsyncBufRead, AsyncRead};
+use mybin_core::lob::{ColumnReader, ToColumnReader};
+use std::io::{self, BufRead, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// AsyncRead adapter of ColumnReader
+#[derive(Debug, Clone)]
+pub struct AsyncColumnReader(ColumnReader);
+
+impl AsyncColumnReader {
+    pub fn new(reader: ColumnReader) -> Self {
+        AsyncColumnReader(reader)
+    }
+
+    /// number of bytes not read
+    pub fn remaining(&self) -> usize {
+        self.0.remaining()
+    }
+
+    pub fn into_inner(self) -> ColumnReader {
+        self.0
+    }
+}
+
+impl From<ColumnReader> for AsyncColumnReader {
+    fn from(reader: ColumnReader) -> Self {
+        AsyncColumnReader(reader)
+    }
+}
+
+impl AsyncRead for AsyncColumnReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().0.read(buf))
+    }
+}
+
+impl AsyncBufRead for AsyncColumnReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Poll::Ready(self.get_mut().0.fill_buf())
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().0.consume(amt)
+    }
+}
+
+/// column values which can be read asynchronously
+pub trait ToAsyncColumnReader {
+    /// returns None if value is NULL or not a string or binary type
+    fn async_column_reader(&self) -> Option<AsyncColumnReader>;
+}
+
+impl<T: ToColumnReader> ToAsyncColumnReader for T {
+    fn async_column_reader(&self) -> Option<AsyncColumnReader> {
+        self.column_reader().map(AsyncColumnReader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use futures::io::{AsyncBufReadExt, AsyncReadExt};
+    use futures::StreamExt;
+    use mybin_core::col::BinlogColumnValue;
+
+    #[smol_potat::test]
+    async fn test_async_column_reader() {
+        let value = BinlogColumnValue::Blob(Bytes::from(vec![7u8; 10]));
+        let mut reader = value.async_column_reader().unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(4, reader.read(&mut buf).await.unwrap());
+        assert_eq!(6, reader.remaining());
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(vec![7u8; 6], rest);
+
+        let reader = ColumnReader::with_chunk_size(Bytes::from_static(b"a\nb\nc"), 2);
+        let lines: Vec<_> = AsyncColumnReader::from(reader)
+            .lines()
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        assert_eq!(vec!["a", "b", "c"], lines);
+        assert!(BinlogColumnValue::Null.async_column_reader().is_none());
+    }
+}
@@ -0,0 +1,257 @@
+//! merge binlog streams of multiple masters
+//!
+//! this is useful when aggregating shards of multi-source replication
+//! into a single sink.
+use crate::binlog::BinlogStream;
+use crate::error::Result;
+use futures::{AsyncRead, AsyncWrite};
+use mybin_core::binlog::{Event, SourcePosition};
+
+/// transaction received from one of the merged sources
+///
+/// events that do not belong to any transaction, e.g. RotateEvent,
+/// FormatDescriptionEvent or HeartbeatLogEvent, are delivered as
+/// single-event transactions without gtid.
+#[derive(Debug, Clone)]
+pub struct SourceTrx {
+    /// tag of the source stream
+    pub source: String,
+    /// (encoded_sid, encoded_gno) of the GtidLogEvent, if present
+    pub gtid: Option<(u128, u64)>,
+    /// timestamp of the event that terminates the transaction
+    pub commit_ts: u32,
+    /// range from first to last event
+    pub position: SourcePosition,
+    pub events: Vec<Event>,
+}
+
+impl SourceTrx {
+    /// commit timestamp, then server uuid of gtid
+    fn merge_key(&self) -> (u32, Option<u128>) {
+        (self.commit_ts, self.gtid.map(|(sid, _)| sid))
+    }
+}
+
+/// merge multiple binlog streams into one
+///
+/// transactions are ordered by commit timestamp, then by server uuid
+/// of their gtids, and the order of sources is the last tie breaker.
+/// commit timestamp is in seconds, so transactions of different
+/// sources committed in the same second are not ordered by their
+/// actual commit time, but deterministically by server uuid.
+/// transactions of the same source always keep their original order.
+///
+/// each source is read in turn, so a blocking source without any
+/// incoming event stalls the merged stream until its next heartbeat.
+#[derive(Debug)]
+pub struct MergedStream<'s, S> {
+    sources: Vec<MergeSource<'s, S>>,
+}
+
+#[derive(Debug)]
+struct MergeSource<'s, S> {
+    tag: String,
+    stream: BinlogStream<'s, S>,
+    head: Option<SourceTrx>,
+    completed: bool,
+}
+
+impl<'s, S> Default for MergedStream<'s, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'s, S> MergedStream<'s, S> {
+    pub fn new() -> Self {
+        MergedStream { sources: vec![] }
+    }
+
+    /// add a tagged source stream
+    pub fn source<T: Into<String>>(mut self, tag: T, stream: BinlogStream<'s, S>) -> Self {
+        self.sources.push(MergeSource {
+            tag: tag.into(),
+            stream,
+            head: None,
+            completed: false,
+        });
+        self
+    }
+}
+
+impl<'s, S> MergedStream<'s, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// returns next transaction of all sources
+    ///
+    /// None is returned only if all sources are completed
+    pub async fn next_trx(&mut self) -> Result<Option<SourceTrx>> {
+        for src in self.sources.iter_mut() {
+            if src.head.is_none() && !src.completed {
+                src.head = next_source_trx(&src.tag, &mut src.stream).await?;
+                src.completed = src.head.is_none();
+            }
+        }
+        let idx = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, src)| src.head.as_ref().map(|trx| (trx.merge_key(), i)))
+            .min()
+            .map(|(_, i)| i);
+        Ok(idx.and_then(|i| self.sources[i].head.take()))
+    }
+}
+
+/// collect events of next transaction, None if stream ends
+pub(crate) async fn next_source_trx<'s, S>(
+    tag: &str,
+    stream: &mut BinlogStream<'s, S>,
+) -> Result<Option<SourceTrx>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut trx = TrxBuilder::default();
+    while let Some((event, pos)) = stream.next_event_with_position().await? {
+        if let Some(trx) = trx.push(tag, event, pos)? {
+            return Ok(Some(trx));
+        }
+    }
+    // stream ends in middle of a transaction
+    if !trx.events.is_empty() {
+        log::warn!(
+            "source {} ends with incomplete transaction of {} events",
+            tag,
+            trx.events.len()
+        );
+    }
+    Ok(None)
+}
+
+/// collect events until transaction boundary
+#[derive(Debug, Default)]
+struct TrxBuilder {
+    gtid: Option<(u128, u64)>,
+    in_trx: bool,
+    position: Option<SourcePosition>,
+    events: Vec<Event>,
+}
+
+impl TrxBuilder {
+    fn push(&mut self, tag: &str, event: Event, pos: SourcePosition) -> Result<Option<SourceTrx>> {
+        let ts = event.header().timestamp;
+        let end = match &event {
+            Event::GtidLogEvent(raw) => {
+                let data = raw.clone().into_data()?;
+                self.gtid = Some((data.encoded_sid, data.encoded_gno));
+                false
+            }
+            Event::AnonymousGtidLogEvent(_) => false,
+            Event::XidEvent(_) => true,
+            Event::QueryEvent(raw) => {
+                let data = raw.clone().into_data()?;
+                let query = data.query_text()?;
+                if query.eq_ignore_ascii_case("BEGIN") {
+                    self.in_trx = true;
+                    false
+                } else {
+                    // COMMIT of non-transactional tables or DDL
+                    true
+                }
+            }
+            // standalone event out of transaction
+            _ => !self.in_trx && self.events.is_empty(),
+        };
+        self.events.push(event);
+        match self.position.as_mut() {
+            Some(position) => position.extend(&pos),
+            None => self.position = Some(pos),
+        }
+        if end {
+            self.in_trx = false;
+            return Ok(Some(SourceTrx {
+                source: tag.to_owned(),
+                gtid: self.gtid.take(),
+                commit_ts: ts,
+                position: self.position.take().unwrap(),
+                events: std::mem::take(&mut self.events),
+            }));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::tests::new_conn;
+
+    fn source_trx(source: &str, sid: Option<u128>, commit_ts: u32) -> SourceTrx {
+        SourceTrx {
+            source: source.to_owned(),
+            gtid: sid.map(|sid| (sid, 1)),
+            commit_ts,
+            position: SourcePosition {
+                binlog_filename: "mysql-bin.000001".to_owned(),
+                start_pos: 4,
+                end_pos: 4,
+                gtid: None,
+                server_id: 1,
+                timestamp: commit_ts,
+            },
+            events: vec![],
+        }
+    }
+
+    #[test]
+    fn test_merge_key() {
+        let mut trxs = [
+            source_trx("s1", Some(3), 10),
+            source_trx("s2", Some(2), 10),
+            source_trx("s3", None, 10),
+            source_trx("s4", Some(1), 9),
+        ];
+        trxs.sort_by_key(|trx| trx.merge_key());
+        assert_eq!(
+            vec!["s4", "s3", "s2", "s1"],
+            trxs.iter()
+                .map(|trx| trx.source.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[smol_potat::test]
+    async fn test_merged_stream() {
+        let mut conn1 = new_conn().await;
+        let mut conn2 = new_conn().await;
+        let stream1 = conn1
+            .binlog()
+            .binlog_filename("mysql-bin.000002")
+            .binlog_pos(4)
+            .non_block(true)
+            .request_stream()
+            .await
+            .unwrap();
+        let stream2 = conn2
+            .binlog()
+            .binlog_filename("mysql-bin.000002")
+            .binlog_pos(4)
+            .non_block(true)
+            .request_stream()
+            .await
+            .unwrap();
+        let mut merged = MergedStream::new()
+            .source("s1", stream1)
+            .source("s2", stream2);
+        let mut cnt = 0;
+        while let Some(trx) = merged.next_trx().await.unwrap() {
+            dbg!(&trx.source, trx.gtid, trx.commit_ts, trx.events.len());
+            assert!(trx.position.start_pos <= trx.position.end_pos);
+            cnt += 1;
+            if cnt == 50 {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! connector of multiple MySQL hosts
+//!
+//! endpoints and read policy are configured in ConnOpts.
+//! writes always go to primaries, in the configured order as failover
+//! order. reads are distributed by read policy. host failed to connect
+//! is marked down for a period and skipped, unless all hosts are down.
+//!
+//! the connector does not depend on any runtime, streams are created
+//! by user provided function.
+use crate::conn::{Conn, ConnOpts};
+use crate::error::{Error, Result};
+use futures::{AsyncRead, AsyncWrite};
+use serde_derive::*;
+use std::future::Future;
+use std::io;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostRole {
+    Primary,
+    Replica,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub role: HostRole,
+}
+
+impl Endpoint {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadPolicy {
+    /// reads go to primaries as writes
+    #[default]
+    PrimaryOnly,
+    /// round-robin over replicas, fall back to primaries
+    /// if no replica is available
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, Default)]
+struct HostHealth {
+    down_until: Option<Instant>,
+    failures: u32,
+}
+
+/// connection together with index of its endpoint
+#[derive(Debug)]
+pub struct HostConn<S> {
+    pub endpoint_idx: usize,
+    pub conn: Conn<S>,
+}
+
+#[derive(Debug)]
+pub struct MultiHostConnector<F> {
+    opts: ConnOpts,
+    connect: F,
+    health: Vec<HostHealth>,
+    down_period: Duration,
+    next_read: usize,
+}
+
+impl<F> MultiHostConnector<F> {
+    pub fn new(opts: ConnOpts, connect: F) -> Self {
+        let health = vec![HostHealth::default(); opts.endpoints.len()];
+        MultiHostConnector {
+            opts,
+            connect,
+            health,
+            down_period: Duration::from_secs(30),
+            next_read: 0,
+        }
+    }
+
+    /// how long a failed host is skipped, 30 seconds by default
+    pub fn down_period(mut self, down_period: Duration) -> Self {
+        self.down_period = down_period;
+        self
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.opts.endpoints
+    }
+
+    /// whether host is considered available now
+    pub fn is_up(&self, idx: usize) -> bool {
+        self.is_up_at(idx, Instant::now())
+    }
+
+    /// mark host down, e.g. on connection error detected by user
+    pub fn mark_down(&mut self, idx: usize) {
+        let h = &mut self.health[idx];
+        h.failures += 1;
+        h.down_until = Some(Instant::now() + self.down_period);
+        log::warn!(
+            "endpoint {} marked down, failures={}",
+            self.opts.endpoints[idx].addr(),
+            h.failures
+        );
+    }
+
+    pub fn mark_up(&mut self, idx: usize) {
+        self.health[idx] = HostHealth::default();
+    }
+
+    fn is_up_at(&self, idx: usize, now: Instant) -> bool {
+        match self.health[idx].down_until {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+
+    /// primaries in failover order, hosts down are moved to the end
+    fn write_candidates(&self, now: Instant) -> Vec<usize> {
+        let primaries = self.indices_of(HostRole::Primary);
+        self.up_first(primaries, now)
+    }
+
+    /// candidates by read policy, hosts down are moved to the end
+    fn read_candidates(&mut self, now: Instant) -> Vec<usize> {
+        let primaries = self.write_candidates(now);
+        if self.opts.read_policy == ReadPolicy::PrimaryOnly {
+            return primaries;
+        }
+        let mut replicas = self.indices_of(HostRole::Replica);
+        if !replicas.is_empty() {
+            let start = self.next_read % replicas.len();
+            replicas.rotate_left(start);
+            self.next_read = self.next_read.wrapping_add(1);
+        }
+        let mut candidates = self.up_first(replicas, now);
+        // primaries go after available replicas but before replicas down
+        let n_up = candidates
+            .iter()
+            .take_while(|idx| self.is_up_at(**idx, now))
+            .count();
+        let down = candidates.split_off(n_up);
+        candidates.extend(primaries);
+        candidates.extend(down);
+        candidates
+    }
+
+    fn indices_of(&self, role: HostRole) -> Vec<usize> {
+        self.opts
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, ep)| ep.role == role)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    fn up_first(&self, indices: Vec<usize>, now: Instant) -> Vec<usize> {
+        let (mut up, down): (Vec<_>, Vec<_>) = indices
+            .into_iter()
+            .partition(|idx| self.is_up_at(*idx, now));
+        up.extend(down);
+        up
+    }
+}
+
+impl<F, Fut, S> MultiHostConnector<F>
+where
+    F: Fn(&Endpoint) -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// connect to primary for writes
+    ///
+    /// a primary with read_only enabled is treated as failed,
+    /// as it is probably demoted
+    pub async fn connect_primary(&mut self) -> Result<HostConn<S>> {
+        let candidates = self.write_candidates(Instant::now());
+        self.connect_any(candidates, true).await
+    }
+
+    /// connect to host for reads
+    pub async fn connect_read(&mut self) -> Result<HostConn<S>> {
+        let candidates = self.read_candidates(Instant::now());
+        self.connect_any(candidates, false).await
+    }
+
+    /// connect and ping every endpoint, returns health of each endpoint
+    pub async fn check_health(&mut self) -> Vec<bool> {
+        let mut res = Vec::with_capacity(self.health.len());
+        for idx in 0..self.health.len() {
+            let healthy = match self.connect_one(idx).await {
+                Ok(mut conn) => conn.ping().await.is_ok(),
+                Err(_) => false,
+            };
+            if healthy {
+                self.mark_up(idx);
+            } else {
+                self.mark_down(idx);
+            }
+            res.push(healthy);
+        }
+        res
+    }
+
+    async fn connect_any(&mut self, candidates: Vec<usize>, writable: bool) -> Result<HostConn<S>> {
+        let mut last_err = None;
+        for idx in candidates {
+            match self.connect_checked(idx, writable).await {
+                Ok(conn) => {
+                    self.mark_up(idx);
+                    return Ok(HostConn {
+                        endpoint_idx: idx,
+                        conn,
+                    });
+                }
+                Err(e) => {
+                    log::warn!(
+                        "failed to connect endpoint {}: {}",
+                        self.opts.endpoints[idx].addr(),
+                        e
+                    );
+                    self.mark_down(idx);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| Error::CustomError("no endpoint available".to_owned())))
+    }
+
+    async fn connect_checked(&self, idx: usize, writable: bool) -> Result<Conn<S>> {
+        let mut conn = self.connect_one(idx).await?;
+        if writable {
+            let read_only: Option<String> = conn.get_var("read_only", true).await?;
+            if read_only.as_deref() == Some("ON") {
+                return Err(Error::CustomError(format!(
+                    "primary {} is read only",
+                    self.opts.endpoints[idx].addr()
+                )));
+            }
+        }
+        Ok(conn)
+    }
+
+    async fn connect_one(&self, idx: usize) -> Result<Conn<S>> {
+        let stream = (self.connect)(&self.opts.endpoints[idx]).await?;
+        let mut conn = Conn::new(stream);
+        conn.handshake(self.opts.clone()).await?;
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_host_candidates() {
+        let ep = |port, role| Endpoint {
+            host: "127.0.0.1".to_owned(),
+            port,
+            role,
+        };
+        let opts = ConnOpts {
+            endpoints: vec![
+                ep(1, HostRole::Primary),
+                ep(2, HostRole::Replica),
+                ep(3, HostRole::Primary),
+                ep(4, HostRole::Replica),
+            ],
+            read_policy: ReadPolicy::RoundRobin,
+            ..Default::default()
+        };
+        let mut connector = MultiHostConnector::new(opts, |_: &Endpoint| async {
+            Err::<futures::io::Cursor<Vec<u8>>, _>(io::Error::from(io::ErrorKind::Other))
+        });
+        let now = Instant::now();
+        assert_eq!(vec![0, 2], connector.write_candidates(now));
+        assert_eq!(vec![1, 3, 0, 2], connector.read_candidates(now));
+        assert_eq!(vec![3, 1, 0, 2], connector.read_candidates(now));
+        connector.mark_down(0);
+        connector.mark_down(1);
+        let now = Instant::now();
+        assert_eq!(vec![2, 0], connector.write_candidates(now));
+        assert_eq!(vec![3, 2, 0, 1], connector.read_candidates(now));
+        connector.mark_up(0);
+        assert!(connector.is_up(0));
+    }
+}
@@ -0,0 +1,251 @@
+//! parse binlog events on worker threads
+//!
+//! checksum validation and parsing are cpu bound, and may become
+//! the bottleneck when the stream is catching up a large backlog.
+//! the offload distributes messages to a bounded pool of threads
+//! while the IO task keeps reading, and returns results in the
+//! same order as messages are submitted.
+//! transaction payloads are decompressed by workers as well.
+use crate::error::{Error, Result};
+use crate::task::TaskName;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use mybin_core::binlog::{Event, ParserV4};
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type ParseResult = mybin_core::error::Result<Parsed>;
+
+/// parsed event, and events in it if it is a transaction payload
+#[derive(Debug)]
+pub(crate) struct Parsed {
+    pub(crate) event: Option<Event>,
+    pub(crate) payload: Option<Vec<Event>>,
+}
+
+impl Parsed {
+    fn parse(pv4: &ParserV4, msg: &mut Bytes, validate_checksum: bool) -> ParseResult {
+        let event = pv4.parse_event(msg, validate_checksum)?;
+        let payload = match &event {
+            Some(Event::TransactionPayloadEvent(raw)) => {
+                Some(pv4.parse_payload(&raw.clone().into_data()?)?)
+            }
+            _ => None,
+        };
+        Ok(Parsed { event, payload })
+    }
+}
+
+struct Job {
+    msg: Bytes,
+    // parser may change on FDE of next file
+    pv4: Arc<ParserV4>,
+    reply: oneshot::Sender<ParseResult>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseOffload {
+    pv4: Arc<ParserV4>,
+    tx: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    // receivers in order of submission
+    inflight: VecDeque<oneshot::Receiver<ParseResult>>,
+    // result of earliest message, received by wait_head
+    head: Option<ParseResult>,
+    max_inflight: usize,
+}
+
+impl ParseOffload {
+    /// threads are named by given name with index
+    pub(crate) fn new(
+        pv4: Arc<ParserV4>,
+        validate_checksum: bool,
+        concurrency: usize,
+        name: TaskName,
+    ) -> Result<Self> {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let mut offload = ParseOffload {
+            pv4,
+            tx: Some(tx),
+            workers: Vec::with_capacity(concurrency),
+            inflight: VecDeque::new(),
+            head: None,
+            max_inflight: concurrency * 2,
+        };
+        // spawned workers exit when offload is dropped on error
+        for i in 0..concurrency {
+            let rx = Arc::clone(&rx);
+            let worker = thread::Builder::new()
+                .name(name.clone().index(i).to_string())
+                .spawn(move || loop {
+                    // lock is released before parsing
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        Ok(Job {
+                            mut msg,
+                            pv4,
+                            reply,
+                        }) => {
+                            let _ = reply.send(Parsed::parse(&pv4, &mut msg, validate_checksum));
+                        }
+                        // offload dropped
+                        Err(_) => return,
+                    }
+                })?;
+            offload.workers.push(worker);
+        }
+        Ok(offload)
+    }
+
+    /// parser of messages submitted afterwards
+    pub(crate) fn set_parser(&mut self, pv4: Arc<ParserV4>) {
+        self.pv4 = pv4;
+    }
+
+    /// submit message without binlog stream header
+    pub(crate) fn submit(&mut self, msg: Bytes) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let pv4 = Arc::clone(&self.pv4);
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(Job { msg, pv4, reply }).ok())
+            .ok_or_else(worker_terminated)?;
+        self.inflight.push_back(rx);
+        Ok(())
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.inflight.len() + self.head.is_some() as usize >= self.max_inflight
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inflight.is_empty() && self.head.is_none()
+    }
+
+    /// result of earliest submitted message, if already parsed
+    pub(crate) fn try_next(&mut self) -> Result<Option<Parsed>> {
+        if let Some(res) = self.head.take() {
+            return Ok(Some(res?));
+        }
+        let head = match self.inflight.front_mut() {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        match head.try_recv().map_err(|_| worker_terminated())? {
+            Some(res) => {
+                self.inflight.pop_front();
+                Ok(Some(res?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// wait until earliest submitted message is parsed, the result
+    /// is kept for try_next. it is cancel safe
+    pub(crate) async fn wait_head(&mut self) -> Result<()> {
+        if self.head.is_some() {
+            return Ok(());
+        }
+        if let Some(head) = self.inflight.front_mut() {
+            let res = head.await.map_err(|_| worker_terminated())?;
+            self.inflight.pop_front();
+            self.head = Some(res);
+        }
+        Ok(())
+    }
+
+    /// wait for result of earliest submitted message
+    pub(crate) async fn next(&mut self) -> Result<Option<Parsed>> {
+        if let Some(res) = self.head.take() {
+            return Ok(Some(res?));
+        }
+        match self.inflight.pop_front() {
+            Some(head) => Ok(Some(head.await.map_err(|_| worker_terminated())??)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Drop for ParseOffload {
+    fn drop(&mut self) {
+        // close channel so that workers exit
+        self.tx.take();
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
+
+fn worker_terminated() -> Error {
+    Error::CustomError("binlog parser worker terminated".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskKind;
+    use bytes::Buf;
+    use bytes_parser::ReadFromBytes;
+    use mybin_core::binlog::EventLength;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] =
+        include_bytes!("../../mybin-core/data/mysql-bin.5.7.30.RowsEventV2");
+
+    #[test]
+    fn test_parse_offload_preserves_order() {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        let pv4 = Arc::new(ParserV4::from_binlog_file(&mut input).unwrap());
+        let mut offload = ParseOffload::new(
+            Arc::clone(&pv4),
+            false,
+            4,
+            TaskName::new(TaskKind::BinlogParser),
+        )
+        .unwrap();
+        let mut expected = vec![];
+        let mut actual = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            let msg = input.split_to(len);
+            expected.push(pv4.parse_event(&mut msg.clone(), false).unwrap());
+            if offload.is_full() {
+                actual.push(
+                    futures::executor::block_on(offload.next())
+                        .unwrap()
+                        .unwrap()
+                        .event,
+                );
+            }
+            offload.submit(msg).unwrap();
+        }
+        while let Some(parsed) = futures::executor::block_on(offload.next()).unwrap() {
+            actual.push(parsed.event);
+        }
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            assert_eq!(
+                e.as_ref().map(|e| e.header()),
+                a.as_ref().map(|a| a.header())
+            );
+        }
+    }
+
+    #[test]
+    fn test_wait_head() {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        let pv4 = Arc::new(ParserV4::from_binlog_file(&mut input).unwrap());
+        let mut offload =
+            ParseOffload::new(pv4, false, 1, TaskName::new(TaskKind::BinlogParser)).unwrap();
+        assert!(offload.is_empty());
+        let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+        offload.submit(input.split_to(len)).unwrap();
+        futures::executor::block_on(offload.wait_head()).unwrap();
+        // parsed result is kept until taken
+        assert!(!offload.is_empty());
+        assert!(offload.try_next().unwrap().unwrap().event.is_some());
+        assert!(offload.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! PROXY protocol header sent before MySQL handshake
+//!
+//! load balancers like HAProxy pass the original client address to
+//! MySQL by the header. the header is sent by Conn::handshake if
+//! configured in ConnOpts.
+//!
+//! reference: https://www.haproxy.org/download/2.0/doc/proxy-protocol.txt
+use crate::error::{Error, Result};
+use serde_derive::*;
+use std::net::SocketAddr;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyVersion {
+    /// human-readable header
+    V1,
+    /// binary header
+    V2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyProtocol {
+    pub version: ProxyVersion,
+    /// address reported as client address, if source or destination
+    /// is not set, the header tells the addresses are unknown (v1)
+    /// or the connection is local (v2)
+    #[serde(default)]
+    pub source: Option<SocketAddr>,
+    #[serde(default)]
+    pub destination: Option<SocketAddr>,
+}
+
+impl ProxyProtocol {
+    /// encode header, source and destination must be of same family
+    pub fn header(&self) -> Result<Vec<u8>> {
+        let addrs = match (self.source, self.destination) {
+            (Some(src), Some(dst)) => {
+                if src.is_ipv4() != dst.is_ipv4() {
+                    return Err(Error::CustomError(format!(
+                        "address family mismatch in proxy header: {} and {}",
+                        src, dst
+                    )));
+                }
+                Some((src, dst))
+            }
+            _ => None,
+        };
+        let header = match self.version {
+            ProxyVersion::V1 => v1_header(addrs),
+            ProxyVersion::V2 => v2_header(addrs),
+        };
+        Ok(header)
+    }
+}
+
+fn v1_header(addrs: Option<(SocketAddr, SocketAddr)>) -> Vec<u8> {
+    let line = match addrs {
+        Some((src, dst)) => format!(
+            "PROXY {} {} {} {} {}\r\n",
+            if src.is_ipv4() { "TCP4" } else { "TCP6" },
+            src.ip(),
+            dst.ip(),
+            src.port(),
+            dst.port()
+        ),
+        None => "PROXY UNKNOWN\r\n".to_owned(),
+    };
+    line.into_bytes()
+}
+
+fn v2_header(addrs: Option<(SocketAddr, SocketAddr)>) -> Vec<u8> {
+    let mut header = V2_SIGNATURE.to_vec();
+    let mut body = vec![];
+    match addrs {
+        Some((src, dst)) => {
+            // version 2, command PROXY
+            header.push(0x21);
+            match (src, dst) {
+                (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+                    // AF_INET, STREAM
+                    header.push(0x11);
+                    body.extend_from_slice(&src.ip().octets());
+                    body.extend_from_slice(&dst.ip().octets());
+                }
+                (src, dst) => {
+                    // AF_INET6, STREAM
+                    header.push(0x21);
+                    body.extend_from_slice(&ipv6_octets(src));
+                    body.extend_from_slice(&ipv6_octets(dst));
+                }
+            }
+            body.extend_from_slice(&src.port().to_be_bytes());
+            body.extend_from_slice(&dst.port().to_be_bytes());
+        }
+        None => {
+            // version 2, command LOCAL, AF_UNSPEC
+            header.push(0x20);
+            header.push(0x00);
+        }
+    }
+    header.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    header.extend(body);
+    header
+}
+
+fn ipv6_octets(addr: SocketAddr) -> [u8; 16] {
+    match addr {
+        SocketAddr::V4(addr) => addr.ip().to_ipv6_mapped().octets(),
+        SocketAddr::V6(addr) => addr.ip().octets(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_header() {
+        let proxy = ProxyProtocol {
+            version: ProxyVersion::V1,
+            source: Some("192.168.0.1:56324".parse().unwrap()),
+            destination: Some("192.168.0.11:3306".parse().unwrap()),
+        };
+        assert_eq!(
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 3306\r\n".to_vec(),
+            proxy.header().unwrap()
+        );
+        let v2 = ProxyProtocol {
+            version: ProxyVersion::V2,
+            ..proxy.clone()
+        };
+        let header = v2.header().unwrap();
+        assert_eq!(V2_SIGNATURE, &header[..12]);
+        assert_eq!(
+            &[0x21, 0x11, 0, 12, 192, 168, 0, 1, 192, 168, 0, 11, 0xdc, 0x04, 0x0c, 0xea],
+            &header[12..]
+        );
+        let unknown = ProxyProtocol {
+            version: ProxyVersion::V1,
+            source: None,
+            destination: None,
+        };
+        assert_eq!(b"PROXY UNKNOWN\r\n".to_vec(), unknown.header().unwrap());
+        let local = ProxyProtocol {
+            version: ProxyVersion::V2,
+            ..unknown
+        };
+        assert_eq!(&[0x20, 0, 0, 0], &local.header().unwrap()[12..]);
+        let mismatch = ProxyProtocol {
+            destination: Some("[::1]:3306".parse().unwrap()),
+            ..proxy
+        };
+        assert!(mismatch.header().is_err());
+    }
+}
@@ -1,10 +1,38 @@
 use crate::conn::Conn;
 use crate::error::Result;
 use crate::resultset::{new_result_set, ResultSet};
+use crate::timing::Timing;
+use bytes::Buf;
 use futures::{AsyncRead, AsyncWrite};
 use mybin_core::cmd::ComQuery;
 use mybin_core::col::TextColumnValue;
+use mybin_core::flag::StatusFlags;
 use mybin_core::packet::{ErrPacket, OkPacket};
+use mybin_core::Command;
+
+/// result of a query that does not return any rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub affected_rows: u64,
+    pub last_insert_id: u64,
+    pub status_flags: StatusFlags,
+    pub warnings: u16,
+    pub info: String,
+    pub timing: Timing,
+}
+
+impl From<OkPacket> for QueryResult {
+    fn from(ok: OkPacket) -> Self {
+        QueryResult {
+            affected_rows: ok.affected_rows,
+            last_insert_id: ok.last_insert_id,
+            status_flags: ok.status_flags,
+            warnings: ok.warnings,
+            info: String::from_utf8_lossy(&ok.info).into_owned(),
+            timing: Timing::default(),
+        }
+    }
+}
 
 /// wrapper struct on Conn to provide query functionality
 #[derive(Debug)]
@@ -24,22 +52,55 @@ where
     /// execute a query
     ///
     /// the query should not return any rows
-    pub async fn exec<Q: Into<String>>(self, qry: Q) -> Result<()> {
+    pub async fn exec<Q: Into<String>>(self, qry: Q) -> Result<QueryResult> {
         // let qry = ComQuery::new(qry);
         // QueryExecFuture::new(self.conn, qry)
+        self.conn.timing.start();
+        self.conn.rollback_pending().await?;
+        self.conn.timing.queued();
         let qry = ComQuery::new(qry);
         self.conn.send_msg(qry, true).await?;
-        // handle query like result set
+        self.conn.timing.sent();
+        self.recv_result().await
+    }
+
+    /// execute a query given as chunks, e.g. a large INSERT batch
+    ///
+    /// chunks are concatenated as query text and written directly
+    /// into packets, the query should not return any rows
+    pub async fn exec_streamed<I, B>(self, chunks: I) -> Result<QueryResult>
+    where
+        I: IntoIterator<Item = B>,
+        B: Buf,
+    {
+        self.conn.timing.start();
+        self.conn.rollback_pending().await?;
+        self.conn.timing.queued();
+        let chunks: Vec<B> = chunks.into_iter().collect();
+        self.conn
+            .send_chunks(Command::Query.to_byte(), chunks)
+            .await?;
+        self.conn.timing.sent();
+        self.recv_result().await
+    }
+
+    // handle query like result set
+    async fn recv_result(self) -> Result<QueryResult> {
         loop {
             let mut msg = self.conn.recv_msg().await?;
+            self.conn.timing.first_byte();
             match msg[0] {
                 0xff => {
                     let err = ErrPacket::read_from(&mut msg, &self.conn.cap_flags, true)?;
                     return Err(err.into());
                 }
                 0x00 => {
-                    OkPacket::read_from(&mut msg, &self.conn.cap_flags)?;
-                    return Ok(());
+                    let ok = OkPacket::read_from(&mut msg, &self.conn.cap_flags)?;
+                    self.conn.server_status = ok.status_flags;
+                    self.conn.timing.complete();
+                    let mut res = QueryResult::from(ok);
+                    res.timing = self.conn.last_timing();
+                    return Ok(res);
                 }
                 _ => {
                     log::warn!("execute statement but returns additional data");
@@ -49,8 +110,12 @@ where
     }
 
     pub async fn qry<Q: Into<String>>(self, qry: Q) -> Result<ResultSet<'a, S, TextColumnValue>> {
+        self.conn.timing.start();
+        self.conn.rollback_pending().await?;
+        self.conn.timing.queued();
         let qry = ComQuery::new(qry);
         self.conn.send_msg(qry, true).await?;
+        self.conn.timing.sent();
         new_result_set(self.conn, None).await
     }
 }
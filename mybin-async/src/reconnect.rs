@@ -0,0 +1,340 @@
+//! connection re-established automatically when broken
+//!
+//! a connection idle for long may be closed by server on wait_timeout
+//! or by network devices in between. the wrapper reconnects with the
+//! same ConnOpts, so authentication, session variables and default
+//! schema are restored.
+//!
+//! a statement failed with IO error may or may not be executed by
+//! server, so only statements allowed by the policy are retried.
+//! statements in an open transaction are never retried, as the
+//! transaction is rolled back by server once the connection is lost.
+use crate::conn::{set_session_var_stmt, Conn, ConnOpts};
+use crate::error::{Error, Result};
+use crate::query::QueryResult;
+use futures::{AsyncRead, AsyncWrite};
+use mybin_core::col::TextColumnValue;
+use mybin_core::resultset::{FromColumnValue, FromRow};
+use serde_derive::*;
+use std::future::Future;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// statements retried after reconnect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryScope {
+    /// reconnect but return the error to caller
+    None,
+    /// SELECT, SHOW, DESCRIBE and EXPLAIN, except SELECT with
+    /// INTO or locking clause. functions with side effects called
+    /// in SELECT are not detected
+    #[default]
+    ReadOnly,
+    /// every statement, only if all statements are idempotent
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconnectPolicy {
+    /// max times a statement is retried
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default)]
+    pub retry: RetryScope,
+    /// ping before use if idle longer than this, broken connection
+    /// found by ping is re-established before the statement is sent
+    #[serde(default)]
+    pub ping_after_idle: Option<Duration>,
+}
+
+fn default_max_retries() -> u32 {
+    1
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_retries: default_max_retries(),
+            retry: RetryScope::default(),
+            ping_after_idle: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn is_retryable(&self, qry: &str) -> bool {
+        match self.retry {
+            RetryScope::None => false,
+            RetryScope::ReadOnly => is_read_only(qry),
+            RetryScope::All => true,
+        }
+    }
+}
+
+/// whether statement only reads data, by its leading keyword
+pub fn is_read_only(qry: &str) -> bool {
+    let qry = skip_comments(qry);
+    let keyword: String = qry
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    match keyword.as_str() {
+        "SHOW" | "DESC" | "DESCRIBE" | "EXPLAIN" => true,
+        "SELECT" => {
+            let upper = qry.to_ascii_uppercase();
+            let words: Vec<&str> = upper.split_whitespace().collect();
+            !words.contains(&"INTO")
+                && !words.windows(2).any(|w| w == ["FOR", "UPDATE"])
+                && !words.windows(2).any(|w| w == ["FOR", "SHARE"])
+                && !words.windows(2).any(|w| w == ["SHARE", "MODE"])
+        }
+        _ => false,
+    }
+}
+
+/// skip leading whitespaces, comments and parentheses
+fn skip_comments(mut qry: &str) -> &str {
+    loop {
+        qry = qry.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+        if let Some(rest) = qry.strip_prefix("/*") {
+            match rest.find("*/") {
+                Some(end) => qry = &rest[end + 2..],
+                None => return "",
+            }
+        } else if qry.starts_with("-- ") || qry.starts_with('#') {
+            match qry.find('\n') {
+                Some(end) => qry = &qry[end + 1..],
+                None => return "",
+            }
+        } else {
+            return qry;
+        }
+    }
+}
+
+/// connection reconnecting on IO error
+///
+/// the connection is established lazily on first use
+pub struct ReconnectingConn<F, S> {
+    opts: ConnOpts,
+    connect: F,
+    policy: ReconnectPolicy,
+    conn: Option<Conn<S>>,
+    last_used: Instant,
+    connects: u64,
+}
+
+impl<F, S> ReconnectingConn<F, S> {
+    pub fn new(opts: ConnOpts, connect: F) -> Self {
+        ReconnectingConn {
+            opts,
+            connect,
+            policy: ReconnectPolicy::default(),
+            conn: None,
+            last_used: Instant::now(),
+            connects: 0,
+        }
+    }
+
+    pub fn policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// times connection is re-established
+    pub fn reconnects(&self) -> u64 {
+        self.connects.saturating_sub(1)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// drop current connection, next use connects again
+    pub fn disconnect(&mut self) {
+        self.conn = None;
+    }
+
+    /// whether the failed statement can be retried, connection
+    /// is dropped on IO error
+    fn retry_on<T>(&mut self, res: &Result<T>, retryable: bool, retries: &mut u32) -> bool {
+        if !matches!(res, Err(Error::IO(_))) {
+            return false;
+        }
+        // status of last completed statement
+        let in_trx = self.conn.take().is_some_and(|conn| conn.in_transaction());
+        if in_trx || !retryable || *retries >= self.policy.max_retries {
+            return false;
+        }
+        *retries += 1;
+        true
+    }
+}
+
+impl<F, Fut, S> ReconnectingConn<F, S>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// current connection, connect if not connected
+    ///
+    /// statements executed on returned connection are not retried
+    pub async fn conn(&mut self) -> Result<&mut Conn<S>> {
+        if let (Some(conn), Some(idle)) = (self.conn.as_mut(), self.policy.ping_after_idle) {
+            if self.last_used.elapsed() >= idle {
+                match conn.ping().await {
+                    Ok(_) => (),
+                    Err(e @ Error::IO(_)) => {
+                        let in_trx = conn.in_transaction();
+                        self.conn = None;
+                        // reconnect silently would lose the transaction
+                        if in_trx {
+                            return Err(e);
+                        }
+                        log::warn!("idle connection broken: {}", e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if self.conn.is_none() {
+            let stream = (self.connect)().await?;
+            let mut conn = Conn::new(stream);
+            conn.handshake(self.opts.clone()).await?;
+            if self.connects > 0 {
+                log::info!("connection re-established");
+            }
+            self.conn = Some(conn);
+            self.connects += 1;
+        }
+        self.last_used = Instant::now();
+        Ok(self.conn.as_mut().unwrap())
+    }
+
+    /// execute a statement that does not return any rows
+    pub async fn exec<Q: Into<String>>(&mut self, qry: Q) -> Result<QueryResult> {
+        let qry = qry.into();
+        let retryable = self.policy.is_retryable(&qry);
+        let mut retries = 0;
+        loop {
+            let res = self.conn().await?.exec(qry.clone()).await;
+            if !self.retry_on(&res, retryable, &mut retries) {
+                return res;
+            }
+            log::warn!("connection broken, retry statement: {}", qry);
+        }
+    }
+
+    /// query a single value, see Conn::query_scalar
+    pub async fn query_scalar<T, Q>(&mut self, qry: Q) -> Result<T>
+    where
+        T: FromColumnValue<TextColumnValue>,
+        Q: Into<String>,
+    {
+        let qry = qry.into();
+        let retryable = self.policy.is_retryable(&qry);
+        let mut retries = 0;
+        loop {
+            let res = self.conn().await?.query_scalar(qry.clone()).await;
+            if !self.retry_on(&res, retryable, &mut retries) {
+                return res;
+            }
+            log::warn!("connection broken, retry statement: {}", qry);
+        }
+    }
+
+    /// query a single row, see Conn::query_one
+    pub async fn query_one<T, Q>(&mut self, qry: Q) -> Result<T>
+    where
+        T: FromRow<TextColumnValue>,
+        Q: Into<String>,
+    {
+        let qry = qry.into();
+        let retryable = self.policy.is_retryable(&qry);
+        let mut retries = 0;
+        loop {
+            let res = self.conn().await?.query_one(qry.clone()).await;
+            if !self.retry_on(&res, retryable, &mut retries) {
+                return res;
+            }
+            log::warn!("connection broken, retry statement: {}", qry);
+        }
+    }
+
+    /// ping server, always retried as it has no side effect
+    pub async fn ping(&mut self) -> Result<()> {
+        let mut retries = 0;
+        loop {
+            let res = self.conn().await?.ping().await;
+            if !self.retry_on(&res, true, &mut retries) {
+                return res;
+            }
+        }
+    }
+
+    /// change default schema, which is kept after reconnect
+    pub async fn init_db<T: Into<String>>(&mut self, db_name: T) -> Result<()> {
+        let db_name = db_name.into();
+        let res = self.conn().await?.init_db(&db_name).await;
+        self.retry_on(&res, false, &mut 0);
+        res?;
+        self.opts.database = db_name;
+        Ok(())
+    }
+
+    /// set session variable, which is set again after reconnect.
+    /// value is quoted as in ConnOpts::session_vars
+    pub async fn set_session_var<T, V>(&mut self, name: T, value: V) -> Result<()>
+    where
+        T: Into<String>,
+        V: Into<String>,
+    {
+        let (name, value) = (name.into(), value.into());
+        let qry = set_session_var_stmt(&name, &value)?;
+        let res = self.conn().await?.exec(qry).await;
+        self.retry_on(&res, false, &mut 0);
+        res?;
+        self.opts.session_vars.retain(|(n, _)| *n != name);
+        self.opts.session_vars.push((name, value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable_statements() {
+        assert!(is_read_only("SELECT 1"));
+        assert!(is_read_only("  select * from t where id = 1"));
+        assert!(is_read_only("/* app */ SELECT 1"));
+        assert!(is_read_only("-- app\nshow tables"));
+        assert!(is_read_only("(SELECT 1) UNION (SELECT 2)"));
+        assert!(is_read_only("EXPLAIN SELECT 1"));
+        assert!(!is_read_only("SELECT * FROM t FOR UPDATE"));
+        assert!(!is_read_only("SELECT * FROM t LOCK IN SHARE MODE"));
+        assert!(!is_read_only("SELECT 1 INTO @a"));
+        assert!(!is_read_only("INSERT INTO t VALUES (1)"));
+        assert!(!is_read_only("WITH c AS (SELECT 1) DELETE FROM t"));
+        assert!(!is_read_only("/* unclosed"));
+        assert!(!is_read_only(""));
+
+        let policy = ReconnectPolicy::default();
+        assert!(policy.is_retryable("SELECT 1"));
+        assert!(!policy.is_retryable("UPDATE t SET a = 1"));
+        let policy = ReconnectPolicy {
+            retry: RetryScope::None,
+            ..Default::default()
+        };
+        assert!(!policy.is_retryable("SELECT 1"));
+        let policy = ReconnectPolicy {
+            retry: RetryScope::All,
+            ..Default::default()
+        };
+        assert!(policy.is_retryable("UPDATE t SET a = 1"));
+    }
+}
@@ -0,0 +1,299 @@
+//! typed results of replication status
+//!
+//! MySQL 8.0.22 renamed master/slave to source/replica in both
+//! statements and column names, mappers accept either naming.
+use crate::error::{Error, Result};
+use mybin_core::col::TextColumnValue;
+use mybin_core::error::Error as CoreError;
+use mybin_core::resultset::{ColumnExtractor, FromColumnValue, RowMapper};
+use rand::Rng;
+
+/// lower bound of generated server_id, configured server_ids are
+/// usually small numbers
+pub const MIN_GENERATED_SERVER_ID: u32 = 0x8000_0000;
+
+/// one row of SHOW REPLICA STATUS, one for each channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaStatus {
+    pub channel_name: String,
+    pub source_host: String,
+    pub source_port: u16,
+    pub source_user: String,
+    pub source_server_id: u32,
+    pub source_uuid: String,
+    /// e.g. Yes, No, Connecting
+    pub io_running: String,
+    pub sql_running: String,
+    pub source_log_file: String,
+    pub read_source_log_pos: u64,
+    pub relay_source_log_file: String,
+    pub exec_source_log_pos: u64,
+    pub relay_log_file: String,
+    pub relay_log_pos: u64,
+    /// None if SQL thread is not running
+    pub seconds_behind_source: Option<u64>,
+    pub last_io_errno: u32,
+    pub last_io_error: String,
+    pub last_sql_errno: u32,
+    pub last_sql_error: String,
+    pub retrieved_gtid_set: String,
+    pub executed_gtid_set: String,
+    pub auto_position: bool,
+}
+
+impl ReplicaStatus {
+    /// both IO and SQL threads are running
+    pub fn is_running(&self) -> bool {
+        self.io_running == "Yes" && self.sql_running == "Yes"
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplicaStatusMapper;
+
+impl RowMapper<TextColumnValue> for ReplicaStatusMapper {
+    type Output = Result<ReplicaStatus>;
+
+    fn map_row(&self, extr: &ColumnExtractor, row: Vec<TextColumnValue>) -> Self::Output {
+        Ok(ReplicaStatus {
+            // channel is supported since 5.7
+            channel_name: get_optional(extr, &row, "Channel_Name")?.unwrap_or_default(),
+            source_host: get_either(extr, &row, "Source_Host", "Master_Host")?,
+            source_port: get_either(extr, &row, "Source_Port", "Master_Port")?,
+            source_user: get_either(extr, &row, "Source_User", "Master_User")?,
+            source_server_id: get_either(extr, &row, "Source_Server_Id", "Master_Server_Id")?,
+            source_uuid: get_either(extr, &row, "Source_UUID", "Master_UUID")?,
+            io_running: get_either(extr, &row, "Replica_IO_Running", "Slave_IO_Running")?,
+            sql_running: get_either(extr, &row, "Replica_SQL_Running", "Slave_SQL_Running")?,
+            source_log_file: get_either(extr, &row, "Source_Log_File", "Master_Log_File")?,
+            read_source_log_pos: get_either(
+                extr,
+                &row,
+                "Read_Source_Log_Pos",
+                "Read_Master_Log_Pos",
+            )?,
+            relay_source_log_file: get_either(
+                extr,
+                &row,
+                "Relay_Source_Log_File",
+                "Relay_Master_Log_File",
+            )?,
+            exec_source_log_pos: get_either(
+                extr,
+                &row,
+                "Exec_Source_Log_Pos",
+                "Exec_Master_Log_Pos",
+            )?,
+            relay_log_file: extr.get_named_col(&row, "Relay_Log_File")?,
+            relay_log_pos: extr.get_named_col(&row, "Relay_Log_Pos")?,
+            seconds_behind_source: get_either(
+                extr,
+                &row,
+                "Seconds_Behind_Source",
+                "Seconds_Behind_Master",
+            )?,
+            last_io_errno: extr.get_named_col(&row, "Last_IO_Errno")?,
+            last_io_error: extr.get_named_col(&row, "Last_IO_Error")?,
+            last_sql_errno: extr.get_named_col(&row, "Last_SQL_Errno")?,
+            last_sql_error: extr.get_named_col(&row, "Last_SQL_Error")?,
+            retrieved_gtid_set: extr.get_named_col(&row, "Retrieved_Gtid_Set")?,
+            executed_gtid_set: extr.get_named_col(&row, "Executed_Gtid_Set")?,
+            auto_position: extr.get_named_col(&row, "Auto_Position")?,
+        })
+    }
+}
+
+/// replica registered on source, from SHOW REPLICAS
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopologyNode {
+    pub server_id: u32,
+    /// empty unless replica is started with --report-host
+    pub host: String,
+    pub port: u16,
+    pub source_id: u32,
+    pub uuid: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TopologyNodeMapper;
+
+impl RowMapper<TextColumnValue> for TopologyNodeMapper {
+    type Output = Result<TopologyNode>;
+
+    fn map_row(&self, extr: &ColumnExtractor, row: Vec<TextColumnValue>) -> Self::Output {
+        let uuid = match get_optional(extr, &row, "Replica_UUID")? {
+            Some(uuid) => Some(uuid),
+            None => get_optional(extr, &row, "Slave_UUID")?,
+        };
+        Ok(TopologyNode {
+            server_id: extr.get_named_col(&row, "Server_id")?,
+            host: extr.get_named_col(&row, "Host")?,
+            port: extr.get_named_col(&row, "Port")?,
+            source_id: get_either(extr, &row, "Source_id", "Master_id")?,
+            uuid,
+        })
+    }
+}
+
+/// state of replication channel in performance_schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationChannel {
+    pub channel_name: String,
+    pub source_uuid: String,
+    /// state of receiver, ON, OFF or CONNECTING
+    pub receiver_state: String,
+    /// state of applier, ON or OFF
+    pub applier_state: Option<String>,
+    pub received_transaction_set: String,
+    pub last_error_number: u32,
+    pub last_error_message: String,
+}
+
+/// SQL to query replication channels
+pub(crate) const REPLICATION_CHANNELS_SQL: &str = "SELECT c.CHANNEL_NAME, c.SOURCE_UUID, \
+     c.SERVICE_STATE, a.SERVICE_STATE, c.RECEIVED_TRANSACTION_SET, \
+     c.LAST_ERROR_NUMBER, c.LAST_ERROR_MESSAGE \
+     FROM performance_schema.replication_connection_status c \
+     LEFT JOIN performance_schema.replication_applier_status a \
+     ON c.CHANNEL_NAME = a.CHANNEL_NAME";
+
+#[derive(Debug)]
+pub struct ReplicationChannelMapper;
+
+impl RowMapper<TextColumnValue> for ReplicationChannelMapper {
+    type Output = Result<ReplicationChannel>;
+
+    fn map_row(&self, extr: &ColumnExtractor, row: Vec<TextColumnValue>) -> Self::Output {
+        Ok(ReplicationChannel {
+            channel_name: extr.get_col(&row, 0)?,
+            source_uuid: extr.get_col(&row, 1)?,
+            receiver_state: extr.get_col(&row, 2)?,
+            applier_state: extr.get_col(&row, 3)?,
+            received_transaction_set: extr.get_col(&row, 4)?,
+            last_error_number: extr.get_col(&row, 5)?,
+            last_error_message: extr.get_col(&row, 6)?,
+        })
+    }
+}
+
+/// get column by new name, or old name on older server
+fn get_either<V>(extr: &ColumnExtractor, row: &[TextColumnValue], new: &str, old: &str) -> Result<V>
+where
+    V: FromColumnValue<TextColumnValue>,
+{
+    match extr.get_named_col(row, new) {
+        Err(CoreError::ColumnNameNotFound(_)) => Ok(extr.get_named_col(row, old)?),
+        other => Ok(other?),
+    }
+}
+
+/// get column which may not exist
+fn get_optional(
+    extr: &ColumnExtractor,
+    row: &[TextColumnValue],
+    name: &str,
+) -> Result<Option<String>> {
+    match extr.get_named_col(row, name) {
+        Err(CoreError::ColumnNameNotFound(_)) => Ok(None),
+        other => Ok(other?),
+    }
+}
+
+/// result of waiting for replica to apply up to a position or gtid set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitOutcome {
+    /// target applied, with number of events waited for, which is
+    /// always 0 if waiting for gtid set
+    Reached(u64),
+    TimedOut,
+    /// replication SQL thread is not running or not configured
+    NotRunning,
+}
+
+impl WaitOutcome {
+    /// parse result of WAIT_FOR_EXECUTED_GTID_SET(), 0 on success
+    /// and 1 on timeout
+    pub fn from_gtid_wait(value: Option<&str>) -> Result<Self> {
+        match value.map(str::trim) {
+            Some("0") => Ok(WaitOutcome::Reached(0)),
+            Some("1") => Ok(WaitOutcome::TimedOut),
+            None => Ok(WaitOutcome::NotRunning),
+            Some(other) => Err(Error::CustomError(format!(
+                "invalid result of WAIT_FOR_EXECUTED_GTID_SET: {}",
+                other
+            ))),
+        }
+    }
+
+    /// parse result of MASTER_POS_WAIT(), number of events waited
+    /// for, -1 on timeout and NULL if SQL thread is not running
+    pub fn from_pos_wait(value: Option<&str>) -> Result<Self> {
+        let value = match value.map(str::trim) {
+            Some(value) => value,
+            None => return Ok(WaitOutcome::NotRunning),
+        };
+        match value.parse::<i64>() {
+            Ok(-1) => Ok(WaitOutcome::TimedOut),
+            Ok(n) if n >= 0 => Ok(WaitOutcome::Reached(n as u64)),
+            _ => Err(Error::CustomError(format!(
+                "invalid result of MASTER_POS_WAIT: {}",
+                value
+            ))),
+        }
+    }
+
+    pub fn is_reached(&self) -> bool {
+        matches!(self, WaitOutcome::Reached(_))
+    }
+}
+
+/// random server_id not in taken ids, None if all attempts collide
+pub fn pick_server_id<R: Rng>(rng: &mut R, taken: &[u32], attempts: usize) -> Option<u32> {
+    (0..attempts)
+        .map(|_| rng.gen_range(MIN_GENERATED_SERVER_ID..=u32::MAX))
+        .find(|id| !taken.contains(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::tests::new_conn;
+
+    #[smol_potat::test]
+    async fn test_replication_status() {
+        let mut conn = new_conn().await;
+        // test server is not a replica
+        let status = conn.show_replica_status().await.unwrap();
+        dbg!(status);
+        let replicas = conn.show_replicas().await.unwrap();
+        dbg!(replicas);
+        let channels = conn.replication_channels().await.unwrap();
+        dbg!(channels);
+        let server_id = conn.unique_server_id().await.unwrap();
+        conn.check_server_id(server_id).await.unwrap();
+    }
+
+    #[test]
+    fn test_pick_server_id() {
+        let mut rng = rand::thread_rng();
+        let id = pick_server_id(&mut rng, &[1, 2], 1).unwrap();
+        assert!(id >= MIN_GENERATED_SERVER_ID);
+        let taken: Vec<_> = (MIN_GENERATED_SERVER_ID..=MIN_GENERATED_SERVER_ID + 1).collect();
+        let mut rng = rand::rngs::mock::StepRng::new(0, 0);
+        assert_eq!(None, pick_server_id(&mut rng, &taken, 3));
+    }
+
+    #[test]
+    fn test_wait_outcome() {
+        let gtid = WaitOutcome::from_gtid_wait;
+        assert_eq!(WaitOutcome::Reached(0), gtid(Some("0")).unwrap());
+        assert_eq!(WaitOutcome::TimedOut, gtid(Some("1")).unwrap());
+        assert_eq!(WaitOutcome::NotRunning, gtid(None).unwrap());
+        assert!(gtid(Some("2")).is_err());
+        let pos = WaitOutcome::from_pos_wait;
+        assert_eq!(WaitOutcome::Reached(12), pos(Some("12")).unwrap());
+        assert_eq!(WaitOutcome::TimedOut, pos(Some("-1")).unwrap());
+        assert_eq!(WaitOutcome::NotRunning, pos(None).unwrap());
+        assert!(pos(Some("-2")).is_err());
+    }
+}
@@ -1,16 +1,19 @@
 use crate::conn::Conn;
 use crate::error::{Error, Result};
+use crate::timing::Timing;
 use bytes::{Buf, Bytes};
 use bytes_parser::my::LenEncInt;
 use bytes_parser::ReadFromBytes;
 use futures::{AsyncRead, AsyncWrite};
 use mybin_core::cmd::ComStmtClose;
 use mybin_core::col::{BinaryColumnValue, ColumnDefinition, ColumnType, TextColumnValue};
-use mybin_core::flag::CapabilityFlags;
+use mybin_core::flag::{CapabilityFlags, StatusFlags};
 use mybin_core::packet::{EofPacket, ErrPacket, OkPacket};
 use mybin_core::resultset::{ColumnExtractor, RowMapper};
-use mybin_core::row::{BinaryRow, TextRow};
+use mybin_core::row::{BinaryRow, TextRow, TextRowParser, TextRowRef};
+use mybin_core::value::{ColumnNames, NamedRow, Row, TextValueDecoder};
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 /// construct a new result set from given connection
 ///
@@ -25,8 +28,10 @@ where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     let mut msg = conn.recv_msg().await?;
-    let col_cnt = parse_col_cnt_packet(&mut msg, &conn.cap_flags)?;
+    conn.timing.first_byte();
+    let col_cnt = parse_col_cnt_packet(&mut msg, &conn.cap_flags, &mut conn.server_status)?;
     if col_cnt == 0 {
+        conn.timing.complete();
         return Ok(ResultSet::empty(conn, stmt_id));
     }
     let mut col_defs = Vec::with_capacity(col_cnt as usize);
@@ -39,7 +44,8 @@ where
     if !conn.cap_flags.contains(CapabilityFlags::DEPRECATE_EOF) {
         // additional EOF if not deprecate
         let mut msg = conn.recv_msg().await?;
-        EofPacket::read_from(&mut msg, &conn.cap_flags)?;
+        let eof = EofPacket::read_from(&mut msg, &conn.cap_flags)?;
+        conn.server_status = eof.status_flags;
     }
     // incoming rows
     Ok(ResultSet::new(conn, col_defs, stmt_id))
@@ -47,14 +53,19 @@ where
 
 /// parse column count packet
 /// if returns 0, means the response is completed
-fn parse_col_cnt_packet(msg: &mut Bytes, cap_flags: &CapabilityFlags) -> Result<u32> {
+fn parse_col_cnt_packet(
+    msg: &mut Bytes,
+    cap_flags: &CapabilityFlags,
+    server_status: &mut StatusFlags,
+) -> Result<u32> {
     match msg[0] {
         0xff => {
             let err = ErrPacket::read_from(msg, cap_flags, true)?;
             Err(err.into())
         }
         0x00 => {
-            OkPacket::read_from(msg, cap_flags)?;
+            let ok = OkPacket::read_from(msg, cap_flags)?;
+            *server_status = ok.status_flags;
             return Ok(0);
         }
         _ => {
@@ -77,6 +88,11 @@ pub struct ResultSet<'s, S: 's, Q> {
     // only used for binary columns
     col_types: Vec<ColumnType>,
     stmt_id: Option<u32>,
+    // last row packet and parser, only used for borrowed text rows
+    row_packet: Bytes,
+    row_parser: TextRowParser,
+    // only used for text values
+    text_decoder: TextValueDecoder,
     _marker: PhantomData<Q>,
 }
 
@@ -90,6 +106,9 @@ impl<'s, S: 's, Q> ResultSet<'s, S, Q> {
             completed: true,
             col_types: vec![],
             stmt_id,
+            row_packet: Bytes::new(),
+            row_parser: TextRowParser::new(),
+            text_decoder: TextValueDecoder::new(&[]),
             _marker: PhantomData,
         }
     }
@@ -100,21 +119,35 @@ impl<'s, S: 's, Q> ResultSet<'s, S, Q> {
         stmt_id: Option<u32>,
     ) -> Self {
         let col_types = col_defs.iter().map(|d| d.col_type).collect();
+        let text_decoder = TextValueDecoder::new(&col_defs);
         Self {
             conn,
             col_defs,
             completed: false,
             col_types,
             stmt_id,
+            row_packet: Bytes::new(),
+            row_parser: TextRowParser::new(),
+            text_decoder,
             _marker: PhantomData,
         }
     }
 
+    /// timing of the query, complete is set once all rows are read
+    pub fn timing(&self) -> Timing {
+        self.conn.last_timing()
+    }
+
     /// create a column extractor base on column definitions
     pub fn extractor(&self) -> ColumnExtractor {
         ColumnExtractor::new(&self.col_defs)
     }
 
+    /// column positions by name, shared by named rows
+    pub fn column_names(&self) -> Arc<ColumnNames> {
+        Arc::new(ColumnNames::from_col_defs(&self.col_defs))
+    }
+
     pub fn map_rows<M>(self, mapper: M) -> MapperResultSet<'s, S, M, Q>
     where
         M: RowMapper<Q> + Unpin,
@@ -157,6 +190,26 @@ where
         Ok(first)
     }
 
+    /// returns the only row of result set
+    ///
+    /// fails if result set is empty or has more than one row,
+    /// all rows are consumed in either case
+    pub async fn one(mut self) -> Result<Vec<Q>> {
+        let mut one = None;
+        let mut cnt = 0;
+        while let Some(row) = self.next_row().await? {
+            if one.is_none() {
+                one.replace(row);
+            }
+            cnt += 1;
+        }
+        match (one, cnt) {
+            (Some(row), 1) => Ok(row),
+            (None, _) => Err(Error::EmptyResultSet),
+            (_, cnt) => Err(Error::TooManyRows(cnt)),
+        }
+    }
+
     pub async fn count(mut self) -> Result<usize> {
         let mut cnt = 0;
         while let Some(_) = self.next_row().await? {
@@ -166,6 +219,46 @@ where
     }
 
     pub async fn next_row(&mut self) -> Result<Option<Vec<Q>>> {
+        match self.next_row_packet().await? {
+            Some(mut msg) => Ok(Some(self.read_row(&mut msg)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// returns next row of unified values, same for text and
+    /// binary protocols
+    pub async fn next_values(&mut self) -> Result<Option<Row>> {
+        match self.next_row_packet().await? {
+            Some(mut msg) => Ok(Some(self.read_values(&mut msg)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn all_values(mut self) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_values().await? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// rows of unified values accessible by column name
+    pub async fn all_named(mut self) -> Result<Vec<NamedRow>> {
+        let names = self.column_names();
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_values().await? {
+            rows.push(NamedRow::new(Arc::clone(&names), row));
+        }
+        Ok(rows)
+    }
+}
+
+impl<'s, S: 's, Q> ResultSet<'s, S, Q>
+where
+    S: AsyncRead + Unpin,
+{
+    /// returns packet of next row, None if result set is completed
+    async fn next_row_packet(&mut self) -> Result<Option<Bytes>> {
         if self.completed {
             return Ok(None);
         }
@@ -179,7 +272,9 @@ where
             0xfe if msg.remaining() <= 0xffffff => {
                 if self.conn.cap_flags.contains(CapabilityFlags::DEPRECATE_EOF) {
                     match OkPacket::read_from(&mut msg, &self.conn.cap_flags) {
-                        Ok(_) => {
+                        Ok(ok) => {
+                            self.conn.server_status = ok.status_flags;
+                            self.conn.timing.complete();
                             self.completed = true;
                             return Ok(None);
                         }
@@ -190,7 +285,9 @@ where
                     }
                 }
                 match EofPacket::read_from(&mut msg, &self.conn.cap_flags) {
-                    Ok(_) => {
+                    Ok(eof) => {
+                        self.conn.server_status = eof.status_flags;
+                        self.conn.timing.complete();
                         self.completed = true;
                         Ok(None)
                     }
@@ -200,10 +297,29 @@ where
                     }
                 }
             }
-            _ => {
-                let r = self.read_row(&mut msg)?;
-                Ok(Some(r))
+            _ => Ok(Some(msg)),
+        }
+    }
+}
+
+impl<'s, S: 's> ResultSet<'s, S, TextColumnValue>
+where
+    S: AsyncRead + Unpin,
+{
+    /// returns next row borrowing cells from packet buffer
+    ///
+    /// the row is valid until next read on result set, it avoids
+    /// allocation per cell for dump-style queries
+    pub async fn next_row_ref(&mut self) -> Result<Option<TextRowRef<'_>>> {
+        match self.next_row_packet().await? {
+            Some(msg) => {
+                self.row_packet = msg;
+                let row = self
+                    .row_parser
+                    .parse(&self.row_packet, self.col_defs.len())?;
+                Ok(Some(row))
             }
+            None => Ok(None),
         }
     }
 }
@@ -225,6 +341,8 @@ pub trait RowReader {
     type Column;
 
     fn read_row(&self, input: &mut Bytes) -> Result<Vec<Self::Column>>;
+
+    fn read_values(&self, input: &mut Bytes) -> Result<Row>;
 }
 
 impl<'s, S> RowReader for ResultSet<'s, S, BinaryColumnValue> {
@@ -233,6 +351,11 @@ impl<'s, S> RowReader for ResultSet<'s, S, BinaryColumnValue> {
         let r = BinaryRow::read_from(input, &self.col_types)?;
         Ok(r.0)
     }
+
+    fn read_values(&self, input: &mut Bytes) -> Result<Row> {
+        let r = BinaryRow::read_from(input, &self.col_types)?;
+        Ok(Row::from_binary(r, &self.col_defs)?)
+    }
 }
 
 impl<'s, S> RowReader for ResultSet<'s, S, TextColumnValue> {
@@ -241,6 +364,11 @@ impl<'s, S> RowReader for ResultSet<'s, S, TextColumnValue> {
         let r = TextRow::read_from(input, self.col_defs.len())?;
         Ok(r.0)
     }
+
+    fn read_values(&self, input: &mut Bytes) -> Result<Row> {
+        let r = TextRow::read_from(input, self.col_defs.len())?;
+        Ok(self.text_decoder.decode_row(r)?)
+    }
 }
 
 pub struct MapperResultSet<'s, S: 's, M, Q> {
@@ -341,6 +469,20 @@ mod tests {
         assert_eq!(1, count_rs.unwrap());
     }
 
+    #[smol_potat::test]
+    async fn test_result_set_next_row_ref() {
+        let mut conn = new_conn().await;
+        let mut rs = conn.query().qry("select 1, null, 'abc'").await.unwrap();
+        let mut cnt = 0;
+        while let Some(row) = rs.next_row_ref().await.unwrap() {
+            assert_eq!(Some(Some(&b"1"[..])), row.get(0));
+            assert_eq!(Some(None), row.get(1));
+            assert_eq!(Some(Some(&b"abc"[..])), row.get(2));
+            cnt += 1;
+        }
+        assert_eq!(1, cnt);
+    }
+
     #[smol_potat::test]
     async fn test_result_set_ops_mapper() {
         let mut conn = new_conn().await;
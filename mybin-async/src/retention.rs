@@ -0,0 +1,215 @@
+//! watchdog of binlog retention
+//!
+//! binlogs are purged by master once expired, regardless of whether
+//! consumers have read them. a consumer lagging behind retention can
+//! not resume from its checkpoint. the watchdog compares age of
+//! checkpoint with retention settings and list of binlog files, and
+//! reports to observers before the checkpoint is purged.
+use crate::binlog::BinlogFile;
+use crate::conn::Conn;
+use crate::error::Result;
+use futures::{AsyncRead, AsyncWrite};
+use mybin_core::binlog::checkpoint::Checkpoint;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RetentionLevel {
+    Ok,
+    /// age of checkpoint exceeds warning ratio of retention
+    Warning,
+    /// age of checkpoint exceeds critical ratio of retention
+    Critical,
+    /// binlog file of checkpoint no longer exists
+    Purged,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionStatus {
+    pub level: RetentionLevel,
+    pub earliest_file: Option<String>,
+    /// files before the file of checkpoint, which are purged first
+    pub files_ahead: usize,
+    pub bytes_ahead: u64,
+    /// time since the event of checkpoint
+    pub age: Duration,
+    /// None if binlogs never expire
+    pub retention: Option<Duration>,
+}
+
+impl RetentionStatus {
+    /// time left before binlog of checkpoint may expire
+    pub fn headroom(&self) -> Option<Duration> {
+        self.retention.map(|r| r.saturating_sub(self.age))
+    }
+}
+
+pub trait RetentionObserver: Send + Sync {
+    fn on_check(&self, _ckpt: &Checkpoint, _status: &RetentionStatus) {}
+}
+
+/// checks retention of checkpoint, called periodically by consumer
+#[derive(Clone)]
+pub struct RetentionWatchdog {
+    warning_ratio: f64,
+    critical_ratio: f64,
+    observers: Vec<Arc<dyn RetentionObserver>>,
+}
+
+impl Default for RetentionWatchdog {
+    fn default() -> Self {
+        RetentionWatchdog {
+            warning_ratio: 0.5,
+            critical_ratio: 0.8,
+            observers: vec![],
+        }
+    }
+}
+
+impl fmt::Debug for RetentionWatchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetentionWatchdog")
+            .field("warning_ratio", &self.warning_ratio)
+            .field("critical_ratio", &self.critical_ratio)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl RetentionWatchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ratios of checkpoint age to retention, 0.5 and 0.8 by default
+    pub fn ratios(mut self, warning: f64, critical: f64) -> Self {
+        self.warning_ratio = warning;
+        self.critical_ratio = critical;
+        self
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn RetentionObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// check checkpoint against current binlog files of master,
+    /// checkpoint_ts is the timestamp of event at checkpoint
+    pub async fn check<S>(
+        &self,
+        conn: &mut Conn<S>,
+        ckpt: &Checkpoint,
+        checkpoint_ts: u32,
+    ) -> Result<RetentionStatus>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let files = conn.binlog_files().await?;
+        let retention = conn.binlog_retention().await?.retention();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let age = Duration::from_secs(now.saturating_sub(checkpoint_ts as u64));
+        let status = self.evaluate(&files, ckpt, age, retention);
+        match status.level {
+            RetentionLevel::Ok => (),
+            RetentionLevel::Warning => log::warn!(
+                "checkpoint {}:{} is {:?} old, retention is {:?}",
+                ckpt.binlog_filename,
+                ckpt.binlog_pos,
+                status.age,
+                status.retention
+            ),
+            RetentionLevel::Critical | RetentionLevel::Purged => log::error!(
+                "checkpoint {}:{} is {:?}, {:?} old, retention is {:?}",
+                ckpt.binlog_filename,
+                ckpt.binlog_pos,
+                status.level,
+                status.age,
+                status.retention
+            ),
+        }
+        for observer in &self.observers {
+            observer.on_check(ckpt, &status);
+        }
+        Ok(status)
+    }
+
+    /// files are listed earliest first, as SHOW BINARY LOGS does
+    pub fn evaluate(
+        &self,
+        files: &[BinlogFile],
+        ckpt: &Checkpoint,
+        age: Duration,
+        retention: Option<Duration>,
+    ) -> RetentionStatus {
+        let idx = files
+            .iter()
+            .position(|f| f.filename == ckpt.binlog_filename);
+        let (files_ahead, bytes_ahead) = match idx {
+            Some(idx) => (idx, files[..idx].iter().map(|f| f.size).sum()),
+            None => (0, 0),
+        };
+        let level = match (idx, retention) {
+            (None, _) => RetentionLevel::Purged,
+            (Some(_), None) => RetentionLevel::Ok,
+            (Some(_), Some(retention)) => {
+                let ratio = age.as_secs_f64() / retention.as_secs_f64().max(1.0);
+                if ratio >= self.critical_ratio {
+                    RetentionLevel::Critical
+                } else if ratio >= self.warning_ratio {
+                    RetentionLevel::Warning
+                } else {
+                    RetentionLevel::Ok
+                }
+            }
+        };
+        RetentionStatus {
+            level,
+            earliest_file: files.first().map(|f| f.filename.clone()),
+            files_ahead,
+            bytes_ahead,
+            age,
+            retention,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_watchdog() {
+        let files: Vec<_> = (1..=3)
+            .map(|i| BinlogFile {
+                filename: format!("mysql-bin.00000{}", i),
+                size: 100,
+            })
+            .collect();
+        let ckpt = |name: &str| Checkpoint {
+            binlog_filename: name.to_owned(),
+            binlog_pos: 4,
+            executed_gtid_set: None,
+        };
+        let wd = RetentionWatchdog::new();
+        let day = Duration::from_secs(86400);
+        let status = wd.evaluate(&files, &ckpt("mysql-bin.000003"), day, Some(day * 7));
+        assert_eq!(RetentionLevel::Ok, status.level);
+        assert_eq!(2, status.files_ahead);
+        assert_eq!(200, status.bytes_ahead);
+        assert_eq!(Some(day * 6), status.headroom());
+        let status = wd.evaluate(&files, &ckpt("mysql-bin.000001"), day * 4, Some(day * 7));
+        assert_eq!(RetentionLevel::Warning, status.level);
+        let status = wd.evaluate(&files, &ckpt("mysql-bin.000001"), day * 6, Some(day * 7));
+        assert_eq!(RetentionLevel::Critical, status.level);
+        // never expire, but purged manually
+        let status = wd.evaluate(&files, &ckpt("mysql-bin.000001"), day * 60, None);
+        assert_eq!(RetentionLevel::Ok, status.level);
+        let status = wd.evaluate(&files[1..], &ckpt("mysql-bin.000001"), day, None);
+        assert_eq!(RetentionLevel::Purged, status.level);
+        assert_eq!(Some("mysql-bin.000002"), status.earliest_file.as_deref());
+    }
+}
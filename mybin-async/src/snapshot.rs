@@ -0,0 +1,321 @@
+//! initial snapshot followed by binlog stream
+//!
+//! the typical bootstrap of change data capture:
+//! 1. FLUSH TABLES WITH READ LOCK to block writes.
+//! 2. START TRANSACTION WITH CONSISTENT SNAPSHOT.
+//! 3. record binlog position and executed gtid set.
+//! 4. UNLOCK TABLES, writes are resumed.
+//! 5. scan tables in the snapshot transaction.
+//! 6. COMMIT and request binlog stream from recorded position.
+use crate::binlog::Binlog;
+use crate::config::SnapshotConfig;
+use crate::conn::Conn;
+use crate::error::{Error, Result};
+use crate::resultset::ResultSet;
+use crate::trx::PendingRollback;
+use futures::{AsyncRead, AsyncWrite};
+use mybin_core::backfill::{quote_ident, BackfillPlan};
+use mybin_core::col::TextColumnValue;
+use mybin_core::value::{Row, Value};
+
+/// builder of snapshot
+#[derive(Debug)]
+pub struct SnapshotAndFollow<'a, S> {
+    conn: &'a mut Conn<S>,
+    tables: Vec<(String, String)>,
+    lock_tables: bool,
+}
+
+impl<'a, S> SnapshotAndFollow<'a, S> {
+    pub fn new(conn: &'a mut Conn<S>) -> Self {
+        SnapshotAndFollow {
+            conn,
+            tables: vec![],
+            lock_tables: true,
+        }
+    }
+
+    pub fn table<D: Into<String>, T: Into<String>>(mut self, db: D, tbl: T) -> Self {
+        self.tables.push((db.into(), tbl.into()));
+        self
+    }
+
+    /// add tables and options of configuration
+    pub fn config(mut self, config: &SnapshotConfig) -> Result<Self> {
+        for (db, tbl) in config.table_names()? {
+            self = self.table(db, tbl);
+        }
+        Ok(self.lock_tables(config.lock_tables))
+    }
+
+    /// whether to use FLUSH TABLES WITH READ LOCK, enabled by default
+    ///
+    /// it requires RELOAD privilege. if disabled, writes committed between
+    /// start of snapshot and reading binlog position may be delivered
+    /// twice, by both snapshot and binlog stream.
+    pub fn lock_tables(mut self, lock_tables: bool) -> Self {
+        self.lock_tables = lock_tables;
+        self
+    }
+}
+
+impl<'a, S> SnapshotAndFollow<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// take consistent snapshot and record binlog position
+    pub async fn snapshot(mut self) -> Result<Snapshot<'a, S>> {
+        if self.lock_tables {
+            self.conn.exec("FLUSH TABLES WITH READ LOCK").await?;
+        }
+        let position = match self.start_snapshot().await {
+            Ok(position) => position,
+            Err(e) => {
+                if self.lock_tables {
+                    // keep original error
+                    let _ = self.conn.exec("UNLOCK TABLES").await;
+                }
+                return Err(e);
+            }
+        };
+        if self.lock_tables {
+            self.conn.exec("UNLOCK TABLES").await?;
+        }
+        log::debug!("snapshot taken at {:?}", position);
+        Ok(Snapshot {
+            conn: Some(self.conn),
+            tables: self.tables,
+            position,
+        })
+    }
+
+    async fn start_snapshot(&mut self) -> Result<SnapshotPosition> {
+        self.conn
+            .exec("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY")
+            .await?;
+        let status = self
+            .conn
+            .show_master_status()
+            .await?
+            .ok_or_else(|| Error::CustomError("binlog is not enabled on server".to_owned()))?;
+        Ok(SnapshotPosition {
+            binlog_filename: status.file,
+            binlog_pos: status.position,
+            executed_gtid_set: status.executed_gtid_set.filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+/// binlog position where snapshot is taken
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotPosition {
+    pub binlog_filename: String,
+    pub binlog_pos: u64,
+    pub executed_gtid_set: Option<String>,
+}
+
+/// snapshot transaction
+///
+/// if dropped before follow, the transaction is rolled back
+/// before next command on the connection.
+#[derive(Debug)]
+pub struct Snapshot<'a, S> {
+    conn: Option<&'a mut Conn<S>>,
+    tables: Vec<(String, String)>,
+    position: SnapshotPosition,
+}
+
+impl<'a, S> Snapshot<'a, S> {
+    pub fn position(&self) -> &SnapshotPosition {
+        &self.position
+    }
+
+    /// tables configured to scan
+    pub fn tables(&self) -> &[(String, String)] {
+        &self.tables
+    }
+}
+
+impl<'a, S> Snapshot<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// read all rows of table in snapshot
+    pub async fn scan(&mut self, db: &str, tbl: &str) -> Result<ResultSet<'_, S, TextColumnValue>> {
+        let conn = self.conn.as_mut().expect("snapshot connection");
+        conn.query()
+            .qry(format!(
+                "SELECT * FROM {}.{}",
+                quote_ident(db),
+                quote_ident(tbl)
+            ))
+            .await
+    }
+
+    /// plan of chunked scan by column definitions of table
+    pub async fn backfill_plan(&mut self, db: &str, tbl: &str) -> Result<BackfillPlan> {
+        let conn = self.conn.as_mut().expect("snapshot connection");
+        backfill_plan(conn, db, tbl).await
+    }
+
+    /// read table in chunks of primary key order
+    pub fn backfill(&mut self, plan: BackfillPlan) -> Backfill<'_, S> {
+        Backfill::new(self.conn.as_mut().expect("snapshot connection"), plan)
+    }
+
+    /// end snapshot and returns binlog builder starting at snapshot position
+    ///
+    /// other options such as server_id could be set on returned builder
+    /// before requesting stream.
+    pub async fn follow(mut self) -> Result<Binlog<'a, S>> {
+        let conn = self.conn.take().expect("snapshot connection");
+        conn.exec("COMMIT").await?;
+        let binlog = conn
+            .binlog()
+            .binlog_filename(self.position.binlog_filename.clone())
+            .binlog_pos(self.position.binlog_pos);
+        Ok(binlog)
+    }
+}
+
+impl<'a, S> Drop for Snapshot<'a, S> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            log::warn!("snapshot dropped without follow");
+            conn.pending_rollback = Some(PendingRollback::All);
+        }
+    }
+}
+
+/// fails if table does not have primary key
+pub async fn backfill_plan<S>(conn: &mut Conn<S>, db: &str, tbl: &str) -> Result<BackfillPlan>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let rs = conn
+        .query()
+        .qry(format!(
+            "SELECT * FROM {}.{} LIMIT 0",
+            quote_ident(db),
+            quote_ident(tbl)
+        ))
+        .await?;
+    let col_defs = rs.col_defs.clone();
+    rs.count().await?;
+    BackfillPlan::from_col_defs(&col_defs)
+        .ok_or_else(|| Error::CustomError(format!("table `{}`.`{}` has no primary key", db, tbl)))
+}
+
+/// chunked scan of table
+///
+/// rows are returned as unified rows, same as rows decoded from
+/// binlog. key of last row is kept so scan can be resumed with
+/// a new connection.
+#[derive(Debug)]
+pub struct Backfill<'a, S> {
+    conn: &'a mut Conn<S>,
+    plan: BackfillPlan,
+    last_key: Option<Vec<Value>>,
+    completed: bool,
+}
+
+impl<'a, S> Backfill<'a, S> {
+    pub fn new(conn: &'a mut Conn<S>, plan: BackfillPlan) -> Self {
+        Backfill {
+            conn,
+            plan,
+            last_key: None,
+            completed: false,
+        }
+    }
+
+    /// start after given key
+    pub fn resume_after(mut self, key: Vec<Value>) -> Self {
+        self.last_key = Some(key);
+        self
+    }
+
+    pub fn plan(&self) -> &BackfillPlan {
+        &self.plan
+    }
+
+    /// key of last returned row
+    pub fn last_key(&self) -> Option<&[Value]> {
+        self.last_key.as_deref()
+    }
+}
+
+impl<'a, S> Backfill<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// returns None if all rows are read
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<Row>>> {
+        if self.completed {
+            return Ok(None);
+        }
+        let qry = self.plan.chunk_query(self.last_key.as_deref())?;
+        log::debug!("backfill chunk: {}", qry);
+        let rows = self.conn.query().qry(qry).await?.all_values().await?;
+        if rows.len() < self.plan.chunk_size {
+            self.completed = true;
+        }
+        match rows.last() {
+            Some(row) => self.last_key = Some(self.plan.key_of(row)?),
+            None => return Ok(None),
+        }
+        Ok(Some(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conn::tests::new_conn;
+
+    #[smol_potat::test]
+    async fn test_snapshot_and_follow() {
+        let mut conn = new_conn().await;
+        conn.exec("CREATE DATABASE IF NOT EXISTS bintest1")
+            .await
+            .unwrap();
+        conn.exec("DROP TABLE IF EXISTS bintest1.snapshot1")
+            .await
+            .unwrap();
+        conn.exec("CREATE TABLE bintest1.snapshot1 (id INT PRIMARY KEY)")
+            .await
+            .unwrap();
+        conn.exec("INSERT INTO bintest1.snapshot1 VALUES (1), (2)")
+            .await
+            .unwrap();
+        let mut snapshot = conn
+            .snapshot_and_follow()
+            .table("bintest1", "snapshot1")
+            .snapshot()
+            .await
+            .unwrap();
+        dbg!(snapshot.position());
+        let tables = snapshot.tables().to_vec();
+        for (db, tbl) in &tables {
+            let cnt = snapshot.scan(db, tbl).await.unwrap().count().await.unwrap();
+            assert_eq!(2, cnt);
+            let plan = snapshot.backfill_plan(db, tbl).await.unwrap().chunk_size(1);
+            let mut backfill = snapshot.backfill(plan);
+            let mut ids = vec![];
+            while let Some(rows) = backfill.next_chunk().await.unwrap() {
+                ids.extend(rows.iter().map(|r| r.get(0).unwrap().as_i64().unwrap()));
+            }
+            assert_eq!(vec![1, 2], ids);
+        }
+        let mut stream = snapshot
+            .follow()
+            .await
+            .unwrap()
+            .non_block(true)
+            .request_stream()
+            .await
+            .unwrap();
+        while let Some(event) = stream.next_event().await.unwrap() {
+            dbg!(event);
+        }
+    }
+}
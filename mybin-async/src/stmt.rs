@@ -3,11 +3,14 @@ use crate::error::{Error, Needed, Result};
 use crate::resultset::{new_result_set, ResultSet};
 use bytes::{Buf, Bytes};
 use bytes_parser::ReadFromBytes;
-use futures::{AsyncRead, AsyncWrite};
-use mybin_core::cmd::{ComStmtClose, ComStmtExecute, ComStmtPrepare, StmtPrepareOk};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite};
+use mybin_core::cmd::{
+    ComStmtClose, ComStmtExecute, ComStmtPrepare, ComStmtReset, ComStmtSendLongData, StmtPrepareOk,
+};
 use mybin_core::col::{BinaryColumnValue, ColumnDefinition};
 use mybin_core::flag::CapabilityFlags;
 use mybin_core::packet::{EofPacket, ErrPacket, OkPacket};
+use mybin_core::resp::ComResponse;
 use mybin_core::stmt::StmtColumnValue;
 
 #[derive(Debug)]
@@ -24,6 +27,7 @@ where
     }
 
     pub async fn prepare<Q: Into<String>>(self, qry: Q) -> Result<PreparedStmt<'a, S>> {
+        self.conn.rollback_pending().await?;
         let cmd = ComStmtPrepare::new(qry);
         self.conn.send_msg(cmd, true).await?;
         let mut msg = self.conn.recv_msg().await?;
@@ -78,6 +82,7 @@ where
             col_defs,
             param_defs,
             n_warnings: ok.n_warnings,
+            long_data_params: vec![],
         })
     }
 }
@@ -89,24 +94,99 @@ pub struct PreparedStmt<'s, S> {
     pub col_defs: Vec<ColumnDefinition>,
     pub param_defs: Vec<ColumnDefinition>,
     pub n_warnings: u16,
+    // parameters sent as long data since last execution
+    long_data_params: Vec<u16>,
 }
 
+/// size of each chunk sent by send_long_data
+pub const LONG_DATA_CHUNK_SIZE: usize = 1024 * 1024;
+
 impl<'s, S> PreparedStmt<'s, S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
+    /// stream value of parameter to server in chunks before execution
+    ///
+    /// each chunk is sent in its own packet and fits max_allowed_packet,
+    /// so the value can exceed the limit. the parameter passed to next execution only
+    /// provides the type, e.g. `StmtColumnValue::new_blob(vec![])`.
+    /// returns number of bytes sent.
+    ///
+    /// if reading from reader fails, data already sent is discarded
+    /// by reset(), so the statement can be reused.
+    pub async fn send_long_data<R>(&mut self, param_id: u16, mut reader: R) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if param_id as usize >= self.param_defs.len() {
+            return Err(Error::CustomError(format!(
+                "parameter index {} out of bound {}",
+                param_id,
+                self.param_defs.len()
+            )));
+        }
+        // command header takes 7 bytes
+        let chunk_size = match self.conn.max_allowed_packet {
+            Some(allowed) => LONG_DATA_CHUNK_SIZE.min((allowed as usize).saturating_sub(7).max(1)),
+            None => LONG_DATA_CHUNK_SIZE,
+        };
+        let mut buf = vec![0u8; chunk_size];
+        let mut total = 0u64;
+        loop {
+            let mut filled = 0;
+            while filled < buf.len() {
+                let n = match reader.read(&mut buf[filled..]).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        if total > 0 || !self.long_data_params.is_empty() {
+                            self.reset().await?;
+                        }
+                        return Err(e.into());
+                    }
+                };
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            // at least one packet, so server treats the parameter as long data
+            if filled > 0 || total == 0 {
+                let data = Bytes::copy_from_slice(&buf[..filled]);
+                let cmd = ComStmtSendLongData::new(self.stmt_id, param_id, data);
+                // no response from server
+                self.conn.send_msg(cmd, true).await?;
+                total += filled as u64;
+            }
+            if filled < buf.len() {
+                break;
+            }
+        }
+        if !self.long_data_params.contains(&param_id) {
+            self.long_data_params.push(param_id);
+        }
+        Ok(total)
+    }
+
     pub async fn exec(&mut self, params: Vec<StmtColumnValue>) -> Result<()> {
-        let cmd = ComStmtExecute::single(self.stmt_id, params);
+        // long data is reset by server after execution
+        let long_data_params = std::mem::take(&mut self.long_data_params);
+        let cmd = ComStmtExecute::single(self.stmt_id, params).long_data_params(long_data_params);
+        self.conn.timing.start();
+        self.conn.timing.queued();
         self.conn.send_msg(cmd, true).await?;
+        self.conn.timing.sent();
         loop {
             let mut msg = self.conn.recv_msg().await?;
+            self.conn.timing.first_byte();
             match msg[0] {
                 0xff => {
                     let err = ErrPacket::read_from(&mut msg, &self.conn.cap_flags, true)?;
                     return Err(err.into());
                 }
                 0x00 => {
-                    OkPacket::read_from(&mut msg, &self.conn.cap_flags)?;
+                    let ok = OkPacket::read_from(&mut msg, &self.conn.cap_flags)?;
+                    self.conn.server_status = ok.status_flags;
+                    self.conn.timing.complete();
                     // todo: handle session state changes and provide close handler
                     return Ok(());
                 }
@@ -117,6 +197,22 @@ where
         }
     }
 
+    /// discard long data sent since last execution, and close
+    /// cursor if opened, the statement is kept prepared
+    pub async fn reset(&mut self) -> Result<()> {
+        let cmd = ComStmtReset::new(self.stmt_id);
+        self.conn.send_msg(cmd, true).await?;
+        self.long_data_params.clear();
+        let mut msg = self.conn.recv_msg().await?;
+        match ComResponse::read_from(&mut msg, &self.conn.cap_flags)? {
+            ComResponse::Ok(ok) => {
+                self.conn.server_status = ok.status_flags;
+                Ok(())
+            }
+            ComResponse::Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn exec_close(mut self, params: Vec<StmtColumnValue>) -> Result<()> {
         match self.exec(params).await {
             Ok(_) => {
@@ -151,8 +247,12 @@ where
         self,
         params: Vec<StmtColumnValue>,
     ) -> Result<ResultSet<'s, S, BinaryColumnValue>> {
-        let cmd = ComStmtExecute::single(self.stmt_id, params);
+        let cmd =
+            ComStmtExecute::single(self.stmt_id, params).long_data_params(self.long_data_params);
+        self.conn.timing.start();
+        self.conn.timing.queued();
         self.conn.send_msg(cmd, true).await?;
+        self.conn.timing.sent();
         let rs = new_result_set(self.conn, Some(self.stmt_id)).await?;
         Ok(rs)
     }
@@ -223,6 +323,94 @@ mod tests {
         assert_eq!(3, count);
     }
 
+    #[smol_potat::test]
+    async fn test_stmt_send_long_data() {
+        let mut conn = new_conn().await;
+        conn.exec("create database if not exists bintest1")
+            .await
+            .unwrap();
+        conn.init_db("bintest1").await.unwrap();
+        conn.exec("drop table if exists long_data").await.unwrap();
+        conn.exec("create table long_data (id int, data longblob)")
+            .await
+            .unwrap();
+        let data = vec![1u8; super::LONG_DATA_CHUNK_SIZE * 2 + 10];
+        let mut stmt = conn
+            .stmt()
+            .prepare("insert into long_data (id, data) values (?, ?)")
+            .await
+            .unwrap();
+        let sent = stmt
+            .send_long_data(1, futures::io::Cursor::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(data.len() as u64, sent);
+        stmt.exec(vec![
+            StmtColumnValue::new_int(1),
+            StmtColumnValue::new_blob(vec![]),
+        ])
+        .await
+        .unwrap();
+        stmt.close().await.unwrap();
+        let len: u64 = conn
+            .query_scalar("select length(data) from long_data where id = 1")
+            .await
+            .unwrap();
+        assert_eq!(data.len() as u64, len);
+    }
+
+    #[smol_potat::test]
+    async fn test_stmt_reset_after_failed_long_data() {
+        let mut conn = new_conn().await;
+        conn.exec("create database if not exists bintest1")
+            .await
+            .unwrap();
+        conn.init_db("bintest1").await.unwrap();
+        conn.exec("drop table if exists long_data_reset")
+            .await
+            .unwrap();
+        conn.exec("create table long_data_reset (id int, data longblob)")
+            .await
+            .unwrap();
+        let mut stmt = conn
+            .stmt()
+            .prepare("insert into long_data_reset (id, data) values (?, ?)")
+            .await
+            .unwrap();
+        // reader fails after first chunk
+        let data = futures::io::Cursor::new(vec![1u8; super::LONG_DATA_CHUNK_SIZE]);
+        let failing = futures::io::AsyncReadExt::chain(data, FailingReader);
+        assert!(stmt.send_long_data(1, failing).await.is_err());
+        stmt.send_long_data(1, futures::io::Cursor::new(b"abc".to_vec()))
+            .await
+            .unwrap();
+        stmt.exec(vec![
+            StmtColumnValue::new_int(1),
+            StmtColumnValue::new_blob(vec![]),
+        ])
+        .await
+        .unwrap();
+        stmt.reset().await.unwrap();
+        stmt.close().await.unwrap();
+        let len: u64 = conn
+            .query_scalar("select length(data) from long_data_reset where id = 1")
+            .await
+            .unwrap();
+        assert_eq!(3, len);
+    }
+
+    struct FailingReader;
+
+    impl futures::AsyncRead for FailingReader {
+        fn poll_read(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+        }
+    }
+
     #[smol_potat::test]
     async fn test_stmt_qry_empty() {
         let mut conn = new_conn().await;
@@ -0,0 +1,103 @@
+//! names and metadata of tasks driving connections
+//!
+//! the crate is runtime agnostic and does not spawn async tasks, futures
+//! like binlog stream are driven by tasks spawned by users. TaskName
+//! describes such a task, so it can be attached to the runtime, e.g.
+//! tokio::task::Builder::name shown in tokio-console, or to logs.
+//! parser threads of binlog stream are named the same way.
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    /// reads and parses binlog stream
+    BinlogReader,
+    /// thread parsing binlog events, see BinlogStreamRequest::parse_workers
+    BinlogParser,
+    /// runs queries on connection
+    Query,
+}
+
+impl TaskKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskKind::BinlogReader => "binlog-reader",
+            TaskKind::BinlogParser => "binlog-parser",
+            TaskKind::Query => "query",
+        }
+    }
+}
+
+/// displayed as `mybin.<kind>[#<index>] conn=<id> pos=<file>:<pos>`,
+/// absent metadata is omitted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskName {
+    pub kind: TaskKind,
+    /// connection id assigned by server
+    pub connection_id: Option<u32>,
+    /// binlog position when the name is taken
+    pub position: Option<(String, u64)>,
+    /// index among tasks of same kind
+    pub index: Option<usize>,
+}
+
+impl TaskName {
+    pub fn new(kind: TaskKind) -> Self {
+        TaskName {
+            kind,
+            connection_id: None,
+            position: None,
+            index: None,
+        }
+    }
+
+    pub fn connection_id(mut self, connection_id: u32) -> Self {
+        self.connection_id = Some(connection_id);
+        self
+    }
+
+    pub fn position<T: Into<String>>(mut self, binlog_filename: T, binlog_pos: u64) -> Self {
+        self.position = Some((binlog_filename.into(), binlog_pos));
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+}
+
+impl fmt::Display for TaskName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mybin.{}", self.kind.as_str())?;
+        if let Some(index) = self.index {
+            write!(f, "#{}", index)?;
+        }
+        if let Some(connection_id) = self.connection_id {
+            write!(f, " conn={}", connection_id)?;
+        }
+        if let Some((filename, pos)) = &self.position {
+            write!(f, " pos={}:{}", filename, pos)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_name() {
+        assert_eq!(
+            "mybin.binlog-reader conn=12 pos=mysql-bin.000003:4",
+            TaskName::new(TaskKind::BinlogReader)
+                .connection_id(12)
+                .position("mysql-bin.000003", 4)
+                .to_string()
+        );
+        assert_eq!(
+            "mybin.binlog-parser#1",
+            TaskName::new(TaskKind::BinlogParser).index(1).to_string()
+        );
+    }
+}
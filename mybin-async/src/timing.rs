@@ -0,0 +1,96 @@
+//! client side timing of commands
+//!
+//! each field is offset from the time command is issued, so
+//! latency of each phase can be derived, e.g. time to first byte
+//! minus sent is mostly server execution time plus network delay.
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timing {
+    /// command starts to be written, after deferred work such as
+    /// rollback of dropped transaction
+    pub queued: Duration,
+    /// command written to stream
+    pub sent: Duration,
+    /// first response packet received
+    pub first_byte: Duration,
+    /// whole response received, zero if result set is not
+    /// fully consumed
+    pub complete: Duration,
+}
+
+impl Timing {
+    /// time spent on server and network, from command sent to
+    /// first response packet
+    pub fn server_latency(&self) -> Duration {
+        self.first_byte.saturating_sub(self.sent)
+    }
+
+    /// time spent on transferring result after first packet
+    pub fn transfer(&self) -> Duration {
+        self.complete.saturating_sub(self.first_byte)
+    }
+}
+
+/// records timing of the running command on connection
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimingRecorder {
+    start: Option<Instant>,
+    timing: Timing,
+}
+
+impl TimingRecorder {
+    pub(crate) fn start(&mut self) {
+        self.start = Some(Instant::now());
+        self.timing = Timing::default();
+    }
+
+    pub(crate) fn queued(&mut self) {
+        self.timing.queued = self.elapsed();
+    }
+
+    pub(crate) fn sent(&mut self) {
+        self.timing.sent = self.elapsed();
+    }
+
+    pub(crate) fn first_byte(&mut self) {
+        self.timing.first_byte = self.elapsed();
+    }
+
+    pub(crate) fn complete(&mut self) {
+        self.timing.complete = self.elapsed();
+    }
+
+    pub(crate) fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.map(|s| s.elapsed()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_recorder() {
+        let mut rec = TimingRecorder::default();
+        // not started
+        rec.sent();
+        assert_eq!(Timing::default(), rec.timing());
+        rec.start();
+        rec.queued();
+        rec.sent();
+        std::thread::sleep(Duration::from_millis(2));
+        rec.first_byte();
+        rec.complete();
+        let t = rec.timing();
+        assert!(t.queued <= t.sent && t.sent <= t.first_byte && t.first_byte <= t.complete);
+        assert!(t.server_latency() >= Duration::from_millis(2));
+        assert!(t.transfer() <= t.complete);
+        rec.start();
+        assert_eq!(Timing::default(), rec.timing());
+    }
+}
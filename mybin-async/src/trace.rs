@@ -0,0 +1,179 @@
+//! tracer of wire protocol packets
+//!
+//! every packet sent or received is recorded with direction, sequence
+//! id, length and payload, either as text log with hex dump or passed
+//! to callback. auth data sent during handshake is redacted by default.
+use crate::hook::PacketDirection;
+use chrono::{DateTime, Utc};
+use std::fmt::{self, Write as _};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// packet recorded by tracer
+#[derive(Debug, Clone)]
+pub struct PacketRecord<'a> {
+    pub time: DateTime<Utc>,
+    pub direction: PacketDirection,
+    pub seq: u8,
+    /// length of original payload
+    pub len: usize,
+    /// empty if redacted
+    pub payload: &'a [u8],
+    pub redacted: bool,
+}
+
+impl fmt::Display for PacketRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            PacketDirection::Sent => ">>",
+            PacketDirection::Received => "<<",
+        };
+        writeln!(
+            f,
+            "{} {} seq={} len={}",
+            self.time.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            arrow,
+            self.seq,
+            self.len
+        )?;
+        if self.redacted {
+            writeln!(f, "<redacted>")
+        } else {
+            f.write_str(&hex_dump(self.payload))
+        }
+    }
+}
+
+enum TraceSink {
+    Writer(Mutex<Box<dyn Write + Send>>),
+    Callback(Box<dyn Fn(&PacketRecord<'_>) + Send + Sync>),
+}
+
+pub struct ProtocolTracer {
+    sink: TraceSink,
+    redact_auth: bool,
+}
+
+impl ProtocolTracer {
+    /// append text log to file
+    pub fn to_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::to_writer(BufWriter::new(file)))
+    }
+
+    /// write text log to writer, flushed after each packet
+    pub fn to_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        ProtocolTracer {
+            sink: TraceSink::Writer(Mutex::new(Box::new(writer))),
+            redact_auth: true,
+        }
+    }
+
+    pub fn with_callback<F>(f: F) -> Self
+    where
+        F: Fn(&PacketRecord<'_>) + Send + Sync + 'static,
+    {
+        ProtocolTracer {
+            sink: TraceSink::Callback(Box::new(f)),
+            redact_auth: true,
+        }
+    }
+
+    /// whether to hide payload of packets sent in authentication,
+    /// enabled by default
+    pub fn redact_auth(mut self, redact_auth: bool) -> Self {
+        self.redact_auth = redact_auth;
+        self
+    }
+
+    pub(crate) fn trace(&self, direction: PacketDirection, seq: u8, payload: &[u8], auth: bool) {
+        let redacted = auth && self.redact_auth && direction == PacketDirection::Sent;
+        let record = PacketRecord {
+            time: Utc::now(),
+            direction,
+            seq,
+            len: payload.len(),
+            payload: if redacted { &[] } else { payload },
+            redacted,
+        };
+        match &self.sink {
+            TraceSink::Writer(writer) => {
+                let mut writer = writer.lock().unwrap();
+                if let Err(e) = write!(writer, "{}", record).and_then(|_| writer.flush()) {
+                    log::warn!("failed to write protocol trace: {}", e);
+                }
+            }
+            TraceSink::Callback(f) => f(&record),
+        }
+    }
+}
+
+impl fmt::Debug for ProtocolTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolTracer")
+            .field("redact_auth", &self.redact_auth)
+            .finish()
+    }
+}
+
+/// 16 bytes per line with offset, hex and ascii
+pub fn hex_dump(payload: &[u8]) -> String {
+    let mut s = String::new();
+    for (i, line) in payload.chunks(16).enumerate() {
+        let _ = write!(s, "{:04x} ", i * 16);
+        for j in 0..16 {
+            match line.get(j) {
+                Some(b) => {
+                    let _ = write!(s, " {:02x}", b);
+                }
+                None => s.push_str("   "),
+            }
+        }
+        s.push_str("  |");
+        s.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        s.push_str("|\n");
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_protocol_tracer() {
+        assert_eq!(
+            "0000  01 41 62 ff                                      |.Ab.|\n",
+            hex_dump(&[1, b'A', b'b', 0xff])
+        );
+        let records = Arc::new(Mutex::new(vec![]));
+        let r = records.clone();
+        let tracer = ProtocolTracer::with_callback(move |rec| {
+            r.lock()
+                .unwrap()
+                .push((rec.direction, rec.seq, rec.len, rec.payload.to_vec()))
+        });
+        tracer.trace(PacketDirection::Received, 0, b"seed", true);
+        tracer.trace(PacketDirection::Sent, 1, b"secret", true);
+        tracer.trace(PacketDirection::Sent, 0, b"\x03select 1", false);
+        assert_eq!(
+            vec![
+                (PacketDirection::Received, 0, 4, b"seed".to_vec()),
+                (PacketDirection::Sent, 1, 6, vec![]),
+                (PacketDirection::Sent, 0, 9, b"\x03select 1".to_vec()),
+            ],
+            *records.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! transaction management over text protocol
+//!
+//! a Transaction is a guard on connection. if it is dropped without
+//! commit or rollback, the rollback is deferred and sent right before
+//! the next command on the same connection, because async operations
+//! can not be performed in Drop.
+use crate::conn::Conn;
+use crate::error::Result;
+use crate::query::QueryResult;
+use futures::{AsyncRead, AsyncWrite};
+pub use mybin_core::session::{AccessMode, IsolationLevel};
+use std::ops::{Deref, DerefMut};
+
+/// rollback that should be sent before next command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PendingRollback {
+    /// rollback whole transaction
+    All,
+    /// rollback to given savepoint
+    Savepoint(String),
+}
+
+impl PendingRollback {
+    pub(crate) fn to_sql(&self) -> String {
+        match self {
+            PendingRollback::All => "ROLLBACK".to_owned(),
+            PendingRollback::Savepoint(name) => format!("ROLLBACK TO SAVEPOINT {}", name),
+        }
+    }
+}
+
+/// builder to start a transaction with modifiers
+#[derive(Debug)]
+pub struct TransactionBuilder<'a, S> {
+    conn: &'a mut Conn<S>,
+    isolation_level: Option<IsolationLevel>,
+    access_mode: Option<AccessMode>,
+    consistent_snapshot: bool,
+}
+
+impl<'a, S> TransactionBuilder<'a, S> {
+    pub fn new(conn: &'a mut Conn<S>) -> Self {
+        TransactionBuilder {
+            conn,
+            isolation_level: None,
+            access_mode: None,
+            consistent_snapshot: false,
+        }
+    }
+
+    /// isolation level applies to this transaction only
+    pub fn isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    pub fn access_mode(mut self, access_mode: AccessMode) -> Self {
+        self.access_mode = Some(access_mode);
+        self
+    }
+
+    pub fn read_only(self, read_only: bool) -> Self {
+        self.access_mode(if read_only {
+            AccessMode::ReadOnly
+        } else {
+            AccessMode::ReadWrite
+        })
+    }
+
+    /// START TRANSACTION WITH CONSISTENT SNAPSHOT
+    pub fn consistent_snapshot(mut self, consistent_snapshot: bool) -> Self {
+        self.consistent_snapshot = consistent_snapshot;
+        self
+    }
+
+    fn start_sql(&self) -> String {
+        let mut modifiers = vec![];
+        if self.consistent_snapshot {
+            modifiers.push("WITH CONSISTENT SNAPSHOT");
+        }
+        if let Some(access_mode) = self.access_mode {
+            modifiers.push(access_mode.as_sql());
+        }
+        if modifiers.is_empty() {
+            "START TRANSACTION".to_owned()
+        } else {
+            format!("START TRANSACTION {}", modifiers.join(", "))
+        }
+    }
+}
+
+impl<'a, S> TransactionBuilder<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub async fn begin(self) -> Result<Transaction<'a, S>> {
+        let sql = self.start_sql();
+        if let Some(isolation_level) = self.isolation_level {
+            self.conn
+                .exec(format!(
+                    "SET TRANSACTION ISOLATION LEVEL {}",
+                    isolation_level.as_sql()
+                ))
+                .await?;
+        }
+        self.conn.exec(sql).await?;
+        Ok(Transaction {
+            conn: self.conn,
+            savepoint: None,
+            depth: 0,
+            completed: false,
+        })
+    }
+}
+
+/// transaction guard
+///
+/// the connection can be accessed through deref, and all commands
+/// are executed in this transaction.
+/// nested transactions are supported by savepoints.
+#[derive(Debug)]
+pub struct Transaction<'a, S> {
+    conn: &'a mut Conn<S>,
+    // savepoint name if this is a nested transaction
+    savepoint: Option<String>,
+    depth: u32,
+    completed: bool,
+}
+
+impl<'a, S> Transaction<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// create a savepoint and returns the nested transaction
+    ///
+    /// commit of nested transaction releases the savepoint, and
+    /// rollback of it rolls back to the savepoint
+    pub async fn savepoint(&mut self) -> Result<Transaction<'_, S>> {
+        let depth = self.depth + 1;
+        let name = format!("mybin_sp_{}", depth);
+        self.conn.exec(format!("SAVEPOINT {}", name)).await?;
+        Ok(Transaction {
+            conn: &mut *self.conn,
+            savepoint: Some(name),
+            depth,
+            completed: false,
+        })
+    }
+
+    pub async fn commit(mut self) -> Result<QueryResult> {
+        let sql = match self.savepoint.as_ref() {
+            Some(name) => format!("RELEASE SAVEPOINT {}", name),
+            None => "COMMIT".to_owned(),
+        };
+        self.completed = true;
+        self.conn.exec(sql).await
+    }
+
+    pub async fn rollback(mut self) -> Result<QueryResult> {
+        let sql = match self.savepoint.as_ref() {
+            Some(name) => PendingRollback::Savepoint(name.clone()).to_sql(),
+            None => PendingRollback::All.to_sql(),
+        };
+        self.completed = true;
+        self.conn.exec(sql).await
+    }
+}
+
+impl<'a, S> Deref for Transaction<'a, S> {
+    type Target = Conn<S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+    }
+}
+
+impl<'a, S> DerefMut for Transaction<'a, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn
+    }
+}
+
+impl<'a, S> Drop for Transaction<'a, S> {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        log::warn!(
+            "transaction dropped without commit or rollback, depth={}",
+            self.depth
+        );
+        let pending = match self.savepoint.as_ref() {
+            Some(name) => PendingRollback::Savepoint(name.clone()),
+            None => PendingRollback::All,
+        };
+        // rollback of whole transaction always takes precedence
+        if self.conn.pending_rollback != Some(PendingRollback::All) {
+            self.conn.pending_rollback = Some(pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conn::tests::new_conn;
+
+    #[smol_potat::test]
+    async fn test_transaction_commit_and_rollback() {
+        let mut conn = new_conn().await;
+        conn.exec("CREATE DATABASE IF NOT EXISTS bintest1")
+            .await
+            .unwrap();
+        conn.exec("DROP TABLE IF EXISTS bintest1.trx1")
+            .await
+            .unwrap();
+        conn.exec("CREATE TABLE bintest1.trx1 (id INT PRIMARY KEY)")
+            .await
+            .unwrap();
+        let mut tx = conn.begin().await.unwrap();
+        tx.exec("INSERT INTO bintest1.trx1 VALUES (1)")
+            .await
+            .unwrap();
+        {
+            let mut sp = tx.savepoint().await.unwrap();
+            sp.exec("INSERT INTO bintest1.trx1 VALUES (2)")
+                .await
+                .unwrap();
+            sp.rollback().await.unwrap();
+        }
+        {
+            let mut sp = tx.savepoint().await.unwrap();
+            sp.exec("INSERT INTO bintest1.trx1 VALUES (3)")
+                .await
+                .unwrap();
+            // dropped without commit
+        }
+        tx.commit().await.unwrap();
+        let cnt: u32 = conn
+            .query_scalar("SELECT COUNT(*) FROM bintest1.trx1")
+            .await
+            .unwrap();
+        assert_eq!(1, cnt);
+        {
+            let mut tx = conn
+                .transaction()
+                .isolation_level(IsolationLevel::ReadCommitted)
+                .begin()
+                .await
+                .unwrap();
+            tx.exec("INSERT INTO bintest1.trx1 VALUES (4)")
+                .await
+                .unwrap();
+        }
+        let cnt: u32 = conn
+            .query_scalar("SELECT COUNT(*) FROM bintest1.trx1")
+            .await
+            .unwrap();
+        assert_eq!(1, cnt);
+    }
+}
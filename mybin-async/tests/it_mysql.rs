@@ -0,0 +1,46 @@
+//! run with `cargo test -p mybin-async --features it-tests --test it_mysql`
+//!
+//! versions can be selected by env MYBIN_IT_VERSIONS, e.g. "5.7,8.0"
+#![cfg(feature = "it-tests")]
+
+use mybin_async::it::*;
+use mybin_core::binlog::LogEventType;
+use std::time::Duration;
+
+fn versions() -> Vec<MysqlVersion> {
+    match std::env::var("MYBIN_IT_VERSIONS") {
+        Ok(vs) => vs.split(',').map(|v| v.trim().parse().unwrap()).collect(),
+        Err(_) => MysqlVersion::all(),
+    }
+}
+
+#[test]
+fn test_binlog_of_workloads() {
+    for (i, version) in versions().into_iter().enumerate() {
+        let container =
+            MysqlContainer::start(version, 33061 + i as u16, Duration::from_secs(180)).unwrap();
+        smol::block_on(async {
+            let mut conn = container.connect().await.unwrap();
+            let start = conn.show_master_status().await.unwrap().unwrap();
+            let workload = ddl_workload()
+                .merge(all_types_workload())
+                .merge(big_trx_workload(10000));
+            workload.run(&mut conn).await.unwrap();
+            assert_query_rows(
+                &mut conn,
+                "SELECT id, c_int, c_varchar FROM mybin_it.all_types",
+                &[&[Some("1"), Some("1"), Some("updated")]],
+            )
+            .await
+            .unwrap();
+
+            let mut binlog_conn = container.connect().await.unwrap();
+            let summary = collect_binlog(&mut binlog_conn, &start.file, start.position)
+                .await
+                .unwrap();
+            summary.assert_workload(&workload);
+            assert!(summary.count(LogEventType::GtidLogEvent) > 0);
+            assert!(summary.queries.iter().any(|q| q.starts_with("ALTER TABLE")));
+        });
+    }
+}
@@ -0,0 +1,174 @@
+//! paginated SELECT plan for snapshot of table
+//!
+//! rows are read in chunks ordered by primary key. each chunk starts
+//! after the key of last row of previous chunk (keyset pagination),
+//! so the cost of a chunk does not grow with offset, and chunks can
+//! be resumed from any returned key.
+use crate::binlog::TableMap;
+use crate::col::{ColumnDefinition, ColumnFlags};
+use crate::error::{Error, Result};
+use crate::value::{Row, Value};
+use smol_str::SmolStr;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackfillPlan {
+    pub db: SmolStr,
+    pub tbl: SmolStr,
+    /// selected columns, in table definition order
+    pub columns: Vec<SmolStr>,
+    /// indexes of primary key columns in selected columns
+    pub key_cols: Vec<usize>,
+    pub chunk_size: usize,
+}
+
+impl BackfillPlan {
+    /// returns None if table does not have primary key
+    pub fn from_col_defs(col_defs: &[ColumnDefinition]) -> Option<Self> {
+        let first = col_defs.first()?;
+        let key_cols: Vec<_> = col_defs
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| def.flags.contains(ColumnFlags::PRIMARY_KEY))
+            .map(|(idx, _)| idx)
+            .collect();
+        if key_cols.is_empty() {
+            return None;
+        }
+        Some(BackfillPlan {
+            db: first.schema.clone(),
+            tbl: first.org_table.clone(),
+            columns: col_defs.iter().map(|def| def.org_name.clone()).collect(),
+            key_cols,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    /// plan by optional metadata of table map
+    ///
+    /// returns None if column names or primary key is not recorded,
+    /// see binlog_row_metadata=FULL
+    pub fn from_table_map(table_map: &TableMap) -> Option<Self> {
+        let meta = &table_map.metadata;
+        if meta.column_names.is_empty() || meta.primary_key.is_empty() {
+            return None;
+        }
+        Some(BackfillPlan {
+            db: table_map.schema_name.clone(),
+            tbl: table_map.table_name.clone(),
+            columns: meta.column_names.clone(),
+            key_cols: meta
+                .primary_key
+                .iter()
+                .map(|(idx, _)| *idx as usize)
+                .collect(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// query of chunk after given key, or the first chunk if None
+    pub fn chunk_query(&self, after: Option<&[Value]>) -> Result<String> {
+        let cols: Vec<_> = self
+            .columns
+            .iter()
+            .map(|c| quote_ident(c.as_str()))
+            .collect();
+        let keys: Vec<_> = self
+            .key_cols
+            .iter()
+            .map(|idx| cols[*idx].as_str())
+            .collect();
+        let mut qry = format!(
+            "SELECT {} FROM {}.{}",
+            cols.join(", "),
+            quote_ident(&self.db),
+            quote_ident(&self.tbl)
+        );
+        if let Some(after) = after {
+            if after.len() != keys.len() {
+                return Err(Error::ColumnIndexOutOfBound(format!(
+                    "key values {} / {}",
+                    after.len(),
+                    keys.len()
+                )));
+            }
+            let values: Vec<_> = after.iter().map(Value::to_sql_literal).collect();
+            qry.push_str(&format!(
+                " WHERE ({}) > ({})",
+                keys.join(", "),
+                values.join(", ")
+            ));
+        }
+        qry.push_str(&format!(
+            " ORDER BY {} LIMIT {}",
+            keys.join(", "),
+            self.chunk_size
+        ));
+        Ok(qry)
+    }
+
+    /// primary key values of row returned by chunk query
+    pub fn key_of(&self, row: &Row) -> Result<Vec<Value>> {
+        self.key_cols
+            .iter()
+            .map(|idx| {
+                row.get(*idx).cloned().ok_or_else(|| {
+                    Error::ColumnIndexOutOfBound(format!("column index {} / {}", idx, row.len()))
+                })
+            })
+            .collect()
+    }
+}
+
+/// quote identifier with backticks, embedded backticks are doubled
+pub fn quote_ident(s: &str) -> String {
+    format!("`{}`", s.replace('`', "``"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::ColumnType;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_backfill_plan() {
+        let col_defs = vec![
+            col_def("id", ColumnFlags::PRIMARY_KEY),
+            col_def("v", ColumnFlags::empty()),
+            col_def("name", ColumnFlags::PRIMARY_KEY),
+        ];
+        let plan = BackfillPlan::from_col_defs(&col_defs)
+            .unwrap()
+            .chunk_size(100);
+        assert_eq!(
+            "SELECT `id`, `v`, `name` FROM `db1`.`t1` ORDER BY `id`, `name` LIMIT 100",
+            plan.chunk_query(None).unwrap()
+        );
+        let last = Row(vec![
+            Value::Int(7),
+            Value::Null,
+            Value::Bytes(Bytes::from("o'k")),
+        ]);
+        let key = plan.key_of(&last).unwrap();
+        assert_eq!(
+            "SELECT `id`, `v`, `name` FROM `db1`.`t1` WHERE (`id`, `name`) > (7, 'o''k') \
+             ORDER BY `id`, `name` LIMIT 100",
+            plan.chunk_query(Some(&key)).unwrap()
+        );
+        assert!(plan.chunk_query(Some(&key[..1])).is_err());
+        let no_key = vec![col_def("v", ColumnFlags::empty())];
+        assert!(BackfillPlan::from_col_defs(&no_key).is_none());
+        assert_eq!("`a``b`", quote_ident("a`b"));
+    }
+
+    fn col_def(name: &str, flags: ColumnFlags) -> ColumnDefinition {
+        crate::col::tests::col_def(name, ColumnType::Long, flags)
+    }
+}
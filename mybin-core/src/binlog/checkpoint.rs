@@ -0,0 +1,213 @@
+//! durable checkpoint of stream position
+//!
+//! the file starts with a fixed header: magic `MYBCKPT\0`, version
+//! (u16), payload length (u32) and crc32 of payload (u32), all little
+//! endian, followed by the payload. payload of version 1 is JSON.
+//!
+//! a checkpoint is written to a temporary file, synced, and renamed
+//! over the current one, whose content is kept as `<path>.prev`.
+//! if the current file is missing or corrupted, e.g. by a crash or
+//! a full disk, the previous checkpoint is loaded instead.
+use crate::error::{Error, Result};
+use crate::util::checksum_crc32;
+use serde_derive::*;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"MYBCKPT\0";
+const HEADER_LEN: usize = 18;
+pub const CHECKPOINT_VERSION: u16 = 1;
+
+/// position to resume stream from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub binlog_filename: String,
+    pub binlog_pos: u64,
+    /// gtid_executed including transactions before the position,
+    /// None if gtid is not enabled
+    pub executed_gtid_set: Option<String>,
+}
+
+impl Checkpoint {
+    /// encode in current version
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let payload =
+            serde_json::to_vec(self).map_err(|e| Error::CorruptedCheckpoint(e.to_string()))?;
+        let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&checksum_crc32(&payload).to_le_bytes());
+        out.extend_from_slice(&payload);
+        Ok(out)
+    }
+
+    /// decode and migrate to current version
+    pub fn decode(bs: &[u8]) -> Result<Self> {
+        if bs.len() < HEADER_LEN || &bs[..8] != MAGIC {
+            return Err(Error::CorruptedCheckpoint("invalid header".to_owned()));
+        }
+        let version = u16::from_le_bytes([bs[8], bs[9]]);
+        let len = u32::from_le_bytes([bs[10], bs[11], bs[12], bs[13]]) as usize;
+        let crc32 = u32::from_le_bytes([bs[14], bs[15], bs[16], bs[17]]);
+        let payload = &bs[HEADER_LEN..];
+        if payload.len() != len {
+            return Err(Error::CorruptedCheckpoint(format!(
+                "payload length mismatch: expected={}, actual={}",
+                len,
+                payload.len()
+            )));
+        }
+        let actual = checksum_crc32(payload);
+        if actual != crc32 {
+            return Err(Error::CorruptedCheckpoint(format!(
+                "checksum mismatch: expected={}, actual={}",
+                crc32, actual
+            )));
+        }
+        // payloads of older versions are converted here
+        match version {
+            1 => serde_json::from_slice(payload)
+                .map_err(|e| Error::CorruptedCheckpoint(e.to_string())),
+            v => Err(Error::CorruptedCheckpoint(format!(
+                "unsupported version {}, max supported is {}",
+                v, CHECKPOINT_VERSION
+            ))),
+        }
+    }
+}
+
+/// checkpoint stored in local file
+#[derive(Debug, Clone)]
+pub struct PositionStore {
+    path: PathBuf,
+}
+
+impl PositionStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        PositionStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// load last good checkpoint, None if never saved
+    pub fn load(&self) -> Result<Option<Checkpoint>> {
+        let err = match read_checkpoint(&self.path) {
+            Ok(Some(ckpt)) => return Ok(Some(ckpt)),
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("failed to load checkpoint {:?}: {}", self.path, e);
+                Some(e)
+            }
+        };
+        // current file is missing if crash happens between renames
+        match read_checkpoint(&self.prev_path())? {
+            Some(ckpt) => {
+                log::warn!("fallback to previous checkpoint {:?}", ckpt);
+                Ok(Some(ckpt))
+            }
+            None => match err {
+                Some(e) => Err(e),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// write checkpoint atomically, returns after data is synced.
+    /// concurrent saves on the same path are not supported
+    pub fn save(&self, ckpt: &Checkpoint) -> Result<()> {
+        let data = ckpt.encode()?;
+        let tmp_path = self.sibling(".tmp");
+        {
+            let mut f = File::create(&tmp_path)?;
+            f.write_all(&data)?;
+            f.sync_all()?;
+        }
+        match fs::rename(&self.path, self.prev_path()) {
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        sync_dir(&self.path)?;
+        Ok(())
+    }
+
+    fn prev_path(&self) -> PathBuf {
+        self.sibling(".prev")
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+}
+
+fn read_checkpoint(path: &Path) -> Result<Option<Checkpoint>> {
+    match fs::read(path) {
+        Ok(bs) => Checkpoint::decode(&bs).map(Some),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// make renames durable, directories can't be opened on windows
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_store() {
+        let dir = std::env::temp_dir().join(format!("mybin-ckpt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = PositionStore::new(dir.join("position"));
+        assert_eq!(None, store.load().unwrap());
+
+        let ckpt = |pos| Checkpoint {
+            binlog_filename: "mysql-bin.000001".to_owned(),
+            binlog_pos: pos,
+            executed_gtid_set: None,
+        };
+        store.save(&ckpt(4)).unwrap();
+        store.save(&ckpt(120)).unwrap();
+        assert_eq!(Some(ckpt(120)), store.load().unwrap());
+
+        // torn write of current file falls back to previous one
+        let mut data = fs::read(store.path()).unwrap();
+        let n = data.len();
+        data[n - 2] ^= 0xff;
+        fs::write(store.path(), &data).unwrap();
+        assert_eq!(Some(ckpt(4)), store.load().unwrap());
+        fs::remove_file(store.path()).unwrap();
+        assert_eq!(Some(ckpt(4)), store.load().unwrap());
+
+        // unknown version
+        let mut data = ckpt(4).encode().unwrap();
+        data[8] = 9;
+        assert!(matches!(
+            Checkpoint::decode(&data),
+            Err(Error::CorruptedCheckpoint(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
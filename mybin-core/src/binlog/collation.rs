@@ -0,0 +1,141 @@
+//! comparison of string values by column collation
+//!
+//! server compares strings by weights of collation rather than raw
+//! bytes, e.g. 'abc' = 'ABC ' in utf8mb4_general_ci. the weights here
+//! cover the common utf8mb4, utf8 and latin1 collations. case and
+//! accent folding is limited to ASCII and Latin-1 supplement letters,
+//! and unknown collations are compared as binary.
+use super::text::EventText;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    /// raw bytes
+    Binary,
+    /// code points
+    CodePoint,
+    /// case and accent insensitive
+    Folded,
+}
+
+/// collation of string column, identified by collation id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Collation {
+    pub id: u16,
+    rule: Rule,
+    /// trailing spaces are ignored, true for all collations
+    /// except utf8mb4_0900_*
+    pad_space: bool,
+}
+
+impl Collation {
+    pub fn new(id: u16) -> Self {
+        let (rule, pad_space) = match id {
+            // latin1_bin, utf8mb4_bin, utf8_bin
+            47 | 46 | 83 => (Rule::CodePoint, true),
+            // utf8mb4_0900_bin
+            309 => (Rule::CodePoint, false),
+            // latin1_german1_ci, latin1_swedish_ci, latin1_danish_ci,
+            // latin1_general_ci, latin1_spanish_ci,
+            // utf8_general_ci, utf8mb4_general_ci,
+            // utf8_unicode_ci, utf8mb4_unicode_ci
+            5 | 8 | 15 | 48 | 94 | 33 | 45 | 192 | 224 => (Rule::Folded, true),
+            // utf8mb4_0900_ai_ci
+            255 => (Rule::Folded, false),
+            _ => (Rule::Binary, false),
+        };
+        Collation {
+            id,
+            rule,
+            pad_space,
+        }
+    }
+
+    /// whether bytes are compared as is
+    pub fn is_binary(&self) -> bool {
+        self.rule == Rule::Binary
+    }
+
+    /// weights of string value
+    ///
+    /// two values are equal in collation if and only if their sort
+    /// keys are equal, and sort keys are ordered as values
+    pub fn sort_key<'a>(&self, bs: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.rule == Rule::Binary {
+            return Cow::Borrowed(bs);
+        }
+        let s = bs.to_string_with_collation(self.id);
+        let s = if self.pad_space {
+            s.trim_end_matches(' ')
+        } else {
+            &s
+        };
+        match self.rule {
+            Rule::Folded => Cow::Owned(s.chars().map(fold).collect::<String>().into_bytes()),
+            // utf8 bytes are ordered as code points
+            _ => Cow::Owned(s.as_bytes().to_vec()),
+        }
+    }
+
+    pub fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        self.sort_key(a).cmp(&self.sort_key(b))
+    }
+
+    pub fn equals(&self, a: &[u8], b: &[u8]) -> bool {
+        self.compare(a, b) == Ordering::Equal
+    }
+}
+
+/// uppercase without accent, as weights of general_ci
+fn fold(c: char) -> char {
+    let c = match c {
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        _ => c,
+    };
+    c.to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collation_compare() {
+        let ci = Collation::new(45);
+        assert!(ci.equals(b"abc", b"ABC  "));
+        assert!(ci.equals("résumé".as_bytes(), b"RESUME"));
+        // 'A' < '_' < 'a' in bytes, but weight of 'a' is 'A'
+        assert_eq!(Ordering::Less, ci.compare(b"a", b"_"));
+        let no_pad = Collation::new(255);
+        assert!(no_pad.equals(b"Abc", b"aBC"));
+        assert!(!no_pad.equals(b"abc", b"abc "));
+        let bin = Collation::new(46);
+        assert!(bin.equals(b"abc", b"abc "));
+        assert!(!bin.equals(b"abc", b"ABC"));
+        let binary = Collation::new(63);
+        assert!(binary.is_binary());
+        assert!(!binary.equals(b"abc", b"abc "));
+        // latin1 bytes decoded before folding
+        let latin1 = Collation::new(8);
+        assert!(latin1.equals(b"caf\xe9", b"CAFE"));
+        let mut names = vec!["b", "A", "a ", "C"];
+        names.sort_by(|a, b| ci.compare(a.as_bytes(), b.as_bytes()));
+        assert_eq!(vec!["A", "a ", "b", "C"], names);
+    }
+}
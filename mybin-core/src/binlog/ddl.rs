@@ -0,0 +1,448 @@
+//! light-weight classification of statements in QueryEvent
+//!
+//! only leading keywords and object names are inspected, the rest of
+//! statement is not validated.
+use crate::digest::{tokenize, Token};
+use smol_str::SmolStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    CreateTable,
+    AlterTable,
+    DropTable,
+    CreateIndex,
+    DropIndex,
+    CreateDatabase,
+    AlterDatabase,
+    DropDatabase,
+    Truncate,
+    /// RENAME TABLE
+    Rename,
+    /// INSERT, REPLACE, UPDATE, DELETE and LOAD DATA
+    Dml,
+    /// BEGIN, COMMIT, ROLLBACK, SAVEPOINT and XA
+    TransactionControl,
+    Other,
+}
+
+impl StatementKind {
+    /// changes definition of table or database
+    pub fn is_ddl(self) -> bool {
+        !matches!(
+            self,
+            StatementKind::Dml | StatementKind::TransactionControl | StatementKind::Other
+        )
+    }
+
+    pub fn is_database(self) -> bool {
+        matches!(
+            self,
+            StatementKind::CreateDatabase
+                | StatementKind::AlterDatabase
+                | StatementKind::DropDatabase
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectName {
+    /// None if name is not qualified
+    pub db: Option<SmolStr>,
+    pub name: SmolStr,
+}
+
+impl ObjectName {
+    pub fn new(db: Option<&str>, name: &str) -> Self {
+        ObjectName {
+            db: db.map(SmolStr::new),
+            name: SmolStr::new(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classified {
+    pub kind: StatementKind,
+    /// affected tables in order of appearance, e.g. old and new names
+    /// of RENAME. for database statements, name is the database and
+    /// db is None
+    pub objects: Vec<ObjectName>,
+}
+
+impl Classified {
+    /// qualify table names with default database
+    pub fn with_default_db(mut self, db: &str) -> Self {
+        if !self.kind.is_database() && !db.is_empty() {
+            for obj in self.objects.iter_mut().filter(|obj| obj.db.is_none()) {
+                obj.db = Some(SmolStr::new(db));
+            }
+        }
+        self
+    }
+}
+
+pub fn classify(sql: &str) -> Classified {
+    let tokens = tokenize(sql);
+    let mut c = Cursor {
+        tokens: &tokens,
+        pos: 0,
+    };
+    let (kind, objects) = c.statement().unwrap_or((StatementKind::Other, vec![]));
+    Classified { kind, objects }
+}
+
+/// whether statement implicitly commits the ongoing transaction,
+/// so it is logged as a transaction of its own
+///
+/// these are DDL except CREATE and DROP of temporary tables,
+/// statements on accounts, views, routines, triggers and events,
+/// and administrative statements like ANALYZE TABLE or FLUSH
+pub fn is_implicit_commit(sql: &str) -> bool {
+    let tokens = tokenize(sql);
+    let mut c = Cursor {
+        tokens: &tokens,
+        pos: 0,
+    };
+    c.implicit_commit()
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn statement(&mut self) -> Option<(StatementKind, Vec<ObjectName>)> {
+        use StatementKind::*;
+        if self.eat_any(&["BEGIN", "COMMIT", "ROLLBACK", "SAVEPOINT", "RELEASE", "XA"])
+            || (self.eat("START") && self.eat("TRANSACTION"))
+        {
+            return Some((TransactionControl, vec![]));
+        }
+        if self.eat("CREATE") {
+            if self.eat("OR") && !self.eat("REPLACE") {
+                return None;
+            }
+            self.eat("TEMPORARY");
+            if self.eat("TABLE") {
+                self.eat_seq(&["IF", "NOT", "EXISTS"]);
+                return Some((CreateTable, vec![self.object()?]));
+            }
+            self.eat_any(&["UNIQUE", "FULLTEXT", "SPATIAL"]);
+            if self.eat("INDEX") {
+                self.skip_to("ON")?;
+                return Some((CreateIndex, vec![self.object()?]));
+            }
+            if self.eat_any(&["DATABASE", "SCHEMA"]) {
+                self.eat_seq(&["IF", "NOT", "EXISTS"]);
+                return Some((CreateDatabase, vec![self.database()?]));
+            }
+            return None;
+        }
+        if self.eat("ALTER") {
+            self.eat_any(&["ONLINE", "OFFLINE"]);
+            self.eat("IGNORE");
+            if self.eat("TABLE") {
+                let mut objects = vec![self.object()?];
+                if let Some(new_name) = self.rename_in_alter() {
+                    objects.push(new_name);
+                }
+                return Some((AlterTable, objects));
+            }
+            if self.eat_any(&["DATABASE", "SCHEMA"]) {
+                // name can be omitted for default database
+                let objects = self.database().into_iter().collect();
+                return Some((AlterDatabase, objects));
+            }
+            return None;
+        }
+        if self.eat("DROP") {
+            self.eat("TEMPORARY");
+            if self.eat_any(&["TABLE", "TABLES"]) {
+                self.eat_seq(&["IF", "EXISTS"]);
+                return Some((DropTable, self.object_list()));
+            }
+            if self.eat("INDEX") {
+                self.skip_to("ON")?;
+                return Some((DropIndex, vec![self.object()?]));
+            }
+            if self.eat_any(&["DATABASE", "SCHEMA"]) {
+                self.eat_seq(&["IF", "EXISTS"]);
+                return Some((DropDatabase, vec![self.database()?]));
+            }
+            return None;
+        }
+        if self.eat("TRUNCATE") {
+            self.eat("TABLE");
+            return Some((Truncate, vec![self.object()?]));
+        }
+        if self.eat("RENAME") {
+            if !self.eat_any(&["TABLE", "TABLES"]) {
+                return None;
+            }
+            let mut objects = vec![];
+            loop {
+                objects.push(self.object()?);
+                if !self.eat("TO") {
+                    return None;
+                }
+                objects.push(self.object()?);
+                if !self.eat_other(",") {
+                    return Some((Rename, objects));
+                }
+            }
+        }
+        if self.eat_any(&["INSERT", "REPLACE"]) {
+            self.eat_any(&["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY"]);
+            self.eat("IGNORE");
+            self.eat("INTO");
+            return Some((Dml, self.object().into_iter().collect()));
+        }
+        if self.eat("UPDATE") {
+            self.eat("LOW_PRIORITY");
+            self.eat("IGNORE");
+            return Some((Dml, self.object().into_iter().collect()));
+        }
+        if self.eat("DELETE") {
+            while self.eat_any(&["LOW_PRIORITY", "QUICK", "IGNORE"]) {}
+            self.eat("FROM");
+            return Some((Dml, self.object().into_iter().collect()));
+        }
+        if self.eat("LOAD") {
+            self.skip_to("TABLE")?;
+            return Some((Dml, vec![self.object()?]));
+        }
+        None
+    }
+
+    fn implicit_commit(&mut self) -> bool {
+        if self.eat_any(&["CREATE", "DROP"]) {
+            return !self.eat("TEMPORARY");
+        }
+        self.eat_any(&[
+            "ALTER",
+            "RENAME",
+            "TRUNCATE",
+            "GRANT",
+            "REVOKE",
+            "ANALYZE",
+            "OPTIMIZE",
+            "REPAIR",
+            "FLUSH",
+            "INSTALL",
+            "UNINSTALL",
+        ]) || self.eat_seq(&["SET", "PASSWORD"])
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is_word(word));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn eat_any(&mut self, words: &[&str]) -> bool {
+        words.iter().any(|w| self.eat(w))
+    }
+
+    /// all or none of the words are consumed
+    fn eat_seq(&mut self, words: &[&str]) -> bool {
+        let matched = words
+            .iter()
+            .enumerate()
+            .all(|(i, w)| self.tokens.get(self.pos + i).is_some_and(|t| t.is_word(w)));
+        if matched {
+            self.pos += words.len();
+        }
+        matched
+    }
+
+    fn eat_other(&mut self, s: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is(s));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    /// move to position after given word
+    fn skip_to(&mut self, word: &str) -> Option<()> {
+        let offset = self.tokens[self.pos..]
+            .iter()
+            .position(|t| t.is_word(word))?;
+        self.pos += offset + 1;
+        Some(())
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let id = self.peek()?.ident()?;
+        self.pos += 1;
+        Some(id)
+    }
+
+    /// table name optionally qualified by database
+    fn object(&mut self) -> Option<ObjectName> {
+        let first = self.ident()?;
+        if !self.eat_other(".") {
+            return Some(ObjectName::new(None, first));
+        }
+        let second = self.ident()?;
+        Some(ObjectName::new(Some(first), second))
+    }
+
+    fn object_list(&mut self) -> Vec<ObjectName> {
+        let mut objects = vec![];
+        while let Some(obj) = self.object() {
+            objects.push(obj);
+            if !self.eat_other(",") {
+                break;
+            }
+        }
+        objects
+    }
+
+    fn database(&mut self) -> Option<ObjectName> {
+        self.ident().map(|name| ObjectName::new(None, name))
+    }
+
+    /// new name in ALTER TABLE ... RENAME [TO|AS] new_name
+    fn rename_in_alter(&mut self) -> Option<ObjectName> {
+        while self.pos < self.tokens.len() {
+            if self.eat("RENAME") {
+                if self.eat_any(&["COLUMN", "INDEX", "KEY"]) {
+                    continue;
+                }
+                self.eat_any(&["TO", "AS"]);
+                return self.object();
+            }
+            self.pos += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StatementKind::*;
+
+    #[test]
+    fn test_classify() {
+        let t = |db: Option<&str>, name: &str| ObjectName::new(db, name);
+        for (sql, kind, objects) in vec![
+            ("BEGIN", TransactionControl, vec![]),
+            ("start transaction read only", TransactionControl, vec![]),
+            (
+                "CREATE TABLE IF NOT EXISTS `db1`.`t1` (id int primary key)",
+                CreateTable,
+                vec![t(Some("db1"), "t1")],
+            ),
+            (
+                "/* app */ alter table t1 add column c2 int, rename to db2.t2",
+                AlterTable,
+                vec![t(None, "t1"), t(Some("db2"), "t2")],
+            ),
+            (
+                "ALTER TABLE t1 RENAME COLUMN a TO b",
+                AlterTable,
+                vec![t(None, "t1")],
+            ),
+            (
+                "DROP TABLE IF EXISTS t1, `db2`.`t 2` /* generated by server */",
+                DropTable,
+                vec![t(None, "t1"), t(Some("db2"), "t 2")],
+            ),
+            (
+                "create unique index idx1 using btree on t1 (c1)",
+                CreateIndex,
+                vec![t(None, "t1")],
+            ),
+            (
+                "DROP INDEX idx1 ON db1.t1",
+                DropIndex,
+                vec![t(Some("db1"), "t1")],
+            ),
+            (
+                "CREATE SCHEMA IF NOT EXISTS db1",
+                CreateDatabase,
+                vec![t(None, "db1")],
+            ),
+            (
+                "ALTER DATABASE CHARACTER SET utf8mb4",
+                AlterDatabase,
+                vec![],
+            ),
+            ("DROP DATABASE `db1`", DropDatabase, vec![t(None, "db1")]),
+            ("TRUNCATE t1", Truncate, vec![t(None, "t1")]),
+            (
+                "RENAME TABLE a TO b, db1.c TO db2.c",
+                Rename,
+                vec![
+                    t(None, "a"),
+                    t(None, "b"),
+                    t(Some("db1"), "c"),
+                    t(Some("db2"), "c"),
+                ],
+            ),
+            (
+                "INSERT /*!IGNORE*/ INTO t1 VALUES (1)",
+                Dml,
+                vec![t(None, "t1")],
+            ),
+            (
+                "delete quick from db1.t1 where id = 1",
+                Dml,
+                vec![t(Some("db1"), "t1")],
+            ),
+            (
+                "LOAD DATA INFILE '/tmp/x' INTO TABLE t1",
+                Dml,
+                vec![t(None, "t1")],
+            ),
+            ("CREATE USER u1", Other, vec![]),
+            ("RENAME USER u1 TO u2", Other, vec![]),
+            ("", Other, vec![]),
+        ] {
+            assert_eq!(Classified { kind, objects }, classify(sql), "{}", sql);
+        }
+        let c = classify("RENAME TABLE a TO db2.b").with_default_db("db1");
+        assert_eq!(vec![t(Some("db1"), "a"), t(Some("db2"), "b")], c.objects);
+        let c = classify("DROP DATABASE db2").with_default_db("db1");
+        assert_eq!(vec![t(None, "db2")], c.objects);
+        assert!(CreateIndex.is_ddl());
+        assert!(!Dml.is_ddl());
+    }
+
+    #[test]
+    fn test_implicit_commit() {
+        for sql in &[
+            "create database bintest1",
+            "ALTER TABLE t1 ADD COLUMN c2 int",
+            "/* app */ CREATE DEFINER=`root`@`%` VIEW v1 AS SELECT 1",
+            "DROP TABLE IF EXISTS `t1` /* generated by server */",
+            "TRUNCATE t1",
+            "GRANT SELECT ON *.* TO 'u1'@'%'",
+            "CREATE USER u1",
+            "ANALYZE TABLE t1",
+            "SET PASSWORD FOR u1 = 'x'",
+        ] {
+            assert!(is_implicit_commit(sql), "{}", sql);
+        }
+        for sql in &[
+            "BEGIN",
+            "COMMIT",
+            "INSERT INTO t1 VALUES (1)",
+            "CREATE TEMPORARY TABLE t1 (id int)",
+            "DROP TEMPORARY TABLE IF EXISTS t1",
+            "SET @a = 1",
+            "",
+        ] {
+            assert!(!is_implicit_commit(sql), "{}", sql);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! drop transactions already delivered, based on GTID
+//!
+//! after reconnect or failover, the stream usually restarts from an
+//! earlier position, so transactions may be received twice. a GTID is
+//! recorded only when its transaction is complete, so a transaction
+//! interrupted in the middle is delivered again as a whole.
+use super::{Event, GtidSet, TrxBoundary};
+use crate::error::Result;
+
+#[derive(Debug, Default)]
+pub struct GtidDeduplicator {
+    executed: GtidSet,
+    // gtid of the transaction being received
+    pending: Option<(u128, u64)>,
+    // transaction is started by BEGIN
+    begun: bool,
+    skipping: bool,
+    dropped_trxs: u64,
+    dropped_events: u64,
+}
+
+impl GtidDeduplicator {
+    /// create deduplicator with GTIDs already delivered,
+    /// e.g. restored from checkpoint
+    pub fn new(executed: GtidSet) -> Self {
+        GtidDeduplicator {
+            executed,
+            ..Default::default()
+        }
+    }
+
+    /// returns whether the event should be delivered
+    pub fn filter(&mut self, event: &Event) -> Result<bool> {
+        match event {
+            Event::GtidLogEvent(e) => {
+                let data = e.clone().into_data()?;
+                let (sid, gno) = (data.encoded_sid, data.encoded_gno);
+                if self.pending.is_some() {
+                    log::debug!("discard incomplete transaction before gtid {}", gno);
+                }
+                self.begun = false;
+                self.skipping = self.executed.contains(sid, gno);
+                if self.skipping {
+                    self.pending = None;
+                    self.dropped_trxs += 1;
+                    self.dropped_events += 1;
+                    log::info!(
+                        "drop duplicate transaction {}:{}, total dropped {}",
+                        super::sid_to_string(sid),
+                        gno,
+                        self.dropped_trxs
+                    );
+                    return Ok(false);
+                }
+                self.pending = Some((sid, gno));
+                Ok(true)
+            }
+            Event::AnonymousGtidLogEvent(_) => {
+                // no way to identify anonymous transaction
+                self.pending = None;
+                self.begun = false;
+                self.skipping = false;
+                Ok(true)
+            }
+            Event::XidEvent(_) => Ok(self.end_trx()),
+            Event::QueryEvent(e) => {
+                // DML in statement-based replication does not end
+                // transaction started by BEGIN
+                match (self.begun, e.clone().into_data()?.trx_boundary()?) {
+                    (false, TrxBoundary::Begin) => {
+                        self.begun = true;
+                        Ok(self.in_trx())
+                    }
+                    (true, TrxBoundary::Commit) | (false, TrxBoundary::ImplicitCommit) => {
+                        Ok(self.end_trx())
+                    }
+                    _ => Ok(self.in_trx()),
+                }
+            }
+            Event::TableMapEvent(_)
+            | Event::WriteRowsEventV1(_)
+            | Event::UpdateRowsEventV1(_)
+            | Event::DeleteRowsEventV1(_)
+            | Event::WriteRowsEventV2(_)
+            | Event::UpdateRowsEventV2(_)
+            | Event::DeleteRowsEventV2(_)
+            | Event::IntvarEvent(_)
+            | Event::RandEvent(_)
+            | Event::UserVarEvent(_) => Ok(self.in_trx()),
+            // events outside of transaction
+            _ => Ok(true),
+        }
+    }
+
+    /// GTIDs of delivered transactions
+    pub fn executed(&self) -> &GtidSet {
+        &self.executed
+    }
+
+    /// number of dropped duplicate transactions
+    pub fn dropped_trxs(&self) -> u64 {
+        self.dropped_trxs
+    }
+
+    /// number of dropped events, including GTID events
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events
+    }
+
+    fn in_trx(&mut self) -> bool {
+        if self.skipping {
+            self.dropped_events += 1;
+        }
+        !self.skipping
+    }
+
+    fn end_trx(&mut self) -> bool {
+        let deliver = self.in_trx();
+        self.begun = false;
+        self.skipping = false;
+        if let Some((sid, gno)) = self.pending.take() {
+            self.executed.add(sid, gno);
+        }
+        deliver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventLength, LogEventType, ParserV4, QueryEvent};
+    use bytes::{Buf, Bytes, BytesMut};
+    use bytes_parser::ReadFromBytes;
+
+    const BINLOG_GTID_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.GtidEvent");
+
+    fn parse_all() -> Vec<Event> {
+        let mut input = Bytes::copy_from_slice(BINLOG_GTID_EVENT);
+        let pv4 = ParserV4::from_binlog_file(&mut input).unwrap();
+        let mut events = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            let mut raw = input.split_to(len);
+            if let Some(event) = pv4.parse_event(&mut raw, false).unwrap() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_gtid_deduplicator() {
+        let events = parse_all();
+        let n_gtids = events
+            .iter()
+            .filter(|e| e.header().type_code == LogEventType::GtidLogEvent)
+            .count();
+        assert!(n_gtids > 0);
+        let mut dedup = GtidDeduplicator::default();
+        for e in &events {
+            assert!(dedup.filter(e).unwrap());
+        }
+        // replay after reconnect
+        for e in &events {
+            let deliver = dedup.filter(e).unwrap();
+            match e.header().type_code {
+                LogEventType::GtidLogEvent | LogEventType::XidEvent => assert!(!deliver),
+                LogEventType::PreviousGtidsLogEvent => assert!(deliver),
+                _ => (),
+            }
+        }
+        assert_eq!(n_gtids as u64, dedup.dropped_trxs());
+        assert!(dedup.dropped_events() > dedup.dropped_trxs());
+    }
+
+    /// query event of given statement, based on another query event
+    fn query(template: &QueryEvent, sql: &str) -> Event {
+        let query_len = template.clone().into_data().unwrap().query.len();
+        let mut data = BytesMut::from(&template.data[..template.data.len() - query_len]);
+        data.extend_from_slice(sql.as_bytes());
+        Event::QueryEvent(QueryEvent::new(template.header.clone(), data.freeze()))
+    }
+
+    #[test]
+    fn test_gtid_deduplicator_statement_based() {
+        let events = parse_all();
+        let gtid = events
+            .iter()
+            .find(|e| matches!(e, Event::GtidLogEvent(_)))
+            .unwrap()
+            .clone();
+        let ddl = match events.iter().find(|e| matches!(e, Event::QueryEvent(_))) {
+            Some(Event::QueryEvent(e)) => e.clone(),
+            other => panic!("unexpected event {:?}", other),
+        };
+        // DML in transaction is logged as query
+        let trx = vec![
+            gtid,
+            query(&ddl, "BEGIN"),
+            query(&ddl, "INSERT INTO t1 VALUES (1)"),
+            query(&ddl, "UPDATE t1 SET c1 = 2"),
+            query(&ddl, "COMMIT"),
+        ];
+        let mut dedup = GtidDeduplicator::default();
+        for e in &trx {
+            assert!(dedup.filter(e).unwrap());
+        }
+        for e in &trx {
+            assert!(!dedup.filter(e).unwrap());
+        }
+        assert_eq!(1, dedup.dropped_trxs());
+        assert_eq!(trx.len() as u64, dedup.dropped_events());
+        // DDL outside of transaction is delivered
+        assert!(dedup
+            .filter(&query(&ddl, "CREATE TABLE t2 (c1 INT)"))
+            .unwrap());
+    }
+}
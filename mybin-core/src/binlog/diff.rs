@@ -0,0 +1,323 @@
+//! compare transactions of two binlog sources
+//!
+//! transactions are aligned by GTID, so the sources can be binlogs of
+//! different servers, e.g. old master and new master after failover.
+//! events are consumed from iterators, e.g. LocalBinlogSet, or events
+//! collected from non-blocking binlog streams.
+//!
+//! transactions without GTID can not be aligned and are only counted.
+use super::ddl;
+use super::{sid_to_string, Event, TableMap};
+use crate::col::BinlogColumnValue;
+use crate::error::Result;
+use linked_hash_map::LinkedHashMap;
+use serde_derive::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// compare row images of rows events
+    pub compare_rows: bool,
+    /// compare statements of QueryEvents other than BEGIN
+    pub compare_statements: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            compare_rows: true,
+            compare_statements: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// DDL statements differ
+    Ddl,
+    /// other statements differ
+    Statement,
+    /// tables or row images differ
+    Rows,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// gtid in form of "uuid:gno"
+    pub gtid: String,
+    pub kind: DivergenceKind,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffReport {
+    /// number of transactions found in both sources
+    pub compared: usize,
+    /// gtids only found in a, in order of a
+    pub only_in_a: Vec<String>,
+    /// gtids only found in b, in order of b
+    pub only_in_b: Vec<String>,
+    pub divergent: Vec<Divergence>,
+    /// number of transactions without gtid in a
+    pub unaligned_a: usize,
+    /// number of transactions without gtid in b
+    pub unaligned_b: usize,
+}
+
+impl DiffReport {
+    pub fn is_consistent(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.divergent.is_empty()
+    }
+}
+
+/// compare transactions of two event sources
+pub fn diff_binlogs<A, B>(a: A, b: B, opts: &DiffOptions) -> Result<DiffReport>
+where
+    A: IntoIterator<Item = Result<Event>>,
+    B: IntoIterator<Item = Result<Event>>,
+{
+    let (trxs_a, unaligned_a) = collect_trxs(a)?;
+    let (mut trxs_b, unaligned_b) = collect_trxs(b)?;
+    let mut report = DiffReport {
+        unaligned_a,
+        unaligned_b,
+        ..Default::default()
+    };
+    for (gtid, trx_a) in trxs_a {
+        let gtid_str = format!("{}:{}", sid_to_string(gtid.0), gtid.1);
+        match trxs_b.remove(&gtid) {
+            Some(trx_b) => {
+                report.compared += 1;
+                if let Some((kind, detail)) = trx_a.compare(&trx_b, opts) {
+                    report.divergent.push(Divergence {
+                        gtid: gtid_str,
+                        kind,
+                        detail,
+                    });
+                }
+            }
+            None => report.only_in_a.push(gtid_str),
+        }
+    }
+    report.only_in_b = trxs_b
+        .keys()
+        .map(|(sid, gno)| format!("{}:{}", sid_to_string(*sid), gno))
+        .collect();
+    Ok(report)
+}
+
+/// content of transaction independent of server
+#[derive(Debug, Default)]
+struct TrxImage {
+    statements: Vec<String>,
+    rows: Vec<RowsImage>,
+}
+
+#[derive(Debug, PartialEq)]
+struct RowsImage {
+    table: String,
+    kind: &'static str,
+    before: Vec<Vec<BinlogColumnValue>>,
+    after: Vec<Vec<BinlogColumnValue>>,
+}
+
+impl TrxImage {
+    fn compare(&self, other: &TrxImage, opts: &DiffOptions) -> Option<(DivergenceKind, String)> {
+        if opts.compare_statements && self.statements != other.statements {
+            let is_ddl = |stmts: &[String]| stmts.iter().any(|s| ddl::classify(s).kind.is_ddl());
+            let kind = if is_ddl(&self.statements) || is_ddl(&other.statements) {
+                DivergenceKind::Ddl
+            } else {
+                DivergenceKind::Statement
+            };
+            return Some((
+                kind,
+                format!("{:?} != {:?}", self.statements, other.statements),
+            ));
+        }
+        if opts.compare_rows {
+            if self.rows.len() != other.rows.len() {
+                return Some((
+                    DivergenceKind::Rows,
+                    format!(
+                        "{} rows events != {} rows events",
+                        self.rows.len(),
+                        other.rows.len()
+                    ),
+                ));
+            }
+            for (i, (ra, rb)) in self.rows.iter().zip(&other.rows).enumerate() {
+                if ra != rb {
+                    return Some((
+                        DivergenceKind::Rows,
+                        format!(
+                            "rows event {} differs: {} on {} != {} on {}",
+                            i, ra.kind, ra.table, rb.kind, rb.table
+                        ),
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+type Gtid = (u128, u64);
+
+/// returns transactions with gtid, and number of those without
+fn collect_trxs<I>(events: I) -> Result<(LinkedHashMap<Gtid, TrxImage>, usize)>
+where
+    I: IntoIterator<Item = Result<Event>>,
+{
+    let mut trxs = LinkedHashMap::new();
+    let mut unaligned = 0;
+    let mut table_maps: HashMap<u64, TableMap> = HashMap::new();
+    // gtid and image of current transaction
+    let mut current: Option<(Option<Gtid>, TrxImage)> = None;
+    for event in events {
+        let event = event?;
+        let end = match &event {
+            Event::GtidLogEvent(e) => {
+                let data = e.clone().into_data()?;
+                current = Some((
+                    Some((data.encoded_sid, data.encoded_gno)),
+                    TrxImage::default(),
+                ));
+                false
+            }
+            Event::AnonymousGtidLogEvent(_) => {
+                current = Some((None, TrxImage::default()));
+                false
+            }
+            Event::XidEvent(_) => true,
+            Event::QueryEvent(e) => {
+                let data = e.clone().into_data()?;
+                let query = data.query_text()?;
+                if query.eq_ignore_ascii_case("BEGIN") {
+                    false
+                } else {
+                    if let Some((_, trx)) = current.as_mut() {
+                        trx.statements.push(query.trim().to_owned());
+                    }
+                    true
+                }
+            }
+            Event::TableMapEvent(e) => {
+                let data = e.clone().into_data()?;
+                table_maps.insert(data.table_id, data.table_map()?);
+                false
+            }
+            Event::WriteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let (Some((_, trx)), Some(tm)) =
+                    (current.as_mut(), table_maps.get(&data.table_id))
+                {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    trx.rows.push(RowsImage {
+                        table: format!("{}.{}", tm.schema_name, tm.table_name),
+                        kind: "insert",
+                        before: vec![],
+                        after: rows.rows.into_iter().map(|r| r.0).collect(),
+                    });
+                }
+                false
+            }
+            Event::DeleteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let (Some((_, trx)), Some(tm)) =
+                    (current.as_mut(), table_maps.get(&data.table_id))
+                {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    trx.rows.push(RowsImage {
+                        table: format!("{}.{}", tm.schema_name, tm.table_name),
+                        kind: "delete",
+                        before: rows.rows.into_iter().map(|r| r.0).collect(),
+                        after: vec![],
+                    });
+                }
+                false
+            }
+            Event::UpdateRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let (Some((_, trx)), Some(tm)) =
+                    (current.as_mut(), table_maps.get(&data.table_id))
+                {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    let (before, after) = rows.rows.into_iter().map(|r| (r.0, r.1)).unzip();
+                    trx.rows.push(RowsImage {
+                        table: format!("{}.{}", tm.schema_name, tm.table_name),
+                        kind: "update",
+                        before,
+                        after,
+                    });
+                }
+                false
+            }
+            _ => false,
+        };
+        if end {
+            match current.take() {
+                Some((Some(gtid), trx)) => {
+                    trxs.insert(gtid, trx);
+                }
+                Some((None, _)) => unaligned += 1,
+                None => (),
+            }
+            table_maps.clear();
+        }
+    }
+    Ok((trxs, unaligned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventLength, ParserV4};
+    use bytes::{Buf, Bytes};
+    use bytes_parser::ReadFromBytes;
+
+    const BINLOG_GTID_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.GtidEvent");
+
+    fn events(input: &[u8], skip_last: usize) -> Vec<Result<Event>> {
+        let mut input = Bytes::copy_from_slice(input);
+        let pv4 = ParserV4::from_binlog_file(&mut input).unwrap();
+        let mut events = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            let mut raw = input.split_to(len);
+            if let Some(event) = pv4.parse_event(&mut raw, false).unwrap() {
+                events.push(Ok(event));
+            }
+        }
+        events.truncate(events.len() - skip_last);
+        events
+    }
+
+    #[test]
+    fn test_diff_binlogs() {
+        let opts = DiffOptions::default();
+        let report = diff_binlogs(
+            events(BINLOG_GTID_EVENT, 0),
+            events(BINLOG_GTID_EVENT, 0),
+            &opts,
+        )
+        .unwrap();
+        assert!(report.is_consistent());
+        let n_trxs = report.compared;
+        assert!(n_trxs > 0);
+        // last transaction is incomplete in b
+        let report = diff_binlogs(
+            events(BINLOG_GTID_EVENT, 0),
+            events(BINLOG_GTID_EVENT, 1),
+            &opts,
+        )
+        .unwrap();
+        assert!(!report.is_consistent());
+        assert_eq!(1, report.only_in_a.len());
+        assert_eq!(n_trxs - 1, report.compared);
+        assert!(report.only_in_b.is_empty());
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"only_in_a\""));
+    }
+}
@@ -0,0 +1,154 @@
+//! BINLOG statements to replay raw events through SQL connection
+//!
+//! server only accepts format description event and row events
+//! in BINLOG statement, and format description event must be
+//! executed first in the session. other events, e.g. query events,
+//! should be executed as plain SQL, after pending statement is flushed.
+use super::LogEventType;
+use crate::error::{Error, Result};
+use bytes::{Bytes, BytesMut};
+use std::convert::TryFrom;
+
+/// length of "BINLOG ''" plus 1-byte command code
+const STMT_OVERHEAD: usize = 10;
+
+/// emitter of BINLOG statements
+///
+/// consecutive row events are merged in one statement until
+/// max_allowed_packet is reached.
+#[derive(Debug)]
+pub struct BinlogStmtEmitter {
+    max_packet: usize,
+    fde: Option<Bytes>,
+    pending: BytesMut,
+}
+
+impl BinlogStmtEmitter {
+    pub fn new(max_allowed_packet: usize) -> Self {
+        BinlogStmtEmitter {
+            max_packet: max_allowed_packet,
+            fde: None,
+            pending: BytesMut::new(),
+        }
+    }
+
+    /// whether event of given type can be replayed by BINLOG statement
+    pub fn accepts(type_code: LogEventType) -> bool {
+        matches!(
+            type_code,
+            LogEventType::FormatDescriptionEvent
+                | LogEventType::TableMapEvent
+                | LogEventType::WriteRowsEventV1
+                | LogEventType::UpdateRowsEventV1
+                | LogEventType::DeleteRowsEventV1
+                | LogEventType::WriteRowsEventV2
+                | LogEventType::UpdateRowsEventV2
+                | LogEventType::DeleteRowsEventV2
+        )
+    }
+
+    /// push raw event including header and checksum,
+    /// returns statements ready to execute
+    pub fn push(&mut self, raw: &[u8]) -> Result<Vec<String>> {
+        if raw.len() < 19 {
+            return Err(Error::BinlogEventError(format!(
+                "event too short: {} bytes",
+                raw.len()
+            )));
+        }
+        let type_code = LogEventType::try_from(raw[4])?;
+        if !Self::accepts(type_code) {
+            return Err(Error::BinlogEventError(format!(
+                "{:?} not allowed in BINLOG statement",
+                type_code
+            )));
+        }
+        if encoded_len(raw.len()) + STMT_OVERHEAD > self.max_packet {
+            return Err(Error::BinlogEventError(format!(
+                "event of {} bytes exceeds max_allowed_packet {}",
+                raw.len(),
+                self.max_packet
+            )));
+        }
+        let mut stmts = vec![];
+        if type_code == LogEventType::FormatDescriptionEvent {
+            stmts.extend(self.flush());
+            let fde = Bytes::copy_from_slice(raw);
+            stmts.push(binlog_stmt(&fde));
+            self.fde.replace(fde);
+            return Ok(stmts);
+        }
+        if self.fde.is_none() {
+            return Err(Error::BinlogEventError(
+                "format description event must be pushed first".to_owned(),
+            ));
+        }
+        if encoded_len(self.pending.len() + raw.len()) + STMT_OVERHEAD > self.max_packet {
+            stmts.extend(self.flush());
+        }
+        self.pending.extend_from_slice(raw);
+        Ok(stmts)
+    }
+
+    /// take pending events as one statement
+    pub fn flush(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let pending = self.pending.split().freeze();
+        Some(binlog_stmt(&pending))
+    }
+
+    /// statement of format description event, which should be
+    /// executed again on new connection
+    pub fn preamble(&self) -> Option<String> {
+        self.fde.as_ref().map(|fde| binlog_stmt(fde))
+    }
+}
+
+fn encoded_len(n: usize) -> usize {
+    n.div_ceil(3) * 4
+}
+
+fn binlog_stmt(raw: &[u8]) -> String {
+    format!("BINLOG '{}'", base64::encode(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::EventLength;
+    use bytes::Buf;
+    use bytes_parser::ReadFromBytes;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RowsEventV2");
+
+    #[test]
+    fn test_binlog_stmt_emitter() -> Result<()> {
+        let mut input = Bytes::copy_from_slice(&BINLOG_ROWS_EVENT_V2[4..]);
+        let mut emitter = BinlogStmtEmitter::new(200);
+        let mut stmts = vec![];
+        let mut skipped = 0;
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone())?.0 as usize;
+            let raw = input.split_to(len);
+            if BinlogStmtEmitter::accepts(LogEventType::try_from(raw[4])?) {
+                stmts.extend(emitter.push(&raw)?);
+            } else {
+                stmts.extend(emitter.flush());
+                skipped += 1;
+            }
+        }
+        stmts.extend(emitter.flush());
+        assert!(skipped > 0);
+        assert_eq!(Some(&stmts[0]), emitter.preamble().as_ref());
+        for stmt in &stmts {
+            assert!(stmt.starts_with("BINLOG '") && stmt.ends_with('\''));
+            assert!(stmt.len() < 200);
+        }
+        // FDE larger than limit
+        let mut emitter = BinlogStmtEmitter::new(100);
+        assert!(emitter.push(&BINLOG_ROWS_EVENT_V2[4..4 + 123]).is_err());
+        Ok(())
+    }
+}
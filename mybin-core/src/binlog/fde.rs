@@ -1,8 +1,9 @@
 //! start event and format description event
 use super::LogEventType;
+use crate::version::ServerVersion;
 use bytes::{Buf, Bytes};
-use bytes_parser::Result;
-use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use bytes_parser::error::{Error, Result};
+use bytes_parser::{checked_sub_len, ReadBytesExt, ReadFromBytes};
 
 /// Data of StartEvent
 ///
@@ -45,6 +46,13 @@ pub struct FormatDescriptionData {
     pub checksum_flag: u8,
 }
 
+impl FormatDescriptionData {
+    /// None if server version is not recognized
+    pub fn version(&self) -> Option<ServerVersion> {
+        ServerVersion::parse(&self.server_version)
+    }
+}
+
 /// because FDE is the first event in binlog, we do not know its post header length,
 /// so we need the total data size as input argument,
 /// which can be calculated by event_length - 19
@@ -67,8 +75,17 @@ impl ReadFromBytes for FormatDescriptionData {
         // we use self contained FDE post header len to check if
         // the checksum flag and checksum value exist
         let fde_type_code = LogEventType::FormatDescriptionEvent;
-        let fde_post_header_len = input[u8::from(fde_type_code) as usize - 1] - 57;
-        if input.remaining() == fde_post_header_len as usize {
+        let fde_idx = u8::from(fde_type_code) as usize - 1;
+        let fde_post_header_len = match input.chunk().get(fde_idx) {
+            Some(n) => checked_sub_len(*n as usize, 57)?,
+            None => {
+                return Err(Error::ConstraintError(format!(
+                    "post header lengths too short: {}",
+                    input.remaining()
+                )))
+            }
+        };
+        if input.remaining() == fde_post_header_len {
             // version not support checksum
             let post_header_lengths = input.split_to(input.remaining());
             let post_header_lengths = Vec::from(post_header_lengths.chunk());
@@ -82,7 +99,7 @@ impl ReadFromBytes for FormatDescriptionData {
             });
         }
         // version supports checksum
-        let post_header_lengths = input.split_to(fde_post_header_len as usize);
+        let post_header_lengths = input.read_len(fde_post_header_len)?;
         let post_header_lengths = Vec::from(post_header_lengths.chunk());
         let checksum_flag = input.read_u8()?;
         // there may be remaining 4-byte crc32 checksum at last or not
@@ -1,8 +1,11 @@
 //! gtid related events and parsing logic
+use crate::cmd::SidRange;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
 use linked_hash_map::LinkedHashMap;
+use std::fmt;
+use std::str::FromStr;
 
 /// Data of GtidEvent
 ///
@@ -122,7 +125,7 @@ impl ReadFromBytes for PreviousGtidsLogData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct GtidSet {
     sids: LinkedHashMap<u128, GtidRange>,
 }
@@ -140,6 +143,266 @@ pub struct GtidInterval {
     pub end: u64,
 }
 
+impl GtidSet {
+    /// gtid ranges of all sids
+    pub fn ranges(&self) -> impl Iterator<Item = &GtidRange> {
+        self.sids.values()
+    }
+
+    pub fn contains(&self, sid: u128, gno: u64) -> bool {
+        self.sids
+            .get(&sid)
+            .map(|range| range.contains(sid, gno))
+            .unwrap_or_default()
+    }
+
+    /// add single gtid, returns false if already exists
+    pub fn add(&mut self, sid: u128, gno: u64) -> bool {
+        self.sids
+            .entry(sid)
+            .or_insert_with(|| GtidRange {
+                sid,
+                intervals: vec![],
+            })
+            .add(gno)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sids.values().all(|range| range.intervals.is_empty())
+    }
+
+    /// gtids in this set but not in other
+    pub fn subtract(&self, other: &GtidSet) -> GtidSet {
+        let mut sids = LinkedHashMap::new();
+        for range in self.ranges() {
+            let intervals = match other.sids.get(&range.sid) {
+                Some(other) => subtract_intervals(&range.intervals, &other.intervals),
+                None => range.intervals.clone(),
+            };
+            if !intervals.is_empty() {
+                sids.insert(
+                    range.sid,
+                    GtidRange {
+                        sid: range.sid,
+                        intervals,
+                    },
+                );
+            }
+        }
+        GtidSet { sids }
+    }
+
+    /// convert sid ranges of binlog dump command, whose interval
+    /// ends are exclusive
+    pub fn from_sid_ranges(sid_ranges: &[SidRange]) -> Self {
+        let mut sids = LinkedHashMap::new();
+        for sr in sid_ranges {
+            let intervals = sr
+                .intervals
+                .iter()
+                .filter(|(start, end)| *start > 0 && end > start)
+                .map(|(start, end)| GtidInterval {
+                    start: *start as u64,
+                    end: *end as u64 - 1,
+                })
+                .collect();
+            sids.insert(
+                sr.sid,
+                GtidRange {
+                    sid: sr.sid,
+                    intervals,
+                },
+            );
+        }
+        GtidSet { sids }
+    }
+}
+
+/// both interval lists are sorted and disjoint
+fn subtract_intervals(from: &[GtidInterval], other: &[GtidInterval]) -> Vec<GtidInterval> {
+    let mut res = vec![];
+    for itv in from {
+        let mut start = itv.start;
+        for o in other {
+            if o.end < start || o.start > itv.end {
+                continue;
+            }
+            if o.start > start {
+                res.push(GtidInterval {
+                    start,
+                    end: o.start - 1,
+                });
+            }
+            start = o.end.saturating_add(1);
+            if start > itv.end {
+                break;
+            }
+        }
+        if start <= itv.end && start > 0 {
+            res.push(GtidInterval {
+                start,
+                end: itv.end,
+            });
+        }
+    }
+    res
+}
+
+/// parse gtid set from text format, ranges are separated by comma,
+/// e.g. value of @@GTID_EXECUTED
+impl FromStr for GtidSet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut sids = LinkedHashMap::new();
+        for range in s.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let range: GtidRange = range.parse()?;
+            sids.insert(range.sid, range);
+        }
+        Ok(GtidSet { sids })
+    }
+}
+
+impl GtidRange {
+    /// whether the gtid is in this range
+    pub fn contains(&self, sid: u128, gno: u64) -> bool {
+        self.sid == sid
+            && self
+                .intervals
+                .iter()
+                .any(|itv| itv.start <= gno && gno <= itv.end)
+    }
+
+    /// max gno of this range
+    pub fn max_gno(&self) -> Option<u64> {
+        self.intervals.iter().map(|itv| itv.end).max()
+    }
+
+    /// add gno and merge adjacent intervals, returns false if
+    /// already exists
+    pub fn add(&mut self, gno: u64) -> bool {
+        // index of first interval ending at or after gno - 1
+        let idx = self
+            .intervals
+            .iter()
+            .position(|itv| itv.end + 1 >= gno)
+            .unwrap_or(self.intervals.len());
+        match self.intervals.get_mut(idx) {
+            Some(itv) if itv.start <= gno && gno <= itv.end => return false,
+            Some(itv) if itv.end + 1 == gno => itv.end = gno,
+            Some(itv) if gno + 1 == itv.start => itv.start = gno,
+            _ => {
+                self.intervals.insert(
+                    idx,
+                    GtidInterval {
+                        start: gno,
+                        end: gno,
+                    },
+                );
+                return true;
+            }
+        }
+        // extended interval may touch the next one
+        if idx + 1 < self.intervals.len()
+            && self.intervals[idx].end + 1 == self.intervals[idx + 1].start
+        {
+            let next = self.intervals.remove(idx + 1);
+            self.intervals[idx].end = next.end;
+        }
+        true
+    }
+}
+
+/// parse gtid range from text format, e.g.
+/// 3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:11
+///
+/// the sid is encoded in the same way as GtidLogData
+impl FromStr for GtidRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().split(':');
+        let uuid = parts.next().unwrap_or_default().replace('-', "");
+        let mut sid_bytes = [0u8; 16];
+        if uuid.len() != 32 {
+            return Err(Error::ConstraintError(format!("invalid gtid sid: {}", s)));
+        }
+        for (i, b) in sid_bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&uuid[i * 2..i * 2 + 2], 16)
+                .map_err(|_| Error::ConstraintError(format!("invalid gtid sid: {}", s)))?;
+        }
+        let sid = u128::from_le_bytes(sid_bytes);
+        let mut intervals = vec![];
+        for itv in parts {
+            let parse_gno = |n: &str| {
+                n.parse::<u64>()
+                    .map_err(|_| Error::ConstraintError(format!("invalid gtid interval: {}", itv)))
+            };
+            let (start, end) = match itv.find('-') {
+                Some(idx) => (parse_gno(&itv[..idx])?, parse_gno(&itv[idx + 1..])?),
+                None => {
+                    let gno = parse_gno(itv)?;
+                    (gno, gno)
+                }
+            };
+            if start == 0 || end < start {
+                return Err(Error::ConstraintError(format!(
+                    "invalid gtid interval: {}",
+                    itv
+                )));
+            }
+            intervals.push(GtidInterval { start, end });
+        }
+        if intervals.is_empty() {
+            return Err(Error::ConstraintError(format!(
+                "gtid range without interval: {}",
+                s
+            )));
+        }
+        Ok(GtidRange { sid, intervals })
+    }
+}
+
+/// format encoded sid as uuid, e.g. 3e11fa47-71ca-11e1-9e33-c80aa9429562
+pub fn sid_to_string(sid: u128) -> String {
+    let hex = hex::encode(sid.to_le_bytes());
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl fmt::Display for GtidRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", sid_to_string(self.sid))?;
+        for itv in &self.intervals {
+            if itv.start == itv.end {
+                write!(f, ":{}", itv.start)?;
+            } else {
+                write!(f, ":{}-{}", itv.start, itv.end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ranges are separated by comma, same as @@GTID_EXECUTED
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, range) in self.ranges().enumerate() {
+            if i > 0 {
+                f.write_str(",\n")?;
+            }
+            write!(f, "{}", range)?;
+        }
+        Ok(())
+    }
+}
+
 /// parse gtid set from payload of PreviousGtidsLogEvent
 ///
 /// reference: https://github.com/mysql/mysql-server/blob/5.7/sql/rpl_gtid_set.cc#L1469
@@ -181,3 +444,70 @@ impl ReadFromBytes for GtidRange {
         Ok(GtidRange { sid, intervals })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gtid_range() {
+        let range: GtidRange = "3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:11"
+            .parse()
+            .unwrap();
+        assert_eq!(2, range.intervals.len());
+        let sid = range.sid;
+        assert!(range.contains(sid, 3));
+        assert!(range.contains(sid, 11));
+        assert!(!range.contains(sid, 6));
+        assert!(!range.contains(sid + 1, 3));
+        assert_eq!(Some(11), range.max_gno());
+        assert_eq!(
+            "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:11",
+            range.to_string()
+        );
+        assert!("3E11FA47-71CA-11E1-9E33-C80AA9429562"
+            .parse::<GtidRange>()
+            .is_err());
+        assert!("3E11FA47:1-5".parse::<GtidRange>().is_err());
+    }
+
+    #[test]
+    fn test_gtid_set_add() {
+        let mut set: GtidSet = "3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:7"
+            .parse()
+            .unwrap();
+        let sid = set.ranges().next().unwrap().sid;
+        assert!(set.contains(sid, 5));
+        assert!(!set.add(sid, 3));
+        assert!(set.add(sid, 6));
+        assert!(set.add(sid, 9));
+        assert!(set.add(sid + 1, 1));
+        assert_eq!(
+            "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-7:9,\n\
+             3f11fa47-71ca-11e1-9e33-c80aa9429562:1",
+            set.to_string()
+        );
+    }
+
+    #[test]
+    fn test_gtid_set_subtract() {
+        let purged: GtidSet = "3E11FA47-71CA-11E1-9E33-C80AA9429562:1-10:20-30"
+            .parse()
+            .unwrap();
+        let sid = purged.ranges().next().unwrap().sid;
+        let requested = GtidSet::from_sid_ranges(&[SidRange {
+            sid,
+            intervals: vec![(1, 4), (6, 25)],
+        }]);
+        assert_eq!(
+            "3e11fa47-71ca-11e1-9e33-c80aa9429562:4-5:25-30",
+            purged.subtract(&requested).to_string()
+        );
+        assert_eq!(
+            "3e11fa47-71ca-11e1-9e33-c80aa9429562:11-19",
+            requested.subtract(&purged).to_string()
+        );
+        assert!(requested.subtract(&requested).is_empty());
+        assert!(purged.subtract(&GtidSet::default()).ranges().count() == 1);
+    }
+}
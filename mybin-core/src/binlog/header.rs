@@ -1,11 +1,13 @@
 use super::LogEventType;
 use bitflags::bitflags;
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use bytes_parser::error::Result;
-use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use bytes_parser::{checked_sub_len, ReadBytesExt, ReadFromBytes, WriteToBytes};
+use chrono::{DateTime, Utc};
 use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EventHeaderV1 {
     pub timestamp: u32,
     pub type_code: LogEventType,
@@ -18,8 +20,8 @@ impl EventHeaderV1 {
     /// NOTE: do not count START_EVENT_V3 and FORMAT_DESCRIPTION_EVENT
     /// because they use EventHeader, not EventHeaderV1
     #[allow(dead_code)]
-    fn data_len(&self) -> u32 {
-        self.event_len - 13
+    fn data_len(&self) -> Result<u32> {
+        Ok(checked_sub_len(self.event_len as usize, 13)? as u32)
     }
 }
 
@@ -57,7 +59,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EventHeader {
     pub timestamp: u32,
     pub type_code: LogEventType,
@@ -68,9 +70,33 @@ pub struct EventHeader {
 }
 
 impl EventHeader {
-    /// always equals event_length - 19
-    pub fn data_len(&self) -> u32 {
-        self.event_len - 19
+    /// always equals event_length - 19, error if event is
+    /// shorter than header
+    pub fn data_len(&self) -> Result<u32> {
+        Ok(checked_sub_len(self.event_len as usize, 19)? as u32)
+    }
+
+    /// time when the statement began executing on the original server
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp as u64)
+    }
+
+    pub fn datetime(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.timestamp as i64, 0).unwrap_or_default()
+    }
+
+    pub fn flags(&self) -> EventHeaderFlags {
+        self.flags
+    }
+
+    /// position of next event, 0 for artificial events
+    pub fn end_position(&self) -> u32 {
+        self.next_pos
+    }
+
+    /// position of this event, only valid if end position is set
+    pub fn start_position(&self) -> u32 {
+        self.next_pos.saturating_sub(self.event_len)
     }
 }
 
@@ -99,3 +125,41 @@ impl ReadFromBytes for EventHeader {
         })
     }
 }
+
+impl WriteToBytes for &EventHeader {
+    fn write_to(self, out: &mut BytesMut) -> Result<usize> {
+        out.put_u32_le(self.timestamp);
+        out.put_u8(self.type_code.into());
+        out.put_u32_le(self.server_id);
+        out.put_u32_le(self.event_len);
+        out.put_u32_le(self.next_pos);
+        out.put_u16_le(self.flags.bits());
+        Ok(19)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_header_accessors() {
+        let mut input = Bytes::from_static(
+            b"\xe7\x3b\x1d\x5f\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x7b\x00\x00\x00\x01\x00",
+        );
+        let header = EventHeader::read_from(&mut input).unwrap();
+        assert_eq!(LogEventType::FormatDescriptionEvent, header.type_code);
+        assert_eq!(123, header.end_position());
+        assert_eq!(4, header.start_position());
+        assert_eq!(EventHeaderFlags::BINLOG_IN_USE, header.flags());
+        assert_eq!(
+            "2020-07-26 08:16:39",
+            header.datetime().format("%F %T").to_string()
+        );
+        assert_eq!(
+            Duration::from_secs(1_595_751_399),
+            header.timestamp().duration_since(UNIX_EPOCH).unwrap()
+        );
+        assert_eq!(header, header.clone());
+    }
+}
@@ -0,0 +1,91 @@
+use bytes::{Buf, Bytes};
+use bytes_parser::error::{Error, Result};
+use bytes_parser::my::ReadMyEnc;
+use bytes_parser::{ReadBytesExt, ReadFromBytes};
+
+/// Data of HeartbeatLogEvent
+///
+/// the position is not in payload, use next_pos of event header instead.
+/// reference: https://dev.mysql.com/doc/internals/en/heartbeat-event.html
+#[derive(Debug, Clone)]
+pub struct HeartbeatData {
+    pub log_filename: Bytes,
+}
+
+impl ReadFromBytes for HeartbeatData {
+    fn read_from(input: &mut Bytes) -> Result<Self> {
+        let log_filename = input.split_to(input.remaining());
+        Ok(HeartbeatData { log_filename })
+    }
+}
+
+const OTW_HB_HEADER_END_MARK: u64 = 0;
+const OTW_HB_LOG_FILENAME_FIELD: u64 = 1;
+const OTW_HB_LOG_POSITION_FIELD: u64 = 2;
+
+/// Data of HeartbeatLogEventV2, introduced in 8.0.26
+///
+/// the payload is a list of type-value fields, which supports
+/// log position larger than 4GB.
+/// reference: https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/src/control_events.cpp#L531
+#[derive(Debug, Clone)]
+pub struct HeartbeatDataV2 {
+    pub log_filename: Bytes,
+    pub log_position: u64,
+}
+
+impl ReadFromBytes for HeartbeatDataV2 {
+    fn read_from(input: &mut Bytes) -> Result<Self> {
+        let mut log_filename = Bytes::new();
+        let mut log_position = 0;
+        while input.has_remaining() {
+            match read_field_u64(input)? {
+                OTW_HB_HEADER_END_MARK => break,
+                OTW_HB_LOG_FILENAME_FIELD => {
+                    let len = read_field_u64(input)?;
+                    log_filename = input.read_len(len as usize)?;
+                }
+                OTW_HB_LOG_POSITION_FIELD => {
+                    // length-encoded position, prefixed by its length
+                    let len = read_field_u64(input)?;
+                    let mut value = input.read_len(len as usize)?;
+                    log_position = read_field_u64(&mut value)?;
+                }
+                other => {
+                    return Err(Error::ConstraintError(format!(
+                        "invalid heartbeat field type {}",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(HeartbeatDataV2 {
+            log_filename,
+            log_position,
+        })
+    }
+}
+
+fn read_field_u64(input: &mut Bytes) -> Result<u64> {
+    let lei = input.read_len_enc_int()?;
+    lei.to_u64()
+        .ok_or_else(|| Error::ConstraintError(format!("invalid heartbeat field {:?}", lei)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_v2() -> Result<()> {
+        let mut input = Bytes::from_static(b"\x01\x10mysql-bin.000001\x02\x03\xfc\x10\x27");
+        let hb = HeartbeatDataV2::read_from(&mut input)?;
+        assert_eq!(&b"mysql-bin.000001"[..], hb.log_filename.chunk());
+        assert_eq!(10000, hb.log_position);
+        assert!(!input.has_remaining());
+        // position of single byte
+        let mut input = Bytes::from_static(b"\x01\x01a\x02\x01\x04");
+        assert_eq!(4, HeartbeatDataV2::read_from(&mut input)?.log_position);
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use bytes::{Buf, Bytes};
+use bytes_parser::error::Result;
+use bytes_parser::ReadFromBytes;
+
+/// Data of IgnorableLogEvent
+///
+/// also used for events of unsupported types flagged IGNORABLE, e.g.
+/// RowsQueryLogEvent, which replicas are allowed to skip. type code
+/// in header is kept as is.
+///
+/// reference: https://dev.mysql.com/doc/internals/en/ignorable-event.html
+#[derive(Debug, Clone)]
+pub struct IgnorableData {
+    pub payload: Bytes,
+}
+
+impl ReadFromBytes for IgnorableData {
+    fn read_from(input: &mut Bytes) -> Result<Self> {
+        let payload = input.split_to(input.remaining());
+        Ok(IgnorableData { payload })
+    }
+}
@@ -1,6 +1,8 @@
-use bytes::Bytes;
-use bytes_parser::error::Result;
+use bytes::{Buf, Bytes};
+use bytes_parser::error::{Error, Result};
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use std::borrow::Cow;
+use std::convert::TryFrom;
 
 /// Data of IncidentEvent
 ///
@@ -14,6 +16,18 @@ pub struct IncidentData {
     pub msg: Bytes,
 }
 
+impl IncidentData {
+    pub fn incident(&self) -> Result<IncidentType> {
+        IncidentType::try_from(self.incident_type)
+    }
+
+    /// message is written in system charset of server, which is always utf8,
+    /// invalid bytes are replaced instead of raising error
+    pub fn message(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.msg.chunk())
+    }
+}
+
 impl ReadFromBytes for IncidentData {
     fn read_from(input: &mut Bytes) -> Result<Self> {
         let incident_type = input.read_le_u16()?;
@@ -26,3 +40,36 @@ impl ReadFromBytes for IncidentData {
         })
     }
 }
+
+/// reference: https://github.com/mysql/mysql-server/blob/5.7/libbinlogevents/include/control_events.h#L664
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IncidentType {
+    /// no incident
+    None,
+    /// there are possibly lost events in the replication stream
+    LostEvents,
+}
+
+impl TryFrom<u16> for IncidentType {
+    type Error = Error;
+
+    fn try_from(code: u16) -> Result<Self> {
+        match code {
+            0 => Ok(IncidentType::None),
+            1 => Ok(IncidentType::LostEvents),
+            _ => Err(Error::ConstraintError(format!(
+                "invalid incident type {}",
+                code
+            ))),
+        }
+    }
+}
+
+impl From<IncidentType> for u16 {
+    fn from(incident: IncidentType) -> u16 {
+        match incident {
+            IncidentType::None => 0,
+            IncidentType::LostEvents => 1,
+        }
+    }
+}
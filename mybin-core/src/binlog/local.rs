@@ -0,0 +1,635 @@
+//! binlog files on local disk
+//!
+//! MySQL keeps the ordered list of binlog files in an index file,
+//! e.g. mysql-bin.index, each line is the path of one binlog file,
+//! relative to the data directory in most cases.
+//!
+//! index files may be copied across platforms, so lines can end with
+//! CRLF, use either separator, and contain bytes not valid in utf8.
+use super::{
+    Event, EventHeader, LogEventType, ParserLimits, ParserState, ParserV4, RotateListener,
+    RotateListeners,
+};
+use crate::error::{Error, Result};
+use bytes::{Buf, Bytes};
+use bytes_parser::ReadFromBytes;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// path of binlog file as recorded by server, independent of the
+/// platform reading it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinlogPath {
+    raw: Vec<u8>,
+}
+
+impl BinlogPath {
+    /// surrounding whitespaces and line endings are removed
+    pub fn from_bytes(bs: &[u8]) -> Self {
+        let start = bs
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(bs.len());
+        let end = bs
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(start, |i| i + 1);
+        BinlogPath {
+            raw: bs[start..end].to_vec(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// components separated by either '/' or '\'
+    pub fn components(&self) -> impl Iterator<Item = &[u8]> {
+        self.raw
+            .split(|b| *b == b'/' || *b == b'\\')
+            .filter(|c| !c.is_empty())
+    }
+
+    pub fn file_name(&self) -> Option<&[u8]> {
+        self.components().last()
+    }
+
+    pub fn file_name_lossy(&self) -> Option<Cow<'_, str>> {
+        self.file_name().map(String::from_utf8_lossy)
+    }
+
+    /// absolute on either platform, e.g. /var/lib/mysql or C:\mysql
+    pub fn is_absolute(&self) -> bool {
+        self.has_root() || self.drive().is_some()
+    }
+
+    fn has_root(&self) -> bool {
+        matches!(self.raw.first(), Some(b'/') | Some(b'\\'))
+    }
+
+    fn drive(&self) -> Option<&[u8]> {
+        match self.raw.get(..2) {
+            Some([letter, b':']) if letter.is_ascii_alphabetic() => Some(&self.raw[..2]),
+            _ => None,
+        }
+    }
+
+    /// path on local platform
+    ///
+    /// relative path is resolved against base directory. absolute path
+    /// of the other platform can not be opened locally, so its file
+    /// name is resolved against base directory instead.
+    pub fn resolve<P: AsRef<Path>>(&self, base_dir: P) -> PathBuf {
+        let base_dir = base_dir.as_ref();
+        let foreign = if cfg!(windows) {
+            self.has_root() && self.drive().is_none()
+        } else {
+            self.drive().is_some() || self.raw.first() == Some(&b'\\')
+        };
+        if foreign {
+            return match self.file_name() {
+                Some(name) => base_dir.join(os_string(name)),
+                None => base_dir.to_path_buf(),
+            };
+        }
+        let mut path = if let Some(drive) = self.drive() {
+            let mut root = os_string(drive);
+            root.push("\\");
+            PathBuf::from(root)
+        } else if self.has_root() {
+            PathBuf::from(std::path::MAIN_SEPARATOR.to_string())
+        } else {
+            base_dir.to_path_buf()
+        };
+        let skip = if self.drive().is_some() { 1 } else { 0 };
+        for c in self.components().skip(skip) {
+            path.push(os_string(c));
+        }
+        path
+    }
+
+    /// whether local path has the same file name
+    pub fn matches_file_name(path: &Path, filename: &[u8]) -> bool {
+        match path.file_name() {
+            Some(name) => os_bytes(name) == filename,
+            None => false,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn os_string(bs: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bs).to_os_string()
+}
+
+#[cfg(not(unix))]
+fn os_string(bs: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bs).into_owned())
+}
+
+#[cfg(unix)]
+fn os_bytes(s: &std::ffi::OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(s.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn os_bytes(s: &std::ffi::OsStr) -> Cow<'_, [u8]> {
+    match s.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+/// parsed binlog index file
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinlogIndex {
+    pub files: Vec<PathBuf>,
+}
+
+impl BinlogIndex {
+    /// parse content of index file
+    ///
+    /// relative paths are resolved against given base directory,
+    /// empty lines are ignored
+    pub fn parse<P: AsRef<Path>>(content: &str, base_dir: P) -> Self {
+        Self::parse_bytes(content.as_bytes(), base_dir)
+    }
+
+    /// parse content of index file which may not be valid utf8
+    pub fn parse_bytes<P: AsRef<Path>>(content: &[u8], base_dir: P) -> Self {
+        let files = content
+            .split(|b| *b == b'\n')
+            .map(BinlogPath::from_bytes)
+            .filter(|path| !path.is_empty())
+            .map(|path| path.resolve(base_dir.as_ref()))
+            .collect();
+        BinlogIndex { files }
+    }
+
+    /// read index file, relative paths are resolved against
+    /// the directory of index file
+    pub fn from_file<P: AsRef<Path>>(index_file: P) -> Result<Self> {
+        let index_file = index_file.as_ref();
+        let content = std::fs::read(index_file)?;
+        let base_dir = index_file.parent().unwrap_or_else(|| Path::new("."));
+        Ok(Self::parse_bytes(&content, base_dir))
+    }
+
+    /// basename of binlog files, e.g. mysql-bin
+    pub fn basename(&self) -> Option<&str> {
+        self.files
+            .first()
+            .and_then(|f| f.file_stem())
+            .and_then(|s| s.to_str())
+    }
+}
+
+/// ordered set of local binlog files
+///
+/// iterates events across file boundaries. when a file ends, the file
+/// named by its last RotateEvent is opened, or the next file in the set
+/// if there is no rotation, e.g. the server crashed.
+#[derive(Debug)]
+pub struct LocalBinlogSet {
+    files: Vec<PathBuf>,
+    // index of next file to open
+    next_idx: usize,
+    current: Option<LocalBinlogFile>,
+    limits: ParserLimits,
+    listeners: RotateListeners,
+    // name of last opened file
+    last_file: String,
+    // checksum of last opened file
+    checksum: Option<bool>,
+}
+
+#[derive(Debug)]
+struct LocalBinlogFile {
+    path: PathBuf,
+    parser: ParserV4,
+    input: Bytes,
+    // length of whole file
+    len: u64,
+    rotate_to: Option<Bytes>,
+}
+
+impl LocalBinlogSet {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        LocalBinlogSet {
+            files,
+            next_idx: 0,
+            current: None,
+            limits: ParserLimits::default(),
+            listeners: RotateListeners::default(),
+            last_file: String::new(),
+            checksum: None,
+        }
+    }
+
+    /// notified when next file is opened
+    pub fn rotate_listener(mut self, listener: Arc<dyn RotateListener>) -> Self {
+        self.listeners.add(listener);
+        self
+    }
+
+    /// limits applied on parser of each file
+    pub fn parser_limits(mut self, limits: ParserLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn from_index_file<P: AsRef<Path>>(index_file: P) -> Result<Self> {
+        let index = BinlogIndex::from_file(index_file)?;
+        Ok(Self::new(index.files))
+    }
+
+    /// path of file currently read
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current.as_ref().map(|f| f.path.as_path())
+    }
+
+    /// offset of next event in current file
+    pub fn position(&self) -> Option<u64> {
+        self.current
+            .as_ref()
+            .map(|f| f.len - f.input.remaining() as u64)
+    }
+
+    /// state of parser of current file
+    pub fn parser_state(&self) -> Option<ParserState> {
+        self.current
+            .as_ref()
+            .map(|f| ParserState::snapshot(&f.parser, f.name()))
+    }
+
+    /// start reading at given offset of a file in the set,
+    /// e.g. position saved in checkpoint
+    ///
+    /// FDE at offset 4 is read to build the parser, but not returned.
+    /// the offset must be the start of an event, which is checked by
+    /// the header found there.
+    pub fn start_at(mut self, filename: &str, pos: u64) -> Result<Self> {
+        self.seek_file(filename.as_bytes())?;
+        let path = self.files[self.next_idx].clone();
+        self.next_idx += 1;
+        let file = LocalBinlogFile::open_at(path, self.limits, pos)?;
+        let name = file.name();
+        let checksum = file.parser.checksum();
+        self.listeners.on_rotate(&self.last_file, &name, pos);
+        self.listeners.on_format_description(&name, checksum);
+        self.last_file = name;
+        self.checksum = Some(checksum);
+        self.current = Some(file);
+        Ok(self)
+    }
+
+    /// returns next event, None if all files are consumed
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            if self.current.is_none() {
+                if self.next_idx >= self.files.len() {
+                    return Ok(None);
+                }
+                let path = self.files[self.next_idx].clone();
+                self.next_idx += 1;
+                let file = LocalBinlogFile::open(path, self.limits)?;
+                let name = file.name();
+                self.listeners.on_rotate(&self.last_file, &name, 4);
+                self.last_file = name;
+                self.current = Some(file);
+            }
+            let file = self.current.as_mut().unwrap();
+            if !file.input.has_remaining() {
+                let rotate_to = file.rotate_to.take();
+                self.current = None;
+                if let Some(next) = rotate_to {
+                    self.seek_file(&next)?;
+                }
+                continue;
+            }
+            // checksum of FDE is calculated with LOG_EVENT_BINLOG_IN_USE_F
+            // flag cleared, skip validation on it
+            let header = EventHeader::read_from(&mut file.input.clone())?;
+            let validate = header.type_code != LogEventType::FormatDescriptionEvent;
+            // unsupported events are skipped
+            if let Some(event) = file.parser.parse_event(&mut file.input, validate)? {
+                match &event {
+                    Event::RotateEvent(raw) => {
+                        let data = raw.clone().into_data()?;
+                        file.rotate_to = Some(data.next_binlog_filename);
+                    }
+                    Event::FormatDescriptionEvent(raw) => {
+                        let data = raw.clone().into_data()?;
+                        let checksum = data.checksum_flag == 1;
+                        self.listeners
+                            .on_format_description(&self.last_file, checksum);
+                        if self.checksum.replace(checksum) == Some(!checksum) {
+                            self.listeners.on_checksum_change(&self.last_file, checksum);
+                        }
+                    }
+                    _ => (),
+                }
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// move cursor to file with given name
+    fn seek_file(&mut self, filename: &[u8]) -> Result<()> {
+        match self
+            .files
+            .iter()
+            .position(|f| BinlogPath::matches_file_name(f, filename))
+        {
+            Some(idx) => {
+                self.next_idx = idx;
+                Ok(())
+            }
+            None => Err(Error::InvalidBinlogFormat(format!(
+                "rotate to binlog file {} not in index",
+                String::from_utf8_lossy(filename)
+            ))),
+        }
+    }
+}
+
+impl Iterator for LocalBinlogSet {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+impl LocalBinlogFile {
+    fn open(path: PathBuf, limits: ParserLimits) -> Result<Self> {
+        log::debug!("open binlog file {:?}", path);
+        let mut input = Bytes::from(std::fs::read(&path)?);
+        let len = input.len() as u64;
+        // parse FDE in advance, and keep it in input
+        let parser = ParserV4::from_binlog_file(&mut input.clone())?.with_limits(limits);
+        input.advance(4);
+        Ok(LocalBinlogFile {
+            path,
+            parser,
+            input,
+            len,
+            rotate_to: None,
+        })
+    }
+
+    /// open file and skip to given offset after FDE
+    fn open_at(path: PathBuf, limits: ParserLimits, pos: u64) -> Result<Self> {
+        let mut file = Self::open(path, limits)?;
+        let fde = EventHeader::read_from(&mut file.input.clone())?;
+        let fde_end = 4 + fde.event_len as u64;
+        if pos < fde_end || pos > file.len {
+            return Err(Error::InvalidBinlogFormat(format!(
+                "start position {} out of range [{}, {}] of {:?}",
+                pos, fde_end, file.len, file.path
+            )));
+        }
+        file.input.advance((pos - 4) as usize);
+        if file.input.has_remaining() {
+            file.check_event_start(pos)?;
+        }
+        Ok(file)
+    }
+
+    /// check whether input starts with a sane event header,
+    /// as it would if positioned at an event boundary
+    fn check_event_start(&self, pos: u64) -> Result<()> {
+        let misaligned = |reason: String| {
+            Error::InvalidBinlogFormat(format!(
+                "position {} of {:?} is not start of event: {}",
+                pos, self.path, reason
+            ))
+        };
+        let header = EventHeader::read_from(&mut self.input.clone())
+            .map_err(|e| misaligned(e.to_string()))?;
+        self.parser
+            .check_event_len(&header)
+            .map_err(|e| misaligned(e.to_string()))?;
+        let end = pos + header.event_len as u64;
+        if end > self.len {
+            return Err(misaligned(format!("event ends at {}", end)));
+        }
+        // next_pos is zero in relay logs of old versions
+        if header.next_pos != 0 && header.next_pos as u64 != end {
+            return Err(misaligned(format!(
+                "next position {} does not match event end {}",
+                header.next_pos, end
+            )));
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+impl TryFrom<BinlogIndex> for LocalBinlogSet {
+    type Error = Error;
+
+    fn try_from(index: BinlogIndex) -> Result<Self> {
+        if index.files.is_empty() {
+            return Err(Error::InvalidBinlogFormat("empty binlog index".to_owned()));
+        }
+        Ok(Self::new(index.files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINLOG_ROTATE_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RotateEvent");
+    const BINLOG_QUERY_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.QueryEvent");
+
+    #[test]
+    fn test_binlog_index_parse() {
+        let index = BinlogIndex::parse(
+            "./mysql-bin.000001\n/var/lib/mysql/mysql-bin.000002\n\n",
+            "/data",
+        );
+        assert_eq!(
+            vec![
+                PathBuf::from("/data/./mysql-bin.000001"),
+                PathBuf::from("/var/lib/mysql/mysql-bin.000002"),
+            ],
+            index.files
+        );
+        assert_eq!(Some("mysql-bin"), index.basename());
+    }
+
+    #[test]
+    fn test_binlog_path_portable() {
+        // index written on windows
+        let index = BinlogIndex::parse(".\\mysql-bin.000001\r\n.\\mysql-bin.000002\r\n", "data");
+        assert_eq!(
+            vec![
+                PathBuf::from("data").join(".").join("mysql-bin.000001"),
+                PathBuf::from("data").join(".").join("mysql-bin.000002"),
+            ],
+            index.files
+        );
+        let path = BinlogPath::from_bytes(b"C:\\ProgramData\\MySQL\\binlog.000003\r");
+        assert!(path.is_absolute());
+        assert_eq!(Some(&b"binlog.000003"[..]), path.file_name());
+        if !cfg!(windows) {
+            // foreign absolute path resolved by file name
+            assert_eq!(PathBuf::from("/data/binlog.000003"), path.resolve("/data"));
+            assert_eq!(
+                PathBuf::from("/var/lib/mysql/binlog.000001"),
+                BinlogPath::from_bytes(b"/var/lib/mysql/binlog.000001").resolve("/data")
+            );
+        }
+        #[cfg(unix)]
+        {
+            // non-utf8 file name is kept as is
+            let index = BinlogIndex::parse_bytes(b"./bin\xff.000001\n", "/data");
+            assert!(BinlogPath::matches_file_name(
+                &index.files[0],
+                b"bin\xff.000001"
+            ));
+            assert!(!BinlogPath::matches_file_name(
+                &index.files[0],
+                "bin\u{fffd}.000001".as_bytes()
+            ));
+        }
+    }
+
+    #[derive(Default)]
+    struct RotateRecorder(std::sync::Mutex<Vec<String>>);
+
+    impl RotateListener for RotateRecorder {
+        fn on_rotate(&self, from: &str, to: &str, _pos: u64) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("rotate {} -> {}", from, to));
+        }
+
+        fn on_format_description(&self, binlog_filename: &str, checksum: bool) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("fde {} {}", binlog_filename, checksum));
+        }
+    }
+
+    #[test]
+    fn test_local_binlog_set_follows_rotate() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mybin-local-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        // find out name of next file from rotate event
+        let mut set = LocalBinlogSet::new(vec![dir.join("mysql-bin.000001")]);
+        std::fs::write(dir.join("mysql-bin.000001"), BINLOG_ROTATE_EVENT)?;
+        let next = set
+            .by_ref()
+            .filter_map(|e| match e {
+                Ok(Event::RotateEvent(raw)) => raw.into_data().ok(),
+                _ => None,
+            })
+            .map(|d| String::from_utf8_lossy(d.next_binlog_filename.chunk()).into_owned())
+            .next()
+            .unwrap();
+        std::fs::write(dir.join(&next), BINLOG_QUERY_EVENT)?;
+        // a stale file between them should be skipped
+        std::fs::write(dir.join("mysql-bin.000000"), BINLOG_QUERY_EVENT)?;
+        std::fs::write(
+            dir.join("mysql-bin.index"),
+            format!("./mysql-bin.000001\n./mysql-bin.000000\n./{}\n", next),
+        )?;
+        let rotations = Arc::new(RotateRecorder::default());
+        let set = LocalBinlogSet::from_index_file(dir.join("mysql-bin.index"))?
+            .rotate_listener(rotations.clone());
+        let events = set.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            vec![
+                "rotate  -> mysql-bin.000001".to_owned(),
+                "fde mysql-bin.000001 true".to_owned(),
+                format!("rotate mysql-bin.000001 -> {}", next),
+                format!("fde {} true", next),
+            ],
+            *rotations.0.lock().unwrap()
+        );
+        let fdes = events
+            .iter()
+            .filter(|e| matches!(e, Event::FormatDescriptionEvent(_)))
+            .count();
+        assert_eq!(2, fdes);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_binlog_set_start_at() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mybin-start-at-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("mysql-bin.000001");
+        std::fs::write(&path, BINLOG_QUERY_EVENT)?;
+        let mut set = LocalBinlogSet::new(vec![path.clone()]);
+        let mut positions = vec![];
+        let mut events = vec![];
+        loop {
+            let pos = set.position();
+            match set.next_event()? {
+                Some(e) => {
+                    positions.push(pos);
+                    events.push(e);
+                }
+                None => break,
+            }
+        }
+        assert!(events.len() > 2);
+        let pos = positions[2].unwrap();
+        // all files consumed
+        assert!(set.parser_state().is_none());
+
+        let rotations = Arc::new(RotateRecorder::default());
+        let set = LocalBinlogSet::new(vec![path.clone()])
+            .rotate_listener(rotations.clone())
+            .start_at("mysql-bin.000001", pos)?;
+        assert_eq!(Some(pos), set.position());
+        assert!(set.parser_state().unwrap().checksum);
+        let resumed = set.collect::<Result<Vec<_>>>()?;
+        assert_eq!(events.len() - 2, resumed.len());
+        for (e1, e2) in events[2..].iter().zip(&resumed) {
+            assert_eq!(e1.header(), e2.header());
+        }
+        assert_eq!(
+            vec![
+                "rotate  -> mysql-bin.000001".to_owned(),
+                "fde mysql-bin.000001 true".to_owned(),
+            ],
+            *rotations.0.lock().unwrap()
+        );
+
+        // inside FDE, not on event boundary, past end of file
+        for pos in &[4, pos + 1, BINLOG_QUERY_EVENT.len() as u64 + 1] {
+            assert!(LocalBinlogSet::new(vec![path.clone()])
+                .start_at("mysql-bin.000001", *pos)
+                .is_err());
+        }
+        // end of file has no more event
+        let mut set = LocalBinlogSet::new(vec![path.clone()])
+            .start_at("mysql-bin.000001", BINLOG_QUERY_EVENT.len() as u64)?;
+        assert!(set.next_event()?.is_none());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,42 @@
+pub mod checkpoint;
+pub mod collation;
+pub mod ddl;
+pub mod dedup;
+pub mod diff;
+pub mod emitter;
 mod fde;
 mod gtid;
 mod header;
+mod heartbeat;
+mod ignorable;
 mod incident;
 mod intvar;
 mod load;
+pub mod local;
+pub mod osc;
 mod parser;
+mod payload;
+pub mod pipe;
+pub mod pk;
+mod position;
+pub mod printer;
+mod projection;
 mod query;
 mod rand;
+pub mod relay;
 mod rotate;
 mod rows_v1;
 pub mod rows_v2;
+pub mod sample;
+pub mod schema;
+pub mod spill;
+pub mod stmt_group;
+mod table_cache;
+pub mod table_filter;
 mod table_map;
+pub mod text;
 pub mod transform;
+pub mod trx;
 mod user_var;
 mod util;
 mod xid;
@@ -21,24 +46,36 @@ use bytes::Bytes;
 use bytes_parser::error::{Error, Result};
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
 use fde::{FormatDescriptionData, StartData};
+pub use gtid::{sid_to_string, GtidInterval, GtidRange, GtidSet};
 use gtid::{AnonymousGtidLogData, GtidLogData, PreviousGtidsLogData};
-pub use header::{EventHeader, EventHeaderV1};
+pub use header::{EventHeader, EventHeaderFlags, EventHeaderV1};
+use heartbeat::{HeartbeatData, HeartbeatDataV2};
+pub use ignorable::IgnorableData;
 use incident::IncidentData;
+pub use incident::IncidentType;
 use intvar::IntvarData;
 use load::*;
-pub use parser::{BinlogVersion, ParserV4};
+pub use parser::{BinlogVersion, ParserLimits, ParserState, ParserV4};
+pub use payload::{CompressionType, TransactionPayloadData};
+pub use position::{OrderingKey, SourcePosition};
+pub use projection::{ColumnRef, Projections};
 use query::QueryData;
+pub use query::TrxBoundary;
 use rand::RandData;
-pub use rotate::RotateData;
+pub use rotate::{RotateData, RotateListener, RotateListeners};
 use rows_v1::{DeleteRowsDataV1, UpdateRowsDataV1, WriteRowsDataV1};
 use rows_v2::{DeleteRowsDataV2, UpdateRowsDataV2, WriteRowsDataV2};
+pub use rows_v2::{ExtraRowInfo, RowsEventFlags};
 use std::convert::TryFrom;
 use std::marker::PhantomData;
+pub use table_cache::{DecodedTableMap, TableMapCache};
 use table_map::TableMapData;
+pub use table_map::{DefaultCharset, TableMap, TableMetadata};
+pub use text::EventText;
 use user_var::UserVarData;
 use xid::XidData;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LogEventType {
     Unknown,
     StartEventV3,
@@ -79,6 +116,9 @@ pub enum LogEventType {
     TransactionContextEvent,
     ViewChangeEvent,
     XaPrepareLogEvent,
+    PartialUpdateRowsEvent,
+    TransactionPayloadEvent,
+    HeartbeatLogEventV2,
 }
 
 impl TryFrom<u8> for LogEventType {
@@ -130,6 +170,11 @@ impl TryFrom<u8> for LogEventType {
             36 => LogEventType::TransactionContextEvent,
             37 => LogEventType::ViewChangeEvent,
             38 => LogEventType::XaPrepareLogEvent,
+            // below is from 8.0 source code
+            // https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/include/binlog_event.h
+            39 => LogEventType::PartialUpdateRowsEvent,
+            40 => LogEventType::TransactionPayloadEvent,
+            41 => LogEventType::HeartbeatLogEventV2,
             _ => {
                 return Err(Error::ConstraintError(format!(
                     "invalid event type code {}",
@@ -183,6 +228,9 @@ impl From<LogEventType> for u8 {
             LogEventType::TransactionContextEvent => 36,
             LogEventType::ViewChangeEvent => 37,
             LogEventType::XaPrepareLogEvent => 38,
+            LogEventType::PartialUpdateRowsEvent => 39,
+            LogEventType::TransactionPayloadEvent => 40,
+            LogEventType::HeartbeatLogEventV2 => 41,
         }
     }
 }
@@ -296,7 +344,17 @@ try_from_event!(UserVarEvent, UserVarData);
 pub type IncidentEvent = RawEvent<IncidentData>;
 try_from_event!(IncidentEvent, IncidentData);
 
-pub type HeartbeatLogEvent = RawEvent<()>;
+pub type HeartbeatLogEvent = RawEvent<HeartbeatData>;
+try_from_event!(HeartbeatLogEvent, HeartbeatData);
+
+pub type HeartbeatLogEventV2 = RawEvent<HeartbeatDataV2>;
+try_from_event!(HeartbeatLogEventV2, HeartbeatDataV2);
+
+pub type TransactionPayloadEvent = RawEvent<TransactionPayloadData>;
+try_from_event!(TransactionPayloadEvent, TransactionPayloadData);
+
+pub type IgnorableLogEvent = RawEvent<IgnorableData>;
+try_from_event!(IgnorableLogEvent, IgnorableData);
 
 pub type TableMapEvent = RawEvent<TableMapData>;
 try_from_event!(TableMapEvent, TableMapData);
@@ -376,6 +434,8 @@ pub enum Event {
     IncidentEvent(IncidentEvent),
     // 27
     HeartbeatLogEvent(HeartbeatLogEvent),
+    // 28, or unsupported event flagged IGNORABLE
+    IgnorableLogEvent(IgnorableLogEvent),
     // 30
     WriteRowsEventV2(WriteRowsEventV2),
     // 31
@@ -388,4 +448,131 @@ pub enum Event {
     AnonymousGtidLogEvent(AnonymousGtidLogEvent),
     // 35
     PreviousGtidsLogEvent(PreviousGtidsLogEvent),
+    // 40
+    TransactionPayloadEvent(TransactionPayloadEvent),
+    // 41
+    HeartbeatLogEventV2(HeartbeatLogEventV2),
+}
+
+impl Event {
+    /// header of the event, available regardless of the payload type
+    pub fn header(&self) -> &EventHeader {
+        match self {
+            Event::StartEventV3(e) => &e.header,
+            Event::QueryEvent(e) => &e.header,
+            Event::StopEvent(e) => &e.header,
+            Event::RotateEvent(e) => &e.header,
+            Event::IntvarEvent(e) => &e.header,
+            Event::LoadEvent(e) => &e.header,
+            Event::CreateFileEvent(e) => &e.header,
+            Event::AppendBlockEvent(e) => &e.header,
+            Event::ExecLoadEvent(e) => &e.header,
+            Event::DeleteFileEvent(e) => &e.header,
+            Event::NewLoadEvent(e) => &e.header,
+            Event::RandEvent(e) => &e.header,
+            Event::UserVarEvent(e) => &e.header,
+            Event::FormatDescriptionEvent(e) => &e.header,
+            Event::XidEvent(e) => &e.header,
+            Event::BeginLoadQueryEvent(e) => &e.header,
+            Event::ExecuteLoadQueryEvent(e) => &e.header,
+            Event::TableMapEvent(e) => &e.header,
+            Event::WriteRowsEventV1(e) => &e.header,
+            Event::UpdateRowsEventV1(e) => &e.header,
+            Event::DeleteRowsEventV1(e) => &e.header,
+            Event::IncidentEvent(e) => &e.header,
+            Event::HeartbeatLogEvent(e) => &e.header,
+            Event::IgnorableLogEvent(e) => &e.header,
+            Event::WriteRowsEventV2(e) => &e.header,
+            Event::UpdateRowsEventV2(e) => &e.header,
+            Event::DeleteRowsEventV2(e) => &e.header,
+            Event::GtidLogEvent(e) => &e.header,
+            Event::AnonymousGtidLogEvent(e) => &e.header,
+            Event::PreviousGtidsLogEvent(e) => &e.header,
+            Event::TransactionPayloadEvent(e) => &e.header,
+            Event::HeartbeatLogEventV2(e) => &e.header,
+        }
+    }
+
+    /// payload of the event, without header and checksum
+    pub fn data(&self) -> &Bytes {
+        match self {
+            Event::StartEventV3(e) => &e.data,
+            Event::QueryEvent(e) => &e.data,
+            Event::StopEvent(e) => &e.data,
+            Event::RotateEvent(e) => &e.data,
+            Event::IntvarEvent(e) => &e.data,
+            Event::LoadEvent(e) => &e.data,
+            Event::CreateFileEvent(e) => &e.data,
+            Event::AppendBlockEvent(e) => &e.data,
+            Event::ExecLoadEvent(e) => &e.data,
+            Event::DeleteFileEvent(e) => &e.data,
+            Event::NewLoadEvent(e) => &e.data,
+            Event::RandEvent(e) => &e.data,
+            Event::UserVarEvent(e) => &e.data,
+            Event::FormatDescriptionEvent(e) => &e.data,
+            Event::XidEvent(e) => &e.data,
+            Event::BeginLoadQueryEvent(e) => &e.data,
+            Event::ExecuteLoadQueryEvent(e) => &e.data,
+            Event::TableMapEvent(e) => &e.data,
+            Event::WriteRowsEventV1(e) => &e.data,
+            Event::UpdateRowsEventV1(e) => &e.data,
+            Event::DeleteRowsEventV1(e) => &e.data,
+            Event::IncidentEvent(e) => &e.data,
+            Event::HeartbeatLogEvent(e) => &e.data,
+            Event::IgnorableLogEvent(e) => &e.data,
+            Event::WriteRowsEventV2(e) => &e.data,
+            Event::UpdateRowsEventV2(e) => &e.data,
+            Event::DeleteRowsEventV2(e) => &e.data,
+            Event::GtidLogEvent(e) => &e.data,
+            Event::AnonymousGtidLogEvent(e) => &e.data,
+            Event::PreviousGtidsLogEvent(e) => &e.data,
+            Event::TransactionPayloadEvent(e) => &e.data,
+            Event::HeartbeatLogEventV2(e) => &e.data,
+        }
+    }
+
+    /// flags of rows event, None if not a rows event
+    pub fn rows_flags(&self) -> Result<Option<RowsEventFlags>> {
+        let flags = match self {
+            Event::WriteRowsEventV1(e) => e.clone().into_data()?.rows_flags(),
+            Event::UpdateRowsEventV1(e) => e.clone().into_data()?.rows_flags(),
+            Event::DeleteRowsEventV1(e) => e.clone().into_data()?.rows_flags(),
+            Event::WriteRowsEventV2(e) => e.clone().into_data()?.rows_flags(),
+            Event::UpdateRowsEventV2(e) => e.clone().into_data()?.rows_flags(),
+            Event::DeleteRowsEventV2(e) => e.clone().into_data()?.rows_flags(),
+            _ => return Ok(None),
+        };
+        Ok(Some(flags))
+    }
+
+    /// table id of rows event, None if not a rows event
+    pub fn rows_table_id(&self) -> Result<Option<u64>> {
+        let table_id = match self {
+            Event::WriteRowsEventV1(e) => e.clone().into_data()?.table_id,
+            Event::UpdateRowsEventV1(e) => e.clone().into_data()?.table_id,
+            Event::DeleteRowsEventV1(e) => e.clone().into_data()?.table_id,
+            Event::WriteRowsEventV2(e) => e.clone().into_data()?.table_id,
+            Event::UpdateRowsEventV2(e) => e.clone().into_data()?.table_id,
+            Event::DeleteRowsEventV2(e) => e.clone().into_data()?.table_id,
+            _ => return Ok(None),
+        };
+        Ok(Some(table_id))
+    }
+
+    /// whether the event can be skipped by replicas, either
+    /// IgnorableLogEvent or unsupported event flagged IGNORABLE
+    pub fn is_ignorable(&self) -> bool {
+        matches!(self, Event::IgnorableLogEvent(_))
+    }
+
+    /// whether the event is heartbeat, either v1 or v2
+    ///
+    /// heartbeats only keep the connection alive and are not
+    /// written in binlog file
+    pub fn is_heartbeat(&self) -> bool {
+        matches!(
+            self,
+            Event::HeartbeatLogEvent(_) | Event::HeartbeatLogEventV2(_)
+        )
+    }
 }
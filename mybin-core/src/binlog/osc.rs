@@ -0,0 +1,288 @@
+//! awareness of online schema change tools
+//!
+//! gh-ost and pt-online-schema-change copy rows into a shadow table
+//! with new definition, then swap it with the original table by
+//! RENAME TABLE. row events of shadow tables duplicate changes of
+//! the original table, so consumers usually drop them.
+//!
+//! shadow tables are recognized by names:
+//! - gh-ost: `_t_gho` (copy), `_t_ghc` (changelog), `_t_del` (old)
+//! - pt-osc: `_t_new` (copy), `_t_old` (old)
+use crate::binlog::ddl::{classify, ObjectName, StatementKind};
+use serde_derive::*;
+use smol_str::SmolStr;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OscTool {
+    GhOst,
+    PtOsc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowKind {
+    /// table with new definition, rows are copied into it
+    Copy,
+    /// gh-ost heartbeat and state of migration
+    Changelog,
+    /// original table after swap
+    Old,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowTable {
+    pub tool: OscTool,
+    pub kind: ShadowKind,
+    /// name of original table
+    pub origin: SmolStr,
+}
+
+impl ShadowTable {
+    /// recognize shadow table by name
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.strip_prefix('_')?;
+        for (suffix, tool, kind) in [
+            ("_gho", OscTool::GhOst, ShadowKind::Copy),
+            ("_ghc", OscTool::GhOst, ShadowKind::Changelog),
+            ("_del", OscTool::GhOst, ShadowKind::Old),
+            ("_new", OscTool::PtOsc, ShadowKind::Copy),
+            ("_old", OscTool::PtOsc, ShadowKind::Old),
+        ] {
+            if let Some(origin) = name.strip_suffix(suffix) {
+                if !origin.is_empty() {
+                    return Some(ShadowTable {
+                        tool,
+                        kind,
+                        origin: SmolStr::new(origin),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// how row events of shadow tables are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowPolicy {
+    /// deliver as ordinary tables
+    Keep,
+    /// drop row events of all shadow tables
+    #[default]
+    Suppress,
+    /// deliver row events of copy table as original table, other
+    /// shadow tables are dropped. changes of original table are
+    /// then received twice during migration
+    MapToOrigin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OscEvent {
+    /// copy table created
+    Started {
+        db: SmolStr,
+        table: SmolStr,
+        tool: OscTool,
+    },
+    /// copy table renamed to original table, definition of the
+    /// table is changed
+    Swapped {
+        db: SmolStr,
+        table: SmolStr,
+        tool: OscTool,
+    },
+    /// copy table dropped before swap
+    Aborted {
+        db: SmolStr,
+        table: SmolStr,
+        tool: OscTool,
+    },
+}
+
+/// tracks migrations by DDL in QueryEvent
+#[derive(Debug, Default)]
+pub struct OscTracker {
+    policy: ShadowPolicy,
+    // (db, original table) -> tool
+    migrations: HashMap<(SmolStr, SmolStr), OscTool>,
+}
+
+impl OscTracker {
+    pub fn new(policy: ShadowPolicy) -> Self {
+        OscTracker {
+            policy,
+            migrations: HashMap::new(),
+        }
+    }
+
+    /// inspect statement of QueryEvent, default_db is the database
+    /// the statement is executed in
+    pub fn on_query(&mut self, default_db: &str, sql: &str) -> Vec<OscEvent> {
+        let c = classify(sql);
+        if !c.kind.is_ddl() {
+            return vec![];
+        }
+        let c = c.with_default_db(default_db);
+        let mut events = vec![];
+        match c.kind {
+            StatementKind::CreateTable => {
+                if let Some((db, shadow)) = copy_table(&c.objects[0]) {
+                    self.migrations
+                        .insert((db.clone(), shadow.origin.clone()), shadow.tool);
+                    events.push(OscEvent::Started {
+                        db,
+                        table: shadow.origin,
+                        tool: shadow.tool,
+                    });
+                }
+            }
+            StatementKind::Rename => {
+                for pair in c.objects.chunks(2) {
+                    if let [from, to] = pair {
+                        if let Some((db, shadow)) = copy_table(from) {
+                            if to.name == shadow.origin {
+                                self.migrations.remove(&(db.clone(), shadow.origin.clone()));
+                                events.push(OscEvent::Swapped {
+                                    db,
+                                    table: shadow.origin,
+                                    tool: shadow.tool,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            StatementKind::DropTable => {
+                for obj in &c.objects {
+                    if let Some((db, shadow)) = copy_table(obj) {
+                        let key = (db.clone(), shadow.origin.clone());
+                        if self.migrations.remove(&key).is_some() {
+                            events.push(OscEvent::Aborted {
+                                db,
+                                table: shadow.origin,
+                                tool: shadow.tool,
+                            });
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+        for e in &events {
+            log::info!("online schema change: {:?}", e);
+        }
+        events
+    }
+
+    /// name rows of the table are delivered as, None if dropped
+    pub fn route<'a>(&self, db: &str, table: &'a str) -> Option<&'a str> {
+        if self.policy == ShadowPolicy::Keep {
+            return Some(table);
+        }
+        let shadow = match ShadowTable::parse(table) {
+            Some(shadow) => shadow,
+            None => return Some(table),
+        };
+        // without seeing the migration start, e.g. stream started
+        // in the middle of it, only gh-ost names are distinctive enough
+        let tracked = self
+            .migrations
+            .contains_key(&(SmolStr::new(db), shadow.origin.clone()));
+        if !tracked && shadow.tool == OscTool::PtOsc {
+            return Some(table);
+        }
+        match (self.policy, shadow.kind) {
+            (ShadowPolicy::MapToOrigin, ShadowKind::Copy) => {
+                // origin is a suffix-stripped slice of table
+                Some(&table[1..1 + shadow.origin.len()])
+            }
+            _ => None,
+        }
+    }
+
+    /// migrations started and not swapped or aborted
+    pub fn migrations(&self) -> impl Iterator<Item = (&str, &str, OscTool)> {
+        self.migrations
+            .iter()
+            .map(|((db, table), tool)| (db.as_str(), table.as_str(), *tool))
+    }
+}
+
+fn copy_table(obj: &ObjectName) -> Option<(SmolStr, ShadowTable)> {
+    let shadow = ShadowTable::parse(&obj.name)?;
+    if shadow.kind != ShadowKind::Copy {
+        return None;
+    }
+    Some((obj.db.clone().unwrap_or_default(), shadow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc_tracker() {
+        assert_eq!(
+            Some(ShadowTable {
+                tool: OscTool::GhOst,
+                kind: ShadowKind::Changelog,
+                origin: "orders".into(),
+            }),
+            ShadowTable::parse("_orders_ghc")
+        );
+        assert!(ShadowTable::parse("orders_new").is_none());
+        assert!(ShadowTable::parse("__new").is_none());
+
+        let mut tracker = OscTracker::new(ShadowPolicy::MapToOrigin);
+        // pt-osc names are not dropped unless migration is seen
+        assert_eq!(Some("_t1_new"), tracker.route("db1", "_t1_new"));
+        assert_eq!(Some("t1"), tracker.route("db1", "_t1_gho"));
+        assert_eq!(None, tracker.route("db1", "_t1_del"));
+        assert_eq!(
+            vec![OscEvent::Started {
+                db: "db1".into(),
+                table: "t1".into(),
+                tool: OscTool::PtOsc,
+            }],
+            tracker.on_query("db1", "CREATE TABLE `_t1_new` LIKE `t1`")
+        );
+        assert_eq!(Some("t1"), tracker.route("db1", "_t1_new"));
+        assert_eq!(Some("t2"), tracker.route("db1", "t2"));
+        assert_eq!(1, tracker.migrations().count());
+        assert_eq!(
+            vec![OscEvent::Swapped {
+                db: "db1".into(),
+                table: "t1".into(),
+                tool: OscTool::PtOsc,
+            }],
+            tracker.on_query(
+                "",
+                "RENAME TABLE `db1`.`t1` TO `db1`.`_t1_old`, `db1`.`_t1_new` TO `db1`.`t1`"
+            )
+        );
+        assert_eq!(0, tracker.migrations().count());
+
+        let mut tracker = OscTracker::new(ShadowPolicy::Suppress);
+        tracker.on_query(
+            "db1",
+            "create /* gh-ost */ table `db1`.`_t1_gho` like `db1`.`t1`",
+        );
+        assert_eq!(None, tracker.route("db1", "_t1_gho"));
+        assert_eq!(None, tracker.route("db1", "_t1_ghc"));
+        assert_eq!(
+            vec![OscEvent::Aborted {
+                db: "db1".into(),
+                table: "t1".into(),
+                tool: OscTool::GhOst,
+            }],
+            tracker.on_query("db1", "drop table if exists `_t1_gho`")
+        );
+        assert!(tracker
+            .on_query("db1", "INSERT INTO _t1_gho VALUES (1)")
+            .is_empty());
+        let tracker = OscTracker::new(ShadowPolicy::Keep);
+        assert_eq!(Some("_t1_gho"), tracker.route("db1", "_t1_gho"));
+    }
+}
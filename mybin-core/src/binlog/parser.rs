@@ -1,8 +1,11 @@
 use super::header::EventHeader;
 use super::*;
+use crate::col::TemporalFormat;
 use crate::util::checksum_crc32;
+use crate::version::ServerVersion;
 use bytes::{Buf, Bytes};
-use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use bytes_parser::{checked_sub_len, ReadBytesExt, ReadFromBytes};
+use serde_derive::*;
 // use bytes_parser::error::{Result, Error};
 use crate::error::{Error, Result};
 
@@ -47,13 +50,72 @@ impl ReadFromBytes for BinlogVersion {
     }
 }
 
-#[derive(Debug)]
+/// guardrails on untrusted input
+///
+/// corrupted header may declare an absurd length, limits are checked
+/// before any buffer is allocated for it.
+/// rows are decoded lazily out of parser, so number of rows is
+/// limited per call of rows_limited() or rows_projected()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParserLimits {
+    /// max length of event including header, 1GB by default
+    /// which is the upper bound of max_allowed_packet
+    pub max_event_size: u32,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        ParserLimits {
+            max_event_size: 1 << 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ParserV4 {
     // post header lengths of all events
     post_header_lengths: Vec<u8>,
     // whether the crc32 checksum is enabled
     // if enabled, will validate the tail 4-byte checksum of all events
     checksum: bool,
+    limits: ParserLimits,
+    // server version of FDE
+    server_version: Option<ServerVersion>,
+}
+
+/// serializable state of parser, to resume parsing in the middle
+/// of a binlog file without reading its FDE again
+///
+/// limits are not part of the state, and should be set again on
+/// the restored parser
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParserState {
+    /// binlog file whose FDE the state is built from
+    pub binlog_filename: String,
+    pub post_header_lengths: Vec<u8>,
+    pub checksum: bool,
+    /// server version in FDE, e.g. 10.3.27-MariaDB
+    pub server_version: Option<String>,
+}
+
+impl ParserState {
+    pub fn snapshot<S: Into<String>>(parser: &ParserV4, binlog_filename: S) -> Self {
+        ParserState {
+            binlog_filename: binlog_filename.into(),
+            post_header_lengths: parser.post_header_lengths.clone(),
+            checksum: parser.checksum,
+            server_version: parser.server_version.map(|v| v.to_string()),
+        }
+    }
+
+    pub fn restore(&self) -> ParserV4 {
+        let mut parser = ParserV4::new(self.post_header_lengths.clone(), self.checksum);
+        parser.server_version = self
+            .server_version
+            .as_deref()
+            .and_then(ServerVersion::parse);
+        parser
+    }
 }
 
 #[allow(dead_code)]
@@ -63,14 +125,58 @@ impl ParserV4 {
         ParserV4 {
             post_header_lengths,
             checksum,
+            limits: ParserLimits::default(),
+            server_version: None,
         }
     }
 
+    pub fn with_limits(mut self, limits: ParserLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &ParserLimits {
+        &self.limits
+    }
+
+    /// whether events end with 4-byte crc32
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// server version of FDE, None if not recognized
+    pub fn server_version(&self) -> Option<ServerVersion> {
+        self.server_version
+    }
+
+    /// layouts of temporal columns by server version of FDE,
+    /// to decode table maps of historical binlogs
+    pub fn temporal_format(&self) -> TemporalFormat {
+        self.server_version
+            .map(|v| v.temporal_format())
+            .unwrap_or(TemporalFormat::Mixed)
+    }
+
+    /// follow FDE of next file, limits are kept
+    ///
+    /// returns true if checksum is changed, e.g. binlog_checksum
+    /// is changed before rotation
+    pub fn update_fde(&mut self, fde: FormatDescriptionData) -> bool {
+        let parser = Self::from_fde(fde);
+        let changed = parser.checksum != self.checksum;
+        self.post_header_lengths = parser.post_header_lengths;
+        self.checksum = parser.checksum;
+        self.server_version = parser.server_version;
+        changed
+    }
+
     /// create parser from given format description event
     pub fn from_fde(fde: FormatDescriptionData) -> Self {
         let post_header_lengths = post_header_lengths_from_raw(fde.post_header_lengths.as_ref());
         let checksum = fde.checksum_flag == 1;
-        ParserV4::new(post_header_lengths, checksum)
+        let mut parser = ParserV4::new(post_header_lengths, checksum);
+        parser.server_version = fde.version();
+        parser
     }
 
     // this function will verify binlog version to be v4
@@ -93,8 +199,9 @@ impl ParserV4 {
     /// this function will additional crc32 code if checksum is enabled
     pub fn from_fde_bytes(input: &mut Bytes) -> Result<(Self, Option<u32>)> {
         let header = EventHeader::read_from(input)?;
+        check_event_len(&header, 0, ParserLimits::default().max_event_size)?;
         // raw data may contains 4 bytes checksum at end
-        let mut raw_data = input.read_len(header.data_len() as usize)?;
+        let mut raw_data = input.read_len(header.data_len()? as usize)?;
         let data = FormatDescriptionData::read_from(&mut raw_data)?;
         let crc32 = if data.checksum_flag == 1 {
             if raw_data.remaining() < 4 {
@@ -114,11 +221,20 @@ impl ParserV4 {
     // verify crc32 checksum if possible
     // for any non-supported event, returns None
     pub fn parse_event(&self, input: &mut Bytes, validate_checksum: bool) -> Result<Option<Event>> {
-        if self.checksum && validate_checksum {
+        let header = EventHeader::read_from(&mut input.clone())?;
+        self.check_event_len(&header)?;
+        // FDE tells checksum of its own file, which may differ from
+        // previous file if binlog_checksum is changed
+        let checksum = if header.type_code == LogEventType::FormatDescriptionEvent {
+            fde_checksum(input, &header)?
+        } else {
+            self.checksum
+        };
+        if checksum && validate_checksum {
             // do not consume original input for checksum
             let header = EventHeader::read_from(&mut input.clone())?;
             let mut raw_data = (&mut input.clone()).read_len(header.event_len as usize)?;
-            let mut checksum_data = raw_data.split_off(raw_data.remaining() - 4);
+            let mut checksum_data = raw_data.split_off(checked_sub_len(raw_data.remaining(), 4)?);
             let expected = checksum_data.read_le_u32()?;
             let actual = checksum_crc32(raw_data.as_ref());
             if expected != actual {
@@ -128,78 +244,40 @@ impl ParserV4 {
 
         let header = EventHeader::read_from(input)?;
         log::debug!("event header={:?}", header);
-        let mut data = input.read_len(header.data_len() as usize)?;
-        if self.checksum {
+        let mut data = input.read_len(header.data_len()? as usize)?;
+        if checksum {
             // need to remove 4-byte crc32 code at end
-            data.truncate(data.remaining() - 4);
+            data.truncate(checked_sub_len(data.remaining(), 4)?);
         }
-        let event = match LogEventType::from(header.type_code) {
-            // UnknownEvent not supported
-            LogEventType::StartEventV3 => Event::StartEventV3(RawEvent::new(header, data)),
-            LogEventType::QueryEvent => Event::QueryEvent(RawEvent::new(header, data)),
-            LogEventType::StopEvent => Event::StopEvent(RawEvent::new(header, data)),
-            LogEventType::RotateEvent => Event::RotateEvent(RawEvent::new(header, data)),
-            LogEventType::IntvarEvent => Event::IntvarEvent(RawEvent::new(header, data)),
-            LogEventType::LoadEvent => Event::LoadEvent(RawEvent::new(header, data)),
-            LogEventType::CreateFileEvent => Event::CreateFileEvent(RawEvent::new(header, data)),
-            LogEventType::AppendBlockEvent => Event::AppendBlockEvent(RawEvent::new(header, data)),
-            LogEventType::ExecLoadEvent => Event::ExecLoadEvent(RawEvent::new(header, data)),
-            LogEventType::DeleteFileEvent => Event::DeleteFileEvent(RawEvent::new(header, data)),
-            LogEventType::NewLoadEvent => Event::NewLoadEvent(RawEvent::new(header, data)),
-            LogEventType::RandEvent => Event::RandEvent(RawEvent::new(header, data)),
-            LogEventType::UserVarEvent => Event::UserVarEvent(RawEvent::new(header, data)),
-            LogEventType::FormatDescriptionEvent => {
-                Event::FormatDescriptionEvent(RawEvent::new(header, data))
-            }
-            LogEventType::XidEvent => Event::XidEvent(RawEvent::new(header, data)),
-            LogEventType::BeginLoadQueryEvent => {
-                Event::BeginLoadQueryEvent(RawEvent::new(header, data))
-            }
-            LogEventType::ExecuteLoadQueryEvent => {
-                Event::ExecuteLoadQueryEvent(RawEvent::new(header, data))
-            }
-            LogEventType::TableMapEvent => Event::TableMapEvent(RawEvent::new(header, data)),
-            // WriteRowsEventV0 not supported
-            // UpdateRowsEventV0 not supported
-            // DeleteRowsEventV0 not supported
-            LogEventType::WriteRowsEventV1 => Event::WriteRowsEventV1(RawEvent::new(header, data)),
-            LogEventType::UpdateRowsEventV1 => {
-                Event::UpdateRowsEventV1(RawEvent::new(header, data))
-            }
-            LogEventType::DeleteRowsEventV1 => {
-                Event::DeleteRowsEventV1(RawEvent::new(header, data))
-            }
-            LogEventType::IncidentEvent => Event::IncidentEvent(RawEvent::new(header, data)),
-            LogEventType::HeartbeatLogEvent => {
-                Event::HeartbeatLogEvent(RawEvent::new(header, data))
-            }
-            // IgnorableLogEvent not supported
-            // RowsQueryLogEvent not supported
-            LogEventType::WriteRowsEventV2 => Event::WriteRowsEventV2(RawEvent::new(header, data)),
-            LogEventType::UpdateRowsEventV2 => {
-                Event::UpdateRowsEventV2(RawEvent::new(header, data))
-            }
-            LogEventType::DeleteRowsEventV2 => {
-                Event::DeleteRowsEventV2(RawEvent::new(header, data))
-            }
-            LogEventType::GtidLogEvent => Event::GtidLogEvent(RawEvent::new(header, data)),
-            LogEventType::AnonymousGtidLogEvent => {
-                Event::AnonymousGtidLogEvent(RawEvent::new(header, data))
-            }
-            LogEventType::PreviousGtidsLogEvent => {
-                Event::PreviousGtidsLogEvent(RawEvent::new(header, data))
+        Ok(new_event(header, data))
+    }
+
+    /// decompress transaction payload and parse events in it
+    ///
+    /// events in payload do not have checksum, and unsupported ones
+    /// are skipped
+    pub fn parse_payload(&self, data: &TransactionPayloadData) -> Result<Vec<Event>> {
+        let mut input = data.decompress()?;
+        let mut inner = self.clone();
+        inner.checksum = false;
+        let mut events = vec![];
+        while input.has_remaining() {
+            if let Some(event) = inner.parse_event(&mut input, false)? {
+                events.push(event);
             }
-            // TransactionContextEvent not supported
-            // ViewChangeEvent not supported
-            // XaPrepareLogEvent not supported
-            _ => return Ok(None),
-        };
-        Ok(Some(event))
+        }
+        Ok(events)
+    }
+
+    pub(crate) fn check_event_len(&self, header: &EventHeader) -> Result<()> {
+        let checksum_len = if self.checksum { 4 } else { 0 };
+        check_event_len(header, checksum_len, self.limits.max_event_size)
     }
 
     pub fn skip_event(&self, input: &mut Bytes) -> Result<()> {
         let header = EventHeader::read_from(input)?;
-        input.read_len(header.data_len() as usize)?;
+        self.check_event_len(&header)?;
+        input.read_len(header.data_len()? as usize)?;
         Ok(())
     }
 
@@ -211,8 +289,9 @@ impl ParserV4 {
         }
         let mut input = input.clone();
         let header = EventHeader::read_from(&mut input.clone())?;
+        self.check_event_len(&header)?;
         let mut raw_data = (&mut input).read_len(header.event_len as usize)?;
-        let mut checksum_data = raw_data.split_off(raw_data.remaining() - 4);
+        let mut checksum_data = raw_data.split_off(checked_sub_len(raw_data.remaining(), 4)?);
         let expected = checksum_data.read_le_u32()?;
         let actual = checksum_crc32(raw_data.as_ref());
         if expected != actual {
@@ -222,6 +301,88 @@ impl ParserV4 {
     }
 }
 
+/// event of given header and payload without checksum,
+/// None if event type is not supported
+pub(crate) fn new_event(header: EventHeader, data: Bytes) -> Option<Event> {
+    let event = match header.type_code {
+        // UnknownEvent not supported
+        LogEventType::StartEventV3 => Event::StartEventV3(RawEvent::new(header, data)),
+        LogEventType::QueryEvent => Event::QueryEvent(RawEvent::new(header, data)),
+        LogEventType::StopEvent => Event::StopEvent(RawEvent::new(header, data)),
+        LogEventType::RotateEvent => Event::RotateEvent(RawEvent::new(header, data)),
+        LogEventType::IntvarEvent => Event::IntvarEvent(RawEvent::new(header, data)),
+        LogEventType::LoadEvent => Event::LoadEvent(RawEvent::new(header, data)),
+        LogEventType::CreateFileEvent => Event::CreateFileEvent(RawEvent::new(header, data)),
+        LogEventType::AppendBlockEvent => Event::AppendBlockEvent(RawEvent::new(header, data)),
+        LogEventType::ExecLoadEvent => Event::ExecLoadEvent(RawEvent::new(header, data)),
+        LogEventType::DeleteFileEvent => Event::DeleteFileEvent(RawEvent::new(header, data)),
+        LogEventType::NewLoadEvent => Event::NewLoadEvent(RawEvent::new(header, data)),
+        LogEventType::RandEvent => Event::RandEvent(RawEvent::new(header, data)),
+        LogEventType::UserVarEvent => Event::UserVarEvent(RawEvent::new(header, data)),
+        LogEventType::FormatDescriptionEvent => {
+            Event::FormatDescriptionEvent(RawEvent::new(header, data))
+        }
+        LogEventType::XidEvent => Event::XidEvent(RawEvent::new(header, data)),
+        LogEventType::BeginLoadQueryEvent => {
+            Event::BeginLoadQueryEvent(RawEvent::new(header, data))
+        }
+        LogEventType::ExecuteLoadQueryEvent => {
+            Event::ExecuteLoadQueryEvent(RawEvent::new(header, data))
+        }
+        LogEventType::TableMapEvent => Event::TableMapEvent(RawEvent::new(header, data)),
+        // WriteRowsEventV0 not supported
+        // UpdateRowsEventV0 not supported
+        // DeleteRowsEventV0 not supported
+        LogEventType::WriteRowsEventV1 => Event::WriteRowsEventV1(RawEvent::new(header, data)),
+        LogEventType::UpdateRowsEventV1 => Event::UpdateRowsEventV1(RawEvent::new(header, data)),
+        LogEventType::DeleteRowsEventV1 => Event::DeleteRowsEventV1(RawEvent::new(header, data)),
+        LogEventType::IncidentEvent => Event::IncidentEvent(RawEvent::new(header, data)),
+        LogEventType::HeartbeatLogEvent => Event::HeartbeatLogEvent(RawEvent::new(header, data)),
+        LogEventType::IgnorableLogEvent => Event::IgnorableLogEvent(RawEvent::new(header, data)),
+        LogEventType::WriteRowsEventV2 => Event::WriteRowsEventV2(RawEvent::new(header, data)),
+        LogEventType::UpdateRowsEventV2 => Event::UpdateRowsEventV2(RawEvent::new(header, data)),
+        LogEventType::DeleteRowsEventV2 => Event::DeleteRowsEventV2(RawEvent::new(header, data)),
+        LogEventType::GtidLogEvent => Event::GtidLogEvent(RawEvent::new(header, data)),
+        LogEventType::AnonymousGtidLogEvent => {
+            Event::AnonymousGtidLogEvent(RawEvent::new(header, data))
+        }
+        LogEventType::PreviousGtidsLogEvent => {
+            Event::PreviousGtidsLogEvent(RawEvent::new(header, data))
+        }
+        // TransactionContextEvent not supported
+        // ViewChangeEvent not supported
+        // XaPrepareLogEvent not supported
+        // PartialUpdateRowsEvent not supported
+        LogEventType::TransactionPayloadEvent => {
+            Event::TransactionPayloadEvent(RawEvent::new(header, data))
+        }
+        LogEventType::HeartbeatLogEventV2 => {
+            Event::HeartbeatLogEventV2(RawEvent::new(header, data))
+        }
+        // unsupported event can be skipped if flagged ignorable,
+        // e.g. RowsQueryLogEvent
+        _ if header.flags.contains(EventHeaderFlags::IGNORABLE) => {
+            Event::IgnorableLogEvent(RawEvent::new(header, data))
+        }
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// event must be long enough to hold header and checksum
+fn check_event_len(header: &EventHeader, checksum_len: u32, max_event_size: u32) -> Result<()> {
+    if header.event_len < 19 + checksum_len {
+        return Err(Error::BinlogEventError(format!(
+            "invalid event length {}",
+            header.event_len
+        )));
+    }
+    if header.event_len > max_event_size {
+        return Err(Error::EventTooLarge(header.event_len, max_event_size));
+    }
+    Ok(())
+}
+
 // raw lengths originated from FDE in binlog file/stream does not include
 // length on UnknownEvent(code=0),
 // we need to push 0 at first position
@@ -232,6 +393,15 @@ fn post_header_lengths_from_raw(raw_lengths: &[u8]) -> Vec<u8> {
     post_header_lengths
 }
 
+/// checksum flag recorded in FDE at start of input
+fn fde_checksum(input: &Bytes, header: &EventHeader) -> Result<bool> {
+    let mut data = input.clone();
+    data.advance(19);
+    let mut data = data.read_len(header.data_len()? as usize)?;
+    let fde = FormatDescriptionData::read_from(&mut data)?;
+    Ok(fde.checksum_flag == 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -481,7 +651,8 @@ mod tests {
     // FDE, PreviousGtid, AnonymousGtid, Query
     #[test]
     fn test_query_event() -> Result<()> {
-        use crate::binlog::query::{Flags2Code, QueryStatusVar, QueryStatusVars, SqlModeCode};
+        use crate::binlog::query::{Flags2Code, QueryStatusVar, QueryStatusVars};
+        use crate::session::SqlMode;
         let input = BINLOG_QUERY_EVENT;
         let mut input = Bytes::copy_from_slice(&input[..]);
         let pv4 = ParserV4::from_binlog_file(&mut input)?;
@@ -493,8 +664,8 @@ mod tests {
         let qe: QueryEvent = qe.unwrap().try_into()?;
         let qe = qe.into_data()?;
         println!("{:#?}", qe);
-        dbg!(std::str::from_utf8(&qe.schema)?);
-        dbg!(std::str::from_utf8(&qe.query)?);
+        dbg!(qe.schema.as_str()?);
+        assert_eq!(qe.query.as_str()?, qe.query_text()?);
         let vars = QueryStatusVars::read_from(&mut qe.status_vars.clone())?;
         println!("{:#?}", vars);
         vars.iter().for_each(|v| match v {
@@ -503,8 +674,9 @@ mod tests {
                 dbg!(f2c);
             }
             QueryStatusVar::SqlModeCode(n) => {
-                let smc = SqlModeCode::from_bits(*n).unwrap();
-                dbg!(smc);
+                let smc = SqlMode::from_bits(*n).unwrap();
+                assert_eq!(Some(smc), qe.sql_mode().unwrap());
+                dbg!(smc.to_string());
             }
             _ => (),
         });
@@ -675,6 +847,7 @@ mod tests {
                     println!("{:#?}", uve);
                     let uvv = UserVarValue::read_from(&mut uve.value)?;
                     println!("{:#?}", uvv);
+                    println!("{:?}", uvv.decode()?);
                 }
                 _ => pv4.skip_event(&mut input)?,
             }
@@ -682,9 +855,112 @@ mod tests {
         Ok(())
     }
 
-    // rename after implementation
     #[test]
-    fn test_incident_event_unimplemented() -> Result<()> {
+    fn test_checksum_change() -> Result<()> {
+        let mut pv4 = ParserV4::from_binlog_file(&mut Bytes::from_static(BINLOG_5_7_30))?;
+        assert!(pv4.checksum());
+        // FDE of next file without checksum
+        let mut input = Bytes::from_static(&BINLOG_NO_CHECKSUM[4..]);
+        let fde = match pv4.parse_event(&mut input, true)? {
+            Some(Event::FormatDescriptionEvent(raw)) => raw.into_data()?,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_eq!(0, fde.checksum_flag);
+        assert!(pv4.update_fde(fde));
+        assert!(!pv4.checksum());
+        while input.has_remaining() {
+            pv4.parse_event(&mut input, true)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_lengths() {
+        // post header length of FDE less than fixed part
+        let mut fde = vec![0u8; 57];
+        fde[0] = 4;
+        fde.extend_from_slice(&[56, 13, 0, 8, 0, 18, 0, 4, 4, 4, 4, 18, 0, 0, 50]);
+        assert!(FormatDescriptionData::read_from(&mut Bytes::from(fde.clone())).is_err());
+        // post header lengths truncated
+        fde.truncate(60);
+        assert!(FormatDescriptionData::read_from(&mut Bytes::from(fde)).is_err());
+        let header = EventHeader {
+            timestamp: 0,
+            type_code: LogEventType::XidEvent,
+            server_id: 1,
+            event_len: 10,
+            next_pos: 0,
+            flags: EventHeaderFlags::empty(),
+        };
+        assert!(header.data_len().is_err());
+    }
+
+    #[test]
+    fn test_incident_event() -> Result<()> {
+        let msg = b"error writing to the binary log";
+        let payload_len = 2 + 1 + msg.len();
+        let event_len = 19 + payload_len as u32;
+        let mut buf = vec![];
+        buf.extend_from_slice(&1600000000u32.to_le_bytes());
+        buf.push(LogEventType::IncidentEvent.into());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&event_len.to_le_bytes());
+        buf.extend_from_slice(&(4 + event_len).to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        buf.push(msg.len() as u8);
+        buf.extend_from_slice(msg);
+        let mut input = Bytes::from(buf);
+        let pv4 = ParserV4::new(vec![], false);
+        let ie = pv4.parse_event(&mut input, false)?;
+        let ie: IncidentEvent = ie.unwrap().try_into()?;
+        let ie = ie.into_data()?;
+        println!("{:#?}", ie);
+        assert_eq!(IncidentType::LostEvents, ie.incident()?);
+        assert_eq!("error writing to the binary log", ie.message());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignorable_event() -> Result<()> {
+        let event = |type_code: LogEventType, flags: EventHeaderFlags| {
+            let payload = b"\x01insert into t values (1)";
+            let event_len = 19 + payload.len() as u32;
+            let mut buf = vec![];
+            buf.extend_from_slice(&1600000000u32.to_le_bytes());
+            buf.push(type_code.into());
+            buf.extend_from_slice(&1u32.to_le_bytes());
+            buf.extend_from_slice(&event_len.to_le_bytes());
+            buf.extend_from_slice(&(4 + event_len).to_le_bytes());
+            buf.extend_from_slice(&flags.bits().to_le_bytes());
+            buf.extend_from_slice(payload);
+            Bytes::from(buf)
+        };
+        let pv4 = ParserV4::new(vec![], false);
+        let ie = pv4
+            .parse_event(
+                &mut event(LogEventType::IgnorableLogEvent, EventHeaderFlags::empty()),
+                false,
+            )?
+            .unwrap();
+        assert!(ie.is_ignorable());
+        // unsupported rows query event is kept only if flagged ignorable
+        let rq = pv4
+            .parse_event(
+                &mut event(LogEventType::RowsQueryLogEvent, EventHeaderFlags::IGNORABLE),
+                false,
+            )?
+            .unwrap();
+        assert!(rq.is_ignorable());
+        assert_eq!(LogEventType::RowsQueryLogEvent, rq.header().type_code);
+        let rq: IgnorableLogEvent = rq.try_into()?;
+        assert_eq!(25, rq.into_data()?.payload.len());
+        assert!(pv4
+            .parse_event(
+                &mut event(LogEventType::RowsQueryLogEvent, EventHeaderFlags::empty()),
+                false,
+            )?
+            .is_none());
         Ok(())
     }
 
@@ -714,6 +990,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parser_limits() -> Result<()> {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        let pv4 = ParserV4::from_binlog_file(&mut input)?;
+        let limits = ParserLimits { max_event_size: 19 };
+        let pv4_limited =
+            ParserV4::from_binlog_file(&mut Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2))?
+                .with_limits(limits);
+        match pv4_limited.parse_event(&mut input.clone(), true) {
+            Err(Error::EventTooLarge(_, 19)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        // corrupted length shorter than header and checksum
+        let mut corrupted = bytes::BytesMut::from(&input[..]);
+        corrupted[9..13].copy_from_slice(&20u32.to_le_bytes());
+        match pv4.parse_event(&mut corrupted.freeze(), true) {
+            Err(Error::BinlogEventError(_)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        for _ in 0..3 {
+            pv4.skip_event(&mut input)?;
+        }
+        let tme: TableMapEvent = pv4.parse_event(&mut input, true)?.unwrap().try_into()?;
+        let tm = tme.into_data()?.into_table_map()?;
+        let wre: WriteRowsEventV2 = pv4.parse_event(&mut input, true)?.unwrap().try_into()?;
+        let wre = wre.into_data()?;
+        match wre.rows_limited(&tm.col_metas, 0) {
+            Err(Error::TooManyRows(0)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let rows = wre.rows_limited(&tm.col_metas, 2)?;
+        assert_eq!(2, rows.rows.len());
+        Ok(())
+    }
+
     #[test]
     fn test_delete_rows_event_v1() -> Result<()> {
         let input = BINLOG_ROWS_EVENT_V1;
@@ -0,0 +1,195 @@
+use crate::error::{Error, Result};
+use bytes::{Buf, Bytes};
+use bytes_parser::my::ReadMyEnc;
+use bytes_parser::{ReadBytesExt, ReadFromBytes};
+
+const OTW_PAYLOAD_HEADER_END_MARK: u64 = 0;
+const OTW_PAYLOAD_SIZE_FIELD: u64 = 1;
+const OTW_PAYLOAD_COMPRESSION_TYPE_FIELD: u64 = 2;
+const OTW_PAYLOAD_UNCOMPRESSED_SIZE_FIELD: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionType {
+    Zstd,
+    None,
+    Unknown(u64),
+}
+
+impl From<u64> for CompressionType {
+    fn from(code: u64) -> Self {
+        match code {
+            0 => CompressionType::Zstd,
+            255 => CompressionType::None,
+            other => CompressionType::Unknown(other),
+        }
+    }
+}
+
+/// Data of TransactionPayloadEvent, introduced in 8.0.20
+///
+/// events of a whole transaction are compressed into the payload
+/// if binlog_transaction_compression is enabled. the header is a list
+/// of type-length-value fields, followed by the payload.
+/// events in payload do not have checksum.
+/// reference: https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/src/compression/payload_event_buffer_istream.cpp
+#[derive(Debug, Clone)]
+pub struct TransactionPayloadData {
+    pub compression_type: CompressionType,
+    pub uncompressed_size: u64,
+    pub payload: Bytes,
+}
+
+impl ReadFromBytes for TransactionPayloadData {
+    fn read_from(input: &mut Bytes) -> bytes_parser::error::Result<Self> {
+        let mut payload_size = None;
+        let mut compression_type = CompressionType::None;
+        let mut uncompressed_size = 0;
+        while input.has_remaining() {
+            let ty = read_field_u64(input)?;
+            if ty == OTW_PAYLOAD_HEADER_END_MARK {
+                break;
+            }
+            let len = read_field_u64(input)? as usize;
+            let mut value = input.read_len(len)?;
+            match ty {
+                OTW_PAYLOAD_SIZE_FIELD => payload_size = Some(read_field_u64(&mut value)?),
+                OTW_PAYLOAD_COMPRESSION_TYPE_FIELD => {
+                    compression_type = CompressionType::from(read_field_u64(&mut value)?)
+                }
+                OTW_PAYLOAD_UNCOMPRESSED_SIZE_FIELD => {
+                    uncompressed_size = read_field_u64(&mut value)?
+                }
+                // unknown fields are skipped by length
+                _ => (),
+            }
+        }
+        let payload = match payload_size {
+            Some(size) => input.read_len(size as usize)?,
+            None => input.split_to(input.remaining()),
+        };
+        Ok(TransactionPayloadData {
+            compression_type,
+            uncompressed_size,
+            payload,
+        })
+    }
+}
+
+impl TransactionPayloadData {
+    /// events of the transaction, concatenated
+    pub fn decompress(&self) -> Result<Bytes> {
+        match self.compression_type {
+            CompressionType::None => Ok(self.payload.clone()),
+            CompressionType::Zstd => decompress_zstd(&self.payload, self.uncompressed_size),
+            CompressionType::Unknown(code) => Err(Error::BinlogEventError(format!(
+                "unknown compression type {} of transaction payload",
+                code
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(payload: &[u8], uncompressed_size: u64) -> Result<Bytes> {
+    // declared size is not trusted for allocation
+    let mut out = Vec::with_capacity(payload.len());
+    zstd::stream::copy_decode(payload, &mut out)?;
+    if out.len() as u64 != uncompressed_size {
+        return Err(Error::BinlogEventError(format!(
+            "transaction payload decompressed to {} bytes, expected {}",
+            out.len(),
+            uncompressed_size
+        )));
+    }
+    Ok(Bytes::from(out))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_payload: &[u8], _uncompressed_size: u64) -> Result<Bytes> {
+    Err(Error::BinlogEventError(
+        "zstd compressed transaction payload requires feature zstd".to_owned(),
+    ))
+}
+
+fn read_field_u64(input: &mut Bytes) -> bytes_parser::error::Result<u64> {
+    let lei = input.read_len_enc_int()?;
+    lei.to_u64().ok_or_else(|| {
+        bytes_parser::error::Error::ConstraintError(format!(
+            "invalid transaction payload field {:?}",
+            lei
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventLength, ParserV4};
+    use bytes::{BufMut, BytesMut};
+    use bytes_parser::my::LenEncInt;
+    use bytes_parser::WriteToBytes;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RowsEventV2");
+
+    fn payload_data(compression_type: u64, payload: &[u8], uncompressed_size: usize) -> Bytes {
+        let mut out = BytesMut::new();
+        for (ty, value) in [
+            (OTW_PAYLOAD_SIZE_FIELD, payload.len() as u64),
+            (OTW_PAYLOAD_COMPRESSION_TYPE_FIELD, compression_type),
+            (
+                OTW_PAYLOAD_UNCOMPRESSED_SIZE_FIELD,
+                uncompressed_size as u64,
+            ),
+        ] {
+            let mut field = BytesMut::new();
+            LenEncInt::from(value).write_to(&mut field).unwrap();
+            LenEncInt::from(ty).write_to(&mut out).unwrap();
+            LenEncInt::from(field.len() as u64)
+                .write_to(&mut out)
+                .unwrap();
+            out.put_slice(&field);
+        }
+        out.put_u8(OTW_PAYLOAD_HEADER_END_MARK as u8);
+        out.put_slice(payload);
+        out.freeze()
+    }
+
+    /// events following FDE in binlog file, and their count
+    fn inner_events() -> (ParserV4, Bytes, usize) {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        let pv4 = ParserV4::from_binlog_file(&mut input).unwrap();
+        let mut n = 0;
+        let mut rest = input.clone();
+        while rest.has_remaining() {
+            let len = EventLength::read_from(&mut rest.clone()).unwrap().0 as usize;
+            rest.advance(len);
+            n += 1;
+        }
+        (pv4, input, n)
+    }
+
+    #[test]
+    fn test_uncompressed_payload() {
+        let (pv4, events, n) = inner_events();
+        let mut input = payload_data(255, &events, events.len());
+        let data = TransactionPayloadData::read_from(&mut input).unwrap();
+        assert_eq!(CompressionType::None, data.compression_type);
+        assert_eq!(events.len() as u64, data.uncompressed_size);
+        assert_eq!(n, pv4.parse_payload(&data).unwrap().len());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_payload() {
+        let (pv4, events, n) = inner_events();
+        let compressed = zstd::stream::encode_all(&events[..], 0).unwrap();
+        let mut input = payload_data(0, &compressed, events.len());
+        let data = TransactionPayloadData::read_from(&mut input).unwrap();
+        assert_eq!(CompressionType::Zstd, data.compression_type);
+        assert_eq!(n, pv4.parse_payload(&data).unwrap().len());
+        // size mismatch is detected
+        let mut input = payload_data(0, &compressed, events.len() + 1);
+        let data = TransactionPayloadData::read_from(&mut input).unwrap();
+        assert!(pv4.parse_payload(&data).is_err());
+    }
+}
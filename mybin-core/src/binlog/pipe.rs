@@ -0,0 +1,305 @@
+//! binlog read from a pipe, e.g. output of mysqlbinlog --raw
+//!
+//! the stream is plain binlog format, possibly concatenated across
+//! files. a new file starts with the magic number followed by
+//! FormatDescriptionEvent, which is detected at event boundaries.
+use super::{
+    Event, EventHeader, LogEventType, ParserLimits, ParserState, ParserV4, RotateListener,
+    RotateListeners,
+};
+use crate::error::{Error, Result};
+use bytes::{Bytes, BytesMut};
+use bytes_parser::ReadFromBytes;
+use std::io::{ErrorKind, Read};
+use std::sync::Arc;
+
+const BINLOG_MAGIC: &[u8] = b"\xfebin";
+const EVENT_HEADER_LEN: usize = 19;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// reads events from any reader, without loading whole file
+#[derive(Debug)]
+pub struct PipeBinlogReader<R> {
+    reader: R,
+    buf: BytesMut,
+    // None before FDE of current file
+    parser: Option<ParserV4>,
+    limits: ParserLimits,
+    listeners: RotateListeners,
+    // name of current file, known from rotate event of previous file
+    current_file: String,
+    rotate_to: Option<String>,
+    // offset in current file
+    pos: u64,
+    // checksum of previous file
+    checksum: Option<bool>,
+}
+
+impl<R: Read> PipeBinlogReader<R> {
+    pub fn new(reader: R) -> Self {
+        PipeBinlogReader {
+            reader,
+            buf: BytesMut::new(),
+            parser: None,
+            limits: ParserLimits::default(),
+            listeners: RotateListeners::default(),
+            current_file: String::new(),
+            rotate_to: None,
+            pos: 0,
+            checksum: None,
+        }
+    }
+
+    /// resume stream positioned at given offset of a file, with
+    /// parser state saved when reading the same file
+    pub fn resume(reader: R, state: &ParserState, pos: u64) -> Self {
+        let mut pipe = Self::new(reader);
+        pipe.parser = Some(state.restore());
+        pipe.current_file = state.binlog_filename.clone();
+        pipe.pos = pos;
+        pipe.checksum = Some(state.checksum);
+        pipe
+    }
+
+    /// notified when next file starts in the stream
+    pub fn rotate_listener(mut self, listener: Arc<dyn RotateListener>) -> Self {
+        self.listeners.add(listener);
+        self
+    }
+
+    /// limits applied on parser of each file
+    pub fn parser_limits(mut self, limits: ParserLimits) -> Self {
+        self.limits = limits;
+        self.parser = self.parser.take().map(|p| p.with_limits(limits));
+        self
+    }
+
+    /// name of current file, empty if the stream does not tell it,
+    /// e.g. the first file
+    pub fn current_file(&self) -> &str {
+        &self.current_file
+    }
+
+    /// offset of next event in current file
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// state of parser of current file, None before its FDE
+    pub fn parser_state(&self) -> Option<ParserState> {
+        self.parser
+            .as_ref()
+            .map(|p| ParserState::snapshot(p, self.current_file.clone()))
+    }
+
+    /// returns next event, None if the stream ends at event boundary
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            if !self.fill(BINLOG_MAGIC.len())? {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(truncated(self.buf.len()));
+            }
+            if self.at_file_start()? {
+                let _ = self.buf.split_to(BINLOG_MAGIC.len());
+                self.parser = None;
+                self.pos = BINLOG_MAGIC.len() as u64;
+                let name = self.rotate_to.take().unwrap_or_default();
+                self.listeners
+                    .on_rotate(&self.current_file, &name, self.pos);
+                self.current_file = name;
+                continue;
+            }
+            if self.pos == 0 {
+                return Err(Error::InvalidBinlogFormat(
+                    "missing magic number at start of binlog stream".to_owned(),
+                ));
+            }
+            if !self.fill(EVENT_HEADER_LEN)? {
+                return Err(truncated(self.buf.len()));
+            }
+            let header =
+                EventHeader::read_from(&mut Bytes::copy_from_slice(&self.buf[..EVENT_HEADER_LEN]))?;
+            // check length before reading the event into buffer
+            if header.event_len > self.limits.max_event_size {
+                return Err(Error::EventTooLarge(
+                    header.event_len,
+                    self.limits.max_event_size,
+                ));
+            }
+            if !self.fill(header.event_len as usize)? {
+                return Err(truncated(self.buf.len()));
+            }
+            let mut input = self.buf.split_to(header.event_len as usize).freeze();
+            self.pos += header.event_len as u64;
+            let parser = match self.parser.take() {
+                Some(parser) => parser,
+                None => {
+                    if header.type_code != LogEventType::FormatDescriptionEvent {
+                        return Err(Error::InvalidBinlogFormat(format!(
+                            "expect FormatDescriptionEvent at start of file, got {:?}",
+                            header.type_code
+                        )));
+                    }
+                    let (parser, crc32) = ParserV4::from_fde_bytes(&mut input.clone())?;
+                    let checksum = crc32.is_some();
+                    self.listeners
+                        .on_format_description(&self.current_file, checksum);
+                    if self.checksum.replace(checksum) == Some(!checksum) {
+                        self.listeners
+                            .on_checksum_change(&self.current_file, checksum);
+                    }
+                    parser.with_limits(self.limits)
+                }
+            };
+            // checksum of FDE is calculated with LOG_EVENT_BINLOG_IN_USE_F
+            // flag cleared, skip validation on it
+            let validate = header.type_code != LogEventType::FormatDescriptionEvent;
+            let event = parser.parse_event(&mut input, validate);
+            self.parser = Some(parser);
+            // unsupported events are skipped
+            if let Some(event) = event? {
+                if let Event::RotateEvent(raw) = &event {
+                    let data = raw.clone().into_data()?;
+                    self.rotate_to = Some(
+                        String::from_utf8_lossy(data.next_binlog_filename.as_ref()).into_owned(),
+                    );
+                }
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// magic number at stream start, or mid-stream followed by FDE
+    ///
+    /// timestamp of an ordinary event may look like the magic number,
+    /// so the type code after it is checked as well
+    fn at_file_start(&mut self) -> Result<bool> {
+        if &self.buf[..BINLOG_MAGIC.len()] != BINLOG_MAGIC {
+            return Ok(false);
+        }
+        if self.parser.is_none() {
+            return Ok(true);
+        }
+        // magic, timestamp, type code
+        if !self.fill(BINLOG_MAGIC.len() + 5)? {
+            return Ok(false);
+        }
+        Ok(self.buf[BINLOG_MAGIC.len() + 4] == u8::from(LogEventType::FormatDescriptionEvent))
+    }
+
+    /// read until buffer has n bytes, false if stream ends before that
+    fn fill(&mut self, n: usize) -> Result<bool> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        while self.buf.len() < n {
+            let len = (n - self.buf.len()).clamp(EVENT_HEADER_LEN, READ_CHUNK_SIZE);
+            match self.reader.read(&mut chunk[..len]) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.buf.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for PipeBinlogReader<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+fn truncated(len: usize) -> Error {
+    Error::InvalidBinlogFormat(format!("binlog stream truncated with {} bytes left", len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const BINLOG_ROTATE_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RotateEvent");
+    const BINLOG_QUERY_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.QueryEvent");
+
+    // returns at most 7 bytes on each read, as a pipe may do
+    struct SlowReader<'a>(&'a [u8]);
+
+    impl Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(7).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[derive(Default)]
+    struct RotateRecorder(Mutex<Vec<String>>);
+
+    impl RotateListener for RotateRecorder {
+        fn on_rotate(&self, from: &str, to: &str, _pos: u64) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("rotate {} -> {}", from, to));
+        }
+    }
+
+    #[test]
+    fn test_pipe_binlog_reader() -> Result<()> {
+        let mut stream = BINLOG_ROTATE_EVENT.to_vec();
+        stream.extend_from_slice(BINLOG_QUERY_EVENT);
+        let rotations = Arc::new(RotateRecorder::default());
+        let reader = PipeBinlogReader::new(SlowReader(&stream)).rotate_listener(rotations.clone());
+        let events = reader.collect::<Result<Vec<_>>>()?;
+        let fdes = events
+            .iter()
+            .filter(|e| matches!(e, Event::FormatDescriptionEvent(_)))
+            .count();
+        assert_eq!(2, fdes);
+        let rotations = rotations.0.lock().unwrap();
+        assert_eq!(2, rotations.len());
+        assert_eq!("rotate  -> ", rotations[0]);
+        assert!(rotations[1].starts_with("rotate  -> mysql-bin."));
+
+        // stream cut in the middle of an event
+        let reader = PipeBinlogReader::new(SlowReader(&stream[..stream.len() - 1]));
+        assert!(reader.collect::<Result<Vec<_>>>().is_err());
+        // stream without magic number
+        let mut reader = PipeBinlogReader::new(SlowReader(&BINLOG_QUERY_EVENT[4..]));
+        assert!(reader.next_event().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pipe_resume_from_state() -> Result<()> {
+        let mut reader = PipeBinlogReader::new(SlowReader(BINLOG_QUERY_EVENT));
+        // FDE and the event after it
+        reader.next_event()?.unwrap();
+        reader.next_event()?.unwrap();
+        let pos = reader.position();
+        let state = reader.parser_state().unwrap();
+        let rest = reader.collect::<Result<Vec<_>>>()?;
+        assert!(!rest.is_empty());
+
+        let json = serde_json::to_string(&state).unwrap();
+        let state: ParserState = serde_json::from_str(&json).unwrap();
+        assert_eq!(Some("5.7.30"), state.server_version.as_deref());
+        let restored = state.restore();
+        assert_eq!(state.checksum, restored.checksum());
+        assert!(restored.server_version().is_some());
+
+        let resumed =
+            PipeBinlogReader::resume(SlowReader(&BINLOG_QUERY_EVENT[pos as usize..]), &state, pos);
+        let events = resumed.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rest.len(), events.len());
+        for (e1, e2) in rest.iter().zip(&events) {
+            assert_eq!(e1.header(), e2.header());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! canonical primary key of row images
+//!
+//! values are normalized regardless of their storage types, e.g.
+//! signed and unsigned integers are both converted to i128,
+//! so keys of the same row extracted from different images are equal.
+//! string values are compared by sort keys of column collation, so
+//! keys equal in server are also equal here, while original values
+//! are kept for display.
+use super::collation::Collation;
+use crate::bitmap;
+use crate::col::{BinaryColumnValue, BinlogColumnValue, ColumnDefinition, ColumnFlags};
+use crate::error::Result;
+use crate::stmt::StmtColumnValue;
+use bigdecimal::BigDecimal;
+use bytes::Buf;
+use smol_str::SmolStr;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// primary key columns of a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimaryKeyDef {
+    cols: Vec<PkCol>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PkCol {
+    idx: usize,
+    name: SmolStr,
+    unsigned: bool,
+    collation: Collation,
+}
+
+impl PrimaryKeyDef {
+    /// returns None if table does not have primary key
+    pub fn from_col_defs(col_defs: &[ColumnDefinition]) -> Option<Self> {
+        let cols: Vec<_> = col_defs
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| def.flags.contains(ColumnFlags::PRIMARY_KEY))
+            .map(|(idx, def)| PkCol {
+                idx,
+                name: def.name.clone(),
+                unsigned: def.flags.contains(ColumnFlags::UNSIGNED),
+                collation: Collation::new(def.charset),
+            })
+            .collect();
+        if cols.is_empty() {
+            None
+        } else {
+            Some(PrimaryKeyDef { cols })
+        }
+    }
+
+    pub fn col_names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.cols.iter().map(|col| &col.name)
+    }
+
+    /// extract primary key from row image
+    ///
+    /// returns None if any key column is absent in the image
+    pub fn extract(
+        &self,
+        present_bitmap: &[u8],
+        row: &[BinlogColumnValue],
+    ) -> Result<Option<PrimaryKey>> {
+        let mut values = Vec::with_capacity(self.cols.len());
+        for col in &self.cols {
+            if !bitmap::index(present_bitmap, col.idx) {
+                return Ok(None);
+            }
+            // position in row is number of present columns before it
+            let pos = bitmap::to_iter(present_bitmap, 0)
+                .take(col.idx)
+                .filter(|present| *present)
+                .count();
+            let value = match row.get(pos) {
+                Some(value) => value.clone(),
+                None => return Ok(None),
+            };
+            let value = PkValue::new(StmtColumnValue::from((value, col.unsigned)))?;
+            values.push(value.collate(&col.collation));
+        }
+        Ok(Some(PrimaryKey(values)))
+    }
+}
+
+/// normalized value of key column
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PkValue {
+    Null,
+    Int(i128),
+    Decimal(BigDecimal),
+    /// date and time in text format
+    Text(String),
+    Bytes(Vec<u8>),
+    /// string of non-binary collation
+    Collated(CollatedBytes),
+}
+
+/// string value compared by sort key of its collation
+#[derive(Debug, Clone)]
+pub struct CollatedBytes {
+    pub value: Vec<u8>,
+    pub sort_key: Vec<u8>,
+}
+
+impl PartialEq for CollatedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.sort_key == other.sort_key
+    }
+}
+
+impl Eq for CollatedBytes {}
+
+impl PartialOrd for CollatedBytes {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CollatedBytes {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key.cmp(&other.sort_key)
+    }
+}
+
+impl Hash for CollatedBytes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sort_key.hash(state)
+    }
+}
+
+impl PkValue {
+    fn new(value: StmtColumnValue) -> Result<Self> {
+        let v = match &value.val {
+            BinaryColumnValue::Null => PkValue::Null,
+            BinaryColumnValue::Tiny(_)
+            | BinaryColumnValue::Short(_)
+            | BinaryColumnValue::Long(_)
+            | BinaryColumnValue::LongLong(_)
+            | BinaryColumnValue::Int24(_)
+            | BinaryColumnValue::Year(_)
+            | BinaryColumnValue::Bit(_) => PkValue::Int(value.to_sql_literal().0.parse()?),
+            BinaryColumnValue::Float(_)
+            | BinaryColumnValue::Double(_)
+            | BinaryColumnValue::NewDecimal(_) => {
+                PkValue::Decimal(BigDecimal::from_str(&value.to_sql_literal().0)?)
+            }
+            BinaryColumnValue::Timestamp(_)
+            | BinaryColumnValue::Date { .. }
+            | BinaryColumnValue::Time(_)
+            | BinaryColumnValue::DateTime(_) => {
+                PkValue::Text(value.to_sql_literal().0.into_owned())
+            }
+            BinaryColumnValue::Blob(bs)
+            | BinaryColumnValue::VarString(bs)
+            | BinaryColumnValue::String(bs)
+            | BinaryColumnValue::Geometry(bs) => PkValue::Bytes(bs.chunk().to_vec()),
+        };
+        Ok(v)
+    }
+
+    fn collate(self, collation: &Collation) -> Self {
+        match self {
+            PkValue::Bytes(value) if !collation.is_binary() => {
+                let sort_key = collation.sort_key(&value).into_owned();
+                PkValue::Collated(CollatedBytes { value, sort_key })
+            }
+            v => v,
+        }
+    }
+}
+
+impl fmt::Display for PkValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PkValue::Null => write!(f, "NULL"),
+            PkValue::Int(n) => write!(f, "{}", n),
+            PkValue::Decimal(d) => write!(f, "{}", d),
+            PkValue::Text(s) => write!(f, "'{}'", s),
+            PkValue::Bytes(bs) | PkValue::Collated(CollatedBytes { value: bs, .. }) => {
+                match std::str::from_utf8(bs) {
+                    Ok(s) => write!(f, "'{}'", s.replace('\'', "''")),
+                    Err(_) => write!(f, "x'{}'", hex::encode(bs)),
+                }
+            }
+        }
+    }
+}
+
+/// primary key extracted from row image
+///
+/// ordered by key columns in table definition order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrimaryKey(pub Vec<PkValue>);
+
+impl PrimaryKey {
+    /// 64-bit FNV-1a hash of the key
+    ///
+    /// unlike Hash trait, the result is stable across processes and
+    /// versions, so it can be used for partitioning
+    pub fn pk_hash(&self) -> u64 {
+        let mut h = Fnv1a::default();
+        for v in &self.0 {
+            match v {
+                PkValue::Null => h.write(&[0]),
+                PkValue::Int(n) => {
+                    h.write(&[1]);
+                    h.write(&n.to_le_bytes());
+                }
+                PkValue::Decimal(d) => {
+                    h.write(&[2]);
+                    h.write_len_bytes(d.to_string().as_bytes());
+                }
+                PkValue::Text(s) => {
+                    h.write(&[3]);
+                    h.write_len_bytes(s.as_bytes());
+                }
+                PkValue::Bytes(bs) | PkValue::Collated(CollatedBytes { sort_key: bs, .. }) => {
+                    h.write(&[4]);
+                    h.write_len_bytes(bs);
+                }
+            }
+        }
+        h.0
+    }
+}
+
+impl fmt::Display for PrimaryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, v) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// 64-bit FNV-1a, stable across processes and versions
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, bs: &[u8]) {
+        for b in bs {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_len_bytes(&mut self, bs: &[u8]) {
+        self.write(&(bs.len() as u64).to_le_bytes());
+        self.write(bs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::tests::col_def;
+    use crate::col::ColumnType;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_primary_key() {
+        let col_defs = vec![
+            col_def("v", ColumnType::Long, ColumnFlags::empty()),
+            col_def(
+                "id",
+                ColumnType::Long,
+                ColumnFlags::PRIMARY_KEY | ColumnFlags::UNSIGNED,
+            ),
+            col_def("name", ColumnType::VarString, ColumnFlags::PRIMARY_KEY),
+        ];
+        let pk_def = PrimaryKeyDef::from_col_defs(&col_defs).unwrap();
+        let full = pk_def
+            .extract(
+                &[0b111],
+                &[
+                    BinlogColumnValue::Long(1),
+                    BinlogColumnValue::Long(0xffff_ffff),
+                    BinlogColumnValue::VarString(Bytes::from("a'b")),
+                ],
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!("(4294967295, 'a''b')", full.to_string());
+        // minimal image without non-key column
+        let minimal = pk_def
+            .extract(
+                &[0b110],
+                &[
+                    BinlogColumnValue::Long(0xffff_ffff),
+                    BinlogColumnValue::VarString(Bytes::from("A'b ")),
+                ],
+            )
+            .unwrap()
+            .unwrap();
+        // equal in utf8_general_ci, displayed as is
+        assert_eq!("(4294967295, 'A''b ')", minimal.to_string());
+        assert_eq!(full, minimal);
+        assert_eq!(full.pk_hash(), minimal.pk_hash());
+        // key column absent
+        assert!(pk_def
+            .extract(
+                &[0b011],
+                &[BinlogColumnValue::Long(1), BinlogColumnValue::Long(2)]
+            )
+            .unwrap()
+            .is_none());
+        let smaller = PrimaryKey(vec![PkValue::Int(9), PkValue::Bytes(b"z".to_vec())]);
+        assert!(smaller < full);
+    }
+}
@@ -0,0 +1,204 @@
+//! provenance of events and transactions
+use super::gtid::sid_to_string;
+use super::EventHeader;
+use crate::error::{Error, Result};
+use serde_derive::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// where an event or a transaction comes from
+///
+/// positions are 0 for artificial events generated by master.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourcePosition {
+    pub binlog_filename: String,
+    /// position of first event
+    pub start_pos: u64,
+    /// end position of last event
+    pub end_pos: u64,
+    /// (encoded_sid, gno) of enclosing transaction, None if gtid
+    /// is not enabled or event is out of transaction
+    pub gtid: Option<(u128, u64)>,
+    pub server_id: u32,
+    /// timestamp of last event
+    pub timestamp: u32,
+}
+
+impl SourcePosition {
+    pub fn of_event(
+        binlog_filename: &str,
+        header: &EventHeader,
+        gtid: Option<(u128, u64)>,
+    ) -> Self {
+        let (start_pos, end_pos) = if header.end_position() == 0 {
+            (0, 0)
+        } else {
+            (header.start_position() as u64, header.end_position() as u64)
+        };
+        SourcePosition {
+            binlog_filename: binlog_filename.to_owned(),
+            start_pos,
+            end_pos,
+            gtid,
+            server_id: header.server_id,
+            timestamp: header.timestamp,
+        }
+    }
+
+    /// cover following event of the same transaction
+    pub fn extend(&mut self, next: &SourcePosition) {
+        if next.end_pos != 0 {
+            if self.end_pos == 0 {
+                self.start_pos = next.start_pos;
+            }
+            self.end_pos = next.end_pos;
+        }
+        if next.gtid.is_some() {
+            self.gtid = next.gtid;
+        }
+        self.binlog_filename = next.binlog_filename.clone();
+        self.server_id = next.server_id;
+        self.timestamp = next.timestamp;
+    }
+
+    /// gtid in form of "uuid:gno"
+    pub fn gtid_string(&self) -> Option<String> {
+        self.gtid
+            .map(|(sid, gno)| format!("{}:{}", sid_to_string(sid), gno))
+    }
+
+    /// sortable key of end position, None for artificial events
+    pub fn ordering_key(&self) -> Option<OrderingKey> {
+        if self.end_pos == 0 {
+            return None;
+        }
+        OrderingKey::from_file_pos(&self.binlog_filename, self.end_pos)
+    }
+}
+
+/// totally ordered key of position in binlog of a server
+///
+/// file index is the numeric extension of binlog file name, which
+/// increases across rotation. end position of each event is unique,
+/// so the key can be used as idempotency key of events. both the
+/// string and the byte encoding preserve the order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OrderingKey {
+    pub file_index: u64,
+    pub position: u64,
+}
+
+impl OrderingKey {
+    pub fn new(file_index: u64, position: u64) -> Self {
+        OrderingKey {
+            file_index,
+            position,
+        }
+    }
+
+    /// None if file name has no numeric extension
+    pub fn from_file_pos(binlog_filename: &str, position: u64) -> Option<Self> {
+        let (_, ext) = binlog_filename.rsplit_once('.')?;
+        if ext.is_empty() || !ext.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let file_index = ext.parse().ok()?;
+        Some(OrderingKey::new(file_index, position))
+    }
+
+    /// big endian bytes, compared same as the key
+    pub fn to_be_bytes(self) -> [u8; 16] {
+        let mut bs = [0u8; 16];
+        bs[..8].copy_from_slice(&self.file_index.to_be_bytes());
+        bs[8..].copy_from_slice(&self.position.to_be_bytes());
+        bs
+    }
+
+    pub fn from_be_bytes(bs: [u8; 16]) -> Self {
+        let mut file_index = [0u8; 8];
+        let mut position = [0u8; 8];
+        file_index.copy_from_slice(&bs[..8]);
+        position.copy_from_slice(&bs[8..]);
+        OrderingKey::new(u64::from_be_bytes(file_index), u64::from_be_bytes(position))
+    }
+}
+
+/// zero padded to keep order of strings
+impl fmt::Display for OrderingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:020}:{:020}", self.file_index, self.position)
+    }
+}
+
+impl FromStr for OrderingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (file_index, position) = s
+            .split_once(':')
+            .ok_or_else(|| Error::BinlogEventError(format!("invalid ordering key: {}", s)))?;
+        Ok(OrderingKey::new(file_index.parse()?, position.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventHeaderFlags, LogEventType};
+
+    #[test]
+    fn test_source_position_extend() {
+        let header = |next_pos: u32, event_len: u32| EventHeader {
+            timestamp: next_pos,
+            type_code: LogEventType::QueryEvent,
+            server_id: 1,
+            event_len,
+            next_pos,
+            flags: EventHeaderFlags::empty(),
+        };
+        let gtid = Some((1u128, 5u64));
+        let mut pos = SourcePosition::of_event("mysql-bin.000001", &header(219, 65), gtid);
+        assert_eq!((154, 219), (pos.start_pos, pos.end_pos));
+        pos.extend(&SourcePosition::of_event(
+            "mysql-bin.000001",
+            &header(300, 81),
+            gtid,
+        ));
+        assert_eq!((154, 300, 300), (pos.start_pos, pos.end_pos, pos.timestamp));
+        // artificial event does not move position
+        pos.extend(&SourcePosition::of_event(
+            "mysql-bin.000001",
+            &header(0, 40),
+            None,
+        ));
+        assert_eq!((154, 300), (pos.start_pos, pos.end_pos));
+        assert_eq!(
+            Some("01000000-0000-0000-0000-000000000000:5".to_owned()),
+            pos.gtid_string()
+        );
+    }
+
+    #[test]
+    fn test_ordering_key() {
+        let key = |file: &str, pos: u64| OrderingKey::from_file_pos(file, pos).unwrap();
+        let k1 = key("mysql-bin.000009", 12345);
+        let k2 = key("mysql-bin.000010", 4);
+        let k3 = key("mysql-bin.1000000", 4);
+        assert!(k1 < k2 && k2 < k3);
+        assert!(k1.to_string() < k2.to_string() && k2.to_string() < k3.to_string());
+        assert!(k1.to_be_bytes() < k2.to_be_bytes());
+        assert_eq!(k1, OrderingKey::from_be_bytes(k1.to_be_bytes()));
+        assert_eq!(k3, k3.to_string().parse().unwrap());
+        assert_eq!(None, OrderingKey::from_file_pos("mysql-bin", 4));
+        assert_eq!(None, OrderingKey::from_file_pos("mysql-bin.index", 4));
+        let pos = SourcePosition {
+            binlog_filename: "mysql-bin.000042".to_owned(),
+            start_pos: 154,
+            end_pos: 219,
+            gtid: None,
+            server_id: 1,
+            timestamp: 0,
+        };
+        assert_eq!(Some(OrderingKey::new(42, 219)), pos.ordering_key());
+    }
+}
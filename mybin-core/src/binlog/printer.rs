@@ -0,0 +1,501 @@
+//! text output of events in the format of mysqlbinlog
+//!
+//! the output follows `mysqlbinlog -v` close enough to be diffed.
+//! timestamps are printed in UTC, so run mysqlbinlog with TZ=UTC.
+//! session variables mysqlbinlog sets before each query are omitted.
+use super::*;
+use crate::bitmap;
+use crate::col::BinlogColumnValue;
+use crate::error::Result;
+use crate::stmt::StmtColumnValue;
+use crate::value::Value;
+use bytes::Buf;
+use bytes_parser::checked_sub_len;
+use chrono::DateTime;
+use intvar::IntvarKey;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use table_map::TableMap;
+use text::BINARY_COLLATION_ID;
+use user_var::{UserVarTypedValue, UserVarValue};
+
+/// rows event with STMT_END_F flag closes the statement
+const STMT_END_F: u16 = 0x0001;
+
+/// stateful printer of events
+///
+/// table maps are kept to print rows of subsequent rows events.
+#[derive(Debug)]
+pub struct EventPrinter {
+    verbose: bool,
+    base64: bool,
+    checksum: bool,
+    table_maps: HashMap<u64, TableMap>,
+}
+
+impl Default for EventPrinter {
+    fn default() -> Self {
+        EventPrinter {
+            verbose: true,
+            base64: false,
+            checksum: false,
+            table_maps: HashMap::new(),
+        }
+    }
+}
+
+impl EventPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// print rows as pseudo SQL with ### prefix, enabled by default
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// print BINLOG statement of raw event, only works with `print_raw()`
+    pub fn base64(mut self, base64: bool) -> Self {
+        self.base64 = base64;
+        self
+    }
+
+    /// whether events carry checksum, only needed if format
+    /// description event is not printed by this printer
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// print event without raw bytes
+    pub fn print(&mut self, event: &Event) -> Result<String> {
+        let mut out = String::new();
+        self.write_event(&mut out, event, None)?;
+        Ok(out)
+    }
+
+    /// print event with its raw bytes, including the header
+    /// and the checksum if enabled
+    pub fn print_raw(&mut self, event: &Event, raw: &[u8]) -> Result<String> {
+        let mut out = String::new();
+        self.write_event(&mut out, event, Some(raw))?;
+        Ok(out)
+    }
+
+    fn write_event(&mut self, out: &mut String, event: &Event, raw: Option<&[u8]>) -> Result<()> {
+        let header = event.header();
+        let _ = writeln!(out, "# at {}", header.start_position());
+        let _ = write!(
+            out,
+            "#{} server id {}  end_log_pos {} ",
+            format_ts(header.timestamp),
+            header.server_id,
+            header.next_pos
+        );
+        if let Event::FormatDescriptionEvent(e) = event {
+            // checksum of FDE itself depends on its own flag
+            self.checksum = e.clone().into_data()?.checksum_flag == 1;
+        }
+        if let Some(raw) = raw {
+            if self.checksum {
+                if let Ok(crc_pos) = checked_sub_len(raw.len(), 4) {
+                    let mut crc = &raw[crc_pos..];
+                    let _ = write!(out, "CRC32 0x{:08x} ", crc.get_u32_le());
+                }
+            }
+        }
+        self.write_body(out, event)?;
+        if let (true, Some(raw)) = (self.base64, raw) {
+            write_base64(out, raw);
+        }
+        if self.verbose {
+            self.write_rows(out, event)?;
+        }
+        Ok(())
+    }
+
+    fn write_body(&mut self, out: &mut String, event: &Event) -> Result<()> {
+        match event {
+            Event::FormatDescriptionEvent(e) => {
+                let fde = e.clone().into_data()?;
+                let _ = write!(
+                    out,
+                    "\tStart: binlog v {}, server v {}",
+                    fde.binlog_version, fde.server_version
+                );
+                // only the first binlog after startup has created timestamp
+                if fde.create_timestamp != 0 {
+                    let _ = write!(
+                        out,
+                        " created {} at startup",
+                        format_ts(fde.create_timestamp)
+                    );
+                }
+                out.push('\n');
+                if e.header.flags.contains(EventHeaderFlags::BINLOG_IN_USE) {
+                    out.push_str(
+                        "# Warning: this binlog is either in use or was not closed properly.\n",
+                    );
+                }
+            }
+            Event::StartEventV3(e) => {
+                let start = e.clone().into_data()?;
+                let _ = writeln!(
+                    out,
+                    "\tStart: binlog v {}, server v {} created {}",
+                    start.binlog_version,
+                    start.server_version,
+                    format_ts(start.create_timestamp)
+                );
+            }
+            Event::PreviousGtidsLogEvent(e) => {
+                let gtid_set = e.clone().into_data()?.gtid_set()?;
+                if gtid_set.ranges().next().is_none() {
+                    out.push_str("\tPrevious-GTIDs\n# [empty]\n");
+                } else {
+                    let _ = writeln!(out, "\tPrevious-GTIDs\n# {}", gtid_set);
+                }
+            }
+            Event::GtidLogEvent(e) => {
+                let gtid = e.clone().into_data()?;
+                let rbr_only = gtid.gtid_flags & 0x01 != 0;
+                let _ = writeln!(
+                    out,
+                    "\tGTID\tlast_committed={}\tsequence_number={}\trbr_only={}",
+                    gtid.last_committed,
+                    gtid.seq_num,
+                    if rbr_only { "yes" } else { "no" }
+                );
+                if rbr_only {
+                    out.push_str(
+                        "/*!50718 SET TRANSACTION ISOLATION LEVEL READ COMMITTED*//*!*/;\n",
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "SET @@SESSION.GTID_NEXT= '{}:{}'/*!*/;",
+                    gtid::sid_to_string(gtid.encoded_sid),
+                    gtid.encoded_gno
+                );
+            }
+            Event::AnonymousGtidLogEvent(e) => {
+                let gtid = e.clone().into_data()?;
+                let _ = writeln!(
+                    out,
+                    "\tAnonymous_GTID\tlast_committed={}\tsequence_number={}\trbr_only={}",
+                    gtid.last_committed,
+                    gtid.seq_num,
+                    if gtid.gtid_flags & 0x01 != 0 {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                );
+                out.push_str("SET @@SESSION.GTID_NEXT= 'ANONYMOUS'/*!*/;\n");
+            }
+            Event::QueryEvent(e) => {
+                let query = e.clone().into_data()?;
+                let _ = writeln!(
+                    out,
+                    "\tQuery\tthread_id={}\texec_time={}\terror_code={}",
+                    query.slave_proxy_id, query.exec_time, query.error_code
+                );
+                if query.schema.has_remaining() {
+                    let _ = writeln!(out, "use `{}`/*!*/;", query.schema.to_string_lossy());
+                }
+                let _ = writeln!(out, "SET TIMESTAMP={}/*!*/;", e.header.timestamp);
+                let _ = writeln!(out, "{}\n/*!*/;", query.query_text()?);
+            }
+            Event::XidEvent(e) => {
+                let xid = e.clone().into_data()?;
+                let _ = writeln!(out, "\tXid = {}\nCOMMIT/*!*/;", xid.xid);
+            }
+            Event::RotateEvent(e) => {
+                let rotate = e.clone().into_data()?;
+                let _ = writeln!(
+                    out,
+                    "\tRotate to {}  pos: {}",
+                    rotate.next_binlog_filename.to_string_lossy(),
+                    rotate.position
+                );
+            }
+            Event::StopEvent(_) => out.push_str("\tStop\n"),
+            Event::IntvarEvent(e) => {
+                let intvar = e.clone().into_data()?;
+                let name = if intvar.key == IntvarKey::LAST_INSERT_ID {
+                    "LAST_INSERT_ID"
+                } else {
+                    "INSERT_ID"
+                };
+                let _ = writeln!(out, "\tIntvar\nSET {}={}/*!*/;", name, intvar.value);
+            }
+            Event::RandEvent(e) => {
+                let rand = e.clone().into_data()?;
+                let _ = writeln!(
+                    out,
+                    "\tRand\nSET @@RAND_SEED1={}, @@RAND_SEED2={}/*!*/;",
+                    rand.seed1, rand.seed2
+                );
+            }
+            Event::UserVarEvent(e) => {
+                let var = e.clone().into_data()?;
+                let value = if var.is_null != 0 {
+                    "NULL".to_owned()
+                } else {
+                    format_user_var(UserVarValue::read_from(&mut var.value.clone())?)?
+                };
+                let _ = writeln!(
+                    out,
+                    "\tUser_var\nSET @`{}`:={}/*!*/;",
+                    var.name.to_string_lossy(),
+                    value
+                );
+            }
+            Event::TableMapEvent(e) => {
+                let data = e.clone().into_data()?;
+                let tm = data.table_map()?;
+                let _ = writeln!(
+                    out,
+                    "\tTable_map: `{}`.`{}` mapped to number {}",
+                    tm.schema_name, tm.table_name, data.table_id
+                );
+                self.table_maps.insert(data.table_id, tm);
+            }
+            Event::WriteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Write_rows", data.table_id, data.flags);
+            }
+            Event::UpdateRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Update_rows", data.table_id, data.flags);
+            }
+            Event::DeleteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Delete_rows", data.table_id, data.flags);
+            }
+            Event::WriteRowsEventV1(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Write_rows_v1", data.table_id, data.flags);
+            }
+            Event::UpdateRowsEventV1(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Update_rows_v1", data.table_id, data.flags);
+            }
+            Event::DeleteRowsEventV1(e) => {
+                let data = e.clone().into_data()?;
+                write_rows_header(out, "Delete_rows_v1", data.table_id, data.flags);
+            }
+            Event::IncidentEvent(e) => {
+                let incident = e.clone().into_data()?;
+                let _ = writeln!(out, "\tIncident\n# Incident: {}", incident.message());
+            }
+            Event::HeartbeatLogEvent(_) | Event::HeartbeatLogEventV2(_) => {
+                out.push_str("\tHeartbeat\n")
+            }
+            _ => {
+                let _ = writeln!(out, "\t{:?}", event.header().type_code);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_rows(&mut self, out: &mut String, event: &Event) -> Result<()> {
+        match event {
+            Event::WriteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let Some(tm) = self.table_maps.get(&data.table_id) {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    for row in &rows.rows {
+                        let _ = writeln!(
+                            out,
+                            "### INSERT INTO `{}`.`{}`\n### SET",
+                            tm.schema_name, tm.table_name
+                        );
+                        write_row(out, rows.present_bitmap.chunk(), &row.0);
+                    }
+                }
+            }
+            Event::DeleteRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let Some(tm) = self.table_maps.get(&data.table_id) {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    for row in &rows.rows {
+                        let _ = writeln!(
+                            out,
+                            "### DELETE FROM `{}`.`{}`\n### WHERE",
+                            tm.schema_name, tm.table_name
+                        );
+                        write_row(out, rows.present_bitmap.chunk(), &row.0);
+                    }
+                }
+            }
+            Event::UpdateRowsEventV2(e) => {
+                let data = e.clone().into_data()?;
+                if let Some(tm) = self.table_maps.get(&data.table_id) {
+                    let rows = data.into_rows(&tm.col_metas)?;
+                    for row in &rows.rows {
+                        let _ = writeln!(
+                            out,
+                            "### UPDATE `{}`.`{}`\n### WHERE",
+                            tm.schema_name, tm.table_name
+                        );
+                        write_row(out, rows.before_present_bitmap.chunk(), &row.0);
+                        out.push_str("### SET\n");
+                        write_row(out, rows.after_present_bitmap.chunk(), &row.1);
+                    }
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+fn write_rows_header(out: &mut String, name: &str, table_id: u64, flags: u16) {
+    let _ = write!(out, "\t{}: table id {}", name, table_id);
+    if flags & STMT_END_F != 0 {
+        out.push_str(" flags: STMT_END_F");
+    }
+    out.push('\n');
+}
+
+/// columns are named by position starting from 1, as table map
+/// does not have column names
+fn write_row(out: &mut String, present_bitmap: &[u8], cols: &[BinlogColumnValue]) {
+    for (i, (present, col)) in bitmap::to_iter(present_bitmap, 0).zip(cols).enumerate() {
+        if !present {
+            continue;
+        }
+        // signedness is unknown without optional metadata, same as mysqlbinlog
+        let sv = StmtColumnValue::from((col.clone(), false));
+        let (lit, quote) = sv.to_sql_literal();
+        if quote {
+            let _ = writeln!(out, "###   @{}='{}'", i + 1, lit);
+        } else {
+            let _ = writeln!(out, "###   @{}={}", i + 1, lit);
+        }
+    }
+}
+
+/// BINLOG statement with base64 lines of 76 chars
+fn write_base64(out: &mut String, raw: &[u8]) {
+    out.push_str("\nBINLOG '\n");
+    let encoded = base64::encode(raw);
+    for line in encoded.as_bytes().chunks(76) {
+        // base64 output is ascii
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str("'/*!*/;\n");
+}
+
+/// format timestamp as %y%m%d %k:%M:%S
+fn format_ts(ts: u32) -> String {
+    match DateTime::from_timestamp(ts as i64, 0) {
+        Some(dt) => dt.format("%y%m%d %k:%M:%S").to_string(),
+        None => ts.to_string(),
+    }
+}
+
+/// strings are decoded by their charset, binary strings
+/// are printed in hex
+fn format_user_var(value: UserVarValue) -> Result<String> {
+    if value.charset_num != BINARY_COLLATION_ID as u32 {
+        if let Some(text) = value.to_text() {
+            return Ok(Value::Bytes(Bytes::from(text.into_owned())).to_sql_literal());
+        }
+    }
+    let s = match value.decode()? {
+        UserVarTypedValue::Real(n) => n.to_string(),
+        UserVarTypedValue::Int(n) => n.to_string(),
+        UserVarTypedValue::UInt(n) => n.to_string(),
+        UserVarTypedValue::Decimal(d) => d.to_string(),
+        UserVarTypedValue::String(bs) => format!("0x{}", hex::encode(bs.chunk())),
+    };
+    Ok(s)
+}
+
+/// stateless text of event, rows are not printed as table map is
+/// unknown, use EventPrinter instead
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match EventPrinter::new().verbose(false).print(self) {
+            Ok(s) => f.write_str(&s),
+            Err(e) => write!(
+                f,
+                "# at {}\n# invalid event: {}\n",
+                self.header().start_position(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RowsEventV2");
+
+    #[test]
+    fn test_format_user_var() -> Result<()> {
+        let mut value = bytes::BytesMut::new();
+        value.put_u8(0);
+        // latin1_swedish_ci
+        value.put_u32_le(8);
+        value.put_u32_le(5);
+        value.put_slice(b"it\xe9's");
+        let mut uvv = UserVarValue::read_from(&mut value.freeze())?;
+        assert_eq!("'ité''s'", format_user_var(uvv.clone())?);
+        uvv.charset_num = BINARY_COLLATION_ID as u32;
+        assert_eq!("0x6974e92773", format_user_var(uvv)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_events() -> Result<()> {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        BinlogVersion::read_from(&mut input)?;
+        let (pv4, _) = ParserV4::from_fde_bytes(&mut input.clone())?;
+        let mut printer = EventPrinter::new().base64(true);
+        let mut out = String::new();
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone())?.0 as usize;
+            let raw = input.slice(..len);
+            // server clears in-use flag of FDE without updating checksum
+            let validate = !out.is_empty();
+            let event = pv4.parse_event(&mut input, validate)?.unwrap();
+            out.push_str(&printer.print_raw(&event, &raw)?);
+        }
+        println!("{}", out);
+        assert!(out.starts_with("# at 4\n#"));
+        assert!(out.contains("\tPrevious-GTIDs\n# [empty]\n"));
+        assert!(out.contains("\tStart: binlog v 4, server v 5.7.30"));
+        assert!(out.contains("CRC32 0x"));
+        assert!(out.contains("\nBINLOG '\n"));
+        assert!(out.contains("### INSERT INTO `"));
+        assert!(out.contains("### UPDATE `"));
+        assert!(out.contains("### DELETE FROM `"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_invalid_event() -> Result<()> {
+        let mut input = Bytes::copy_from_slice(BINLOG_ROWS_EVENT_V2);
+        let pv4 = ParserV4::from_binlog_file(&mut input)?;
+        while input.has_remaining() {
+            if let Some(Event::QueryEvent(e)) = pv4.parse_event(&mut input, false)? {
+                let start = e.header.start_position();
+                assert!(start > 0);
+                // query of BEGIN cut off
+                let e = QueryEvent::new(e.header, e.data.slice(..4));
+                let text = Event::QueryEvent(e).to_string();
+                assert!(text.starts_with(&format!("# at {}\n# invalid event", start)));
+                return Ok(());
+            }
+        }
+        panic!("query event not found")
+    }
+}
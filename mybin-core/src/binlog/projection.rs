@@ -0,0 +1,171 @@
+//! per-table column projection of rows events
+//!
+//! rows decoder skips bytes of columns not in projection instead of
+//! materializing them, skipped columns are Null in decoded rows.
+//! tables without projection are fully decoded.
+use crate::binlog::TableMap;
+use crate::error::{Error, Result};
+use serde_derive::*;
+use std::collections::HashMap;
+
+/// column referred by index or name, names are only recorded in
+/// TableMapEvent if binlog_row_metadata=FULL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for ColumnRef {
+    fn from(idx: usize) -> Self {
+        ColumnRef::Index(idx)
+    }
+}
+
+impl From<&str> for ColumnRef {
+    fn from(name: &str) -> Self {
+        ColumnRef::Name(name.to_owned())
+    }
+}
+
+impl From<String> for ColumnRef {
+    fn from(name: String) -> Self {
+        ColumnRef::Name(name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Projections {
+    /// (schema, table) -> projected columns
+    tables: HashMap<(String, String), Vec<ColumnRef>>,
+}
+
+impl Projections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// project columns of given table
+    pub fn table<S, T, I, C>(mut self, schema: S, table: T, cols: I) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+        I: IntoIterator<Item = C>,
+        C: Into<ColumnRef>,
+    {
+        self.tables.insert(
+            (schema.into(), table.into()),
+            cols.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// bitmap of projected columns of table, None if the table
+    /// is not projected
+    pub fn bitmap(&self, table_map: &TableMap) -> Result<Option<Vec<u8>>> {
+        let cols = match self.tables.get(&(
+            table_map.schema_name.to_string(),
+            table_map.table_name.to_string(),
+        )) {
+            Some(cols) => cols,
+            None => return Ok(None),
+        };
+        let n_cols = table_map.col_metas.len();
+        let mut bm = vec![0u8; n_cols.div_ceil(8)];
+        for col in cols {
+            let idx = match col {
+                ColumnRef::Index(idx) if *idx < n_cols => *idx,
+                ColumnRef::Index(idx) => {
+                    return Err(Error::ColumnIndexOutOfBound(format!(
+                        "{}.{}: {}",
+                        table_map.schema_name, table_map.table_name, idx
+                    )))
+                }
+                ColumnRef::Name(name) => (0..n_cols)
+                    .find(|i| table_map.column_name(*i) == Some(name.as_str()))
+                    .ok_or_else(|| {
+                        Error::ColumnNameNotFound(format!(
+                            "{}.{}: {}",
+                            table_map.schema_name, table_map.table_name, name
+                        ))
+                    })?,
+            };
+            crate::bitmap::mark(&mut bm, idx, true);
+        }
+        Ok(Some(bm))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::rows_v2::RowsV2;
+    use crate::binlog::TableMetadata;
+    use crate::col::{BinlogColumnValue, ColumnMeta, ColumnMetas};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_projected_rows() {
+        let table_map = TableMap {
+            schema_name: "db1".into(),
+            table_name: "t1".into(),
+            col_metas: ColumnMetas(vec![
+                ColumnMeta::Long,
+                ColumnMeta::Blob { pack_len: 2 },
+                ColumnMeta::VarString { max_len: 20 },
+            ]),
+            null_bitmap: vec![0],
+            metadata: TableMetadata {
+                column_names: vec!["id".into(), "body".into(), "name".into()],
+                ..Default::default()
+            },
+        };
+        let projections = Projections::new().table("db1", "t1", vec!["id", "name"]);
+        let projection = projections.bitmap(&table_map).unwrap().unwrap();
+        assert_eq!(vec![0b101u8], projection);
+        assert!(Projections::new()
+            .table("db1", "t1", vec![3usize])
+            .bitmap(&table_map)
+            .is_err());
+        assert!(Projections::new()
+            .table("db1", "t1", vec!["missing"])
+            .bitmap(&table_map)
+            .is_err());
+        assert!(projections
+            .bitmap(&TableMap {
+                table_name: "t2".into(),
+                ..table_map.clone()
+            })
+            .unwrap()
+            .is_none());
+
+        // n_cols, present bitmap, then one row with null bitmap
+        let mut payload = vec![3u8, 0b111, 0];
+        payload.extend_from_slice(&7u32.to_le_bytes());
+        payload.extend_from_slice(&[4, 0]);
+        payload.extend_from_slice(b"blob");
+        payload.extend_from_slice(&[2]);
+        payload.extend_from_slice(b"ab");
+        let rows = RowsV2::read_projected(
+            &mut Bytes::from(payload),
+            2,
+            &table_map.col_metas,
+            usize::MAX,
+            &projection,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                BinlogColumnValue::Long(7),
+                BinlogColumnValue::Null,
+                BinlogColumnValue::VarString(Bytes::from_static(b"ab")),
+            ],
+            rows.rows[0].0
+        );
+    }
+}
@@ -1,8 +1,12 @@
 //! meaningful data structures and parsing logic of QueryEvent
+use super::ddl::{self, Classified};
+use super::text::{EventText, DEFAULT_COLLATION_ID};
+use crate::session::SqlMode;
 use bitflags::bitflags;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use std::borrow::Cow;
 
 /// Data of QueryEvent
 ///
@@ -48,6 +52,80 @@ impl ReadFromBytes for QueryData {
     }
 }
 
+impl QueryData {
+    pub fn status_vars(&self) -> Result<QueryStatusVars> {
+        QueryStatusVars::read_from(&mut self.status_vars.clone())
+    }
+
+    /// collation id of client charset, None if not recorded
+    pub fn charset_client(&self) -> Result<Option<u16>> {
+        let charset = self.status_vars()?.iter().find_map(|var| match var {
+            QueryStatusVar::CharsetCode { client, .. } => Some(*client),
+            _ => None,
+        });
+        Ok(charset)
+    }
+
+    /// sql_mode of session executing the statement, None if not recorded
+    pub fn sql_mode(&self) -> Result<Option<SqlMode>> {
+        let sql_mode = self.status_vars()?.iter().find_map(|var| match var {
+            QueryStatusVar::SqlModeCode(n) => Some(SqlMode::from_bits_truncate(*n)),
+            _ => None,
+        });
+        Ok(sql_mode)
+    }
+
+    /// statement decoded with client charset
+    pub fn query_text(&self) -> Result<Cow<'_, str>> {
+        let collation_id = self.charset_client()?.unwrap_or(DEFAULT_COLLATION_ID);
+        Ok(self.query.to_string_with_collation(collation_id))
+    }
+
+    /// normalized statement, see [`crate::digest::fingerprint`]
+    pub fn fingerprint(&self) -> Result<String> {
+        Ok(crate::digest::fingerprint(&self.query_text()?))
+    }
+
+    /// how the statement starts or ends a transaction
+    pub fn trx_boundary(&self) -> Result<TrxBoundary> {
+        let query = self.query_text()?;
+        let query = query.trim();
+        let boundary = if query.eq_ignore_ascii_case("BEGIN") {
+            TrxBoundary::Begin
+        } else if query.eq_ignore_ascii_case("COMMIT") || query.eq_ignore_ascii_case("ROLLBACK") {
+            TrxBoundary::Commit
+        } else if ddl::is_implicit_commit(query) {
+            TrxBoundary::ImplicitCommit
+        } else {
+            TrxBoundary::Statement
+        };
+        Ok(boundary)
+    }
+
+    /// kind and affected objects of statement, unqualified table names
+    /// are resolved against default database of the event
+    pub fn classify(&self) -> Result<Classified> {
+        let classified = ddl::classify(&self.query_text()?);
+        Ok(classified.with_default_db(&String::from_utf8_lossy(self.schema.chunk())))
+    }
+}
+
+/// role of QueryEvent in transaction
+///
+/// in statement-based replication, DML statements between BEGIN and
+/// COMMIT are also logged as QueryEvent, so only COMMIT or ROLLBACK
+/// ends a transaction started by BEGIN. DDL outside of BEGIN is a
+/// transaction by itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrxBoundary {
+    Begin,
+    /// COMMIT or ROLLBACK
+    Commit,
+    /// statement causing implicit commit, e.g. DDL
+    ImplicitCommit,
+    Statement,
+}
+
 #[derive(Debug, Clone)]
 pub enum QueryStatusVar {
     Flags2Code(u32),
@@ -189,40 +267,3 @@ bitflags! {
         const RELAXED_UNIQUE_CHECKS = 0x0800_0000;
     }
 }
-
-bitflags! {
-    pub struct SqlModeCode: u64 {
-        const REAL_AS_FLOAT     = 0x0000_0001;
-        const PIPES_AS_CONCAT   = 0x0000_0002;
-        const ANSI_QUOTES       = 0x0000_0004;
-        const IGNORE_SPACE      = 0x0000_0008;
-        const NOT_USED          = 0x0000_0010;
-        const ONLY_FULL_GROUP_BY    = 0x0000_0020;
-        const NO_UNSIGNED_SUBTRACTION   = 0x0000_0040;
-        const NO_DIR_IN_CREATE  = 0x0000_0080;
-        const POSTGRESQL        = 0x0000_0100;
-        const ORACLE            = 0x0000_0200;
-        const MSSQL             = 0x0000_0400;
-        const DB2               = 0x0000_0800;
-        const MAXDB             = 0x0000_1000;
-        const NO_KEY_OPTIONS    = 0x0000_2000;
-        const NO_TABLE_OPTIONS  = 0x0000_4000;
-        const NO_FIELD_OPTIONS  = 0x0000_8000;
-        const MYSQL323          = 0x0001_0000;
-        const MYSQL40           = 0x0002_0000;
-        const ANSI              = 0x0004_0000;
-        const NO_AUTO_VALUE_ON_ZERO = 0x0008_0000;
-        const NO_BACKSLASH_ESCAPES  = 0x0010_0000;
-        const STRICT_TRANS_TABLES   = 0x0020_0000;
-        const STRICT_ALL_TABLES = 0x0040_0000;
-        const NO_ZERO_IN_DATE   = 0x0080_0000;
-        const NO_ZERO_DATE      = 0x0100_0000;
-        const INVALID_DATES     = 0x0200_0000;
-        const ERROR_FOR_DIVISION_BY_ZERO    = 0x0400_0000;
-        const TRANDITIONAL      = 0x0800_0000;
-        const NO_AUTO_CREATE_USER   = 0x1000_0000;
-        const HIGH_NOT_PRECEDENCE   = 0x2000_0000;
-        const NO_ENGINE_SUBSTITUTION    = 0x4000_0000;
-        const PAD_CHAR_TO_FULL_LENGTH   = 0x8000_0000;
-    }
-}
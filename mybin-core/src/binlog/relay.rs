@@ -0,0 +1,215 @@
+//! rewrite events relayed to downstream replicas
+//!
+//! a relay may change server id, timestamp and flags of events, modify
+//! their payload, or drop some of them. replicas verify next position
+//! and checksum of every event, so positions are re-assigned by length
+//! of output events and checksums are computed on rewritten bytes.
+use super::fde::FormatDescriptionData;
+use super::rotate::RotateData;
+use super::{Event, EventHeader, EventHeaderFlags, LogEventType};
+use crate::error::{Error, Result};
+use crate::util::checksum_crc32;
+use bytes::{BufMut, Bytes, BytesMut};
+use bytes_parser::{checked_sub_len, ReadFromBytes, WriteToBytes};
+use std::convert::TryFrom;
+
+/// rewrites events in output order
+///
+/// dropped events are just not passed to the rewriter. events with zero
+/// next position, e.g. artificial rotate at start of dump, keep it and
+/// do not occupy output positions. after a RotateEvent, positions
+/// start from the position it tells.
+#[derive(Debug, Clone)]
+pub struct EventRewriter {
+    server_id: Option<u32>,
+    artificial: Option<bool>,
+    timestamp_offset: i64,
+    // events end with crc32, follows last FDE
+    checksum: bool,
+    // position of next output event
+    pos: u64,
+}
+
+impl EventRewriter {
+    /// checksum tells whether events end with crc32 before any FDE
+    pub fn new(checksum: bool) -> Self {
+        EventRewriter {
+            server_id: None,
+            artificial: None,
+            timestamp_offset: 0,
+            checksum,
+            pos: 4,
+        }
+    }
+
+    pub fn server_id(mut self, server_id: u32) -> Self {
+        self.server_id = Some(server_id);
+        self
+    }
+
+    /// set or clear artificial flag of all events
+    pub fn artificial(mut self, artificial: bool) -> Self {
+        self.artificial = Some(artificial);
+        self
+    }
+
+    /// shift timestamps by seconds, zero timestamps are kept
+    pub fn timestamp_offset(mut self, timestamp_offset: i64) -> Self {
+        self.timestamp_offset = timestamp_offset;
+        self
+    }
+
+    /// position of first output event, 4 by default,
+    /// right after the magic number
+    pub fn start_pos(mut self, pos: u64) -> Self {
+        self.pos = pos;
+        self
+    }
+
+    /// position of next output event
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// rewrite raw event of header, payload and checksum if any
+    pub fn rewrite(&mut self, raw: &[u8]) -> Result<Bytes> {
+        let mut input = Bytes::copy_from_slice(raw);
+        let header = EventHeader::read_from(&mut input)?;
+        if header.event_len as usize != raw.len() {
+            return Err(Error::BinlogEventError(format!(
+                "event length {} does not match raw length {}",
+                header.event_len,
+                raw.len()
+            )));
+        }
+        let checksum = if header.type_code == LogEventType::FormatDescriptionEvent {
+            FormatDescriptionData::read_from(&mut input.clone())?.checksum_flag == 1
+        } else {
+            self.checksum
+        };
+        if checksum {
+            input.truncate(checked_sub_len(input.len(), 4)?);
+        }
+        self.encode(header, &input)
+    }
+
+    /// rewrite parsed event with its original payload
+    pub fn rewrite_event(&mut self, event: &Event) -> Result<Bytes> {
+        self.encode(event.header().clone(), event.data())
+    }
+
+    /// build event from header and payload without checksum, e.g. a
+    /// modified payload. event length, next position and checksum
+    /// are computed again
+    pub fn encode(&mut self, mut header: EventHeader, data: &[u8]) -> Result<Bytes> {
+        let fde = header.type_code == LogEventType::FormatDescriptionEvent;
+        if fde {
+            let fde = FormatDescriptionData::read_from(&mut Bytes::copy_from_slice(data))?;
+            self.checksum = fde.checksum_flag == 1;
+        }
+        if let Some(server_id) = self.server_id {
+            header.server_id = server_id;
+        }
+        if let Some(artificial) = self.artificial {
+            header.flags.set(EventHeaderFlags::ARTIFICIAL, artificial);
+        }
+        if header.timestamp != 0 {
+            let ts = header.timestamp as i64 + self.timestamp_offset;
+            header.timestamp = ts.clamp(0, u32::MAX as i64) as u32;
+        }
+        let len = 19 + data.len() + if self.checksum { 4 } else { 0 };
+        header.event_len = u32::try_from(len)
+            .map_err(|_| Error::BinlogEventError(format!("event too large: {}", len)))?;
+        if header.next_pos != 0 {
+            let end = self.pos + len as u64;
+            header.next_pos = u32::try_from(end)
+                .map_err(|_| Error::BinlogEventError(format!("position {} exceeds 4GB", end)))?;
+            self.pos = end;
+        }
+        let mut out = BytesMut::with_capacity(len);
+        header.write_to(&mut out)?;
+        out.put_slice(data);
+        if self.checksum {
+            let crc32 = if fde {
+                // checksum of FDE is calculated with in-use flag cleared
+                let mut cleared = header.clone();
+                cleared.flags.remove(EventHeaderFlags::BINLOG_IN_USE);
+                let mut buf = BytesMut::with_capacity(len);
+                cleared.write_to(&mut buf)?;
+                buf.put_slice(data);
+                checksum_crc32(&buf)
+            } else {
+                checksum_crc32(&out)
+            };
+            out.put_u32_le(crc32);
+        }
+        if header.type_code == LogEventType::RotateEvent {
+            self.pos = RotateData::read_from(&mut Bytes::copy_from_slice(data))?.position;
+        }
+        Ok(out.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventLength, ParserV4};
+    use bytes::Buf;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RowsEventV2");
+
+    fn raw_events(file: &[u8]) -> Vec<Bytes> {
+        let mut input = Bytes::copy_from_slice(&file[4..]);
+        let mut events = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            events.push(input.split_to(len));
+        }
+        events
+    }
+
+    #[test]
+    fn test_event_rewriter() -> Result<()> {
+        // unchanged events reproduce the file
+        let mut rewriter = EventRewriter::new(false);
+        let mut out = BINLOG_ROWS_EVENT_V2[..4].to_vec();
+        for raw in raw_events(BINLOG_ROWS_EVENT_V2) {
+            out.extend_from_slice(&rewriter.rewrite(&raw)?);
+        }
+        assert_eq!(BINLOG_ROWS_EVENT_V2, &out[..]);
+
+        // drop previous gtids, rewrite the others
+        let mut rewriter = EventRewriter::new(false)
+            .server_id(99)
+            .artificial(true)
+            .timestamp_offset(-10);
+        let mut out = BINLOG_ROWS_EVENT_V2[..4].to_vec();
+        let raws = raw_events(BINLOG_ROWS_EVENT_V2);
+        for raw in &raws {
+            if raw[4] == u8::from(LogEventType::PreviousGtidsLogEvent) {
+                continue;
+            }
+            out.extend_from_slice(&rewriter.rewrite(raw)?);
+        }
+        assert_eq!(out.len() as u64, rewriter.position());
+
+        let mut input = Bytes::from(out);
+        let pv4 = ParserV4::from_binlog_file(&mut input.clone())?;
+        input.advance(4);
+        let mut pos = 4;
+        let mut n = 0;
+        while input.has_remaining() {
+            // FDE is checksummed with in-use flag cleared
+            let validate = input[4] != u8::from(LogEventType::FormatDescriptionEvent);
+            let event = pv4.parse_event(&mut input, validate)?.unwrap();
+            let header = event.header();
+            pos += header.event_len;
+            assert_eq!(pos, header.next_pos);
+            assert_eq!(99, header.server_id);
+            assert!(header.flags.contains(EventHeaderFlags::ARTIFICIAL));
+            n += 1;
+        }
+        assert_eq!(raws.len() - 1, n);
+        Ok(())
+    }
+}
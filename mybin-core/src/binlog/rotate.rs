@@ -1,6 +1,8 @@
 use bytes::{Buf, Bytes};
 use bytes_parser::error::Result;
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use std::fmt;
+use std::sync::Arc;
 
 /// Data of RotateEvent
 ///
@@ -22,3 +24,60 @@ impl ReadFromBytes for RotateData {
         })
     }
 }
+
+/// observer of switches between binlog files
+///
+/// consumers spanning many files, e.g. relay log writers or per-file
+/// statistics, close state bound to previous file on rotation.
+pub trait RotateListener: Send + Sync {
+    /// moved to another file, called before any event of new file,
+    /// from is empty for the first file
+    fn on_rotate(&self, _from: &str, _to: &str, _pos: u64) {}
+
+    /// FormatDescriptionEvent of current file, checksum tells whether
+    /// events of the file end with crc32
+    fn on_format_description(&self, _binlog_filename: &str, _checksum: bool) {}
+
+    /// checksum of new file differs from previous file, parser has
+    /// adjusted validation accordingly
+    fn on_checksum_change(&self, _binlog_filename: &str, _checksum: bool) {}
+}
+
+#[derive(Clone, Default)]
+pub struct RotateListeners(Vec<Arc<dyn RotateListener>>);
+
+impl RotateListeners {
+    pub fn add(&mut self, listener: Arc<dyn RotateListener>) {
+        self.0.push(listener);
+    }
+
+    pub fn on_rotate(&self, from: &str, to: &str, pos: u64) {
+        log::debug!("rotate from {:?} to {}:{}", from, to, pos);
+        for l in &self.0 {
+            l.on_rotate(from, to, pos);
+        }
+    }
+
+    pub fn on_format_description(&self, binlog_filename: &str, checksum: bool) {
+        for l in &self.0 {
+            l.on_format_description(binlog_filename, checksum);
+        }
+    }
+
+    pub fn on_checksum_change(&self, binlog_filename: &str, checksum: bool) {
+        log::info!(
+            "binlog checksum of {} changed to {}",
+            binlog_filename,
+            if checksum { "CRC32" } else { "NONE" }
+        );
+        for l in &self.0 {
+            l.on_checksum_change(binlog_filename, checksum);
+        }
+    }
+}
+
+impl fmt::Debug for RotateListeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RotateListeners({})", self.0.len())
+    }
+}
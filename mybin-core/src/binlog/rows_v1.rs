@@ -1,4 +1,8 @@
 //! meaningful data structures and parsing logic of RowsEventV1
+//!
+//! rows of v1 events are encoded same as v2 events without extra data
+use crate::binlog::rows_v2::{RowsEventFlags, RowsV2, UpdateRowsV2};
+use crate::col::ColumnMeta;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::Result;
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
@@ -17,6 +21,44 @@ pub struct WriteRowsDataV1 {
     pub payload: Bytes,
 }
 
+impl WriteRowsDataV1 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            None,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            Some(projection),
+        )
+    }
+}
+
 impl ReadFromBytes for WriteRowsDataV1 {
     fn read_from(input: &mut Bytes) -> Result<Self> {
         let table_id = input.read_le_u48()?;
@@ -40,6 +82,44 @@ pub struct UpdateRowsDataV1 {
     pub payload: Bytes,
 }
 
+impl UpdateRowsDataV1 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<UpdateRowsV2> {
+        UpdateRowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            None,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<UpdateRowsV2> {
+        UpdateRowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            Some(projection),
+        )
+    }
+}
+
 impl ReadFromBytes for UpdateRowsDataV1 {
     fn read_from(input: &mut Bytes) -> Result<Self> {
         let wrd = WriteRowsDataV1::read_from(input)?;
@@ -61,6 +141,44 @@ pub struct DeleteRowsDataV1 {
     pub payload: Bytes,
 }
 
+impl DeleteRowsDataV1 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            None,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_rows(
+            &mut self.payload.clone(),
+            Bytes::new(),
+            col_metas,
+            max_rows,
+            Some(projection),
+        )
+    }
+}
+
 impl ReadFromBytes for DeleteRowsDataV1 {
     fn read_from(input: &mut Bytes) -> Result<Self> {
         let wrd = WriteRowsDataV1::read_from(input)?;
@@ -1,11 +1,28 @@
 //! meaningful data structures and parsing logic of RowsEventV2
+use crate::binlog::TableMap;
 use crate::bitmap;
 use crate::col::{BinlogColumnValue, ColumnMeta};
 use crate::row::LogRow;
+use crate::value::Row;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::my::ReadMyEnc;
-use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use bytes_parser::{checked_sub_len, ReadBytesExt, ReadFromBytes};
+
+bitflags::bitflags! {
+    /// flags in post header of rows events, v1 and v2
+    ///
+    /// reference: https://github.com/mysql/mysql-server/blob/5.7/libbinlogevents/include/rows_event.h#L620
+    pub struct RowsEventFlags: u16 {
+        /// last event of the statement, a statement changing many
+        /// rows may be logged in several events
+        const STMT_END              = 0x0001;
+        const NO_FOREIGN_KEY_CHECKS = 0x0002;
+        const RELAXED_UNIQUE_CHECKS = 0x0004;
+        /// all columns are present, not only changed ones
+        const COMPLETE_ROWS         = 0x0008;
+    }
+}
 
 /// Data of WriteRowsEventV2
 ///
@@ -23,6 +40,10 @@ pub struct WriteRowsDataV2 {
 }
 
 impl WriteRowsDataV2 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
     pub fn rows(&self, col_metas: &[ColumnMeta]) -> Result<RowsV2> {
         RowsV2::read_from(
             &mut self.payload.clone(),
@@ -31,6 +52,37 @@ impl WriteRowsDataV2 {
         )
     }
 
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_limited(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_projected(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+            projection,
+        )
+    }
+
     pub fn into_rows(mut self, col_metas: &[ColumnMeta]) -> Result<RowsV2> {
         RowsV2::read_from(&mut self.payload, self.extra_data_len as usize, col_metas)
     }
@@ -63,16 +115,62 @@ pub struct UpdateRowsDataV2 {
 }
 
 impl UpdateRowsDataV2 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
     pub fn rows(&self, col_metas: &[ColumnMeta]) -> Result<UpdateRowsV2> {
-        UpdateRowsV2::read_from(
+        UpdateRowsV2::read_limited(
             &mut self.payload.clone(),
             self.extra_data_len as usize,
             col_metas,
+            usize::MAX,
+            None,
         )
+        .map_err(into_parse_error)
     }
 
     pub fn into_rows(mut self, col_metas: &[ColumnMeta]) -> Result<UpdateRowsV2> {
-        UpdateRowsV2::read_from(&mut self.payload, self.extra_data_len as usize, col_metas)
+        UpdateRowsV2::read_limited(
+            &mut self.payload,
+            self.extra_data_len as usize,
+            col_metas,
+            usize::MAX,
+            None,
+        )
+        .map_err(into_parse_error)
+    }
+
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<UpdateRowsV2> {
+        UpdateRowsV2::read_limited(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+            None,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<UpdateRowsV2> {
+        UpdateRowsV2::read_limited(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+            Some(projection),
+        )
     }
 }
 
@@ -100,6 +198,10 @@ pub struct DeleteRowsDataV2 {
 }
 
 impl DeleteRowsDataV2 {
+    pub fn rows_flags(&self) -> RowsEventFlags {
+        RowsEventFlags::from_bits_truncate(self.flags)
+    }
+
     pub fn rows(&self, col_metas: &[ColumnMeta]) -> Result<RowsV2> {
         RowsV2::read_from(
             &mut self.payload.clone(),
@@ -108,6 +210,37 @@ impl DeleteRowsDataV2 {
         )
     }
 
+    /// fails if event contains more than max_rows rows
+    pub fn rows_limited(
+        &self,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_limited(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+        )
+    }
+
+    /// skip columns not marked in projection bitmap, see Projections,
+    /// fails if event contains more than max_rows rows
+    pub fn rows_projected(
+        &self,
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        RowsV2::read_projected(
+            &mut self.payload.clone(),
+            self.extra_data_len as usize,
+            col_metas,
+            max_rows,
+            projection,
+        )
+    }
+
     pub fn into_rows(mut self, col_metas: &[ColumnMeta]) -> Result<RowsV2> {
         RowsV2::read_from(&mut self.payload, self.extra_data_len as usize, col_metas)
     }
@@ -125,6 +258,60 @@ impl ReadFromBytes for DeleteRowsDataV2 {
     }
 }
 
+// type codes of extra row info
+// reference: https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/include/rows_event.h#L872
+const EXTRA_ROW_INFO_NDB: u8 = 0;
+const EXTRA_ROW_INFO_PART: u8 = 1;
+
+/// parsed extra data of v2 rows events
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtraRowInfo {
+    /// NDB cluster info, including its length and format bytes
+    pub ndb_info: Option<Bytes>,
+    /// partition the rows belong to, since 8.0.16
+    pub partition_id: Option<u16>,
+    /// partition of before image, only for update events.
+    /// differs from partition_id if the row moves across partitions
+    pub source_partition_id: Option<u16>,
+}
+
+impl ExtraRowInfo {
+    /// parse type-length-value entries of extra data,
+    /// length bytes of the whole region are excluded
+    pub fn parse(extra_data: &Bytes, update: bool) -> Result<ExtraRowInfo> {
+        let mut input = extra_data.clone();
+        let mut info = ExtraRowInfo::default();
+        while input.has_remaining() {
+            match input.read_u8()? {
+                EXTRA_ROW_INFO_NDB => {
+                    // length includes length byte and format byte
+                    let len = input.chunk().first().copied().unwrap_or_default() as usize;
+                    if len < 2 {
+                        return Err(Error::ConstraintError(format!(
+                            "invalid ndb info length: {}",
+                            len
+                        )));
+                    }
+                    info.ndb_info = Some(input.read_len(len)?);
+                }
+                EXTRA_ROW_INFO_PART => {
+                    info.partition_id = Some(input.read_le_u16()?);
+                    if update {
+                        info.source_partition_id = Some(input.read_le_u16()?);
+                    }
+                }
+                other => {
+                    return Err(Error::ConstraintError(format!(
+                        "invalid extra row info type: {}",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(info)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RowsV2 {
     pub extra_data: Bytes,
@@ -140,7 +327,39 @@ impl RowsV2 {
         extra_data_len: usize,
         col_metas: &[ColumnMeta],
     ) -> Result<RowsV2> {
-        let extra_data = input.read_len(extra_data_len - 2)?;
+        Self::read_limited(input, extra_data_len, col_metas, usize::MAX).map_err(into_parse_error)
+    }
+
+    pub fn read_limited(
+        input: &mut Bytes,
+        extra_data_len: usize,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+    ) -> crate::error::Result<RowsV2> {
+        let extra_data = input.read_len(extra_data_len_checked(extra_data_len)?)?;
+        Self::read_rows(input, extra_data, col_metas, max_rows, None)
+    }
+
+    /// columns not marked in projection bitmap are skipped
+    pub fn read_projected(
+        input: &mut Bytes,
+        extra_data_len: usize,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+        projection: &[u8],
+    ) -> crate::error::Result<RowsV2> {
+        let extra_data = input.read_len(extra_data_len_checked(extra_data_len)?)?;
+        Self::read_rows(input, extra_data, col_metas, max_rows, Some(projection))
+    }
+
+    /// read rows after extra data, shared with v1 events
+    pub(crate) fn read_rows(
+        input: &mut Bytes,
+        extra_data: Bytes,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+        projection: Option<&[u8]>,
+    ) -> crate::error::Result<RowsV2> {
         // all columns
         let n_cols = input.read_len_enc_int()?;
         let n_cols = n_cols
@@ -156,6 +375,8 @@ impl RowsV2 {
         let null_bitmap_len = (present_cols + 7) >> 3;
         let mut rows = Vec::new();
         while input.has_remaining() {
+            check_row_limit(rows.len(), max_rows)?;
+            let remaining = input.remaining();
             let null_bitmap = input.read_len(null_bitmap_len as usize)?;
             // use present_bitmap as base and mark null using null_bitmap
             let mut col_bitmap = Vec::from(present_bitmap.chunk());
@@ -171,7 +392,8 @@ impl RowsV2 {
                 );
                 j += 1;
             }
-            let row = LogRow::read_from(input, n_cols as usize, &col_bitmap[..], col_metas)?;
+            let row = read_row(input, n_cols as usize, &col_bitmap, col_metas, projection)?;
+            check_progress(remaining, input.remaining())?;
             rows.push(row);
         }
         Ok(RowsV2 {
@@ -181,6 +403,18 @@ impl RowsV2 {
             rows,
         })
     }
+
+    pub fn extra_row_info(&self) -> Result<ExtraRowInfo> {
+        ExtraRowInfo::parse(&self.extra_data, false)
+    }
+
+    /// rows of unified values
+    pub fn values(&self, table_map: &TableMap) -> crate::error::Result<Vec<Row>> {
+        self.rows
+            .iter()
+            .map(|row| Row::from_binlog(row.clone(), table_map))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -193,12 +427,25 @@ pub struct UpdateRowsV2 {
 }
 
 impl UpdateRowsV2 {
-    fn read_from(
+    fn read_limited(
         input: &mut Bytes,
         extra_data_len: usize,
         col_metas: &[ColumnMeta],
-    ) -> Result<UpdateRowsV2> {
-        let extra_data = input.read_len(extra_data_len - 2)?;
+        max_rows: usize,
+        projection: Option<&[u8]>,
+    ) -> crate::error::Result<UpdateRowsV2> {
+        let extra_data = input.read_len(extra_data_len_checked(extra_data_len)?)?;
+        Self::read_rows(input, extra_data, col_metas, max_rows, projection)
+    }
+
+    /// read rows after extra data, shared with v1 events
+    pub(crate) fn read_rows(
+        input: &mut Bytes,
+        extra_data: Bytes,
+        col_metas: &[ColumnMeta],
+        max_rows: usize,
+        projection: Option<&[u8]>,
+    ) -> crate::error::Result<UpdateRowsV2> {
         // all columns
         let n_cols = input.read_len_enc_int()?;
         let n_cols = n_cols
@@ -220,6 +467,8 @@ impl UpdateRowsV2 {
         let after_null_bitmap_len = (after_present_cols + 7) >> 3;
         let mut rows = Vec::new();
         while input.has_remaining() {
+            check_row_limit(rows.len(), max_rows)?;
+            let remaining = input.remaining();
             // before row processing
             let before_null_bitmap = input.read_len(before_null_bitmap_len as usize)?;
             // use present_bitmap as base and mark null using null_bitmap
@@ -236,8 +485,13 @@ impl UpdateRowsV2 {
                 );
                 j += 1;
             }
-            let before_row =
-                LogRow::read_from(input, n_cols as usize, &before_col_bitmap[..], col_metas)?;
+            let before_row = read_row(
+                input,
+                n_cols as usize,
+                &before_col_bitmap,
+                col_metas,
+                projection,
+            )?;
 
             // after row processing
             let after_null_bitmap = input.read_len(after_null_bitmap_len as usize)?;
@@ -255,8 +509,14 @@ impl UpdateRowsV2 {
                 );
                 j += 1;
             }
-            let after_row =
-                LogRow::read_from(input, n_cols as usize, &after_col_bitmap[..], col_metas)?;
+            let after_row = read_row(
+                input,
+                n_cols as usize,
+                &after_col_bitmap,
+                col_metas,
+                projection,
+            )?;
+            check_progress(remaining, input.remaining())?;
             rows.push(UpdateRow(before_row.0, after_row.0));
         }
         Ok(UpdateRowsV2 {
@@ -267,14 +527,190 @@ impl UpdateRowsV2 {
             rows,
         })
     }
+
+    pub fn extra_row_info(&self) -> Result<ExtraRowInfo> {
+        ExtraRowInfo::parse(&self.extra_data, true)
+    }
+
+    /// before and after rows of unified values
+    pub fn values(&self, table_map: &TableMap) -> crate::error::Result<Vec<(Row, Row)>> {
+        self.rows
+            .iter()
+            .map(|UpdateRow(before, after)| {
+                Ok((
+                    Row::from_binlog(LogRow(before.clone()), table_map)?,
+                    Row::from_binlog(LogRow(after.clone()), table_map)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// returns changed columns of each row
+    ///
+    /// a column is changed if it's present in after image, and either
+    /// absent in before image or has different value.
+    /// columns only present in before image are not updated.
+    pub fn diff(&self) -> Vec<Vec<ColumnDiff<'_>>> {
+        // (column index, position in before row, position in after row)
+        let mut positions = vec![];
+        let (mut before_pos, mut after_pos) = (0, 0);
+        for (idx, (before, after)) in bitmap::to_iter(self.before_present_bitmap.chunk(), 0)
+            .zip(bitmap::to_iter(self.after_present_bitmap.chunk(), 0))
+            .take(self.n_cols as usize)
+            .enumerate()
+        {
+            let bp = if before {
+                before_pos += 1;
+                Some(before_pos - 1)
+            } else {
+                None
+            };
+            if after {
+                positions.push((idx, bp, after_pos));
+                after_pos += 1;
+            }
+        }
+        self.rows
+            .iter()
+            .map(|row| {
+                positions
+                    .iter()
+                    .filter_map(|(idx, bp, ap)| {
+                        let before = bp.and_then(|bp| row.0.get(bp));
+                        let after = row.1.get(*ap)?;
+                        if before == Some(after) {
+                            None
+                        } else {
+                            Some(ColumnDiff {
+                                idx: *idx,
+                                before,
+                                after,
+                            })
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 }
 
 /// combine before row and after row
 #[derive(Debug, Clone)]
 pub struct UpdateRow(pub Vec<BinlogColumnValue>, pub Vec<BinlogColumnValue>);
 
+/// changed column of update row
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDiff<'a> {
+    /// index of column in table
+    pub idx: usize,
+    /// None if column is absent in before image
+    pub before: Option<&'a BinlogColumnValue>,
+    pub after: &'a BinlogColumnValue,
+}
+
+/// extra_data_len includes its own 2 bytes
+fn extra_data_len_checked(extra_data_len: usize) -> Result<usize> {
+    checked_sub_len(extra_data_len, 2)
+}
+
+fn read_row(
+    input: &mut Bytes,
+    n_cols: usize,
+    col_bm: &[u8],
+    col_metas: &[ColumnMeta],
+    projection: Option<&[u8]>,
+) -> Result<LogRow> {
+    match projection {
+        Some(projection) => LogRow::read_projected(input, n_cols, col_bm, col_metas, projection),
+        None => LogRow::read_from(input, n_cols, col_bm, col_metas),
+    }
+}
+
+fn check_row_limit(n_rows: usize, max_rows: usize) -> crate::error::Result<()> {
+    if n_rows >= max_rows {
+        return Err(crate::error::Error::TooManyRows(max_rows));
+    }
+    Ok(())
+}
+
+// row consuming no input would loop forever
+fn check_progress(before: usize, after: usize) -> Result<()> {
+    if before == after {
+        return Err(Error::ConstraintError("empty row in rows event".to_owned()));
+    }
+    Ok(())
+}
+
+// errors other than parse error only occur if limit is set
+fn into_parse_error(err: crate::error::Error) -> Error {
+    match err {
+        crate::error::Error::ParseError(e) => e,
+        e => Error::ConstraintError(e.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_row_info() {
+        let info = ExtraRowInfo::parse(&Bytes::new(), false).unwrap();
+        assert_eq!(ExtraRowInfo::default(), info);
+        let info = ExtraRowInfo::parse(&Bytes::from_static(&[1, 3, 0]), false).unwrap();
+        assert_eq!(Some(3), info.partition_id);
+        assert_eq!(None, info.source_partition_id);
+        // ndb info followed by partition info of update event
+        let data = Bytes::from_static(&[0, 4, 0, 0xab, 0xcd, 1, 2, 0, 5, 0]);
+        let info = ExtraRowInfo::parse(&data, true).unwrap();
+        assert_eq!(Some(Bytes::from_static(&[4, 0, 0xab, 0xcd])), info.ndb_info);
+        assert_eq!(Some(2), info.partition_id);
+        assert_eq!(Some(5), info.source_partition_id);
+        assert!(ExtraRowInfo::parse(&Bytes::from_static(&[1, 3]), false).is_err());
+        assert!(ExtraRowInfo::parse(&Bytes::from_static(&[9]), false).is_err());
+    }
+
+    #[test]
+    fn test_update_rows_diff() {
+        let rows = UpdateRowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 4,
+            // minimal image: before has c0, c1, c3, after has c1, c2
+            before_present_bitmap: Bytes::from(vec![0b1011u8]),
+            after_present_bitmap: Bytes::from(vec![0b0110u8]),
+            rows: vec![
+                UpdateRow(
+                    vec![
+                        BinlogColumnValue::Long(1),
+                        BinlogColumnValue::Long(10),
+                        BinlogColumnValue::Null,
+                    ],
+                    vec![BinlogColumnValue::Long(10), BinlogColumnValue::Long(20)],
+                ),
+                UpdateRow(
+                    vec![
+                        BinlogColumnValue::Long(2),
+                        BinlogColumnValue::Long(10),
+                        BinlogColumnValue::Null,
+                    ],
+                    vec![BinlogColumnValue::Long(11), BinlogColumnValue::Long(20)],
+                ),
+            ],
+        };
+        let diff = rows.diff();
+        assert_eq!(
+            vec![ColumnDiff {
+                idx: 2,
+                before: None,
+                after: &BinlogColumnValue::Long(20),
+            }],
+            diff[0]
+        );
+        assert_eq!(2, diff[1].len());
+        assert_eq!(1, diff[1][0].idx);
+        assert_eq!(Some(&BinlogColumnValue::Long(10)), diff[1][0].before);
+    }
+
     #[test]
     fn test_bit_xor() {
         let bm1 = 255;
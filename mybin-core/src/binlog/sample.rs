@@ -0,0 +1,343 @@
+//! sample transactions of binlog stream
+//!
+//! monitoring agents usually only need statistics of the workload,
+//! so part of transactions are delivered. a transaction is either
+//! delivered as a whole or dropped as a whole. events outside of
+//! transactions, e.g. rotate and heartbeat, are always delivered.
+//!
+//! the sampler either filters raw events, or transactions assembled
+//! by [`TransactionAssembler`](super::trx::TransactionAssembler).
+use super::trx::Assembled;
+use super::{Event, TrxBoundary};
+use crate::error::Result;
+use serde_derive::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleRate {
+    /// first transaction of every N transactions
+    Transactions(u64),
+    /// first transaction after every N bytes of transaction events
+    Bytes(u64),
+}
+
+#[derive(Debug)]
+pub struct TrxSampler {
+    rate: SampleRate,
+    // GTID event or BEGIN received, transaction not ended
+    in_trx: bool,
+    // transaction is started by BEGIN
+    begun: bool,
+    sampling: bool,
+    // bytes since last sampled transaction started
+    since_sample: u64,
+    total_trxs: u64,
+    sampled_trxs: u64,
+    total_bytes: u64,
+    sampled_bytes: u64,
+}
+
+impl TrxSampler {
+    pub fn new(rate: SampleRate) -> Self {
+        TrxSampler {
+            rate,
+            in_trx: false,
+            begun: false,
+            sampling: false,
+            // first transaction is always sampled
+            since_sample: u64::MAX,
+            total_trxs: 0,
+            sampled_trxs: 0,
+            total_bytes: 0,
+            sampled_bytes: 0,
+        }
+    }
+
+    /// returns whether the event should be delivered
+    pub fn filter(&mut self, event: &Event) -> Result<bool> {
+        let len = event.header().event_len as u64;
+        match event {
+            Event::GtidLogEvent(_) | Event::AnonymousGtidLogEvent(_) => {
+                self.start_trx();
+                Ok(self.account(len))
+            }
+            Event::XidEvent(_) => Ok(self.end_trx(len)),
+            Event::QueryEvent(e) => {
+                // BEGIN starts a transaction if not started by GTID,
+                // DML in statement-based replication does not end it
+                match (self.begun, e.clone().into_data()?.trx_boundary()?) {
+                    (false, TrxBoundary::Begin) => {
+                        if !self.in_trx {
+                            self.start_trx();
+                        }
+                        self.begun = true;
+                        Ok(self.account(len))
+                    }
+                    (_, TrxBoundary::Commit) => Ok(self.end_trx(len)),
+                    (begun, TrxBoundary::ImplicitCommit) => {
+                        if begun {
+                            // COMMIT missing before DDL
+                            self.end_trx(0);
+                        }
+                        if !self.in_trx {
+                            // DDL without GTID is a transaction itself
+                            self.start_trx();
+                        }
+                        Ok(self.end_trx(len))
+                    }
+                    _ => {
+                        if !self.in_trx {
+                            // statement without BEGIN is a transaction itself
+                            self.start_trx();
+                            return Ok(self.end_trx(len));
+                        }
+                        Ok(self.account(len))
+                    }
+                }
+            }
+            Event::TableMapEvent(_)
+            | Event::WriteRowsEventV1(_)
+            | Event::UpdateRowsEventV1(_)
+            | Event::DeleteRowsEventV1(_)
+            | Event::WriteRowsEventV2(_)
+            | Event::UpdateRowsEventV2(_)
+            | Event::DeleteRowsEventV2(_)
+            | Event::IntvarEvent(_)
+            | Event::RandEvent(_)
+            | Event::UserVarEvent(_)
+                if self.in_trx =>
+            {
+                Ok(self.account(len))
+            }
+            // events outside of transaction
+            _ => Ok(true),
+        }
+    }
+
+    /// returns whether the output of TransactionAssembler should be
+    /// delivered, chunks of split transaction follow the first one
+    pub fn filter_assembled(&mut self, assembled: &Assembled) -> bool {
+        let trx = match assembled {
+            Assembled::Transaction(trx) => trx,
+            Assembled::Single(_) => return true,
+        };
+        if trx.chunk == 0 {
+            self.start_trx();
+        }
+        if !self.in_trx {
+            // rest chunks of transaction started before the sampler
+            return false;
+        }
+        let deliver = self.account(trx.bytes);
+        if trx.last {
+            self.in_trx = false;
+            self.sampling = false;
+        }
+        deliver
+    }
+
+    /// number of transactions seen, including sampled ones
+    pub fn total_trxs(&self) -> u64 {
+        self.total_trxs
+    }
+
+    pub fn sampled_trxs(&self) -> u64 {
+        self.sampled_trxs
+    }
+
+    /// bytes of transaction events seen
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub fn sampled_bytes(&self) -> u64 {
+        self.sampled_bytes
+    }
+
+    /// factor to scale statistics of sampled transactions to
+    /// the whole stream, 0 if nothing sampled
+    pub fn scale(&self) -> f64 {
+        if self.sampled_trxs == 0 {
+            return 0.0;
+        }
+        match self.rate {
+            SampleRate::Transactions(_) => self.total_trxs as f64 / self.sampled_trxs as f64,
+            SampleRate::Bytes(_) => self.total_bytes as f64 / self.sampled_bytes as f64,
+        }
+    }
+
+    fn start_trx(&mut self) {
+        if self.in_trx {
+            log::debug!("transaction not ended before next one");
+        }
+        self.in_trx = true;
+        self.begun = false;
+        self.total_trxs += 1;
+        self.sampling = match self.rate {
+            SampleRate::Transactions(n) => (self.total_trxs - 1).is_multiple_of(n.max(1)),
+            SampleRate::Bytes(n) => self.since_sample >= n,
+        };
+        if self.sampling {
+            self.sampled_trxs += 1;
+            self.since_sample = 0;
+        }
+    }
+
+    fn account(&mut self, len: u64) -> bool {
+        self.total_bytes += len;
+        self.since_sample = self.since_sample.saturating_add(len);
+        if self.sampling {
+            self.sampled_bytes += len;
+        }
+        self.sampling
+    }
+
+    fn end_trx(&mut self, len: u64) -> bool {
+        if !self.in_trx {
+            // end without start, e.g. stream starts in the middle
+            // of a transaction
+            return false;
+        }
+        let deliver = self.account(len);
+        self.in_trx = false;
+        self.begun = false;
+        self.sampling = false;
+        deliver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::trx::TransactionAssembler;
+    use crate::binlog::{EventLength, LogEventType, ParserV4, QueryEvent, XidEvent};
+    use bytes::{Buf, Bytes, BytesMut};
+    use bytes_parser::ReadFromBytes;
+
+    const BINLOG_GTID_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.GtidEvent");
+
+    fn parse_all() -> Vec<Event> {
+        let mut input = Bytes::copy_from_slice(BINLOG_GTID_EVENT);
+        let pv4 = ParserV4::from_binlog_file(&mut input).unwrap();
+        let mut events = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            let mut raw = input.split_to(len);
+            if let Some(event) = pv4.parse_event(&mut raw, false).unwrap() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_trx_sampler() {
+        // the file has one transaction, replay it as another one
+        let events: Vec<_> = parse_all().into_iter().chain(parse_all()).collect();
+        let n_gtids = events
+            .iter()
+            .filter(|e| e.header().type_code == LogEventType::GtidLogEvent)
+            .count() as u64;
+        assert!(n_gtids > 1);
+
+        let mut sampler = TrxSampler::new(SampleRate::Transactions(2));
+        let mut delivered = vec![];
+        for e in &events {
+            if sampler.filter(e).unwrap() {
+                delivered.push(e.header().type_code);
+            }
+        }
+        assert_eq!(n_gtids, sampler.total_trxs());
+        assert_eq!(n_gtids.div_ceil(2), sampler.sampled_trxs());
+        // every delivered GTID is followed by its whole transaction
+        let gtids = delivered
+            .iter()
+            .filter(|t| **t == LogEventType::GtidLogEvent)
+            .count() as u64;
+        let xids = delivered
+            .iter()
+            .filter(|t| **t == LogEventType::XidEvent)
+            .count() as u64;
+        assert_eq!(sampler.sampled_trxs(), gtids);
+        assert!(xids <= gtids);
+        assert!(delivered.contains(&LogEventType::PreviousGtidsLogEvent));
+        assert!(sampler.scale() >= 1.0);
+
+        // each transaction exceeds 1 byte, so every one is sampled
+        let mut sampler = TrxSampler::new(SampleRate::Bytes(1));
+        for e in &events {
+            assert!(sampler.filter(e).unwrap());
+        }
+        assert_eq!(sampler.total_trxs(), sampler.sampled_trxs());
+        assert_eq!(sampler.total_bytes(), sampler.sampled_bytes());
+    }
+
+    /// query event of given statement, based on another query event
+    fn query(template: &QueryEvent, sql: &str) -> Event {
+        let query_len = template.clone().into_data().unwrap().query.len();
+        let mut data = BytesMut::from(&template.data[..template.data.len() - query_len]);
+        data.extend_from_slice(sql.as_bytes());
+        Event::QueryEvent(QueryEvent::new(template.header.clone(), data.freeze()))
+    }
+
+    /// transaction of statement-based replication without GTID
+    fn statement_trx() -> Vec<Event> {
+        let events = parse_all();
+        let ddl = match events.iter().find(|e| matches!(e, Event::QueryEvent(_))) {
+            Some(Event::QueryEvent(e)) => e.clone(),
+            other => panic!("unexpected event {:?}", other),
+        };
+        let mut header = ddl.header.clone();
+        header.type_code = LogEventType::XidEvent;
+        header.event_len = 19 + 8;
+        let xid = Event::XidEvent(XidEvent::new(
+            header,
+            Bytes::from(1u64.to_le_bytes().to_vec()),
+        ));
+        vec![
+            query(&ddl, "BEGIN"),
+            query(&ddl, "INSERT INTO t1 VALUES (1)"),
+            query(&ddl, "UPDATE t1 SET c1 = 2"),
+            xid,
+        ]
+    }
+
+    #[test]
+    fn test_trx_sampler_statement_based() {
+        let trx = statement_trx();
+        let mut sampler = TrxSampler::new(SampleRate::Transactions(2));
+        for e in &trx {
+            assert!(sampler.filter(e).unwrap());
+        }
+        for e in &trx {
+            assert!(!sampler.filter(e).unwrap());
+        }
+        assert_eq!(2, sampler.total_trxs());
+        assert_eq!(1, sampler.sampled_trxs());
+        assert_eq!(sampler.total_bytes(), sampler.sampled_bytes() * 2);
+    }
+
+    #[test]
+    fn test_trx_sampler_assembled() {
+        let mut assembler = TransactionAssembler::new();
+        let mut assembled = vec![];
+        for e in parse_all().into_iter().chain(statement_trx()) {
+            assembled.extend(assembler.push(e).unwrap());
+        }
+        let n_trxs = assembled
+            .iter()
+            .filter(|a| matches!(a, Assembled::Transaction(_)))
+            .count() as u64;
+        assert!(n_trxs > 1);
+
+        let mut sampler = TrxSampler::new(SampleRate::Transactions(2));
+        for a in &assembled {
+            let deliver = sampler.filter_assembled(a);
+            if let Assembled::Single(_) = a {
+                assert!(deliver);
+            }
+        }
+        assert_eq!(n_trxs, sampler.total_trxs());
+        assert_eq!(n_trxs.div_ceil(2), sampler.sampled_trxs());
+    }
+}
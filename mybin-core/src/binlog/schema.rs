@@ -0,0 +1,562 @@
+//! column attributes of tables, tracked from DDL in QueryEvent
+//!
+//! binlog carries no defaults or nullability of columns, so an applier
+//! receiving MINIMAL row images can not tell how to fill the columns
+//! missing in the image. the tracker parses CREATE TABLE and ALTER
+//! TABLE to keep name, nullability, default and ON UPDATE of each
+//! column. statements not understood leave the schema unchanged, and
+//! tables created before tracking starts are unknown.
+use super::ddl::{self, ObjectName, StatementKind};
+use crate::digest::{tokenize, Token};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnDefault {
+    /// literal as written in DDL, e.g. 'abc', -1, NULL
+    Literal(String),
+    /// function or parenthesized expression, e.g. CURRENT_TIMESTAMP(3)
+    Expression(String),
+}
+
+impl ColumnDefault {
+    /// text usable in INSERT statement
+    pub fn as_sql(&self) -> &str {
+        match self {
+            ColumnDefault::Literal(s) | ColumnDefault::Expression(s) => s,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnAttrs {
+    pub name: SmolStr,
+    pub nullable: bool,
+    pub default: Option<ColumnDefault>,
+    /// e.g. CURRENT_TIMESTAMP
+    pub on_update: Option<String>,
+    pub auto_increment: bool,
+}
+
+impl ColumnAttrs {
+    /// value of the column if omitted in INSERT, None if the server
+    /// generates it or falls back to implicit default of the type
+    pub fn insert_value(&self) -> Option<&str> {
+        match &self.default {
+            Some(default) => Some(default.as_sql()),
+            None if self.nullable && !self.auto_increment => Some("NULL"),
+            None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SchemaTracker {
+    tables: HashMap<(SmolStr, SmolStr), Vec<ColumnAttrs>>,
+}
+
+impl SchemaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// columns in order of table definition
+    pub fn columns(&self, db: &str, table: &str) -> Option<&[ColumnAttrs]> {
+        self.tables
+            .get(&(SmolStr::new(db), SmolStr::new(table)))
+            .map(|cols| cols.as_slice())
+    }
+
+    pub fn column(&self, db: &str, table: &str, name: &str) -> Option<&ColumnAttrs> {
+        self.columns(db, table)?
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// apply DDL executed in given default database
+    pub fn apply(&mut self, default_db: &str, sql: &str) {
+        let classified = ddl::classify(sql).with_default_db(default_db);
+        let key = |obj: &ObjectName| {
+            (
+                obj.db.clone().unwrap_or_else(|| SmolStr::new(default_db)),
+                obj.name.clone(),
+            )
+        };
+        let objects: Vec<_> = classified.objects.iter().map(key).collect();
+        match classified.kind {
+            StatementKind::CreateTable => {
+                let tokens = tokenize(sql);
+                let cols = match create_like(&tokens) {
+                    Some(src) => self.tables.get(&key(&src)).cloned(),
+                    None => table_elements(&tokens).map(|elems| create_columns(&elems)),
+                };
+                match cols {
+                    Some(cols) => {
+                        self.tables.insert(objects[0].clone(), cols);
+                    }
+                    // e.g. CREATE TABLE ... SELECT
+                    None => {
+                        self.tables.remove(&objects[0]);
+                    }
+                }
+            }
+            StatementKind::AlterTable => {
+                let tokens = tokenize(sql);
+                if let Some(cols) = self.tables.get_mut(&objects[0]) {
+                    for spec in alter_specs(&tokens) {
+                        alter_columns(cols, spec);
+                    }
+                }
+                // ALTER TABLE ... RENAME TO
+                if objects.len() == 2 {
+                    if let Some(cols) = self.tables.remove(&objects[0]) {
+                        self.tables.insert(objects[1].clone(), cols);
+                    }
+                }
+            }
+            StatementKind::Rename => {
+                for pair in objects.chunks(2) {
+                    if let [from, to] = pair {
+                        if let Some(cols) = self.tables.remove(from) {
+                            self.tables.insert(to.clone(), cols);
+                        }
+                    }
+                }
+            }
+            StatementKind::DropTable => {
+                for obj in &objects {
+                    self.tables.remove(obj);
+                }
+            }
+            StatementKind::DropDatabase => {
+                if let Some(db) = classified.objects.first() {
+                    self.tables.retain(|(d, _), _| *d != db.name);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// source table of CREATE TABLE ... LIKE
+fn create_like(tokens: &[Token]) -> Option<ObjectName> {
+    let pos = tokens.iter().position(|t| t.is_word("LIKE"))?;
+    let first = tokens.get(pos + 1)?.ident()?;
+    match (tokens.get(pos + 2), tokens.get(pos + 3)) {
+        (Some(t), Some(second)) if t.is(".") => Some(ObjectName::new(Some(first), second.ident()?)),
+        _ => Some(ObjectName::new(None, first)),
+    }
+}
+
+/// comma separated elements in first parentheses of CREATE TABLE
+fn table_elements(tokens: &[Token]) -> Option<Vec<&[Token]>> {
+    let start = tokens.iter().position(|t| t.is("("))? + 1;
+    let mut elems = vec![];
+    let mut depth = 0;
+    let mut elem_start = start;
+    for (i, t) in tokens.iter().enumerate().skip(start) {
+        if t.is("(") {
+            depth += 1;
+        } else if t.is(")") {
+            if depth == 0 {
+                elems.push(&tokens[elem_start..i]);
+                return Some(elems);
+            }
+            depth -= 1;
+        } else if t.is(",") && depth == 0 {
+            elems.push(&tokens[elem_start..i]);
+            elem_start = i + 1;
+        }
+    }
+    None
+}
+
+fn create_columns(elems: &[&[Token]]) -> Vec<ColumnAttrs> {
+    let mut cols: Vec<ColumnAttrs> = elems.iter().filter_map(|e| column_def(e)).collect();
+    // columns of primary key are implicitly not null
+    for elem in elems {
+        if let Some(names) = primary_key_columns(elem) {
+            for col in cols.iter_mut().filter(|c| names.contains(&c.name)) {
+                col.nullable = false;
+            }
+        }
+    }
+    cols
+}
+
+const CONSTRAINT_WORDS: [&str; 9] = [
+    "PRIMARY",
+    "KEY",
+    "INDEX",
+    "UNIQUE",
+    "CONSTRAINT",
+    "FOREIGN",
+    "FULLTEXT",
+    "SPATIAL",
+    "CHECK",
+];
+
+fn primary_key_columns(elem: &[Token]) -> Option<Vec<SmolStr>> {
+    let pos = elem
+        .windows(2)
+        .position(|w| w[0].is_word("PRIMARY") && w[1].is_word("KEY"))?;
+    // inline PRIMARY KEY of column definition is handled by column_def
+    if !CONSTRAINT_WORDS.iter().any(|w| elem[0].is_word(w)) {
+        return None;
+    }
+    let open = pos + elem[pos..].iter().position(|t| t.is("("))?;
+    let mut names = vec![];
+    let mut depth = 0;
+    for t in &elem[open + 1..] {
+        if t.is("(") {
+            depth += 1;
+        } else if t.is(")") {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+        } else if depth == 0 {
+            if let Some(name) = t.ident().or_else(|| word(t)) {
+                names.push(SmolStr::new(name));
+            }
+        }
+    }
+    Some(names)
+}
+
+/// name and attributes of column definition, None if the element
+/// is an index or constraint
+fn column_def(elem: &[Token]) -> Option<ColumnAttrs> {
+    let first = elem.first()?;
+    if CONSTRAINT_WORDS.iter().any(|w| first.is_word(w)) {
+        return None;
+    }
+    let name = first.ident().or_else(|| word(first))?;
+    let mut col = ColumnAttrs {
+        name: SmolStr::new(name),
+        nullable: true,
+        default: None,
+        on_update: None,
+        auto_increment: false,
+    };
+    let mut i = 1;
+    let mut depth = 0;
+    while i < elem.len() {
+        let t = &elem[i];
+        if t.is("(") {
+            depth += 1;
+        } else if t.is(")") {
+            depth -= 1;
+        } else if depth > 0 {
+            // type arguments, e.g. ENUM('a', 'b')
+        } else if t.is_word("NOT") && is_null(elem.get(i + 1)) {
+            col.nullable = false;
+            i += 1;
+        } else if is_null(Some(t)) {
+            col.nullable = true;
+        } else if t.is_word("PRIMARY") {
+            col.nullable = false;
+        } else if t.is_word("AUTO_INCREMENT") {
+            col.auto_increment = true;
+        } else if t.is_word("DEFAULT") {
+            if let Some((default, next)) = default_value(elem, i + 1) {
+                col.default = Some(default);
+                i = next;
+                continue;
+            }
+        } else if t.is_word("ON") && elem.get(i + 1).is_some_and(|t| t.is_word("UPDATE")) {
+            if let Some((expr, next)) = function_call(elem, i + 2) {
+                col.on_update = Some(expr);
+                i = next;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    Some(col)
+}
+
+fn is_null(t: Option<&Token>) -> bool {
+    matches!(t, Some(Token::Literal(lit)) if lit.eq_ignore_ascii_case("NULL"))
+}
+
+fn word(t: &Token) -> Option<&str> {
+    match t {
+        Token::Keyword(w) | Token::Ident(w) => Some(w),
+        _ => None,
+    }
+}
+
+/// default value starting at given position, and position after it
+fn default_value(elem: &[Token], start: usize) -> Option<(ColumnDefault, usize)> {
+    match elem.get(start)? {
+        Token::Literal(lit) => Some((ColumnDefault::Literal(lit.clone()), start + 1)),
+        Token::Other(sign) if sign == "-" || sign == "+" => match elem.get(start + 1)? {
+            Token::Literal(lit) => Some((
+                ColumnDefault::Literal(format!("{}{}", sign, lit)),
+                start + 2,
+            )),
+            _ => None,
+        },
+        t if t.is("(") => {
+            let end = closing_paren(elem, start)?;
+            Some((
+                ColumnDefault::Expression(render(&elem[start..=end])),
+                end + 1,
+            ))
+        }
+        _ => {
+            let (expr, next) = function_call(elem, start)?;
+            Some((ColumnDefault::Expression(expr), next))
+        }
+    }
+}
+
+/// word optionally followed by arguments, e.g. NOW() or CURRENT_TIMESTAMP
+fn function_call(elem: &[Token], start: usize) -> Option<(String, usize)> {
+    let name = word(elem.get(start)?)?;
+    match elem.get(start + 1) {
+        Some(t) if t.is("(") => {
+            let end = closing_paren(elem, start + 1)?;
+            Some((render(&elem[start..=end]), end + 1))
+        }
+        _ => Some((name.to_ascii_uppercase(), start + 1)),
+    }
+}
+
+fn closing_paren(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate().skip(open) {
+        if t.is("(") {
+            depth += 1;
+        } else if t.is(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn render(tokens: &[Token]) -> String {
+    let mut s = String::new();
+    let mut prev_word = false;
+    for t in tokens {
+        let (text, is_word) = match t {
+            Token::Keyword(w) => (w.to_ascii_uppercase(), true),
+            Token::Ident(w) => (w.clone(), true),
+            Token::Quoted(w) => (format!("`{}`", w.replace('`', "``")), true),
+            Token::Literal(lit) => (lit.clone(), true),
+            Token::Other(o) => (o.clone(), false),
+        };
+        if prev_word && is_word {
+            s.push(' ');
+        }
+        s.push_str(&text);
+        prev_word = is_word;
+    }
+    s
+}
+
+/// comma separated specifications after table name of ALTER TABLE
+fn alter_specs(tokens: &[Token]) -> Vec<&[Token]> {
+    let start = match tokens.iter().position(|t| t.is_word("TABLE")) {
+        Some(pos) => {
+            let dotted = tokens.get(pos + 2).is_some_and(|t| t.is("."));
+            pos + if dotted { 4 } else { 2 }
+        }
+        None => return vec![],
+    };
+    let mut specs = vec![];
+    let mut depth = 0;
+    let mut spec_start = start;
+    for (i, t) in tokens.iter().enumerate().skip(start) {
+        if t.is("(") {
+            depth += 1;
+        } else if t.is(")") {
+            depth -= 1;
+        } else if t.is(",") && depth == 0 {
+            specs.push(&tokens[spec_start..i]);
+            spec_start = i + 1;
+        }
+    }
+    if spec_start < tokens.len() {
+        specs.push(&tokens[spec_start..]);
+    }
+    specs
+}
+
+fn alter_columns(cols: &mut Vec<ColumnAttrs>, spec: &[Token]) {
+    let first = match spec.first() {
+        Some(t) => t,
+        None => return,
+    };
+    let mut rest = &spec[1..];
+    let skip_column = |rest: &mut &[Token]| {
+        if rest.first().is_some_and(|t| t.is_word("COLUMN")) {
+            *rest = &rest[1..];
+        }
+    };
+    if first.is_word("ADD") {
+        skip_column(&mut rest);
+        if rest.first().is_some_and(|t| t.is("(")) {
+            // ADD (c1 INT, c2 INT)
+            if let Some(elems) = table_elements(rest) {
+                cols.extend(elems.iter().filter_map(|e| column_def(e)));
+            }
+        } else if let Some(col) = column_def(rest) {
+            let pos = column_position(cols, rest);
+            cols.insert(pos, col);
+        }
+    } else if first.is_word("DROP") {
+        skip_column(&mut rest);
+        if let Some(name) = rest.first().and_then(|t| t.ident().or_else(|| word(t))) {
+            if !CONSTRAINT_WORDS.iter().any(|w| rest[0].is_word(w)) {
+                cols.retain(|c| !c.name.eq_ignore_ascii_case(name));
+            }
+        }
+    } else if first.is_word("MODIFY") || first.is_word("CHANGE") {
+        skip_column(&mut rest);
+        let old = match rest.first().and_then(|t| t.ident().or_else(|| word(t))) {
+            Some(old) => SmolStr::new(old),
+            None => return,
+        };
+        if first.is_word("CHANGE") {
+            rest = &rest[1..];
+        }
+        if let (Some(idx), Some(col)) = (
+            cols.iter().position(|c| c.name.eq_ignore_ascii_case(&old)),
+            column_def(rest),
+        ) {
+            cols.remove(idx);
+            let pos = if has_position(rest) {
+                column_position(cols, rest)
+            } else {
+                idx
+            };
+            cols.insert(pos, col);
+        }
+    } else if first.is_word("ALTER") {
+        skip_column(&mut rest);
+        let col = match rest
+            .first()
+            .and_then(|t| t.ident().or_else(|| word(t)))
+            .and_then(|name| cols.iter_mut().find(|c| c.name.eq_ignore_ascii_case(name)))
+        {
+            Some(col) => col,
+            None => return,
+        };
+        if rest.get(1).is_some_and(|t| t.is_word("SET"))
+            && rest.get(2).is_some_and(|t| t.is_word("DEFAULT"))
+        {
+            col.default = default_value(rest, 3).map(|(d, _)| d);
+        } else if rest.get(1).is_some_and(|t| t.is_word("DROP")) {
+            col.default = None;
+        }
+    } else if first.is_word("RENAME") && rest.first().is_some_and(|t| t.is_word("COLUMN")) {
+        if let (Some(old), Some(new)) = (
+            rest.get(1).and_then(|t| t.ident().or_else(|| word(t))),
+            rest.get(3).and_then(|t| t.ident().or_else(|| word(t))),
+        ) {
+            if let Some(col) = cols.iter_mut().find(|c| c.name.eq_ignore_ascii_case(old)) {
+                col.name = SmolStr::new(new);
+            }
+        }
+    }
+}
+
+fn has_position(def: &[Token]) -> bool {
+    def.iter().any(|t| t.is_word("FIRST") || t.is_word("AFTER"))
+}
+
+/// index to insert column by FIRST or AFTER, at end by default
+fn column_position(cols: &[ColumnAttrs], def: &[Token]) -> usize {
+    if def.iter().any(|t| t.is_word("FIRST")) {
+        return 0;
+    }
+    def.iter()
+        .position(|t| t.is_word("AFTER"))
+        .and_then(|pos| def.get(pos + 1))
+        .and_then(|t| t.ident().or_else(|| word(t)))
+        .and_then(|name| cols.iter().position(|c| c.name.eq_ignore_ascii_case(name)))
+        .map_or(cols.len(), |idx| idx + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_tracker() {
+        let mut tracker = SchemaTracker::new();
+        tracker.apply(
+            "db1",
+            "CREATE TABLE t1 (
+                id BIGINT AUTO_INCREMENT,
+                name VARCHAR(20) NOT NULL DEFAULT 'n/a',
+                kind ENUM('a', 'b') DEFAULT NULL,
+                score INT DEFAULT -1,
+                note TEXT,
+                created DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+                updated TIMESTAMP DEFAULT now() ON UPDATE CURRENT_TIMESTAMP,
+                PRIMARY KEY (id),
+                KEY idx_name (name)
+            )",
+        );
+        let cols = tracker.columns("db1", "t1").unwrap();
+        let names: Vec<_> = cols.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            vec!["id", "name", "kind", "score", "note", "created", "updated"],
+            names
+        );
+        assert!(!cols[0].nullable && cols[0].auto_increment);
+        assert_eq!(None, cols[0].insert_value());
+        assert_eq!(Some("'n/a'"), cols[1].insert_value());
+        assert!(!cols[1].nullable);
+        assert_eq!(Some("NULL"), cols[2].insert_value());
+        assert_eq!(Some("-1"), cols[3].insert_value());
+        assert_eq!(Some("NULL"), cols[4].insert_value());
+        assert_eq!(
+            Some(&ColumnDefault::Expression(
+                "CURRENT_TIMESTAMP(3)".to_owned()
+            )),
+            cols[5].default.as_ref()
+        );
+        assert_eq!(Some("CURRENT_TIMESTAMP"), cols[6].on_update.as_deref());
+        assert_eq!(Some("NOW()"), cols[6].insert_value());
+
+        tracker.apply(
+            "db1",
+            "ALTER TABLE `db1`.`t1` ADD COLUMN flag TINYINT NOT NULL DEFAULT 0 AFTER id, \
+             DROP COLUMN note, MODIFY score INT NOT NULL, ALTER name DROP DEFAULT, \
+             RENAME COLUMN kind TO category, ADD INDEX idx_flag (flag)",
+        );
+        let names: Vec<_> = tracker
+            .columns("db1", "t1")
+            .unwrap()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(
+            vec!["id", "flag", "name", "category", "score", "created", "updated"],
+            names
+        );
+        let flag = tracker.column("db1", "t1", "FLAG").unwrap();
+        assert_eq!(Some("0"), flag.insert_value());
+        assert!(!tracker.column("db1", "t1", "score").unwrap().nullable);
+        assert_eq!(None, tracker.column("db1", "t1", "name").unwrap().default);
+
+        tracker.apply("db2", "CREATE TABLE t2 LIKE db1.t1");
+        assert_eq!(7, tracker.columns("db2", "t2").unwrap().len());
+        tracker.apply("db1", "RENAME TABLE t1 TO t3");
+        assert!(tracker.columns("db1", "t1").is_none());
+        assert!(tracker.columns("db1", "t3").is_some());
+        tracker.apply("db1", "CREATE TABLE t4 AS SELECT * FROM t3");
+        assert!(tracker.columns("db1", "t4").is_none());
+        tracker.apply("db1", "DROP TABLE t3");
+        assert!(tracker.columns("db1", "t3").is_none());
+        tracker.apply("", "DROP DATABASE db2");
+        assert!(tracker.columns("db2", "t2").is_none());
+    }
+}
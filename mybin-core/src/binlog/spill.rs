@@ -0,0 +1,152 @@
+//! buffer of events spilled to a temporary file
+//!
+//! each event is appended as a record of 4-byte length, 19-byte header
+//! and payload without checksum. the file is read back sequentially,
+//! one event at a time, and removed when the buffer is dropped.
+use super::header::EventHeader;
+use super::parser::new_event;
+use super::Event;
+use crate::error::{Error, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use bytes_parser::{ReadFromBytes, WriteToBytes};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const HEADER_LEN: usize = 19;
+
+static SPILL_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+pub struct SpillBuffer {
+    path: PathBuf,
+    // None once finished
+    writer: Option<BufWriter<File>>,
+    len: usize,
+    bytes: u64,
+}
+
+impl SpillBuffer {
+    /// create an empty file in given directory
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref().join(format!(
+            "mybin-spill-{}-{}",
+            std::process::id(),
+            SPILL_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        // never truncate an existing file
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(SpillBuffer {
+            path,
+            writer: Some(BufWriter::new(file)),
+            len: 0,
+            bytes: 0,
+        })
+    }
+
+    pub fn push(&mut self, event: &Event) -> Result<()> {
+        let writer = self.writer.as_mut().ok_or_else(|| {
+            Error::IoError(std::io::Error::other("spill buffer already finished"))
+        })?;
+        let header = event.header();
+        let data = event.data();
+        let mut buf = BytesMut::with_capacity(4 + HEADER_LEN);
+        buf.put_u32_le((HEADER_LEN + data.len()) as u32);
+        header.write_to(&mut buf)?;
+        writer.write_all(&buf)?;
+        writer.write_all(data)?;
+        self.len += 1;
+        self.bytes += header.event_len as u64;
+        Ok(())
+    }
+
+    /// flush written events, no more event can be pushed
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// number of spilled events
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// total length of spilled events
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// read events back in order, buffer must be finished
+    pub fn iter(&self) -> Result<SpillIter> {
+        if self.writer.is_some() {
+            return Err(Error::IoError(std::io::Error::other(
+                "spill buffer not finished",
+            )));
+        }
+        let reader = BufReader::new(File::open(&self.path)?);
+        Ok(SpillIter {
+            reader,
+            remaining: self.len,
+        })
+    }
+}
+
+impl Drop for SpillBuffer {
+    fn drop(&mut self) {
+        self.writer.take();
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("failed to remove spill file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SpillIter {
+    reader: BufReader<File>,
+    remaining: usize,
+}
+
+impl SpillIter {
+    fn read_event(&mut self) -> Result<Event> {
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut record)?;
+        let mut data = Bytes::from(record);
+        let header = EventHeader::read_from(&mut data)?;
+        let type_code = header.type_code;
+        new_event(header, data).ok_or_else(|| {
+            Error::BinlogEventError(format!("unsupported spilled event {:?}", type_code))
+        })
+    }
+}
+
+impl Iterator for SpillIter {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let res = self.read_event();
+        if res.is_err() {
+            self.remaining = 0;
+        }
+        Some(res)
+    }
+}
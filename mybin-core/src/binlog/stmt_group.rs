@@ -0,0 +1,133 @@
+//! group rows events of one statement
+//!
+//! a statement changing many rows is logged as several rows events,
+//! each preceded by table maps, and only the last one is flagged with
+//! STMT_END. consumers applying statements atomically, e.g. to check
+//! foreign keys, need the events as a group.
+use super::{Event, RowsEventFlags};
+use crate::error::Result;
+
+#[derive(Debug, Clone)]
+pub enum Grouped {
+    /// table maps and rows events of one statement
+    Statement(Vec<Event>),
+    /// event not belonging to any statement
+    Single(Event),
+}
+
+#[derive(Debug, Default)]
+pub struct StatementGrouper {
+    pending: Vec<Event>,
+}
+
+impl StatementGrouper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// push next event, returns completed groups in order
+    pub fn push(&mut self, event: Event) -> Result<Vec<Grouped>> {
+        if let Event::TableMapEvent(_) = event {
+            self.pending.push(event);
+            return Ok(vec![]);
+        }
+        match event.rows_flags()? {
+            Some(flags) => {
+                self.pending.push(event);
+                if flags.contains(RowsEventFlags::STMT_END) {
+                    let stmt = std::mem::take(&mut self.pending);
+                    return Ok(vec![Grouped::Statement(stmt)]);
+                }
+                Ok(vec![])
+            }
+            None => {
+                let mut res = Vec::with_capacity(2);
+                if let Some(stmt) = self.flush() {
+                    log::warn!("statement not ended before {:?}", event.header().type_code);
+                    res.push(stmt);
+                }
+                res.push(Grouped::Single(event));
+                Ok(res)
+            }
+        }
+    }
+
+    /// events of statement not ended, e.g. at end of stream
+    pub fn flush(&mut self) -> Option<Grouped> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(Grouped::Statement(std::mem::take(&mut self.pending)))
+    }
+
+    /// number of events waiting for end of statement
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventHeader, EventHeaderFlags, LogEventType, RawEvent};
+    use bytes::Bytes;
+
+    fn event(type_code: LogEventType, payload: Vec<u8>) -> Event {
+        let header = EventHeader {
+            timestamp: 0,
+            type_code,
+            server_id: 1,
+            event_len: 19 + payload.len() as u32,
+            next_pos: 0,
+            flags: EventHeaderFlags::empty(),
+        };
+        let data = Bytes::from(payload);
+        match type_code {
+            LogEventType::TableMapEvent => Event::TableMapEvent(RawEvent::new(header, data)),
+            LogEventType::WriteRowsEventV2 => Event::WriteRowsEventV2(RawEvent::new(header, data)),
+            _ => Event::XidEvent(RawEvent::new(header, data)),
+        }
+    }
+
+    // table id, flags, extra data length
+    fn rows(flags: RowsEventFlags) -> Event {
+        let mut payload = vec![1, 0, 0, 0, 0, 0];
+        payload.extend_from_slice(&flags.bits().to_le_bytes());
+        payload.extend_from_slice(&[2, 0]);
+        event(LogEventType::WriteRowsEventV2, payload)
+    }
+
+    #[test]
+    fn test_statement_grouper() {
+        let mut grouper = StatementGrouper::new();
+        let tm = || event(LogEventType::TableMapEvent, vec![]);
+        assert!(grouper.push(tm()).unwrap().is_empty());
+        assert!(grouper
+            .push(rows(RowsEventFlags::NO_FOREIGN_KEY_CHECKS))
+            .unwrap()
+            .is_empty());
+        assert!(grouper.push(tm()).unwrap().is_empty());
+        assert_eq!(3, grouper.pending());
+        let end = rows(RowsEventFlags::STMT_END | RowsEventFlags::NO_FOREIGN_KEY_CHECKS);
+        assert_eq!(
+            Some(RowsEventFlags::STMT_END | RowsEventFlags::NO_FOREIGN_KEY_CHECKS),
+            end.rows_flags().unwrap()
+        );
+        match &grouper.push(end).unwrap()[..] {
+            [Grouped::Statement(events)] => assert_eq!(4, events.len()),
+            other => panic!("unexpected groups {:?}", other),
+        }
+        assert_eq!(0, grouper.pending());
+
+        // statement interrupted by xid
+        grouper.push(tm()).unwrap();
+        grouper.push(rows(RowsEventFlags::empty())).unwrap();
+        let xid = event(LogEventType::XidEvent, vec![0; 8]);
+        assert_eq!(None, xid.rows_flags().unwrap());
+        match &grouper.push(xid).unwrap()[..] {
+            [Grouped::Statement(events), Grouped::Single(_)] => assert_eq!(2, events.len()),
+            other => panic!("unexpected groups {:?}", other),
+        }
+        assert!(grouper.flush().is_none());
+    }
+}
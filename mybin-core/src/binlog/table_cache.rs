@@ -0,0 +1,163 @@
+//! decoded table maps shared across threads
+//!
+//! workers applying transactions in parallel look up table maps of
+//! the same tables, the cache decodes each table map only once.
+use crate::binlog::table_map::TableMapData;
+use crate::binlog::TableMap;
+use crate::error::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+
+/// immutable decoded table map, clones are cheap
+#[derive(Debug, Clone)]
+pub struct DecodedTableMap(Arc<TableMap>);
+
+impl Deref for DecodedTableMap {
+    type Target = TableMap;
+
+    fn deref(&self) -> &TableMap {
+        &self.0
+    }
+}
+
+impl From<TableMap> for DecodedTableMap {
+    fn from(table_map: TableMap) -> Self {
+        DecodedTableMap(Arc::new(table_map))
+    }
+}
+
+/// concurrent cache keyed by (table_id, schema_version)
+///
+/// the caller bumps schema_version on DDL so stale entries are not hit.
+/// MySQL may also reassign a table id without any DDL, e.g. after
+/// the table is evicted from table definition cache or FLUSH TABLES,
+/// so the raw table map is compared on lookup and a mismatched entry
+/// is replaced.
+#[derive(Debug, Default)]
+pub struct TableMapCache {
+    // decoded table map with its raw payload
+    maps: RwLock<HashMap<(u64, u64), (Bytes, DecodedTableMap)>>,
+}
+
+impl TableMapCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// cached table map of same table id and payload
+    pub fn get(&self, data: &TableMapData, schema_version: u64) -> Option<DecodedTableMap> {
+        match self
+            .maps
+            .read()
+            .unwrap()
+            .get(&(data.table_id, schema_version))
+        {
+            Some((payload, tm)) if *payload == data.payload => Some(tm.clone()),
+            _ => None,
+        }
+    }
+
+    /// returns cached table map or decodes and caches it
+    ///
+    /// decoding is done without holding the lock, if two threads
+    /// decode the same table map, the first inserted one is kept
+    pub fn get_or_decode(
+        &self,
+        data: &TableMapData,
+        schema_version: u64,
+    ) -> Result<DecodedTableMap> {
+        if let Some(tm) = self.get(data, schema_version) {
+            return Ok(tm);
+        }
+        let tm = DecodedTableMap::from(data.table_map()?);
+        let mut maps = self.maps.write().unwrap();
+        let entry = maps
+            .entry((data.table_id, schema_version))
+            .or_insert_with(|| (data.payload.clone(), tm.clone()));
+        if entry.0 != data.payload {
+            // table id is reassigned to another table
+            *entry = (data.payload.clone(), tm);
+        }
+        Ok(entry.1.clone())
+    }
+
+    pub fn insert(&self, data: &TableMapData, schema_version: u64, table_map: DecodedTableMap) {
+        self.maps.write().unwrap().insert(
+            (data.table_id, schema_version),
+            (data.payload.clone(), table_map),
+        );
+    }
+
+    /// remove entries of schema versions older than given one
+    pub fn evict_before(&self, schema_version: u64) {
+        self.maps
+            .write()
+            .unwrap()
+            .retain(|(_, version), _| *version >= schema_version);
+    }
+
+    pub fn len(&self) -> usize {
+        self.maps.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.maps.write().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_table_map_cache() {
+        // schema db1, table t1, one LONG column
+        let mut payload = vec![3u8];
+        payload.extend_from_slice(b"db1\0");
+        payload.push(2);
+        payload.extend_from_slice(b"t1\0");
+        payload.extend_from_slice(&[1, 3, 0, 0]);
+        let data = TableMapData {
+            table_id: 100,
+            flags: 1,
+            payload: Bytes::from(payload),
+        };
+        let cache = Arc::new(TableMapCache::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                let data = data.clone();
+                std::thread::spawn(move || cache.get_or_decode(&data, 1).unwrap())
+            })
+            .collect();
+        let tms: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!("t1", tms[0].table_name);
+        assert!(tms.iter().all(|tm| Arc::ptr_eq(&tm.0, &tms[0].0)));
+        assert_eq!(1, cache.len());
+        assert!(cache.get(&data, 2).is_none());
+        cache.get_or_decode(&data, 2).unwrap();
+        cache.evict_before(2);
+        assert_eq!(1, cache.len());
+        assert!(cache.get(&data, 1).is_none());
+
+        // table id reassigned to table t2 without DDL
+        let mut payload = data.payload.to_vec();
+        payload[7] = b'2';
+        let data2 = TableMapData {
+            payload: Bytes::from(payload),
+            ..data.clone()
+        };
+        assert!(cache.get(&data2, 2).is_none());
+        assert_eq!("t2", cache.get_or_decode(&data2, 2).unwrap().table_name);
+        assert_eq!(1, cache.len());
+        assert!(cache.get(&data, 2).is_none());
+        assert_eq!("t2", cache.get(&data2, 2).unwrap().table_name);
+    }
+}
@@ -0,0 +1,129 @@
+//! allow and deny lists of tables with glob patterns
+//!
+//! patterns are `db.table`, where both parts may contain `*` matching
+//! any characters and `?` matching a single character, e.g.
+//! `shop.order_*`. names are matched case sensitively, as table names
+//! of MySQL on Linux.
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TablePattern {
+    db: String,
+    table: String,
+}
+
+impl TablePattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        match pattern.split_once('.') {
+            Some((db, table)) if !db.is_empty() && !table.is_empty() => Ok(TablePattern {
+                db: db.to_owned(),
+                table: table.to_owned(),
+            }),
+            _ => Err(Error::InvalidTablePattern(format!(
+                "{}, expected db.table",
+                pattern
+            ))),
+        }
+    }
+
+    pub fn matches(&self, db: &str, table: &str) -> bool {
+        glob_match(self.db.as_bytes(), db.as_bytes())
+            && glob_match(self.table.as_bytes(), table.as_bytes())
+    }
+}
+
+/// tables not in include list, or in exclude list, are filtered out.
+/// empty include list includes all tables
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableFilter {
+    include: Vec<TablePattern>,
+    exclude: Vec<TablePattern>,
+}
+
+impl TableFilter {
+    pub fn new<I, E, T, U>(include: I, exclude: E) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        E: IntoIterator<Item = U>,
+        T: AsRef<str>,
+        U: AsRef<str>,
+    {
+        let include = include
+            .into_iter()
+            .map(|p| TablePattern::parse(p.as_ref()))
+            .collect::<Result<_>>()?;
+        let exclude = exclude
+            .into_iter()
+            .map(|p| TablePattern::parse(p.as_ref()))
+            .collect::<Result<_>>()?;
+        Ok(TableFilter { include, exclude })
+    }
+
+    /// whether all tables are accepted
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn accepts(&self, db: &str, table: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(db, table)))
+            && !self.exclude.iter().any(|p| p.matches(db, table))
+    }
+}
+
+// iterative matching with backtrack to last star
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_filter() {
+        assert!(glob_match(b"order_*", b"order_items"));
+        assert!(glob_match(b"order_*", b"order_"));
+        assert!(!glob_match(b"order_*", b"orders"));
+        assert!(glob_match(b"*_bak?", b"t_1_bak2"));
+        assert!(!glob_match(b"t?", b"t"));
+        assert!(glob_match(b"*", b""));
+
+        let filter =
+            TableFilter::new(["shop.orders", "shop.order_*"], ["shop.order_bak*"]).unwrap();
+        assert!(filter.accepts("shop", "orders"));
+        assert!(filter.accepts("shop", "order_items"));
+        assert!(!filter.accepts("shop", "order_bak1"));
+        assert!(!filter.accepts("shop", "users"));
+        assert!(!filter.accepts("shop2", "orders"));
+
+        let filter = TableFilter::new(Vec::<String>::new(), ["*.tmp_*"]).unwrap();
+        assert!(filter.accepts("shop", "orders"));
+        assert!(!filter.accepts("crm", "tmp_1"));
+        assert!(TableFilter::default().is_empty());
+
+        assert!(TableFilter::new(["orders"], Vec::<String>::new()).is_err());
+        assert!(TableFilter::new(["shop."], Vec::<String>::new()).is_err());
+    }
+}
@@ -48,6 +48,27 @@ impl<'a> TableMapData {
         let rtm = RawTableMap::read_from(&mut self.payload)?;
         rtm.try_into()
     }
+
+    /// schema and table names, without decoding columns
+    pub fn names(&self) -> Result<(SmolStr, SmolStr)> {
+        let mut input = self.payload.clone();
+        let schema_name_len = input.read_u8()?;
+        let schema_name = input.read_len(schema_name_len as usize)?;
+        input.read_len(1)?;
+        let table_name_len = input.read_u8()?;
+        let table_name = input.read_len(table_name_len as usize)?;
+        Ok((
+            SmolStr::new(String::from_utf8_lossy(&schema_name)),
+            SmolStr::new(String::from_utf8_lossy(&table_name)),
+        ))
+    }
+
+    /// table map of binlog written in given temporal format,
+    /// see ParserV4::temporal_format
+    pub fn table_map_with_format(&self, format: TemporalFormat) -> crate::error::Result<TableMap> {
+        let rtm = RawTableMap::read_from(&mut self.payload.clone())?;
+        rtm.into_table_map(format)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +79,8 @@ struct RawTableMap {
     pub col_defs: Bytes,
     pub col_meta_defs: Bytes,
     pub null_bitmap: Bytes,
+    // optional metadata since 8.0.1, controlled by binlog_row_metadata
+    pub optional_metadata: Bytes,
 }
 
 /// reference: https://github.com/mysql/mysql-server/blob/5.7/libbinlogevents/include/rows_event.h
@@ -85,6 +108,7 @@ impl ReadFromBytes for RawTableMap {
         // 1+2
         let bitmap_len = (col_cnt + 7) / 8u64;
         let null_bitmap = input.read_len(bitmap_len as usize)?;
+        let optional_metadata = input.split_to(input.remaining());
         Ok(RawTableMap {
             schema_name,
             table_name,
@@ -92,6 +116,7 @@ impl ReadFromBytes for RawTableMap {
             col_defs,
             col_meta_defs,
             null_bitmap,
+            optional_metadata,
         })
     }
 }
@@ -102,24 +127,261 @@ pub struct TableMap {
     pub table_name: SmolStr,
     pub col_metas: ColumnMetas,
     pub null_bitmap: Vec<u8>,
+    pub metadata: TableMetadata,
+}
+
+impl TableMap {
+    /// signedness of numeric column, None if not numeric
+    /// or not recorded
+    pub fn is_unsigned(&self, col_idx: usize) -> Option<bool> {
+        let mut unsigned = self.metadata.signedness.iter();
+        let mut flag = None;
+        for meta in self.col_metas.iter().take(col_idx + 1) {
+            flag = if is_numeric(meta) {
+                unsigned.next().cloned()
+            } else {
+                None
+            };
+        }
+        flag
+    }
+
+    /// column name, only recorded if binlog_row_metadata=FULL
+    pub fn column_name(&self, col_idx: usize) -> Option<&str> {
+        self.metadata.column_names.get(col_idx).map(|s| s.as_str())
+    }
+}
+
+/// charset of character columns, encoded as default charset
+/// and exceptions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DefaultCharset {
+    pub default_charset: u32,
+    /// (index of character column, charset)
+    pub exceptions: Vec<(u32, u32)>,
+}
+
+/// optional metadata of TableMapEvent
+///
+/// fields are empty if not recorded. with binlog_row_metadata=MINIMAL,
+/// only signedness, charsets, geometry types are recorded.
+/// column indexes of charsets and string values are counted among
+/// columns of related types, as MySQL does.
+/// reference: https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/include/rows_event.h
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableMetadata {
+    /// signedness of numeric columns, true if unsigned
+    pub signedness: Vec<bool>,
+    pub default_charset: Option<DefaultCharset>,
+    /// charsets of character columns
+    pub column_charsets: Vec<u32>,
+    pub column_names: Vec<SmolStr>,
+    /// string values of each SET column
+    pub set_str_values: Vec<Vec<Bytes>>,
+    /// string values of each ENUM column
+    pub enum_str_values: Vec<Vec<Bytes>>,
+    pub geometry_types: Vec<u32>,
+    /// (column index, prefix length), prefix length is 0 if
+    /// the whole column is used
+    pub primary_key: Vec<(u32, u32)>,
+    pub enum_and_set_default_charset: Option<DefaultCharset>,
+    pub enum_and_set_column_charsets: Vec<u32>,
+    /// visibility of all columns, since 8.0.23
+    pub column_visibility: Vec<bool>,
+}
+
+impl TableMetadata {
+    /// parse TLV fields, unknown fields are ignored
+    pub fn read_from(input: &mut Bytes, col_metas: &[ColumnMeta]) -> Result<Self> {
+        let mut md = TableMetadata::default();
+        while input.has_remaining() {
+            let field_type = input.read_u8()?;
+            let len = read_len_enc_u32(input)?;
+            let mut value = input.read_len(len as usize)?;
+            match field_type {
+                1 => {
+                    let n_numeric = col_metas.iter().filter(|m| is_numeric(m)).count();
+                    md.signedness = read_bitmap(&value, n_numeric);
+                }
+                2 => md.default_charset = Some(read_default_charset(&mut value)?),
+                3 => md.column_charsets = read_u32_list(&mut value)?,
+                4 => {
+                    while value.has_remaining() {
+                        let name = read_len_enc_bytes(&mut value)?;
+                        md.column_names
+                            .push(SmolStr::from(std::str::from_utf8(name.chunk())?));
+                    }
+                }
+                5 => md.set_str_values = read_str_values(&mut value)?,
+                6 => md.enum_str_values = read_str_values(&mut value)?,
+                7 => md.geometry_types = read_u32_list(&mut value)?,
+                8 => {
+                    md.primary_key = read_u32_list(&mut value)?
+                        .into_iter()
+                        .map(|idx| (idx, 0))
+                        .collect()
+                }
+                9 => {
+                    while value.has_remaining() {
+                        let idx = read_len_enc_u32(&mut value)?;
+                        let prefix = read_len_enc_u32(&mut value)?;
+                        md.primary_key.push((idx, prefix));
+                    }
+                }
+                10 => md.enum_and_set_default_charset = Some(read_default_charset(&mut value)?),
+                11 => md.enum_and_set_column_charsets = read_u32_list(&mut value)?,
+                12 => md.column_visibility = read_bitmap(&value, col_metas.len()),
+                _ => log::debug!("unknown table map optional metadata type {}", field_type),
+            }
+        }
+        Ok(md)
+    }
+}
+
+fn is_numeric(meta: &ColumnMeta) -> bool {
+    matches!(
+        meta,
+        ColumnMeta::Decimal
+            | ColumnMeta::Tiny
+            | ColumnMeta::Short
+            | ColumnMeta::Int24
+            | ColumnMeta::Long
+            | ColumnMeta::LongLong
+            | ColumnMeta::Float { .. }
+            | ColumnMeta::Double { .. }
+            | ColumnMeta::NewDecimal { .. }
+    )
+}
+
+fn read_len_enc_u32(input: &mut Bytes) -> Result<u32> {
+    input
+        .read_len_enc_int()?
+        .to_u64()
+        .map(|n| n as u32)
+        .ok_or_else(|| Error::ConstraintError("invalid length encoded integer".to_owned()))
+}
+
+fn read_len_enc_bytes(input: &mut Bytes) -> Result<Bytes> {
+    let len = read_len_enc_u32(input)?;
+    input.read_len(len as usize)
+}
+
+fn read_u32_list(input: &mut Bytes) -> Result<Vec<u32>> {
+    let mut list = vec![];
+    while input.has_remaining() {
+        list.push(read_len_enc_u32(input)?);
+    }
+    Ok(list)
+}
+
+fn read_default_charset(input: &mut Bytes) -> Result<DefaultCharset> {
+    let default_charset = read_len_enc_u32(input)?;
+    let mut exceptions = vec![];
+    while input.has_remaining() {
+        let idx = read_len_enc_u32(input)?;
+        let charset = read_len_enc_u32(input)?;
+        exceptions.push((idx, charset));
+    }
+    Ok(DefaultCharset {
+        default_charset,
+        exceptions,
+    })
+}
+
+fn read_str_values(input: &mut Bytes) -> Result<Vec<Vec<Bytes>>> {
+    let mut cols = vec![];
+    while input.has_remaining() {
+        let n = read_len_enc_u32(input)?;
+        let values = (0..n)
+            .map(|_| read_len_enc_bytes(input))
+            .collect::<Result<Vec<_>>>()?;
+        cols.push(values);
+    }
+    Ok(cols)
+}
+
+// most significant bit first
+fn read_bitmap(input: &[u8], n: usize) -> Vec<bool> {
+    (0..n.min(input.len() * 8))
+        .map(|i| input[i / 8] & (0x80 >> (i % 8)) != 0)
+        .collect()
 }
 
 impl TryFrom<RawTableMap> for TableMap {
     type Error = crate::error::Error;
     fn try_from(raw: RawTableMap) -> crate::error::Result<Self> {
-        let schema_name = SmolStr::from(String::from_utf8(Vec::from(raw.schema_name.as_ref()))?);
-        let table_name = SmolStr::from(String::from_utf8(Vec::from(raw.table_name.as_ref()))?);
-        let null_bitmap = Vec::from(raw.null_bitmap.chunk());
-        let col_metas = ColumnMetas::read_from(
-            &mut raw.col_meta_defs.clone(),
-            raw.col_cnt as usize,
-            raw.col_defs.chunk(),
+        raw.into_table_map(TemporalFormat::Mixed)
+    }
+}
+
+impl RawTableMap {
+    fn into_table_map(self, format: TemporalFormat) -> crate::error::Result<TableMap> {
+        let schema_name = SmolStr::from(String::from_utf8(Vec::from(self.schema_name.as_ref()))?);
+        let table_name = SmolStr::from(String::from_utf8(Vec::from(self.table_name.as_ref()))?);
+        let null_bitmap = Vec::from(self.null_bitmap.chunk());
+        let col_metas = ColumnMetas::read_with_format(
+            &mut self.col_meta_defs.clone(),
+            self.col_cnt as usize,
+            self.col_defs.chunk(),
+            format,
         )?;
+        let metadata = TableMetadata::read_from(&mut self.optional_metadata.clone(), &col_metas)?;
         Ok(TableMap {
             schema_name,
             table_name,
             col_metas,
             null_bitmap,
+            metadata,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_map_optional_metadata() {
+        let mut payload = vec![2, b'd', b'b', 0, 1, b't', 0];
+        // LONG, VARCHAR(80), TINY
+        payload.extend_from_slice(&[3, 3, 15, 1, 2, 80, 0, 0b010]);
+        // signedness: TINY is unsigned
+        payload.extend_from_slice(&[1, 1, 0x40]);
+        // default charset utf8mb4, column charsets
+        payload.extend_from_slice(&[2, 1, 45, 3, 1, 33]);
+        // column names
+        payload.extend_from_slice(&[4, 10, 2, b'i', b'd', 4, b'n', b'a', b'm', b'e', 1, b'f']);
+        // enum values of one column
+        payload.extend_from_slice(&[6, 5, 2, 1, b'a', 1, b'b']);
+        // primary key with prefix
+        payload.extend_from_slice(&[9, 4, 0, 0, 1, 10]);
+        // unknown field
+        payload.extend_from_slice(&[99, 1, 0]);
+        let data = TableMapData {
+            table_id: 1,
+            flags: 1,
+            payload: Bytes::from(payload),
+        };
+        let tm = data.into_table_map().unwrap();
+        assert_eq!("db", tm.schema_name);
+        assert_eq!(vec![false, true], tm.metadata.signedness);
+        assert_eq!(Some(false), tm.is_unsigned(0));
+        assert_eq!(None, tm.is_unsigned(1));
+        assert_eq!(Some(true), tm.is_unsigned(2));
+        assert_eq!(
+            45,
+            tm.metadata
+                .default_charset
+                .as_ref()
+                .unwrap()
+                .default_charset
+        );
+        assert_eq!(vec![33], tm.metadata.column_charsets);
+        assert_eq!(Some("name"), tm.column_name(1));
+        assert_eq!(
+            vec![vec![Bytes::from("a"), Bytes::from("b")]],
+            tm.metadata.enum_str_values
+        );
+        assert_eq!(vec![(0, 0), (1, 10)], tm.metadata.primary_key);
+    }
+}
@@ -0,0 +1,82 @@
+//! decoding of text-bearing fields in events
+//!
+//! names of schema, table, file and user variable are always
+//! in utf8 (system charset). statement text is in the client charset,
+//! which is recorded in status vars of QueryEvent.
+use crate::error::Result;
+use bytes::{Buf, Bytes};
+use std::borrow::Cow;
+
+/// collation id of utf8mb4_general_ci
+pub const DEFAULT_COLLATION_ID: u16 = 45;
+
+/// collation id of binary
+pub const BINARY_COLLATION_ID: u16 = 63;
+
+/// accessors of raw text field
+pub trait EventText {
+    /// strict utf8, error on invalid sequence
+    fn as_str(&self) -> Result<&str>;
+
+    /// utf8, invalid sequence is replaced with U+FFFD
+    fn to_string_lossy(&self) -> Cow<'_, str>;
+
+    /// decode with charset of given collation id
+    ///
+    /// only latin1 is decoded differently, other charsets are
+    /// treated as utf8
+    fn to_string_with_collation(&self, collation_id: u16) -> Cow<'_, str>;
+}
+
+impl EventText for [u8] {
+    fn as_str(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(self)?)
+    }
+
+    fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self)
+    }
+
+    fn to_string_with_collation(&self, collation_id: u16) -> Cow<'_, str> {
+        // pure ascii is same in all supported charsets
+        if is_latin1(collation_id) && !self.is_ascii() {
+            // mysql latin1 is cp1252, approximated by iso-8859-1
+            return Cow::Owned(self.iter().map(|&b| b as char).collect());
+        }
+        self.to_string_lossy()
+    }
+}
+
+impl EventText for Bytes {
+    fn as_str(&self) -> Result<&str> {
+        self.chunk().as_str()
+    }
+
+    fn to_string_lossy(&self) -> Cow<'_, str> {
+        self.chunk().to_string_lossy()
+    }
+
+    fn to_string_with_collation(&self, collation_id: u16) -> Cow<'_, str> {
+        self.chunk().to_string_with_collation(collation_id)
+    }
+}
+
+fn is_latin1(collation_id: u16) -> bool {
+    matches!(collation_id, 5 | 8 | 15 | 31 | 47 | 48 | 49 | 94)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_text() {
+        let bs = Bytes::from_static("héllo".as_bytes());
+        assert_eq!("héllo", bs.as_str().unwrap());
+        assert_eq!("héllo", bs.to_string_with_collation(DEFAULT_COLLATION_ID));
+        let latin1 = Bytes::from_static(b"h\xe9llo");
+        assert!(latin1.as_str().is_err());
+        assert_eq!("h\u{fffd}llo", latin1.to_string_lossy());
+        assert_eq!("héllo", latin1.to_string_with_collation(8));
+    }
+}
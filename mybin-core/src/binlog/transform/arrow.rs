@@ -0,0 +1,559 @@
+//! columnar output of row events in Arrow format
+//!
+//! rows are accumulated per table until the end of a transaction,
+//! then converted to one RecordBatch for each table.
+//! each batch has a leading column "_op" with value I, U or D.
+//! update rows carry the after image, delete rows carry the before image.
+//! columns absent in minimal row image are filled with null.
+use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::FromRowsV2;
+use crate::bitmap;
+use crate::col::{BinlogColumnValue, ColumnDefinition, ColumnFlags, ColumnType};
+use crate::error::{Error, Result};
+use arrow_array::types::*;
+use arrow_array::{
+    ArrayRef, ArrowPrimitiveType, BinaryArray, Decimal128Array, NullArray, PrimitiveArray,
+    RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bigdecimal::BigDecimal;
+use bytes::Buf;
+use chrono::{Datelike, NaiveDate};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const OP_COLUMN: &str = "_op";
+const BINARY_CHARSET: u16 = 63;
+const MAX_DECIMAL128_PRECISION: u32 = 38;
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+/// map column definition to Arrow type
+///
+/// decimals exceeding precision of Decimal128 are mapped to Utf8
+pub fn arrow_type(def: &ColumnDefinition) -> DataType {
+    let unsigned = def.flags.contains(ColumnFlags::UNSIGNED);
+    match def.col_type {
+        ColumnType::Tiny if unsigned => DataType::UInt8,
+        ColumnType::Tiny => DataType::Int8,
+        ColumnType::Short if unsigned => DataType::UInt16,
+        ColumnType::Short => DataType::Int16,
+        ColumnType::Int24 | ColumnType::Long if unsigned => DataType::UInt32,
+        ColumnType::Int24 | ColumnType::Long => DataType::Int32,
+        ColumnType::LongLong if unsigned => DataType::UInt64,
+        ColumnType::LongLong => DataType::Int64,
+        ColumnType::Year => DataType::UInt16,
+        ColumnType::Bit => DataType::UInt64,
+        ColumnType::Float => DataType::Float32,
+        ColumnType::Double => DataType::Float64,
+        ColumnType::Decimal | ColumnType::NewDecimal => {
+            let precision = decimal_precision(def.col_len, def.decimals, unsigned);
+            if precision == 0
+                || precision > MAX_DECIMAL128_PRECISION
+                || precision < def.decimals as u32
+            {
+                DataType::Utf8
+            } else {
+                DataType::Decimal128(precision as u8, def.decimals as i8)
+            }
+        }
+        ColumnType::Date => DataType::Date32,
+        ColumnType::Time | ColumnType::Time2 => DataType::Duration(TimeUnit::Microsecond),
+        ColumnType::DateTime | ColumnType::DateTime2 => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        ColumnType::Timestamp | ColumnType::Timestamp2 => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into()))
+        }
+        ColumnType::Varchar
+        | ColumnType::VarString
+        | ColumnType::String
+        | ColumnType::TinyBlob
+        | ColumnType::MediumBlob
+        | ColumnType::LongBlob
+        | ColumnType::Blob => {
+            if def.charset == BINARY_CHARSET {
+                DataType::Binary
+            } else {
+                DataType::Utf8
+            }
+        }
+        ColumnType::Geometry => DataType::Binary,
+        ColumnType::Null => DataType::Null,
+    }
+}
+
+/// precision of decimal column, derived from its scale and
+/// display length which includes point and sign
+fn decimal_precision(col_len: u32, decimals: u8, unsigned: bool) -> u32 {
+    let point = if decimals > 0 { 1 } else { 0 };
+    let sign = if unsigned { 0 } else { 1 };
+    col_len.saturating_sub(point + sign)
+}
+
+/// schema of record batch of given table
+pub fn arrow_schema(col_defs: &[ColumnDefinition]) -> Schema {
+    let mut fields = Vec::with_capacity(col_defs.len() + 1);
+    fields.push(Field::new(OP_COLUMN, DataType::Utf8, false));
+    for def in col_defs {
+        // always nullable because of minimal row image
+        fields.push(Field::new(def.name.as_str(), arrow_type(def), true));
+    }
+    Schema::new(fields)
+}
+
+/// rows of one rows event, expanded to full width of table
+#[derive(Debug, Clone)]
+pub struct ArrowRows {
+    pub db: SmolStr,
+    pub tbl: SmolStr,
+    pub col_defs: Vec<ColumnDefinition>,
+    pub rows: Vec<(&'static str, Vec<BinlogColumnValue>)>,
+}
+
+impl ArrowRows {
+    fn new(
+        db: SmolStr,
+        tbl: SmolStr,
+        op: &'static str,
+        present_bitmap: &[u8],
+        rows: impl Iterator<Item = Vec<BinlogColumnValue>>,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        let rows = rows
+            .map(|row| {
+                let mut present = row.into_iter();
+                let full = bitmap::to_iter(present_bitmap, 0)
+                    .take(col_defs.len())
+                    .map(|p| {
+                        if p {
+                            present.next().unwrap_or(BinlogColumnValue::Null)
+                        } else {
+                            BinlogColumnValue::Null
+                        }
+                    })
+                    .collect();
+                (op, full)
+            })
+            .collect();
+        ArrowRows {
+            db,
+            tbl,
+            col_defs: col_defs.to_vec(),
+            rows,
+        }
+    }
+}
+
+impl FromRowsV2 for ArrowRows {
+    fn from_insert(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        ArrowRows::new(
+            db,
+            tbl,
+            "I",
+            rowsv2.present_bitmap.chunk(),
+            rowsv2.rows.into_iter().map(|r| r.0),
+            col_defs,
+        )
+    }
+
+    fn from_delete(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        ArrowRows::new(
+            db,
+            tbl,
+            "D",
+            rowsv2.present_bitmap.chunk(),
+            rowsv2.rows.into_iter().map(|r| r.0),
+            col_defs,
+        )
+    }
+
+    fn from_update(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        ArrowRows::new(
+            db,
+            tbl,
+            "U",
+            rowsv2.after_present_bitmap.chunk(),
+            rowsv2.rows.into_iter().map(|r| r.1),
+            col_defs,
+        )
+    }
+}
+
+/// record batch of single table
+#[derive(Debug, Clone)]
+pub struct TableBatch {
+    pub db: SmolStr,
+    pub tbl: SmolStr,
+    pub batch: RecordBatch,
+}
+
+/// accumulate rows of a transaction window
+///
+/// tables are kept in the order they are first met
+#[derive(Debug, Default)]
+pub struct RecordBatchBuilder {
+    tables: Vec<ArrowRows>,
+    index: HashMap<(SmolStr, SmolStr), usize>,
+}
+
+impl RecordBatchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn push(&mut self, rows: ArrowRows) {
+        let key = (rows.db.clone(), rows.tbl.clone());
+        match self.index.get(&key) {
+            Some(idx) => self.tables[*idx].rows.extend(rows.rows),
+            None => {
+                self.index.insert(key, self.tables.len());
+                self.tables.push(rows);
+            }
+        }
+    }
+
+    /// build one batch per table, the builder is reset for next window
+    pub fn finish(&mut self) -> Result<Vec<TableBatch>> {
+        self.index.clear();
+        std::mem::take(&mut self.tables)
+            .into_iter()
+            .map(|rows| {
+                let batch = build_batch(&rows)?;
+                Ok(TableBatch {
+                    db: rows.db,
+                    tbl: rows.tbl,
+                    batch,
+                })
+            })
+            .collect()
+    }
+}
+
+fn build_batch(rows: &ArrowRows) -> Result<RecordBatch> {
+    let schema = Arc::new(arrow_schema(&rows.col_defs));
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(rows.col_defs.len() + 1);
+    columns.push(Arc::new(StringArray::from_iter_values(
+        rows.rows.iter().map(|(op, _)| *op),
+    )));
+    for (i, def) in rows.col_defs.iter().enumerate() {
+        let values: Vec<&BinlogColumnValue> = rows.rows.iter().map(|(_, row)| &row[i]).collect();
+        columns.push(build_array(&arrow_type(def), &values)?);
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn build_array(data_type: &DataType, values: &[&BinlogColumnValue]) -> Result<ArrayRef> {
+    use BinlogColumnValue as V;
+    let arr: ArrayRef = match data_type {
+        DataType::Int8 => primitive::<Int8Type, _>(data_type, values, |v| match v {
+            V::Tiny(n) => Some(*n as i8),
+            _ => None,
+        })?,
+        DataType::UInt8 => primitive::<UInt8Type, _>(data_type, values, |v| match v {
+            V::Tiny(n) => Some(*n),
+            _ => None,
+        })?,
+        DataType::Int16 => primitive::<Int16Type, _>(data_type, values, |v| match v {
+            V::Short(n) => Some(*n as i16),
+            _ => None,
+        })?,
+        DataType::UInt16 => primitive::<UInt16Type, _>(data_type, values, |v| match v {
+            V::Short(n) | V::Year(n) => Some(*n),
+            _ => None,
+        })?,
+        DataType::Int32 => primitive::<Int32Type, _>(data_type, values, |v| match v {
+            V::Long(n) => Some(*n as i32),
+            // sign extension of 3-byte integer
+            V::Int24(n) => Some(((*n << 8) as i32) >> 8),
+            _ => None,
+        })?,
+        DataType::UInt32 => primitive::<UInt32Type, _>(data_type, values, |v| match v {
+            V::Long(n) | V::Int24(n) => Some(*n),
+            _ => None,
+        })?,
+        DataType::Int64 => primitive::<Int64Type, _>(data_type, values, |v| match v {
+            V::LongLong(n) => Some(*n as i64),
+            _ => None,
+        })?,
+        DataType::UInt64 => primitive::<UInt64Type, _>(data_type, values, |v| match v {
+            V::LongLong(n) => Some(*n),
+            V::Bit(bs) => Some(
+                bs.chunk()
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |n, (i, b)| n | ((*b as u64) << (i * 8))),
+            ),
+            _ => None,
+        })?,
+        DataType::Float32 => primitive::<Float32Type, _>(data_type, values, |v| match v {
+            V::Float(n) => Some(*n),
+            _ => None,
+        })?,
+        DataType::Float64 => primitive::<Float64Type, _>(data_type, values, |v| match v {
+            V::Double(n) => Some(*n),
+            _ => None,
+        })?,
+        DataType::Decimal128(precision, scale) => {
+            let mut arr = Vec::with_capacity(values.len());
+            for v in values {
+                match v {
+                    V::Null => arr.push(None),
+                    V::NewDecimal(d) => {
+                        arr.push(Some(decimal_to_i128(&d.to_string(), *scale as i64)?))
+                    }
+                    other => return Err(mismatch(data_type, other)),
+                }
+            }
+            Arc::new(Decimal128Array::from(arr).with_precision_and_scale(*precision, *scale)?)
+        }
+        DataType::Date32 => primitive::<Date32Type, _>(data_type, values, |v| match v {
+            V::Date { year, month, day } => {
+                NaiveDate::from_ymd_opt(*year as i32, *month as u32, *day as u32)
+                    .map(|dt| dt.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE)
+            }
+            _ => None,
+        })?,
+        DataType::Duration(_) => {
+            primitive::<DurationMicrosecondType, _>(data_type, values, |v| match v {
+                V::Time(tm) => {
+                    let secs = (tm.days as i64 * 24 + tm.hour as i64) * 3600
+                        + tm.minute as i64 * 60
+                        + tm.second as i64;
+                    let micros = secs * 1_000_000 + tm.micro_second as i64;
+                    Some(if tm.negative { -micros } else { micros })
+                }
+                _ => None,
+            })?
+        }
+        DataType::Timestamp(_, tz) => {
+            let arr = primitive_array::<TimestampMicrosecondType, _>(
+                data_type,
+                values,
+                |v| match v {
+                    V::Timestamp(secs) => Some(*secs as i64 * 1_000_000),
+                    // zero date is mapped to null
+                    V::DateTime(dt) => {
+                        NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)
+                            .and_then(|d| {
+                                d.and_hms_micro_opt(
+                                    dt.hour as u32,
+                                    dt.minute as u32,
+                                    dt.second as u32,
+                                    dt.micro_second,
+                                )
+                            })
+                            .map(|ts| ts.and_utc().timestamp() * 1_000_000 + dt.micro_second as i64)
+                    }
+                    _ => None,
+                },
+                |v| matches!(v, V::DateTime(_)),
+            )?;
+            let arr: TimestampMicrosecondArray = arr;
+            match tz {
+                Some(tz) => Arc::new(arr.with_timezone(tz.clone())),
+                None => Arc::new(arr),
+            }
+        }
+        DataType::Utf8 => {
+            let mut arr = Vec::with_capacity(values.len());
+            for v in values {
+                let s = match v {
+                    V::Null => None,
+                    V::VarString(bs) | V::String(bs) | V::Blob(bs) => {
+                        Some(String::from_utf8_lossy(bs.chunk()).into_owned())
+                    }
+                    V::NewDecimal(d) => Some(d.to_string()),
+                    V::Enum(e) => Some(e.to_u64().to_string()),
+                    V::Set(n) => Some(n.to_string()),
+                    other => return Err(mismatch(data_type, other)),
+                };
+                arr.push(s);
+            }
+            Arc::new(StringArray::from(arr))
+        }
+        DataType::Binary => {
+            let mut arr = Vec::with_capacity(values.len());
+            for v in values {
+                let bs = match v {
+                    V::Null => None,
+                    V::VarString(bs) | V::String(bs) | V::Blob(bs) | V::Geometry(bs) => {
+                        Some(bs.chunk())
+                    }
+                    other => return Err(mismatch(data_type, other)),
+                };
+                arr.push(bs);
+            }
+            Arc::new(BinaryArray::from(arr))
+        }
+        _ => Arc::new(NullArray::new(values.len())),
+    };
+    Ok(arr)
+}
+
+fn primitive<T, F>(data_type: &DataType, values: &[&BinlogColumnValue], f: F) -> Result<ArrayRef>
+where
+    T: ArrowPrimitiveType,
+    F: Fn(&BinlogColumnValue) -> Option<T::Native>,
+{
+    let arr = primitive_array::<T, F>(data_type, values, f, |_| false)?;
+    Ok(Arc::new(arr))
+}
+
+/// values which can not be converted are treated as type mismatch,
+/// unless they are accepted as null
+fn primitive_array<T, F>(
+    data_type: &DataType,
+    values: &[&BinlogColumnValue],
+    f: F,
+    null_on_fail: impl Fn(&BinlogColumnValue) -> bool,
+) -> Result<PrimitiveArray<T>>
+where
+    T: ArrowPrimitiveType,
+    F: Fn(&BinlogColumnValue) -> Option<T::Native>,
+{
+    let mut arr = Vec::with_capacity(values.len());
+    for v in values {
+        if let BinlogColumnValue::Null = v {
+            arr.push(None);
+            continue;
+        }
+        match f(v) {
+            Some(n) => arr.push(Some(n)),
+            None if null_on_fail(v) => arr.push(None),
+            None => return Err(mismatch(data_type, v)),
+        }
+    }
+    Ok(arr.into_iter().collect())
+}
+
+fn decimal_to_i128(s: &str, scale: i64) -> Result<i128> {
+    let (n, _) = BigDecimal::from_str(s)?
+        .with_scale(scale)
+        .into_bigint_and_exponent();
+    Ok(n.to_string().parse()?)
+}
+
+fn mismatch(data_type: &DataType, actual: &BinlogColumnValue) -> Error {
+    Error::ColumnTypeMismatch(format!("expected={}, actual={:?}", data_type, actual))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::row::LogRow;
+    use arrow_array::{Array, Int32Array};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_record_batch_builder() {
+        let col_defs = vec![
+            col_def("id", ColumnType::Long, 11, 0, ColumnFlags::PRIMARY_KEY),
+            col_def("price", ColumnType::NewDecimal, 7, 2, ColumnFlags::empty()),
+            col_def("name", ColumnType::VarString, 20, 0, ColumnFlags::empty()),
+        ];
+        assert_eq!(DataType::Decimal128(5, 2), arrow_type(&col_defs[1]));
+        let insert = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 3,
+            present_bitmap: Bytes::from(vec![0b111u8]),
+            rows: vec![LogRow(vec![
+                BinlogColumnValue::Long(-1i32 as u32),
+                BinlogColumnValue::Null,
+                BinlogColumnValue::VarString(Bytes::from("a")),
+            ])],
+        };
+        // minimal image of delete
+        let delete = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 3,
+            present_bitmap: Bytes::from(vec![0b001u8]),
+            rows: vec![LogRow(vec![BinlogColumnValue::Long(2)])],
+        };
+        let mut builder = RecordBatchBuilder::new();
+        builder.push(ArrowRows::from_insert(
+            "db1".into(),
+            "t1".into(),
+            insert,
+            &col_defs,
+        ));
+        builder.push(ArrowRows::from_delete(
+            "db1".into(),
+            "t1".into(),
+            delete,
+            &col_defs,
+        ));
+        let batches = builder.finish().unwrap();
+        assert!(builder.is_empty());
+        assert_eq!(1, batches.len());
+        let batch = &batches[0].batch;
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(4, batch.num_columns());
+        let ids = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(-1, ids.value(0));
+        assert_eq!(2, ids.value(1));
+        assert!(batch.column(3).is_null(1));
+    }
+
+    #[test]
+    fn test_arrow_type() {
+        // DECIMAL(5,2)
+        let def = col_def("d", ColumnType::NewDecimal, 7, 2, ColumnFlags::empty());
+        assert_eq!(DataType::Decimal128(5, 2), arrow_type(&def));
+        // invalid display length falls back to string
+        for len in 0..2 {
+            let def = col_def("d", ColumnType::NewDecimal, len, 2, ColumnFlags::empty());
+            assert_eq!(DataType::Utf8, arrow_type(&def));
+        }
+        // ENUM and SET are both carried as STRING type
+        let def = col_def("s", ColumnType::String, 3, 0, ColumnFlags::SET);
+        assert_eq!(DataType::Utf8, arrow_type(&def));
+        let arr = build_array(&DataType::Utf8, &[&BinlogColumnValue::Set(0b101)]).unwrap();
+        let arr = arr.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!("5", arr.value(0));
+    }
+
+    #[test]
+    fn test_decimal_to_i128() {
+        assert_eq!(-12345, decimal_to_i128("-123.45", 2).unwrap());
+        assert_eq!(1200, decimal_to_i128("12", 2).unwrap());
+    }
+
+    fn col_def(
+        name: &str,
+        col_type: ColumnType,
+        col_len: u32,
+        decimals: u8,
+        flags: ColumnFlags,
+    ) -> ColumnDefinition {
+        ColumnDefinition {
+            col_len,
+            decimals,
+            ..crate::col::tests::col_def(name, col_type, flags)
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! user defined conversion of column values
+//!
+//! converters are applied on raw binlog values, so the converted
+//! values are formatted by any transform in the same way, e.g.
+//! `registry.from_insert::<JsonRows>(...)` or `PreparedSql` alike.
+use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::mask::present_col_defs;
+use crate::binlog::transform::FromRowsV2;
+use crate::col::{BinlogColumnValue, ColumnDefinition, ColumnType};
+use bytes::{Buf, Bytes};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// convert value of a single column
+pub trait ValueConverter: Send + Sync {
+    fn convert(&self, col_def: &ColumnDefinition, value: BinlogColumnValue) -> BinlogColumnValue;
+}
+
+impl<F> ValueConverter for F
+where
+    F: Fn(&ColumnDefinition, BinlogColumnValue) -> BinlogColumnValue + Send + Sync,
+{
+    fn convert(&self, col_def: &ColumnDefinition, value: BinlogColumnValue) -> BinlogColumnValue {
+        self(col_def, value)
+    }
+}
+
+/// encode binary value to base64 string
+#[derive(Debug, Clone, Copy)]
+pub struct Base64Converter;
+
+impl ValueConverter for Base64Converter {
+    fn convert(&self, _col_def: &ColumnDefinition, value: BinlogColumnValue) -> BinlogColumnValue {
+        match value {
+            BinlogColumnValue::Blob(bs)
+            | BinlogColumnValue::VarString(bs)
+            | BinlogColumnValue::String(bs)
+            | BinlogColumnValue::Geometry(bs) => {
+                BinlogColumnValue::VarString(Bytes::from(base64::encode(bs.chunk())))
+            }
+            other => other,
+        }
+    }
+}
+
+/// registry of converters
+///
+/// converter registered on (db, tbl, col) takes precedence over
+/// the one registered on column type.
+#[derive(Clone, Default)]
+pub struct ConverterRegistry {
+    by_col: HashMap<(SmolStr, SmolStr, SmolStr), Arc<dyn ValueConverter>>,
+    by_type: HashMap<ColumnType, Arc<dyn ValueConverter>>,
+}
+
+impl fmt::Debug for ConverterRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConverterRegistry")
+            .field("by_col", &self.by_col.keys().collect::<Vec<_>>())
+            .field("by_type", &self.by_type.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ConverterRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn column<D, T, C, V>(mut self, db: D, tbl: T, col: C, converter: V) -> Self
+    where
+        D: Into<SmolStr>,
+        T: Into<SmolStr>,
+        C: Into<SmolStr>,
+        V: ValueConverter + 'static,
+    {
+        self.by_col
+            .insert((db.into(), tbl.into(), col.into()), Arc::new(converter));
+        self
+    }
+
+    pub fn col_type<V: ValueConverter + 'static>(
+        mut self,
+        col_type: ColumnType,
+        converter: V,
+    ) -> Self {
+        self.by_type.insert(col_type, Arc::new(converter));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_col.is_empty() && self.by_type.is_empty()
+    }
+
+    pub fn get(
+        &self,
+        db: &str,
+        tbl: &str,
+        col_def: &ColumnDefinition,
+    ) -> Option<&dyn ValueConverter> {
+        // avoid allocation of key if no column converter
+        if !self.by_col.is_empty() {
+            let key = (SmolStr::new(db), SmolStr::new(tbl), col_def.name.clone());
+            if let Some(conv) = self.by_col.get(&key) {
+                return Some(&**conv);
+            }
+        }
+        self.by_type.get(&col_def.col_type).map(|conv| &**conv)
+    }
+
+    /// convert all values of insert or delete rows
+    pub fn convert_rows(
+        &self,
+        db: &str,
+        tbl: &str,
+        rowsv2: &mut RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let defs = present_col_defs(rowsv2.present_bitmap.chunk(), col_defs);
+        for row in rowsv2.rows.iter_mut() {
+            self.convert_values(db, tbl, &defs, &mut row.0);
+        }
+    }
+
+    /// convert all values of update rows, both before and after images
+    pub fn convert_update_rows(
+        &self,
+        db: &str,
+        tbl: &str,
+        rowsv2: &mut UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let before_defs = present_col_defs(rowsv2.before_present_bitmap.chunk(), col_defs);
+        let after_defs = present_col_defs(rowsv2.after_present_bitmap.chunk(), col_defs);
+        for row in rowsv2.rows.iter_mut() {
+            self.convert_values(db, tbl, &before_defs, &mut row.0);
+            self.convert_values(db, tbl, &after_defs, &mut row.1);
+        }
+    }
+
+    /// convert insert rows before formatting them as json, sql or others
+    pub fn from_insert<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        self.convert_rows(&db, &tbl, &mut rowsv2, col_defs);
+        T::from_insert(db, tbl, rowsv2, col_defs)
+    }
+
+    pub fn from_delete<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        self.convert_rows(&db, &tbl, &mut rowsv2, col_defs);
+        T::from_delete(db, tbl, rowsv2, col_defs)
+    }
+
+    pub fn from_update<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        self.convert_update_rows(&db, &tbl, &mut rowsv2, col_defs);
+        T::from_update(db, tbl, rowsv2, col_defs)
+    }
+
+    fn convert_values(
+        &self,
+        db: &str,
+        tbl: &str,
+        defs: &[&ColumnDefinition],
+        values: &mut [BinlogColumnValue],
+    ) {
+        for (def, value) in defs.iter().zip(values.iter_mut()) {
+            if let Some(conv) = self.get(db, tbl, def) {
+                let v = std::mem::replace(value, BinlogColumnValue::Null);
+                *value = conv.convert(def, v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::ColumnFlags;
+    use crate::row::LogRow;
+
+    #[test]
+    fn test_converter_registry() {
+        let registry = ConverterRegistry::new()
+            .col_type(ColumnType::Blob, Base64Converter)
+            .column("db1", "t1", "n", |_: &ColumnDefinition, v| match v {
+                BinlogColumnValue::Long(n) => BinlogColumnValue::Long(n * 2),
+                other => other,
+            });
+        let col_defs = vec![
+            col_def("n", ColumnType::Long),
+            col_def("m", ColumnType::Long),
+            col_def("b", ColumnType::Blob),
+        ];
+        let mut rows = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 3,
+            present_bitmap: Bytes::from(vec![0b111u8]),
+            rows: vec![LogRow(vec![
+                BinlogColumnValue::Long(1),
+                BinlogColumnValue::Long(1),
+                BinlogColumnValue::Blob(Bytes::from("hello")),
+            ])],
+        };
+        registry.convert_rows("db1", "t1", &mut rows, &col_defs);
+        assert_eq!(
+            vec![
+                BinlogColumnValue::Long(2),
+                BinlogColumnValue::Long(1),
+                BinlogColumnValue::VarString(Bytes::from("aGVsbG8=")),
+            ],
+            rows.rows[0].0
+        );
+        // column converter only applies to given table
+        let mut rows2 = rows.clone();
+        registry.convert_rows("db1", "t2", &mut rows2, &col_defs);
+        assert_eq!(BinlogColumnValue::Long(2), rows2.rows[0].0[0]);
+    }
+
+    fn col_def(name: &str, col_type: ColumnType) -> ColumnDefinition {
+        crate::col::tests::col_def(name, col_type, ColumnFlags::empty())
+    }
+}
@@ -0,0 +1,343 @@
+//! csv and tsv lines for bulk loading
+//!
+//! binary values are hex encoded, so they can be loaded by
+//! `LOAD DATA ... SET col = UNHEX(@col)`.
+use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::{filter_col_defs, ColDef, FromRowsV2};
+use crate::col::{BinaryColumnValue, BinlogColumnValue, ColumnDefinition};
+use crate::stmt::StmtColumnValue;
+use bytes::Buf;
+use smol_str::SmolStr;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// operation of changed row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl CsvOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CsvOp::Insert => "I",
+            CsvOp::Update => "U",
+            CsvOp::Delete => "D",
+        }
+    }
+}
+
+/// options of csv output
+#[derive(Debug, Clone)]
+pub struct CsvOpts {
+    delimiter: char,
+    quote: char,
+    null: String,
+    header: bool,
+    op_column: bool,
+}
+
+impl Default for CsvOpts {
+    fn default() -> Self {
+        CsvOpts {
+            delimiter: ',',
+            quote: '"',
+            null: String::from("\\N"),
+            header: false,
+            op_column: false,
+        }
+    }
+}
+
+impl CsvOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// tab separated values
+    pub fn tsv() -> Self {
+        Self::default().delimiter('\t')
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(mut self, quote: char) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// representation of NULL, \N by default as LOAD DATA expects
+    pub fn null<S: Into<String>>(mut self, null: S) -> Self {
+        self.null = null.into();
+        self
+    }
+
+    /// emit header row of column names, once per table in CsvWriter
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// prepend column "op" with value I, U or D
+    pub fn op_column(mut self, op_column: bool) -> Self {
+        self.op_column = op_column;
+        self
+    }
+
+    fn push_field(&self, line: &mut String, field: &str) {
+        let need_quote = field == self.null
+            || field
+                .chars()
+                .any(|c| c == self.delimiter || c == self.quote || c == '\r' || c == '\n');
+        if !need_quote {
+            line.push_str(field);
+            return;
+        }
+        line.push(self.quote);
+        for c in field.chars() {
+            if c == self.quote {
+                line.push(self.quote);
+            }
+            line.push(c);
+        }
+        line.push(self.quote);
+    }
+}
+
+/// rows of one rows event
+///
+/// update rows carry only the after image, delete rows carry the
+/// before image.
+#[derive(Debug, Clone)]
+pub struct CsvRows {
+    pub db: SmolStr,
+    pub tbl: SmolStr,
+    pub col_names: Vec<SmolStr>,
+    pub rows: Vec<CsvRow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvRow {
+    pub op: CsvOp,
+    /// text of values, None if NULL
+    pub values: Vec<Option<String>>,
+}
+
+impl CsvRows {
+    fn new(
+        db: SmolStr,
+        tbl: SmolStr,
+        op: CsvOp,
+        col_defs: Vec<ColDef>,
+        rows: impl Iterator<Item = Vec<BinlogColumnValue>>,
+    ) -> Self {
+        let rows = rows
+            .map(|cols| CsvRow {
+                op,
+                values: col_defs
+                    .iter()
+                    .zip(cols)
+                    .map(|(def, col)| to_csv_text(StmtColumnValue::from((col, def.unsigned))))
+                    .collect(),
+            })
+            .collect();
+        CsvRows {
+            db,
+            tbl,
+            col_names: col_defs.into_iter().map(|def| def.name).collect(),
+            rows,
+        }
+    }
+
+    pub fn header_line(&self, opts: &CsvOpts) -> String {
+        let mut line = String::new();
+        if opts.op_column {
+            opts.push_field(&mut line, "op");
+        }
+        for (i, name) in self.col_names.iter().enumerate() {
+            if i > 0 || opts.op_column {
+                line.push(opts.delimiter);
+            }
+            opts.push_field(&mut line, name);
+        }
+        line
+    }
+
+    /// data lines without header and line terminator
+    pub fn lines(&self, opts: &CsvOpts) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let mut line = String::new();
+                if opts.op_column {
+                    line.push_str(row.op.as_str());
+                }
+                for (i, value) in row.values.iter().enumerate() {
+                    if i > 0 || opts.op_column {
+                        line.push(opts.delimiter);
+                    }
+                    match value {
+                        Some(v) => opts.push_field(&mut line, v),
+                        None => line.push_str(&opts.null),
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+impl FromRowsV2 for CsvRows {
+    fn from_insert(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        let col_defs = filter_col_defs(rowsv2.present_bitmap.chunk(), col_defs);
+        CsvRows::new(
+            db,
+            tbl,
+            CsvOp::Insert,
+            col_defs,
+            rowsv2.rows.into_iter().map(|r| r.0),
+        )
+    }
+
+    fn from_delete(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        let col_defs = filter_col_defs(rowsv2.present_bitmap.chunk(), col_defs);
+        CsvRows::new(
+            db,
+            tbl,
+            CsvOp::Delete,
+            col_defs,
+            rowsv2.rows.into_iter().map(|r| r.0),
+        )
+    }
+
+    fn from_update(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        let col_defs = filter_col_defs(rowsv2.after_present_bitmap.chunk(), col_defs);
+        CsvRows::new(
+            db,
+            tbl,
+            CsvOp::Update,
+            col_defs,
+            rowsv2.rows.into_iter().map(|r| r.1),
+        )
+    }
+}
+
+fn to_csv_text(sv: StmtColumnValue) -> Option<String> {
+    let s = match &sv.val {
+        BinaryColumnValue::Null => return None,
+        BinaryColumnValue::VarString(bs) | BinaryColumnValue::String(bs) => {
+            String::from_utf8_lossy(bs.chunk()).into_owned()
+        }
+        BinaryColumnValue::Blob(bs) | BinaryColumnValue::Geometry(bs) => hex::encode(bs.chunk()),
+        _ => sv.to_sql_literal().0.into_owned(),
+    };
+    Some(s)
+}
+
+/// write csv lines of multiple tables
+///
+/// header row is emitted when a table is met the first time.
+#[derive(Debug)]
+pub struct CsvWriter<W> {
+    out: W,
+    opts: CsvOpts,
+    seen: HashSet<(SmolStr, SmolStr)>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(out: W, opts: CsvOpts) -> Self {
+        CsvWriter {
+            out,
+            opts,
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn write_rows(&mut self, rows: &CsvRows) -> io::Result<()> {
+        if self.opts.header && self.seen.insert((rows.db.clone(), rows.tbl.clone())) {
+            writeln!(self.out, "{}", rows.header_line(&self.opts))?;
+        }
+        for line in rows.lines(&self.opts) {
+            writeln!(self.out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::ColumnType;
+
+    #[test]
+    fn test_csv_lines() {
+        let rows = CsvRows {
+            db: SmolStr::new("db1"),
+            tbl: SmolStr::new("t1"),
+            col_names: vec![SmolStr::new("id"), SmolStr::new("name")],
+            rows: vec![
+                CsvRow {
+                    op: CsvOp::Insert,
+                    values: vec![Some("1".to_owned()), Some("a,\"b\"".to_owned())],
+                },
+                CsvRow {
+                    op: CsvOp::Delete,
+                    values: vec![Some("2".to_owned()), None],
+                },
+            ],
+        };
+        let opts = CsvOpts::new().header(true).op_column(true);
+        let mut writer = CsvWriter::new(vec![], opts);
+        writer.write_rows(&rows).unwrap();
+        // header only once per table
+        writer.write_rows(&rows).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            "op,id,name\nI,1,\"a,\"\"b\"\"\"\nD,2,\\N\nI,1,\"a,\"\"b\"\"\"\nD,2,\\N\n",
+            out
+        );
+        let tsv = rows.lines(&CsvOpts::tsv().null("NULL"));
+        assert_eq!(vec!["1\t\"a,\"\"b\"\"\"", "2\tNULL"], tsv);
+    }
+
+    #[test]
+    fn test_csv_text() {
+        assert_eq!(
+            Some("0102".to_owned()),
+            to_csv_text(StmtColumnValue::new_blob(vec![1u8, 2]))
+        );
+        assert_eq!(
+            Some("-1".to_owned()),
+            to_csv_text(StmtColumnValue::new(
+                ColumnType::Long,
+                false,
+                BinaryColumnValue::Long(-1i32 as u32)
+            ))
+        );
+        assert_eq!(None, to_csv_text(StmtColumnValue::new_null()));
+    }
+}
@@ -0,0 +1,145 @@
+//! text of FLOAT and DOUBLE values in json and sql output
+//!
+//! FLOAT values are widened through their shortest decimal text, so
+//! 1.1 stored in a FLOAT column is output as 1.1 instead of
+//! 1.100000023841858.
+use crate::error::{Error, Result};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// shortest text reading back to the same value, e.g. 0.1, 1e20
+    Shortest,
+    /// given digits after decimal point
+    Fixed(usize),
+    /// shortest mantissa with exponent, e.g. 1.5e3
+    Scientific,
+}
+
+/// how to output NaN, Infinity and -Infinity,
+/// which MySQL never stores but may come from corrupted or foreign binlog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// output as null
+    Null,
+    /// output as string "NaN", "Infinity" or "-Infinity"
+    String,
+    /// fail the transformation
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FloatOpts {
+    format: FloatFormat,
+    non_finite: NonFinitePolicy,
+}
+
+impl Default for FloatOpts {
+    fn default() -> Self {
+        FloatOpts {
+            format: FloatFormat::Shortest,
+            non_finite: NonFinitePolicy::Null,
+        }
+    }
+}
+
+impl FloatOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn format(mut self, format: FloatFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn non_finite(mut self, non_finite: NonFinitePolicy) -> Self {
+        self.non_finite = non_finite;
+        self
+    }
+
+    pub(crate) fn render(&self, v: f64) -> Result<FloatText> {
+        if !v.is_finite() {
+            return match self.non_finite {
+                NonFinitePolicy::Null => Ok(FloatText::Null),
+                NonFinitePolicy::String => Ok(FloatText::String(non_finite_name(v))),
+                NonFinitePolicy::Error => Err(Error::NonFiniteFloat(non_finite_name(v).to_owned())),
+            };
+        }
+        let s = match self.format {
+            FloatFormat::Shortest => shortest(v),
+            FloatFormat::Fixed(precision) => format!("{:.*}", precision, v),
+            FloatFormat::Scientific => format!("{:e}", v),
+        };
+        Ok(FloatText::Number(Cow::Owned(s)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FloatText {
+    Number(Cow<'static, str>),
+    String(&'static str),
+    Null,
+}
+
+pub(crate) fn non_finite_name(v: f64) -> &'static str {
+    if v.is_nan() {
+        "NaN"
+    } else if v > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
+/// widen FLOAT to DOUBLE keeping its shortest decimal text
+pub(crate) fn widen_f32(v: f32) -> f64 {
+    if !v.is_finite() {
+        return v as f64;
+    }
+    ryu::Buffer::new().format_finite(v).parse().unwrap()
+}
+
+// ryu output without trailing ".0", as MySQL prints integral doubles
+fn shortest(v: f64) -> String {
+    let mut buf = ryu::Buffer::new();
+    let s = buf.format_finite(v);
+    s.strip_suffix(".0").unwrap_or(s).to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(s: &'static str) -> FloatText {
+        FloatText::Number(Cow::Borrowed(s))
+    }
+
+    #[test]
+    fn test_float_render() {
+        assert_eq!(1.1, widen_f32(1.1));
+        assert_ne!(1.1, 1.1f32 as f64);
+
+        let opts = FloatOpts::new();
+        assert_eq!(
+            number("0.30000000000000004"),
+            opts.render(0.1 + 0.2).unwrap()
+        );
+        assert_eq!(number("3"), opts.render(3.0).unwrap());
+        assert_eq!(number("1e20"), opts.render(1e20).unwrap());
+        assert_eq!(FloatText::Null, opts.render(f64::NAN).unwrap());
+
+        let opts = opts.format(FloatFormat::Fixed(2));
+        assert_eq!(number("0.30"), opts.render(0.1 + 0.2).unwrap());
+        let opts = opts.format(FloatFormat::Scientific);
+        assert_eq!(number("1.5e3"), opts.render(1500.0).unwrap());
+
+        let opts = opts.non_finite(NonFinitePolicy::String);
+        assert_eq!(
+            FloatText::String("-Infinity"),
+            opts.render(f64::NEG_INFINITY).unwrap()
+        );
+        let opts = opts.non_finite(NonFinitePolicy::Error);
+        assert!(opts.render(f64::INFINITY).is_err());
+    }
+}
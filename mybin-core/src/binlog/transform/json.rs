@@ -1,15 +1,313 @@
 use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::float::{self, FloatOpts, FloatText};
 use crate::binlog::transform::{filter_col_defs, FromRowsV2};
-use crate::col::{BinaryColumnValue, ColumnDefinition};
+use crate::col::{BinaryColumnValue, ColumnDefinition, ColumnFlags, ColumnType};
+use crate::error::Result;
 use crate::stmt::StmtColumnValue;
 use bytes::Buf;
 use serde_derive::*;
-use serde_json::{Map, Number, Value};
+use serde_json::{json, Map, Number, Value};
 use smol_str::SmolStr;
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize)]
 pub struct JsonRows(Vec<JsonRow>);
 
+impl JsonRows {
+    pub fn rows(&self) -> &[JsonRow] {
+        &self.0
+    }
+
+    pub fn into_rows(self) -> Vec<JsonRow> {
+        self.0
+    }
+
+    /// format rows with given options
+    ///
+    /// column definitions should be the full definitions of the table,
+    /// they are used to generate schema section and to find float columns.
+    pub fn to_values(
+        &self,
+        opts: &JsonOpts,
+        source: &JsonSource,
+        col_defs: &[ColumnDefinition],
+    ) -> Result<Vec<Value>> {
+        let schema = if opts.include_schema {
+            self.0
+                .first()
+                .map(|row| opts.schema(&row.db, &row.tbl, col_defs))
+        } else {
+            None
+        };
+        let float_cols: Vec<&str> = col_defs
+            .iter()
+            .filter(|def| matches!(def.col_type, ColumnType::Float | ColumnType::Double))
+            .map(|def| def.name.as_str())
+            .collect();
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                let row = if float_cols.is_empty() {
+                    Cow::Borrowed(row)
+                } else {
+                    Cow::Owned(opts.format_floats(row, &float_cols)?)
+                };
+                let payload = match opts.envelope {
+                    JsonEnvelope::Plain => serde_json::to_value(&row).unwrap(),
+                    JsonEnvelope::Debezium => opts.debezium_payload(&row, source, idx),
+                };
+                Ok(match &schema {
+                    Some(schema) => json!({ "schema": schema, "payload": payload }),
+                    None => payload,
+                })
+            })
+            .collect()
+    }
+}
+
+/// shape of json output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonEnvelope {
+    /// same as serialized JsonRow
+    Plain,
+    /// before/after/source/op/ts_ms, as Debezium MySQL connector emits
+    Debezium,
+}
+
+/// options of json output
+#[derive(Debug, Clone)]
+pub struct JsonOpts {
+    envelope: JsonEnvelope,
+    include_schema: bool,
+    server_name: SmolStr,
+    float: FloatOpts,
+}
+
+impl Default for JsonOpts {
+    fn default() -> Self {
+        JsonOpts {
+            envelope: JsonEnvelope::Plain,
+            include_schema: false,
+            server_name: SmolStr::new("mybin"),
+            float: FloatOpts::default(),
+        }
+    }
+}
+
+impl JsonOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn envelope(mut self, envelope: JsonEnvelope) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    /// wrap each row as {"schema": ..., "payload": ...}, with schema
+    /// in the format of Kafka Connect JsonConverter
+    pub fn include_schema(mut self, include_schema: bool) -> Self {
+        self.include_schema = include_schema;
+        self
+    }
+
+    /// logical server name, used as prefix of schema names
+    /// and name in source section
+    pub fn server_name<S: Into<SmolStr>>(mut self, server_name: S) -> Self {
+        self.server_name = server_name.into();
+        self
+    }
+
+    /// format and policy of FLOAT and DOUBLE values. json numbers carry
+    /// no notation, so only precision of the format takes effect
+    pub fn float(mut self, float: FloatOpts) -> Self {
+        self.float = float;
+        self
+    }
+
+    fn format_floats(&self, row: &JsonRow, float_cols: &[&str]) -> Result<JsonRow> {
+        let mut row = row.clone();
+        for image in row.before.iter_mut().chain(row.after.iter_mut()) {
+            if let Value::Object(map) = image {
+                for name in float_cols {
+                    if let Some(v) = map.get_mut(*name) {
+                        *v = self.float_value(v)?;
+                    }
+                }
+            }
+        }
+        Ok(row)
+    }
+
+    // non-finite values are kept as names by to_json_value
+    fn float_value(&self, v: &Value) -> Result<Value> {
+        let f = match v {
+            Value::Number(n) => n.as_f64().unwrap_or_default(),
+            Value::String(s) => match s.as_str() {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                _ => return Ok(v.clone()),
+            },
+            _ => return Ok(v.clone()),
+        };
+        let v = match self.float.render(f)? {
+            FloatText::Number(s) => s
+                .parse()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            FloatText::String(s) => Value::String(s.to_owned()),
+            FloatText::Null => Value::Null,
+        };
+        Ok(v)
+    }
+
+    fn debezium_payload(&self, row: &JsonRow, source: &JsonSource, idx: usize) -> Value {
+        let op = match row.ty {
+            "insert" => "c",
+            "update" => "u",
+            "delete" => "d",
+            other => other,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        json!({
+            "before": row.before,
+            "after": row.after,
+            "source": {
+                "version": env!("CARGO_PKG_VERSION"),
+                "connector": "mysql",
+                "name": self.server_name,
+                "ts_ms": source.ts_ms,
+                "snapshot": "false",
+                "db": row.db,
+                "table": row.tbl,
+                "server_id": source.server_id,
+                "gtid": source.gtid,
+                "file": source.file,
+                "pos": source.pos,
+                "row": idx,
+            },
+            "op": op,
+            "ts_ms": now,
+        })
+    }
+
+    fn schema(&self, db: &str, tbl: &str, col_defs: &[ColumnDefinition]) -> Value {
+        let value_name = format!("{}.{}.{}.Value", self.server_name, db, tbl);
+        let fields: Vec<Value> = col_defs
+            .iter()
+            .map(|def| {
+                json!({
+                    "type": connect_type(def),
+                    "optional": !def.flags.contains(ColumnFlags::NOT_NULL),
+                    "field": def.name,
+                })
+            })
+            .collect();
+        let image = |field: &str| {
+            json!({
+                "type": "struct",
+                "fields": fields,
+                "optional": true,
+                "name": value_name,
+                "field": field,
+            })
+        };
+        match self.envelope {
+            JsonEnvelope::Plain => json!({
+                "type": "struct",
+                "fields": [
+                    { "type": "string", "optional": false, "field": "type" },
+                    {
+                        "type": "array",
+                        "items": { "type": "string", "optional": false },
+                        "optional": false,
+                        "field": "base64_encoded",
+                    },
+                    { "type": "string", "optional": false, "field": "db" },
+                    { "type": "string", "optional": false, "field": "tbl" },
+                    image("before"),
+                    image("after"),
+                ],
+                "optional": false,
+                "name": format!("{}.{}.{}.Row", self.server_name, db, tbl),
+            }),
+            JsonEnvelope::Debezium => json!({
+                "type": "struct",
+                "fields": [
+                    image("before"),
+                    image("after"),
+                    {
+                        "type": "struct",
+                        "fields": [
+                            { "type": "string", "optional": false, "field": "version" },
+                            { "type": "string", "optional": false, "field": "connector" },
+                            { "type": "string", "optional": false, "field": "name" },
+                            { "type": "int64", "optional": false, "field": "ts_ms" },
+                            { "type": "string", "optional": true, "field": "snapshot" },
+                            { "type": "string", "optional": false, "field": "db" },
+                            { "type": "string", "optional": true, "field": "table" },
+                            { "type": "int64", "optional": false, "field": "server_id" },
+                            { "type": "string", "optional": true, "field": "gtid" },
+                            { "type": "string", "optional": false, "field": "file" },
+                            { "type": "int64", "optional": false, "field": "pos" },
+                            { "type": "int32", "optional": false, "field": "row" },
+                        ],
+                        "optional": false,
+                        "name": "io.debezium.connector.mysql.Source",
+                        "field": "source",
+                    },
+                    { "type": "string", "optional": false, "field": "op" },
+                    { "type": "int64", "optional": true, "field": "ts_ms" },
+                ],
+                "optional": false,
+                "name": format!("{}.{}.{}.Envelope", self.server_name, db, tbl),
+            }),
+        }
+    }
+}
+
+/// origin of rows, filled in source section of Debezium envelope
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonSource {
+    pub server_id: u32,
+    /// timestamp of binlog event in milliseconds
+    pub ts_ms: u64,
+    pub file: String,
+    pub pos: u64,
+    pub gtid: Option<String>,
+}
+
+/// Kafka Connect type of column, consistent with values
+/// generated by to_json_value
+fn connect_type(def: &ColumnDefinition) -> &'static str {
+    let unsigned = def.flags.contains(ColumnFlags::UNSIGNED);
+    match def.col_type {
+        ColumnType::Tiny => "int16",
+        ColumnType::Short if unsigned => "int32",
+        ColumnType::Short => "int16",
+        ColumnType::Int24 | ColumnType::Year => "int32",
+        ColumnType::Long if unsigned => "int64",
+        ColumnType::Long => "int32",
+        ColumnType::LongLong | ColumnType::Bit => "int64",
+        ColumnType::Float => "float",
+        ColumnType::Double => "double",
+        ColumnType::TinyBlob
+        | ColumnType::MediumBlob
+        | ColumnType::LongBlob
+        | ColumnType::Blob
+        | ColumnType::Geometry => "bytes",
+        _ => "string",
+    }
+}
+
 impl FromRowsV2 for JsonRows {
     fn from_insert(
         db: SmolStr,
@@ -118,6 +416,13 @@ impl FromRowsV2 for JsonRows {
     }
 }
 
+fn float_json_value(v: f64) -> Value {
+    match Number::from_f64(v) {
+        Some(n) => Value::Number(n),
+        None => Value::String(float::non_finite_name(v).to_owned()),
+    }
+}
+
 fn to_json_value(sv: StmtColumnValue) -> (Value, bool) {
     let v = match sv.val {
         BinaryColumnValue::Tiny(v) => {
@@ -141,8 +446,8 @@ fn to_json_value(sv: StmtColumnValue) -> (Value, bool) {
                 Value::Number((v as i32).into())
             }
         }
-        BinaryColumnValue::Float(v) => Value::Number(Number::from_f64(v as f64).unwrap()),
-        BinaryColumnValue::Double(v) => Value::Number(Number::from_f64(v).unwrap()),
+        BinaryColumnValue::Float(v) => float_json_value(float::widen_f32(v)),
+        BinaryColumnValue::Double(v) => float_json_value(v),
         BinaryColumnValue::Null => Value::Null,
         BinaryColumnValue::Timestamp(ts) | BinaryColumnValue::DateTime(ts) => {
             if ts.micro_second == 0 {
@@ -221,7 +526,7 @@ fn to_json_value(sv: StmtColumnValue) -> (Value, bool) {
     (v, false)
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct JsonRow {
     #[serde(rename = "type")]
     pub ty: &'static str,
@@ -237,11 +542,150 @@ pub struct JsonRow {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::binlog::transform::float::{FloatFormat, NonFinitePolicy};
+
     #[test]
     fn test_stmt_value_to_json() {
         let sv1 = StmtColumnValue::new_decimal("1.23".parse().unwrap());
         let (jv, enc) = to_json_value(sv1);
         assert_eq!(Value::String("1.23".to_owned()), jv);
         assert!(!enc);
+        let (jv, _) = to_json_value(StmtColumnValue::new_float(1.1));
+        assert_eq!(json!(1.1), jv);
+        let (jv, _) = to_json_value(StmtColumnValue::new_double(f64::NAN));
+        assert_eq!(json!("NaN"), jv);
+    }
+
+    #[test]
+    fn test_json_rows_converted() {
+        use crate::binlog::transform::convert::{Base64Converter, ConverterRegistry};
+        use crate::col::tests::col_def;
+        use crate::col::BinlogColumnValue;
+        use crate::row::LogRow;
+        use bytes::Bytes;
+
+        let col_defs = vec![
+            col_def("id", ColumnType::Long, ColumnFlags::empty()),
+            col_def("data", ColumnType::Blob, ColumnFlags::empty()),
+        ];
+        let rowsv2 = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 2,
+            present_bitmap: Bytes::from(vec![0b11u8]),
+            rows: vec![LogRow(vec![
+                BinlogColumnValue::Long(1),
+                BinlogColumnValue::Blob(Bytes::from("hello")),
+            ])],
+        };
+        let rows = JsonRows::from_insert("db1".into(), "t1".into(), rowsv2.clone(), &col_defs);
+        assert_eq!(vec!["data"], rows.rows()[0].base64_encoded);
+        // id is negated, blob is converted to base64 string by converter
+        let registry = ConverterRegistry::new()
+            .col_type(ColumnType::Blob, Base64Converter)
+            .column("db1", "t1", "id", |_: &ColumnDefinition, v| match v {
+                BinlogColumnValue::Long(n) => BinlogColumnValue::Long(n.wrapping_neg()),
+                other => other,
+            });
+        let rows: JsonRows = registry.from_insert("db1".into(), "t1".into(), rowsv2, &col_defs);
+        assert!(rows.rows()[0].base64_encoded.is_empty());
+        assert_eq!(
+            Some(json!({"id": -1, "data": "aGVsbG8="})),
+            rows.rows()[0].after
+        );
+    }
+
+    #[test]
+    fn test_float_opts() {
+        let row = JsonRow {
+            ty: "insert",
+            base64_encoded: vec![],
+            db: SmolStr::new("db1"),
+            tbl: SmolStr::new("t1"),
+            before: None,
+            after: Some(json!({ "f": 0.30000000000000004, "d": "NaN", "s": "NaN" })),
+        };
+        let rows = JsonRows(vec![row]);
+        let source = JsonSource {
+            server_id: 1,
+            ts_ms: 0,
+            file: "mysql-bin.000001".to_owned(),
+            pos: 4,
+            gtid: None,
+        };
+        let col_def = |name: &str, col_type| ColumnDefinition {
+            charset: 63,
+            col_len: 22,
+            decimals: 31,
+            ..crate::col::tests::col_def(name, col_type, ColumnFlags::empty())
+        };
+        let col_defs = vec![
+            col_def("f", ColumnType::Float),
+            col_def("d", ColumnType::Double),
+            col_def("s", ColumnType::VarString),
+        ];
+        let v = &rows
+            .to_values(&JsonOpts::new(), &source, &col_defs)
+            .unwrap()[0];
+        assert_eq!(
+            json!({ "f": 0.30000000000000004, "d": null, "s": "NaN" }),
+            v["after"]
+        );
+
+        let float = FloatOpts::new()
+            .format(FloatFormat::Fixed(2))
+            .non_finite(NonFinitePolicy::String);
+        let opts = JsonOpts::new().float(float);
+        let v = &rows.to_values(&opts, &source, &col_defs).unwrap()[0];
+        assert_eq!(json!({ "f": 0.3, "d": "NaN", "s": "NaN" }), v["after"]);
+
+        let opts = JsonOpts::new().float(float.non_finite(NonFinitePolicy::Error));
+        assert!(rows.to_values(&opts, &source, &col_defs).is_err());
+    }
+
+    #[test]
+    fn test_debezium_envelope() {
+        let row = JsonRow {
+            ty: "update",
+            base64_encoded: vec![],
+            db: SmolStr::new("db1"),
+            tbl: SmolStr::new("t1"),
+            before: Some(json!({ "id": 1 })),
+            after: Some(json!({ "id": 2 })),
+        };
+        let rows = JsonRows(vec![row]);
+        let source = JsonSource {
+            server_id: 1,
+            ts_ms: 1_600_000_000_000,
+            file: "mysql-bin.000001".to_owned(),
+            pos: 4,
+            gtid: None,
+        };
+        let col_defs = vec![ColumnDefinition {
+            charset: 63,
+            ..crate::col::tests::col_def(
+                "id",
+                ColumnType::Long,
+                ColumnFlags::NOT_NULL | ColumnFlags::PRIMARY_KEY,
+            )
+        }];
+        let opts = JsonOpts::new()
+            .envelope(JsonEnvelope::Debezium)
+            .server_name("srv");
+        let v = &rows.to_values(&opts, &source, &col_defs).unwrap()[0];
+        assert_eq!("u", v["op"]);
+        assert_eq!(json!({ "id": 1 }), v["before"]);
+        assert_eq!("t1", v["source"]["table"]);
+        assert_eq!(4, v["source"]["pos"]);
+        assert!(v.get("schema").is_none());
+        // wrapped with schema
+        let v = &rows
+            .to_values(&opts.include_schema(true), &source, &col_defs)
+            .unwrap()[0];
+        assert_eq!("srv.db1.t1.Envelope", v["schema"]["name"]);
+        assert_eq!(
+            json!({ "type": "int32", "optional": false, "field": "id" }),
+            v["schema"]["fields"][0]["fields"][0]
+        );
+        assert_eq!("u", v["payload"]["op"]);
     }
 }
@@ -0,0 +1,243 @@
+//! masking of sensitive column values
+//!
+//! masks are applied on raw binlog values before any transformation,
+//! so redacted data never reaches the output.
+use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::route::NameMatcher;
+use crate::bitmap;
+use crate::col::{BinlogColumnValue, ColumnDefinition};
+use bytes::{Buf, Bytes};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+
+/// mask value of a single column
+pub trait ValueMasker: Debug + Send + Sync {
+    /// returns the masked value, or the original value if column
+    /// is not sensitive
+    fn mask(
+        &self,
+        db: &str,
+        tbl: &str,
+        col_def: &ColumnDefinition,
+        value: BinlogColumnValue,
+    ) -> BinlogColumnValue;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskAction {
+    /// replace with NULL
+    Null,
+    /// replace string and binary value with hex of its SHA-256 digest,
+    /// other types are replaced with NULL
+    Hash,
+    /// keep at most given number of bytes of string and binary value,
+    /// UTF-8 text is cut at character boundary, other types are kept
+    /// as is
+    Truncate(usize),
+}
+
+impl MaskAction {
+    pub fn apply(self, value: BinlogColumnValue) -> BinlogColumnValue {
+        if value == BinlogColumnValue::Null {
+            return value;
+        }
+        match self {
+            MaskAction::Null => BinlogColumnValue::Null,
+            MaskAction::Hash => map_bytes(value, |bs| {
+                Bytes::from(hex::encode(Sha256::digest(bs.chunk())))
+            })
+            .unwrap_or(BinlogColumnValue::Null),
+            MaskAction::Truncate(len) => match map_bytes(value.clone(), |bs| truncate(bs, len)) {
+                Some(v) => v,
+                None => value,
+            },
+        }
+    }
+}
+
+/// apply function on bytes of string and binary values
+fn map_bytes<F>(value: BinlogColumnValue, f: F) -> Option<BinlogColumnValue>
+where
+    F: FnOnce(Bytes) -> Bytes,
+{
+    let v = match value {
+        BinlogColumnValue::Blob(bs) => BinlogColumnValue::Blob(f(bs)),
+        BinlogColumnValue::VarString(bs) => BinlogColumnValue::VarString(f(bs)),
+        BinlogColumnValue::String(bs) => BinlogColumnValue::String(f(bs)),
+        BinlogColumnValue::Geometry(bs) => BinlogColumnValue::Geometry(f(bs)),
+        _ => return None,
+    };
+    Some(v)
+}
+
+// binary value is cut at any byte, so only valid text is
+// kept whole
+fn truncate(mut bs: Bytes, len: usize) -> Bytes {
+    if len >= bs.len() {
+        return bs;
+    }
+    let end = match std::str::from_utf8(bs.chunk()) {
+        Ok(s) => (0..=len)
+            .rev()
+            .find(|i| s.is_char_boundary(*i))
+            .unwrap_or(0),
+        Err(_) => len,
+    };
+    bs.truncate(end);
+    bs
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskRule {
+    db: NameMatcher,
+    tbl: NameMatcher,
+    col: NameMatcher,
+    action: MaskAction,
+}
+
+impl MaskRule {
+    pub fn new(db: NameMatcher, tbl: NameMatcher, col: NameMatcher, action: MaskAction) -> Self {
+        MaskRule {
+            db,
+            tbl,
+            col,
+            action,
+        }
+    }
+
+    pub fn is_match(&self, db: &str, tbl: &str, col: &str) -> bool {
+        self.db.is_match(db) && self.tbl.is_match(tbl) && self.col.is_match(col)
+    }
+}
+
+/// rule based masker, the first matched rule takes effect
+#[derive(Debug, Clone, Default)]
+pub struct MaskRules {
+    rules: Vec<MaskRule>,
+}
+
+impl MaskRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rule(mut self, rule: MaskRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+}
+
+impl ValueMasker for MaskRules {
+    fn mask(
+        &self,
+        db: &str,
+        tbl: &str,
+        col_def: &ColumnDefinition,
+        value: BinlogColumnValue,
+    ) -> BinlogColumnValue {
+        match self
+            .rules
+            .iter()
+            .find(|r| r.is_match(db, tbl, &col_def.name))
+        {
+            Some(rule) => rule.action.apply(value),
+            None => value,
+        }
+    }
+}
+
+/// mask all values of insert or delete rows
+pub fn mask_rows<M: ValueMasker + ?Sized>(
+    masker: &M,
+    db: &str,
+    tbl: &str,
+    rowsv2: &mut RowsV2,
+    col_defs: &[ColumnDefinition],
+) {
+    let defs = present_col_defs(rowsv2.present_bitmap.chunk(), col_defs);
+    for row in rowsv2.rows.iter_mut() {
+        mask_values(masker, db, tbl, &defs, &mut row.0);
+    }
+}
+
+/// mask all values of update rows, both before and after images
+pub fn mask_update_rows<M: ValueMasker + ?Sized>(
+    masker: &M,
+    db: &str,
+    tbl: &str,
+    rowsv2: &mut UpdateRowsV2,
+    col_defs: &[ColumnDefinition],
+) {
+    let before_defs = present_col_defs(rowsv2.before_present_bitmap.chunk(), col_defs);
+    let after_defs = present_col_defs(rowsv2.after_present_bitmap.chunk(), col_defs);
+    for row in rowsv2.rows.iter_mut() {
+        mask_values(masker, db, tbl, &before_defs, &mut row.0);
+        mask_values(masker, db, tbl, &after_defs, &mut row.1);
+    }
+}
+
+fn mask_values<M: ValueMasker + ?Sized>(
+    masker: &M,
+    db: &str,
+    tbl: &str,
+    defs: &[&ColumnDefinition],
+    values: &mut [BinlogColumnValue],
+) {
+    for (def, value) in defs.iter().zip(values.iter_mut()) {
+        let v = std::mem::replace(value, BinlogColumnValue::Null);
+        *value = masker.mask(db, tbl, def, v);
+    }
+}
+
+pub(super) fn present_col_defs<'a>(
+    present_bitmap: &[u8],
+    col_defs: &'a [ColumnDefinition],
+) -> Vec<&'a ColumnDefinition> {
+    bitmap::to_iter(present_bitmap, 0)
+        .zip(col_defs.iter())
+        .filter(|(present, _)| *present)
+        .map(|(_, def)| def)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_action() {
+        let v = BinlogColumnValue::VarString(Bytes::from("hello"));
+        assert_eq!(BinlogColumnValue::Null, MaskAction::Null.apply(v.clone()));
+        assert_eq!(
+            BinlogColumnValue::VarString(Bytes::from(
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            )),
+            MaskAction::Hash.apply(v.clone())
+        );
+        assert_eq!(
+            BinlogColumnValue::VarString(Bytes::from("he")),
+            MaskAction::Truncate(2).apply(v)
+        );
+        let n = BinlogColumnValue::Long(42);
+        assert_eq!(BinlogColumnValue::Null, MaskAction::Hash.apply(n.clone()));
+        assert_eq!(n, MaskAction::Truncate(1).apply(n.clone()));
+        let b = BinlogColumnValue::Bit(Bytes::from_static(&[0x01, 0x02]));
+        assert_eq!(BinlogColumnValue::Null, MaskAction::Hash.apply(b.clone()));
+        assert_eq!(b, MaskAction::Truncate(1).apply(b.clone()));
+        // multi-byte character is not split
+        let t = BinlogColumnValue::VarString(Bytes::from("a\u{4e2d}\u{6587}"));
+        assert_eq!(
+            BinlogColumnValue::VarString(Bytes::from("a\u{4e2d}")),
+            MaskAction::Truncate(5).apply(t.clone())
+        );
+        assert_eq!(
+            BinlogColumnValue::VarString(Bytes::from("a")),
+            MaskAction::Truncate(2).apply(t)
+        );
+        let bin = BinlogColumnValue::Blob(Bytes::from_static(&[0xff, 0xe4, 0xb8, 0xad]));
+        assert_eq!(
+            BinlogColumnValue::Blob(Bytes::from_static(&[0xff, 0xe4])),
+            MaskAction::Truncate(2).apply(bin)
+        );
+    }
+}
@@ -1,5 +1,13 @@
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod convert;
+pub mod csv;
+pub mod float;
 pub mod json;
+pub mod mask;
+pub mod route;
 pub mod sql;
+pub mod undo;
 
 use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
 use crate::bitmap;
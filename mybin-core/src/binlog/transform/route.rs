@@ -0,0 +1,336 @@
+//! routing rules applied before rows are transformed
+//!
+//! a rule matches database and table names either exactly or by regex,
+//! and could rename them and exclude some columns,
+//! e.g. route shard_001.orders to analytics.orders.
+use crate::binlog::rows_v2::{RowsV2, UpdateRow, UpdateRowsV2};
+use crate::binlog::transform::convert::ConverterRegistry;
+use crate::binlog::transform::mask::{mask_rows, mask_update_rows, ValueMasker};
+use crate::binlog::transform::FromRowsV2;
+use crate::bitmap;
+use crate::col::ColumnDefinition;
+use crate::error::Result;
+use crate::row::LogRow;
+use bytes::{Buf, Bytes};
+use regex::Regex;
+use smol_str::SmolStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    Exact(SmolStr),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    /// the regex must match the whole name
+    pub fn regex<T: AsRef<str>>(pattern: T) -> Result<Self> {
+        let re = Regex::new(&format!("^(?:{})$", pattern.as_ref()))?;
+        Ok(NameMatcher::Regex(re))
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Exact(s) => s == name,
+            NameMatcher::Regex(re) => re.is_match(name),
+        }
+    }
+
+    /// rewrite matched name by target
+    ///
+    /// capture groups like $1 can be referred in target if matched by regex
+    fn rewrite(&self, name: &str, target: &str) -> SmolStr {
+        match self {
+            NameMatcher::Exact(_) => SmolStr::new(target),
+            NameMatcher::Regex(re) => SmolStr::new(re.replace(name, target)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutingRule {
+    db: NameMatcher,
+    tbl: NameMatcher,
+    target_db: Option<String>,
+    target_tbl: Option<String>,
+    exclude_cols: Vec<SmolStr>,
+}
+
+impl RoutingRule {
+    pub fn new(db: NameMatcher, tbl: NameMatcher) -> Self {
+        RoutingRule {
+            db,
+            tbl,
+            target_db: None,
+            target_tbl: None,
+            exclude_cols: vec![],
+        }
+    }
+
+    pub fn exact<D: Into<SmolStr>, T: Into<SmolStr>>(db: D, tbl: T) -> Self {
+        Self::new(
+            NameMatcher::Exact(db.into()),
+            NameMatcher::Exact(tbl.into()),
+        )
+    }
+
+    pub fn regex<D: AsRef<str>, T: AsRef<str>>(db: D, tbl: T) -> Result<Self> {
+        Ok(Self::new(NameMatcher::regex(db)?, NameMatcher::regex(tbl)?))
+    }
+
+    pub fn target_db<T: Into<String>>(mut self, target_db: T) -> Self {
+        self.target_db = Some(target_db.into());
+        self
+    }
+
+    pub fn target_tbl<T: Into<String>>(mut self, target_tbl: T) -> Self {
+        self.target_tbl = Some(target_tbl.into());
+        self
+    }
+
+    pub fn exclude_col<T: Into<SmolStr>>(mut self, col: T) -> Self {
+        self.exclude_cols.push(col.into());
+        self
+    }
+
+    pub fn is_match(&self, db: &str, tbl: &str) -> bool {
+        self.db.is_match(db) && self.tbl.is_match(tbl)
+    }
+}
+
+/// route result of a table
+#[derive(Debug, Clone)]
+pub struct Route<'a> {
+    pub db: SmolStr,
+    pub tbl: SmolStr,
+    pub exclude_cols: &'a [SmolStr],
+}
+
+/// ordered routing rules, the first matched rule takes effect
+///
+/// tables without any matched rule are kept as is.
+/// if masker is set, values are masked against the original
+/// table names before routing, and then converted by converters.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingRules {
+    rules: Vec<RoutingRule>,
+    masker: Option<Arc<dyn ValueMasker>>,
+    converters: Option<Arc<ConverterRegistry>>,
+}
+
+impl RoutingRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rule(mut self, rule: RoutingRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn masker<M: ValueMasker + 'static>(mut self, masker: M) -> Self {
+        self.masker = Some(Arc::new(masker));
+        self
+    }
+
+    pub fn converters(mut self, converters: ConverterRegistry) -> Self {
+        self.converters = Some(Arc::new(converters));
+        self
+    }
+
+    pub fn route(&self, db: &str, tbl: &str) -> Route<'_> {
+        match self.rules.iter().find(|r| r.is_match(db, tbl)) {
+            Some(rule) => Route {
+                db: match rule.target_db.as_ref() {
+                    Some(target) => rule.db.rewrite(db, target),
+                    None => SmolStr::new(db),
+                },
+                tbl: match rule.target_tbl.as_ref() {
+                    Some(target) => rule.tbl.rewrite(tbl, target),
+                    None => SmolStr::new(tbl),
+                },
+                exclude_cols: &rule.exclude_cols,
+            },
+            None => Route {
+                db: SmolStr::new(db),
+                tbl: SmolStr::new(tbl),
+                exclude_cols: &[],
+            },
+        }
+    }
+
+    pub fn from_insert<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        if let Some(masker) = self.masker.as_ref() {
+            mask_rows(&**masker, &db, &tbl, &mut rowsv2, col_defs);
+        }
+        if let Some(converters) = self.converters.as_ref() {
+            converters.convert_rows(&db, &tbl, &mut rowsv2, col_defs);
+        }
+        let route = self.route(&db, &tbl);
+        exclude_cols(
+            &mut rowsv2.present_bitmap,
+            &mut rowsv2.rows,
+            col_defs,
+            route.exclude_cols,
+        );
+        T::from_insert(route.db, route.tbl, rowsv2, col_defs)
+    }
+
+    pub fn from_delete<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        if let Some(masker) = self.masker.as_ref() {
+            mask_rows(&**masker, &db, &tbl, &mut rowsv2, col_defs);
+        }
+        if let Some(converters) = self.converters.as_ref() {
+            converters.convert_rows(&db, &tbl, &mut rowsv2, col_defs);
+        }
+        let route = self.route(&db, &tbl);
+        exclude_cols(
+            &mut rowsv2.present_bitmap,
+            &mut rowsv2.rows,
+            col_defs,
+            route.exclude_cols,
+        );
+        T::from_delete(route.db, route.tbl, rowsv2, col_defs)
+    }
+
+    pub fn from_update<T: FromRowsV2>(
+        &self,
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> T {
+        if let Some(masker) = self.masker.as_ref() {
+            mask_update_rows(&**masker, &db, &tbl, &mut rowsv2, col_defs);
+        }
+        if let Some(converters) = self.converters.as_ref() {
+            converters.convert_update_rows(&db, &tbl, &mut rowsv2, col_defs);
+        }
+        let route = self.route(&db, &tbl);
+        if !route.exclude_cols.is_empty() {
+            let (mut before, mut after): (Vec<_>, Vec<_>) = rowsv2
+                .rows
+                .into_iter()
+                .map(|r| (LogRow(r.0), LogRow(r.1)))
+                .unzip();
+            exclude_cols(
+                &mut rowsv2.before_present_bitmap,
+                &mut before,
+                col_defs,
+                route.exclude_cols,
+            );
+            exclude_cols(
+                &mut rowsv2.after_present_bitmap,
+                &mut after,
+                col_defs,
+                route.exclude_cols,
+            );
+            rowsv2.rows = before
+                .into_iter()
+                .zip(after)
+                .map(|(b, a)| UpdateRow(b.0, a.0))
+                .collect();
+        }
+        T::from_update(route.db, route.tbl, rowsv2, col_defs)
+    }
+}
+
+/// remove excluded columns from rows and unmark them in present bitmap
+fn exclude_cols(
+    present_bitmap: &mut Bytes,
+    rows: &mut [LogRow],
+    col_defs: &[ColumnDefinition],
+    exclude_cols: &[SmolStr],
+) {
+    if exclude_cols.is_empty() {
+        return;
+    }
+    let mut bm = Vec::from(present_bitmap.chunk());
+    // positions in row of excluded columns
+    let mut excluded = vec![];
+    let mut pos = 0;
+    for (idx, (present, def)) in bitmap::to_iter(present_bitmap.chunk(), 0)
+        .zip(col_defs.iter())
+        .enumerate()
+    {
+        if !present {
+            continue;
+        }
+        if exclude_cols.contains(&def.name) {
+            bitmap::mark(&mut bm, idx, false);
+            excluded.push(pos);
+        }
+        pos += 1;
+    }
+    if excluded.is_empty() {
+        return;
+    }
+    for row in rows.iter_mut() {
+        let mut pos = 0;
+        row.0.retain(|_| {
+            let keep = !excluded.contains(&pos);
+            pos += 1;
+            keep
+        });
+    }
+    *present_bitmap = Bytes::from(bm);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::{BinlogColumnValue, ColumnFlags, ColumnType};
+
+    fn col_def(name: &str) -> ColumnDefinition {
+        crate::col::tests::col_def(name, ColumnType::Long, ColumnFlags::empty())
+    }
+
+    #[test]
+    fn test_route_exact_and_regex() -> Result<()> {
+        let rules = RoutingRules::new()
+            .rule(RoutingRule::exact("db1", "tbl1").target_tbl("tbl2"))
+            .rule(RoutingRule::regex(r"shard_(\d+)", "orders")?.target_db("analytics_$1"));
+        let route = rules.route("db1", "tbl1");
+        assert_eq!("db1", route.db);
+        assert_eq!("tbl2", route.tbl);
+        let route = rules.route("shard_001", "orders");
+        assert_eq!("analytics_001", route.db);
+        assert_eq!("orders", route.tbl);
+        // regex must match whole name
+        let route = rules.route("shard_001_bak", "orders");
+        assert_eq!("shard_001_bak", route.db);
+        Ok(())
+    }
+
+    #[test]
+    fn test_route_exclude_cols() {
+        let col_defs = vec![col_def("id"), col_def("secret"), col_def("name")];
+        let mut present_bitmap = Bytes::from(vec![0b111u8]);
+        let mut rows = vec![LogRow(vec![
+            BinlogColumnValue::Long(1),
+            BinlogColumnValue::Long(2),
+            BinlogColumnValue::Long(3),
+        ])];
+        exclude_cols(
+            &mut present_bitmap,
+            &mut rows,
+            &col_defs,
+            &[SmolStr::new("secret")],
+        );
+        assert_eq!(&[0b101u8][..], present_bitmap.chunk());
+        assert_eq!(2, rows[0].0.len());
+        assert!(matches!(rows[0].0[1], BinlogColumnValue::Long(3)));
+    }
+}
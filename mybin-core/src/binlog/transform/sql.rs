@@ -1,8 +1,10 @@
 use crate::binlog::rows_v2::{RowsV2, UpdateRowsV2};
+use crate::binlog::transform::float::{self, FloatOpts, FloatText};
 use crate::binlog::transform::FromRowsV2;
 use crate::binlog::transform::{filter_col_defs, ColDef};
-use crate::col::ColumnDefinition;
-use crate::stmt::StmtColumnValue;
+use crate::col::{BinaryColumnValue, ColumnDefinition};
+use crate::error::Result;
+use crate::stmt::{StmtColumnValue, SQL_NULL};
 use bytes::Buf;
 use smol_str::SmolStr;
 use std::borrow::Cow;
@@ -131,8 +133,9 @@ impl FromRowsV2 for PreparedSql {
     }
 }
 
-impl SqlCollection for PreparedSql {
-    fn sql_list(&self) -> Vec<Cow<str>> {
+impl PreparedSql {
+    /// list sql with FLOAT and DOUBLE values formatted by given options
+    pub fn sql_list_with(&self, float: &FloatOpts) -> Result<Vec<String>> {
         let mut list = Vec::with_capacity(self.params.len());
         for cols in &self.params {
             let mut sql = String::new();
@@ -140,7 +143,7 @@ impl SqlCollection for PreparedSql {
             for f in &self.sql_fragments {
                 if f == "?" {
                     if let Some(param) = param_iter.next() {
-                        let (lit, quote) = param.to_sql_literal();
+                        let (lit, quote) = sql_literal(param, float)?;
                         if quote {
                             sql.push('\'');
                         }
@@ -153,12 +156,36 @@ impl SqlCollection for PreparedSql {
                     sql.push_str(f);
                 }
             }
-            list.push(Cow::Owned(sql));
+            list.push(sql);
         }
-        list
+        Ok(list)
     }
 }
 
+impl SqlCollection for PreparedSql {
+    fn sql_list(&self) -> Vec<Cow<str>> {
+        self.sql_list_with(&FloatOpts::default())
+            .expect("null policy never fails")
+            .into_iter()
+            .map(Cow::Owned)
+            .collect()
+    }
+}
+
+fn sql_literal<'a>(param: &'a StmtColumnValue, float: &FloatOpts) -> Result<(Cow<'a, str>, bool)> {
+    let v = match param.val {
+        BinaryColumnValue::Float(v) => float::widen_f32(v),
+        BinaryColumnValue::Double(v) => v,
+        _ => return Ok(param.to_sql_literal()),
+    };
+    let lit = match float.render(v)? {
+        FloatText::Number(s) => (s, false),
+        FloatText::String(s) => (Cow::Borrowed(s), true),
+        FloatText::Null => (Cow::Borrowed(SQL_NULL), false),
+    };
+    Ok(lit)
+}
+
 fn delete_sql_fragments(db: &SmolStr, tbl: &SmolStr, col_defs: &[ColDef]) -> Vec<String> {
     let mut sql_fragments = Vec::new();
     sql_fragments.push(format!("DELETE FROM `{}`.`{}` WHERE ", db, tbl));
@@ -244,6 +271,7 @@ fn insert_sql_fragments(db: &SmolStr, tbl: &SmolStr, col_defs: &[ColDef]) -> Vec
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::binlog::transform::float::{FloatFormat, NonFinitePolicy};
     use crate::stmt::ToColumnValue;
 
     #[test]
@@ -267,4 +295,79 @@ mod tests {
         );
         assert_eq!(vec!["insert into plain1 (id) values (1)"], ps.sql_list());
     }
+
+    #[test]
+    fn test_prepared_sql_converted() {
+        use crate::binlog::transform::convert::ConverterRegistry;
+        use crate::col::tests::col_def;
+        use crate::col::{BinlogColumnValue, ColumnFlags, ColumnType};
+        use crate::row::LogRow;
+        use bytes::Bytes;
+
+        let col_defs = vec![
+            col_def("id", ColumnType::Long, ColumnFlags::PRIMARY_KEY),
+            col_def("name", ColumnType::VarString, ColumnFlags::empty()),
+        ];
+        let rowsv2 = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 2,
+            present_bitmap: Bytes::from(vec![0b11u8]),
+            rows: vec![LogRow(vec![
+                BinlogColumnValue::Long(1),
+                BinlogColumnValue::VarString(Bytes::from("abc")),
+            ])],
+        };
+        let registry =
+            ConverterRegistry::new().col_type(ColumnType::VarString, |_: &ColumnDefinition, v| {
+                match v {
+                    BinlogColumnValue::VarString(bs) => {
+                        BinlogColumnValue::VarString(Bytes::from(bs.to_ascii_uppercase()))
+                    }
+                    other => other,
+                }
+            });
+        let ps: PreparedSql =
+            registry.from_insert("db1".into(), "t1".into(), rowsv2.clone(), &col_defs);
+        assert_eq!(
+            vec!["INSERT INTO `db1`.`t1` (`id`,`name`) VALUES (1,'ABC')"],
+            ps.sql_list()
+        );
+        // values as is without registry
+        let ps = PreparedSql::from_insert("db1".into(), "t1".into(), rowsv2, &col_defs);
+        assert_eq!(
+            vec!["INSERT INTO `db1`.`t1` (`id`,`name`) VALUES (1,'abc')"],
+            ps.sql_list()
+        );
+    }
+
+    #[test]
+    fn test_prepared_sql_floats() {
+        let ps = PreparedSql::new(
+            "bintest1".into(),
+            vec!["insert into f1 (a, b) values (", "?", ", ", "?", ")"]
+                .into_iter()
+                .map(|s| s.to_owned())
+                .collect(),
+            vec![
+                vec![1.1f32.to_col(), 1e20f64.to_col()],
+                vec![f32::NAN.to_col(), 0.2f64.to_col()],
+            ],
+        );
+        assert_eq!(
+            vec![
+                "insert into f1 (a, b) values (1.1, 1e20)",
+                "insert into f1 (a, b) values (null, 0.2)",
+            ],
+            ps.sql_list()
+        );
+        let opts = FloatOpts::new()
+            .format(FloatFormat::Fixed(2))
+            .non_finite(NonFinitePolicy::String);
+        assert_eq!(
+            "insert into f1 (a, b) values ('NaN', 0.20)",
+            ps.sql_list_with(&opts).unwrap()[1]
+        );
+        let opts = opts.non_finite(NonFinitePolicy::Error);
+        assert!(ps.sql_list_with(&opts).is_err());
+    }
 }
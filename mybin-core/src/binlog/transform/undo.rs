@@ -0,0 +1,128 @@
+//! inverse of row changes
+//!
+//! used to flash back data: insert is undone by delete, delete by insert,
+//! and update by swapping before and after images.
+//! rows are reversed so that applying the result of one event restores
+//! the state before that event.
+use crate::binlog::rows_v2::{RowsV2, UpdateRow, UpdateRowsV2};
+use crate::binlog::transform::sql::{PreparedSql, SqlCollection};
+use crate::binlog::transform::FromRowsV2;
+use crate::col::ColumnDefinition;
+use smol_str::SmolStr;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone)]
+pub struct UndoSql(pub PreparedSql);
+
+impl FromRowsV2 for UndoSql {
+    fn from_insert(
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        rowsv2.rows.reverse();
+        UndoSql(PreparedSql::from_delete(db, tbl, rowsv2, col_defs))
+    }
+
+    fn from_delete(
+        db: SmolStr,
+        tbl: SmolStr,
+        mut rowsv2: RowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        rowsv2.rows.reverse();
+        UndoSql(PreparedSql::from_insert(db, tbl, rowsv2, col_defs))
+    }
+
+    fn from_update(
+        db: SmolStr,
+        tbl: SmolStr,
+        rowsv2: UpdateRowsV2,
+        col_defs: &[ColumnDefinition],
+    ) -> Self {
+        let rows = rowsv2
+            .rows
+            .into_iter()
+            .rev()
+            .map(|UpdateRow(before, after)| UpdateRow(after, before))
+            .collect();
+        let inversed = UpdateRowsV2 {
+            extra_data: rowsv2.extra_data,
+            n_cols: rowsv2.n_cols,
+            before_present_bitmap: rowsv2.after_present_bitmap,
+            after_present_bitmap: rowsv2.before_present_bitmap,
+            rows,
+        };
+        UndoSql(PreparedSql::from_update(db, tbl, inversed, col_defs))
+    }
+}
+
+impl SqlCollection for UndoSql {
+    fn sql_list(&self) -> Vec<Cow<'_, str>> {
+        self.0.sql_list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::{BinlogColumnValue, ColumnFlags, ColumnType};
+    use crate::row::LogRow;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_undo_sql() {
+        let col_defs = vec![
+            col_def("id", ColumnFlags::PRIMARY_KEY | ColumnFlags::UNIQUE_KEY),
+            col_def("v", ColumnFlags::empty()),
+        ];
+        let rows = RowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 2,
+            present_bitmap: Bytes::from(vec![0b11u8]),
+            rows: vec![
+                LogRow(vec![
+                    BinlogColumnValue::Long(1),
+                    BinlogColumnValue::Long(10),
+                ]),
+                LogRow(vec![
+                    BinlogColumnValue::Long(2),
+                    BinlogColumnValue::Long(20),
+                ]),
+            ],
+        };
+        let undo = UndoSql::from_insert("db1".into(), "t1".into(), rows.clone(), &col_defs);
+        assert_eq!(
+            vec![
+                "DELETE FROM `db1`.`t1` WHERE `id` = 2",
+                "DELETE FROM `db1`.`t1` WHERE `id` = 1",
+            ],
+            undo.sql_list()
+        );
+        let undo = UndoSql::from_delete("db1".into(), "t1".into(), rows, &col_defs);
+        assert_eq!(
+            "INSERT INTO `db1`.`t1` (`id`,`v`) VALUES (2,20)",
+            undo.sql_list()[0]
+        );
+        let upd = UpdateRowsV2 {
+            extra_data: Bytes::new(),
+            n_cols: 2,
+            before_present_bitmap: Bytes::from(vec![0b11u8]),
+            after_present_bitmap: Bytes::from(vec![0b11u8]),
+            rows: vec![UpdateRow(
+                vec![BinlogColumnValue::Long(1), BinlogColumnValue::Long(10)],
+                vec![BinlogColumnValue::Long(1), BinlogColumnValue::Long(11)],
+            )],
+        };
+        let undo = UndoSql::from_update("db1".into(), "t1".into(), upd, &col_defs);
+        assert_eq!(
+            vec!["UPDATE `db1`.`t1` SET `id` = 1, `v` = 10 WHERE `id` = 1"],
+            undo.sql_list()
+        );
+    }
+
+    fn col_def(name: &str, flags: ColumnFlags) -> ColumnDefinition {
+        crate::col::tests::col_def(name, ColumnType::Long, flags)
+    }
+}
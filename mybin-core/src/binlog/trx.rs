@@ -0,0 +1,612 @@
+//! assemble events of one transaction, with guards on its size
+//!
+//! a transaction starts at GTID event or BEGIN query, and ends at XID
+//! event, COMMIT or ROLLBACK query, or the DDL following a GTID event.
+//! DDL and other statements causing implicit commit are transactions
+//! of their own, and end the transaction started by BEGIN before them
+//! if its COMMIT is missing, e.g. in binlog without GTID.
+//! without guards, the whole transaction is buffered until its end,
+//! so a transaction of millions of rows may exhaust memory. guards
+//! limit rows, bytes and duration of buffered events, and either fail
+//! or deliver the transaction in chunks split at statement boundaries,
+//! or spill the transaction to a temporary file.
+use super::ddl::is_implicit_commit;
+use super::spill::{SpillBuffer, SpillIter};
+use super::{Event, EventText, RowsEventFlags};
+use crate::col::ColumnMetas;
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// what to do if buffered events exceed the guards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizePolicy {
+    /// fail with TransactionTooLarge error
+    Error,
+    /// deliver buffered statements as a chunk, only the last chunk
+    /// of a transaction is flagged as last
+    Split,
+    /// move buffered events and following events of the transaction
+    /// to a temporary file, which is read back when consumed
+    Spill,
+}
+
+/// events of transaction, or a chunk of it
+///
+/// events may be spilled to file, so they are no longer a public
+/// field. use memory_events() for events kept in memory, or
+/// events() to iterate regardless of where they are
+#[derive(Debug)]
+pub struct BinlogTransaction {
+    events: TrxEvents,
+    /// index of chunk in split transaction, starting from 0
+    pub chunk: u32,
+    /// whether the transaction ends in this chunk,
+    /// false if the transaction is split or incomplete
+    pub last: bool,
+    /// rows of rows events, only counted if max rows is guarded
+    pub rows: u64,
+    /// total length of events
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+enum TrxEvents {
+    Memory(Vec<Event>),
+    Spilled(SpillBuffer),
+}
+
+impl BinlogTransaction {
+    pub fn len(&self) -> usize {
+        match &self.events {
+            TrxEvents::Memory(events) => events.len(),
+            TrxEvents::Spilled(spill) => spill.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// whether events are in temporary file
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.events, TrxEvents::Spilled(_))
+    }
+
+    /// events kept in memory, None if spilled
+    pub fn memory_events(&self) -> Option<&[Event]> {
+        match &self.events {
+            TrxEvents::Memory(events) => Some(events),
+            TrxEvents::Spilled(_) => None,
+        }
+    }
+
+    /// iterate events in order, spilled events are read
+    /// from file one by one
+    pub fn events(&self) -> Result<TrxEventIter<'_>> {
+        let iter = match &self.events {
+            TrxEvents::Memory(events) => TrxEventIter::Memory(events.iter()),
+            TrxEvents::Spilled(spill) => TrxEventIter::Spilled(spill.iter()?),
+        };
+        Ok(iter)
+    }
+}
+
+#[derive(Debug)]
+pub enum TrxEventIter<'a> {
+    Memory(std::slice::Iter<'a, Event>),
+    Spilled(SpillIter),
+}
+
+impl<'a> Iterator for TrxEventIter<'a> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            TrxEventIter::Memory(iter) => iter.next().cloned().map(Ok),
+            TrxEventIter::Spilled(iter) => iter.next(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Assembled {
+    Transaction(BinlogTransaction),
+    /// event not belonging to any transaction, e.g. rotate or heartbeat
+    Single(Event),
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    events: Vec<Event>,
+    // once spilled, all following events go to file
+    spill: Option<SpillBuffer>,
+    rows: u64,
+    bytes: u64,
+    chunk: u32,
+    // timestamp of first event of current chunk
+    start_ts: u32,
+    // started by BEGIN, so only COMMIT or ROLLBACK ends it
+    begun: bool,
+    // in the middle of statement, e.g. after table map
+    in_stmt: bool,
+    // column metas of table maps, to count rows
+    col_metas: HashMap<u64, ColumnMetas>,
+}
+
+impl Pending {
+    fn new(start_ts: u32) -> Self {
+        Pending {
+            start_ts,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, event: Event) -> Result<()> {
+        match self.spill.as_mut() {
+            Some(spill) => spill.push(&event),
+            None => {
+                self.events.push(event);
+                Ok(())
+            }
+        }
+    }
+
+    fn spill(&mut self, dir: &Path) -> Result<()> {
+        let mut spill = SpillBuffer::new(dir)?;
+        for event in self.events.drain(..) {
+            spill.push(&event)?;
+        }
+        log::info!(
+            "spill transaction of {} events to {:?}",
+            spill.len(),
+            spill.path()
+        );
+        self.spill = Some(spill);
+        Ok(())
+    }
+
+    fn take_chunk(&mut self, last: bool) -> Result<BinlogTransaction> {
+        let events = match self.spill.take() {
+            Some(mut spill) => {
+                spill.finish()?;
+                TrxEvents::Spilled(spill)
+            }
+            None => TrxEvents::Memory(std::mem::take(&mut self.events)),
+        };
+        let trx = BinlogTransaction {
+            events,
+            chunk: self.chunk,
+            last,
+            rows: self.rows,
+            bytes: self.bytes,
+        };
+        self.chunk += 1;
+        self.rows = 0;
+        self.bytes = 0;
+        Ok(trx)
+    }
+}
+
+#[derive(Debug)]
+pub struct TransactionAssembler {
+    max_rows: Option<u64>,
+    max_bytes: Option<u64>,
+    max_duration: Option<u32>,
+    policy: OversizePolicy,
+    spill_dir: PathBuf,
+    pending: Option<Pending>,
+}
+
+impl Default for TransactionAssembler {
+    fn default() -> Self {
+        TransactionAssembler {
+            max_rows: None,
+            max_bytes: None,
+            max_duration: None,
+            policy: OversizePolicy::Error,
+            spill_dir: std::env::temp_dir(),
+            pending: None,
+        }
+    }
+}
+
+impl TransactionAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// rows are counted by decoding rows events v2 with table maps
+    /// of the transaction, so only enable it if needed
+    pub fn max_rows(mut self, max_rows: u64) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// duration between timestamps of buffered events, in seconds
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration.as_secs() as u32);
+        self
+    }
+
+    pub fn oversize_policy(mut self, policy: OversizePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// directory of temporary files if spilled, system temporary
+    /// directory by default
+    pub fn spill_dir<P: Into<PathBuf>>(mut self, spill_dir: P) -> Self {
+        self.spill_dir = spill_dir.into();
+        self
+    }
+
+    /// push next event, returns completed transactions, chunks and
+    /// single events in order
+    ///
+    /// if guards are exceeded with error policy, buffered events are
+    /// discarded and the stream should be stopped
+    pub fn push(&mut self, event: Event) -> Result<Vec<Assembled>> {
+        let mut res = vec![];
+        match &event {
+            Event::GtidLogEvent(_) | Event::AnonymousGtidLogEvent(_) => {
+                if let Some(pending) = self.pending.take() {
+                    log::warn!(
+                        "discard incomplete transaction of {} events",
+                        pending.events.len()
+                    );
+                }
+                self.pending = Some(Pending::new(event.header().timestamp));
+            }
+            Event::QueryEvent(_) => {
+                let begun = self.pending.as_ref().map(|p| p.begun);
+                if begun.is_none() && is_query(&event, "BEGIN")? {
+                    self.pending = Some(Pending::new(event.header().timestamp));
+                } else if begun != Some(false) && implicit_commit(&event)? {
+                    if let Some(mut pending) = self.pending.take() {
+                        log::debug!(
+                            "transaction of {} events committed implicitly",
+                            pending.events.len()
+                        );
+                        res.push(Assembled::Transaction(pending.take_chunk(true)?));
+                    }
+                    self.pending = Some(Pending::new(event.header().timestamp));
+                }
+            }
+            Event::RotateEvent(_)
+            | Event::FormatDescriptionEvent(_)
+            | Event::PreviousGtidsLogEvent(_)
+            | Event::HeartbeatLogEvent(_)
+            | Event::HeartbeatLogEventV2(_) => {
+                res.push(Assembled::Single(event));
+                return Ok(res);
+            }
+            _ => (),
+        }
+        let count_rows = self.max_rows.is_some();
+        let pending = match self.pending.as_mut() {
+            Some(pending) => pending,
+            None => {
+                res.push(Assembled::Single(event));
+                return Ok(res);
+            }
+        };
+        let ts = event.header().timestamp;
+        pending.bytes += event.header().event_len as u64;
+        let mut ends = false;
+        pending.in_stmt = match &event {
+            Event::TableMapEvent(e) => {
+                if count_rows {
+                    let data = e.clone().into_data()?;
+                    let col_metas = data.table_map()?.col_metas;
+                    pending.col_metas.insert(data.table_id, col_metas);
+                }
+                true
+            }
+            Event::IntvarEvent(_) | Event::RandEvent(_) | Event::UserVarEvent(_) => true,
+            Event::QueryEvent(_) => {
+                if is_query(&event, "BEGIN")? {
+                    pending.begun = true;
+                } else if pending.begun {
+                    ends = is_query(&event, "COMMIT")? || is_query(&event, "ROLLBACK")?;
+                } else {
+                    // DDL after GTID, or implicit commit
+                    ends = true;
+                }
+                false
+            }
+            Event::XidEvent(_) => {
+                ends = true;
+                false
+            }
+            _ => match event.rows_flags()? {
+                Some(flags) => {
+                    if count_rows {
+                        pending.rows += count_rows_v2(&event, &pending.col_metas)?;
+                    }
+                    !flags.contains(RowsEventFlags::STMT_END)
+                }
+                None => false,
+            },
+        };
+        pending.push(event)?;
+        if ends {
+            let trx = pending.take_chunk(true)?;
+            self.pending = None;
+            res.push(Assembled::Transaction(trx));
+            return Ok(res);
+        }
+        if pending.spill.is_some() {
+            return Ok(res);
+        }
+        let exceeded = self.max_rows.is_some_and(|max| pending.rows > max)
+            || self.max_bytes.is_some_and(|max| pending.bytes > max)
+            || self
+                .max_duration
+                .is_some_and(|max| ts.saturating_sub(pending.start_ts) > max);
+        if !exceeded {
+            return Ok(res);
+        }
+        match self.policy {
+            OversizePolicy::Error => {
+                let msg = format!(
+                    "rows={}, bytes={}, duration={}s",
+                    pending.rows,
+                    pending.bytes,
+                    ts.saturating_sub(pending.start_ts)
+                );
+                self.pending = None;
+                Err(Error::TransactionTooLarge(msg))
+            }
+            OversizePolicy::Split => {
+                // statement can not be split
+                if !pending.in_stmt {
+                    res.push(Assembled::Transaction(pending.take_chunk(false)?));
+                    pending.start_ts = ts;
+                }
+                Ok(res)
+            }
+            OversizePolicy::Spill => {
+                pending.spill(&self.spill_dir)?;
+                Ok(res)
+            }
+        }
+    }
+
+    /// events of transaction not ended, e.g. at end of stream
+    pub fn flush(&mut self) -> Result<Option<BinlogTransaction>> {
+        let mut pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        if pending.events.is_empty() && pending.spill.is_none() {
+            return Ok(None);
+        }
+        pending.take_chunk(false).map(Some)
+    }
+
+    /// number of events buffered in current transaction
+    pub fn pending(&self) -> usize {
+        self.pending.as_ref().map_or(0, |p| {
+            p.events.len() + p.spill.as_ref().map_or(0, SpillBuffer::len)
+        })
+    }
+}
+
+fn is_query(event: &Event, query: &str) -> Result<bool> {
+    match event {
+        Event::QueryEvent(e) => {
+            let data = e.clone().into_data()?;
+            Ok(data
+                .query
+                .to_string_lossy()
+                .trim()
+                .eq_ignore_ascii_case(query))
+        }
+        _ => Ok(false),
+    }
+}
+
+fn implicit_commit(event: &Event) -> Result<bool> {
+    match event {
+        Event::QueryEvent(e) => {
+            let data = e.clone().into_data()?;
+            Ok(is_implicit_commit(&data.query.to_string_lossy()))
+        }
+        _ => Ok(false),
+    }
+}
+
+fn count_rows_v2(event: &Event, col_metas: &HashMap<u64, ColumnMetas>) -> Result<u64> {
+    let table_id = match event.rows_table_id()? {
+        Some(table_id) => table_id,
+        None => return Ok(0),
+    };
+    let col_metas = match col_metas.get(&table_id) {
+        Some(col_metas) => col_metas,
+        None => {
+            log::debug!("rows of table {} not counted without table map", table_id);
+            return Ok(0);
+        }
+    };
+    let n = match event {
+        Event::WriteRowsEventV2(e) => e.clone().into_data()?.rows(col_metas)?.rows.len(),
+        Event::UpdateRowsEventV2(e) => e.clone().into_data()?.rows(col_metas)?.rows.len(),
+        Event::DeleteRowsEventV2(e) => e.clone().into_data()?.rows(col_metas)?.rows.len(),
+        _ => 0,
+    };
+    Ok(n as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{EventLength, ParserV4, QueryEvent};
+    use bytes::{Buf, Bytes, BytesMut};
+    use bytes_parser::ReadFromBytes;
+
+    const BINLOG_ROWS_EVENT_V2: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.RowsEventV2");
+    const BINLOG_QUERY_EVENT: &[u8] = include_bytes!("../../data/mysql-bin.5.7.30.QueryEvent");
+
+    fn parse_all() -> Vec<Event> {
+        parse_file(BINLOG_ROWS_EVENT_V2)
+    }
+
+    fn parse_file(file: &'static [u8]) -> Vec<Event> {
+        let mut input = Bytes::from_static(file);
+        let pv4 = ParserV4::from_binlog_file(&mut input).unwrap();
+        let mut events = vec![];
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone()).unwrap().0 as usize;
+            let mut raw = input.split_to(len);
+            if let Some(event) = pv4.parse_event(&mut raw, false).unwrap() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn assemble(assembler: &mut TransactionAssembler) -> Result<Vec<Assembled>> {
+        let mut res = vec![];
+        for event in parse_all() {
+            res.extend(assembler.push(event)?);
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn test_transaction_assembler() {
+        // previous gtids, then gtid, begin, 3 statements and xid
+        let mut assembler = TransactionAssembler::new();
+        match &assemble(&mut assembler).unwrap()[..] {
+            [Assembled::Single(_), Assembled::Transaction(trx)] => {
+                assert_eq!(9, trx.len());
+                assert!(!trx.is_spilled());
+                assert_eq!(9, trx.memory_events().unwrap().len());
+                assert!(trx.last);
+                assert_eq!(0, trx.rows);
+            }
+            other => panic!("unexpected assembled {:?}", other),
+        }
+        assert_eq!(0, assembler.pending());
+
+        // split after each statement, rows counted
+        let mut assembler = TransactionAssembler::new()
+            .max_rows(1)
+            .oversize_policy(OversizePolicy::Split);
+        let chunks: Vec<_> = assemble(&mut assembler)
+            .unwrap()
+            .into_iter()
+            .filter_map(|a| match a {
+                Assembled::Transaction(trx) => Some(trx),
+                Assembled::Single(_) => None,
+            })
+            .collect();
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|trx| (trx.chunk, trx.last, trx.rows, trx.len()))
+            .collect();
+        assert_eq!(
+            vec![(0, false, 2, 4), (1, false, 2, 4), (2, true, 0, 1)],
+            summary
+        );
+
+        let mut assembler = TransactionAssembler::new().max_bytes(200);
+        match assemble(&mut assembler) {
+            Err(Error::TransactionTooLarge(_)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(0, assembler.pending());
+
+        // spilled after table map of second statement
+        let mut assembler = TransactionAssembler::new()
+            .max_bytes(200)
+            .oversize_policy(OversizePolicy::Spill);
+        let trx = match assemble(&mut assembler).unwrap().pop() {
+            Some(Assembled::Transaction(trx)) => trx,
+            other => panic!("unexpected assembled {:?}", other),
+        };
+        assert!(trx.is_spilled());
+        assert!(trx.memory_events().is_none());
+        assert_eq!(9, trx.len());
+        let events = trx.events().unwrap().collect::<Result<Vec<_>>>().unwrap();
+        let expected = &parse_all()[1..];
+        assert_eq!(expected.len(), events.len());
+        for (e1, e2) in expected.iter().zip(&events) {
+            assert_eq!(e1.header(), e2.header());
+            assert_eq!(e1.data(), e2.data());
+        }
+    }
+
+    #[test]
+    fn test_transaction_assembler_implicit_commit() {
+        let ddl = parse_file(BINLOG_QUERY_EVENT)
+            .into_iter()
+            .find(|e| matches!(e, Event::QueryEvent(_)))
+            .unwrap();
+        // begin, table map and rows of first statement, without gtid and xid
+        let mut events: Vec<_> = parse_all().into_iter().skip(2).take(3).collect();
+        events.push(ddl.clone());
+        let mut assembler = TransactionAssembler::new();
+        let mut res = vec![];
+        for event in events {
+            res.extend(assembler.push(event).unwrap());
+        }
+        let lens: Vec<_> = res
+            .iter()
+            .map(|a| match a {
+                Assembled::Transaction(trx) => (trx.len(), trx.last),
+                other => panic!("unexpected assembled {:?}", other),
+            })
+            .collect();
+        assert_eq!(vec![(3, true), (1, true)], lens);
+        assert_eq!(0, assembler.pending());
+
+        // DDL without gtid is not a single event
+        match &assembler.push(ddl).unwrap()[..] {
+            [Assembled::Transaction(trx)] => assert_eq!(1, trx.len()),
+            other => panic!("unexpected assembled {:?}", other),
+        }
+    }
+
+    /// query event of given statement, based on another query event
+    fn query(template: &Event, sql: &[u8]) -> Event {
+        let template = match template {
+            Event::QueryEvent(e) => e,
+            other => panic!("unexpected event {:?}", other),
+        };
+        let query_len = template.clone().into_data().unwrap().query.len();
+        let mut data = BytesMut::from(&template.data[..template.data.len() - query_len]);
+        data.extend_from_slice(sql);
+        Event::QueryEvent(QueryEvent::new(template.header.clone(), data.freeze()))
+    }
+
+    #[test]
+    fn test_transaction_assembler_non_utf8_query() {
+        let ddl = parse_file(BINLOG_QUERY_EVENT)
+            .into_iter()
+            .find(|e| matches!(e, Event::QueryEvent(_)))
+            .unwrap();
+        // latin1 statement in statement-based replication
+        let events = vec![
+            query(&ddl, b"BEGIN"),
+            query(&ddl, b"INSERT INTO t1 VALUES ('caf\xe9')"),
+            query(&ddl, b"COMMIT"),
+        ];
+        let mut assembler = TransactionAssembler::new();
+        let mut res = vec![];
+        for event in events {
+            res.extend(assembler.push(event).unwrap());
+        }
+        match &res[..] {
+            [Assembled::Transaction(trx)] => assert_eq!((3, true), (trx.len(), trx.last)),
+            other => panic!("unexpected assembled {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use super::text::{EventText, BINARY_COLLATION_ID};
+use crate::decimal::MyDecimal;
 use bitflags::bitflags;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::{ReadBytesExt, ReadFromBytes};
+use std::borrow::Cow;
+use std::convert::TryFrom;
 
 /// Data of UserVarEvent
 ///
@@ -30,21 +34,50 @@ impl ReadFromBytes for UserVarData {
     }
 }
 
+/// type of user variable, Item_result in server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserVarType {
+    String,
+    Real,
+    Int,
+    Row,
+    Decimal,
+}
+
+impl UserVarType {
+    fn from_u8(code: u8) -> Result<Self> {
+        let ty = match code {
+            0 => UserVarType::String,
+            1 => UserVarType::Real,
+            2 => UserVarType::Int,
+            3 => UserVarType::Row,
+            4 => UserVarType::Decimal,
+            _ => {
+                return Err(Error::ConstraintError(format!(
+                    "invalid user var type {}",
+                    code
+                )))
+            }
+        };
+        Ok(ty)
+    }
+}
+
 /// value part of UserVarEvent
 ///
 /// reference: https://github.com/mysql/mysql-server/blob/5.7/libbinlogevents/include/statement_events.h#L824
 #[derive(Debug, Clone)]
 pub struct UserVarValue {
-    pub value_type: u8,
+    pub value_type: UserVarType,
+    /// collation id of string value
     pub charset_num: u32,
     pub value: Bytes,
     pub flags: UserVarFlags,
 }
 
-// todo: extract meaningful value from value byte arrary
 impl ReadFromBytes for UserVarValue {
     fn read_from(input: &mut Bytes) -> Result<Self> {
-        let value_type = input.read_u8()?;
+        let value_type = UserVarType::from_u8(input.read_u8()?)?;
         let charset_num = input.read_le_u32()?;
         let value_len = input.read_le_u32()?;
         let value = input.read_len(value_len as usize)?;
@@ -65,8 +98,118 @@ impl ReadFromBytes for UserVarValue {
     }
 }
 
+impl UserVarValue {
+    pub fn is_unsigned(&self) -> bool {
+        self.flags.contains(UserVarFlags::UNSIGNED)
+    }
+
+    /// string value decoded by charset of charset_num,
+    /// None if value is not a string
+    pub fn to_text(&self) -> Option<Cow<'_, str>> {
+        if self.value_type != UserVarType::String {
+            return None;
+        }
+        let collation_id = u16::try_from(self.charset_num).unwrap_or(BINARY_COLLATION_ID);
+        Some(self.value.to_string_with_collation(collation_id))
+    }
+
+    /// decode value by its type, strings are kept as raw bytes
+    /// in charset of charset_num, see to_text()
+    pub fn decode(&self) -> Result<UserVarTypedValue> {
+        let mut bs = self.value.clone();
+        let value = match self.value_type {
+            UserVarType::String => UserVarTypedValue::String(bs),
+            UserVarType::Real => UserVarTypedValue::Real(f64::from_bits(bs.read_le_u64()?)),
+            UserVarType::Int => {
+                let n = bs.read_le_u64()?;
+                if self.is_unsigned() {
+                    UserVarTypedValue::UInt(n)
+                } else {
+                    UserVarTypedValue::Int(n as i64)
+                }
+            }
+            UserVarType::Decimal => {
+                // precision and scale precede binary decimal
+                let precision = bs.read_u8()?;
+                let scale = bs.read_u8()?;
+                if scale > precision {
+                    return Err(Error::ConstraintError(format!(
+                        "invalid user var decimal precision={}, scale={}",
+                        precision, scale
+                    )));
+                }
+                UserVarTypedValue::Decimal(MyDecimal::read_from(&mut bs, precision - scale, scale)?)
+            }
+            UserVarType::Row => {
+                return Err(Error::ConstraintError(
+                    "row type of user var not supported".to_owned(),
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserVarTypedValue {
+    String(Bytes),
+    Real(f64),
+    Int(i64),
+    UInt(u64),
+    Decimal(MyDecimal),
+}
+
 bitflags! {
     pub struct UserVarFlags: u8 {
         const UNSIGNED = 0x01;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{BufMut, BytesMut};
+    use bytes_parser::WriteToBytes;
+
+    fn value_bytes(value_type: u8, value: &[u8], flags: u8) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u8(value_type);
+        buf.put_u32_le(33);
+        buf.put_u32_le(value.len() as u32);
+        buf.put_slice(value);
+        buf.put_u8(flags);
+        buf.freeze()
+    }
+
+    #[test]
+    fn test_user_var_value() {
+        let uvv = UserVarValue::read_from(&mut value_bytes(2, &(-1i64).to_le_bytes(), 1)).unwrap();
+        assert!(uvv.is_unsigned());
+        assert_eq!(UserVarTypedValue::UInt(u64::MAX), uvv.decode().unwrap());
+        let uvv = UserVarValue::read_from(&mut value_bytes(2, &(-1i64).to_le_bytes(), 0)).unwrap();
+        assert_eq!(UserVarTypedValue::Int(-1), uvv.decode().unwrap());
+        let uvv = UserVarValue::read_from(&mut value_bytes(1, &1.5f64.to_le_bytes(), 0)).unwrap();
+        assert_eq!(UserVarTypedValue::Real(1.5), uvv.decode().unwrap());
+        assert!(uvv.to_text().is_none());
+        let uvv = UserVarValue::read_from(&mut value_bytes(0, b"abc", 0)).unwrap();
+        assert_eq!((UserVarType::String, 33), (uvv.value_type, uvv.charset_num));
+        assert_eq!(
+            UserVarTypedValue::String(Bytes::from_static(b"abc")),
+            uvv.decode().unwrap()
+        );
+        assert_eq!("abc", uvv.to_text().unwrap());
+        let mut uvv = UserVarValue::read_from(&mut value_bytes(0, b"caf\xe9", 0)).unwrap();
+        // latin1_swedish_ci
+        uvv.charset_num = 8;
+        assert_eq!("café", uvv.to_text().unwrap());
+
+        let dec = MyDecimal::parse("-123.45", 5, 2).unwrap();
+        let mut value = BytesMut::new();
+        value.put_u8(5);
+        value.put_u8(2);
+        dec.clone().write_to(&mut value).unwrap();
+        let uvv = UserVarValue::read_from(&mut value_bytes(4, &value, 0)).unwrap();
+        assert_eq!(UserVarTypedValue::Decimal(dec), uvv.decode().unwrap());
+        assert!(UserVarValue::read_from(&mut value_bytes(9, b"", 0)).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! row hash and table checksum for consistency checking
+//!
+//! values are hashed in canonical form, so rows selected by query
+//! and rows reconstructed from binlog have equal hashes. columns are
+//! hashed in order of their names, so tables with the same columns
+//! in different order have equal checksums.
+//!
+//! limitations of comparing query results with binlog rows:
+//! TIMESTAMP in query result must be selected in UTC time zone,
+//! ENUM and SET in query result must be selected by their indexes,
+//! e.g. `col+0`.
+use crate::binlog::pk::Fnv1a;
+use crate::binlog::TableMap;
+use crate::col::ColumnDefinition;
+use crate::error::{Error, Result};
+use crate::time::MyDateTime;
+use crate::value::{Row, Value};
+use bytes::Buf;
+use chrono::{Datelike, Timelike};
+use serde_derive::*;
+use smol_str::SmolStr;
+
+/// hasher of rows with given columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowHasher {
+    /// (lowercase name, index in row) sorted by name
+    cols: Vec<(SmolStr, usize)>,
+}
+
+impl RowHasher {
+    /// column names in order of row values
+    pub fn new<S: AsRef<str>>(col_names: &[S]) -> Self {
+        let mut cols: Vec<_> = col_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (SmolStr::new(name.as_ref().to_lowercase()), idx))
+            .collect();
+        cols.sort();
+        RowHasher { cols }
+    }
+
+    pub fn from_col_defs(col_defs: &[ColumnDefinition]) -> Self {
+        let names: Vec<_> = col_defs.iter().map(|def| def.org_name.as_str()).collect();
+        Self::new(&names)
+    }
+
+    /// returns None if column names are not recorded in table map,
+    /// see binlog_row_metadata=FULL
+    pub fn from_table_map(table_map: &TableMap) -> Option<Self> {
+        let names: Vec<_> = table_map
+            .metadata
+            .column_names
+            .iter()
+            .map(|name| name.as_str())
+            .collect();
+        if names.is_empty() {
+            None
+        } else {
+            Some(Self::new(&names))
+        }
+    }
+
+    /// 64-bit hash of row, stable across processes and versions
+    pub fn row_hash(&self, row: &Row) -> Result<u64> {
+        if row.len() != self.cols.len() {
+            return Err(Error::ColumnIndexOutOfBound(format!(
+                "row has {} columns, {} expected",
+                row.len(),
+                self.cols.len()
+            )));
+        }
+        let mut h = Fnv1a::default();
+        for (name, idx) in &self.cols {
+            h.write_len_bytes(name.as_bytes());
+            // index is checked by row length
+            hash_value(&mut h, &row.0[*idx]);
+        }
+        Ok(h.0)
+    }
+}
+
+fn hash_value(h: &mut Fnv1a, value: &Value) {
+    match value {
+        Value::Null => h.write(&[0]),
+        Value::Int(n) => hash_int(h, *n as i128),
+        Value::UInt(n) | Value::Enum(n) => hash_int(h, *n as i128),
+        Value::Year(n) => hash_int(h, *n as i128),
+        Value::Float(n) => {
+            h.write(&[2]);
+            h.write(&(*n as f64).to_bits().to_le_bytes());
+        }
+        Value::Double(n) => {
+            h.write(&[2]);
+            h.write(&n.to_bits().to_le_bytes());
+        }
+        Value::Decimal(d) => {
+            h.write(&[3]);
+            h.write_len_bytes(d.to_string().as_bytes());
+        }
+        Value::Date { .. } | Value::Time(_) | Value::DateTime(_) => {
+            h.write(&[4]);
+            h.write_len_bytes(value.to_sql_literal().as_bytes());
+        }
+        Value::Timestamp(ts) => {
+            // same as DATETIME selected in UTC
+            let dt = ts.to_datetime().naive_utc();
+            let dt = Value::DateTime(MyDateTime {
+                year: dt.year() as u16,
+                month: dt.month() as u8,
+                day: dt.day() as u8,
+                hour: dt.hour() as u8,
+                minute: dt.minute() as u8,
+                second: dt.second() as u8,
+                micro_second: ts.micro_second,
+            });
+            h.write(&[4]);
+            h.write_len_bytes(dt.to_sql_literal().as_bytes());
+        }
+        Value::Bit(bs) | Value::Bytes(bs) => {
+            h.write(&[5]);
+            h.write_len_bytes(bs.chunk());
+        }
+    }
+}
+
+fn hash_int(h: &mut Fnv1a, n: i128) {
+    h.write(&[1]);
+    h.write(&n.to_le_bytes());
+}
+
+/// order-independent checksum of a set of rows
+///
+/// rows can be removed, so checksum of a table can be maintained
+/// by replaying row events, e.g. delete removes the before image,
+/// update removes the before image and adds the after image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableChecksum {
+    pub rows: i64,
+    /// wrapping sum of row hashes, duplicate rows do not cancel out
+    pub sum: u64,
+}
+
+impl TableChecksum {
+    pub fn add(&mut self, row_hash: u64) {
+        self.rows += 1;
+        self.sum = self.sum.wrapping_add(row_hash);
+    }
+
+    pub fn remove(&mut self, row_hash: u64) {
+        self.rows -= 1;
+        self.sum = self.sum.wrapping_sub(row_hash);
+    }
+
+    pub fn add_row(&mut self, hasher: &RowHasher, row: &Row) -> Result<()> {
+        self.add(hasher.row_hash(row)?);
+        Ok(())
+    }
+
+    pub fn remove_row(&mut self, hasher: &RowHasher, row: &Row) -> Result<()> {
+        self.remove(hasher.row_hash(row)?);
+        Ok(())
+    }
+
+    /// combine checksums of disjoint sets, e.g. chunks of a table
+    pub fn merge(&mut self, other: &TableChecksum) {
+        self.rows += other.rows;
+        self.sum = self.sum.wrapping_add(other.sum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::col::tests::col_def;
+    use crate::col::{BinlogColumnValue, ColumnFlags, ColumnType, TextColumnValue};
+    use crate::time::UtcTimestamp;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_table_checksum() {
+        let defs = vec![
+            col_def("ID", ColumnType::Long, ColumnFlags::UNSIGNED),
+            col_def("name", ColumnType::VarString, ColumnFlags::empty()),
+            col_def("ts", ColumnType::Timestamp2, ColumnFlags::empty()),
+        ];
+        let text = |id: &str, name: Option<&str>| -> Row {
+            let vals: Vec<TextColumnValue> = vec![
+                Some(Bytes::copy_from_slice(id.as_bytes())),
+                name.map(|s| Bytes::copy_from_slice(s.as_bytes())),
+                Some(Bytes::from("1970-01-01 00:00:01.000000")),
+            ];
+            Row(vals
+                .into_iter()
+                .zip(&defs)
+                .map(|(v, def)| Value::from_text(v, def).unwrap())
+                .collect())
+        };
+        // binlog row with different column order
+        let binlog = |id: u32| -> Row {
+            Row(vec![
+                Value::Timestamp(UtcTimestamp::new(1, 0)),
+                Value::Null,
+                Value::from_binlog(BinlogColumnValue::Long(id), true).unwrap(),
+            ])
+        };
+        let by_def = RowHasher::from_col_defs(&defs);
+        let by_name = RowHasher::new(&["ts", "name", "id"]);
+        assert_eq!(
+            by_def.row_hash(&text("7", None)).unwrap(),
+            by_name.row_hash(&binlog(7)).unwrap()
+        );
+        assert_ne!(
+            by_def.row_hash(&text("7", Some(""))).unwrap(),
+            by_def.row_hash(&text("7", None)).unwrap()
+        );
+        assert!(by_def.row_hash(&Row(vec![Value::Null])).is_err());
+
+        let mut snapshot = TableChecksum::default();
+        for id in &["1", "2", "2"] {
+            snapshot.add_row(&by_def, &text(id, None)).unwrap();
+        }
+        // replay: insert 1, 2, 3, 2, then delete 3
+        let mut replayed = TableChecksum::default();
+        for id in &[3, 1, 2, 2] {
+            replayed.add_row(&by_name, &binlog(*id)).unwrap();
+        }
+        assert_ne!(snapshot, replayed);
+        replayed.remove_row(&by_name, &binlog(3)).unwrap();
+        assert_eq!(snapshot, replayed);
+        let mut chunks = TableChecksum::default();
+        chunks.add_row(&by_def, &text("2", None)).unwrap();
+        let mut rest = TableChecksum::default();
+        rest.add_row(&by_def, &text("1", None)).unwrap();
+        rest.add_row(&by_def, &text("2", None)).unwrap();
+        chunks.merge(&rest);
+        assert_eq!(snapshot, chunks);
+    }
+}
@@ -18,6 +18,9 @@ pub struct ComStmtExecute {
     // the previous one, this flag should be true
     pub new_params_bound: bool,
     pub params: Vec<StmtColumnValue>,
+    // parameters already sent by COM_STMT_SEND_LONG_DATA,
+    // only their types are written
+    pub long_data_params: Vec<u16>,
 }
 
 impl ComStmtExecute {
@@ -32,8 +35,14 @@ impl ComStmtExecute {
             null_bitmap,
             new_params_bound: true,
             params,
+            long_data_params: vec![],
         }
     }
+
+    pub fn long_data_params(mut self, long_data_params: Vec<u16>) -> Self {
+        self.long_data_params = long_data_params;
+        self
+    }
 }
 
 impl WriteToBytes for ComStmtExecute {
@@ -53,8 +62,10 @@ impl WriteToBytes for ComStmtExecute {
                     len += out.write_u8(if param.unsigned { 0x80 } else { 0x00 })?;
                 }
             }
-            for param in self.params {
-                len += out.write_bytes(param.val)?;
+            for (i, param) in self.params.into_iter().enumerate() {
+                if !self.long_data_params.contains(&(i as u16)) {
+                    len += out.write_bytes(param.val)?;
+                }
             }
         }
         Ok(len)
@@ -4,6 +4,7 @@ use bytes::BytesMut;
 use bytes_parser::error::Result;
 use bytes_parser::{WriteBytesExt, WriteToBytes};
 
+#[derive(Debug, Clone)]
 pub struct ComStmtReset {
     pub cmd: Command,
     pub stmt_id: u32,
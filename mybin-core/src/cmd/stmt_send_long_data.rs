@@ -1,7 +1,12 @@
 use crate::Command;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use bytes_parser::error::Result;
+use bytes_parser::{WriteBytesExt, WriteToBytes};
 
-// todo: long data sending currently not supported
+/// send part of parameter value before execution
+///
+/// server does not respond to this command, and appends data of
+/// multiple commands on the same parameter
 #[derive(Debug, Clone)]
 pub struct ComStmtSendLongData {
     pub cmd: Command,
@@ -9,3 +14,39 @@ pub struct ComStmtSendLongData {
     pub param_id: u16,
     pub data: Bytes,
 }
+
+impl ComStmtSendLongData {
+    pub fn new(stmt_id: u32, param_id: u16, data: Bytes) -> Self {
+        Self {
+            cmd: Command::StmtSendLongData,
+            stmt_id,
+            param_id,
+            data,
+        }
+    }
+}
+
+impl WriteToBytes for ComStmtSendLongData {
+    fn write_to(self, out: &mut BytesMut) -> Result<usize> {
+        let mut len = 0;
+        len += out.write_u8(self.cmd.to_byte())?;
+        len += out.write_le_u32(self.stmt_id)?;
+        len += out.write_le_u16(self.param_id)?;
+        len += out.write_bytes(self.data)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_stmt_send_long_data() {
+        let cmd = ComStmtSendLongData::new(1, 2, Bytes::from_static(b"abc"));
+        let mut out = BytesMut::new();
+        let len = cmd.write_to(&mut out).unwrap();
+        assert_eq!(10, len);
+        assert_eq!(&[0x18, 1, 0, 0, 0, 2, 0, b'a', b'b', b'c'][..], &out[..]);
+    }
+}
@@ -0,0 +1,181 @@
+//! MySQL packet framing for tokio-util based transports
+//!
+//! each packet has 3-byte payload length and 1-byte sequence id.
+//! PacketCodec decodes single packets, so proxies can forward them
+//! untouched. messages larger than 16MB are split into multiple
+//! packets on encoding, see MessageCodec to receive them as a whole.
+use crate::error::Error;
+use crate::packet::Packet;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// max payload length of a single packet
+pub const MAX_PAYLOAD_LEN: usize = 0xff_ffff;
+
+const HEADER_LEN: usize = 4;
+
+#[derive(Debug, Clone, Default)]
+pub struct PacketCodec {
+    /// sequence id of next packet to send
+    seq_id: u8,
+}
+
+impl PacketCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seq_id(&self) -> u8 {
+        self.seq_id
+    }
+
+    /// must be called before sending a command
+    pub fn reset_seq_id(&mut self) {
+        self.seq_id = 0;
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, Error> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let payload_len = src[0] as usize | (src[1] as usize) << 8 | (src[2] as usize) << 16;
+        if src.len() < HEADER_LEN + payload_len {
+            src.reserve(HEADER_LEN + payload_len - src.len());
+            return Ok(None);
+        }
+        let seq_id = src[3];
+        src.advance(HEADER_LEN);
+        let payload = src.split_to(payload_len).freeze();
+        // reply continues the sequence
+        self.seq_id = seq_id.wrapping_add(1);
+        Ok(Some(Packet {
+            payload_len: payload_len as u32,
+            seq_id,
+            payload,
+        }))
+    }
+}
+
+/// forward packet with its own sequence id
+impl Encoder<Packet> for PacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, pkt: Packet, dst: &mut BytesMut) -> Result<(), Error> {
+        write_packet(pkt.seq_id, pkt.payload.chunk(), dst);
+        self.seq_id = pkt.seq_id.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// send message payload, split into packets if too large
+impl Encoder<Bytes> for PacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, mut msg: Bytes, dst: &mut BytesMut) -> Result<(), Error> {
+        dst.reserve(msg.len() + (msg.len() / MAX_PAYLOAD_LEN + 1) * HEADER_LEN);
+        loop {
+            let len = msg.len().min(MAX_PAYLOAD_LEN);
+            write_packet(self.seq_id, &msg[..len], dst);
+            self.seq_id = self.seq_id.wrapping_add(1);
+            msg.advance(len);
+            // payload of max length is followed by another packet,
+            // which may be empty
+            if len < MAX_PAYLOAD_LEN {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn write_packet(seq_id: u8, payload: &[u8], dst: &mut BytesMut) {
+    dst.reserve(HEADER_LEN + payload.len());
+    dst.put_uint_le(payload.len() as u64, 3);
+    dst.put_u8(seq_id);
+    dst.put_slice(payload);
+}
+
+/// decodes full messages concatenated from packets
+#[derive(Debug, Clone, Default)]
+pub struct MessageCodec {
+    inner: PacketCodec,
+    partial: BytesMut,
+}
+
+impl MessageCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seq_id(&self) -> u8 {
+        self.inner.seq_id()
+    }
+
+    pub fn reset_seq_id(&mut self) {
+        self.inner.reset_seq_id()
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, Error> {
+        while let Some(pkt) = self.inner.decode(src)? {
+            if self.partial.is_empty() && (pkt.payload_len as usize) < MAX_PAYLOAD_LEN {
+                return Ok(Some(pkt.payload));
+            }
+            self.partial.extend_from_slice(&pkt.payload);
+            if (pkt.payload_len as usize) < MAX_PAYLOAD_LEN {
+                return Ok(Some(self.partial.split().freeze()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder<Bytes> for MessageCodec {
+    type Error = Error;
+
+    fn encode(&mut self, msg: Bytes, dst: &mut BytesMut) -> Result<(), Error> {
+        self.inner.encode(msg, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_codec() {
+        let mut codec = PacketCodec::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Bytes::from_static(b"\x0eping"), &mut buf)
+            .unwrap();
+        assert_eq!(&b"\x05\x00\x00\x00\x0eping"[..], &buf[..]);
+        assert_eq!(1, codec.seq_id());
+        // incomplete packet
+        let mut partial = BytesMut::from(&buf[..6]);
+        assert!(codec.decode(&mut partial).unwrap().is_none());
+        partial.extend_from_slice(&buf[6..]);
+        let pkt = codec.decode(&mut partial).unwrap().unwrap();
+        assert_eq!((0, &b"\x0eping"[..]), (pkt.seq_id, pkt.payload.chunk()));
+        assert!(partial.is_empty());
+
+        // large message is split and ends with empty packet
+        let msg = Bytes::from(vec![1u8; MAX_PAYLOAD_LEN]);
+        let mut codec = MessageCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(msg.clone(), &mut buf).unwrap();
+        assert_eq!(MAX_PAYLOAD_LEN + HEADER_LEN * 2, buf.len());
+        assert_eq!(&[0, 0, 0, 1][..], &buf[buf.len() - HEADER_LEN..]);
+        assert_eq!(2, codec.seq_id());
+        assert_eq!(msg, codec.decode(&mut buf).unwrap().unwrap());
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+}
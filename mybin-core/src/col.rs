@@ -1,11 +1,14 @@
 //! defines structure and metadata for mysql columns
 use crate::decimal::MyDecimal;
-use crate::time::{MyDateTime, MyTime};
+use crate::time::{MyDateTime, MyTime, UtcTimestamp, WallClockDateTime};
+use crate::version::ServerVersion;
 use bitflags::bitflags;
 use bytes::{Bytes, BytesMut};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::my::{LenEncStr, ReadMyEnc};
-use bytes_parser::{ReadBytesExt, WriteBytesExt, WriteToBytes};
+use bytes_parser::{
+    checked_sub_len, ReadBytesExt, WriteBytesExt, WriteToBytes, WriteToBytesWithContext,
+};
 use smol_str::SmolStr;
 use std::convert::TryFrom;
 
@@ -16,7 +19,7 @@ use std::convert::TryFrom;
 ///
 /// several types are missing in binlog, refer to:
 /// https://github.com/mysql/mysql-server/blob/5.7/libbinlogevents/include/rows_event.h#L174
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ColumnType {
     Decimal,
     Tiny,
@@ -149,11 +152,13 @@ impl From<&ColumnMeta> for ColumnType {
             ColumnMeta::Double { .. } => ColumnType::Double,
             ColumnMeta::Null => ColumnType::Null,
             ColumnMeta::Timestamp { .. } => ColumnType::Timestamp,
+            ColumnMeta::OldTimestamp => ColumnType::Timestamp,
             ColumnMeta::LongLong => ColumnType::LongLong,
             ColumnMeta::Int24 => ColumnType::Int24,
             ColumnMeta::Date => ColumnType::Date,
             ColumnMeta::Time => ColumnType::Time,
             ColumnMeta::DateTime { .. } => ColumnType::DateTime,
+            ColumnMeta::OldDateTime => ColumnType::DateTime,
             ColumnMeta::Year => ColumnType::Year,
             // NewDate,
             // ColumnMeta::Varchar { .. } => ColumnType::Varchar,
@@ -164,7 +169,7 @@ impl From<&ColumnMeta> for ColumnType {
             // Json,
             ColumnMeta::NewDecimal { .. } => ColumnType::NewDecimal,
             ColumnMeta::Enum { .. } => ColumnType::String,
-            // Set,
+            ColumnMeta::Set { .. } => ColumnType::String,
             // TinyBlob,
             // MediumBlob,
             // LongBlob,
@@ -182,10 +187,20 @@ pub struct ColumnMetas(pub Vec<ColumnMeta>);
 impl ColumnMetas {
     // bitmap may be longer than the size
     pub fn read_from(input: &mut Bytes, col_cnt: usize, col_defs: &[u8]) -> Result<Self> {
+        Self::read_with_format(input, col_cnt, col_defs, TemporalFormat::Mixed)
+    }
+
+    /// read column metas of table written by server of given format
+    pub fn read_with_format(
+        input: &mut Bytes,
+        col_cnt: usize,
+        col_defs: &[u8],
+        format: TemporalFormat,
+    ) -> Result<Self> {
         let mut col_metas = Vec::with_capacity(col_cnt);
-        for i in 0..col_cnt {
-            let col_type = ColumnType::try_from(col_defs[i])?;
-            let col_meta = ColumnMeta::read_from(input, col_type)?;
+        for &col_def in col_defs.iter().take(col_cnt) {
+            let col_type = ColumnType::try_from(col_def)?;
+            let col_meta = ColumnMeta::read_with_format(input, col_type, format)?;
             col_metas.push(col_meta);
         }
         Ok(ColumnMetas(col_metas))
@@ -219,12 +234,16 @@ pub enum ColumnMeta {
     Double { pack_len: u8 },
     Null,
     Timestamp { frac: u8 },
+    // before 5.6.4, seconds as little endian u32
+    OldTimestamp,
     LongLong,
     Int24,
     Date,
     // should be deprecated
     Time,
     DateTime { frac: u8 },
+    // before 5.6.4, YYYYMMDDhhmmss as little endian u64
+    OldDateTime,
     Year,
     // NewDate,
     // Varchar { max_len: u16 },
@@ -236,7 +255,8 @@ pub enum ColumnMeta {
     NewDecimal { prec: u8, frac: u8 },
     // Enum is acually encoded in real_type of String type
     Enum { pack_len: u8 },
-    // Set,
+    // Set is also encoded in real_type of String type
+    Set { pack_len: u8 },
     // TinyBlob,
     // MediumBlob,
     // LongBlob,
@@ -249,8 +269,59 @@ pub enum ColumnMeta {
     Geometry { pack_len: u8 },
 }
 
+/// layouts of temporal columns written by server, hinted by server
+/// version in FDE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemporalFormat {
+    /// before 5.6.4, only TIMESTAMP, TIME and DATETIME without fraction
+    Legacy,
+    /// from 5.6.4, TIMESTAMP2, TIME2 and DATETIME2, old layouts remain
+    /// in tables not altered since upgrade
+    Mixed,
+    /// from 8.0, old layouts are converted on upgrade
+    Current,
+}
+
+impl TemporalFormat {
+    /// format of server version, e.g. 5.5.62-log, unknown version
+    /// is treated as mixed
+    pub fn from_server_version(server_version: &str) -> Self {
+        ServerVersion::parse(server_version)
+            .map(|v| v.temporal_format())
+            .unwrap_or(TemporalFormat::Mixed)
+    }
+
+    fn accepts(self, col_type: ColumnType) -> bool {
+        match col_type {
+            ColumnType::Timestamp | ColumnType::Time | ColumnType::DateTime => {
+                self != TemporalFormat::Current
+            }
+            ColumnType::Timestamp2 | ColumnType::Time2 | ColumnType::DateTime2 => {
+                self != TemporalFormat::Legacy
+            }
+            _ => true,
+        }
+    }
+}
+
 impl ColumnMeta {
     pub fn read_from(input: &mut Bytes, col_type: ColumnType) -> Result<Self> {
+        Self::read_with_format(input, col_type, TemporalFormat::Mixed)
+    }
+
+    /// read column meta, temporal type not written in given format
+    /// is rejected
+    pub fn read_with_format(
+        input: &mut Bytes,
+        col_type: ColumnType,
+        format: TemporalFormat,
+    ) -> Result<Self> {
+        if !format.accepts(col_type) {
+            return Err(Error::ConstraintError(format!(
+                "column type {:?} not expected in {:?} temporal format",
+                col_type, format
+            )));
+        }
         let col_meta = match col_type {
             ColumnType::Decimal => ColumnMeta::Decimal,
             ColumnType::Tiny => ColumnMeta::Tiny,
@@ -267,7 +338,7 @@ impl ColumnMeta {
                 ColumnMeta::Double { pack_len }
             }
             ColumnType::Null => ColumnMeta::Null,
-            ColumnType::Timestamp => unimplemented!(),
+            ColumnType::Timestamp => ColumnMeta::OldTimestamp,
             ColumnType::Timestamp2 => {
                 let frac = input.read_u8()?;
                 ColumnMeta::Timestamp { frac }
@@ -275,12 +346,12 @@ impl ColumnMeta {
             ColumnType::LongLong => ColumnMeta::LongLong,
             ColumnType::Int24 => ColumnMeta::Int24,
             ColumnType::Date => ColumnMeta::Date,
-            ColumnType::Time => unimplemented!(),
+            ColumnType::Time => ColumnMeta::Time,
             ColumnType::Time2 => {
                 let frac = input.read_u8()?;
                 ColumnMeta::Time2 { frac }
             }
-            ColumnType::DateTime => unimplemented!(),
+            ColumnType::DateTime => ColumnMeta::OldDateTime,
             ColumnType::DateTime2 => {
                 let frac = input.read_u8()?;
                 ColumnMeta::DateTime { frac }
@@ -323,6 +394,9 @@ impl ColumnMeta {
                     0xf7 => ColumnMeta::Enum {
                         pack_len: field_len,
                     },
+                    0xf8 => ColumnMeta::Set {
+                        pack_len: field_len,
+                    },
                     0xfe => {
                         let from_len =
                             (((((real_type >> 4) & 0x03) ^ 0x03) as u16) << 8) + field_len as u16;
@@ -768,6 +842,8 @@ pub enum BinlogColumnValue {
     Bit(Bytes),
     NewDecimal(MyDecimal),
     Enum(MyEnum),
+    // bitmap of set members
+    Set(u64),
     Blob(Bytes),
     VarString(Bytes),
     String(Bytes),
@@ -796,6 +872,22 @@ impl MyEnum {
 }
 
 impl BinlogColumnValue {
+    /// instant of TIMESTAMP value
+    pub fn utc_timestamp(&self) -> Option<UtcTimestamp> {
+        match self {
+            BinlogColumnValue::Timestamp(secs) => Some(UtcTimestamp::new(*secs as i64, 0)),
+            _ => None,
+        }
+    }
+
+    /// wall clock of DATETIME value
+    pub fn wall_clock(&self) -> Option<WallClockDateTime> {
+        match self {
+            BinlogColumnValue::DateTime(dt) => Some(dt.clone().into()),
+            _ => None,
+        }
+    }
+
     /// read bytes based on binlog protocol
     ///
     /// binlog protocol use separate column meta to distinguish different types
@@ -814,6 +906,7 @@ impl BinlogColumnValue {
                 let secs = input.read_be_u32()?;
                 BinlogColumnValue::Timestamp(secs)
             }
+            ColumnMeta::OldTimestamp => BinlogColumnValue::Timestamp(input.read_le_u32()?),
             ColumnMeta::LongLong => BinlogColumnValue::LongLong(input.read_le_u64()?),
             // in binlog int24 is stored as 3-byte integer
             ColumnMeta::Int24 => BinlogColumnValue::Int24(input.read_le_u24()?),
@@ -855,9 +948,12 @@ impl BinlogColumnValue {
             ColumnMeta::DateTime { frac } => {
                 Self::DateTime(MyDateTime::from_binlog(input, *frac as usize)?)
             }
+            ColumnMeta::OldDateTime => Self::DateTime(MyDateTime::from_legacy_binlog(input)?),
             // year in binary log is stored as tiny(single-byte int)
             // the real year should be the number plus offset 1900
-            // except 0 (which means 0 in MySQL)
+            // except 0 (which means 0 in MySQL).
+            // YEAR(2) before 5.7.5 stores the same number, e.g. 69 as
+            // 2069, so the full year is recovered regardless of width
             ColumnMeta::Year => {
                 let n = input.read_u8()?;
                 let y = if n == 0 { 0 } else { (n as u16) + 1900 };
@@ -877,8 +973,7 @@ impl BinlogColumnValue {
             // Json,
             ColumnMeta::NewDecimal { prec, frac } => {
                 // https://github.com/mysql/mysql-server/blob/5.7/strings/decimal.c#L1273
-                debug_assert!(prec >= frac);
-                let intg = prec - frac;
+                let intg = checked_sub_len(*prec as usize, *frac as usize)? as u8;
                 let d = MyDecimal::read_from(input, intg, *frac)?;
                 BinlogColumnValue::NewDecimal(d)
             }
@@ -898,7 +993,19 @@ impl BinlogColumnValue {
                 };
                 BinlogColumnValue::Enum(me)
             }
-            // Set,
+            ColumnMeta::Set { pack_len } => {
+                if *pack_len == 0 || *pack_len > 8 {
+                    return Err(Error::ConstraintError(format!(
+                        "invalid length of set: {}",
+                        pack_len
+                    )));
+                }
+                let mut bits = 0u64;
+                for i in 0..*pack_len {
+                    bits |= (input.read_u8()? as u64) << (i * 8);
+                }
+                BinlogColumnValue::Set(bits)
+            }
             // TinyBlob,
             // MediumBlob,
             // LongBlob,
@@ -914,7 +1021,12 @@ impl BinlogColumnValue {
                     3 => input.read_le_u24()?,
                     // longblob, longtext
                     4 => input.read_le_u32()?,
-                    _ => unreachable!("unexpected blob length {}", *pack_len),
+                    _ => {
+                        return Err(Error::ConstraintError(format!(
+                            "invalid length of blob: {}",
+                            pack_len
+                        )))
+                    }
                 };
                 let bs = input.read_len(len as usize)?;
                 BinlogColumnValue::Blob(bs)
@@ -944,12 +1056,68 @@ impl BinlogColumnValue {
         };
         Ok(col_val)
     }
+
+    /// skip bytes of value without materializing it
+    pub fn skip(input: &mut Bytes, col_meta: &ColumnMeta) -> Result<()> {
+        let len = match col_meta {
+            ColumnMeta::Null => 0,
+            ColumnMeta::Tiny | ColumnMeta::Year => 1,
+            ColumnMeta::Short => 2,
+            ColumnMeta::Int24 | ColumnMeta::Date | ColumnMeta::Time => 3,
+            ColumnMeta::Long
+            | ColumnMeta::Float { .. }
+            | ColumnMeta::Timestamp { .. }
+            | ColumnMeta::OldTimestamp => 4,
+            ColumnMeta::LongLong | ColumnMeta::Double { .. } | ColumnMeta::OldDateTime => 8,
+            ColumnMeta::Time2 { frac } => 3 + (*frac as usize).div_ceil(2),
+            ColumnMeta::DateTime { frac } => 5 + (*frac as usize).div_ceil(2),
+            ColumnMeta::Bit { bits, bytes } => *bytes as usize + if *bits > 0 { 1 } else { 0 },
+            ColumnMeta::NewDecimal { prec, frac } => {
+                let intg = checked_sub_len(*prec as usize, *frac as usize)? as u8;
+                MyDecimal::bin_size(intg, *frac)
+            }
+            ColumnMeta::Enum { pack_len } | ColumnMeta::Set { pack_len } => *pack_len as usize,
+            ColumnMeta::Blob { pack_len } | ColumnMeta::Geometry { pack_len } => match *pack_len {
+                1 => input.read_u8()? as usize,
+                2 => input.read_le_u16()? as usize,
+                3 => input.read_le_u24()? as usize,
+                4 => input.read_le_u32()? as usize,
+                _ => {
+                    return Err(Error::ConstraintError(format!(
+                        "invalid length of blob: {}",
+                        pack_len
+                    )))
+                }
+            },
+            ColumnMeta::VarString { max_len } => {
+                if *max_len < 256 {
+                    input.read_u8()? as usize
+                } else {
+                    input.read_le_u16()? as usize
+                }
+            }
+            ColumnMeta::String { from_len } => {
+                if *from_len > 0xff {
+                    input.read_le_u16()? as usize
+                } else {
+                    input.read_u8()? as usize
+                }
+            }
+            ColumnMeta::Decimal => {
+                return Err(Error::ConstraintError(
+                    "unsupported column type: decimal".to_owned(),
+                ))
+            }
+        };
+        input.read_len(len)?;
+        Ok(())
+    }
 }
 
 /// Column definition
 ///
 /// reference: https://dev.mysql.com/doc/internals/en/com-query-response.html
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ColumnDefinition {
     // len-enc-str
     pub catalog: SmolStr,
@@ -1026,6 +1194,118 @@ impl ColumnDefinition {
             default_values,
         })
     }
+
+    /// column computed by expression, e.g. SELECT 1, with charset,
+    /// flags and length as server sets by type
+    pub fn new<T: Into<SmolStr>>(name: T, col_type: ColumnType) -> Self {
+        let (charset, col_len, flags, decimals) = match col_type {
+            ColumnType::Tiny => (BINARY_CHARSET, 4, ColumnFlags::NUM, 0),
+            ColumnType::Short => (BINARY_CHARSET, 6, ColumnFlags::NUM, 0),
+            ColumnType::Int24 => (BINARY_CHARSET, 9, ColumnFlags::NUM, 0),
+            ColumnType::Long => (BINARY_CHARSET, 11, ColumnFlags::NUM, 0),
+            ColumnType::LongLong => (BINARY_CHARSET, 20, ColumnFlags::NUM, 0),
+            ColumnType::Year => (
+                BINARY_CHARSET,
+                4,
+                ColumnFlags::NUM | ColumnFlags::ZEROFILL,
+                0,
+            ),
+            ColumnType::Float => (BINARY_CHARSET, 12, ColumnFlags::NUM, 0x1f),
+            ColumnType::Double => (BINARY_CHARSET, 22, ColumnFlags::NUM, 0x1f),
+            ColumnType::Decimal | ColumnType::NewDecimal => {
+                (BINARY_CHARSET, 67, ColumnFlags::NUM, 30)
+            }
+            ColumnType::Date => (BINARY_CHARSET, 10, ColumnFlags::empty(), 0),
+            ColumnType::Time | ColumnType::Time2 => (BINARY_CHARSET, 10, ColumnFlags::empty(), 0),
+            ColumnType::DateTime | ColumnType::DateTime2 => {
+                (BINARY_CHARSET, 19, ColumnFlags::empty(), 0)
+            }
+            ColumnType::Timestamp | ColumnType::Timestamp2 => {
+                (BINARY_CHARSET, 19, ColumnFlags::TIMESTAMP, 0)
+            }
+            ColumnType::Bit => (BINARY_CHARSET, 1, ColumnFlags::UNSIGNED, 0),
+            ColumnType::Geometry => (BINARY_CHARSET, 0xffff_ffff, ColumnFlags::BLOB, 0),
+            ColumnType::Null => (BINARY_CHARSET, 0, ColumnFlags::empty(), 0),
+            ColumnType::TinyBlob
+            | ColumnType::MediumBlob
+            | ColumnType::LongBlob
+            | ColumnType::Blob => (DEFAULT_CHARSET, 0xffff_ffff, ColumnFlags::BLOB, 0),
+            ColumnType::Varchar | ColumnType::VarString | ColumnType::String => {
+                (DEFAULT_CHARSET, 0xffff, ColumnFlags::empty(), 0x1f)
+            }
+        };
+        // server marks columns of binary charset with BINARY flag
+        let flags = if charset == BINARY_CHARSET {
+            flags | ColumnFlags::BINARY
+        } else {
+            flags
+        };
+        ColumnDefinition {
+            catalog: SmolStr::new("def"),
+            schema: SmolStr::default(),
+            table: SmolStr::default(),
+            org_table: SmolStr::default(),
+            name: name.into(),
+            org_name: SmolStr::default(),
+            charset,
+            col_len,
+            col_type,
+            flags,
+            decimals,
+            default_values: SmolStr::default(),
+        }
+    }
+}
+
+/// binary, used by numeric and temporal columns
+const BINARY_CHARSET: u16 = 63;
+/// utf8_general_ci, as handshake of client
+const DEFAULT_CHARSET: u16 = 33;
+
+impl WriteToBytes for ColumnDefinition {
+    fn write_to(self, out: &mut BytesMut) -> Result<usize> {
+        self.write_with_ctx(out, false)
+    }
+}
+
+/// context tells whether it's a response of COM_FIELD_LIST,
+/// which carries default values
+///
+/// default values are written as NULL if empty, as reading does
+/// not distinguish NULL from empty string
+impl<'c> WriteToBytesWithContext<'c> for ColumnDefinition {
+    type Context = bool;
+
+    fn write_with_ctx(self, out: &mut BytesMut, field_list: bool) -> Result<usize> {
+        let mut len = 0;
+        for s in [
+            self.catalog,
+            self.schema,
+            self.table,
+            self.org_table,
+            self.name,
+            self.org_name,
+        ] {
+            len += out.write_bytes(LenEncStr::Bytes(Bytes::copy_from_slice(s.as_bytes())))?;
+        }
+        // length of fixed fields
+        len += out.write_u8(0x0c)?;
+        len += out.write_le_u16(self.charset)?;
+        len += out.write_le_u32(self.col_len)?;
+        len += out.write_u8(u8::from(self.col_type))?;
+        len += out.write_le_u16(self.flags.bits())?;
+        len += out.write_u8(self.decimals)?;
+        len += out.write_le_u16(0)?;
+        if field_list {
+            let default_values = if self.default_values.is_empty() {
+                LenEncStr::Null
+            } else {
+                LenEncStr::Bytes(Bytes::copy_from_slice(self.default_values.as_bytes()))
+            };
+            len += out.write_bytes(default_values)?;
+        }
+        Ok(len)
+    }
 }
 
 bitflags! {
@@ -1057,11 +1337,33 @@ bitflags! {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::stmt::StmtColumnValue;
     use chrono::NaiveDate;
 
+    /// column of table db1.t1, fields not given are as INT column
+    pub(crate) fn col_def(
+        name: &str,
+        col_type: ColumnType,
+        flags: ColumnFlags,
+    ) -> ColumnDefinition {
+        ColumnDefinition {
+            catalog: SmolStr::new("def"),
+            schema: SmolStr::new("db1"),
+            table: SmolStr::new("t1"),
+            org_table: SmolStr::new("t1"),
+            name: SmolStr::new(name),
+            org_name: SmolStr::new(name),
+            charset: 33,
+            col_len: 11,
+            col_type,
+            flags,
+            decimals: 0,
+            default_values: SmolStr::default(),
+        }
+    }
+
     #[test]
     fn test_read_binlog_int24_negative() {
         let input = vec![78, 160, 254];
@@ -1102,6 +1404,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_binlog_set() {
+        // real type 0xf8 and pack length 2 in meta of STRING column
+        let meta =
+            ColumnMeta::read_from(&mut Bytes::from_static(&[0xf8, 2]), ColumnType::String).unwrap();
+        assert!(matches!(meta, ColumnMeta::Set { pack_len: 2 }));
+        let mut input = Bytes::from_static(&[0x05, 0x01]);
+        let bin_val = BinlogColumnValue::read_from(&mut input, &meta).unwrap();
+        assert_eq!(BinlogColumnValue::Set(0x0105), bin_val);
+        assert!(input.is_empty());
+    }
+
     #[test]
     fn test_read_binlog_datetime0() {
         let input = vec![153_u8, 165, 66, 16, 131];
@@ -1296,6 +1610,98 @@ mod tests {
         assert_eq!(BinlogColumnValue::Year(2155), bin_val);
     }
 
+    #[test]
+    fn read_binlog_legacy_temporal() {
+        assert_eq!(
+            TemporalFormat::Legacy,
+            TemporalFormat::from_server_version("5.5.62-log")
+        );
+        assert_eq!(
+            TemporalFormat::Mixed,
+            TemporalFormat::from_server_version("5.6.4")
+        );
+        assert_eq!(
+            TemporalFormat::Current,
+            TemporalFormat::from_server_version("8.0.22")
+        );
+        assert_eq!(
+            TemporalFormat::Mixed,
+            TemporalFormat::from_server_version("10.3.27-MariaDB-log")
+        );
+        assert_eq!(
+            TemporalFormat::Mixed,
+            TemporalFormat::from_server_version("")
+        );
+
+        // TIMESTAMP, TIME, DATETIME, YEAR, all without metadata
+        let col_defs = [0x07, 0x0b, 0x0c, 0x0d];
+        let metas =
+            ColumnMetas::read_with_format(&mut Bytes::new(), 4, &col_defs, TemporalFormat::Legacy)
+                .unwrap();
+        assert!(matches!(
+            &metas[..],
+            [
+                ColumnMeta::OldTimestamp,
+                ColumnMeta::Time,
+                ColumnMeta::OldDateTime,
+                ColumnMeta::Year
+            ]
+        ));
+        assert!(ColumnMetas::read_with_format(
+            &mut Bytes::new(),
+            4,
+            &col_defs,
+            TemporalFormat::Current
+        )
+        .is_err());
+        assert!(ColumnMetas::read_with_format(
+            &mut Bytes::from_static(b"\x00"),
+            1,
+            &[0x11],
+            TemporalFormat::Legacy
+        )
+        .is_err());
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&1604290466u32.to_le_bytes());
+        // -838:59:59
+        buf.extend_from_slice(&(-8385959i32).to_le_bytes()[..3]);
+        buf.extend_from_slice(&20201102041426u64.to_le_bytes());
+        // YEAR(2) of 69
+        buf.extend_from_slice(&[169]);
+        let mut input = buf.freeze();
+        let vals: Vec<_> = metas
+            .iter()
+            .map(|m| BinlogColumnValue::read_from(&mut input, m).unwrap())
+            .collect();
+        assert!(input.is_empty());
+        assert_eq!(BinlogColumnValue::Timestamp(1604290466), vals[0]);
+        assert_eq!(
+            BinlogColumnValue::Time(MyTime {
+                negative: true,
+                days: 34,
+                hour: 22,
+                minute: 59,
+                second: 59,
+                micro_second: 0,
+            }),
+            vals[1]
+        );
+        assert_eq!(
+            BinlogColumnValue::DateTime(MyDateTime {
+                year: 2020,
+                month: 11,
+                day: 2,
+                hour: 4,
+                minute: 14,
+                second: 26,
+                micro_second: 0,
+            }),
+            vals[2]
+        );
+        assert_eq!(BinlogColumnValue::Year(2069), vals[3]);
+    }
+
     #[test]
     fn read_binlog_timestamp() {
         let input = vec![95u8, 159, 135, 162];
@@ -1365,4 +1771,48 @@ mod tests {
         let output = BinaryColumnValue::read_from(&mut input, ColumnType::DateTime2).unwrap();
         assert_eq!(tm, output);
     }
+
+    #[test]
+    fn test_column_definition_round_trip() {
+        // SELECT 1 on MySQL 5.7
+        let packet: &[u8] = &[
+            0x03, 0x64, 0x65, 0x66, 0x00, 0x00, 0x00, 0x01, 0x31, 0x00, 0x0c, 0x3f, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x08, 0x81, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let col_def = ColumnDefinition::read_from(&mut Bytes::from_static(packet), false).unwrap();
+        assert_eq!("1", col_def.name);
+        assert_eq!(ColumnType::LongLong, col_def.col_type);
+        let mut out = BytesMut::new();
+        let len = col_def.write_to(&mut out).unwrap();
+        assert_eq!(packet.len(), len);
+        assert_eq!(packet, &out[..]);
+
+        // COM_FIELD_LIST of mysql.help_topic, name column without default
+        let packet: &[u8] = &[
+            0x03, 0x64, 0x65, 0x66, 0x05, 0x6d, 0x79, 0x73, 0x71, 0x6c, 0x0a, 0x68, 0x65, 0x6c,
+            0x70, 0x5f, 0x74, 0x6f, 0x70, 0x69, 0x63, 0x0a, 0x68, 0x65, 0x6c, 0x70, 0x5f, 0x74,
+            0x6f, 0x70, 0x69, 0x63, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x04, 0x6e, 0x61, 0x6d, 0x65,
+            0x0c, 0x21, 0x00, 0xc0, 0x00, 0x00, 0x00, 0xfe, 0x05, 0x50, 0x00, 0x00, 0x00, 0xfb,
+        ];
+        let col_def = ColumnDefinition::read_from(&mut Bytes::from_static(packet), true).unwrap();
+        assert_eq!("help_topic", col_def.org_table);
+        assert_eq!(ColumnType::String, col_def.col_type);
+        let mut out = BytesMut::new();
+        col_def.write_with_ctx(&mut out, true).unwrap();
+        assert_eq!(packet, &out[..]);
+
+        let col_def = ColumnDefinition::new("c1", ColumnType::Long);
+        assert_eq!(63, col_def.charset);
+        assert!(col_def
+            .flags
+            .contains(ColumnFlags::BINARY | ColumnFlags::NUM));
+        let mut out = BytesMut::new();
+        col_def.clone().write_to(&mut out).unwrap();
+        let output = ColumnDefinition::read_from(&mut out.freeze(), false).unwrap();
+        assert_eq!(col_def, output);
+        assert_eq!(
+            33,
+            ColumnDefinition::new("c2", ColumnType::VarString).charset
+        );
+    }
 }
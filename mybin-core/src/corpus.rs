@@ -0,0 +1,883 @@
+//! synthetic binlog corpus for fuzzing, benchmarks and tests
+//!
+//! binlog files are generated from a seed, so the same builder always
+//! produces the same bytes, without a live server. generated files
+//! are in v4 format of MySQL 5.7, and contain transactions of row
+//! events on random tables, optionally mixed with DDL statements.
+//!
+//! corruption can be injected into events after the file header,
+//! each injected corruption is recorded so tests know where parsing
+//! is expected to fail.
+use crate::binlog::LogEventType;
+use crate::decimal::MyDecimal;
+use crate::error::Result;
+use crate::util::checksum_crc32;
+use bytes::{BufMut, BytesMut};
+use bytes_parser::my::LenEncInt;
+use bytes_parser::WriteToBytes;
+use std::path::{Path, PathBuf};
+
+const BINLOG_MAGIC: &[u8] = b"\xfebin";
+const HEADER_LEN: usize = 19;
+const SERVER_VERSION: &str = "5.7.30-log";
+/// post header lengths of MySQL 5.7
+const POST_HEADER_LENGTHS: [u8; 38] = [
+    56, 13, 0, 8, 0, 18, 0, 4, 4, 4, 4, 18, 0, 0, 95, 0, 4, 26, 8, 0, 0, 0, 8, 8, 8, 2, 0, 0, 0,
+    10, 10, 10, 42, 42, 0, 18, 52, 0,
+];
+/// flag of last rows event of a statement
+const STMT_END_F: u16 = 0x0001;
+
+/// column types of generated tables
+///
+/// the first column of each table is always a BIGINT primary key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorpusColumn {
+    Tiny,
+    Long,
+    LongLong,
+    Double,
+    Varchar(u16),
+    Blob,
+    DateTime,
+    Decimal(u8, u8),
+}
+
+impl CorpusColumn {
+    fn type_code(self) -> u8 {
+        match self {
+            CorpusColumn::Tiny => 0x01,
+            CorpusColumn::Long => 0x03,
+            CorpusColumn::LongLong => 0x08,
+            CorpusColumn::Double => 0x05,
+            CorpusColumn::Varchar(_) => 0x0f,
+            CorpusColumn::Blob => 0xfc,
+            CorpusColumn::DateTime => 0x12,
+            CorpusColumn::Decimal(..) => 0xf6,
+        }
+    }
+
+    fn put_meta(self, out: &mut BytesMut) {
+        match self {
+            CorpusColumn::Double => out.put_u8(8),
+            CorpusColumn::Varchar(max_len) => out.put_u16_le(max_len),
+            CorpusColumn::Blob => out.put_u8(2),
+            CorpusColumn::DateTime => out.put_u8(0),
+            CorpusColumn::Decimal(prec, scale) => {
+                out.put_u8(prec);
+                out.put_u8(scale);
+            }
+            _ => (),
+        }
+    }
+
+    fn sql_type(self) -> String {
+        match self {
+            CorpusColumn::Tiny => "TINYINT".to_owned(),
+            CorpusColumn::Long => "INT".to_owned(),
+            CorpusColumn::LongLong => "BIGINT".to_owned(),
+            CorpusColumn::Double => "DOUBLE".to_owned(),
+            CorpusColumn::Varchar(max_len) => format!("VARBINARY({})", max_len),
+            CorpusColumn::Blob => "BLOB".to_owned(),
+            CorpusColumn::DateTime => "DATETIME".to_owned(),
+            CorpusColumn::Decimal(prec, scale) => format!("DECIMAL({},{})", prec, scale),
+        }
+    }
+
+    /// random value in binlog format
+    fn put_value(self, rng: &mut SplitMix64, out: &mut BytesMut) -> Result<()> {
+        match self {
+            CorpusColumn::Tiny => out.put_u8(rng.next_u64() as u8),
+            CorpusColumn::Long => out.put_u32_le(rng.next_u64() as u32),
+            CorpusColumn::LongLong => out.put_u64_le(rng.next_u64()),
+            CorpusColumn::Double => out.put_f64_le(rng.next_u64() as f64 / 1024.0),
+            CorpusColumn::Varchar(max_len) => {
+                let len = rng.range(0, max_len.min(64) as u64) as usize;
+                if max_len < 256 {
+                    out.put_u8(len as u8);
+                } else {
+                    out.put_u16_le(len as u16);
+                }
+                out.put_slice(&rng.ascii(len));
+            }
+            CorpusColumn::Blob => {
+                let len = rng.range(0, 256) as usize;
+                out.put_u16_le(len as u16);
+                out.put_slice(&rng.ascii(len));
+            }
+            CorpusColumn::DateTime => {
+                let ym = rng.range(1970, 2038) * 13 + rng.range(1, 12);
+                let ymd = (ym << 5) | rng.range(1, 28);
+                let hms = (rng.range(0, 23) << 12) | (rng.range(0, 59) << 6) | rng.range(0, 59);
+                let packed = ((ymd << 17) | hms) + 0x80_0000_0000;
+                out.put_slice(&packed.to_be_bytes()[3..]);
+            }
+            CorpusColumn::Decimal(prec, scale) => {
+                let intg = (prec - scale).min(18) as u32;
+                let int_part = rng.range(0, 10u64.pow(intg) - 1);
+                let s = if scale > 0 {
+                    let frac = rng.range(0, 10u64.pow(scale.min(18) as u32) - 1);
+                    format!("{}.{:0width$}", int_part, frac, width = scale as usize)
+                } else {
+                    int_part.to_string()
+                };
+                MyDecimal::parse(&s, prec, scale)?.write_to(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// relative weights of transaction kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventMix {
+    pub insert: u32,
+    pub update: u32,
+    pub delete: u32,
+    /// standalone DDL statement instead of row transaction
+    pub ddl: u32,
+}
+
+impl Default for EventMix {
+    fn default() -> Self {
+        EventMix {
+            insert: 6,
+            update: 3,
+            delete: 1,
+            ddl: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptionKind {
+    /// flip a byte in event body
+    FlipByte,
+    /// alter crc32 checksum, only injected if checksum is enabled
+    BadChecksum,
+    /// cut the event in half and end the corpus
+    Truncate,
+}
+
+/// corruption injected into the corpus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    pub file: String,
+    /// start position of the corrupted event
+    pub offset: u64,
+    pub kind: CorruptionKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct CorpusFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    pub files: Vec<CorpusFile>,
+    pub corruptions: Vec<Corruption>,
+    /// number of generated transactions, including DDL
+    pub n_trxs: usize,
+}
+
+impl Corpus {
+    /// write binlog files and index file, returns paths of binlog files
+    pub fn write_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut paths = vec![];
+        let mut index = String::new();
+        for file in &self.files {
+            let path = dir.join(&file.name);
+            std::fs::write(&path, &file.data)?;
+            index.push_str(&format!("./{}\n", file.name));
+            paths.push(path);
+        }
+        if let Some(basename) = self.files.first().and_then(|f| f.name.rsplit_once('.')) {
+            std::fs::write(dir.join(format!("{}.index", basename.0)), index)?;
+        }
+        Ok(paths)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Table {
+    id: u64,
+    name: String,
+    cols: Vec<CorpusColumn>,
+    /// next primary key
+    next_pk: u64,
+}
+
+/// builder of synthetic binlog corpus
+#[derive(Debug, Clone)]
+pub struct CorpusBuilder {
+    seed: u64,
+    n_trxs: usize,
+    basename: String,
+    server_id: u32,
+    start_timestamp: u32,
+    checksum: bool,
+    gtid: bool,
+    schema: String,
+    n_tables: usize,
+    table_width: (usize, usize),
+    col_types: Vec<CorpusColumn>,
+    null_ratio: f64,
+    events_per_trx: (usize, usize),
+    rows_per_event: (usize, usize),
+    event_mix: EventMix,
+    max_file_size: Option<usize>,
+    corruption: Option<(CorruptionKind, f64)>,
+}
+
+impl CorpusBuilder {
+    pub fn new(seed: u64) -> Self {
+        CorpusBuilder {
+            seed,
+            n_trxs: 100,
+            basename: "mysql-bin".to_owned(),
+            server_id: 1,
+            start_timestamp: 1_600_000_000,
+            checksum: true,
+            gtid: true,
+            schema: "corpus".to_owned(),
+            n_tables: 4,
+            table_width: (2, 8),
+            col_types: vec![
+                CorpusColumn::Tiny,
+                CorpusColumn::Long,
+                CorpusColumn::LongLong,
+                CorpusColumn::Double,
+                CorpusColumn::Varchar(255),
+                CorpusColumn::Blob,
+                CorpusColumn::DateTime,
+                CorpusColumn::Decimal(10, 2),
+            ],
+            null_ratio: 0.1,
+            events_per_trx: (1, 3),
+            rows_per_event: (1, 10),
+            event_mix: EventMix::default(),
+            max_file_size: None,
+            corruption: None,
+        }
+    }
+
+    pub fn transactions(mut self, n_trxs: usize) -> Self {
+        self.n_trxs = n_trxs;
+        self
+    }
+
+    /// files are named as basename.000001, basename.000002, ...
+    pub fn basename<T: Into<String>>(mut self, basename: T) -> Self {
+        self.basename = basename.into();
+        self
+    }
+
+    pub fn server_id(mut self, server_id: u32) -> Self {
+        self.server_id = server_id;
+        self
+    }
+
+    /// timestamp of first event, increased by one per transaction
+    pub fn start_timestamp(mut self, start_timestamp: u32) -> Self {
+        self.start_timestamp = start_timestamp;
+        self
+    }
+
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// GtidLogEvent if true, otherwise AnonymousGtidLogEvent
+    pub fn gtid(mut self, gtid: bool) -> Self {
+        self.gtid = gtid;
+        self
+    }
+
+    pub fn tables(mut self, n_tables: usize) -> Self {
+        self.n_tables = n_tables.max(1);
+        self
+    }
+
+    /// number of columns of each table, including primary key
+    pub fn table_width(mut self, min: usize, max: usize) -> Self {
+        self.table_width = (min.max(1), max.max(min).max(1));
+        self
+    }
+
+    /// types of non-key columns are picked from given types
+    pub fn column_types(mut self, col_types: Vec<CorpusColumn>) -> Self {
+        if !col_types.is_empty() {
+            self.col_types = col_types;
+        }
+        self
+    }
+
+    /// ratio of null values in non-key columns
+    pub fn null_ratio(mut self, null_ratio: f64) -> Self {
+        self.null_ratio = null_ratio;
+        self
+    }
+
+    /// number of rows events in each transaction
+    pub fn events_per_trx(mut self, min: usize, max: usize) -> Self {
+        self.events_per_trx = (min.max(1), max.max(min).max(1));
+        self
+    }
+
+    pub fn rows_per_event(mut self, min: usize, max: usize) -> Self {
+        self.rows_per_event = (min.max(1), max.max(min).max(1));
+        self
+    }
+
+    pub fn event_mix(mut self, event_mix: EventMix) -> Self {
+        self.event_mix = event_mix;
+        self
+    }
+
+    /// rotate to next file once size is exceeded, checked
+    /// at transaction boundary
+    pub fn max_file_size(mut self, max_file_size: usize) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// inject corruption of given kind into events with given rate
+    pub fn corruption(mut self, kind: CorruptionKind, rate: f64) -> Self {
+        self.corruption = Some((kind, rate));
+        self
+    }
+
+    pub fn build(self) -> Result<Corpus> {
+        let mut gen = Generator::new(&self);
+        gen.run(&self)?;
+        Ok(gen.finish())
+    }
+}
+
+/// deterministic random number generator, independent of
+/// versions of external crates
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// inclusive range
+    fn range(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        min + self.next_u64() % (max - min + 1)
+    }
+
+    fn chance(&mut self, ratio: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < ratio
+    }
+
+    fn ascii(&mut self, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| b'a' + (self.next_u64() % 26) as u8)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TrxKind {
+    Insert,
+    Update,
+    Delete,
+    Ddl,
+}
+
+struct Generator {
+    rng: SplitMix64,
+    tables: Vec<Table>,
+    sid: u128,
+    next_gno: u64,
+    files: Vec<CorpusFile>,
+    current: BytesMut,
+    corruptions: Vec<Corruption>,
+    truncated: bool,
+    n_trxs: usize,
+    timestamp: u32,
+}
+
+impl Generator {
+    fn new(opts: &CorpusBuilder) -> Self {
+        let mut rng = SplitMix64(opts.seed);
+        let sid = (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+        let tables = (0..opts.n_tables)
+            .map(|i| {
+                let width = rng.range(opts.table_width.0 as u64, opts.table_width.1 as u64);
+                let mut cols = vec![CorpusColumn::LongLong];
+                for _ in 1..width {
+                    let idx = rng.range(0, opts.col_types.len() as u64 - 1) as usize;
+                    cols.push(opts.col_types[idx]);
+                }
+                Table {
+                    id: 100 + i as u64,
+                    name: format!("t{}", i + 1),
+                    cols,
+                    next_pk: 1,
+                }
+            })
+            .collect();
+        Generator {
+            rng,
+            tables,
+            sid,
+            next_gno: 1,
+            files: vec![],
+            current: BytesMut::new(),
+            corruptions: vec![],
+            truncated: false,
+            n_trxs: 0,
+            timestamp: opts.start_timestamp,
+        }
+    }
+
+    fn run(&mut self, opts: &CorpusBuilder) -> Result<()> {
+        self.start_file(opts);
+        for _ in 0..opts.n_trxs {
+            if self.truncated {
+                break;
+            }
+            self.write_trx(opts)?;
+            self.n_trxs += 1;
+            self.timestamp = self.timestamp.wrapping_add(1);
+            if let Some(max_file_size) = opts.max_file_size {
+                if self.current.len() >= max_file_size && !self.truncated {
+                    let next = file_name(&opts.basename, self.files.len() + 2);
+                    let mut body = BytesMut::new();
+                    body.put_u64_le(4);
+                    body.put_slice(next.as_bytes());
+                    self.put_event(opts, LogEventType::RotateEvent, &body, false);
+                    self.start_file(opts);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Corpus {
+        self.close_file();
+        Corpus {
+            files: self.files,
+            corruptions: self.corruptions,
+            n_trxs: self.n_trxs,
+        }
+    }
+
+    fn close_file(&mut self) {
+        if let Some(file) = self.files.last_mut() {
+            file.data = self.current.split().to_vec();
+        }
+    }
+
+    fn start_file(&mut self, opts: &CorpusBuilder) {
+        self.close_file();
+        self.files.push(CorpusFile {
+            name: file_name(&opts.basename, self.files.len() + 1),
+            data: vec![],
+        });
+        self.current.put_slice(BINLOG_MAGIC);
+        // format description event
+        let mut body = BytesMut::new();
+        body.put_u16_le(4);
+        let mut version = [0u8; 50];
+        version[..SERVER_VERSION.len()].copy_from_slice(SERVER_VERSION.as_bytes());
+        body.put_slice(&version);
+        body.put_u32_le(self.timestamp);
+        body.put_u8(HEADER_LEN as u8);
+        body.put_slice(&POST_HEADER_LENGTHS);
+        body.put_u8(opts.checksum as u8);
+        self.put_event(opts, LogEventType::FormatDescriptionEvent, &body, false);
+        // previous gtids event, gtids of earlier files
+        let mut body = BytesMut::new();
+        if opts.gtid && self.next_gno > 1 {
+            body.put_u64_le(1);
+            body.put_u128_le(self.sid);
+            body.put_u64_le(1);
+            body.put_u64_le(1);
+            body.put_u64_le(self.next_gno);
+        } else {
+            body.put_u64_le(0);
+        }
+        self.put_event(opts, LogEventType::PreviousGtidsLogEvent, &body, false);
+    }
+
+    fn pick_kind(&mut self, mix: &EventMix) -> TrxKind {
+        let total = mix.insert + mix.update + mix.delete + mix.ddl;
+        if total == 0 {
+            return TrxKind::Insert;
+        }
+        let n = self.rng.range(0, total as u64 - 1) as u32;
+        if n < mix.insert {
+            TrxKind::Insert
+        } else if n < mix.insert + mix.update {
+            TrxKind::Update
+        } else if n < mix.insert + mix.update + mix.delete {
+            TrxKind::Delete
+        } else {
+            TrxKind::Ddl
+        }
+    }
+
+    fn write_trx(&mut self, opts: &CorpusBuilder) -> Result<()> {
+        let kind = self.pick_kind(&opts.event_mix);
+        self.write_gtid(opts);
+        if let TrxKind::Ddl = kind {
+            let idx = self.rng.range(0, self.tables.len() as u64 - 1) as usize;
+            let stmt = create_table_stmt(&self.tables[idx]);
+            let body = query_body(&opts.schema, &stmt);
+            self.put_event(opts, LogEventType::QueryEvent, &body, true);
+            return Ok(());
+        }
+        let body = query_body(&opts.schema, "BEGIN");
+        self.put_event(opts, LogEventType::QueryEvent, &body, true);
+        let n_events = self
+            .rng
+            .range(opts.events_per_trx.0 as u64, opts.events_per_trx.1 as u64);
+        for i in 0..n_events {
+            let idx = self.rng.range(0, self.tables.len() as u64 - 1) as usize;
+            let table_map = table_map_body(&opts.schema, &self.tables[idx])?;
+            self.put_event(opts, LogEventType::TableMapEvent, &table_map, true);
+            let (event_type, body) = self.rows_body(opts, kind, idx, i + 1 == n_events)?;
+            self.put_event(opts, event_type, &body, true);
+        }
+        let xid = self.rng.next_u64();
+        self.put_event(opts, LogEventType::XidEvent, &xid.to_le_bytes(), true);
+        Ok(())
+    }
+
+    fn write_gtid(&mut self, opts: &CorpusBuilder) {
+        let mut body = BytesMut::new();
+        let event_type = if opts.gtid {
+            body.put_u8(1);
+            body.put_u128_le(self.sid);
+            body.put_u64_le(self.next_gno);
+            self.next_gno += 1;
+            LogEventType::GtidLogEvent
+        } else {
+            body.put_u8(1);
+            body.put_u128_le(0);
+            body.put_u64_le(0);
+            LogEventType::AnonymousGtidLogEvent
+        };
+        // logical timestamp
+        body.put_u8(2);
+        body.put_u64_le(self.n_trxs as u64);
+        body.put_u64_le(self.n_trxs as u64 + 1);
+        self.put_event(opts, event_type, &body, true);
+    }
+
+    fn rows_body(
+        &mut self,
+        opts: &CorpusBuilder,
+        kind: TrxKind,
+        table_idx: usize,
+        stmt_end: bool,
+    ) -> Result<(LogEventType, BytesMut)> {
+        let n_rows = self
+            .rng
+            .range(opts.rows_per_event.0 as u64, opts.rows_per_event.1 as u64);
+        let cols = self.tables[table_idx].cols.clone();
+        let n_cols = cols.len();
+        let mut body = BytesMut::new();
+        body.put_uint_le(self.tables[table_idx].id, 6);
+        body.put_u16_le(if stmt_end { STMT_END_F } else { 0 });
+        // extra data length including itself
+        body.put_u16_le(2);
+        LenEncInt::from(n_cols as u64).write_to(&mut body)?;
+        let bitmap = vec![0xffu8; n_cols.div_ceil(8)];
+        body.put_slice(&bitmap);
+        if let TrxKind::Update = kind {
+            body.put_slice(&bitmap);
+        }
+        for _ in 0..n_rows {
+            let next_pk = &mut self.tables[table_idx].next_pk;
+            let pk = match kind {
+                TrxKind::Insert => {
+                    *next_pk += 1;
+                    *next_pk - 1
+                }
+                _ => {
+                    let max = *next_pk;
+                    self.rng.range(1, max)
+                }
+            };
+            self.put_row(opts, &cols, pk, &mut body)?;
+            if let TrxKind::Update = kind {
+                self.put_row(opts, &cols, pk, &mut body)?;
+            }
+        }
+        let event_type = match kind {
+            TrxKind::Insert => LogEventType::WriteRowsEventV2,
+            TrxKind::Update => LogEventType::UpdateRowsEventV2,
+            _ => LogEventType::DeleteRowsEventV2,
+        };
+        Ok((event_type, body))
+    }
+
+    fn put_row(
+        &mut self,
+        opts: &CorpusBuilder,
+        cols: &[CorpusColumn],
+        pk: u64,
+        out: &mut BytesMut,
+    ) -> Result<()> {
+        let nulls: Vec<bool> = (0..cols.len())
+            .map(|i| i > 0 && self.rng.chance(opts.null_ratio))
+            .collect();
+        out.put_slice(&crate::bitmap::from_iter(nulls.iter().cloned(), 0));
+        out.put_u64_le(pk);
+        for (col, null) in cols.iter().zip(nulls).skip(1) {
+            if !null {
+                col.put_value(&mut self.rng, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// append event with header and checksum, and inject
+    /// corruption if allowed
+    fn put_event(
+        &mut self,
+        opts: &CorpusBuilder,
+        event_type: LogEventType,
+        body: &[u8],
+        corruptible: bool,
+    ) {
+        let checksum_len = if opts.checksum { 4 } else { 0 };
+        let offset = self.current.len();
+        let event_len = HEADER_LEN + body.len() + checksum_len;
+        let mut event = BytesMut::with_capacity(event_len);
+        event.put_u32_le(self.timestamp);
+        event.put_u8(event_type.into());
+        event.put_u32_le(opts.server_id);
+        event.put_u32_le(event_len as u32);
+        event.put_u32_le((offset + event_len) as u32);
+        event.put_u16_le(0);
+        event.put_slice(body);
+        if opts.checksum {
+            let crc32 = checksum_crc32(&event);
+            event.put_u32_le(crc32);
+        }
+        if corruptible && !self.truncated {
+            if let Some((kind, rate)) = opts.corruption {
+                if self.rng.chance(rate) && self.corrupt(&mut event, kind, opts.checksum) {
+                    self.corruptions.push(Corruption {
+                        file: self
+                            .files
+                            .last()
+                            .map(|f| f.name.clone())
+                            .unwrap_or_default(),
+                        offset: offset as u64,
+                        kind,
+                    });
+                }
+            }
+        }
+        self.current.put_slice(&event);
+    }
+
+    /// returns whether corruption is injected
+    fn corrupt(&mut self, event: &mut BytesMut, kind: CorruptionKind, checksum: bool) -> bool {
+        match kind {
+            CorruptionKind::FlipByte => {
+                let checksum_len = if checksum { 4 } else { 0 };
+                if event.len() <= HEADER_LEN + checksum_len {
+                    return false;
+                }
+                let idx = self
+                    .rng
+                    .range(HEADER_LEN as u64, (event.len() - checksum_len - 1) as u64);
+                event[idx as usize] ^= 0xff;
+                true
+            }
+            CorruptionKind::BadChecksum => {
+                if !checksum {
+                    return false;
+                }
+                let last = event.len() - 1;
+                event[last] ^= 0xff;
+                true
+            }
+            CorruptionKind::Truncate => {
+                event.truncate(event.len() / 2);
+                self.truncated = true;
+                true
+            }
+        }
+    }
+}
+
+fn file_name(basename: &str, seq: usize) -> String {
+    format!("{}.{:06}", basename, seq)
+}
+
+fn query_body(schema: &str, query: &str) -> BytesMut {
+    let mut body = BytesMut::new();
+    // slave_proxy_id, exec_time
+    body.put_u32_le(1);
+    body.put_u32_le(0);
+    body.put_u8(schema.len() as u8);
+    // error_code, status_vars_len
+    body.put_u16_le(0);
+    body.put_u16_le(0);
+    body.put_slice(schema.as_bytes());
+    body.put_u8(0);
+    body.put_slice(query.as_bytes());
+    body
+}
+
+fn table_map_body(schema: &str, table: &Table) -> Result<BytesMut> {
+    let mut body = BytesMut::new();
+    body.put_uint_le(table.id, 6);
+    body.put_u16_le(1);
+    body.put_u8(schema.len() as u8);
+    body.put_slice(schema.as_bytes());
+    body.put_u8(0);
+    body.put_u8(table.name.len() as u8);
+    body.put_slice(table.name.as_bytes());
+    body.put_u8(0);
+    LenEncInt::from(table.cols.len() as u64).write_to(&mut body)?;
+    for col in &table.cols {
+        body.put_u8(col.type_code());
+    }
+    let mut metas = BytesMut::new();
+    for col in &table.cols {
+        col.put_meta(&mut metas);
+    }
+    LenEncInt::from(metas.len() as u64).write_to(&mut body)?;
+    body.put_slice(&metas);
+    // all columns except primary key are nullable
+    let nullable = (0..table.cols.len()).map(|i| i > 0);
+    body.put_slice(&crate::bitmap::from_iter(nullable, 0));
+    Ok(body)
+}
+
+fn create_table_stmt(table: &Table) -> String {
+    let cols: Vec<String> = table
+        .cols
+        .iter()
+        .enumerate()
+        .map(|(i, col)| {
+            if i == 0 {
+                format!("`c0` {} NOT NULL", col.sql_type())
+            } else {
+                format!("`c{}` {}", i, col.sql_type())
+            }
+        })
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS `{}` ({}, PRIMARY KEY (`c0`))",
+        table.name,
+        cols.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binlog::{Event, EventLength, ParserV4};
+    use bytes::{Buf, Bytes};
+    use bytes_parser::{ReadBytesExt, ReadFromBytes};
+
+    /// parse all events and rows of file, returns number of
+    /// transactions, or first error
+    fn parse_file(data: &[u8]) -> Result<usize> {
+        let mut input = Bytes::copy_from_slice(data);
+        let pv4 = ParserV4::from_binlog_file(&mut input)?;
+        let mut table_maps = std::collections::HashMap::new();
+        let mut n_trxs = 0;
+        while input.has_remaining() {
+            let len = EventLength::read_from(&mut input.clone())?.0 as usize;
+            let mut raw = input.read_len(len)?;
+            match pv4.parse_event(&mut raw, true)? {
+                Some(Event::TableMapEvent(e)) => {
+                    let data = e.into_data()?;
+                    table_maps.insert(data.table_id, data.table_map()?);
+                }
+                Some(Event::WriteRowsEventV2(e)) => {
+                    let data = e.into_data()?;
+                    data.rows(&table_maps[&data.table_id].col_metas)?;
+                }
+                Some(Event::UpdateRowsEventV2(e)) => {
+                    let data = e.into_data()?;
+                    data.rows(&table_maps[&data.table_id].col_metas)?;
+                }
+                Some(Event::DeleteRowsEventV2(e)) => {
+                    let data = e.into_data()?;
+                    data.rows(&table_maps[&data.table_id].col_metas)?;
+                }
+                Some(Event::XidEvent(_)) => n_trxs += 1,
+                Some(Event::QueryEvent(e)) => {
+                    // DDL is a transaction itself
+                    let data = e.into_data()?;
+                    n_trxs += (data.query_text()? != "BEGIN") as usize;
+                }
+                _ => (),
+            }
+        }
+        Ok(n_trxs)
+    }
+
+    #[test]
+    fn test_corpus_builder() {
+        let build = || {
+            CorpusBuilder::new(42)
+                .transactions(200)
+                .event_mix(EventMix {
+                    ddl: 1,
+                    ..Default::default()
+                })
+                .max_file_size(16 * 1024)
+                .build()
+                .unwrap()
+        };
+        let corpus = build();
+        assert!(corpus.files.len() > 1);
+        assert_eq!(corpus.files[1].name, "mysql-bin.000002");
+        // reproducible
+        let again = build();
+        for (a, b) in corpus.files.iter().zip(&again.files) {
+            assert_eq!(a.data, b.data);
+        }
+        let n_trxs: usize = corpus
+            .files
+            .iter()
+            .map(|f| parse_file(&f.data).unwrap())
+            .sum();
+        assert_eq!(200, n_trxs);
+
+        let corrupted = CorpusBuilder::new(7)
+            .transactions(50)
+            .gtid(false)
+            .corruption(CorruptionKind::BadChecksum, 0.05)
+            .build()
+            .unwrap();
+        assert!(!corrupted.corruptions.is_empty());
+        assert!(parse_file(&corrupted.files[0].data).is_err());
+        let truncated = CorpusBuilder::new(7)
+            .transactions(50)
+            .checksum(false)
+            .corruption(CorruptionKind::Truncate, 0.05)
+            .build()
+            .unwrap();
+        assert_eq!(1, truncated.corruptions.len());
+        assert!(truncated.n_trxs < 50);
+        assert!(parse_file(&truncated.files[0].data).is_err());
+    }
+}
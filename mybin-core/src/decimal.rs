@@ -1,6 +1,6 @@
-use bytes::{Buf, Bytes};
-use bytes_parser::error::Result;
-use bytes_parser::ReadBytesExt;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytes_parser::error::{Error, Result};
+use bytes_parser::{ReadBytesExt, WriteToBytes};
 use std::fmt;
 
 const DIG_PER_DEC1: u8 = 9;
@@ -39,6 +39,98 @@ impl MyDecimal {
         }
     }
 
+    /// parse decimal string with precision and scale of column
+    ///
+    /// extra fractional digits are rounded half up, and error
+    /// is returned if integral digits exceed precision - scale
+    pub fn parse(s: &str, precision: u8, scale: u8) -> Result<Self> {
+        if scale > precision {
+            return Err(Error::ConstraintError(format!(
+                "decimal scale {} larger than precision {}",
+                scale, precision
+            )));
+        }
+        let intg = precision - scale;
+        let (negative, digits) = match s.trim().as_bytes() {
+            [b'-', rest @ ..] => (true, rest),
+            [b'+', rest @ ..] => (false, rest),
+            digits => (false, digits),
+        };
+        let (int_part, frac_part) = match digits.iter().position(|&b| b == b'.') {
+            Some(i) => (&digits[..i], &digits[i + 1..]),
+            None => (digits, &digits[digits.len()..]),
+        };
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !int_part
+                .iter()
+                .chain(frac_part.iter())
+                .all(u8::is_ascii_digit)
+        {
+            return Err(Error::ConstraintError(format!("invalid decimal {:?}", s)));
+        }
+        // digits of integral and fractional parts, rounded to scale
+        let mut ds: Vec<u8> = int_part.iter().map(|b| b - b'0').collect();
+        ds.extend(frac_part.iter().take(scale as usize).map(|b| b - b'0'));
+        ds.resize(int_part.len() + scale as usize, 0);
+        if frac_part.get(scale as usize).is_some_and(|&b| b >= b'5') {
+            let mut carry = true;
+            for d in ds.iter_mut().rev() {
+                if *d == 9 {
+                    *d = 0;
+                } else {
+                    *d += 1;
+                    carry = false;
+                    break;
+                }
+            }
+            if carry {
+                ds.insert(0, 1);
+            }
+        }
+        let n_int = ds.len() - scale as usize;
+        let leading_zeros = ds[..n_int].iter().take_while(|&&d| d == 0).count();
+        if n_int - leading_zeros > intg as usize {
+            return Err(Error::ConstraintError(format!(
+                "decimal {:?} out of range of precision {} and scale {}",
+                s, precision, scale
+            )));
+        }
+        // align integral digits to intg
+        let mut aligned = vec![0u8; intg as usize - (n_int - leading_zeros)];
+        aligned.extend_from_slice(&ds[leading_zeros..]);
+        let intg0x = (intg % DIG_PER_DEC1) as usize;
+        let mut buf = vec![];
+        let (int_ds, frac_ds) = aligned.split_at(intg as usize);
+        if intg0x > 0 {
+            buf.push(to_u32(&int_ds[..intg0x]));
+        }
+        buf.extend(int_ds[intg0x..].chunks(DIG_PER_DEC1 as usize).map(to_u32));
+        for chunk in frac_ds.chunks(DIG_PER_DEC1 as usize) {
+            // fractional digits are left aligned in fragment
+            buf.push(to_u32(chunk) * POWERS_10[DIG_PER_DEC1 as usize - chunk.len()]);
+        }
+        // negative zero is normalized to zero
+        let negative = negative && buf.iter().any(|&n| n != 0);
+        Ok(Self {
+            intg,
+            frac: scale,
+            negative,
+            buf,
+        })
+    }
+
+    /// length of binary format with given digits
+    pub fn bin_size(intg: u8, frac: u8) -> usize {
+        let intg0 = intg / DIG_PER_DEC1;
+        let frac0 = frac / DIG_PER_DEC1;
+        let intg0x = intg - intg0 * DIG_PER_DEC1;
+        let frac0x = frac - frac0 * DIG_PER_DEC1;
+        (intg0 as u32 * 4
+            + DIG_TO_BYTES[intg0x as usize]
+            + frac0 as u32 * 4
+            + DIG_TO_BYTES[frac0x as usize]) as usize
+    }
+
     pub fn read_from(input: &mut Bytes, intg: u8, frac: u8) -> Result<Self> {
         // number of main integral fragments
         let intg0 = intg / DIG_PER_DEC1;
@@ -49,11 +141,8 @@ impl MyDecimal {
         // digit number of extra fractional fragment
         let frac0x = frac - frac0 * DIG_PER_DEC1;
         // total byte length
-        let bin_size = intg0 as u32 * 4
-            + DIG_TO_BYTES[intg0x as usize]
-            + frac0 as u32 * 4
-            + DIG_TO_BYTES[frac0x as usize];
-        if (bin_size as usize) < input.remaining() {
+        let bin_size = Self::bin_size(intg, frac);
+        if bin_size < input.remaining() {
             log::debug!(
                 "decimal length mismatch: intg={}, frac={}, bin_len={}, actual_len={}",
                 intg,
@@ -62,28 +151,27 @@ impl MyDecimal {
                 input.remaining()
             );
         }
-        if !input.has_remaining() {
+        if !input.has_remaining() || bin_size == 0 {
             return Ok(Self::zero(intg, frac));
         }
+        let mut raw = input.read_len(bin_size)?.to_vec();
+        // positive number will have first bit 1, this is MySQL decimal encoding,
+        // and negative number has all bits reversed
+        let negative = raw[0] & 0x80 != 0x80;
+        raw[0] ^= 0x80;
+        if negative {
+            raw.iter_mut().for_each(|b| *b = !*b);
+        }
+        let mut raw = &raw[..];
         let mut buf = vec![];
-        // positive number will have first bit 1, this is MySQL decimal encoding
-        let negative = input[0] & 0x80 != 0x80;
         if intg0x > 0 {
-            let len = DIG_TO_BYTES[intg0x as usize];
-            let frag = read_extra_fragment(input, true, len as usize, negative)?;
-            buf.push(frag);
-        }
-        for _ in 0..intg0 {
-            let frag = read_fragment(input, negative)?;
-            buf.push(frag);
+            buf.push(read_fragment(&mut raw, DIG_TO_BYTES[intg0x as usize]));
         }
-        for _ in 0..frac0 {
-            let frag = read_fragment(input, negative)?;
-            buf.push(frag);
+        for _ in 0..intg0 + frac0 {
+            buf.push(read_fragment(&mut raw, 4));
         }
         if frac0x > 0 {
-            let len = DIG_TO_BYTES[frac0x as usize];
-            let frag = read_extra_fragment(input, false, len as usize, negative)?;
+            let frag = read_fragment(&mut raw, DIG_TO_BYTES[frac0x as usize]);
             buf.push(frag * POWERS_10[(DIG_PER_DEC1 - frac0x) as usize]);
         }
         Ok(Self {
@@ -95,6 +183,50 @@ impl MyDecimal {
     }
 }
 
+/// write in binary format of binlog
+impl WriteToBytes for MyDecimal {
+    fn write_to(self, out: &mut BytesMut) -> Result<usize> {
+        let intg0 = self.intg / DIG_PER_DEC1;
+        let frac0 = self.frac / DIG_PER_DEC1;
+        let intg0x = self.intg - intg0 * DIG_PER_DEC1;
+        let frac0x = self.frac - frac0 * DIG_PER_DEC1;
+        let n_frags = (intg0x > 0) as usize + (intg0 + frac0) as usize + (frac0x > 0) as usize;
+        if !self.buf.is_empty() && self.buf.len() != n_frags {
+            return Err(Error::ConstraintError(format!(
+                "decimal fragments mismatch: intg={}, frac={}, fragments={}",
+                self.intg,
+                self.frac,
+                self.buf.len()
+            )));
+        }
+        let mut frags = self.buf.iter().cloned().chain(std::iter::repeat(0));
+        let mut raw = Vec::with_capacity(Self::bin_size(self.intg, self.frac));
+        if intg0x > 0 {
+            write_fragment(
+                &mut raw,
+                frags.next().unwrap(),
+                DIG_TO_BYTES[intg0x as usize],
+            );
+        }
+        for _ in 0..intg0 + frac0 {
+            write_fragment(&mut raw, frags.next().unwrap(), 4);
+        }
+        if frac0x > 0 {
+            let frag = frags.next().unwrap() / POWERS_10[(DIG_PER_DEC1 - frac0x) as usize];
+            write_fragment(&mut raw, frag, DIG_TO_BYTES[frac0x as usize]);
+        }
+        if raw.is_empty() {
+            return Ok(0);
+        }
+        if self.negative {
+            raw.iter_mut().for_each(|b| *b = !*b);
+        }
+        raw[0] ^= 0x80;
+        out.put_slice(&raw);
+        Ok(raw.len())
+    }
+}
+
 impl fmt::Display for MyDecimal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.buf.is_empty() {
@@ -105,100 +237,50 @@ impl fmt::Display for MyDecimal {
         let intg0x = self.intg - intg0 * DIG_PER_DEC1;
         let frac0 = self.frac / DIG_PER_DEC1;
         let frac0x = self.frac - frac0 * DIG_PER_DEC1;
-        let mut i = 0;
+        let n_intg = (intg0x > 0) as usize + intg0 as usize;
+        let (int_frags, frac_frags) = self.buf.split_at(n_intg);
         if self.negative {
             write!(f, "-")?;
         }
-        if intg0x > 0 {
-            write!(f, "{}", self.buf[i])?;
-            i += 1;
-        }
-        for _ in 0..intg0 {
-            write!(f, "{}", self.buf[i])?;
-            i += 1;
+        // skip leading zero fragments, and pad following ones
+        match int_frags.iter().position(|&n| n != 0) {
+            Some(i) => {
+                write!(f, "{}", int_frags[i])?;
+                for n in &int_frags[i + 1..] {
+                    write!(f, "{:09}", n)?;
+                }
+            }
+            None => write!(f, "0")?,
         }
         if self.frac == 0 {
             return Ok(());
         }
         write!(f, ".")?;
-        for _ in 0..frac0 {
-            write!(f, "{}", self.buf[i])?;
-            i += 1;
+        for n in &frac_frags[..frac0 as usize] {
+            write!(f, "{:09}", n)?;
         }
         if frac0x > 0 {
-            let x = self.buf[i] / POWERS_10[(DIG_PER_DEC1 - frac0x) as usize];
-            write!(f, "{}", x)?;
-            // i += 1;
+            let x = frac_frags[frac0 as usize] / POWERS_10[(DIG_PER_DEC1 - frac0x) as usize];
+            write!(f, "{:0width$}", x, width = frac0x as usize)?;
         }
         Ok(())
     }
 }
 
-/// read the extra fragment at beginning
-/// the fragment may contains 1, 2, 3, or 4 bytes
-/// according to the intg and scale
-/// if negative, all bits of the result needs to be reversed
-fn read_extra_fragment(
-    input: &mut Bytes,
-    rev_first_bit: bool,
-    len: usize,
-    negative: bool,
-) -> Result<u32> {
-    let frag = match len {
-        1 => {
-            let mut n = input.read_u8()?;
-            if rev_first_bit {
-                n ^= 0x80;
-            }
-            if negative {
-                n ^= 0xff;
-            }
-            n as u32
-        }
-        2 => {
-            let mut n = input.read_be_u16()?;
-            if rev_first_bit {
-                n ^= 0x8000;
-            }
-            if negative {
-                n ^= 0xffff;
-            }
-            n as u32
-        }
-        3 => {
-            let mut n = input.read_be_u24()?;
-            if rev_first_bit {
-                n ^= 0x80_0000;
-            }
-            if negative {
-                n ^= 0xff_ffff;
-            }
-            n & 0x00ff_ffff
-        }
-        4 => {
-            let mut n = input.read_be_u32()?;
-            if rev_first_bit {
-                n ^= 0x8000_0000;
-            }
-            if negative {
-                n ^= 0xffff_ffff
-            }
-            n
-        }
-        _ => unreachable!("unexpected decimal fragment length {}", len),
-    };
-    Ok(frag)
+fn to_u32(digits: &[u8]) -> u32 {
+    digits.iter().fold(0, |acc, &d| acc * 10 + d as u32)
 }
 
-/// read fragment with fixed length of 4
-/// if negative, all bits of the result needs to be reversed
-fn read_fragment(input: &mut Bytes, negative: bool) -> Result<u32> {
-    // decimal encoding using BigEndian
-    let mut n = input.read_be_u32()?;
-    if negative {
-        n ^= 0xffff_ffff;
-    }
-    Ok(n)
+/// read big endian fragment of 1 to 4 bytes
+fn read_fragment(input: &mut &[u8], len: u32) -> u32 {
+    let (frag, rest) = input.split_at(len as usize);
+    *input = rest;
+    frag.iter().fold(0, |acc, &b| (acc << 8) | b as u32)
+}
+
+/// write big endian fragment of 1 to 4 bytes
+fn write_fragment(out: &mut Vec<u8>, frag: u32, len: u32) {
+    out.extend_from_slice(&frag.to_be_bytes()[4 - len as usize..]);
 }
 
 #[cfg(test)]
@@ -227,4 +309,59 @@ mod tests {
         assert_eq!(vec![1, 234567890, 123400000], d1.buf);
         assert_eq!("-1234567890.1234", d1.to_string());
     }
+
+    #[test]
+    fn test_write_decimal() {
+        let d = MyDecimal::parse("1234567890.1234", 14, 4).unwrap();
+        let mut out = BytesMut::new();
+        assert_eq!(7, d.write_to(&mut out).unwrap());
+        assert_eq!(
+            &[0x81, 0x0d, 0xfb, 0x38, 0xd2, 0x04, 0xd2][..],
+            out.as_ref()
+        );
+        let d = MyDecimal::parse("-1234567890.1234", 14, 4).unwrap();
+        let mut out = BytesMut::new();
+        d.write_to(&mut out).unwrap();
+        assert_eq!(
+            &[0x7E, 0xF2, 0x04, 0xC7, 0x2D, 0xFB, 0x2D][..],
+            out.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_decimal_round_trip() {
+        for (input, prec, scale, expected) in vec![
+            ("0", 10, 2, "0.00"),
+            ("-0.00", 10, 2, "0.00"),
+            ("1.05", 10, 2, "1.05"),
+            ("-1.05", 10, 2, "-1.05"),
+            ("9.995", 10, 2, "10.00"),
+            ("-9.995", 10, 2, "-10.00"),
+            ("0.004", 10, 2, "0.00"),
+            ("123456789", 9, 0, "123456789"),
+            ("-123456789", 18, 0, "-123456789"),
+            ("1000000001", 18, 0, "1000000001"),
+            (".5", 1, 1, "0.5"),
+            ("-0.000000001", 20, 10, "-0.0000000010"),
+            (
+                "12345678901234567890.123456789012",
+                32,
+                12,
+                "12345678901234567890.123456789012",
+            ),
+            ("999999999999999999", 18, 0, "999999999999999999"),
+        ] {
+            let d = MyDecimal::parse(input, prec, scale).unwrap();
+            assert_eq!(expected, d.to_string(), "parse {}", input);
+            let mut out = BytesMut::new();
+            let len = d.clone().write_to(&mut out).unwrap();
+            assert_eq!(MyDecimal::bin_size(prec - scale, scale), len);
+            let d2 = MyDecimal::read_from(&mut out.freeze(), prec - scale, scale).unwrap();
+            assert_eq!(d, d2, "round trip {}", input);
+        }
+        // carry overflows precision
+        assert!(MyDecimal::parse("9.995", 3, 2).is_err());
+        assert!(MyDecimal::parse("1.2.3", 10, 2).is_err());
+        assert!(MyDecimal::parse("-", 10, 2).is_err());
+    }
 }
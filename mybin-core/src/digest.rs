@@ -0,0 +1,504 @@
+//! normalization and fingerprinting of sql statements
+//!
+//! fingerprint follows DIGEST_TEXT of performance_schema: comments are
+//! removed, literals are replaced with '?', keywords are upper-cased,
+//! identifiers are back-quoted, tokens are separated by single space,
+//! and value lists of IN and VALUES are collapsed.
+//!
+//! digest is sha256 of fingerprint in hex, the same format as DIGEST
+//! column. server hashes its internal token ids, so the value is not
+//! identical to the one reported by server, but statements with the
+//! same DIGEST_TEXT always have the same digest.
+use sha2::{Digest, Sha256};
+
+/// normalized text of statement
+pub fn fingerprint(sql: &str) -> String {
+    let tokens = collapse(merge_signs(tokenize(sql)));
+    let mut s = String::with_capacity(sql.len());
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            s.push(' ');
+        }
+        match token {
+            Token::Keyword(kw) => s.push_str(kw),
+            Token::Ident(id) | Token::Quoted(id) => {
+                s.push('`');
+                s.push_str(&id.replace('`', "``"));
+                s.push('`');
+            }
+            Token::Literal(_) => s.push('?'),
+            Token::Other(o) => s.push_str(o),
+        }
+    }
+    s
+}
+
+/// sha256 of fingerprint, as 64 hex chars
+pub fn digest(sql: &str) -> String {
+    hex::encode(Sha256::digest(fingerprint(sql).as_bytes()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// upper-cased
+    Keyword(String),
+    Ident(String),
+    /// back-quoted identifier
+    Quoted(String),
+    /// as written in statement, including quotes
+    Literal(String),
+    /// operators, punctuations and variables
+    Other(String),
+}
+
+impl Token {
+    pub(crate) fn is(&self, s: &str) -> bool {
+        match self {
+            Token::Keyword(t) | Token::Other(t) => t == s,
+            _ => false,
+        }
+    }
+
+    /// keyword or unquoted identifier, compared case-insensitively
+    pub(crate) fn is_word(&self, s: &str) -> bool {
+        match self {
+            Token::Keyword(t) | Token::Ident(t) => t.eq_ignore_ascii_case(s),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn ident(&self) -> Option<&str> {
+        match self {
+            Token::Ident(id) | Token::Quoted(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+const OPERATORS: [&str; 14] = [
+    "<=>", "->>", "<=", ">=", "<>", "!=", ":=", "||", "&&", "<<", ">>", "->", "(", ")",
+];
+
+/// executable comments, e.g. /*!50001 ... */, are tokenized as normal
+/// statement
+pub(crate) fn tokenize(sql: &str) -> Vec<Token> {
+    let cs: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut in_exec_comment = false;
+    let mut i = 0;
+    while i < cs.len() {
+        let c = cs[i];
+        let next = cs.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('*') && cs.get(i + 2) == Some(&'!') {
+            i += 3;
+            while i < cs.len() && cs[i].is_ascii_digit() {
+                i += 1;
+            }
+            in_exec_comment = true;
+        } else if in_exec_comment && c == '*' && next == Some('/') {
+            i += 2;
+            in_exec_comment = false;
+        } else if c == '#' || (c == '-' && next == Some('-') && is_space_or_end(&cs, i + 2)) {
+            while i < cs.len() && cs[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < cs.len() && !(cs[i] == '*' && cs.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' || c == '"' {
+            let start = i;
+            i = skip_quoted(&cs, i);
+            tokens.push(Token::Literal(cs[start..i.min(cs.len())].iter().collect()));
+        } else if c == '`' {
+            let start = i + 1;
+            i = skip_quoted(&cs, i);
+            let end = i.saturating_sub(1).max(start);
+            let id: String = cs[start..end].iter().collect();
+            tokens.push(Token::Quoted(id.replace("``", "`")));
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            i = skip_number(&cs, i);
+            if c != '.' && i < cs.len() && is_word_char(cs[i]) {
+                // identifier starting with digits, e.g. 1st_col
+                while i < cs.len() && is_word_char(cs[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(cs[start..i].iter().collect()));
+            } else {
+                tokens.push(Token::Literal(cs[start..i].iter().collect()));
+            }
+        } else if c == '@' {
+            let start = i;
+            i += 1;
+            while i < cs.len() && (is_word_char(cs[i]) || cs[i] == '@' || cs[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Other(cs[start..i].iter().collect()));
+        } else if is_word_char(c) {
+            let start = i;
+            while i < cs.len() && is_word_char(cs[i]) {
+                i += 1;
+            }
+            let word: String = cs[start..i].iter().collect();
+            let upper = word.to_ascii_uppercase();
+            if upper == "NULL" || upper == "TRUE" || upper == "FALSE" {
+                tokens.push(Token::Literal(word));
+            } else if (upper == "X" || upper == "B" || upper == "N") && cs.get(i) == Some(&'\'') {
+                // x'0f', b'01', n'abc'
+                i = skip_quoted(&cs, i);
+                tokens.push(Token::Literal(cs[start..i.min(cs.len())].iter().collect()));
+            } else if is_keyword(&upper) {
+                tokens.push(Token::Keyword(upper));
+            } else {
+                tokens.push(Token::Ident(word));
+            }
+        } else {
+            let rest: String = cs[i..cs.len().min(i + 3)].iter().collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Other((*op).to_owned()));
+                    i += op.len();
+                }
+                None => {
+                    tokens.push(Token::Other(c.to_string()));
+                    i += 1;
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_space_or_end(cs: &[char], i: usize) -> bool {
+    cs.get(i).is_none_or(|c| c.is_whitespace())
+}
+
+/// position after closing quote, doubled quote and backslash escape
+/// are skipped
+fn skip_quoted(cs: &[char], start: usize) -> usize {
+    let quote = cs[start];
+    let mut i = start + 1;
+    while i < cs.len() {
+        if cs[i] == '\\' && quote != '`' {
+            i += 2;
+        } else if cs[i] == quote {
+            if cs.get(i + 1) == Some(&quote) {
+                i += 2;
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    cs.len()
+}
+
+fn skip_number(cs: &[char], start: usize) -> usize {
+    let mut i = start;
+    if cs[i] == '0' && matches!(cs.get(i + 1), Some('x') | Some('X') | Some('b') | Some('B')) {
+        i += 2;
+        while i < cs.len() && cs[i].is_ascii_hexdigit() {
+            i += 1;
+        }
+        return i;
+    }
+    while i < cs.len() && (cs[i].is_ascii_digit() || cs[i] == '.') {
+        i += 1;
+    }
+    if i < cs.len() && (cs[i] == 'e' || cs[i] == 'E') {
+        let mut j = i + 1;
+        if matches!(cs.get(j), Some('+') | Some('-')) {
+            j += 1;
+        }
+        if cs.get(j).is_some_and(|c| c.is_ascii_digit()) {
+            i = j;
+            while i < cs.len() && cs[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+/// sign before literal is part of literal if not preceded by operand
+fn merge_signs(tokens: Vec<Token>) -> Vec<Token> {
+    let mut res: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if let Token::Literal(lit) = &token {
+            let signed = matches!(res.last(), Some(t) if t.is("-") || t.is("+"));
+            let operand_before = match res.len().checked_sub(2).map(|i| &res[i]) {
+                None => false,
+                Some(Token::Literal(_)) | Some(Token::Ident(_)) | Some(Token::Quoted(_)) => true,
+                Some(t) => t.is(")"),
+            };
+            if signed && !operand_before {
+                if let Some(Token::Other(sign)) = res.pop() {
+                    res.push(Token::Literal(format!("{}{}", sign, lit)));
+                    continue;
+                }
+            }
+        }
+        res.push(token);
+    }
+    res
+}
+
+/// IN (?, ?) to IN (...), and VALUES (?, ?), (?, ?) to
+/// VALUES (...) /* , ... */
+fn collapse(tokens: Vec<Token>) -> Vec<Token> {
+    let mut res = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if token.is("IN") {
+            if let Some(end) = literal_list(&tokens, i + 1) {
+                res.push(token.clone());
+                res.push(Token::Other("(...)".to_owned()));
+                i = end;
+                continue;
+            }
+        } else if token.is("VALUES") || token.is("VALUE") {
+            if let Some(mut end) = literal_list(&tokens, i + 1) {
+                res.push(token.clone());
+                res.push(Token::Other("(...)".to_owned()));
+                let mut rows = 1;
+                while tokens.get(end).is_some_and(|t| t.is(",")) {
+                    match literal_list(&tokens, end + 1) {
+                        Some(e) => {
+                            end = e;
+                            rows += 1;
+                        }
+                        None => break,
+                    }
+                }
+                if rows > 1 {
+                    res.push(Token::Other("/* , ... */".to_owned()));
+                }
+                i = end;
+                continue;
+            }
+        }
+        res.push(token.clone());
+        i += 1;
+    }
+    res
+}
+
+/// end position of parenthesized list of literals starting at given
+/// position
+fn literal_list(tokens: &[Token], start: usize) -> Option<usize> {
+    if !tokens.get(start)?.is("(") {
+        return None;
+    }
+    let mut i = start + 1;
+    loop {
+        if !matches!(tokens.get(i)?, Token::Literal(_)) {
+            return None;
+        }
+        i += 1;
+        let t = tokens.get(i)?;
+        if t.is(")") {
+            return Some(i + 1);
+        }
+        if !t.is(",") {
+            return None;
+        }
+        i += 1;
+    }
+}
+
+fn is_keyword(upper: &str) -> bool {
+    KEYWORDS.binary_search(&upper).is_ok()
+}
+
+// sorted for binary search
+const KEYWORDS: [&str; 146] = [
+    "ADD",
+    "AFTER",
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "AUTO_INCREMENT",
+    "AVG",
+    "BEGIN",
+    "BETWEEN",
+    "BIGINT",
+    "BINARY",
+    "BLOB",
+    "BOTH",
+    "BY",
+    "CALL",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHANGE",
+    "CHAR",
+    "CHARACTER",
+    "CHARSET",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMENT",
+    "COMMIT",
+    "CONSTRAINT",
+    "CONVERT",
+    "COUNT",
+    "CREATE",
+    "CROSS",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DATE",
+    "DATETIME",
+    "DECIMAL",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DIV",
+    "DOUBLE",
+    "DROP",
+    "DUPLICATE",
+    "ELSE",
+    "END",
+    "ENGINE",
+    "ENUM",
+    "EXISTS",
+    "EXPLAIN",
+    "FLOAT",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "FUNCTION",
+    "GRANT",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INT",
+    "INTEGER",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "JSON",
+    "KEY",
+    "KEYS",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOCK",
+    "LONGBLOB",
+    "LONGTEXT",
+    "MAX",
+    "MEDIUMINT",
+    "MIN",
+    "MOD",
+    "MODIFY",
+    "NATURAL",
+    "NOT",
+    "NOW",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "PARTITION",
+    "PRIMARY",
+    "PROCEDURE",
+    "REFERENCES",
+    "REGEXP",
+    "RENAME",
+    "REPLACE",
+    "RESTRICT",
+    "REVOKE",
+    "RIGHT",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "SCHEMA",
+    "SELECT",
+    "SET",
+    "SHARE",
+    "SHOW",
+    "SMALLINT",
+    "START",
+    "SUM",
+    "TABLE",
+    "TEMPORARY",
+    "TEXT",
+    "THEN",
+    "TIME",
+    "TIMESTAMP",
+    "TINYINT",
+    "TO",
+    "TRANSACTION",
+    "TRIGGER",
+    "TRUNCATE",
+    "UNION",
+    "UNIQUE",
+    "UNLOCK",
+    "UNSIGNED",
+    "UPDATE",
+    "USE",
+    "USING",
+    "VALUE",
+    "VALUES",
+    "VARBINARY",
+    "VARCHAR",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WITH",
+    "WORK",
+    "XOR",
+    "YEAR",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        assert!(KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            "SELECT `a` , `b` FROM `t1` WHERE `id` = ? AND `name` IN (...)",
+            fingerprint("select a,  b from t1 -- comment\n where id=-1 and `name` in ('x', \"y\")")
+        );
+        assert_eq!(
+            "INSERT IGNORE INTO `db1` . `t1` ( `id` , `v` ) VALUES (...) /* , ... */",
+            fingerprint(
+                "/* app */ INSERT /*!IGNORE*/ INTO db1.t1 (id, v) VALUES (1, 'it''s'), (2, NULL)"
+            )
+        );
+        assert_eq!(
+            "UPDATE `t` SET `v` = `v` - ? WHERE `k` = ? LIMIT ?",
+            fingerprint("update t set v = v - 1.5e3 where k = x'0f' limit 10")
+        );
+        assert_eq!(
+            fingerprint("SELECT 1 FROM t WHERE a = 2"),
+            fingerprint("select 3 from T where a = 'x'").replace("`T`", "`t`")
+        );
+        let d = digest("select 1");
+        assert_eq!(64, d.len());
+        assert_eq!(d, digest("SELECT   2"));
+        assert_ne!(d, digest("select 1 from dual"));
+    }
+}
@@ -15,6 +15,12 @@ pub enum Error {
     BinlogEventError(String),
     #[error("binlog checksum mismatch: expected={0}, actual={1}")]
     BinlogChecksumMismatch(u32, u32),
+    #[error("event too large: len={0}, max={1}")]
+    EventTooLarge(u32, u32),
+    #[error("too many rows in event, max={0}")]
+    TooManyRows(usize),
+    #[error("transaction too large: {0}")]
+    TransactionTooLarge(String),
     #[error("utf8 string error: {0}")]
     Utf8StringError(#[from] std::string::FromUtf8Error),
     #[error("utf8 str error: {0}")]
@@ -33,6 +39,14 @@ pub enum Error {
     ParseDateTimeError(#[from] chrono::ParseError),
     #[error("parse mysql time error: {0}")]
     ParseMyTimeError(String),
+    #[error("invalid time zone: {0}")]
+    InvalidTimeZone(String),
+    #[error("invalid table pattern: {0}")]
+    InvalidTablePattern(String),
+    #[error("non-finite float: {0}")]
+    NonFiniteFloat(String),
+    #[error("invalid session value: {0}")]
+    InvalidSessionValue(String),
     #[error("column type mismatch: {0}")]
     ColumnTypeMismatch(String),
     #[error("column index out of bound: {0}")]
@@ -43,6 +57,15 @@ pub enum Error {
     NullValueError,
     #[error("encode hex error {0}")]
     FromHexError(#[from] hex::FromHexError),
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("corrupted checkpoint: {0}")]
+    CorruptedCheckpoint(String),
+    #[error("regex error: {0}")]
+    RegexError(#[from] regex::Error),
+    #[cfg(feature = "arrow")]
+    #[error("arrow error: {0}")]
+    ArrowError(#[from] arrow_schema::ArrowError),
 }
 
 impl Error {
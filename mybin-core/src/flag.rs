@@ -96,6 +96,31 @@ bitflags! {
     }
 }
 
+impl StatusFlags {
+    pub fn in_transaction(&self) -> bool {
+        self.contains(StatusFlags::STATUS_IN_TRANS)
+    }
+
+    pub fn autocommit(&self) -> bool {
+        self.contains(StatusFlags::STATUS_AUTOCOMMIT)
+    }
+
+    /// another result set follows, e.g. multi-statements or
+    /// stored procedure
+    pub fn more_results(&self) -> bool {
+        self.contains(StatusFlags::MORE_RESULTS_EXISTS)
+    }
+
+    pub fn cursor_exists(&self) -> bool {
+        self.contains(StatusFlags::STATUS_CURSOR_EXISTS)
+    }
+
+    /// exceeds long_query_time on server
+    pub fn last_statement_was_slow(&self) -> bool {
+        self.contains(StatusFlags::QUERY_WAS_SLOW)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,5 +129,7 @@ mod tests {
     fn test_check_status_flag() {
         let sf = StatusFlags::from_bits(0b0000100101100110_u16).unwrap();
         dbg!(sf);
+        assert!(sf.autocommit() && sf.cursor_exists() && sf.last_statement_was_slow());
+        assert!(!sf.in_transaction() && !sf.more_results());
     }
 }
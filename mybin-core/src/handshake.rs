@@ -1,4 +1,5 @@
 use crate::flag::*;
+use crate::version::ServerVersion;
 use bytes::{Buf, Bytes, BytesMut};
 use bytes_parser::error::{Error, Result};
 use bytes_parser::my::LenEncInt;
@@ -30,39 +31,79 @@ impl ReadFromBytes for InitialHandshake {
         let auth_plugin_data_1 = input.read_len(8)?;
         input.read_len(1)?;
         let capability_flags_lower = input.read_le_u16()?;
-        let charset = input.read_u8()?;
-        let status_flags = input.read_le_u16()?;
-        let capability_flags_upper = input.read_le_u16()?;
-        let auth_plugin_data_length = input.read_u8()?;
-        input.read_len(10)?;
-        // construct complete capability_flags
-        let capability_flags =
-            (capability_flags_lower as u32) | ((capability_flags_upper as u32) << 16);
-        let cap_flags = CapabilityFlags::from_bits_truncate(capability_flags);
-        let auth_plugin_data_2 = if cap_flags.contains(CapabilityFlags::SECURE_CONNECTION) {
-            let len = std::cmp::max(13, auth_plugin_data_length - 8);
-            input.read_len(len as usize)?
-        } else {
-            Bytes::new()
-        };
-        let auth_plugin_name = if cap_flags.contains(CapabilityFlags::PLUGIN_AUTH) {
-            let apn = input.read_until(0, false)?;
-            String::from_utf8(apn.to_vec())?
-        } else {
-            String::new()
-        };
-        Ok(InitialHandshake {
+        let mut handshake = InitialHandshake {
             protocol_version,
             server_version,
             connection_id,
             auth_plugin_data_1,
-            charset,
-            status_flags,
-            capability_flags,
-            auth_plugin_data_length,
-            auth_plugin_data_2,
-            auth_plugin_name,
-        })
+            charset: 0,
+            status_flags: 0,
+            capability_flags: capability_flags_lower as u32,
+            auth_plugin_data_length: 0,
+            auth_plugin_data_2: Bytes::new(),
+            auth_plugin_name: String::new(),
+        };
+        // very old servers end here
+        if !input.has_remaining() {
+            return Ok(handshake);
+        }
+        handshake.charset = input.read_u8()?;
+        handshake.status_flags = input.read_le_u16()?;
+        let capability_flags_upper = input.read_le_u16()?;
+        handshake.auth_plugin_data_length = input.read_u8()?;
+        input.read_len(10)?;
+        // construct complete capability_flags
+        handshake.capability_flags |= (capability_flags_upper as u32) << 16;
+        let cap_flags = CapabilityFlags::from_bits_truncate(handshake.capability_flags);
+        if cap_flags.contains(CapabilityFlags::SECURE_CONNECTION) {
+            // length is 0 if server does not support plugin auth
+            let len = std::cmp::max(13, handshake.auth_plugin_data_length.saturating_sub(8));
+            // some 4.1 servers send 12 bytes without trailing 0x00
+            let len = std::cmp::min(len as usize, input.remaining());
+            handshake.auth_plugin_data_2 = input.read_len(len)?;
+        }
+        if cap_flags.contains(CapabilityFlags::PLUGIN_AUTH) && input.has_remaining() {
+            // some servers omit trailing 0x00 of plugin name
+            let apn = if input.chunk().contains(&0) {
+                input.read_until(0, false)?
+            } else {
+                input.split_to(input.remaining())
+            };
+            handshake.auth_plugin_name = String::from_utf8(apn.to_vec())?;
+        }
+        Ok(handshake)
+    }
+}
+
+impl InitialHandshake {
+    /// None if server version is not recognized
+    pub fn version(&self) -> Option<ServerVersion> {
+        ServerVersion::parse(&String::from_utf8_lossy(self.server_version.chunk()))
+    }
+
+    /// auth plugin suggested by server, plugin name can be omitted by
+    /// servers without plugin auth
+    pub fn auth_plugin_name_or_default(&self) -> &str {
+        if !self.auth_plugin_name.is_empty() {
+            return &self.auth_plugin_name;
+        }
+        let cap_flags = CapabilityFlags::from_bits_truncate(self.capability_flags);
+        if cap_flags.contains(CapabilityFlags::SECURE_CONNECTION) {
+            "mysql_native_password"
+        } else {
+            "mysql_old_password"
+        }
+    }
+
+    /// concatenated auth plugin data without trailing 0x00
+    pub fn scramble(&self) -> Vec<u8> {
+        let mut seed = Vec::with_capacity(20);
+        seed.extend_from_slice(self.auth_plugin_data_1.chunk());
+        seed.extend_from_slice(self.auth_plugin_data_2.chunk());
+        if let Some(0) = seed.last() {
+            seed.pop();
+        }
+        seed
     }
 }
 
@@ -103,10 +144,30 @@ impl WriteToBytes for HandshakeClientResponse41 {
         // null-terminated username
         len += out.write_bytes(self.username.as_bytes())?;
         len += out.write_u8(0)?;
-        // len-encoded auth response
-        let auth_response_len = LenEncInt::from(self.auth_response.len() as u64);
-        len += auth_response_len.write_to(out)?;
-        len += out.write_bytes(&self.auth_response[..])?;
+        // auth response encoded by capabilities
+        if self
+            .capability_flags
+            .contains(CapabilityFlags::PLUGIN_AUTH_LENENC_CLIENT_DATA)
+        {
+            let auth_response_len = LenEncInt::from(self.auth_response.len() as u64);
+            len += auth_response_len.write_to(out)?;
+            len += out.write_bytes(&self.auth_response[..])?;
+        } else if self
+            .capability_flags
+            .contains(CapabilityFlags::SECURE_CONNECTION)
+        {
+            if self.auth_response.len() > 0xff {
+                return Err(Error::ConstraintError(format!(
+                    "auth response too long: {}",
+                    self.auth_response.len()
+                )));
+            }
+            len += out.write_u8(self.auth_response.len() as u8)?;
+            len += out.write_bytes(&self.auth_response[..])?;
+        } else {
+            len += out.write_bytes(&self.auth_response[..])?;
+            len += out.write_u8(0)?;
+        }
         // null-terminated database if connect with db
         if self
             .capability_flags
@@ -183,6 +244,15 @@ impl ReadFromBytes for AuthSwitchRequest {
                 header
             )));
         }
+        // old servers request switching to pre-4.1 password hashing
+        // by single 0xfe byte
+        if !input.has_remaining() {
+            return Ok(AuthSwitchRequest {
+                header,
+                plugin_name: "mysql_old_password".to_owned(),
+                auth_plugin_data: Bytes::new(),
+            });
+        }
         let plugin_name = input.read_until(0, false)?;
         let auth_plugin_data = input.split_to(input.remaining());
         Ok(AuthSwitchRequest {
@@ -245,4 +315,38 @@ mod tests {
         let pkt = Packet::read_from(&mut input).unwrap();
         dbg!(pkt);
     }
+
+    #[test]
+    fn test_read_handshake_without_plugin_auth() {
+        let cap_flags = CapabilityFlags::PROTOCOL_41 | CapabilityFlags::SECURE_CONNECTION;
+        let mut bs = BytesMut::new();
+        bs.extend_from_slice(b"\x0a5.0.96\x00\x01\x00\x00\x00abcdefgh\x00");
+        bs.extend_from_slice(&(cap_flags.bits() as u16).to_le_bytes());
+        bs.extend_from_slice(&[33, 2, 0]);
+        bs.extend_from_slice(&((cap_flags.bits() >> 16) as u16).to_le_bytes());
+        // auth plugin data length is 0, 12 bytes of scramble without 0x00
+        bs.extend_from_slice(&[0; 11]);
+        bs.extend_from_slice(b"ijklmnopqrst");
+        let handshake = InitialHandshake::read_from(&mut bs.freeze()).unwrap();
+        assert_eq!(b"abcdefghijklmnopqrst".to_vec(), handshake.scramble());
+        assert_eq!(
+            "mysql_native_password",
+            handshake.auth_plugin_name_or_default()
+        );
+
+        // old switch request
+        let switch = AuthSwitchRequest::read_from(&mut Bytes::from_static(b"\xfe")).unwrap();
+        assert_eq!("mysql_old_password", switch.plugin_name);
+
+        // auth response with 1-byte length
+        let resp = HandshakeClientResponse41 {
+            capability_flags: cap_flags,
+            username: "u".to_owned(),
+            auth_response: vec![1, 2],
+            ..Default::default()
+        };
+        let mut out = BytesMut::new();
+        resp.write_to(&mut out).unwrap();
+        assert_eq!(&b"u\x00\x02\x01\x02"[..], &out[32..]);
+    }
 }
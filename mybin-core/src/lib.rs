@@ -1,19 +1,30 @@
 #![forbid(unsafe_code)]
+pub mod backfill;
 pub mod binlog;
 pub mod bitmap;
+pub mod checksum;
 pub mod cmd;
+#[cfg(feature = "codec")]
+pub mod codec;
 pub mod col;
+#[cfg(feature = "testutil")]
+pub mod corpus;
 pub mod decimal;
+pub mod digest;
 pub mod error;
 pub mod flag;
 pub mod handshake;
+pub mod lob;
 pub mod packet;
 pub mod quit;
 pub mod resp;
 pub mod resultset;
 pub mod row;
+pub mod session;
 pub mod stmt;
 pub mod time;
+pub mod value;
+pub mod version;
 
 mod util;
 
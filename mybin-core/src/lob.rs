@@ -0,0 +1,134 @@
+//! chunked access of large column values
+//!
+//! chunks are split from the underlying Bytes without copy.
+//! mybin-async provides AsyncRead adapter of the reader, or it can
+//! be converted to a stream of chunks with `futures::stream::iter`.
+use crate::col::{BinaryColumnValue, BinlogColumnValue, TextColumnValue};
+use bytes::{Buf, Bytes};
+use std::io;
+
+/// default chunk size, 64KB
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// reader over value of BLOB or TEXT column
+#[derive(Debug, Clone)]
+pub struct ColumnReader {
+    data: Bytes,
+    chunk_size: usize,
+}
+
+impl ColumnReader {
+    pub fn new(data: Bytes) -> Self {
+        Self::with_chunk_size(data, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(data: Bytes, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        ColumnReader { data, chunk_size }
+    }
+
+    /// number of bytes not read
+    pub fn remaining(&self) -> usize {
+        self.data.remaining()
+    }
+
+    /// returns the rest bytes in single chunk
+    pub fn into_bytes(self) -> Bytes {
+        self.data
+    }
+}
+
+impl Iterator for ColumnReader {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        if !self.data.has_remaining() {
+            return None;
+        }
+        let n = self.chunk_size.min(self.data.remaining());
+        Some(self.data.split_to(n))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.data.remaining().div_ceil(self.chunk_size);
+        (n, Some(n))
+    }
+}
+
+impl io::Read for ColumnReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.data.remaining());
+        self.data.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+impl io::BufRead for ColumnReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let n = self.chunk_size.min(self.data.remaining());
+        Ok(&self.data.chunk()[..n])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.data.advance(amt)
+    }
+}
+
+/// column values which can be read in chunks
+pub trait ToColumnReader {
+    /// returns None if value is NULL or not a string or binary type
+    fn column_reader(&self) -> Option<ColumnReader>;
+}
+
+impl ToColumnReader for BinlogColumnValue {
+    fn column_reader(&self) -> Option<ColumnReader> {
+        match self {
+            BinlogColumnValue::Blob(bs)
+            | BinlogColumnValue::VarString(bs)
+            | BinlogColumnValue::String(bs)
+            | BinlogColumnValue::Geometry(bs) => Some(ColumnReader::new(bs.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl ToColumnReader for BinaryColumnValue {
+    fn column_reader(&self) -> Option<ColumnReader> {
+        match self {
+            BinaryColumnValue::Blob(bs)
+            | BinaryColumnValue::VarString(bs)
+            | BinaryColumnValue::String(bs)
+            | BinaryColumnValue::Geometry(bs) => Some(ColumnReader::new(bs.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl ToColumnReader for TextColumnValue {
+    fn column_reader(&self) -> Option<ColumnReader> {
+        self.as_ref().map(|bs| ColumnReader::new(bs.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_column_reader() {
+        let value = BinlogColumnValue::Blob(Bytes::from(vec![7u8; 10]));
+        let reader = value.column_reader().unwrap();
+        let chunks: Vec<_> = ColumnReader::with_chunk_size(reader.into_bytes(), 4).collect();
+        assert_eq!(
+            vec![4, 4, 2],
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>()
+        );
+        let mut reader = Some(Bytes::from_static(b"hello")).column_reader().unwrap();
+        let mut s = String::new();
+        reader.read_to_string(&mut s).unwrap();
+        assert_eq!("hello", s);
+        assert_eq!(0, reader.remaining());
+        assert!(BinlogColumnValue::Long(1).column_reader().is_none());
+    }
+}
@@ -17,6 +17,36 @@ where
     fn from_col(value: T) -> Result<Self>;
 }
 
+/// define types that can be constructed from a whole row
+///
+/// unlike RowMapper, the conversion may fail
+pub trait FromRow<T>
+where
+    Self: Sized,
+{
+    fn from_row(extractor: &ColumnExtractor, row: Vec<T>) -> Result<Self>;
+}
+
+macro_rules! from_row_tuple {
+    ($($idx:tt => $ty:ident),*) => {
+        impl<T, $($ty),*> FromRow<T> for ($($ty,)*)
+        where
+            T: Clone,
+            $($ty: FromColumnValue<T>),*
+        {
+            fn from_row(extractor: &ColumnExtractor, row: Vec<T>) -> Result<Self> {
+                Ok(($(extractor.get_col(&row, $idx)?,)*))
+            }
+        }
+    };
+}
+
+from_row_tuple!(0 => A);
+from_row_tuple!(0 => A, 1 => B);
+from_row_tuple!(0 => A, 1 => B, 2 => C);
+from_row_tuple!(0 => A, 1 => B, 2 => C, 3 => D);
+from_row_tuple!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E);
+
 /// RowMapper convert single row to its output
 ///
 /// for simpicity, the conversion must not fail,
@@ -446,6 +476,25 @@ try_non_null_column_value!(BinaryColumnValue => i8, u8, i16, u16, i32, u32, i64,
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::col::ColumnFlags;
+
+    #[test]
+    fn test_from_row_tuple() {
+        let col_defs: Vec<ColumnDefinition> = ["id", "name"]
+            .iter()
+            .map(|name| {
+                crate::col::tests::col_def(name, ColumnType::VarString, ColumnFlags::empty())
+            })
+            .collect();
+        let extractor = ColumnExtractor::new(&col_defs);
+        let row = vec![Some(Bytes::from("1")), Some(Bytes::from("hello"))];
+        let (id, name): (u32, String) = FromRow::from_row(&extractor, row.clone()).unwrap();
+        assert_eq!(1, id);
+        assert_eq!("hello", name);
+        let res: Result<(u32, String, u32)> = FromRow::from_row(&extractor, row);
+        assert!(res.is_err());
+    }
 
     #[test]
     fn test_num() {
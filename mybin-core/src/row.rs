@@ -1,7 +1,7 @@
 use crate::bitmap;
 use crate::col::{BinaryColumnValue, BinlogColumnValue, ColumnMeta, ColumnType, TextColumnValue};
 use bytes::Bytes;
-use bytes_parser::error::{Error, Result};
+use bytes_parser::error::{Error, Needed, Result};
 use bytes_parser::my::{LenEncStr, ReadMyEnc};
 use bytes_parser::ReadBytesExt;
 
@@ -28,6 +28,113 @@ impl TextRow {
     }
 }
 
+/// text row whose cells are slices of packet buffer
+///
+/// no allocation is made for cells, consumer copies what it keeps
+#[derive(Debug, Clone, Copy)]
+pub struct TextRowRef<'a> {
+    packet: &'a [u8],
+    cells: &'a [Option<(usize, usize)>],
+}
+
+impl<'a> TextRowRef<'a> {
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// returns None if index out of bound, Some(None) if value is NULL
+    pub fn get(&self, idx: usize) -> Option<Option<&'a [u8]>> {
+        self.cells
+            .get(idx)
+            .map(|cell| cell.map(|(start, end)| &self.packet[start..end]))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<&'a [u8]>> + 'a {
+        let packet = self.packet;
+        self.cells
+            .iter()
+            .map(move |cell| cell.map(|(start, end)| &packet[start..end]))
+    }
+
+    /// copy cells into owned row
+    pub fn to_text_row(&self) -> TextRow {
+        TextRow(
+            self.iter()
+                .map(|cell| cell.map(Bytes::copy_from_slice))
+                .collect(),
+        )
+    }
+}
+
+/// parser of borrowed text rows
+///
+/// cell offsets are kept in parser and reused across rows
+#[derive(Debug, Default)]
+pub struct TextRowParser {
+    cells: Vec<Option<(usize, usize)>>,
+}
+
+impl TextRowParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse<'a>(&'a mut self, packet: &'a [u8], col_cnt: usize) -> Result<TextRowRef<'a>> {
+        self.cells.clear();
+        let mut pos = 0;
+        for _ in 0..col_cnt {
+            let (len, lei_len) = match packet.get(pos) {
+                None => return Err(Error::InputIncomplete(Bytes::new(), Needed::Unknown)),
+                Some(0xfb) => {
+                    self.cells.push(None);
+                    pos += 1;
+                    continue;
+                }
+                Some(0xfc) => (read_le(packet, pos + 1, 2)?, 3),
+                Some(0xfd) => (read_le(packet, pos + 1, 3)?, 4),
+                Some(0xfe) => (read_le(packet, pos + 1, 8)?, 9),
+                Some(0xff) => {
+                    return Err(Error::ConstraintError(
+                        "invalid text column value".to_owned(),
+                    ))
+                }
+                Some(n) => (*n as usize, 1),
+            };
+            let start = pos + lei_len;
+            let end = start + len;
+            if end > packet.len() {
+                return Err(Error::InputIncomplete(
+                    Bytes::new(),
+                    Needed::Size(end - packet.len()),
+                ));
+            }
+            self.cells.push(Some((start, end)));
+            pos = end;
+        }
+        Ok(TextRowRef {
+            packet,
+            cells: &self.cells,
+        })
+    }
+}
+
+fn read_le(packet: &[u8], pos: usize, len: usize) -> Result<usize> {
+    if pos + len > packet.len() {
+        return Err(Error::InputIncomplete(
+            Bytes::new(),
+            Needed::Size(pos + len - packet.len()),
+        ));
+    }
+    Ok(packet[pos..pos + len]
+        .iter()
+        .rev()
+        .fold(0, |n, b| (n << 8) | *b as usize))
+}
+
 /// used for binary result set of statement execution
 #[derive(Debug, Clone)]
 pub struct BinaryRow(pub Vec<BinaryColumnValue>);
@@ -75,4 +182,57 @@ impl LogRow {
         }
         Ok(LogRow(cols))
     }
+
+    /// read row and skip columns not marked in projection bitmap,
+    /// skipped columns are Null
+    pub fn read_projected(
+        input: &mut Bytes,
+        n_cols: usize,
+        col_bm: &[u8],
+        col_metas: &[ColumnMeta],
+        projection: &[u8],
+    ) -> Result<Self> {
+        if col_metas.len() < n_cols {
+            return Err(Error::ConstraintError(format!(
+                "column count mismatch: table map {}, rows {}",
+                col_metas.len(),
+                n_cols
+            )));
+        }
+        let mut cols = Vec::with_capacity(n_cols);
+        for (i, col_meta) in col_metas.iter().enumerate().take(n_cols) {
+            if !bitmap::index(col_bm, i) {
+                cols.push(BinlogColumnValue::Null);
+            } else if bitmap::index(projection, i) {
+                cols.push(BinlogColumnValue::read_from(input, col_meta)?);
+            } else {
+                BinlogColumnValue::skip(input, col_meta)?;
+                cols.push(BinlogColumnValue::Null);
+            }
+        }
+        Ok(LogRow(cols))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_row_parser() {
+        let mut packet = vec![1u8, b'1', 0xfb, 0xfc, 3, 0];
+        packet.extend_from_slice(b"abc");
+        let mut parser = TextRowParser::new();
+        let row = parser.parse(&packet, 3).unwrap();
+        assert_eq!(3, row.len());
+        assert_eq!(Some(Some(&b"1"[..])), row.get(0));
+        assert_eq!(Some(None), row.get(1));
+        assert_eq!(Some(Some(&b"abc"[..])), row.get(2));
+        assert_eq!(None, row.get(3));
+        let owned = row.to_text_row();
+        let expected = TextRow::read_from(&mut Bytes::from(packet.clone()), 3).unwrap();
+        assert_eq!(expected.0, owned.0);
+        // truncated packet
+        assert!(parser.parse(&packet[..packet.len() - 1], 3).is_err());
+    }
 }
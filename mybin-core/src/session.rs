@@ -0,0 +1,246 @@
+//! typed session settings shared by binlog and connection
+//!
+//! SqlMode is recorded in status vars of QueryEvent, and all of them
+//! can be configured on session. Display produces the form accepted
+//! by SET statements, FromStr also accepts values of system variables,
+//! e.g. "READ-COMMITTED" of transaction_isolation.
+use crate::error::{Error, Result};
+use bitflags::bitflags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::*;
+use std::fmt;
+use std::str::FromStr;
+
+bitflags! {
+    pub struct SqlMode: u64 {
+        const REAL_AS_FLOAT     = 0x0000_0001;
+        const PIPES_AS_CONCAT   = 0x0000_0002;
+        const ANSI_QUOTES       = 0x0000_0004;
+        const IGNORE_SPACE      = 0x0000_0008;
+        const NOT_USED          = 0x0000_0010;
+        const ONLY_FULL_GROUP_BY    = 0x0000_0020;
+        const NO_UNSIGNED_SUBTRACTION   = 0x0000_0040;
+        const NO_DIR_IN_CREATE  = 0x0000_0080;
+        const POSTGRESQL        = 0x0000_0100;
+        const ORACLE            = 0x0000_0200;
+        const MSSQL             = 0x0000_0400;
+        const DB2               = 0x0000_0800;
+        const MAXDB             = 0x0000_1000;
+        const NO_KEY_OPTIONS    = 0x0000_2000;
+        const NO_TABLE_OPTIONS  = 0x0000_4000;
+        const NO_FIELD_OPTIONS  = 0x0000_8000;
+        const MYSQL323          = 0x0001_0000;
+        const MYSQL40           = 0x0002_0000;
+        const ANSI              = 0x0004_0000;
+        const NO_AUTO_VALUE_ON_ZERO = 0x0008_0000;
+        const NO_BACKSLASH_ESCAPES  = 0x0010_0000;
+        const STRICT_TRANS_TABLES   = 0x0020_0000;
+        const STRICT_ALL_TABLES = 0x0040_0000;
+        const NO_ZERO_IN_DATE   = 0x0080_0000;
+        const NO_ZERO_DATE      = 0x0100_0000;
+        const INVALID_DATES     = 0x0200_0000;
+        const ERROR_FOR_DIVISION_BY_ZERO    = 0x0400_0000;
+        const TRADITIONAL       = 0x0800_0000;
+        const NO_AUTO_CREATE_USER   = 0x1000_0000;
+        const HIGH_NOT_PRECEDENCE   = 0x2000_0000;
+        const NO_ENGINE_SUBSTITUTION    = 0x4000_0000;
+        const PAD_CHAR_TO_FULL_LENGTH   = 0x8000_0000;
+        // since 8.0
+        const TIME_TRUNCATE_FRACTIONAL  = 0x0001_0000_0000;
+    }
+}
+
+/// names used in sql_mode variable, NOT_USED is never displayed
+const SQL_MODE_NAMES: &[(SqlMode, &str)] = &[
+    (SqlMode::REAL_AS_FLOAT, "REAL_AS_FLOAT"),
+    (SqlMode::PIPES_AS_CONCAT, "PIPES_AS_CONCAT"),
+    (SqlMode::ANSI_QUOTES, "ANSI_QUOTES"),
+    (SqlMode::IGNORE_SPACE, "IGNORE_SPACE"),
+    (SqlMode::ONLY_FULL_GROUP_BY, "ONLY_FULL_GROUP_BY"),
+    (SqlMode::NO_UNSIGNED_SUBTRACTION, "NO_UNSIGNED_SUBTRACTION"),
+    (SqlMode::NO_DIR_IN_CREATE, "NO_DIR_IN_CREATE"),
+    (SqlMode::POSTGRESQL, "POSTGRESQL"),
+    (SqlMode::ORACLE, "ORACLE"),
+    (SqlMode::MSSQL, "MSSQL"),
+    (SqlMode::DB2, "DB2"),
+    (SqlMode::MAXDB, "MAXDB"),
+    (SqlMode::NO_KEY_OPTIONS, "NO_KEY_OPTIONS"),
+    (SqlMode::NO_TABLE_OPTIONS, "NO_TABLE_OPTIONS"),
+    (SqlMode::NO_FIELD_OPTIONS, "NO_FIELD_OPTIONS"),
+    (SqlMode::MYSQL323, "MYSQL323"),
+    (SqlMode::MYSQL40, "MYSQL40"),
+    (SqlMode::ANSI, "ANSI"),
+    (SqlMode::NO_AUTO_VALUE_ON_ZERO, "NO_AUTO_VALUE_ON_ZERO"),
+    (SqlMode::NO_BACKSLASH_ESCAPES, "NO_BACKSLASH_ESCAPES"),
+    (SqlMode::STRICT_TRANS_TABLES, "STRICT_TRANS_TABLES"),
+    (SqlMode::STRICT_ALL_TABLES, "STRICT_ALL_TABLES"),
+    (SqlMode::NO_ZERO_IN_DATE, "NO_ZERO_IN_DATE"),
+    (SqlMode::NO_ZERO_DATE, "NO_ZERO_DATE"),
+    (SqlMode::INVALID_DATES, "ALLOW_INVALID_DATES"),
+    (
+        SqlMode::ERROR_FOR_DIVISION_BY_ZERO,
+        "ERROR_FOR_DIVISION_BY_ZERO",
+    ),
+    (SqlMode::TRADITIONAL, "TRADITIONAL"),
+    (SqlMode::NO_AUTO_CREATE_USER, "NO_AUTO_CREATE_USER"),
+    (SqlMode::HIGH_NOT_PRECEDENCE, "HIGH_NOT_PRECEDENCE"),
+    (SqlMode::NO_ENGINE_SUBSTITUTION, "NO_ENGINE_SUBSTITUTION"),
+    (SqlMode::PAD_CHAR_TO_FULL_LENGTH, "PAD_CHAR_TO_FULL_LENGTH"),
+    (
+        SqlMode::TIME_TRUNCATE_FRACTIONAL,
+        "TIME_TRUNCATE_FRACTIONAL",
+    ),
+];
+
+impl fmt::Display for SqlMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = SQL_MODE_NAMES
+            .iter()
+            .filter(|(mode, _)| self.contains(*mode))
+            .map(|(_, name)| *name)
+            .collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+impl FromStr for SqlMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut sql_mode = SqlMode::empty();
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let mode = SQL_MODE_NAMES
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|(mode, _)| *mode)
+                .ok_or_else(|| Error::InvalidSessionValue(format!("sql_mode {}", name)))?;
+            sql_mode |= mode;
+        }
+        Ok(sql_mode)
+    }
+}
+
+impl Serialize for SqlMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SqlMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+impl FromStr for IsolationLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let level = match s.trim().replace('-', " ").to_uppercase().as_str() {
+            "READ UNCOMMITTED" => IsolationLevel::ReadUncommitted,
+            "READ COMMITTED" => IsolationLevel::ReadCommitted,
+            "REPEATABLE READ" => IsolationLevel::RepeatableRead,
+            "SERIALIZABLE" => IsolationLevel::Serializable,
+            _ => return Err(Error::InvalidSessionValue(format!("isolation level {}", s))),
+        };
+        Ok(level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessMode {
+    ReadWrite,
+    ReadOnly,
+}
+
+impl AccessMode {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            AccessMode::ReadWrite => "READ WRITE",
+            AccessMode::ReadOnly => "READ ONLY",
+        }
+    }
+}
+
+impl fmt::Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql())
+    }
+}
+
+/// accepts "READ ONLY", "READ WRITE", and boolean values of
+/// transaction_read_only
+impl FromStr for AccessMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mode = match s.trim().replace('-', " ").to_uppercase().as_str() {
+            "READ WRITE" | "OFF" | "0" => AccessMode::ReadWrite,
+            "READ ONLY" | "ON" | "1" => AccessMode::ReadOnly,
+            _ => return Err(Error::InvalidSessionValue(format!("access mode {}", s))),
+        };
+        Ok(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_values() {
+        let sql_mode: SqlMode = "STRICT_TRANS_TABLES, no_zero_date,ALLOW_INVALID_DATES"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            SqlMode::STRICT_TRANS_TABLES | SqlMode::NO_ZERO_DATE | SqlMode::INVALID_DATES,
+            sql_mode
+        );
+        assert_eq!(
+            "STRICT_TRANS_TABLES,NO_ZERO_DATE,ALLOW_INVALID_DATES",
+            sql_mode.to_string()
+        );
+        assert_eq!(SqlMode::empty(), "".parse().unwrap());
+        assert!("NO_SUCH_MODE".parse::<SqlMode>().is_err());
+        let json = serde_json::to_string(&sql_mode).unwrap();
+        assert_eq!(sql_mode, serde_json::from_str::<SqlMode>(&json).unwrap());
+
+        assert_eq!(
+            IsolationLevel::ReadCommitted,
+            "READ-COMMITTED".parse().unwrap()
+        );
+        assert_eq!(
+            IsolationLevel::RepeatableRead,
+            IsolationLevel::RepeatableRead.to_string().parse().unwrap()
+        );
+        assert!("SNAPSHOT".parse::<IsolationLevel>().is_err());
+        assert_eq!(AccessMode::ReadOnly, "ON".parse().unwrap());
+        assert_eq!(AccessMode::ReadWrite, "read write".parse().unwrap());
+    }
+}
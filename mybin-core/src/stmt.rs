@@ -5,10 +5,11 @@ use crate::time::{MyDateTime, MyTime};
 use crate::{to_opt_stmt_column_value, to_stmt_column_value};
 use bigdecimal::BigDecimal;
 use bytes::{Buf, Bytes};
+use chrono::FixedOffset;
 use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use std::borrow::Cow;
 
-const SQL_NULL: &'static str = "null";
+pub(crate) const SQL_NULL: &str = "null";
 
 /// define types that can be converted to StmtColumnValue
 pub trait ToColumnValue {
@@ -536,6 +537,23 @@ impl StmtColumnValue {
     }
 }
 
+impl StmtColumnValue {
+    /// convert binlog value for session in given time zone
+    ///
+    /// TIMESTAMP is converted to wall clock of the time zone, while
+    /// conversion by From treats the session as UTC.
+    pub fn from_binlog_in_time_zone(
+        val: BinlogColumnValue,
+        unsigned: bool,
+        tz: &FixedOffset,
+    ) -> Self {
+        match val.utc_timestamp() {
+            Some(ts) => Self::new_timestamp(ts.to_wall_clock(tz).0),
+            None => Self::from((val, unsigned)),
+        }
+    }
+}
+
 impl<'c> From<(BinlogColumnValue, bool)> for StmtColumnValue {
     fn from((val, unsigned): (BinlogColumnValue, bool)) -> Self {
         match val {
@@ -607,6 +625,7 @@ impl<'c> From<(BinlogColumnValue, bool)> for StmtColumnValue {
             BinlogColumnValue::Bit(bs) => Self::new_bit(Vec::from(bs.chunk())),
             BinlogColumnValue::NewDecimal(d) => Self::new_mydecimal(d),
             BinlogColumnValue::Enum(e) => Self::new_unsigned_bigint(e.to_u64()),
+            BinlogColumnValue::Set(n) => Self::new_unsigned_bigint(n),
             BinlogColumnValue::Blob(bs) => Self::new_blob(bs),
             BinlogColumnValue::VarString(bs) => Self::new_varstring(bs),
             BinlogColumnValue::String(bs) => Self::new_varstring(bs),
@@ -9,7 +9,7 @@ use crate::try_non_null_column_value;
 use bytes::{Buf, Bytes};
 use bytes_parser::error::{Error as BError, Result as BResult};
 use bytes_parser::ReadBytesExt;
-use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Utc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MyTime {
@@ -148,6 +148,25 @@ impl MyDateTime {
             micro_second,
         })
     }
+
+    /// read datetime of layout before 5.6.4, little endian u64
+    /// of decimal YYYYMMDDhhmmss without fraction
+    ///
+    /// https://github.com/mysql/mysql-server/blob/5.5/sql/field.cc#L6208
+    pub fn from_legacy_binlog(input: &mut Bytes) -> BResult<Self> {
+        let n = input.read_le_u64()?;
+        let ymd = n / 1_000_000;
+        let hms = n % 1_000_000;
+        Ok(Self {
+            year: (ymd / 10000) as u16,
+            month: ((ymd / 100) % 100) as u8,
+            day: (ymd % 100) as u8,
+            hour: (hms / 10000) as u8,
+            minute: ((hms / 100) % 100) as u8,
+            second: (hms % 100) as u8,
+            micro_second: 0,
+        })
+    }
 }
 
 impl From<NaiveDateTime> for MyDateTime {
@@ -207,6 +226,87 @@ impl FromColumnValue<TextColumnValue> for Option<MyDateTime> {
 
 try_non_null_column_value!(TextColumnValue => MyDateTime);
 
+/// value of TIMESTAMP column, an instant independent of time zone
+///
+/// binlog stores TIMESTAMP as seconds since epoch in UTC, and server
+/// shows it in session time zone. it must be converted to wall clock
+/// of the target session before used as literal, otherwise the time
+/// is shifted silently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcTimestamp {
+    pub secs: i64,
+    pub micro_second: u32,
+}
+
+impl UtcTimestamp {
+    pub fn new(secs: i64, micro_second: u32) -> Self {
+        UtcTimestamp { secs, micro_second }
+    }
+
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.secs, self.micro_second * 1000).unwrap_or_default()
+    }
+
+    /// wall clock in given time zone
+    pub fn to_wall_clock(self, tz: &FixedOffset) -> WallClockDateTime {
+        WallClockDateTime(self.to_datetime().with_timezone(tz).naive_local())
+    }
+}
+
+impl From<DateTime<Utc>> for UtcTimestamp {
+    fn from(src: DateTime<Utc>) -> Self {
+        UtcTimestamp::new(src.timestamp(), src.timestamp_subsec_micros())
+    }
+}
+
+/// value of DATETIME column, a wall clock without time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WallClockDateTime(pub NaiveDateTime);
+
+impl WallClockDateTime {
+    /// instant of this wall clock in given time zone
+    pub fn to_utc(self, tz: &FixedOffset) -> UtcTimestamp {
+        UtcTimestamp::from((self.0 - *tz).and_utc())
+    }
+}
+
+impl From<MyDateTime> for WallClockDateTime {
+    fn from(src: MyDateTime) -> Self {
+        WallClockDateTime(src.into())
+    }
+}
+
+impl From<WallClockDateTime> for MyDateTime {
+    fn from(src: WallClockDateTime) -> Self {
+        src.0.into()
+    }
+}
+
+/// parse time zone in format of MySQL variable time_zone,
+/// e.g. "+08:00", "-05:30", "UTC"
+///
+/// named time zones other than UTC are not supported,
+/// because no time zone database is available
+pub fn parse_time_zone(s: &str) -> Result<FixedOffset> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("UTC") || s.eq_ignore_ascii_case("Z") {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    let invalid = || Error::InvalidTimeZone(s.to_owned());
+    let (sign, hm) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => return Err(invalid()),
+    };
+    let (h, m) = hm.split_once(':').ok_or_else(invalid)?;
+    let h: i32 = h.parse().map_err(|_| invalid())?;
+    let m: i32 = m.parse().map_err(|_| invalid())?;
+    if m >= 60 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60)).ok_or_else(invalid)
+}
+
 /// convert binary representation of time to packed u64
 ///
 /// will consume 3 ~ 6 bytes from input according to certain fraction
@@ -356,4 +456,28 @@ mod tests {
         let tm = MyTime::from_col(input).unwrap();
         println!("{:?}", tm);
     }
+
+    #[test]
+    fn test_timestamp_time_zone() {
+        let tz = parse_time_zone("+08:00").unwrap();
+        assert_eq!(8 * 3600, tz.local_minus_utc());
+        assert_eq!(
+            -(5 * 3600 + 1800),
+            parse_time_zone("-05:30").unwrap().local_minus_utc()
+        );
+        assert_eq!(0, parse_time_zone("UTC").unwrap().local_minus_utc());
+        assert!(parse_time_zone("Asia/Shanghai").is_err());
+        assert!(parse_time_zone("+08:60").is_err());
+        // 2021-01-01 00:00:00 UTC
+        let ts = UtcTimestamp::new(1609459200, 500);
+        let wc = ts.to_wall_clock(&tz);
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2021, 1, 1)
+                .unwrap()
+                .and_hms_micro_opt(8, 0, 0, 500)
+                .unwrap(),
+            wc.0
+        );
+        assert_eq!(ts, wc.to_utc(&tz));
+    }
 }
@@ -1,6 +1,20 @@
+#[cfg(any(test, feature = "soft-crc32"))]
 use crc_any::CRCu32;
 
+/// crc32 (ISO 3309) of binlog checksum, computed with PCLMULQDQ on
+/// x86_64 or CRC32 instructions on aarch64 if detected at runtime
+#[cfg(not(feature = "soft-crc32"))]
 pub(crate) fn checksum_crc32(bytes: &[u8]) -> u32 {
+    crc32fast::hash(bytes)
+}
+
+#[cfg(feature = "soft-crc32")]
+pub(crate) fn checksum_crc32(bytes: &[u8]) -> u32 {
+    checksum_crc32_soft(bytes)
+}
+
+#[cfg(any(test, feature = "soft-crc32"))]
+fn checksum_crc32_soft(bytes: &[u8]) -> u32 {
     let mut hasher = CRCu32::crc32();
     hasher.digest(bytes);
     hasher.get_crc()
@@ -124,5 +138,13 @@ mod tests {
     fn test_checksum_iso_3309() {
         assert_eq!(907060870, checksum_crc32(b"hello"));
         assert_eq!(980881731, checksum_crc32(b"world"));
+        // accelerated paths differ by length and alignment
+        let data: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+        for len in [0, 1, 15, 16, 63, 64, 65, 127, 128, 1000, 4092] {
+            for offset in 0..4 {
+                let bytes = &data[offset..offset + len];
+                assert_eq!(checksum_crc32_soft(bytes), checksum_crc32(bytes));
+            }
+        }
     }
 }
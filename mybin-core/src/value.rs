@@ -0,0 +1,755 @@
+//! unified column value of text rows, binary rows and binlog rows
+//!
+//! each protocol has its own value enum, which requires consumers to
+//! handle three representations of the same data. Value is converted
+//! losslessly from all of them, so query results and row events can
+//! be processed by the same code.
+use crate::binlog::TableMap;
+use crate::col::{
+    BinaryColumnValue, BinlogColumnValue, ColumnDefinition, ColumnFlags, ColumnType,
+    TextColumnValue,
+};
+use crate::error::{Error, Result};
+use crate::resultset::FromColumnValue;
+use crate::row::{BinaryRow, LogRow, TextRow, TextRowRef};
+use crate::time::{MyDateTime, MyTime, UtcTimestamp};
+use bigdecimal::BigDecimal;
+use bytes::{Buf, Bytes};
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::ops::Index;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    Decimal(BigDecimal),
+    /// zero date is allowed
+    Date {
+        year: u16,
+        month: u8,
+        day: u8,
+    },
+    Time(MyTime),
+    /// DATETIME, and TIMESTAMP in query result which is shown in
+    /// session time zone
+    DateTime(MyDateTime),
+    /// TIMESTAMP in binlog, an instant in UTC
+    Timestamp(UtcTimestamp),
+    Year(u16),
+    Bit(Bytes),
+    /// index of ENUM or bitmap of SET in binlog,
+    /// query result returns their labels as bytes
+    Enum(u64),
+    /// strings, blobs and geometries
+    Bytes(Bytes),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// integer that fits in i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            Value::UInt(n) if *n <= i64::MAX as u64 => Some(*n as i64),
+            Value::Year(n) => Some(*n as i64),
+            Value::Enum(n) if *n <= i64::MAX as u64 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    /// non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(n) if *n >= 0 => Some(*n as u64),
+            Value::UInt(n) | Value::Enum(n) => Some(*n),
+            Value::Year(n) => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bs) | Value::Bit(bs) => Some(bs.chunk()),
+            _ => None,
+        }
+    }
+
+    /// literal in SQL statement
+    ///
+    /// strings are quoted with backslash escaped, so it must not be
+    /// used with sql_mode NO_BACKSLASH_ESCAPES. invalid utf8 is
+    /// written as hex literal.
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Value::Null => "NULL".to_owned(),
+            Value::Int(n) => n.to_string(),
+            Value::UInt(n) | Value::Enum(n) => n.to_string(),
+            Value::Float(n) => n.to_string(),
+            Value::Double(n) => n.to_string(),
+            Value::Decimal(d) => d.to_string(),
+            Value::Date { year, month, day } => {
+                format!("'{:04}-{:02}-{:02}'", year, month, day)
+            }
+            Value::Time(tm) => {
+                let hour = tm.days * 24 + tm.hour as u32;
+                format!(
+                    "'{}{:02}:{:02}:{:02}.{:06}'",
+                    if tm.negative { "-" } else { "" },
+                    hour,
+                    tm.minute,
+                    tm.second,
+                    tm.micro_second
+                )
+            }
+            Value::DateTime(dt) => format!(
+                "'{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.micro_second
+            ),
+            Value::Timestamp(ts) => {
+                format!("FROM_UNIXTIME({}.{:06})", ts.secs, ts.micro_second)
+            }
+            Value::Year(n) => format!("{:04}", n),
+            Value::Bit(bs) => format!("x'{}'", hex::encode(bs.chunk())),
+            Value::Bytes(bs) => match std::str::from_utf8(bs.chunk()) {
+                Ok(s) => {
+                    let mut lit = String::with_capacity(s.len() + 2);
+                    lit.push('\'');
+                    for c in s.chars() {
+                        match c {
+                            '\'' => lit.push_str("''"),
+                            '\\' => lit.push_str("\\\\"),
+                            _ => lit.push(c),
+                        }
+                    }
+                    lit.push('\'');
+                    lit
+                }
+                Err(_) => format!("x'{}'", hex::encode(bs.chunk())),
+            },
+        }
+    }
+
+    /// convert text value by column definition
+    pub fn from_text(value: TextColumnValue, col_def: &ColumnDefinition) -> Result<Self> {
+        match value {
+            None => Ok(Value::Null),
+            Some(bs) => TextKind::of(col_def).decode(bs),
+        }
+    }
+
+    /// convert binary value, integers are unsigned in binary protocol
+    /// and reinterpreted if column is signed
+    pub fn from_binary(value: BinaryColumnValue, unsigned: bool) -> Result<Self> {
+        let v = match value {
+            BinaryColumnValue::Null => Value::Null,
+            BinaryColumnValue::Tiny(n) => int(unsigned, n as u64, n as i8 as i64),
+            BinaryColumnValue::Short(n) => int(unsigned, n as u64, n as i16 as i64),
+            BinaryColumnValue::Long(n) | BinaryColumnValue::Int24(n) => {
+                int(unsigned, n as u64, n as i32 as i64)
+            }
+            BinaryColumnValue::LongLong(n) => int(unsigned, n, n as i64),
+            BinaryColumnValue::Float(n) => Value::Float(n),
+            BinaryColumnValue::Double(n) => Value::Double(n),
+            BinaryColumnValue::NewDecimal(bs) => {
+                Value::Decimal(BigDecimal::from_str(std::str::from_utf8(bs.chunk())?)?)
+            }
+            BinaryColumnValue::Date { year, month, day } => Value::Date { year, month, day },
+            BinaryColumnValue::Time(tm) => Value::Time(tm),
+            BinaryColumnValue::Timestamp(dt) | BinaryColumnValue::DateTime(dt) => {
+                Value::DateTime(dt)
+            }
+            BinaryColumnValue::Year(n) => Value::Year(n),
+            BinaryColumnValue::Bit(bs) => Value::Bit(bs),
+            BinaryColumnValue::Blob(bs)
+            | BinaryColumnValue::VarString(bs)
+            | BinaryColumnValue::String(bs)
+            | BinaryColumnValue::Geometry(bs) => Value::Bytes(bs),
+        };
+        Ok(v)
+    }
+
+    /// convert binlog value, signedness is not stored in binlog
+    /// row and must be provided by caller
+    pub fn from_binlog(value: BinlogColumnValue, unsigned: bool) -> Result<Self> {
+        let v = match value {
+            BinlogColumnValue::Null => Value::Null,
+            BinlogColumnValue::Tiny(n) => int(unsigned, n as u64, n as i8 as i64),
+            BinlogColumnValue::Short(n) => int(unsigned, n as u64, n as i16 as i64),
+            BinlogColumnValue::Long(n) => int(unsigned, n as u64, n as i32 as i64),
+            // sign extension of 3-byte integer
+            BinlogColumnValue::Int24(n) => int(unsigned, n as u64, ((n << 8) as i32 >> 8) as i64),
+            BinlogColumnValue::LongLong(n) => int(unsigned, n, n as i64),
+            BinlogColumnValue::Float(n) => Value::Float(n),
+            BinlogColumnValue::Double(n) => Value::Double(n),
+            BinlogColumnValue::NewDecimal(d) => {
+                Value::Decimal(BigDecimal::from_str(&d.to_string())?)
+            }
+            BinlogColumnValue::Date { year, month, day } => Value::Date { year, month, day },
+            BinlogColumnValue::Time(tm) => Value::Time(tm),
+            BinlogColumnValue::DateTime(dt) => Value::DateTime(dt),
+            BinlogColumnValue::Timestamp(secs) => {
+                Value::Timestamp(UtcTimestamp::new(secs as i64, 0))
+            }
+            BinlogColumnValue::Year(n) => Value::Year(n),
+            BinlogColumnValue::Bit(bs) => Value::Bit(bs),
+            BinlogColumnValue::Enum(e) => Value::Enum(e.to_u64()),
+            BinlogColumnValue::Set(n) => Value::Enum(n),
+            BinlogColumnValue::Blob(bs)
+            | BinlogColumnValue::VarString(bs)
+            | BinlogColumnValue::String(bs)
+            | BinlogColumnValue::Geometry(bs) => Value::Bytes(bs),
+        };
+        Ok(v)
+    }
+}
+
+/// conversion of text cell, resolved from column definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextKind {
+    Int { unsigned: bool },
+    Year,
+    Float,
+    Double,
+    Decimal,
+    Date,
+    Time,
+    DateTime,
+    Bit,
+    Null,
+    Bytes,
+}
+
+impl TextKind {
+    fn of(col_def: &ColumnDefinition) -> Self {
+        match col_def.col_type {
+            ColumnType::Tiny
+            | ColumnType::Short
+            | ColumnType::Long
+            | ColumnType::LongLong
+            | ColumnType::Int24 => TextKind::Int {
+                unsigned: col_def.flags.contains(ColumnFlags::UNSIGNED),
+            },
+            ColumnType::Year => TextKind::Year,
+            ColumnType::Float => TextKind::Float,
+            ColumnType::Double => TextKind::Double,
+            ColumnType::Decimal | ColumnType::NewDecimal => TextKind::Decimal,
+            ColumnType::Date => TextKind::Date,
+            ColumnType::Time | ColumnType::Time2 => TextKind::Time,
+            ColumnType::DateTime
+            | ColumnType::DateTime2
+            | ColumnType::Timestamp
+            | ColumnType::Timestamp2 => TextKind::DateTime,
+            ColumnType::Bit => TextKind::Bit,
+            ColumnType::Null => TextKind::Null,
+            _ => TextKind::Bytes,
+        }
+    }
+
+    fn decode(self, bs: Bytes) -> Result<Value> {
+        let v = match self {
+            TextKind::Int { unsigned } => {
+                let s = std::str::from_utf8(bs.chunk())?;
+                if unsigned {
+                    Value::UInt(s.parse()?)
+                } else {
+                    Value::Int(s.parse()?)
+                }
+            }
+            TextKind::Year => Value::Year(std::str::from_utf8(bs.chunk())?.parse()?),
+            TextKind::Float => Value::Float(std::str::from_utf8(bs.chunk())?.parse()?),
+            TextKind::Double => Value::Double(std::str::from_utf8(bs.chunk())?.parse()?),
+            TextKind::Decimal => {
+                Value::Decimal(BigDecimal::from_str(std::str::from_utf8(bs.chunk())?)?)
+            }
+            TextKind::Date => {
+                let (year, month, day) = parse_date(std::str::from_utf8(bs.chunk())?)?;
+                Value::Date { year, month, day }
+            }
+            TextKind::Time => Value::Time(MyTime::from_col(Some(bs))?),
+            TextKind::DateTime => {
+                Value::DateTime(parse_datetime(std::str::from_utf8(bs.chunk())?)?)
+            }
+            TextKind::Bit => Value::Bit(bs),
+            TextKind::Null => Value::Null,
+            TextKind::Bytes => Value::Bytes(bs),
+        };
+        Ok(v)
+    }
+}
+
+/// decoder of text rows into unified values
+///
+/// conversions are resolved once from column definitions of result
+/// set, values equal those of binary protocol for the same columns.
+/// NULL is Value::Null while empty string is empty Value::Bytes.
+#[derive(Debug, Clone)]
+pub struct TextValueDecoder {
+    kinds: Vec<TextKind>,
+}
+
+impl TextValueDecoder {
+    pub fn new(col_defs: &[ColumnDefinition]) -> Self {
+        TextValueDecoder {
+            kinds: col_defs.iter().map(TextKind::of).collect(),
+        }
+    }
+
+    /// decode single cell of column
+    pub fn decode(&self, idx: usize, value: TextColumnValue) -> Result<Value> {
+        let kind = self.kinds.get(idx).ok_or_else(|| {
+            Error::ColumnIndexOutOfBound(format!("column index {} / {}", idx, self.kinds.len()))
+        })?;
+        match value {
+            None => Ok(Value::Null),
+            Some(bs) => kind.decode(bs),
+        }
+    }
+
+    pub fn decode_row(&self, row: TextRow) -> Result<Row> {
+        self.check_len(row.0.len())?;
+        row.0
+            .into_iter()
+            .zip(&self.kinds)
+            .map(|(v, kind)| match v {
+                None => Ok(Value::Null),
+                Some(bs) => kind.decode(bs),
+            })
+            .collect::<Result<_>>()
+            .map(Row)
+    }
+
+    /// decode borrowed row, only cells kept as bytes are copied
+    pub fn decode_row_ref(&self, row: &TextRowRef<'_>) -> Result<Row> {
+        self.check_len(row.len())?;
+        row.iter()
+            .zip(&self.kinds)
+            .map(|(v, kind)| match v {
+                None => Ok(Value::Null),
+                Some(bs) => kind.decode(Bytes::copy_from_slice(bs)),
+            })
+            .collect::<Result<_>>()
+            .map(Row)
+    }
+
+    fn check_len(&self, len: usize) -> Result<()> {
+        if len != self.kinds.len() {
+            return Err(Error::ColumnIndexOutOfBound(format!(
+                "row has {} columns, expected {}",
+                len,
+                self.kinds.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn int(unsigned: bool, u: u64, i: i64) -> Value {
+    if unsigned {
+        Value::UInt(u)
+    } else {
+        Value::Int(i)
+    }
+}
+
+fn parse_date(s: &str) -> Result<(u16, u8, u8)> {
+    let mut splits = s.trim().splitn(3, '-');
+    match (splits.next(), splits.next(), splits.next()) {
+        (Some(y), Some(m), Some(d)) => Ok((y.parse()?, m.parse()?, d.parse()?)),
+        _ => Err(Error::ParseMyTimeError(format!("invalid date {}", s))),
+    }
+}
+
+fn parse_datetime(s: &str) -> Result<MyDateTime> {
+    let (date, time) = s
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| Error::ParseMyTimeError(format!("invalid datetime {}", s)))?;
+    let (year, month, day) = parse_date(date)?;
+    let tm = MyTime::from_col(Some(Bytes::copy_from_slice(time.as_bytes())))?;
+    if tm.negative || tm.days > 0 {
+        return Err(Error::ParseMyTimeError(format!("invalid datetime {}", s)));
+    }
+    Ok(MyDateTime {
+        year,
+        month,
+        day,
+        hour: tm.hour,
+        minute: tm.minute,
+        second: tm.second,
+        micro_second: tm.micro_second,
+    })
+}
+
+/// row of unified values
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row(pub Vec<Value>);
+
+impl Row {
+    pub fn from_text(row: TextRow, col_defs: &[ColumnDefinition]) -> Result<Self> {
+        TextValueDecoder::new(col_defs).decode_row(row)
+    }
+
+    pub fn from_binary(row: BinaryRow, col_defs: &[ColumnDefinition]) -> Result<Self> {
+        row.0
+            .into_iter()
+            .zip(col_defs)
+            .map(|(v, def)| Value::from_binary(v, def.flags.contains(ColumnFlags::UNSIGNED)))
+            .collect::<Result<_>>()
+            .map(Row)
+    }
+
+    /// signedness is taken from optional metadata of table map,
+    /// numeric columns are signed if it is absent
+    pub fn from_binlog(row: LogRow, table_map: &TableMap) -> Result<Self> {
+        row.0
+            .into_iter()
+            .enumerate()
+            .map(|(idx, v)| Value::from_binlog(v, table_map.is_unsigned(idx).unwrap_or(false)))
+            .collect::<Result<_>>()
+            .map(Row)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Value> {
+        self.0.get(idx)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter()
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.0
+    }
+}
+
+/// positions of columns by name, shared by rows of a result set or
+/// a table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnNames {
+    names: Vec<SmolStr>,
+    by_name: HashMap<SmolStr, usize>,
+    by_lc_name: HashMap<SmolStr, usize>,
+}
+
+impl ColumnNames {
+    /// first column wins if names are duplicated, e.g. in joins
+    pub fn new<I, N>(names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<SmolStr>,
+    {
+        let names: Vec<SmolStr> = names.into_iter().map(Into::into).collect();
+        let mut by_name = HashMap::with_capacity(names.len());
+        let mut by_lc_name = HashMap::with_capacity(names.len());
+        for (idx, name) in names.iter().enumerate() {
+            by_name.entry(name.clone()).or_insert(idx);
+            by_lc_name
+                .entry(SmolStr::new(name.to_lowercase()))
+                .or_insert(idx);
+        }
+        ColumnNames {
+            names,
+            by_name,
+            by_lc_name,
+        }
+    }
+
+    pub fn from_col_defs(col_defs: &[ColumnDefinition]) -> Self {
+        Self::new(col_defs.iter().map(|def| def.name.clone()))
+    }
+
+    /// None if column names are not recorded in table map,
+    /// which requires binlog_row_metadata=FULL
+    pub fn from_table_map(table_map: &TableMap) -> Option<Self> {
+        let names = &table_map.metadata.column_names;
+        if names.is_empty() {
+            return None;
+        }
+        Some(Self::new(names.iter().cloned()))
+    }
+
+    /// exact match is preferred, then case insensitive match as
+    /// column names are in MySQL
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.by_name
+            .get(name)
+            .or_else(|| self.by_lc_name.get(name.to_lowercase().as_str()))
+            .copied()
+    }
+
+    pub fn names(&self) -> &[SmolStr] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// row accessible by column name
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedRow {
+    names: Arc<ColumnNames>,
+    row: Row,
+}
+
+impl NamedRow {
+    pub fn new(names: Arc<ColumnNames>, row: Row) -> Self {
+        NamedRow { names, row }
+    }
+
+    pub fn value(&self, name: &str) -> Result<&Value> {
+        self.names
+            .index_of(name)
+            .and_then(|idx| self.row.get(idx))
+            .ok_or_else(|| Error::ColumnNameNotFound(name.to_owned()))
+    }
+
+    /// typed value of column, NULL is only accepted by Option
+    pub fn get<V>(&self, name: &str) -> Result<V>
+    where
+        V: FromColumnValue<Value>,
+    {
+        V::from_col(self.value(name)?.clone())
+    }
+
+    pub fn names(&self) -> &Arc<ColumnNames> {
+        &self.names
+    }
+
+    pub fn row(&self) -> &Row {
+        &self.row
+    }
+
+    pub fn into_row(self) -> Row {
+        self.row
+    }
+}
+
+/// panics if column not found, use NamedRow::value to handle it
+impl Index<&str> for NamedRow {
+    type Output = Value;
+
+    fn index(&self, name: &str) -> &Value {
+        match self.value(name) {
+            Ok(v) => v,
+            Err(_) => panic!("column {} not found", name),
+        }
+    }
+}
+
+impl FromColumnValue<Value> for Value {
+    fn from_col(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+macro_rules! from_value {
+    ($ty:ty, $expected:literal, $conv:expr) => {
+        impl FromColumnValue<Value> for Option<$ty> {
+            fn from_col(value: Value) -> Result<Self> {
+                if value.is_null() {
+                    return Ok(None);
+                }
+                let conv: fn(&Value) -> Option<$ty> = $conv;
+                match conv(&value) {
+                    Some(v) => Ok(Some(v)),
+                    None => Err(Error::ColumnTypeMismatch(format!(
+                        "expected={}, actual={:?}",
+                        $expected, value
+                    ))),
+                }
+            }
+        }
+
+        impl FromColumnValue<Value> for $ty {
+            fn from_col(value: Value) -> Result<Self> {
+                <Option<$ty>>::from_col(value)?.ok_or(Error::NullValueError)
+            }
+        }
+    };
+}
+
+from_value!(i64, "i64", Value::as_i64);
+from_value!(u64, "u64", Value::as_u64);
+from_value!(f64, "f64", |v| match v {
+    Value::Float(n) => Some(*n as f64),
+    Value::Double(n) => Some(*n),
+    _ => v.as_i64().map(|n| n as f64),
+});
+from_value!(bool, "bool", |v| v.as_i64().map(|n| n != 0));
+from_value!(Bytes, "Bytes", |v| match v {
+    Value::Bytes(bs) | Value::Bit(bs) => Some(bs.clone()),
+    _ => None,
+});
+from_value!(String, "String", |v| match v {
+    Value::Bytes(bs) => String::from_utf8(bs.to_vec()).ok(),
+    _ => None,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::row::TextRowParser;
+
+    #[test]
+    fn test_value_from_all_sources() {
+        let defs = vec![
+            col_def(ColumnType::Long, ColumnFlags::empty()),
+            col_def(ColumnType::LongLong, ColumnFlags::UNSIGNED),
+            col_def(ColumnType::NewDecimal, ColumnFlags::empty()),
+            col_def(ColumnType::Date, ColumnFlags::empty()),
+            col_def(ColumnType::DateTime, ColumnFlags::empty()),
+            col_def(ColumnType::VarString, ColumnFlags::empty()),
+        ];
+        let text = TextRow(vec![
+            Some(Bytes::from("-1")),
+            Some(Bytes::from("18446744073709551615")),
+            Some(Bytes::from("1.50")),
+            Some(Bytes::from("0000-00-00")),
+            Some(Bytes::from("2021-01-02 03:04:05.000006")),
+            None,
+        ]);
+        let binary = BinaryRow(vec![
+            BinaryColumnValue::Long(0xffff_ffff),
+            BinaryColumnValue::LongLong(u64::MAX),
+            BinaryColumnValue::NewDecimal(Bytes::from("1.50")),
+            BinaryColumnValue::Date {
+                year: 0,
+                month: 0,
+                day: 0,
+            },
+            BinaryColumnValue::DateTime(MyDateTime {
+                year: 2021,
+                month: 1,
+                day: 2,
+                hour: 3,
+                minute: 4,
+                second: 5,
+                micro_second: 6,
+            }),
+            BinaryColumnValue::Null,
+        ]);
+        let from_text = Row::from_text(text, &defs).unwrap();
+        let from_binary = Row::from_binary(binary, &defs).unwrap();
+        assert_eq!(from_text, from_binary);
+        assert_eq!(Some(-1), from_text.get(0).unwrap().as_i64());
+        assert_eq!(Some(u64::MAX), from_text.get(1).unwrap().as_u64());
+        assert!(from_text.get(5).unwrap().is_null());
+        // binlog int24
+        assert_eq!(
+            Value::Int(-2),
+            Value::from_binlog(BinlogColumnValue::Int24(0xff_fffe), false).unwrap()
+        );
+        assert_eq!(
+            Value::UInt(0xff_fffe),
+            Value::from_binlog(BinlogColumnValue::Int24(0xff_fffe), true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_text_value_decoder() {
+        let defs = vec![
+            col_def(ColumnType::VarString, ColumnFlags::empty()),
+            col_def(ColumnType::VarString, ColumnFlags::empty()),
+            col_def(ColumnType::Long, ColumnFlags::UNSIGNED),
+            col_def(ColumnType::Time, ColumnFlags::empty()),
+        ];
+        let decoder = TextValueDecoder::new(&defs);
+        let row = TextRow(vec![
+            None,
+            Some(Bytes::new()),
+            Some(Bytes::from("4294967295")),
+            Some(Bytes::from("-838:59:59")),
+        ]);
+        let values = decoder.decode_row(row.clone()).unwrap();
+        assert_eq!(Value::Null, values.0[0]);
+        assert_eq!(Value::Bytes(Bytes::new()), values.0[1]);
+        assert_eq!(Value::UInt(u32::MAX as u64), values.0[2]);
+        assert_eq!(
+            Value::from_binary(
+                BinaryColumnValue::Time(MyTime {
+                    negative: true,
+                    days: 34,
+                    hour: 22,
+                    minute: 59,
+                    second: 59,
+                    micro_second: 0,
+                }),
+                false
+            )
+            .unwrap(),
+            values.0[3]
+        );
+        // borrowed row from packet of the same cells
+        let mut packet = vec![0xfb, 0x00, 10];
+        packet.extend_from_slice(b"4294967295");
+        packet.push(10);
+        packet.extend_from_slice(b"-838:59:59");
+        let mut parser = TextRowParser::new();
+        let row_ref = parser.parse(&packet, 4).unwrap();
+        assert_eq!(values, decoder.decode_row_ref(&row_ref).unwrap());
+        assert!(decoder.decode(2, Some(Bytes::from("-1"))).is_err());
+        assert!(decoder.decode(4, None).is_err());
+        assert!(decoder.decode_row(TextRow(vec![None])).is_err());
+    }
+
+    #[test]
+    fn test_named_row() {
+        let names = Arc::new(ColumnNames::new(vec!["id", "Email", "id"]));
+        assert_eq!(Some(0), names.index_of("id"));
+        assert_eq!(Some(1), names.index_of("email"));
+        assert_eq!(None, names.index_of("name"));
+        let row = NamedRow::new(
+            Arc::clone(&names),
+            Row(vec![
+                Value::UInt(7),
+                Value::Bytes(Bytes::from("a@b.c")),
+                Value::Null,
+            ]),
+        );
+        assert_eq!(Value::UInt(7), row["ID"]);
+        assert_eq!(7i64, row.get::<i64>("id").unwrap());
+        assert_eq!("a@b.c", row.get::<String>("email").unwrap());
+        assert_eq!(
+            Some("a@b.c".to_owned()),
+            row.get::<Option<String>>("Email").unwrap()
+        );
+        assert!(matches!(
+            row.get::<String>("id"),
+            Err(Error::ColumnTypeMismatch(_))
+        ));
+        assert!(matches!(
+            row.value("name"),
+            Err(Error::ColumnNameNotFound(_))
+        ));
+        let row = NamedRow::new(names, Row(vec![Value::Null, Value::Null, Value::Null]));
+        assert_eq!(None, row.get::<Option<u64>>("id").unwrap());
+        assert!(matches!(row.get::<u64>("id"), Err(Error::NullValueError)));
+    }
+
+    fn col_def(col_type: ColumnType, flags: ColumnFlags) -> ColumnDefinition {
+        crate::col::tests::col_def("c1", col_type, flags)
+    }
+}
@@ -0,0 +1,169 @@
+//! server version and features depending on it
+//!
+//! version strings come from initial handshake and FDE, e.g.
+//! `5.7.31-log`, `8.0.22-13` of Percona, or `5.5.5-10.3.27-MariaDB-log`
+//! of MariaDB whose handshake carries a fake 5.5.5 prefix.
+use crate::col::TemporalFormat;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    MySQL,
+    MariaDB,
+    /// only detected if suffix mentions percona, otherwise reported
+    /// as MySQL which shares its protocol and binlog
+    Percona,
+}
+
+/// features gated by server version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// TIMESTAMP2, TIME2 and DATETIME2 with fractional seconds
+    Temporal2,
+    /// TIMESTAMP, TIME and DATETIME of layouts before 5.6.4
+    LegacyTemporal,
+    /// crc32 checksum of binlog events
+    BinlogChecksum,
+    /// GTID events of MySQL or MariaDB
+    Gtid,
+    /// last_committed and sequence_number in GTID events
+    GtidLogicalTimestamp,
+    /// OK packet instead of EOF packet at end of result set
+    DeprecateEof,
+    /// optional metadata of table map, binlog_row_metadata
+    TableMapMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub flavor: Flavor,
+}
+
+impl ServerVersion {
+    pub fn new(major: u16, minor: u16, patch: u16, flavor: Flavor) -> Self {
+        ServerVersion {
+            major,
+            minor,
+            patch,
+            flavor,
+        }
+    }
+
+    /// parse leading major.minor.patch and flavor from suffix,
+    /// None if version numbers are missing
+    pub fn parse(server_version: &str) -> Option<Self> {
+        let lower = server_version.to_ascii_lowercase();
+        let flavor = if lower.contains("mariadb") {
+            Flavor::MariaDB
+        } else if lower.contains("percona") {
+            Flavor::Percona
+        } else {
+            Flavor::MySQL
+        };
+        let s = match flavor {
+            Flavor::MariaDB => server_version
+                .strip_prefix("5.5.5-")
+                .unwrap_or(server_version),
+            _ => server_version,
+        };
+        let mut nums = s
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|n| n.parse::<u16>().ok());
+        match (nums.next(), nums.next(), nums.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch))) => {
+                Some(ServerVersion::new(major, minor, patch, flavor))
+            }
+            _ => None,
+        }
+    }
+
+    fn at_least(&self, major: u16, minor: u16, patch: u16) -> bool {
+        (self.major, self.minor, self.patch) >= (major, minor, patch)
+    }
+
+    pub fn is_mariadb(&self) -> bool {
+        self.flavor == Flavor::MariaDB
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        if self.is_mariadb() {
+            return match feature {
+                Feature::Temporal2 => self.at_least(10, 1, 2),
+                // controlled by mysql56_temporal_format
+                Feature::LegacyTemporal => true,
+                Feature::BinlogChecksum => self.at_least(5, 3, 0),
+                Feature::Gtid => self.at_least(10, 0, 2),
+                Feature::GtidLogicalTimestamp | Feature::TableMapMetadata => false,
+                Feature::DeprecateEof => self.at_least(10, 2, 4),
+            };
+        }
+        match feature {
+            Feature::Temporal2 => self.at_least(5, 6, 4),
+            Feature::LegacyTemporal => !self.at_least(8, 0, 0),
+            Feature::BinlogChecksum => self.at_least(5, 6, 1),
+            Feature::Gtid => self.at_least(5, 6, 5),
+            Feature::GtidLogicalTimestamp => self.at_least(5, 7, 4),
+            Feature::DeprecateEof => self.at_least(5, 7, 5),
+            Feature::TableMapMetadata => self.at_least(8, 0, 1),
+        }
+    }
+
+    /// layouts of temporal columns in binlog written by server
+    pub fn temporal_format(&self) -> TemporalFormat {
+        match (
+            self.supports(Feature::Temporal2),
+            self.supports(Feature::LegacyTemporal),
+        ) {
+            (false, _) => TemporalFormat::Legacy,
+            (true, true) => TemporalFormat::Mixed,
+            (true, false) => TemporalFormat::Current,
+        }
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        match self.flavor {
+            Flavor::MySQL => Ok(()),
+            Flavor::MariaDB => write!(f, "-MariaDB"),
+            Flavor::Percona => write!(f, "-Percona"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_version() {
+        let v = ServerVersion::parse("5.7.31-log").unwrap();
+        assert_eq!(ServerVersion::new(5, 7, 31, Flavor::MySQL), v);
+        assert!(v.supports(Feature::GtidLogicalTimestamp));
+        assert!(!v.supports(Feature::TableMapMetadata));
+        assert_eq!(TemporalFormat::Mixed, v.temporal_format());
+
+        let v = ServerVersion::parse("5.5.5-10.3.27-MariaDB-log").unwrap();
+        assert_eq!(ServerVersion::new(10, 3, 27, Flavor::MariaDB), v);
+        assert_eq!("10.3.27-MariaDB", v.to_string());
+        assert!(v.supports(Feature::Gtid));
+        assert!(!v.supports(Feature::GtidLogicalTimestamp));
+
+        let v = ServerVersion::parse("8.0.22-13-Percona Server").unwrap();
+        assert_eq!(Flavor::Percona, v.flavor);
+        assert_eq!(TemporalFormat::Current, v.temporal_format());
+
+        let v = ServerVersion::parse("5.1.73").unwrap();
+        assert!(!v.supports(Feature::BinlogChecksum));
+        assert!(!v.supports(Feature::DeprecateEof));
+        assert_eq!(TemporalFormat::Legacy, v.temporal_format());
+
+        assert_eq!(None, ServerVersion::parse(""));
+        assert_eq!(None, ServerVersion::parse("5.7"));
+    }
+}
@@ -73,6 +73,7 @@ async fn connect(opts: &Opts) -> Result<Conn<TcpStream>> {
         username: opts.username.to_owned(),
         password: opts.password.to_owned(),
         database: String::new(),
+        ..Default::default()
     };
     conn.handshake(conn_opts.clone()).await?;
     Ok(conn)
@@ -209,7 +210,7 @@ async fn print_dmls(
                     break;
                 }
             }
-            evt @ Event::HeartbeatLogEvent(_) => {
+            evt if evt.is_heartbeat() => {
                 eprintln!("{:#?}", evt);
             }
             _ => (),
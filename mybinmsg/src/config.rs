@@ -1,86 +1,68 @@
-use serde_derive::{Deserialize, Serialize};
+use anyhow::{bail, Result};
+pub use mybin_async::config::Config;
+use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
-    #[serde(flatten)]
-    pub connect: Connect,
-    pub subscribe: Subscribe,
-    pub notify: Notify,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub enum Connect {
-    #[serde(rename = "tcp")]
-    Tcp(Tcp),
-    #[serde(rename = "unix_socket")]
-    UnixSocket(UnixSocket),
-}
-
-impl Connect {
-    pub fn tcp(self) -> Option<Tcp> {
-        match self {
-            Connect::Tcp(t) => Some(t),
-            _ => None,
-        }
+/// load config file, format is decided by extension, TOML by default
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => from_yaml(&s),
+        Some("toml") | None => from_toml(&s),
+        Some(ext) => bail!("unsupported config format {}", ext),
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Tcp {
-    pub host: String,
-    pub port: u32,
-    pub username: String,
-    pub password: String,
+pub fn from_toml(s: &str) -> Result<Config> {
+    Ok(toml::from_str(s)?)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UnixSocket {
-    pub path: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Subscribe {
-    database_filter: Option<String>,
-    table_filter: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Notify {
-    #[serde(rename = "type")]
-    pub ty: NotifyType,
-    #[serde(flatten)]
-    pub execution: NotifyExecution,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub enum NotifyType {
-    #[serde(rename = "http")]
-    Http,
-    #[serde(rename = "stdout")]
-    Stdout,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct NotifyExecution {
-    // used for http notify
-    pub url: Option<String>,
+pub fn from_yaml(s: &str) -> Result<Config> {
+    Ok(serde_yaml::from_str(s)?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mybin_async::config::SinkConfig;
 
     #[test]
-    fn test_config_unix_socket() {
+    fn test_config_formats() {
         let toml_str = r#"
-        [unix_socket]
-        path = "/path/to/tmp.sock"
-        [subscribe]
+        [connection]
+        socket = "/path/to/tmp.sock"
+        username = "root"
+        password = ""
+        database = ""
+        [filters]
         database_filter = ".*"
-        [notify]
-        type = "stdout"
+        [sink]
+        type = "http"
+        url = "http://localhost:8080"
         "#;
-        let config: Config = toml::from_str(toml_str).unwrap();
-        println!("{:?}", config);
+        let config = from_toml(toml_str).unwrap();
+        assert!(config.connection.socket.is_some());
+        assert_eq!(Some(".*"), config.filters.database_filter.as_deref());
+        assert_eq!(
+            SinkConfig::Http {
+                url: "http://localhost:8080".to_owned()
+            },
+            config.sink
+        );
+
+        let yaml_str = r#"
+connection:
+  host: db1
+  port: 3307
+  username: repl
+  password: secret
+  database: ""
+snapshot:
+  tables: [db1.t1, db1.t2]
+  lock_tables: false
+"#;
+        let config = from_yaml(yaml_str).unwrap();
+        assert_eq!("db1:3307", config.connection.addr());
+        assert_eq!(2, config.snapshot.unwrap().tables.len());
     }
 }
@@ -1,34 +1,32 @@
 pub mod cmd_opt;
 pub mod config;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_io::Async;
 use cmd_opt::CommandOpt;
-use config::{Config, Tcp};
-use mybin_async::conn::{Conn, ConnOpts};
-use mybin_core::binlog::Event;
-use std::fs::File;
-use std::io::Read;
+use mybin_async::config::ConnectionConfig;
+use mybin_async::conn::Conn;
 use std::net::{TcpStream, ToSocketAddrs};
 use structopt::StructOpt;
 
 fn main() -> Result<()> {
     env_logger::init();
     let opt = CommandOpt::from_args();
-    let mut conf_file = File::open(&opt.config).context("failed to read config file")?;
-    let mut toml_str = String::new();
-    conf_file.read_to_string(&mut toml_str)?;
-    let conf: Config = toml::from_str(&toml_str)?;
+    let conf = config::load(&opt.config).context("failed to read config file")?;
 
     smol::block_on(async {
-        let mut conn = connect_tcp(&conf.connect.tcp().unwrap()).await?;
+        let mut conn = connect_tcp(&conf.connection).await?;
         let binlog_files = conn.binlog_files().await?;
         log::debug!("list of binlog files: {}", binlog_files.len());
         for bf in binlog_files {
             log::debug!("{:?}", bf);
         }
         log::debug!("requesting binlog stream");
-        let mut binlog_stream = conn.binlog().request_stream().await?;
+        let mut binlog = conn.binlog();
+        if let Some(server_id) = conf.connection.server_id {
+            binlog = binlog.server_id(server_id);
+        }
+        let mut binlog_stream = binlog.request_stream().await?;
         log::debug!("binlog stream requested");
         while let Some(evt) = binlog_stream.next_event().await? {
             log::debug!("{:?}", evt);
@@ -37,16 +35,16 @@ fn main() -> Result<()> {
     })
 }
 
-async fn connect_tcp(conf: &Tcp) -> Result<Conn<Async<TcpStream>>> {
-    let addr = format!("{}:{}", conf.host, conf.port)
+async fn connect_tcp(conf: &ConnectionConfig) -> Result<Conn<Async<TcpStream>>> {
+    if conf.socket.is_some() {
+        bail!("unix socket is not supported");
+    }
+    let addr = conf
+        .addr()
         .to_socket_addrs()?
         .next()
-        .unwrap();
-    let login = ConnOpts {
-        username: conf.username.to_owned(),
-        password: conf.password.to_owned(),
-        database: String::new(),
-    };
+        .context("failed to resolve host")?;
+    let login = conf.opts.clone();
     let stream = Async::<TcpStream>::connect(addr).await?;
     let mut conn = Conn::new(stream);
     conn.handshake(login).await?;